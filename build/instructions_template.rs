@@ -170,6 +170,54 @@ enum REPLCodePtr {
     LoadContextModule,
     #[strum_discriminants(strum(props(Arity = "1", Name = "$prolog_lc_stream")))]
     LoadContextStream,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$set_load_context_line")))]
+    SetLoadContextLine,
+    #[strum_discriminants(strum(props(Arity = "3", Name = "$invoke_traced")))]
+    InvokeTraced,
+    #[strum_discriminants(strum(props(Arity = "4", Name = "$trace_port_hook")))]
+    TracePortHook,
+    #[strum_discriminants(strum(props(Arity = "0", Name = "$start_profiler")))]
+    StartProfiler,
+    #[strum_discriminants(strum(props(Arity = "0", Name = "$stop_profiler")))]
+    StopProfiler,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$profile_data")))]
+    ProfileData,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$prolog_flags")))]
+    PrologFlags,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$prolog_flag")))]
+    PrologFlag,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$set_prolog_flag")))]
+    SetPrologFlag,
+    #[strum_discriminants(strum(props(Arity = "5", Name = "$create_prolog_flag")))]
+    CreatePrologFlag,
+    #[strum_discriminants(strum(props(Arity = "4", Name = "$tbl_variant_table")))]
+    TableVariantTable,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$tbl_enter")))]
+    TableEnter,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$tbl_begin_pass")))]
+    TableBeginPass,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$tbl_end_pass")))]
+    TableEndPass,
+    #[strum_discriminants(strum(props(Arity = "3", Name = "$tbl_add_answer")))]
+    TableAddAnswer,
+    #[strum_discriminants(strum(props(Arity = "3", Name = "$tbl_moded_answer")))]
+    TableModedAnswer,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$tbl_answer_count")))]
+    TableAnswerCount,
+    #[strum_discriminants(strum(props(Arity = "4", Name = "$tbl_answer")))]
+    TableAnswer,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$tbl_negation")))]
+    TableNegation,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$tbl_abandon")))]
+    TableAbandon,
+    #[strum_discriminants(strum(props(Arity = "0", Name = "$tbl_abolish_all")))]
+    TableAbolishAll,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$tbl_incremental_dynamic")))]
+    TableIncrementalDynamic,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$tbl_variant")))]
+    TableVariant,
+    #[strum_discriminants(strum(props(Arity = "3", Name = "$tbl_answer_conditions")))]
+    TableAnswerConditions,
     #[strum_discriminants(strum(props(Arity = "0", Name = "$pop_load_context")))]
     PopLoadContext,
     #[strum_discriminants(strum(props(Arity = "1", Name = "$pop_load_state_payload")))]
@@ -180,6 +228,20 @@ enum REPLCodePtr {
     PushLoadContext,
     #[strum_discriminants(strum(props(Arity = "3", Name = "$use_module")))]
     UseModule,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$qlf_file")))]
+    QlfFile,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$open_qlf")))]
+    OpenQlf,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$next_qlf_term")))]
+    NextQlfTerm,
+    #[strum_discriminants(strum(props(Arity = "0", Name = "$start_qlf_recording")))]
+    StartQlfRecording,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$record_qlf_term")))]
+    RecordQlfTerm,
+    #[strum_discriminants(strum(props(Arity = "0", Name = "$save_qlf")))]
+    SaveQlf,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$save_state")))]
+    SaveState,
     #[strum_discriminants(strum(props(Arity = "2", Name = "$built_in_property")))]
     BuiltInProperty,
     #[strum_discriminants(strum(props(Arity = "4", Name = "$meta_predicate_property")))]
@@ -347,10 +409,12 @@ enum SystemClauseType {
     NumberToCodes,
     #[strum_discriminants(strum(props(Arity = "3", Name = "$op")))]
     OpDeclaration,
-    #[strum_discriminants(strum(props(Arity = "7", Name = "$open")))]
+    #[strum_discriminants(strum(props(Arity = "9", Name = "$open")))]
     Open,
     #[strum_discriminants(strum(props(Arity = "5", Name = "$set_stream_options")))]
     SetStreamOptions,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$set_stream_encoding")))]
+    SetStreamEncoding,
     #[strum_discriminants(strum(props(Arity = "2", Name = "$next_stream")))]
     NextStream,
     #[strum_discriminants(strum(props(Arity = "2", Name = "$partial_string_tail")))]
@@ -373,7 +437,7 @@ enum SystemClauseType {
     PutCode,
     #[strum_discriminants(strum(props(Arity = "5", Name = "$read_query_term")))]
     ReadQueryTerm,
-    #[strum_discriminants(strum(props(Arity = "5", Name = "$read_term")))]
+    #[strum_discriminants(strum(props(Arity = "6", Name = "$read_term")))]
     ReadTerm,
     #[strum_discriminants(strum(props(Arity = "2", Name = "$redo_attr_var_binding")))]
     RedoAttrVarBinding,
@@ -407,16 +471,14 @@ enum SystemClauseType {
     Fail,
     #[strum_discriminants(strum(props(Arity = "1", Name = "$get_ball")))]
     GetBall,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$ball_backtrace")))]
+    BallBacktrace,
     #[strum_discriminants(strum(props(Arity = "1", Name = "$get_current_block")))]
     GetCurrentBlock,
     #[strum_discriminants(strum(props(Arity = "1", Name = "$get_current_scc_block")))]
     GetCurrentSCCBlock,
     #[strum_discriminants(strum(props(Arity = "1", Name = "$get_cp")))]
     GetCutPoint,
-    #[strum_discriminants(strum(props(Arity = "1", Name = "$get_double_quotes")))]
-    GetDoubleQuotes,
-    #[strum_discriminants(strum(props(Arity = "1", Name = "$get_unknown")))]
-    GetUnknown,
     #[strum_discriminants(strum(props(Arity = "1", Name = "$install_new_block")))]
     InstallNewBlock,
     #[strum_discriminants(strum(props(Arity = "0", Name = "$maybe")))]
@@ -445,10 +507,6 @@ enum SystemClauseType {
     PopFromBallStack,
     #[strum_discriminants(strum(props(Arity = "1", Name = "$set_cp_by_default")))]
     SetCutPointByDefault(RegType),
-    #[strum_discriminants(strum(props(Arity = "1", Name = "$set_double_quotes")))]
-    SetDoubleQuotes,
-    #[strum_discriminants(strum(props(Arity = "1", Name = "$set_unknown")))]
-    SetUnknown,
     #[strum_discriminants(strum(props(Arity = "1", Name = "$set_seed")))]
     SetSeed,
     #[strum_discriminants(strum(props(Arity = "4", Name = "$skip_max_list")))]
@@ -533,28 +591,60 @@ enum SystemClauseType {
     UnsetEnv,
     #[strum_discriminants(strum(props(Arity = "2", Name = "$shell")))]
     Shell,
+    #[strum_discriminants(strum(props(Arity = "8", Name = "$process_create")))]
+    ProcessCreate,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$process_wait")))]
+    ProcessWait,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$process_kill")))]
+    ProcessKill,
     #[strum_discriminants(strum(props(Arity = "1", Name = "$pid")))]
     PID,
     #[strum_discriminants(strum(props(Arity = "4", Name = "$chars_base64")))]
     CharsBase64,
     #[strum_discriminants(strum(props(Arity = "1", Name = "$devour_whitespace")))]
     DevourWhitespace,
-    #[strum_discriminants(strum(props(Arity = "1", Name = "$is_sto_enabled")))]
-    IsSTOEnabled,
-    #[strum_discriminants(strum(props(Arity = "0", Name = "$set_sto_as_unify")))]
-    SetSTOAsUnify,
-    #[strum_discriminants(strum(props(Arity = "0", Name = "$set_nsto_as_unify")))]
-    SetNSTOAsUnify,
-    #[strum_discriminants(strum(props(Arity = "0", Name = "$set_sto_with_error_as_unify")))]
-    SetSTOWithErrorAsUnify,
     #[strum_discriminants(strum(props(Arity = "1", Name = "$home_directory")))]
     HomeDirectory,
     #[strum_discriminants(strum(props(Arity = "0", Name = "$debug_hook")))]
     DebugHook,
-    #[strum_discriminants(strum(props(Arity = "2", Name = "$popcount")))]
-    PopCount,
     #[strum_discriminants(strum(props(Arity = "1", Name = "$cpu_now")))]
     CpuNow,
+    #[strum_discriminants(strum(props(Arity = "0", Name = "$garbage_collect")))]
+    GarbageCollect,
+    #[strum_discriminants(strum(props(Arity = "4", Name = "$gc_statistics")))]
+    GCStatistics,
+    #[strum_discriminants(strum(props(Arity = "0", Name = "$garbage_collect_atoms")))]
+    GarbageCollectAtoms,
+    #[strum_discriminants(strum(props(Arity = "4", Name = "$atom_gc_statistics")))]
+    AtomGCStatistics,
+    #[strum_discriminants(strum(props(Arity = "4", Name = "$thread_create")))]
+    ThreadCreate,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$thread_join")))]
+    ThreadJoin,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$thread_self")))]
+    ThreadSelf,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$thread_finish")))]
+    ThreadFinish,
+    #[strum_discriminants(strum(props(Arity = "2", Name = "$thread_send_message")))]
+    ThreadSendMessage,
+    #[strum_discriminants(strum(props(Arity = "3", Name = "$thread_peek_message")))]
+    ThreadPeekMessage,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$thread_remove_message")))]
+    ThreadRemoveMessage,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$mutex_create")))]
+    MutexCreate,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$mutex_lock")))]
+    MutexLock,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$mutex_unlock")))]
+    MutexUnlock,
+    #[strum_discriminants(strum(props(Arity = "3", Name = "$call_foreign_predicate")))]
+    ForeignPredicateCall,
+    #[strum_discriminants(strum(props(Arity = "3", Name = "$open_foreign_solutions")))]
+    OpenForeignSolutions,
+    #[strum_discriminants(strum(props(Arity = "3", Name = "$next_foreign_solution")))]
+    NextForeignSolution,
+    #[strum_discriminants(strum(props(Arity = "1", Name = "$close_foreign_solutions")))]
+    CloseForeignSolutions,
     #[strum_discriminants(strum(props(Arity = "2", Name = "$det_length_rundown")))]
     DeterministicLengthRundown,
     #[strum_discriminants(strum(props(Arity = "7", Name = "$http_open")))]
@@ -773,6 +863,62 @@ enum InstructionTemplate {
     Plus(ArithmeticTerm, usize),
     #[strum_discriminants(strum(props(Arity = "1", Name = "bitwise_complement")))]
     BitwiseComplement(ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "1", Name = "msb")))]
+    Msb(ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "1", Name = "lsb")))]
+    Lsb(ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "1", Name = "popcount")))]
+    PopCount(ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "1", Name = "sinh")))]
+    Sinh(ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "1", Name = "cosh")))]
+    Cosh(ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "1", Name = "tanh")))]
+    Tanh(ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "1", Name = "asinh")))]
+    ASinh(ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "1", Name = "acosh")))]
+    ACosh(ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "1", Name = "atanh")))]
+    ATanh(ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "1", Name = "log2")))]
+    Log2(ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "1", Name = "cot")))]
+    Cot(ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "1", Name = "acot")))]
+    ACot(ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "1", Name = "sind")))]
+    SinD(ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "1", Name = "cosd")))]
+    CosD(ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "1", Name = "tand")))]
+    TanD(ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "1", Name = "asind")))]
+    ASinD(ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "1", Name = "acosd")))]
+    ACosD(ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "1", Name = "atand")))]
+    ATanD(ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "1", Name = "integer")))]
+    ToInteger(ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "1", Name = "random")))]
+    Random(ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "1", Name = "decimal")))]
+    ToDecimal(ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "2", Name = "atan2d")))]
+    ATan2D(ArithmeticTerm, ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "2", Name = "copysign")))]
+    CopySign(ArithmeticTerm, ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "2", Name = "nexttoward")))]
+    NextToward(ArithmeticTerm, ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "2", Name = "cmpflags")))]
+    CmpFlags(ArithmeticTerm, ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "2", Name = "decimal")))]
+    ToDecimalScaled(ArithmeticTerm, ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "3", Name = "powm")))]
+    PowM(ArithmeticTerm, ArithmeticTerm, ArithmeticTerm, usize),
+    #[strum_discriminants(strum(props(Arity = "0", Name = "random_float")))]
+    RandomFloat(usize),
     // control instructions
     #[strum_discriminants(strum(props(Arity = "1", Name = "allocate")))]
     Allocate(usize), // num_frames.
@@ -876,6 +1022,7 @@ fn generate_instruction_preface() -> TokenStream {
         use crate::machine::heap::*;
         use crate::machine::machine_errors::MachineStub;
         use crate::machine::machine_indices::CodeIndex;
+        use crate::machine::qlf::*;
         use crate::parser::ast::*;
         use crate::types::*;
 
@@ -1520,6 +1667,98 @@ fn to_functor(&self, h: usize, arena: &mut Arena) -> MachineStub {
                         at,
                         t,
                     ),
+                    &Instruction::Msb(ref at, t) => {
+                        arith_instr_unary_functor(h, atom!("msb"), arena, at, t)
+                    }
+                    &Instruction::Lsb(ref at, t) => {
+                        arith_instr_unary_functor(h, atom!("lsb"), arena, at, t)
+                    }
+                    &Instruction::PopCount(ref at, t) => {
+                        arith_instr_unary_functor(h, atom!("popcount"), arena, at, t)
+                    }
+                    &Instruction::Sinh(ref at, t) => {
+                        arith_instr_unary_functor(h, atom!("sinh"), arena, at, t)
+                    }
+                    &Instruction::Cosh(ref at, t) => {
+                        arith_instr_unary_functor(h, atom!("cosh"), arena, at, t)
+                    }
+                    &Instruction::Tanh(ref at, t) => {
+                        arith_instr_unary_functor(h, atom!("tanh"), arena, at, t)
+                    }
+                    &Instruction::ASinh(ref at, t) => {
+                        arith_instr_unary_functor(h, atom!("asinh"), arena, at, t)
+                    }
+                    &Instruction::ACosh(ref at, t) => {
+                        arith_instr_unary_functor(h, atom!("acosh"), arena, at, t)
+                    }
+                    &Instruction::ATanh(ref at, t) => {
+                        arith_instr_unary_functor(h, atom!("atanh"), arena, at, t)
+                    }
+                    &Instruction::Log2(ref at, t) => {
+                        arith_instr_unary_functor(h, atom!("log2"), arena, at, t)
+                    }
+                    &Instruction::Cot(ref at, t) => {
+                        arith_instr_unary_functor(h, atom!("cot"), arena, at, t)
+                    }
+                    &Instruction::ACot(ref at, t) => {
+                        arith_instr_unary_functor(h, atom!("acot"), arena, at, t)
+                    }
+                    &Instruction::SinD(ref at, t) => {
+                        arith_instr_unary_functor(h, atom!("sind"), arena, at, t)
+                    }
+                    &Instruction::CosD(ref at, t) => {
+                        arith_instr_unary_functor(h, atom!("cosd"), arena, at, t)
+                    }
+                    &Instruction::TanD(ref at, t) => {
+                        arith_instr_unary_functor(h, atom!("tand"), arena, at, t)
+                    }
+                    &Instruction::ASinD(ref at, t) => {
+                        arith_instr_unary_functor(h, atom!("asind"), arena, at, t)
+                    }
+                    &Instruction::ACosD(ref at, t) => {
+                        arith_instr_unary_functor(h, atom!("acosd"), arena, at, t)
+                    }
+                    &Instruction::ATanD(ref at, t) => {
+                        arith_instr_unary_functor(h, atom!("atand"), arena, at, t)
+                    }
+                    &Instruction::ToInteger(ref at, t) => {
+                        arith_instr_unary_functor(h, atom!("integer"), arena, at, t)
+                    }
+                    &Instruction::Random(ref at, t) => {
+                        arith_instr_unary_functor(h, atom!("random"), arena, at, t)
+                    }
+                    &Instruction::ToDecimal(ref at, t) => {
+                        arith_instr_unary_functor(h, atom!("decimal"), arena, at, t)
+                    }
+                    &Instruction::ATan2D(ref at_1, ref at_2, t) => {
+                        arith_instr_bin_functor(h, atom!("atan2d"), arena, at_1, at_2, t)
+                    }
+                    &Instruction::CopySign(ref at_1, ref at_2, t) => {
+                        arith_instr_bin_functor(h, atom!("copysign"), arena, at_1, at_2, t)
+                    }
+                    &Instruction::NextToward(ref at_1, ref at_2, t) => {
+                        arith_instr_bin_functor(h, atom!("nexttoward"), arena, at_1, at_2, t)
+                    }
+                    &Instruction::CmpFlags(ref at_1, ref at_2, t) => {
+                        arith_instr_bin_functor(h, atom!("cmpflags"), arena, at_1, at_2, t)
+                    }
+                    &Instruction::ToDecimalScaled(ref at_1, ref at_2, t) => {
+                        arith_instr_bin_functor(h, atom!("decimal"), arena, at_1, at_2, t)
+                    }
+                    &Instruction::PowM(ref at_1, ref at_2, ref at_3, t) => {
+                        let at_1_stub = at_1.into_functor(arena);
+                        let at_2_stub = at_2.into_functor(arena);
+                        let at_3_stub = at_3.into_functor(arena);
+
+                        functor!(
+                            atom!("powm"),
+                            [str(h, 0), str(h, 1), str(h, 2), fixnum(t)],
+                            [at_1_stub, at_2_stub, at_3_stub]
+                        )
+                    }
+                    &Instruction::RandomFloat(t) => {
+                        functor!(atom!("random_float"), [fixnum(t)])
+                    }
                     &Instruction::IndexingCode(_) => {
                         // this case is covered in enqueue_functors, which
                         // should be called instead (to_functor is a private
@@ -1770,6 +2009,7 @@ fn to_functor(&self, h: usize, arena: &mut Arena) -> MachineStub {
                     &Instruction::CallOpDeclaration |
                     &Instruction::CallOpen |
                     &Instruction::CallSetStreamOptions |
+                    &Instruction::CallSetStreamEncoding |
                     &Instruction::CallNextStream |
                     &Instruction::CallPartialStringTail |
                     &Instruction::CallPeekByte |
@@ -1798,14 +2038,31 @@ fn to_functor(&self, h: usize, arena: &mut Arena) -> MachineStub {
                     &Instruction::CallCleanUpBlock |
                     &Instruction::CallFail |
                     &Instruction::CallGetBall |
+                    &Instruction::CallBallBacktrace |
                     &Instruction::CallGetCurrentBlock |
                     &Instruction::CallGetCurrentSCCBlock |
                     &Instruction::CallGetCutPoint |
-                    &Instruction::CallGetDoubleQuotes |
-                    &Instruction::CallGetUnknown |
                     &Instruction::CallInstallNewBlock |
                     &Instruction::CallMaybe |
                     &Instruction::CallCpuNow |
+                    &Instruction::CallGarbageCollect |
+                    &Instruction::CallGCStatistics |
+                    &Instruction::CallGarbageCollectAtoms |
+                    &Instruction::CallAtomGCStatistics |
+                    &Instruction::CallThreadCreate |
+                    &Instruction::CallThreadJoin |
+                    &Instruction::CallThreadSelf |
+                    &Instruction::CallThreadFinish |
+                    &Instruction::CallThreadSendMessage |
+                    &Instruction::CallThreadPeekMessage |
+                    &Instruction::CallThreadRemoveMessage |
+                    &Instruction::CallMutexCreate |
+                    &Instruction::CallMutexLock |
+                    &Instruction::CallMutexUnlock |
+                    &Instruction::CallForeignPredicateCall |
+                    &Instruction::CallOpenForeignSolutions |
+                    &Instruction::CallNextForeignSolution |
+                    &Instruction::CallCloseForeignSolutions |
                     &Instruction::CallDeterministicLengthRundown |
                     &Instruction::CallHttpOpen |
                     &Instruction::CallHttpListen |
@@ -1829,8 +2086,6 @@ fn to_functor(&self, h: usize, arena: &mut Arena) -> MachineStub {
                     &Instruction::CallPopBallStack |
                     &Instruction::CallPopFromBallStack |
                     &Instruction::CallSetCutPointByDefault(..) |
-                    &Instruction::CallSetDoubleQuotes |
-                    &Instruction::CallSetUnknown |
                     &Instruction::CallSetSeed |
                     &Instruction::CallSkipMaxList |
                     &Instruction::CallSleep |
@@ -1867,13 +2122,12 @@ fn to_functor(&self, h: usize, arena: &mut Arena) -> MachineStub {
                     &Instruction::CallSetEnv |
                     &Instruction::CallUnsetEnv |
                     &Instruction::CallShell |
+                    &Instruction::CallProcessCreate |
+                    &Instruction::CallProcessWait |
+                    &Instruction::CallProcessKill |
                     &Instruction::CallPID |
                     &Instruction::CallCharsBase64 |
                     &Instruction::CallDevourWhitespace |
-                    &Instruction::CallIsSTOEnabled |
-                    &Instruction::CallSetSTOAsUnify |
-                    &Instruction::CallSetNSTOAsUnify |
-                    &Instruction::CallSetSTOWithErrorAsUnify |
                     &Instruction::CallHomeDirectory |
                     &Instruction::CallDebugHook |
                     &Instruction::CallAddDiscontiguousPredicate |
@@ -1892,11 +2146,42 @@ fn to_functor(&self, h: usize, arena: &mut Arena) -> MachineStub {
                     &Instruction::CallLoadContextDirectory |
                     &Instruction::CallLoadContextModule |
                     &Instruction::CallLoadContextStream |
+                    &Instruction::CallSetLoadContextLine |
+                    &Instruction::CallInvokeTraced |
+                    &Instruction::CallTracePortHook |
+                    &Instruction::CallStartProfiler |
+                    &Instruction::CallStopProfiler |
+                    &Instruction::CallProfileData |
+                    &Instruction::CallPrologFlags |
+                    &Instruction::CallPrologFlag |
+                    &Instruction::CallSetPrologFlag |
+                    &Instruction::CallCreatePrologFlag |
+                    &Instruction::CallTableVariantTable |
+                    &Instruction::CallTableEnter |
+                    &Instruction::CallTableBeginPass |
+                    &Instruction::CallTableEndPass |
+                    &Instruction::CallTableAddAnswer |
+                    &Instruction::CallTableModedAnswer |
+                    &Instruction::CallTableAnswerCount |
+                    &Instruction::CallTableAnswer |
+                    &Instruction::CallTableNegation |
+                    &Instruction::CallTableAbandon |
+                    &Instruction::CallTableAbolishAll |
+                    &Instruction::CallTableIncrementalDynamic |
+                    &Instruction::CallTableVariant |
+                    &Instruction::CallTableAnswerConditions |
                     &Instruction::CallPopLoadContext |
                     &Instruction::CallPopLoadStatePayload |
                     &Instruction::CallPushLoadContext |
                     &Instruction::CallPushLoadStatePayload |
                     &Instruction::CallUseModule |
+                    &Instruction::CallQlfFile |
+                    &Instruction::CallOpenQlf |
+                    &Instruction::CallNextQlfTerm |
+                    &Instruction::CallStartQlfRecording |
+                    &Instruction::CallRecordQlfTerm |
+                    &Instruction::CallSaveQlf |
+                    &Instruction::CallSaveState |
                     &Instruction::CallBuiltInProperty |
                     &Instruction::CallMetaPredicateProperty |
                     &Instruction::CallMultifileProperty |
@@ -1910,7 +2195,6 @@ fn to_functor(&self, h: usize, arena: &mut Arena) -> MachineStub {
                     &Instruction::CallFlushTermQueue |
                     &Instruction::CallRemoveModuleExports |
                     &Instruction::CallAddNonCountedBacktracking |
-                    &Instruction::CallPopCount |
                     &Instruction::CallArgv |
                     &Instruction::CallEd25519SignRaw |
                     &Instruction::CallEd25519VerifyRaw |
@@ -2007,6 +2291,7 @@ fn to_functor(&self, h: usize, arena: &mut Arena) -> MachineStub {
                     &Instruction::ExecuteOpDeclaration |
                     &Instruction::ExecuteOpen |
                     &Instruction::ExecuteSetStreamOptions |
+                    &Instruction::ExecuteSetStreamEncoding |
                     &Instruction::ExecuteNextStream |
                     &Instruction::ExecutePartialStringTail |
                     &Instruction::ExecutePeekByte |
@@ -2035,14 +2320,31 @@ fn to_functor(&self, h: usize, arena: &mut Arena) -> MachineStub {
                     &Instruction::ExecuteCleanUpBlock |
                     &Instruction::ExecuteFail |
                     &Instruction::ExecuteGetBall |
+                    &Instruction::ExecuteBallBacktrace |
                     &Instruction::ExecuteGetCurrentBlock |
                     &Instruction::ExecuteGetCurrentSCCBlock |
                     &Instruction::ExecuteGetCutPoint |
-                    &Instruction::ExecuteGetDoubleQuotes |
-                    &Instruction::ExecuteGetUnknown |
                     &Instruction::ExecuteInstallNewBlock |
                     &Instruction::ExecuteMaybe |
                     &Instruction::ExecuteCpuNow |
+                    &Instruction::ExecuteGarbageCollect |
+                    &Instruction::ExecuteGCStatistics |
+                    &Instruction::ExecuteGarbageCollectAtoms |
+                    &Instruction::ExecuteAtomGCStatistics |
+                    &Instruction::ExecuteThreadCreate |
+                    &Instruction::ExecuteThreadJoin |
+                    &Instruction::ExecuteThreadSelf |
+                    &Instruction::ExecuteThreadFinish |
+                    &Instruction::ExecuteThreadSendMessage |
+                    &Instruction::ExecuteThreadPeekMessage |
+                    &Instruction::ExecuteThreadRemoveMessage |
+                    &Instruction::ExecuteMutexCreate |
+                    &Instruction::ExecuteMutexLock |
+                    &Instruction::ExecuteMutexUnlock |
+                    &Instruction::ExecuteForeignPredicateCall |
+                    &Instruction::ExecuteOpenForeignSolutions |
+                    &Instruction::ExecuteNextForeignSolution |
+                    &Instruction::ExecuteCloseForeignSolutions |
                     &Instruction::ExecuteDeterministicLengthRundown |
                     &Instruction::ExecuteHttpOpen |
                     &Instruction::ExecuteHttpListen |
@@ -2066,8 +2368,6 @@ fn to_functor(&self, h: usize, arena: &mut Arena) -> MachineStub {
                     &Instruction::ExecutePopBallStack |
                     &Instruction::ExecutePopFromBallStack |
                     &Instruction::ExecuteSetCutPointByDefault(_) |
-                    &Instruction::ExecuteSetDoubleQuotes |
-                    &Instruction::ExecuteSetUnknown |
                     &Instruction::ExecuteSetSeed |
                     &Instruction::ExecuteSkipMaxList |
                     &Instruction::ExecuteSleep |
@@ -2104,13 +2404,12 @@ fn to_functor(&self, h: usize, arena: &mut Arena) -> MachineStub {
                     &Instruction::ExecuteSetEnv |
                     &Instruction::ExecuteUnsetEnv |
                     &Instruction::ExecuteShell |
+                    &Instruction::ExecuteProcessCreate |
+                    &Instruction::ExecuteProcessWait |
+                    &Instruction::ExecuteProcessKill |
                     &Instruction::ExecutePID |
                     &Instruction::ExecuteCharsBase64 |
                     &Instruction::ExecuteDevourWhitespace |
-                    &Instruction::ExecuteIsSTOEnabled |
-                    &Instruction::ExecuteSetSTOAsUnify |
-                    &Instruction::ExecuteSetNSTOAsUnify |
-                    &Instruction::ExecuteSetSTOWithErrorAsUnify |
                     &Instruction::ExecuteHomeDirectory |
                     &Instruction::ExecuteDebugHook |
                     &Instruction::ExecuteAddDiscontiguousPredicate |
@@ -2129,11 +2428,42 @@ fn to_functor(&self, h: usize, arena: &mut Arena) -> MachineStub {
                     &Instruction::ExecuteLoadContextDirectory |
                     &Instruction::ExecuteLoadContextModule |
                     &Instruction::ExecuteLoadContextStream |
+                    &Instruction::ExecuteSetLoadContextLine |
+                    &Instruction::ExecuteInvokeTraced |
+                    &Instruction::ExecuteTracePortHook |
+                    &Instruction::ExecuteStartProfiler |
+                    &Instruction::ExecuteStopProfiler |
+                    &Instruction::ExecuteProfileData |
+                    &Instruction::ExecutePrologFlags |
+                    &Instruction::ExecutePrologFlag |
+                    &Instruction::ExecuteSetPrologFlag |
+                    &Instruction::ExecuteCreatePrologFlag |
+                    &Instruction::ExecuteTableVariantTable |
+                    &Instruction::ExecuteTableEnter |
+                    &Instruction::ExecuteTableBeginPass |
+                    &Instruction::ExecuteTableEndPass |
+                    &Instruction::ExecuteTableAddAnswer |
+                    &Instruction::ExecuteTableModedAnswer |
+                    &Instruction::ExecuteTableAnswerCount |
+                    &Instruction::ExecuteTableAnswer |
+                    &Instruction::ExecuteTableNegation |
+                    &Instruction::ExecuteTableAbandon |
+                    &Instruction::ExecuteTableAbolishAll |
+                    &Instruction::ExecuteTableIncrementalDynamic |
+                    &Instruction::ExecuteTableVariant |
+                    &Instruction::ExecuteTableAnswerConditions |
                     &Instruction::ExecutePopLoadContext |
                     &Instruction::ExecutePopLoadStatePayload |
                     &Instruction::ExecutePushLoadContext |
                     &Instruction::ExecutePushLoadStatePayload |
                     &Instruction::ExecuteUseModule |
+                    &Instruction::ExecuteQlfFile |
+                    &Instruction::ExecuteOpenQlf |
+                    &Instruction::ExecuteNextQlfTerm |
+                    &Instruction::ExecuteStartQlfRecording |
+                    &Instruction::ExecuteRecordQlfTerm |
+                    &Instruction::ExecuteSaveQlf |
+                    &Instruction::ExecuteSaveState |
                     &Instruction::ExecuteBuiltInProperty |
                     &Instruction::ExecuteMetaPredicateProperty |
                     &Instruction::ExecuteMultifileProperty |
@@ -2147,7 +2477,6 @@ fn to_functor(&self, h: usize, arena: &mut Arena) -> MachineStub {
                     &Instruction::ExecuteFlushTermQueue |
                     &Instruction::ExecuteRemoveModuleExports |
                     &Instruction::ExecuteAddNonCountedBacktracking |
-                    &Instruction::ExecutePopCount |
                     &Instruction::ExecuteArgv |
                     &Instruction::ExecuteEd25519SignRaw |
                     &Instruction::ExecuteEd25519VerifyRaw |
@@ -3075,6 +3404,71 @@ fn attributeless_enum<T: ToDeriveInput>() -> Vec<Variant> {
         })
         .collect();
 
+    let qlf_encode_arms: Vec<_> = instr_variants
+        .iter()
+        .enumerate()
+        .map(|(tag, variant)| {
+            let variant_ident = &variant.ident;
+
+            let enum_arity = if let Fields::Unnamed(fields) = &variant.fields {
+                fields.unnamed.len()
+            } else {
+                0
+            };
+
+            let placeholder_ids: Vec<_> =
+                (0..enum_arity).map(|n| format_ident!("f_{}", n)).collect();
+
+            if enum_arity == 0 {
+                quote! {
+                    Instruction::#variant_ident => enc.write_usize(#tag)
+                }
+            } else {
+                quote! {
+                    Instruction::#variant_ident(#(#placeholder_ids),*) => {
+                        enc.write_usize(#tag);
+                        #(QlfCode::qlf_encode(#placeholder_ids, enc)?;)*
+                    }
+                }
+            }
+        })
+        .collect();
+
+    let qlf_decode_arms: Vec<_> = instr_variants
+        .iter()
+        .enumerate()
+        .map(|(tag, variant)| {
+            let variant_ident = &variant.ident;
+
+            if let Fields::Unnamed(fields) = &variant.fields {
+                let field_types = fields.unnamed.iter().map(|field| &field.ty);
+
+                quote! {
+                    #tag => Instruction::#variant_ident(
+                        #(<#field_types as QlfCode>::qlf_decode(dec, ctx)?),*
+                    )
+                }
+            } else {
+                quote! {
+                    #tag => Instruction::#variant_ident
+                }
+            }
+        })
+        .collect();
+
+    // .qlf files and saved states store instructions by their position
+    // in the enum, so a file is only valid for the instruction set that
+    // wrote it. The FNV-1a hash of the variants, in order and with
+    // their fields, tells instruction sets apart.
+    let instruction_fingerprint =
+        instr_variants
+            .iter()
+            .fold(0xcbf29ce484222325u64, |hash, variant| {
+                quote!(#variant).to_string().bytes().fold(hash, |hash, b| {
+                    (hash ^ b as u64).wrapping_mul(0x100000001b3)
+                })
+            });
+
     let name_and_arity_arms: Vec<_> = instr_data
         .instr_variants
         .into_iter()
@@ -3281,6 +3675,29 @@ pub fn is_query_instr(&self) -> bool {
             }
         }
 
+        pub(crate) const INSTRUCTION_FINGERPRINT: u64 = #instruction_fingerprint;
+
+        impl QlfCode for Instruction {
+            fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+                match self {
+                    #(
+                        #qlf_encode_arms,
+                    )*
+                }
+
+                Ok(())
+            }
+
+            fn qlf_decode(dec: &mut QlfDecoder, ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+                Ok(match dec.read_usize()? {
+                    #(
+                        #qlf_decode_arms,
+                    )*
+                    _ => return Err(QlfError::UnknownInstruction),
+                })
+            }
+        }
+
         #[macro_export]
         macro_rules! _instr {
             #(
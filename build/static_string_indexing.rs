@@ -53,6 +53,12 @@ fn parse(input: ParseStream) -> Result<Self> {
 
         while !input.is_empty() {
             let _ = input.parse::<Token![,]>();
+
+            // a trailing comma ends the arguments.
+            if input.is_empty() {
+                break;
+            }
+
             args.push(input.parse()?);
         }
 
@@ -8,7 +8,9 @@
 use crate::rcu::RcuRef;
 use crate::read::*;
 
+use crate::parser::char_reader::CharReader;
 use crate::parser::dashu::{Integer, Rational};
+use crate::parser::decimal::Decimal;
 use ordered_float::OrderedFloat;
 
 use std::cell::UnsafeCell;
@@ -193,6 +195,8 @@ pub enum ArenaHeaderTag {
     NamedTlsStream = 0b100000,
     HttpReadStream = 0b100001,
     HttpWriteStream = 0b100010,
+    PipeInputStream = 0b100011,
+    PipeOutputStream = 0b100100,
     ReadlineStream = 0b110000,
     StaticStringStream = 0b110100,
     ByteStream = 0b111000,
@@ -207,6 +211,7 @@ pub enum ArenaHeaderTag {
     IndexPtrDynamicIndex = 0b1000110,
     IndexPtrIndex = 0b1000111,
     IndexPtrUndefined = 0b1001000,
+    Decimal = 0b1001001,
 }
 
 #[bitfield]
@@ -533,6 +538,17 @@ fn tag() -> ArenaHeaderTag {
     }
 }
 
+impl ArenaAllocated for Decimal {
+    type PtrToAllocated = TypedArenaPtr<Decimal>;
+
+    gen_ptr_to_allocated!(Decimal);
+
+    #[inline]
+    fn tag() -> ArenaHeaderTag {
+        ArenaHeaderTag::Decimal
+    }
+}
+
 impl ArenaAllocated for LiveLoadState {
     type PtrToAllocated = TypedArenaPtr<LiveLoadState>;
 
@@ -671,6 +687,9 @@ macro_rules! drop_typed_slab_in_place {
         ArenaHeaderTag::Rational => {
             drop_typed_slab_in_place!(Rational, value);
         }
+        ArenaHeaderTag::Decimal => {
+            drop_typed_slab_in_place!(Decimal, value);
+        }
         ArenaHeaderTag::InputFileStream => {
             drop_typed_slab_in_place!(InputFileStream, value);
         }
@@ -692,6 +711,12 @@ macro_rules! drop_typed_slab_in_place {
             #[cfg(feature = "http")]
             drop_typed_slab_in_place!(HttpWriteStream, value);
         }
+        ArenaHeaderTag::PipeInputStream => {
+            drop_typed_slab_in_place!(StreamLayout<CharReader<PipeInputStream>>, value);
+        }
+        ArenaHeaderTag::PipeOutputStream => {
+            drop_typed_slab_in_place!(StreamLayout<PipeOutputStream>, value);
+        }
         ArenaHeaderTag::ReadlineStream => {
             drop_typed_slab_in_place!(ReadlineStream, value);
         }
@@ -10,11 +10,12 @@
 
 use crate::parser::ast::*;
 use crate::parser::dashu::{Integer, Rational};
+use crate::parser::decimal::Decimal;
 
 use crate::machine::machine_errors::*;
 
-use dashu::base::Abs;
-use dashu::base::BitTest;
+use dashu::base::{Abs, Approximation, Sign};
+use dashu::base::{BitTest, Signed, UnsignedAbs};
 use num_order::NumOrd;
 use ordered_float::*;
 
@@ -23,7 +24,6 @@
 use std::convert::TryFrom;
 use std::f64;
 use std::num::FpCategory;
-use std::ops::Div;
 use std::vec::Vec;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -159,6 +159,7 @@ fn push_literal(interm: &mut Vec<ArithmeticTerm>, c: &Literal) -> Result<(), Ari
         Literal::Integer(n) => interm.push(ArithmeticTerm::Number(Number::Integer(*n))),
         Literal::Float(n) => interm.push(ArithmeticTerm::Number(Number::Float(*n.as_ptr()))),
         Literal::Rational(n) => interm.push(ArithmeticTerm::Number(Number::Rational(*n))),
+        Literal::Decimal(n) => interm.push(ArithmeticTerm::Number(Number::Decimal(*n))),
         Literal::Atom(name) if name == &atom!("e") => interm.push(ArithmeticTerm::Number(
             Number::Float(OrderedFloat(std::f64::consts::E)),
         )),
@@ -168,6 +169,12 @@ fn push_literal(interm: &mut Vec<ArithmeticTerm>, c: &Literal) -> Result<(), Ari
         Literal::Atom(name) if name == &atom!("epsilon") => interm.push(ArithmeticTerm::Number(
             Number::Float(OrderedFloat(std::f64::EPSILON)),
         )),
+        Literal::Atom(name) if name == &atom!("inf") => interm.push(ArithmeticTerm::Number(
+            Number::Float(OrderedFloat(f64::INFINITY)),
+        )),
+        Literal::Atom(name) if name == &atom!("nan") => interm.push(ArithmeticTerm::Number(
+            Number::Float(OrderedFloat(f64::NAN)),
+        )),
         _ => return Err(ArithmeticError::NonEvaluableFunctor(*c, 0)),
     }
 
@@ -211,6 +218,28 @@ fn get_unary_instr(
             atom!("float_fractional_part") => Ok(Instruction::FloatFractionalPart(a1, t)),
             atom!("sign") => Ok(Instruction::Sign(a1, t)),
             atom!("\\") => Ok(Instruction::BitwiseComplement(a1, t)),
+            atom!("msb") => Ok(Instruction::Msb(a1, t)),
+            atom!("lsb") => Ok(Instruction::Lsb(a1, t)),
+            atom!("popcount") => Ok(Instruction::PopCount(a1, t)),
+            atom!("sinh") => Ok(Instruction::Sinh(a1, t)),
+            atom!("cosh") => Ok(Instruction::Cosh(a1, t)),
+            atom!("tanh") => Ok(Instruction::Tanh(a1, t)),
+            atom!("asinh") => Ok(Instruction::ASinh(a1, t)),
+            atom!("acosh") => Ok(Instruction::ACosh(a1, t)),
+            atom!("atanh") => Ok(Instruction::ATanh(a1, t)),
+            atom!("log2") => Ok(Instruction::Log2(a1, t)),
+            atom!("cot") => Ok(Instruction::Cot(a1, t)),
+            atom!("acot") => Ok(Instruction::ACot(a1, t)),
+            atom!("sind") => Ok(Instruction::SinD(a1, t)),
+            atom!("cosd") => Ok(Instruction::CosD(a1, t)),
+            atom!("tand") => Ok(Instruction::TanD(a1, t)),
+            atom!("asind") => Ok(Instruction::ASinD(a1, t)),
+            atom!("acosd") => Ok(Instruction::ACosD(a1, t)),
+            atom!("atand") => Ok(Instruction::ATanD(a1, t)),
+            atom!("trunc") => Ok(Instruction::Truncate(a1, t)),
+            atom!("integer") => Ok(Instruction::ToInteger(a1, t)),
+            atom!("random") => Ok(Instruction::Random(a1, t)),
+            atom!("decimal") => Ok(Instruction::ToDecimal(a1, t)),
             _ => Err(ArithmeticError::NonEvaluableFunctor(Literal::Atom(name), 1)),
         }
     }
@@ -243,10 +272,29 @@ fn get_binary_instr(
             atom!("rem") => Ok(Instruction::Rem(a1, a2, t)),
             atom!("gcd") => Ok(Instruction::Gcd(a1, a2, t)),
             atom!("atan2") => Ok(Instruction::ATan2(a1, a2, t)),
+            atom!("atan2d") => Ok(Instruction::ATan2D(a1, a2, t)),
+            atom!("copysign") => Ok(Instruction::CopySign(a1, a2, t)),
+            atom!("nexttoward") => Ok(Instruction::NextToward(a1, a2, t)),
+            atom!("cmpflags") => Ok(Instruction::CmpFlags(a1, a2, t)),
+            atom!("decimal") => Ok(Instruction::ToDecimalScaled(a1, a2, t)),
             _ => Err(ArithmeticError::NonEvaluableFunctor(Literal::Atom(name), 2)),
         }
     }
 
+    fn get_ternary_instr(
+        &self,
+        name: Atom,
+        a1: ArithmeticTerm,
+        a2: ArithmeticTerm,
+        a3: ArithmeticTerm,
+        t: usize,
+    ) -> Result<Instruction, ArithmeticError> {
+        match name {
+            atom!("powm") => Ok(Instruction::PowM(a1, a2, a3, t)),
+            _ => Err(ArithmeticError::NonEvaluableFunctor(Literal::Atom(name), 3)),
+        }
+    }
+
     fn incr_interm(&mut self) -> usize {
         let temp = self.interm_c;
 
@@ -298,6 +346,24 @@ fn instr_from_clause(
 
                 self.get_binary_instr(name, a1, a2, ninterm)
             }
+            3 => {
+                let a3 = self.interm.pop().unwrap();
+                let a2 = self.interm.pop().unwrap();
+                let a1 = self.interm.pop().unwrap();
+
+                let interms = [a1, a2, a3].map(|a| a.interm_or(0));
+
+                let ninterm = match interms.iter().filter(|&&i| i > 0).min() {
+                    Some(&min_interm) => {
+                        self.interm.push(ArithmeticTerm::Interm(min_interm));
+                        self.interm_c = min_interm + 1;
+                        min_interm
+                    }
+                    None => self.incr_interm(),
+                };
+
+                self.get_ternary_instr(name, a1, a2, a3, ninterm)
+            }
             _ => Err(ArithmeticError::NonEvaluableFunctor(
                 Literal::Atom(name),
                 arity,
@@ -315,6 +381,10 @@ pub(crate) fn compile_is(
 
         for term_ref in src.iter()? {
             match term_ref? {
+                ArithTermRef::Literal(Literal::Atom(atom!("random_float"))) => {
+                    let t = self.incr_interm();
+                    code.push_back(Instruction::RandomFloat(t));
+                }
                 ArithTermRef::Literal(c) => push_literal(&mut self.interm, c)?,
                 ArithTermRef::Var(lvl, cell, name) => {
                     let var_num = name.to_var_num().unwrap();
@@ -372,12 +442,24 @@ pub(crate) fn rnd_i(n: &'_ Number, arena: &mut Arena) -> Number {
             if I64_MIN_TO_F <= f && f <= I64_MAX_TO_F {
                 fixnum!(Number, f.into_inner() as i64, arena)
             } else {
-                Number::Integer(arena_alloc!(Integer::from(f.0 as i64), arena))
+                // a floored, finite float is an integer, so the
+                // conversion is exact.
+                let i = Integer::try_from(f.0).unwrap_or_else(|_| Integer::from(f.0 as i64));
+                Number::Integer(arena_alloc!(i, arena))
             }
         }
         Number::Rational(ref r) => {
             let (_, floor) = (r.fract(), r.floor());
 
+            if let Ok(value) = (&floor).try_into() {
+                fixnum!(Number, value, arena)
+            } else {
+                Number::Integer(arena_alloc!(floor, arena))
+            }
+        }
+        Number::Decimal(ref d) => {
+            let floor = d.to_rational().floor();
+
             if let Ok(value) = (&floor).try_into() {
                 fixnum!(Number, value, arena)
             } else {
@@ -394,133 +476,323 @@ fn from(n: Fixnum) -> Integer {
     }
 }
 
-// floating point rounding function -- 9.1.4.1.
-pub(crate) fn rnd_f(n: &Number) -> f64 {
+// floating point result function -- 9.1.4.2. Floats are results
+// already and are left as they are, infinite or not.
+pub(crate) fn result_f(n: &Number, flags: FloatFlags) -> Result<f64, EvalError> {
     match n {
-        &Number::Fixnum(n) => n.get_num() as f64,
-        Number::Integer(ref n) => n.to_f64().value(),
-        &Number::Float(OrderedFloat(f)) => f,
-        Number::Rational(ref r) => r.to_f64().value(),
+        &Number::Fixnum(n) => float_fn_to_f(n.get_num(), flags),
+        Number::Integer(ref n) => float_i_to_f(n, flags),
+        &Number::Float(OrderedFloat(f)) => Ok(f),
+        Number::Rational(ref r) => float_r_to_f(r, flags),
+        Number::Decimal(ref d) => float_r_to_f(&d.to_rational(), flags),
     }
 }
 
-// floating point result function -- 9.1.4.2.
-pub(crate) fn result_f(n: &Number) -> Result<f64, EvalError> {
-    classify_float(rnd_f(n))
+// the exact value of an integer, a rational or a decimal.
+pub(crate) fn exact_rational(n: &Number) -> Option<Rational> {
+    match n {
+        &Number::Fixnum(n) => Some(Rational::from(n.get_num())),
+        Number::Integer(ref n) => Some(Rational::from((**n).clone())),
+        Number::Rational(ref r) => Some((**r).clone()),
+        Number::Decimal(ref d) => Some(d.to_rational()),
+        Number::Float(_) => None,
+    }
 }
 
-fn classify_float(f: f64) -> Result<f64, EvalError> {
-    match f.classify() {
-        FpCategory::Normal | FpCategory::Zero => Ok(f),
-        FpCategory::Infinite => {
-            if OrderedFloat(f) == OrderedFloat(f64::MAX) {
-                Ok(f)
-            } else {
-                Err(EvalError::FloatOverflow)
-            }
+impl Decimal {
+    // the decimal nearest to self, as floats are compared with
+    // rationals.
+    fn to_f64(&self) -> OrderedFloat<f64> {
+        OrderedFloat(self.to_rational().to_f64().value())
+    }
+}
+
+// the float after f toward positive infinity.
+fn next_up(f: f64) -> f64 {
+    if f.is_nan() || f == f64::INFINITY {
+        f
+    } else if f == 0.0 {
+        f64::from_bits(1)
+    } else if f > 0.0 {
+        f64::from_bits(f.to_bits() + 1)
+    } else {
+        f64::from_bits(f.to_bits() - 1)
+    }
+}
+
+#[inline]
+fn next_down(f: f64) -> f64 {
+    -next_up(-f)
+}
+
+impl FloatRounding {
+    // rounds f, the nearest float to the exact result of an operation,
+    // in this direction. error has the sign of the exact result less
+    // f.
+    fn round(self, f: f64, error: f64) -> f64 {
+        if !f.is_finite() || error == 0.0 || error.is_nan() {
+            return f;
         }
-        FpCategory::Nan => Err(EvalError::Undefined),
-        _ => Ok(f),
+
+        match self {
+            FloatRounding::ToPositive if error > 0.0 => next_up(f),
+            FloatRounding::ToNegative if error < 0.0 => next_down(f),
+            FloatRounding::ToZero if f > 0.0 && error < 0.0 => next_down(f),
+            FloatRounding::ToZero if f < 0.0 && error > 0.0 => next_up(f),
+            _ => f,
+        }
+    }
+}
+
+/// The result f of a float operation on the operands, an error if
+/// the operation overflowed or was undefined for them and the flags
+/// don't make that an infinity or a NaN. Infinities and NaNs among
+/// the operands carry over to the result without an error.
+pub(crate) fn float_op_result(
+    f: f64,
+    operands: &[f64],
+    flags: FloatFlags,
+) -> Result<f64, EvalError> {
+    if f.is_nan() && !flags.undefined_to_nan && !operands.iter().any(|o| o.is_nan()) {
+        Err(EvalError::Undefined)
+    } else if f.is_infinite()
+        && !flags.overflow_to_infinity
+        && operands.iter().all(|o| o.is_finite())
+    {
+        Err(EvalError::FloatOverflow)
+    } else {
+        Ok(f)
     }
 }
 
+/// The result of dividing the finite f by zero: a signed infinity if
+/// the flags allow it, else an error.
+pub(crate) fn zero_div_result(f: f64, divisor: f64, flags: FloatFlags) -> Result<f64, EvalError> {
+    if flags.zero_div_to_infinity {
+        Ok(f / divisor)
+    } else {
+        Err(EvalError::ZeroDivisor)
+    }
+}
+
+// the float nearest to an integer or rational, rounded by the flags.
+fn approximation_to_f(
+    approx: Approximation<f64, Sign>,
+    flags: FloatFlags,
+) -> Result<f64, EvalError> {
+    let (f, error) = match approx {
+        Approximation::Exact(f) => (f, 0.0),
+        // the sign is that of f less the exact value.
+        Approximation::Inexact(f, Sign::Positive) => (f, -1.0),
+        Approximation::Inexact(f, Sign::Negative) => (f, 1.0),
+    };
+
+    float_op_result(flags.rounding.round(f, error), &[], flags)
+}
+
 #[inline]
-pub(crate) fn float_fn_to_f(n: i64) -> Result<f64, EvalError> {
-    classify_float(n as f64)
+pub(crate) fn float_fn_to_f(n: i64, flags: FloatFlags) -> Result<f64, EvalError> {
+    let f = n as f64;
+
+    // f64 can't hold every i64, so the conversion is inexact past 2^53.
+    let error = match (n as i128).cmp(&(f as i128)) {
+        Ordering::Less => -1.0,
+        Ordering::Equal => 0.0,
+        Ordering::Greater => 1.0,
+    };
+
+    Ok(flags.rounding.round(f, error))
 }
 
 #[inline]
-pub(crate) fn float_i_to_f(n: &Integer) -> Result<f64, EvalError> {
-    classify_float(n.to_f64().value())
+pub(crate) fn float_i_to_f(n: &Integer, flags: FloatFlags) -> Result<f64, EvalError> {
+    approximation_to_f(n.to_f64(), flags)
 }
 
 #[inline]
-pub(crate) fn float_r_to_f(r: &Rational) -> Result<f64, EvalError> {
-    classify_float(r.to_f64().value())
+pub(crate) fn float_r_to_f(r: &Rational, flags: FloatFlags) -> Result<f64, EvalError> {
+    approximation_to_f(r.to_f64(), flags)
 }
 
 #[inline]
-pub(crate) fn add_f(f1: f64, f2: f64) -> Result<OrderedFloat<f64>, EvalError> {
-    Ok(OrderedFloat(classify_float(f1 + f2)?))
+pub(crate) fn float_d_to_f(d: &Decimal, flags: FloatFlags) -> Result<f64, EvalError> {
+    float_r_to_f(&d.to_rational(), flags)
+}
+
+#[inline]
+pub(crate) fn add_f(f1: f64, f2: f64, flags: FloatFlags) -> Result<OrderedFloat<f64>, EvalError> {
+    let sum = f1 + f2;
+
+    // the exact error of the sum, by Knuth's TwoSum.
+    let f1_part = sum - f2;
+    let f2_part = sum - f1_part;
+    let error = (f1 - f1_part) + (f2 - f2_part);
+
+    let sum = flags.rounding.round(sum, error);
+    Ok(OrderedFloat(float_op_result(sum, &[f1, f2], flags)?))
 }
 
 #[inline]
-pub(crate) fn mul_f(f1: f64, f2: f64) -> Result<OrderedFloat<f64>, EvalError> {
-    Ok(OrderedFloat(classify_float(f1 * f2)?))
+pub(crate) fn mul_f(f1: f64, f2: f64, flags: FloatFlags) -> Result<OrderedFloat<f64>, EvalError> {
+    let product = f1 * f2;
+    let error = f1.mul_add(f2, -product);
+
+    let product = flags.rounding.round(product, error);
+    Ok(OrderedFloat(float_op_result(product, &[f1, f2], flags)?))
 }
 
 #[inline]
-fn div_f(f1: f64, f2: f64) -> Result<OrderedFloat<f64>, EvalError> {
+fn div_f(f1: f64, f2: f64, flags: FloatFlags) -> Result<OrderedFloat<f64>, EvalError> {
     if FpCategory::Zero == f2.classify() {
-        Err(EvalError::ZeroDivisor)
+        if f1.is_infinite() {
+            Ok(OrderedFloat(f1 / f2))
+        } else if f1 == 0.0 || f1.is_nan() {
+            // 0/0 is undefined, but a division by zero all the same.
+            zero_div_result(f1, f2, flags)?;
+            Ok(OrderedFloat(float_op_result(f1 / f2, &[f1, f2], flags)?))
+        } else {
+            Ok(OrderedFloat(zero_div_result(f1, f2, flags)?))
+        }
     } else {
-        Ok(OrderedFloat(classify_float(f1 / f2)?))
+        let quotient = f1 / f2;
+
+        // f1 less quotient * f2, exactly, whose sign relative to f2's
+        // is that of the error of the quotient.
+        let remainder = (-quotient).mul_add(f2, f1);
+        let error = if remainder == 0.0 {
+            0.0
+        } else if (remainder > 0.0) == (f2 > 0.0) {
+            1.0
+        } else {
+            -1.0
+        };
+
+        let quotient = flags.rounding.round(quotient, error);
+        Ok(OrderedFloat(float_op_result(quotient, &[f1, f2], flags)?))
     }
 }
 
-impl Div<Number> for Number {
-    type Output = Result<Number, EvalError>;
+#[inline]
+pub(crate) fn sqrt_f(f: f64, flags: FloatFlags) -> Result<f64, EvalError> {
+    let root = f.sqrt();
+    let error = (-root).mul_add(root, f);
 
-    fn div(self, rhs: Number) -> Self::Output {
-        match (self, rhs) {
-            (Number::Fixnum(n1), Number::Fixnum(n2)) => Ok(Number::Float(div_f(
-                float_fn_to_f(n1.get_num())?,
-                float_fn_to_f(n2.get_num())?,
-            )?)),
-            (Number::Fixnum(n1), Number::Integer(n2)) => Ok(Number::Float(div_f(
-                float_fn_to_f(n1.get_num())?,
-                float_i_to_f(&n2)?,
-            )?)),
-            (Number::Integer(n1), Number::Fixnum(n2)) => Ok(Number::Float(div_f(
-                float_i_to_f(&n1)?,
-                float_fn_to_f(n2.get_num())?,
-            )?)),
-            (Number::Fixnum(n1), Number::Rational(n2)) => Ok(Number::Float(div_f(
-                float_fn_to_f(n1.get_num())?,
-                float_r_to_f(&n2)?,
-            )?)),
-            (Number::Rational(n1), Number::Fixnum(n2)) => Ok(Number::Float(div_f(
-                float_r_to_f(&n1)?,
-                float_fn_to_f(n2.get_num())?,
-            )?)),
-            (Number::Fixnum(n1), Number::Float(OrderedFloat(n2))) => {
-                Ok(Number::Float(div_f(float_fn_to_f(n1.get_num())?, n2)?))
-            }
-            (Number::Float(OrderedFloat(n1)), Number::Fixnum(n2)) => {
-                Ok(Number::Float(div_f(n1, float_fn_to_f(n2.get_num())?)?))
-            }
-            (Number::Integer(n1), Number::Integer(n2)) => Ok(Number::Float(div_f(
-                float_i_to_f(&n1)?,
-                float_i_to_f(&n2)?,
-            )?)),
-            (Number::Integer(n1), Number::Float(OrderedFloat(n2))) => {
-                Ok(Number::Float(div_f(float_i_to_f(&n1)?, n2)?))
-            }
-            (Number::Float(OrderedFloat(n2)), Number::Integer(n1)) => {
-                Ok(Number::Float(div_f(n2, float_i_to_f(&n1)?)?))
-            }
-            (Number::Integer(n1), Number::Rational(n2)) => Ok(Number::Float(div_f(
-                float_i_to_f(&n1)?,
-                float_r_to_f(&n2)?,
-            )?)),
-            (Number::Rational(n2), Number::Integer(n1)) => Ok(Number::Float(div_f(
-                float_r_to_f(&n2)?,
-                float_i_to_f(&n1)?,
-            )?)),
-            (Number::Rational(n1), Number::Float(OrderedFloat(n2))) => {
-                Ok(Number::Float(div_f(float_r_to_f(&n1)?, n2)?))
-            }
-            (Number::Float(OrderedFloat(n2)), Number::Rational(n1)) => {
-                Ok(Number::Float(div_f(n2, float_r_to_f(&n1)?)?))
-            }
-            (Number::Float(OrderedFloat(f1)), Number::Float(OrderedFloat(f2))) => {
-                Ok(Number::Float(div_f(f1, f2)?))
+    float_op_result(flags.rounding.round(root, error), &[f], flags)
+}
+
+/// The float quotient of n1 and n2, as by `(/)/2`.
+pub(crate) fn float_div(n1: Number, n2: Number, flags: FloatFlags) -> Result<Number, EvalError> {
+    let f1 = result_f(&n1, flags)?;
+    let f2 = result_f(&n2, flags)?;
+
+    Ok(Number::Float(div_f(f1, f2, flags)?))
+}
+
+/// The decimal of an integer or a decimal, the operands of decimal
+/// arithmetic.
+pub(crate) fn decimal_operand(n: &Number) -> Option<Decimal> {
+    match n {
+        &Number::Fixnum(n) => Some(Decimal::from(Integer::from(n.get_num()))),
+        Number::Integer(ref n) => Some(Decimal::from((**n).clone())),
+        Number::Decimal(ref d) => Some((**d).clone()),
+        _ => None,
+    }
+}
+
+/// The decimal quotient of d1 and d2, as by `(/)/2`. It has at least
+/// as many digits after its decimal point as d1 has more than d2, and
+/// if it isn't exact, the `decimal_precision` digits.
+pub(crate) fn decimal_div(
+    d1: &Decimal,
+    d2: &Decimal,
+    flags: DecimalFlags,
+) -> Result<Decimal, EvalError> {
+    if d2.is_zero() {
+        return Err(EvalError::ZeroDivisor);
+    }
+
+    let quotient = d1.to_rational() / d2.to_rational();
+    let min_scale = d1.scale().saturating_sub(d2.scale());
+
+    Ok(Decimal::from_rational_with_precision(
+        &quotient,
+        min_scale,
+        flags.precision,
+        flags.rounding,
+    ))
+}
+
+/// The decimal base^exponent, as by `(^)/2`: exact for natural
+/// exponents and otherwise the quotient of 1 and base^-exponent.
+pub(crate) fn decimal_pow(
+    base: &Decimal,
+    exponent: &Integer,
+    flags: DecimalFlags,
+) -> Result<Decimal, EvalError> {
+    let power =
+        usize::try_from(exponent.clone().unsigned_abs()).map_err(|_| EvalError::Undefined)?;
+    let scale = base
+        .scale()
+        .checked_mul(power)
+        .ok_or(EvalError::Undefined)?;
+    let result = Decimal::new(base.significand().pow(power), scale);
+
+    if exponent.is_negative() {
+        decimal_div(&Decimal::from(Integer::ONE), &result, flags)
+    } else {
+        Ok(result)
+    }
+}
+
+/// The decimal of n, as by `decimal/1`. Floats are their shortest
+/// decimals that read back as them, with at least one digit after the
+/// decimal point, and rationals are as exact as `decimal_precision`
+/// allows.
+pub(crate) fn to_decimal(n: &Number, flags: DecimalFlags) -> Result<Decimal, EvalError> {
+    match n {
+        &Number::Float(OrderedFloat(f)) => {
+            if !f.is_finite() {
+                return Err(EvalError::Undefined);
             }
-            (Number::Rational(r1), Number::Rational(r2)) => Ok(Number::Float(div_f(
-                float_r_to_f(&r1)?,
-                float_r_to_f(&r2)?,
-            )?)),
+
+            let mut buffer = ryu::Buffer::new();
+            let digits = buffer.format_finite(f);
+
+            let (mantissa, exponent) = match digits.split_once('e') {
+                Some((mantissa, exponent)) => (mantissa, exponent.parse::<isize>().unwrap()),
+                None => (digits, 0),
+            };
+
+            let (mantissa, negative) = match mantissa.strip_prefix('-') {
+                Some(mantissa) => (mantissa, true),
+                None => (mantissa, false),
+            };
+
+            let d = Decimal::parse(mantissa).ok_or(EvalError::Undefined)?;
+            let d = if negative { d.neg() } else { d };
+            let r = d.to_rational() * pow10_rational(exponent);
+            let scale = max(1, d.scale() as isize - exponent) as usize;
+
+            Ok(Decimal::from_rational(&r, scale, flags.rounding))
         }
+        Number::Rational(ref r) => Ok(Decimal::from_rational_with_precision(
+            r,
+            0,
+            flags.precision,
+            flags.rounding,
+        )),
+        n => Ok(decimal_operand(n).unwrap()),
+    }
+}
+
+// 10^exponent, for exponents of either sign.
+fn pow10_rational(exponent: isize) -> Rational {
+    let power = Rational::from(Integer::from(10u8).pow(exponent.unsigned_abs()));
+
+    if exponent < 0 {
+        Rational::ONE / power
+    } else {
+        power
     }
 }
 
@@ -573,6 +845,12 @@ fn eq(&self, rhs: &Self) -> bool {
             }
             (&Number::Float(f1), &Number::Float(f2)) => f1.eq(&f2),
             (Number::Rational(ref r1), Number::Rational(ref r2)) => r1.eq(r2),
+            (Number::Decimal(ref d1), Number::Decimal(ref d2)) => d1.value() == d2.value(),
+            (Number::Decimal(ref d), &Number::Float(f))
+            | (&Number::Float(f), Number::Decimal(ref d)) => d.to_f64().eq(&f),
+            (Number::Decimal(ref d), n) | (n, Number::Decimal(ref d)) => {
+                exact_rational(n) == Some(d.to_rational())
+            }
         }
     }
 }
@@ -594,6 +872,7 @@ fn partial_cmp(&self, rhs: &usize) -> Option<Ordering> {
             }
             Number::Integer(n) => Some((n).num_cmp(rhs)),
             Number::Rational(r) => Some((r).num_cmp(&Integer::from(*rhs))),
+            Number::Decimal(d) => Some(d.to_rational().num_cmp(&Integer::from(*rhs))),
             Number::Float(f) => f.partial_cmp(&OrderedFloat(*rhs as f64)),
         }
     }
@@ -614,6 +893,7 @@ fn eq(&self, rhs: &usize) -> bool {
             }
             Number::Integer(n) => (n).num_eq(rhs),
             Number::Rational(r) => (r).num_eq(&Integer::from(*rhs)),
+            Number::Decimal(d) => d.to_rational().num_eq(&Integer::from(*rhs)),
             Number::Float(f) => f.eq(&OrderedFloat(*rhs as f64)),
         }
     }
@@ -670,6 +950,11 @@ fn cmp(&self, rhs: &Number) -> Ordering {
             }
             (&Number::Float(f1), &Number::Float(f2)) => f1.cmp(&f2),
             (&Number::Rational(r1), &Number::Rational(r2)) => (*r1).cmp(&*r2),
+            (Number::Decimal(d1), Number::Decimal(d2)) => d1.value().cmp(d2.value()),
+            (Number::Decimal(d), Number::Float(f)) => d.to_f64().cmp(f),
+            (Number::Float(f), Number::Decimal(d)) => f.cmp(&d.to_f64()),
+            (Number::Decimal(d), n) => d.to_rational().cmp(&exact_rational(n).unwrap()),
+            (n, Number::Decimal(d)) => exact_rational(n).unwrap().cmp(&d.to_rational()),
         }
     }
 }
@@ -688,6 +973,9 @@ fn try_from(value: HeapCellValue) -> Result<Number, Self::Error> {
                   (ArenaHeaderTag::Rational, n) => {
                       Ok(Number::Rational(n))
                   }
+                  (ArenaHeaderTag::Decimal, n) => {
+                      Ok(Number::Decimal(n))
+                  }
                   _ => {
                       Err(())
                   }
@@ -4,12 +4,14 @@
 use crate::types::*;
 
 use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use std::mem;
 use std::ops::Deref;
 use std::ptr;
 use std::slice;
 use std::str;
+use std::sync::atomic::AtomicUsize;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::sync::RwLock;
@@ -185,14 +187,18 @@ pub fn as_ptr(self) -> Option<AtomTableRef<u8>> {
                 arc_atom_table().expect("We should only have an Atom while there is an AtomTable");
             unsafe {
                 AtomTableRef::try_map(atom_table.buf(), |buf| {
-                    (buf as *const u8)
-                        .add((self.index as usize) - (STRINGS.len() << 3))
-                        .as_ref()
+                    (buf as *const u8).add(self.block_offset()).as_ref()
                 })
             }
         }
     }
 
+    // the offset of a dynamic atom into the atom table block.
+    #[inline(always)]
+    fn block_offset(self) -> usize {
+        (self.index as usize) - (STRINGS.len() << 3)
+    }
+
     #[inline(always)]
     pub fn from(index: u64) -> Self {
         Self { index }
@@ -293,8 +299,11 @@ pub struct InnerAtomTable {
 #[derive(Debug)]
 pub struct AtomTable {
     inner: Rcu<InnerAtomTable>,
-    // this lock is taking during resizing
-    update: Mutex<()>,
+    // this lock is taking during resizing and collection. it guards
+    // the slots freed by collections, keyed by slot size.
+    update: Mutex<HashMap<usize, Vec<usize>>>,
+    // the length of the active table, readable without entering it.
+    num_dynamic_atoms: AtomicUsize,
 }
 
 pub type AtomTableRef<M> = RcuRef<InnerAtomTable, M>;
@@ -327,7 +336,8 @@ pub fn new() -> Arc<Self> {
                         block: RawBlock::new(),
                         table: Rcu::new(IndexSet::new()),
                     }),
-                    update: Mutex::new(()),
+                    update: Mutex::new(HashMap::new()),
+                    num_dynamic_atoms: AtomicUsize::new(0),
                 });
                 *guard = Arc::downgrade(&atom_table);
                 atom_table
@@ -356,7 +366,7 @@ pub fn build_with(atom_table: &AtomTable, string: &str) -> Atom {
             }
 
             // take a lock to prevent concurrent updates
-            let update_guard = atom_table.update.lock().unwrap();
+            let mut update_guard = atom_table.update.lock().unwrap();
 
             let is_same_allocation =
                 RcuRef::same_epoch(&block_epoch, &atom_table.inner.active_epoch());
@@ -370,16 +380,19 @@ pub fn build_with(atom_table: &AtomTable, string: &str) -> Atom {
                 continue;
             }
 
-            let size = mem::size_of::<AtomHeader>() + string.len();
-            let align_offset = 8 * mem::align_of::<AtomHeader>();
-            let size = (size & !(align_offset - 1)) + align_offset;
+            let size = slot_size(string.len());
+            let free_slot = update_guard.get_mut(&size).and_then(Vec::pop);
 
             unsafe {
                 let len_ptr = loop {
-                    let ptr = block_epoch.block.alloc(size);
+                    let ptr = match free_slot {
+                        // slots are only freed once no reader can
+                        // reach their former atom through the table.
+                        Some(offset) => (block_epoch.block.base as *mut u8).add(offset),
+                        None => block_epoch.block.alloc(size),
+                    };
 
                     if ptr.is_null() {
-                        // garbage collection would go here
                         let new_block = block_epoch.block.grow_new().unwrap();
                         let new_table = Rcu::new(table_epoch.clone());
                         let new_alloc = InnerAtomTable {
@@ -406,6 +419,10 @@ pub fn build_with(atom_table: &AtomTable, string: &str) -> Atom {
                 table.insert(atom);
                 block_epoch.table.replace(table);
 
+                atom_table
+                    .num_dynamic_atoms
+                    .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+
                 // expicit drop to ensure we don't accidentally drop it early
                 drop(update_guard);
 
@@ -413,6 +430,116 @@ pub fn build_with(atom_table: &AtomTable, string: &str) -> Atom {
             }
         }
     }
+
+    /// The number of atoms interned at runtime, i.e. not counting the
+    /// static atoms.
+    #[inline]
+    pub fn num_dynamic_atoms(&self) -> usize {
+        self.num_dynamic_atoms
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Removes the atoms interned before `marks` was created that
+    /// were not marked since. The table without them is published
+    /// through the RCU before their slots are recycled, so lookups
+    /// racing with the collection never return a reclaimed atom. The
+    /// slots are reused only by atoms of the same size, so the block
+    /// never holds a malformed atom. Returns the number of atoms
+    /// reclaimed.
+    pub fn collect(atom_table: &AtomTable, marks: &AtomMarks) -> usize {
+        let mut free_slots = atom_table.update.lock().unwrap();
+
+        let block_epoch = atom_table.inner.active_epoch();
+        let table_epoch = block_epoch.table.active_epoch();
+
+        let mut live_table = table_epoch.deref().clone();
+        let mut dead_atoms = vec![];
+        let mut idx = 0;
+
+        live_table.retain(|atom| {
+            let is_candidate = idx < marks.candidates;
+            idx += 1;
+
+            if is_candidate && !marks.is_marked(*atom) {
+                dead_atoms.push(*atom);
+                false
+            } else {
+                true
+            }
+        });
+
+        if dead_atoms.is_empty() {
+            return 0;
+        }
+
+        // waits for the threads reading the old table.
+        block_epoch.table.replace(live_table);
+
+        atom_table
+            .num_dynamic_atoms
+            .fetch_sub(dead_atoms.len(), std::sync::atomic::Ordering::Relaxed);
+
+        for atom in &dead_atoms {
+            free_slots
+                .entry(slot_size(atom.len()))
+                .or_default()
+                .push(atom.block_offset());
+        }
+
+        dead_atoms.len()
+    }
+}
+
+/// The number of bytes taken in the atom table block by an atom of
+/// `len` bytes.
+#[inline]
+fn slot_size(len: usize) -> usize {
+    let size = mem::size_of::<AtomHeader>() + len;
+    let align_offset = 8 * mem::align_of::<AtomHeader>();
+    (size & !(align_offset - 1)) + align_offset
+}
+
+/// The dynamic atoms found to be reachable by an atom collection,
+/// one bit per 8-byte offset into the atom table block.
+#[derive(Debug)]
+pub struct AtomMarks {
+    words: Vec<u64>,
+    // only atoms interned before marking began are collected.
+    candidates: usize,
+}
+
+impl AtomMarks {
+    pub fn new(atom_table: &AtomTable) -> Self {
+        AtomMarks {
+            words: vec![],
+            candidates: atom_table.active_table().len(),
+        }
+    }
+
+    #[inline]
+    pub fn mark(&mut self, atom: Atom) {
+        if atom.is_static() {
+            return;
+        }
+
+        let slot = atom.block_offset() >> 3;
+        let word = slot >> 6;
+
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+
+        self.words[word] |= 1 << (slot & 63);
+    }
+
+    #[inline]
+    fn is_marked(&self, atom: Atom) -> bool {
+        let slot = atom.block_offset() >> 3;
+
+        self.words
+            .get(slot >> 6)
+            .is_some_and(|word| word & (1 << (slot & 63)) != 0)
+    }
 }
 
 unsafe impl Send for AtomTable {}
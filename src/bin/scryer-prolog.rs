@@ -21,7 +21,21 @@ fn main() -> std::process::ExitCode {
         .unwrap();
 
     runtime.block_on(async move {
-        let mut wam = machine::Machine::new(Default::default());
+        let restored = machine::saved_state::startup_state().and_then(|state| {
+            state
+                .map(|state| machine::Machine::restore(Default::default(), &state))
+                .transpose()
+        });
+
+        let mut wam = match restored {
+            Ok(Some(wam)) => wam,
+            Ok(None) => machine::Machine::new(Default::default()),
+            Err(err) => {
+                eprintln!("scryer-prolog: {err}");
+                return std::process::ExitCode::FAILURE;
+            }
+        };
+
         wam.run_module_predicate(atom!("$toplevel"), (atom!("$repl"), 0))
     })
 }
@@ -21,6 +21,7 @@
 
 use std::cell::Cell;
 use std::collections::VecDeque;
+use std::ops::Range;
 
 #[derive(Debug)]
 pub struct BranchCodeStack {
@@ -276,6 +277,8 @@ pub(crate) struct CodeGenerator<'a> {
     marker: DebrayAllocator,
     settings: CodeGenSettings,
     pub(crate) skeleton: PredicateSkeleton,
+    // the code of each compiled clause, in the order of the clauses.
+    pub(crate) clause_code_ranges: Vec<Range<usize>>,
 }
 
 impl DebrayAllocator {
@@ -384,6 +387,7 @@ pub(crate) fn new(atom_tbl: &'b AtomTable, settings: CodeGenSettings) -> Self {
             marker: DebrayAllocator::new(),
             settings,
             skeleton: PredicateSkeleton::new(),
+            clause_code_ranges: vec![],
         }
     }
 
@@ -720,6 +724,7 @@ fn compile_inlined(
             InlinedClauseType::IsNumber(..) => match terms[0] {
                 Term::Literal(_, Literal::Float(_))
                 | Term::Literal(_, Literal::Rational(_))
+                | Term::Literal(_, Literal::Decimal(_))
                 | Term::Literal(_, Literal::Integer(_))
                 | Term::Literal(_, Literal::Fixnum(_)) => {
                     instr!("$succeed")
@@ -895,6 +900,7 @@ macro_rules! compile_expr {
                 c @ Literal::Integer(_)
                 | c @ Literal::Float(_)
                 | c @ Literal::Rational(_)
+                | c @ Literal::Decimal(_)
                 | c @ Literal::Fixnum(_),
             ) => {
                 let v = HeapCellValue::from(c);
@@ -1215,6 +1221,7 @@ fn compile_pred_subseq<I: Indexer>(
 
         let mut skip_stub_try_me_else = false;
         let clauses_len = clauses.len();
+        let ranges_lower_bound = self.clause_code_ranges.len();
 
         for (i, clause) in clauses.iter_mut().enumerate() {
             self.marker.reset();
@@ -1267,6 +1274,8 @@ fn compile_pred_subseq<I: Indexer>(
             }
 
             self.skeleton.clauses.push_back(clause_index_info);
+            self.clause_code_ranges
+                .push(code.len()..code.len() + clause_code.len());
             code.extend(clause_code.into_iter());
         }
 
@@ -1276,8 +1285,14 @@ fn compile_pred_subseq<I: Indexer>(
             vec![]
         };
 
+        let ranges = &mut self.clause_code_ranges[ranges_lower_bound..];
+
         if !index_code.is_empty() {
             code.push_front(Instruction::IndexingCode(index_code));
+
+            for range in ranges {
+                *range = range.start + 1..range.end + 1;
+            }
         } else if clauses.len() == 1 && self.settings.is_extensible {
             // the condition is the value of skip_stub_try_me_else, which is
             // true if the predicate is not dynamic. This operation must apply
@@ -1285,6 +1300,10 @@ fn compile_pred_subseq<I: Indexer>(
 
             // remove the TryMeElse(0).
             code.pop_front();
+
+            for range in ranges {
+                *range = range.start - 1..range.end - 1;
+            }
         }
 
         Ok(Vec::from(code))
@@ -1306,6 +1325,7 @@ pub(crate) fn compile_predicate(
         } in split_pred
         {
             let skel_lower_bound = self.skeleton.clauses.len();
+            let ranges_lower_bound = self.clause_code_ranges.len();
             let code_segment = if self.settings.is_dynamic() {
                 self.compile_pred_subseq::<DynamicCodeIndices>(
                     &mut clauses[left..right],
@@ -1344,6 +1364,12 @@ pub(crate) fn compile_predicate(
                 }
             }
 
+            let segment_start = code.len();
+
+            for range in &mut self.clause_code_ranges[ranges_lower_bound..] {
+                *range = range.start + segment_start..range.end + segment_start;
+            }
+
             code.extend(code_segment.into_iter());
         }
 
@@ -70,6 +70,13 @@ pub fn merge(&mut self, other: ForeignFunctionTable) {
         self.table.extend(other.table);
     }
 
+    /// The field type atoms of the defined structs.
+    pub(crate) fn atoms(&self) -> impl Iterator<Item = Atom> + '_ {
+        self.structs
+            .values()
+            .flat_map(|struct_impl| struct_impl.atom_fields.iter().copied())
+    }
+
     pub fn define_struct(&mut self, name: &str, atom_fields: Vec<Atom>) {
         let mut fields: Vec<_> = atom_fields.iter().map(|x| self.map_type_ffi(x)).collect();
         fields.push(std::ptr::null_mut::<ffi_type>());
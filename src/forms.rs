@@ -8,6 +8,7 @@
 use crate::machine::machine_indices::*;
 use crate::parser::ast::*;
 use crate::parser::dashu::{Integer, Rational};
+use crate::parser::decimal::Decimal;
 use crate::parser::parser::CompositeOpDesc;
 use crate::types::*;
 
@@ -619,6 +620,7 @@ pub enum Number {
     Float(OrderedFloat<f64>),
     Integer(TypedArenaPtr<Integer>),
     Rational(TypedArenaPtr<Rational>),
+    Decimal(TypedArenaPtr<Decimal>),
     Fixnum(Fixnum),
 }
 
@@ -634,6 +636,7 @@ fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
             Number::Float(fl) => write!(f, "{}", fl),
             Number::Integer(n) => write!(f, "{}", n),
             Number::Rational(r) => write!(f, "{}", r),
+            Number::Decimal(d) => write!(f, "{}", d),
             Number::Fixnum(n) => write!(f, "{}", n.get_num()),
         }
     }
@@ -657,6 +660,13 @@ fn arena_from(value: Rational, arena: &mut Arena) -> Number {
     }
 }
 
+impl ArenaFrom<Decimal> for Number {
+    #[inline]
+    fn arena_from(value: Decimal, arena: &mut Arena) -> Number {
+        Number::Decimal(arena_alloc!(value, arena))
+    }
+}
+
 impl ArenaFrom<usize> for Number {
     #[inline]
     fn arena_from(value: usize, arena: &mut Arena) -> Number {
@@ -721,6 +731,7 @@ fn arena_from(value: Number, arena: &mut Arena) -> Literal {
             Number::Integer(n) => Literal::Integer(n),
             Number::Float(OrderedFloat(f)) => Literal::from(float_alloc!(f, arena)),
             Number::Rational(r) => Literal::Rational(r),
+            Number::Decimal(d) => Literal::Decimal(d),
         }
     }
 }
@@ -733,6 +744,7 @@ fn arena_from(value: Number, arena: &mut Arena) -> HeapCellValue {
             Number::Integer(n) => typed_arena_ptr_as_cell!(n),
             Number::Float(OrderedFloat(n)) => HeapCellValue::from(float_alloc!(n, arena)),
             Number::Rational(n) => typed_arena_ptr_as_cell!(n),
+            Number::Decimal(n) => typed_arena_ptr_as_cell!(n),
         }
     }
 }
@@ -765,6 +777,7 @@ pub(crate) fn is_positive(&self) -> bool {
             Number::Integer(ref n) => n.is_positive(),
             Number::Float(f) => f.is_sign_positive(),
             Number::Rational(ref r) => r.is_positive(),
+            Number::Decimal(ref d) => d.is_positive(),
         }
     }
 
@@ -775,6 +788,7 @@ pub(crate) fn is_negative(&self) -> bool {
             Number::Integer(ref n) => n.is_negative(),
             &Number::Float(OrderedFloat(f)) => f.is_sign_negative() && f != -0f64,
             Number::Rational(ref r) => r.is_negative(),
+            Number::Decimal(ref d) => d.is_negative(),
         }
     }
 
@@ -785,6 +799,7 @@ pub(crate) fn is_zero(&self) -> bool {
             Number::Integer(ref n) => n.is_zero(),
             &Number::Float(OrderedFloat(f)) => f == 0.0 || f == -0.0,
             Number::Rational(ref r) => r.is_zero(),
+            Number::Decimal(ref d) => d.is_zero(),
         }
     }
 
@@ -4,6 +4,7 @@
 use crate::parser::dashu::base::RemEuclid;
 use crate::parser::dashu::integer::Sign;
 use crate::parser::dashu::{ibig, Integer, Rational};
+use crate::parser::decimal::Decimal;
 use crate::{
     alpha_numeric_char, capital_letter_char, cut_char, decimal_digit_char, graphic_token_char,
     is_fx, is_infix, is_postfix, is_prefix, is_xf, is_xfx, is_xfy, is_yfx, semicolon_char,
@@ -416,6 +417,7 @@ fn negated_op_needs_bracketing(
                 Ok(Number::Float(OrderedFloat(f))) => f > 0f64,
                 Ok(Number::Integer(n)) => n.is_positive(),
                 Ok(Number::Rational(n)) => n.is_positive(),
+                Ok(Number::Decimal(d)) => d.is_positive(),
                 _ => false,
             })
     } else {
@@ -464,6 +466,25 @@ macro_rules! print_char {
 }
 
 pub fn fmt_float(mut fl: f64) -> String {
+    // infinities and NaNs are written as SWI-Prolog writes them, as
+    // 1.0Inf and as the float 1.Fraction tagged with NaN, which read
+    // back as the same float. The sign of a NaN, which differs
+    // between platforms, is left out.
+    if fl.is_infinite() {
+        return if fl.is_sign_negative() {
+            "-1.0Inf".to_string()
+        } else {
+            "1.0Inf".to_string()
+        };
+    }
+
+    if fl.is_nan() {
+        const FRACTION_MASK: u64 = (1 << 52) - 1;
+
+        let fraction = f64::from_bits(1f64.to_bits() | (fl.to_bits() & FRACTION_MASK));
+        return format!("{}NaN", fmt_float(fraction));
+    }
+
     if OrderedFloat(fl) == -0f64 {
         fl = 0f64;
     }
@@ -1402,6 +1423,7 @@ fn handle_op_as_struct(
                                 Ok(Number::Fixnum(n)) => n.get_num() >= 0,
                                 Ok(Number::Float(f)) => f >= OrderedFloat(0f64),
                                 Ok(Number::Rational(r)) => (*r).sign() == Sign::Positive,
+                                Ok(Number::Decimal(d)) => !d.is_negative(),
                                 _ => false,
                             }
                         }) && needs_bracketing(op_desc, op)
@@ -1729,6 +1751,9 @@ fn handle_heap_term(
                    (ArenaHeaderTag::Rational, r) => {
                        self.print_number(max_depth, NumberFocus::Unfocused(Number::Rational(r)), &op);
                    }
+                   (ArenaHeaderTag::Decimal, d) => {
+                       self.print_number(max_depth, NumberFocus::Unfocused(Number::Decimal(d)), &op);
+                   }
                    (ArenaHeaderTag::Stream, stream) => {
                        self.print_stream(stream, max_depth);
                    }
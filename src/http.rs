@@ -3,8 +3,11 @@
 
 use warp::http;
 
+// the receiver is shared by the threads accepting requests on the
+// same listener.
+#[derive(Clone)]
 pub struct HttpListener {
-    pub incoming: std::sync::mpsc::Receiver<HttpRequest>,
+    pub incoming: Arc<Mutex<std::sync::mpsc::Receiver<HttpRequest>>>,
 }
 
 pub struct HttpRequest {
@@ -5,11 +5,12 @@
 use crate::instructions::*;
 
 use fxhash::FxBuildHasher;
-use indexmap::IndexMap;
+use indexmap::{IndexMap, IndexSet};
 
 use std::collections::VecDeque;
 use std::hash::Hash;
 use std::iter::once;
+use std::marker::PhantomData;
 use std::mem;
 
 #[derive(Debug, Clone, Copy)]
@@ -1135,6 +1136,50 @@ pub(crate) fn constant_key_alternatives(
     constants
 }
 
+/// The key a clause is indexed by at one of its arguments, when the
+/// argument isn't a variable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum ArgIndexKey {
+    Constant(Literal),
+    List,
+    Structure(Atom, usize),
+}
+
+/// A position in the head of a clause: argument `arg`, or the subterm
+/// of it reached by following the argument numbers of `path`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct ArgPosition {
+    pub(crate) arg: usize,
+    pub(crate) path: Vec<usize>,
+}
+
+impl ArgPosition {
+    #[inline]
+    pub(crate) fn arg(arg: usize) -> Self {
+        ArgPosition { arg, path: vec![] }
+    }
+
+    /// The positions of the subterms of a term with key `key` at this
+    /// position.
+    fn subterms(&self, key: ArgIndexKey) -> impl Iterator<Item = ArgPosition> + '_ {
+        let arity = match key {
+            ArgIndexKey::Constant(_) => 0,
+            ArgIndexKey::List => 2,
+            ArgIndexKey::Structure(_, arity) => arity,
+        };
+
+        (1..=arity).map(move |n| {
+            let mut path = self.path.clone();
+            path.push(n);
+
+            ArgPosition {
+                arg: self.arg,
+                path,
+            }
+        })
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct StaticCodeIndices {
     constants: IndexMap<Literal, VecDeque<IndexedChoiceInstruction>, FxBuildHasher>,
@@ -1187,6 +1232,10 @@ fn switch_on_list(
     fn remove_instruction_with_offset(code: &mut VecDeque<Self::ThirdLevelIndex>, offset: usize);
 
     fn var_offset_wrapper(var_offset: usize) -> IndexingCodePtr;
+
+    fn choice_line(code: VecDeque<Self::ThirdLevelIndex>) -> IndexingLine;
+
+    fn external_ptr(index: &Self::ThirdLevelIndex) -> IndexingCodePtr;
 }
 
 impl Indexer for StaticCodeIndices {
@@ -1313,6 +1362,17 @@ fn remove_instruction_with_offset(
     fn var_offset_wrapper(var_offset: usize) -> IndexingCodePtr {
         IndexingCodePtr::External(var_offset)
     }
+
+    #[inline]
+    fn choice_line(mut code: VecDeque<IndexedChoiceInstruction>) -> IndexingLine {
+        cap_choice_seq_with_trust(code.make_contiguous());
+        IndexingLine::from(code)
+    }
+
+    #[inline]
+    fn external_ptr(index: &IndexedChoiceInstruction) -> IndexingCodePtr {
+        IndexingCodePtr::External(index.offset())
+    }
 }
 
 impl Indexer for DynamicCodeIndices {
@@ -1423,6 +1483,16 @@ fn remove_instruction_with_offset(code: &mut VecDeque<usize>, offset: usize) {
     fn var_offset_wrapper(var_offset: usize) -> IndexingCodePtr {
         IndexingCodePtr::DynamicExternal(var_offset)
     }
+
+    #[inline]
+    fn choice_line(code: VecDeque<usize>) -> IndexingLine {
+        IndexingLine::DynamicIndexedChoice(code)
+    }
+
+    #[inline]
+    fn external_ptr(index: &usize) -> IndexingCodePtr {
+        IndexingCodePtr::DynamicExternal(*index)
+    }
 }
 
 #[derive(Debug)]
@@ -1581,3 +1651,311 @@ pub(crate) fn compute_indices(mut self, skip_stub_try_me_else: bool) -> Vec<Inde
         prelude.into_iter().collect()
     }
 }
+
+/// The most times the size of an indexed choice of its clauses an
+/// index built by `PositionIndices` grows to before it stops indexing
+/// unbound subterm positions.
+const MAX_INDEX_GROWTH: usize = 4;
+
+/// Where a pointer of the indexing code built by `PositionIndices`
+/// leads: out of the indexing code, or to one of its lines.
+#[derive(Debug, Clone, Copy)]
+enum PositionTarget {
+    Ptr(IndexingCodePtr),
+    Line(usize),
+}
+
+impl PositionTarget {
+    #[inline]
+    fn ptr_from(self, line: usize) -> IndexingCodePtr {
+        match self {
+            PositionTarget::Ptr(ptr) => ptr,
+            PositionTarget::Line(target) => IndexingCodePtr::Internal(target - line),
+        }
+    }
+}
+
+/// The lines of a position index and the positions its `SwitchOnTerm`
+/// lines switch on.
+pub(crate) type PositionIndex = (Vec<IndexingLine>, Vec<(usize, ArgPosition)>);
+
+/// Builds indexing code that switches a sequence of clauses on several
+/// positions of their heads in turn. The clauses sharing a key at one
+/// position are switched on whichever of the remaining positions tells
+/// them apart best, so that the keys of several arguments combine.
+/// Once the clauses agree on the principal functor at a position, the
+/// positions of its subterms become candidates too.
+///
+/// `keys` gives the key of a clause, by its index as in `CodeOffsets`,
+/// at a position. The `SwitchOnTerm` lines switch on the argument of
+/// their position, and their positions are returned beside the lines.
+/// Where a subterm position is unbound, its clauses are switched on
+/// the remaining positions instead, for as long as the index is no
+/// more than `MAX_INDEX_GROWTH` times the size of an indexed choice of
+/// all the clauses. Otherwise they're tried in turn.
+///
+/// Only the indices built just in time by `JitIndices` use this. The
+/// indexing code compiled with a predicate still switches on its first
+/// instantiated argument alone, through `CodeOffsets`.
+pub(crate) struct PositionIndices<'a, I: Indexer, K> {
+    keys: K,
+    atom_tbl: &'a AtomTable,
+    non_counted_bt: bool,
+    num_entries: usize,
+    max_entries: usize,
+    lines: Vec<IndexingLine>,
+    switches: Vec<(usize, ArgPosition)>,
+    _indexer: PhantomData<I>,
+}
+
+impl<'a, I, K> PositionIndices<'a, I, K>
+where
+    I: Indexer,
+    K: FnMut(usize, &ArgPosition) -> Option<ArgIndexKey>,
+{
+    pub(crate) fn new(keys: K, atom_tbl: &'a AtomTable, non_counted_bt: bool) -> Self {
+        PositionIndices {
+            keys,
+            atom_tbl,
+            non_counted_bt,
+            num_entries: 0,
+            max_entries: 0,
+            lines: vec![],
+            switches: vec![],
+            _indexer: PhantomData,
+        }
+    }
+
+    /// Selects the position among `candidates` whose keys tell
+    /// `clauses` apart best, returning it, the keys of the clauses at
+    /// it and the positions left to switch on after it.
+    fn select_position(
+        &mut self,
+        clauses: &[usize],
+        candidates: Vec<ArgPosition>,
+    ) -> Option<(ArgPosition, Vec<ArgIndexKey>, Vec<ArgPosition>)> {
+        let mut candidates = VecDeque::from(candidates);
+        let mut rest = vec![];
+        let mut best: Option<(ArgPosition, Vec<ArgIndexKey>, usize)> = None;
+
+        while let Some(position) = candidates.pop_front() {
+            let keys: Option<Vec<ArgIndexKey>> = clauses
+                .iter()
+                .map(|&index| (self.keys)(index, &position))
+                .collect();
+
+            let Some(keys) = keys else {
+                // a bucket of the clauses may still be told apart by it.
+                rest.push(position);
+                continue;
+            };
+
+            let num_keys = keys.iter().collect::<IndexSet<_>>().len();
+
+            if num_keys == 1 {
+                candidates.extend(position.subterms(keys[0]));
+                continue;
+            }
+
+            match &best {
+                Some((_, _, best_num_keys)) if *best_num_keys >= num_keys => {
+                    rest.push(position);
+                }
+                _ => {
+                    if let Some((position, ..)) = best.replace((position, keys, num_keys)) {
+                        rest.push(position);
+                    }
+                }
+            }
+        }
+
+        best.map(|(position, keys, _)| (position, keys, rest))
+    }
+
+    fn push_line(&mut self, line: IndexingLine) -> usize {
+        self.lines.push(line);
+        self.lines.len() - 1
+    }
+
+    fn push_choice_line(&mut self, clauses: &[usize]) -> usize {
+        let code = clauses
+            .iter()
+            .enumerate()
+            .map(|(i, &index)| I::compute_index(i == 0, index, self.non_counted_bt))
+            .collect();
+
+        self.num_entries += clauses.len();
+        self.push_line(I::choice_line(code))
+    }
+
+    fn index_clauses(&mut self, clauses: &[usize], candidates: Vec<ArgPosition>) -> PositionTarget {
+        if let &[index] = clauses {
+            let index = I::compute_index(true, index, self.non_counted_bt);
+
+            self.num_entries += 1;
+            return PositionTarget::Ptr(I::external_ptr(&index));
+        }
+
+        match self.select_position(clauses, candidates) {
+            Some((position, keys, rest)) => {
+                PositionTarget::Line(self.switch_on_position(clauses, position, keys, rest))
+            }
+            None => PositionTarget::Line(self.push_choice_line(clauses)),
+        }
+    }
+
+    fn switch_on_position(
+        &mut self,
+        clauses: &[usize],
+        position: ArgPosition,
+        keys: Vec<ArgIndexKey>,
+        rest: Vec<ArgPosition>,
+    ) -> usize {
+        let mut constants: IndexMap<Literal, Vec<usize>, FxBuildHasher> =
+            IndexMap::with_hasher(FxBuildHasher::default());
+        let mut lists = vec![];
+        let mut structures: IndexMap<(Atom, usize), Vec<usize>, FxBuildHasher> =
+            IndexMap::with_hasher(FxBuildHasher::default());
+
+        for (&index, key) in clauses.iter().zip(keys) {
+            match key {
+                ArgIndexKey::Constant(constant) => {
+                    let overlapping_constants = constant_key_alternatives(constant, self.atom_tbl);
+
+                    for constant in once(constant).chain(overlapping_constants) {
+                        constants.entry(constant).or_default().push(index);
+                    }
+                }
+                ArgIndexKey::List => {
+                    lists.push(index);
+                }
+                ArgIndexKey::Structure(name, arity) => {
+                    structures.entry((name, arity)).or_default().push(index);
+                }
+            }
+        }
+
+        let placeholder = || {
+            IndexingLine::from(IndexingInstruction::SwitchOnTerm(
+                position.arg,
+                IndexingCodePtr::Fail,
+                IndexingCodePtr::Fail,
+                IndexingCodePtr::Fail,
+                IndexingCodePtr::Fail,
+            ))
+        };
+
+        let switch_line = self.push_line(placeholder());
+        let con_line = (constants.len() > 1).then(|| self.push_line(placeholder()));
+        let str_line = (structures.len() > 1).then(|| self.push_line(placeholder()));
+
+        // the arguments are bound in calls using the index, but their
+        // subterms needn't be.
+        let var_target = if position.path.is_empty() || self.num_entries >= self.max_entries {
+            PositionTarget::Line(self.push_choice_line(clauses))
+        } else {
+            self.index_clauses(clauses, rest.clone())
+        };
+
+        let constants: Vec<(Literal, PositionTarget)> = constants
+            .into_iter()
+            .map(|(constant, clauses)| (constant, self.index_clauses(&clauses, rest.clone())))
+            .collect();
+
+        let lst_target = if lists.is_empty() {
+            PositionTarget::Ptr(IndexingCodePtr::Fail)
+        } else {
+            let candidates = rest
+                .iter()
+                .cloned()
+                .chain(position.subterms(ArgIndexKey::List))
+                .collect();
+
+            self.index_clauses(&lists, candidates)
+        };
+
+        let structures: Vec<((Atom, usize), PositionTarget)> = structures
+            .into_iter()
+            .map(|((name, arity), clauses)| {
+                let candidates = rest
+                    .iter()
+                    .cloned()
+                    .chain(position.subterms(ArgIndexKey::Structure(name, arity)))
+                    .collect();
+
+                ((name, arity), self.index_clauses(&clauses, candidates))
+            })
+            .collect();
+
+        let con_target = match con_line {
+            Some(con_line) => {
+                let index = constants
+                    .into_iter()
+                    .map(|(constant, target)| (constant, target.ptr_from(con_line)))
+                    .collect();
+
+                self.lines[con_line] =
+                    IndexingLine::from(IndexingInstruction::SwitchOnConstant(index));
+
+                PositionTarget::Line(con_line)
+            }
+            None => constants
+                .into_iter()
+                .next()
+                .map(|(_, target)| target)
+                .unwrap_or(PositionTarget::Ptr(IndexingCodePtr::Fail)),
+        };
+
+        let str_target = match str_line {
+            Some(str_line) => {
+                let index = structures
+                    .into_iter()
+                    .map(|(key, target)| (key, target.ptr_from(str_line)))
+                    .collect();
+
+                self.lines[str_line] =
+                    IndexingLine::from(IndexingInstruction::SwitchOnStructure(index));
+
+                PositionTarget::Line(str_line)
+            }
+            None => structures
+                .into_iter()
+                .next()
+                .map(|(_, target)| target)
+                .unwrap_or(PositionTarget::Ptr(IndexingCodePtr::Fail)),
+        };
+
+        self.lines[switch_line] = IndexingLine::from(IndexingInstruction::SwitchOnTerm(
+            position.arg,
+            var_target.ptr_from(switch_line),
+            con_target.ptr_from(switch_line),
+            lst_target.ptr_from(switch_line),
+            str_target.ptr_from(switch_line),
+        ));
+
+        self.switches.push((switch_line, position));
+
+        switch_line
+    }
+
+    /// Indexes `clauses` on `candidates`, returning the lines of the
+    /// index, which starts at its first, and the positions of its
+    /// `SwitchOnTerm` lines. `None` if no candidate tells the clauses
+    /// apart.
+    pub(crate) fn compute_indices(
+        mut self,
+        clauses: &[usize],
+        candidates: Vec<ArgPosition>,
+    ) -> Option<PositionIndex> {
+        if clauses.len() < 2 {
+            return None;
+        }
+
+        self.max_entries = MAX_INDEX_GROWTH * clauses.len();
+
+        let (position, keys, rest) = self.select_position(clauses, candidates)?;
+        self.switch_on_position(clauses, position, keys, rest);
+
+        Some((self.lines, self.switches))
+    }
+}
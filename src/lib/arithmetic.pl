@@ -20,23 +20,9 @@ expmod(Base, Expo, Mod, R) :-
     ;   member(N, [Base, Expo, Mod]), \+ integer(N) ->
         type_error(integer, N, expmod/4)
     ;   Expo < 0 -> domain_error(not_less_than_zero, Expo, expmod/4)
-    ;   expmod_(Base, Expo, Mod, 1, R)
+    ;   R is powm(Base, Expo, Mod)
     ).
 
-expmod_(_, _, 1, _, 0) :- !.
-expmod_(_, 0, _, R, R) :- !.
-expmod_(Base0, Expo0, Mod, C0, R) :-
-    Expo0 /\ 1 =:= 1,
-    C is (C0 * Base0) mod Mod,
-    !,
-    Expo is Expo0 >> 1,
-    Base is (Base0 * Base0) mod Mod,
-    expmod_(Base, Expo, Mod, C, R).
-expmod_(Base0, Expo0, Mod, C, R) :-
-    Expo is Expo0 >> 1,
-    Base is (Base0 * Base0) mod Mod,
-    expmod_(Base, Expo, Mod, C, R).
-
 %% lcm(+A, +B, -Lcm) is det.
 %
 % Calculates the Least common multiple for A and B: the smallest positive integer
@@ -61,8 +47,7 @@ lsb(X, N) :-
     (   \+ integer(X) -> type_error(integer, X, lsb/2)
     ;   X < 1 -> domain_error(not_less_than_one, X, lsb/2)
     ;   builtins:can_be_number(N, lsb/2),
-        X1 is X /\ (-X),
-        msb_(X1, -1, N)
+        N is lsb(X)
     ).
 
 %% msb(+X, -N).
@@ -73,16 +58,9 @@ msb(X, N) :-
     (   \+ integer(X) -> type_error(integer, X, msb/2)
     ;   X < 1 -> domain_error(not_less_than_one, X, msb/2)
     ;   builtins:can_be_number(N, msb/2),
-        X1 is X >> 1,
-        msb_(X1, 0, N)
+        N is msb(X)
     ).
 
-msb_(0, N, N) :- !.
-msb_(X, M, N) :-
-    X1 is X >> 1,
-    M1 is M + 1,
-    msb_(X1, M1, N).
-
 %% number_to_rational(+Real, -Fraction).
 %
 % True iff given a number Real, Fraction is the same number represented as a fraction.
@@ -160,4 +138,4 @@ rational_numerator_denominator(R, N, D) :-
 % of that number has.
 popcount(X, N) :-
     must_be(integer, X),
-    '$popcount'(X, N).
+    N is popcount(X).
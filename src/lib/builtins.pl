@@ -4,10 +4,12 @@
                      atom_chars/2, atom_codes/2, atom_concat/3,
                      atom_length/2, bagof/3, call/1, call/2, call/3,
                      call/4, call/5, call/6, call/7, call/8, call/9,
-                     callable/1, catch/3, char_code/2, clause/2,
+                     callable/1, catch/3,
+                     catch_with_backtrace/3, char_code/2, clause/2,
                      close/1, close/2, current_input/1,
                      current_output/1, current_op/3,
-                     current_predicate/1, current_prolog_flag/2,
+                     create_prolog_flag/3, current_predicate/1,
+                     current_prolog_flag/2,
                      error/2, fail/0, false/0, findall/3, findall/4,
                      flush_output/0, flush_output/1, get_byte/1,
                      get_byte/2, get_char/1, get_char/2, get_code/1,
@@ -19,7 +21,8 @@
                      put_code/2, put_char/1, put_char/2, read/1,
                      read/2, read_term/2, read_term/3, repeat/0,
                      retract/1, retractall/1, set_prolog_flag/2,
-                     set_input/1, set_stream_position/2, set_output/1,
+                     set_input/1, set_stream/2,
+                     set_stream_position/2, set_output/1,
                      setof/3, stream_property/2, sub_atom/5,
                      subsumes_term/2, term_variables/2, throw/1,
                      true/0, unify_with_occurs_check/2, write/1,
@@ -114,6 +117,7 @@ call(_, _, _, _, _, _, _, _, _).
 
 
 :- meta_predicate catch(0, ?, 0).
+:- meta_predicate catch_with_backtrace(0, ?, 0).
 
 % flags.
 
@@ -121,125 +125,140 @@ call(_, _, _, _, _, _, _, _, _).
 %
 % True iff Flag is a flag supported by the processor, and Value is the value currently associated with it.
 % A flag is a setting which value affects internal operation of the Prolog system. Some flags are read-only,
-% while others can be set with `set_prolog_flag/2`.
+% while others can be set with `set_prolog_flag/2`. Enumerates the flags if Flag is a variable.
 %
 % The flags that Scryer Prolog support are:
 %
-%  * `max_arity`: The max arity a predicate can have in Prolog. On Scryer is set to 1023. Read only.
 %  * `bounded`: `true` if integer arithmethic is bounded between some min/max values. On Scryer is always set
 %    to `false` since it supports unbounded integer arithmethic. Read only.
-%  * `integer_rounding_function`: Describes the rounding donde by `//` and `rem` functions. On Scryer is
-%    always set to `toward_zero`. Read only
-%  * `double_quotes`: Determines how double quoted strings are red by Prolog. Scryer uses `chars` by default
-%    which is a list of one-character atoms. Other values are codes (list of integers representing characters),
-%    and atom which creates a whole atom for the string value. Read and write.
 %  * `max_integer`: Maximum integer supported by the system. As Scryer Prolog has unbounded integer arithmethic,
 %    checking the value of this flag fails. Read only.
 %  * `min_integer`: Minimum integer supported by the system. As Scryer Prolog has unbounded integer arithmethic,
 %    checking the value of this flag fails. Read only.
+%  * `integer_rounding_function`: Describes the rounding donde by `//` and `rem` functions. On Scryer is
+%    always set to `toward_zero`. Read only
+%  * `char_conversion`: Whether characters are converted as by `char_conversion/2` when terms are read.
+%    Scryer doesn't convert characters, so it is always `off`.
+%  * `debug`: `on` while the debugger of `library(debug)` is on, else `off`. Read and write.
+%  * `max_arity`: The max arity a predicate can have in Prolog. On Scryer is set to 1023. Read only.
+%  * `unknown`: How undefined predicates are handled when called. Possible values are `error` (the default, an error is thrown),
+%    `fail` (the call silently fails) and `warning` (the call fails and a warning about the undefined predicate is printed).
+%  * `double_quotes`: Determines how double quoted strings are red by Prolog. Scryer uses `chars` by default
+%    which is a list of one-character atoms. Other values are codes (list of integers representing characters),
+%    and atom which creates a whole atom for the string value. Read and write. The flag has a value of its
+%    own in each module: setting it in a module file affects the rest of that file only, and every module
+%    starts with `chars`.
 %  * `occurs_check`: Returns if the occurs check is enabled. The occurs check prevents the creation cyclic terms.
 %    Historically the Prolog unification algorithm didn't do that check so changing the value modifies how Prolog
 %    operates in the low-level. Possible values are `false`  (default), `true` (unification has this check
 %    enabled) and `error` which throws an exception when a cylic term is created. Read and write.
-%  * `unknown`: How undefined predicates are handled when called. Possible values are `error` (the default, an error is thrown),
-%    `fail` (the call silently fails) and `warn` (the call fails and a warning about the undefined predicate is printed).
+%  * `last_call_optimisation`: Whether the last call of a clause reuses the frame of the clause. Always `true`.
+%    Read only.
 %  * `answer_write_options`: Additional write options used by the top level for writing answers.
+%  * `float_overflow`: `error` (the default) if a float operation that overflows throws an evaluation error,
+%    `infinity` if it evaluates to an infinity instead. Read and write.
+%  * `float_zero_div`: `error` (the default) if a float division by zero throws an evaluation error,
+%    `infinity` if it evaluates to a signed infinity instead. Read and write.
+%  * `float_undefined`: `error` (the default) if a float operation without a defined result throws an
+%    evaluation error, `nan` if it evaluates to NaN instead. Read and write.
+%  * `float_rounding`: How the results of float operations are rounded, one of `to_nearest` (the default),
+%    `to_positive`, `to_negative` and `to_zero`. Read and write.
+%  * `decimal_precision`: The number of fractional digits kept when a decimal division or conversion
+%    has no finite exact result. Defaults to 20. Read and write.
+%  * `decimal_rounding`: How decimal results are rounded to `decimal_precision` digits, one of `half_even`
+%    (the default), `half_away_from_zero`, `to_zero`, `away_from_zero`, `to_positive` and `to_negative`.
+%    Read and write.
+%  * `dialect`: The Prolog system, `scryer`. Read only.
+%
+% Flags of one's own are created with `create_prolog_flag/3`.
 %
-current_prolog_flag(Flag, Value) :- Flag == max_arity, !, Value = 1023.
-current_prolog_flag(max_arity, 1023).
-current_prolog_flag(Flag, Value) :- Flag == bounded, !, Value = false.
-current_prolog_flag(bounded, false).
-current_prolog_flag(Flag, Value) :- Flag == integer_rounding_function, !, Value == toward_zero.
-current_prolog_flag(integer_rounding_function, toward_zero).
-current_prolog_flag(Flag, Value) :- Flag == double_quotes, !, '$get_double_quotes'(Value).
-current_prolog_flag(double_quotes, Value) :- '$get_double_quotes'(Value).
-current_prolog_flag(Flag, Value) :- Flag == unknown, !, '$get_unknown'(Value).
-current_prolog_flag(unknown, Value) :- '$get_unknown'(Value).
-current_prolog_flag(Flag, _) :- Flag == max_integer, !, '$fail'.
-current_prolog_flag(Flag, _) :- Flag == min_integer, !, '$fail'.
-current_prolog_flag(Flag, OccursCheckEnabled) :-
-    Flag == occurs_check,
-    !,
-    '$is_sto_enabled'(OccursCheckEnabled).
-current_prolog_flag(occurs_check, OccursCheckEnabled) :-
-    '$is_sto_enabled'(OccursCheckEnabled).
 current_prolog_flag(Flag, Value) :-
-    Flag == answer_write_options,
-    !,
-    answer_write_options(Value).
-current_prolog_flag(answer_write_options, Value) :-
-    answer_write_options(Value).
-current_prolog_flag(Flag, _) :-
-    atom(Flag),
-    throw(error(domain_error(prolog_flag, Flag), current_prolog_flag/2)). % 8.17.2.3 b
-current_prolog_flag(Flag, _) :-
-    nonvar(Flag),
-    throw(error(type_error(atom, Flag), current_prolog_flag/2)). % 8.17.2.3 a
-
-answer_write_options(Value) :-
-    (   iso_ext:bb_get('$answer_write_options', Value) -> true
-    ;   Value = []
+    (   var(Flag) ->
+        '$prolog_flags'(Flags),
+        lists:member(Flag, Flags),
+        '$prolog_flag'(Flag, Value)
+    ;   atom(Flag) ->
+        '$prolog_flag'(Flag, Value) % 8.17.2.3 b
+    ;   throw(error(type_error(atom, Flag), current_prolog_flag/2)) % 8.17.2.3 a
     ).
 
 %% set_prolog_flag(Flag, Value).
 %
 % Sets the internal value of the flag. To see the list of flags supported by Scryer Prolog,
-% check `current_prolog_flag/2`. The flags that are read only will fail if you try to change their values
+% check `current_prolog_flag/2`. Setting a flag that is read only throws a permission error.
 set_prolog_flag(Flag, Value) :-
-    (var(Flag) ; var(Value)),
-    throw(error(instantiation_error, set_prolog_flag/2)). % 8.17.1.3 a, b
-set_prolog_flag(bounded, false) :- !. % 7.11.1.1
-set_prolog_flag(bounded, true)  :- !, '$fail'. % 7.11.1.1
-set_prolog_flag(bounded, Value) :-
-    throw(error(domain_error(flag_value, bounded + Value), set_prolog_flag/2)). % 8.17.1.3 e
-set_prolog_flag(max_integer, Value) :- integer(Value), !, '$fail'. % 7.11.1.2
-set_prolog_flag(max_integer, Value) :-
-    throw(error(domain_error(flag_value, max_integer + Value), set_prolog_flag/2)). % 8.17.1.3 e
-set_prolog_flag(min_integer, Value) :- integer(Value), !, '$fail'. % 7.11.1.3
-set_prolog_flag(min_integer, Value) :-
-    throw(error(domain_error(flag_value, min_integer + Value), set_prolog_flag/2)). % 8.17.1.3 e
-set_prolog_flag(integer_rounding_function, down) :- !. % 7.11.1.4
-set_prolog_flag(integer_rounding_function, Value) :-
-    throw(error(domain_error(flag_value, integer_rounding_function + Value),
-                set_prolog_flag/2)). % 8.17.1.3 e
-set_prolog_flag(double_quotes, chars) :-
-    !, '$set_double_quotes'(chars). % 7.11.2.5, list of one-char atoms.
-set_prolog_flag(double_quotes, atom) :-
-    !, '$set_double_quotes'(atom). % 7.11.2.5, list of char codes (UTF8).
-set_prolog_flag(double_quotes, codes) :-
-    !, '$set_double_quotes'(codes).
-set_prolog_flag(unknown, error) :-
-    !, '$set_unknown'(error).
-set_prolog_flag(unknown, warning) :-
-    !, '$set_unknown'(warning).
-set_prolog_flag(unknown, fail) :-
-    !, '$set_unknown'(fail).
-set_prolog_flag(occurs_check, true) :-
-    !, '$set_sto_as_unify'.
-set_prolog_flag(occurs_check, false) :-
-    !, '$set_nsto_as_unify'.
-set_prolog_flag(occurs_check, error) :-
-    !, '$set_sto_with_error_as_unify'.
-set_prolog_flag(double_quotes, Value) :-
-    flag_domain_error(double_quotes, Value).
-set_prolog_flag(answer_write_options, Options) :-
-    !,
-    catch(catch(builtins:parse_write_options(Options, _, set_prolog_flag/2),
-                error(domain_error(_,_), _),
-                throw(error(type_error(_,_), _))), % convert domain error to type error ....
-          error(type_error(_,_), _),               % ... to catch type and domain errors.
-          flag_domain_error(answer_write_options, Options)),
-    iso_ext:bb_put('$answer_write_options', Options).
-set_prolog_flag(Flag, _) :-
-    atom(Flag),
-    throw(error(domain_error(prolog_flag, Flag), set_prolog_flag/2)). % 8.17.1.3 d
-set_prolog_flag(Flag, _) :-
-    throw(error(type_error(atom, Flag), set_prolog_flag/2)). % 8.17.1.3 c
+    (   (var(Flag) ; var(Value)) ->
+        throw(error(instantiation_error, set_prolog_flag/2)) % 8.17.1.3 a, b
+    ;   \+ atom(Flag) ->
+        throw(error(type_error(atom, Flag), set_prolog_flag/2)) % 8.17.1.3 c
+    ;   \+ acyclic_term(Value) ->
+        flag_domain_error(Flag, Value)
+    ;   Flag == answer_write_options,
+        \+ catch(builtins:parse_write_options(Value, _, set_prolog_flag/2),
+                 error(_, _),
+                 false) ->
+        flag_domain_error(answer_write_options, Value)
+    ;   '$set_prolog_flag'(Flag, Value) % 8.17.1.3 d, e, f
+    ).
 
 flag_domain_error(Flag, Value) :-
     % domain error via 8.17.1.3 e: Value is inappropriate for Flag
     throw(error(domain_error(flag_value, Flag + Value), set_prolog_flag/2)).
 
+%% create_prolog_flag(+Flag, +Value, +Options).
+%
+% Creates the flag Flag with the value Value, which `current_prolog_flag/2` and `set_prolog_flag/2`
+% then read and set as the flags of the system. Options is a list of:
+%
+%  * `access(Access)`: `read_write` (the default) or `read_only`.
+%  * `type(Type)`: the values the flag can take, one of `boolean`, `atom`, `integer`, `float` and
+%    `term`. By default, the type of Value.
+%  * `keep(Boolean)`: if `true`, a flag Flag that already exists is left as it is. By default, `false`.
+%
+% The flags of the system can't be created anew.
+create_prolog_flag(Flag, Value, Options) :-
+    (   (var(Flag) ; var(Value)) ->
+        throw(error(instantiation_error, create_prolog_flag/3))
+    ;   \+ atom(Flag) ->
+        throw(error(type_error(atom, Flag), create_prolog_flag/3))
+    ;   \+ acyclic_term(Value) ->
+        throw(error(domain_error(flag_value, Flag + Value), create_prolog_flag/3))
+    ;   '$skip_max_list'(_, _, Options, Tail),
+        Tail \== [] ->
+        (   var(Tail) ->
+            throw(error(instantiation_error, create_prolog_flag/3))
+        ;   throw(error(type_error(list, Options), create_prolog_flag/3))
+        )
+    ;   lists:maplist(builtins:must_be_flag_option, Options),
+        (   lists:member(access(Access), Options) -> true
+        ;   Access = read_write
+        ),
+        (   lists:member(type(Type), Options) -> true
+        ;   true
+        ),
+        (   lists:member(keep(Keep), Options) -> true
+        ;   Keep = false
+        ),
+        '$create_prolog_flag'(Flag, Value, Access, Type, Keep)
+    ).
+
+must_be_flag_option(Option) :-
+    (   var(Option) ->
+        throw(error(instantiation_error, create_prolog_flag/3))
+    ;   flag_option(Option, Arg, Values) ->
+        (   var(Arg) ->
+            throw(error(instantiation_error, create_prolog_flag/3))
+        ;   lists:member(Arg, Values) ->
+            true
+        ;   throw(error(domain_error(prolog_flag_option, Option), create_prolog_flag/3))
+        )
+    ;   throw(error(domain_error(prolog_flag_option, Option), create_prolog_flag/3))
+    ).
+
+flag_option(access(Access), Access, [read_write, read_only]).
+flag_option(type(Type), Type, [boolean, atom, integer, float, term]).
+flag_option(keep(Keep), Keep, [true, false]).
+
 % control operators.
 
 %% fail.
@@ -729,13 +748,19 @@ select_rightmost_options([], []).
 
 
 parse_read_term_options(Options, OptionValues, Stub) :-
-    DefaultOptions = [singletons-_, variables-_, variable_names-_],
+    DefaultOptions = [comments-_, singleton_positions-_, singletons-_, subterm_positions-_,
+                      term_position-_, variable_names-_, variable_positions-_, variables-_],
     parse_options_list(Options, builtins:parse_read_term_options_, DefaultOptions, OptionValues, Stub).
 
 
+parse_read_term_options_(comments(Comments), comments-Comments) :- !.
+parse_read_term_options_(singleton_positions(Vars), singleton_positions-Vars) :- !.
 parse_read_term_options_(singletons(Vars), singletons-Vars) :- !.
+parse_read_term_options_(subterm_positions(Pos), subterm_positions-Pos) :- !.
+parse_read_term_options_(term_position(Pos), term_position-Pos) :- !.
 parse_read_term_options_(variables(Vars), variables-Vars) :- !.
 parse_read_term_options_(variable_names(Vars), variable_names-Vars) :- !.
+parse_read_term_options_(variable_positions(Vars), variable_positions-Vars) :- !.
 parse_read_term_options_(E,_) :-
     throw(error(domain_error(read_option, E), _)).
 
@@ -746,9 +771,109 @@ parse_read_term_options_(E,_) :-
 %  * `variables(-Vars)` unifies Vars with a list of variables in the term. Similar to do `term_variables/2` with the new term.
 %  * `variable_names(-Vars)` unifies Vars with a list `Name=Var` with Name describing the variable name and Var the variable itself that appears in Term.
 %  * `singletons` similar to `variable_names` but only reports variables occurring only once in Term.
+%  * `subterm_positions(-Pos)` unifies Pos with the positions of Term and its subterms, as character
+%    offsets in the stream: `From-To` for atoms, numbers and variables, `string_position(From, To)`
+%    for double quoted lists, `brace_term_position(From, To, ArgPos)` for `{}/1` terms,
+%    `list_position(From, To, ElemsPos, TailPos)` for lists, where TailPos is `none` without a tail,
+%    `term_position(From, To, FFrom, FTo, ArgsPos)` for compound terms, where FFrom and FTo are the
+%    offsets of the functor, and `parentheses_term_position(From, To, ContentPos)` for terms in
+%    parentheses.
+%  * `term_position(-Pos)` unifies Pos with the position of the first token of Term as
+%    `'$stream_position'(CharNo, LineNo, LinePos, ByteNo)`.
+%  * `comments(-Comments)` unifies Comments with the list of the comments read with Term as pairs
+%    `Pos-Comment`, where Pos is the position of the comment as in `term_position` and Comment is a
+%    string.
+%  * `variable_positions(-Vars)` is like `variable_names`, but unifies Vars with a list
+%    `Name=Positions`, where Positions are the positions `From-To` of the occurrences of the
+%    variable in the order they were read.
+%  * `singleton_positions(-Vars)` is like `singletons`, but unifies Vars with a list `Name=From-To`,
+%    where `From-To` is the position of the only occurrence of the variable.
 read_term(Stream, Term, Options) :-
-    parse_read_term_options(Options, [Singletons, VariableNames, Variables], read_term/3),
-    '$read_term'(Stream, Term, Singletons, Variables, VariableNames).
+    parse_read_term_options(Options, [Comments, SingletonPositions, Singletons, SubtermPositions,
+                                      TermPosition, VariableNames, VariablePositions, Variables],
+                            read_term/3),
+    read_term_positions(Options, SubtermPositions, TermPosition, Comments, Positions),
+    '$read_term'(Stream, Term, Singletons, Variables, VariableNames, Positions),
+    (  Positions = positions(_, _, _) ->
+       variable_positions(VariableNames, Term, SubtermPositions, VariablePositions),
+       singleton_positions(Singletons, VariablePositions, SingletonPositions)
+    ;  true
+    ).
+
+% the positions are only kept if they are asked for.
+read_term_positions(Options, SubtermPositions, TermPosition, Comments, Positions) :-
+    (  lists:member(Option, Options),
+       read_term_position_option(Option) ->
+       Positions = positions(SubtermPositions, TermPosition, Comments)
+    ;  Positions = none
+    ).
+
+read_term_position_option(subterm_positions(_)).
+read_term_position_option(term_position(_)).
+read_term_position_option(comments(_)).
+read_term_position_option(variable_positions(_)).
+read_term_position_option(singleton_positions(_)).
+
+% the positions of the occurrences of each named variable of Term,
+% found by walking Term along its subterm positions.
+variable_positions(VariableNames, Term, SubtermPositions, VariablePositions) :-
+    subterm_variable_positions(SubtermPositions, Term, VarPositions, []),
+    lists:maplist(builtins:named_variable_positions(VarPositions), VariableNames, VariablePositions).
+
+named_variable_positions(VarPositions, Name=Var, Name=Positions) :-
+    occurrence_positions(VarPositions, Var, Positions0),
+    sort(Positions0, Positions).
+
+occurrence_positions([], _, []).
+occurrence_positions([Var0-Position|VarPositions], Var, Positions) :-
+    (  Var0 == Var ->
+       Positions = [Position|Positions0]
+    ;  Positions = Positions0
+    ),
+    occurrence_positions(VarPositions, Var, Positions0).
+
+subterm_variable_positions(Position, _, VarPositions, VarPositions) :-
+    var(Position),
+    !.
+subterm_variable_positions(From-To, Term, VarPositions, VarPositions0) :-
+    !,
+    (  var(Term) ->
+       VarPositions = [Term-(From-To)|VarPositions0]
+    ;  VarPositions = VarPositions0
+    ).
+subterm_variable_positions(term_position(_, _, _, _, ArgsPositions), Term, VarPositions, VarPositions0) :-
+    !,
+    Term =.. [_|Args],
+    args_variable_positions(ArgsPositions, Args, VarPositions, VarPositions0).
+subterm_variable_positions(list_position(_, _, ElemsPositions, TailPosition), Term, VarPositions, VarPositions0) :-
+    !,
+    list_variable_positions(ElemsPositions, TailPosition, Term, VarPositions, VarPositions0).
+subterm_variable_positions(brace_term_position(_, _, ArgPosition), {Arg}, VarPositions, VarPositions0) :-
+    !,
+    subterm_variable_positions(ArgPosition, Arg, VarPositions, VarPositions0).
+subterm_variable_positions(parentheses_term_position(_, _, Position), Term, VarPositions, VarPositions0) :-
+    !,
+    subterm_variable_positions(Position, Term, VarPositions, VarPositions0).
+subterm_variable_positions(_, _, VarPositions, VarPositions).
+
+args_variable_positions([], [], VarPositions, VarPositions).
+args_variable_positions([Position|Positions], [Arg|Args], VarPositions, VarPositions0) :-
+    subterm_variable_positions(Position, Arg, VarPositions, VarPositions1),
+    args_variable_positions(Positions, Args, VarPositions1, VarPositions0).
+
+list_variable_positions([], TailPosition, Tail, VarPositions, VarPositions0) :-
+    (  TailPosition == none ->
+       VarPositions = VarPositions0
+    ;  subterm_variable_positions(TailPosition, Tail, VarPositions, VarPositions0)
+    ).
+list_variable_positions([Position|Positions], TailPosition, [Elem|Elems], VarPositions, VarPositions0) :-
+    subterm_variable_positions(Position, Elem, VarPositions, VarPositions1),
+    list_variable_positions(Positions, TailPosition, Elems, VarPositions1, VarPositions0).
+
+singleton_positions([], _, []).
+singleton_positions([Name=_|Singletons], VariablePositions, [Name=Position|SingletonPositions]) :-
+    lists:memberchk(Name=[Position], VariablePositions),
+    singleton_positions(Singletons, VariablePositions, SingletonPositions).
 
 %% read_term(-Term, +Options).
 %
@@ -827,6 +952,53 @@ catch(G,C,R,Bb) :-
     '$push_ball_stack', % move ball to ball stack.
     handle_ball(Ball, C, R).
 
+:- non_counted_backtracking catch_with_backtrace/3.
+
+%% catch_with_backtrace(Goal, Catcher, Recover).
+%
+% Like `catch/3`, but if the `backtrace` flag was set when an exception `error(Formal, Context)` was
+% thrown, it is caught as `error(Formal, backtrace(Context, Frames))`, where Frames are the calls it
+% was thrown from, innermost first, each as `clause(Module:Name/Arity, ClauseNum, File, Line)` if
+% its clause was loaded from a file, else as `Module:Name/Arity`. If the flag wasn't set, errors
+% raised by built-in predicates have the single frame of the innermost call from a clause loaded
+% from a file, if any. Example:
+%
+% ```
+% ?- set_prolog_flag(backtrace, true),
+%    catch_with_backtrace(atom_length(1, _), error(_, backtrace(_, [Frame|_])), true).
+%    Frame = builtins:throw/1.
+% ```
+catch_with_backtrace(G,C,R) :-
+    '$get_current_block'(Bb),
+    catch_with_backtrace(G,C,R,Bb).
+
+:- meta_predicate catch_with_backtrace(0, ?, 0, ?).
+
+:- non_counted_backtracking catch_with_backtrace/4.
+
+catch_with_backtrace(G,C,R,Bb) :-
+    '$install_new_block'(NBb),
+    call(G),
+    end_block(Bb, NBb).
+catch_with_backtrace(G,C,R,Bb) :-
+    '$reset_block'(Bb),
+    '$get_ball'(Ball0),
+    '$ball_backtrace'(Frames),
+    '$push_ball_stack', % move ball to ball stack.
+    add_backtrace(Frames, Ball0, Ball),
+    handle_ball(Ball, C, R).
+
+:- non_counted_backtracking add_backtrace/3.
+
+add_backtrace([], Ball, Ball) :-
+    !.
+add_backtrace(Frames, Ball0, Ball) :-
+    (   nonvar(Ball0),
+        Ball0 = error(Formal, Context) ->
+        Ball = error(Formal, backtrace(Context, Frames))
+    ;   Ball = Ball0
+    ).
+
 
 :- non_counted_backtracking end_block/2.
 
@@ -1717,6 +1889,8 @@ can_be_number(N, PI) :-
 must_be_number(N, _) :-
     (  integer(N)
     ;  float(N)
+    ;  number(N),
+       \+ rational(N) % a decimal
     ),
     !.
 must_be_number(N, PI) :-
@@ -1922,6 +2096,39 @@ parse_stream_options_(eof_action(Action), eof_action-Action) :-
 parse_stream_options_(E, _) :-
     throw(error(domain_error(stream_option, E), _)). % 8.11.5.3i)
 
+
+parse_open_options(Options, OptionValues, Stub) :-
+    DefaultOptions = [alias-[], bom-default, encoding-default, eof_action-eof_code,
+                      reposition-false, type-text],
+    parse_options_list(Options, builtins:parse_open_options_, DefaultOptions, OptionValues, Stub).
+
+
+parse_open_options_(encoding(Encoding), encoding-Encoding) :-
+    !,
+    (  var(Encoding) ->
+       throw(error(instantiation_error, open/4))
+    ;  stream_encoding(Encoding) ->
+       true
+    ;  throw(error(domain_error(stream_option, encoding(Encoding)), _))
+    ).
+parse_open_options_(bom(Bool), bom-Bool) :-
+    !,
+    (  nonvar(Bool), lists:member(Bool, [true, false]), !
+    ;
+       throw(error(domain_error(stream_option, bom(Bool)), _))
+    ).
+parse_open_options_(Option, OptionPair) :-
+    parse_stream_options_(Option, OptionPair).
+
+
+stream_encoding(octet).
+stream_encoding(ascii).
+stream_encoding(iso_latin_1).
+stream_encoding(windows_1252).
+stream_encoding(utf8).
+stream_encoding(utf16be).
+stream_encoding(utf16le).
+
 %% open(+File, +Mode, +Stream).
 %
 % Equivalent to `open(File, Mode, Stream, [])`.
@@ -1944,6 +2151,11 @@ open(SourceSink, Mode, Stream) :-
 %  * `reposition(+Boolean)`: Specifies whether repositioning is required for the stream. `false` is the default.
 %  * `type(+Type)`: Type can be `text` or `binary`. Defines the type of the stream, if it's optimized for plain text
 %    or just binary
+%  * `encoding(+Encoding)`: The encoding of the characters of a text stream: `utf8` (the default),
+%    `utf16be`, `utf16le`, `iso_latin_1`, `windows_1252`, `ascii` or `octet`. Binary streams are always `octet`.
+%  * `bom(+Boolean)`: Whether a byte order mark is read from the start of the file or written to it.
+%    By default, the encoding of a file opened for reading is that of its byte order mark if it has
+%    one, and none is written.
 %
 % Example:
 %
@@ -1961,16 +2173,20 @@ open(SourceSink, Mode, Stream, StreamOptions) :-
     ;  nonvar(Stream) ->
        throw(error(uninstantiation_error(Stream), open/4)) % 8.11.5.3f)
     ;
-       parse_stream_options(StreamOptions, [Alias, EOFAction, Reposition, Type], open/4),
+       parse_open_options(StreamOptions, [Alias, BOM, Encoding, EOFAction, Reposition, Type], open/4),
        (   SourceSink = stream(S0) ->
            '$set_stream_options'(S0, Alias, EOFAction, Reposition, Type),
+           (  Encoding == default ->
+              true
+           ;  '$set_stream_encoding'(S0, Encoding)
+           ),
            Stream = S0
        ;   (
                 atom(SourceSink) ->
                 atom_chars(SourceSink, SourceSinkString)
            ;    SourceSink = SourceSinkString
            ),
-           '$open'(SourceSinkString, Mode, Stream, Alias, EOFAction, Reposition, Type)
+           '$open'(SourceSinkString, Mode, Stream, Alias, EOFAction, Reposition, Type, BOM, Encoding)
        )
     ).
 
@@ -2154,6 +2370,10 @@ check_stream_property(reposition(B), reposition, B) :-
     ( var(B) -> true ; lists:member(B, [true, false]) ).
 check_stream_property(type(T), type, T) :-
     ( var(T) -> true ; lists:member(T, [text, binary]) ).
+check_stream_property(encoding(E), encoding, E) :-
+    ( var(E) -> true ; stream_encoding(E) ).
+check_stream_property(bom(B), bom, B) :-
+    ( var(B) -> true ; lists:member(B, [true, false]) ).
 
 
 stream_iter_(S, S).
@@ -2184,6 +2404,8 @@ stream_iter(S) :-
 %  * `eof_action(-X)` where X can be `error`, `eof_code` or `reset` depending on the action that will happen on the end of the file.
 %  * `reposition(-Boolean)` specifies if reposition has been enabled for this stream.
 %  * `type(-Type)` where Type can be `text` or `binary`.
+%  * `encoding(-Encoding)` the encoding of the characters of the stream (see `open/4`).
+%  * `bom(-Boolean)` specifies if the stream has a byte order mark.
 stream_property(S, P) :-
     (  nonvar(P), \+ check_stream_property(P, _, _) ->
        throw(error(domain_error(stream_property, P), stream_property/2))
@@ -2226,6 +2448,30 @@ set_stream_position(S_or_a, Position) :-
     ;  throw(error(domain_error(stream_position, Position), set_stream_position/2))
     ).
 
+%% set_stream(+Stream, +Property).
+%
+% Changes the property Property of the stream Stream. The only property
+% that can be changed is `encoding(+Encoding)`, which sets the encoding
+% of the characters read and written from then on (see `open/4`).
+%
+% ```
+% ?- open(F, read, S), set_stream(S, encoding(iso_latin_1)).
+% ```
+set_stream(S_or_a, Property) :-
+    (  var(S_or_a) ->
+       throw(error(instantiation_error, set_stream/2))
+    ;  var(Property) ->
+       throw(error(instantiation_error, set_stream/2))
+    ;  Property = encoding(Encoding) ->
+       (  var(Encoding) ->
+          throw(error(instantiation_error, set_stream/2))
+       ;  stream_encoding(Encoding) ->
+          '$set_stream_encoding'(S_or_a, Encoding)
+       ;  throw(error(domain_error(encoding, Encoding), set_stream/2))
+       )
+    ;  throw(error(domain_error(stream_property, Property), set_stream/2))
+    ).
+
 %% callable(X).
 %
 % True iff X is bound o an atom or a compund term.
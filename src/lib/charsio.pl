@@ -221,7 +221,8 @@ read_from_chars(Chars, Term) :-
 read_term_from_chars(Chars, Term, Options) :-
     must_be(chars, Chars),
     must_be(var, Term),
-    builtins:parse_read_term_options(Options, [Singletons, VariableNames, Variables], read_term_from_chars/3),
+    builtins:parse_read_term_options(Options, [_, _, Singletons, _, _, VariableNames, _, Variables],
+                                     read_term_from_chars/3),
     '$read_term_from_chars'(Chars, Term, Singletons, Variables, VariableNames).
 
 %% write_term_to_chars(+Term, +Options, -Chars).
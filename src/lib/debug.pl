@@ -14,6 +14,28 @@
 
   Source: [https://stackoverflow.com/a/30791637](https://stackoverflow.com/a/30791637)
 
+  The library also provides a procedural debugger in the style of the
+  Byrd box model. Each call to a user predicate is seen as a box with
+  four ports: `call` when it is entered, `exit` when it succeeds,
+  `redo` when it is backtracked into and `fail` when it fails, along
+  with `exception` when an exception leaves it. `trace/0` starts the
+  debugger, which stops at each port to prompt for one of:
+
+  - `c` or RETURN (creep): go on to the next port.
+  - `s` (skip): go on to the next port of this call, without stopping
+    at the ports of the calls it makes.
+  - `l` (leap): go on to the next spy point, set by `spy/1`.
+  - `r` (retry): run this call again from its call port.
+  - `a` (abort): abort the query.
+  - `n` (nodebug): stop the debugger and go on.
+  - `h` (help): list the commands.
+
+  Ports which are not leashed with `leash/1` are printed without
+  stopping. The predicates of the libraries are not traced.
+
+  Embedders can receive the port events in place of the prompt with
+  `Machine::set_trace_hook`.
+
 */
 
 
@@ -24,10 +46,25 @@
     op(950, fy, *),
     (*)/1,
     ($)/1,
-    ($-)/1
+    ($-)/1,
+    trace/0,
+    notrace/0,
+    debug/0,
+    nodebug/0,
+    debugging/0,
+    spy/1,
+    nospy/1,
+    nospyall/0,
+    leash/1
 ]).
 
-:- use_module(library(format), [portray_clause/1]).
+:- use_module(library(charsio), [char_type/2, get_line_to_chars/3]).
+:- use_module(library(error), [domain_error/3, instantiation_error/1, must_be/2]).
+:- use_module(library(format), [format/3, portray_clause/1]).
+:- use_module(library(iso_ext), [bb_b_put/2, bb_get/2, bb_put/2]).
+:- use_module(library(lists), [maplist/2, member/2]).
+
+:- dynamic(spy_point/1).
 
 :- meta_predicate *(0).
 :- meta_predicate $(0).
@@ -54,3 +91,334 @@ $(G_0) :-
 %  Generalize away Goal.
 
 *(_).
+
+%% trace
+%
+%  Start the debugger, stopping at the ports of every call to a user
+%  predicate.
+
+trace :-
+   set_debug_mode(trace).
+
+%% notrace
+%
+%  Stop tracing. The debugger stays on to stop at the spy points, if
+%  there are any.
+
+notrace :-
+   (  spy_point(_) ->
+      set_debug_mode(debug)
+   ;  set_debug_mode(off)
+   ).
+
+%% debug
+%
+%  Start the debugger, stopping only at the spy points.
+
+debug :-
+   set_debug_mode(debug).
+
+%% nodebug
+%
+%  Stop the debugger.
+
+nodebug :-
+   set_debug_mode(off).
+
+set_debug_mode(Mode) :-
+   bb_put('$debug_mode', Mode),
+   bb_put('$debug_skip', none),
+   (  Mode == off ->
+      set_prolog_flag(debug, off)
+   ;  set_prolog_flag(debug, on)
+   ).
+
+debug_mode(Mode) :-
+   (  bb_get('$debug_mode', Mode0) ->
+      Mode = Mode0
+   ;  Mode = off
+   ).
+
+%% debugging
+%
+%  Print the state of the debugger, its spy points and its leashed
+%  ports.
+
+debugging :-
+   debug_mode(Mode),
+   format(user_error, "% Debug mode: ~a~n", [Mode]),
+   (  spy_point(Spec),
+      format(user_error, "% Spy point on ~q~n", [Spec]),
+      false
+   ;  true
+   ),
+   leashed_ports(Ports),
+   format(user_error, "% Leashed ports: ~q~n", [Ports]).
+
+%% spy(+PredicateIndicator)
+%
+%  Set a spy point on the predicates of PredicateIndicator, which is
+%  one of `Name`, `Name/Arity` or either qualified by a module, and
+%  start the debugger if it is off. The debugger stops at the ports of
+%  calls to them.
+
+spy(Spec) :-
+   must_be_spy_spec(Spec, spy/1),
+   (  spy_point(Spec) ->
+      true
+   ;  assertz(spy_point(Spec))
+   ),
+   format(user_error, "% Spy point on ~q~n", [Spec]),
+   (  debug_mode(off) ->
+      debug
+   ;  true
+   ).
+
+%% nospy(+PredicateIndicator)
+%
+%  Remove the spy point set with `spy(PredicateIndicator)`.
+
+nospy(Spec) :-
+   must_be_spy_spec(Spec, nospy/1),
+   (  retract(spy_point(Spec)) ->
+      format(user_error, "% Spy point removed from ~q~n", [Spec])
+   ;  true
+   ).
+
+%% nospyall
+%
+%  Remove all spy points.
+
+nospyall :-
+   retractall(spy_point(_)).
+
+must_be_spy_spec(Spec, Context) :-
+   (  var(Spec) ->
+      instantiation_error(Context)
+   ;  Spec = M:Spec0 ->
+      must_be(atom, M),
+      must_be_spy_spec(Spec0, Context)
+   ;  Spec = N/A ->
+      must_be(atom, N),
+      must_be(integer, A)
+   ;  must_be(atom, Spec)
+   ).
+
+spied(M:G) :-
+   functor(G, N, A),
+   (  spy_point(N)
+   ;  spy_point(N/A)
+   ;  spy_point(M:N)
+   ;  spy_point(M:N/A)
+   ),
+   !.
+
+%% leash(+Ports)
+%
+%  Stop at the ports of Ports, a list of `call`, `exit`, `redo`, `fail`
+%  and `exception`, or one of `all`, `half` (`call` and `redo`) and
+%  `none`. The other ports are printed without stopping. All ports
+%  are leashed initially.
+
+leash(Ports0) :-
+   (  var(Ports0) ->
+      instantiation_error(leash/1)
+   ;  leash_ports(Ports0, Ports) ->
+      true
+   ;  must_be(list, Ports0),
+      maplist(must_be_port, Ports0),
+      Ports = Ports0
+   ),
+   bb_put('$debug_leash', Ports).
+
+leash_ports(all, [call, exit, redo, fail, exception]).
+leash_ports(half, [call, redo]).
+leash_ports(none, []).
+
+must_be_port(Port) :-
+   must_be(atom, Port),
+   (  leash_ports(all, Ports),
+      member(Port, Ports) ->
+      true
+   ;  domain_error(port, Port, leash/1)
+   ).
+
+leashed_ports(Ports) :-
+   (  bb_get('$debug_leash', Ports0) ->
+      Ports = Ports0
+   ;  leash_ports(all, Ports)
+   ).
+
+leashed(Port) :-
+   (  Port = exception(_) ->
+      Key = exception
+   ;  Key = Port
+   ),
+   leashed_ports(Ports),
+   member(Key, Ports),
+   !.
+
+% The machine diverts each call to a user predicate made while the
+% debugger is on to '$trace_goal'/3, with the code of the predicate
+% at P, which '$invoke_traced'/3 calls into.
+
+'$trace_goal'(Goal, P, Dynamic) :-
+   (  bb_get('$debug_depth', D0) ->
+      true
+   ;  D0 = 0
+   ),
+   D is D0 + 1,
+   trace_goal(Goal, P, Dynamic, D0, D).
+
+trace_goal(Goal, P, Dynamic, D0, D) :-
+   catch(trace_box(Goal, P, Dynamic, D0, D),
+         '$debug_retry'(D),
+         trace_goal(Goal, P, Dynamic, D0, D)).
+
+trace_box(Goal, P, Dynamic, D0, D) :-
+   bb_b_put('$debug_depth', D),
+   (  port(call, Goal, D)
+   ;  port(fail, Goal, D),
+      false
+   ),
+   catch(call_traced(Goal, P, Dynamic, Det),
+         E,
+         ( port_exception(E, Goal, D), throw(E) )),
+   bb_b_put('$debug_depth', D0),
+   (  Det == true ->
+      !,
+      port(exit, Goal, D)
+   ;  (  port(exit, Goal, D)
+      ;  port(redo, Goal, D),
+         false
+      )
+   ).
+
+call_traced(Goal, P, Dynamic, Det) :-
+   '$get_b_value'(B0),
+   '$invoke_traced'(Goal, P, Dynamic),
+   '$get_b_value'(B),
+   (  B == B0 ->
+      Det = true
+   ;  Det = false
+   ).
+
+port_exception(E, Goal, D) :-
+   (  E \= '$aborted',
+      E \= '$debug_retry'(_) ->
+      port(exception(E), Goal, D)
+   ;  true
+   ).
+
+% The debugger is off while it is at a port, lest it trace the user
+% hooks its own goals run, such as goal_expansion/2.
+
+port(Port, Goal, D) :-
+   (  show_port(Goal, D) ->
+      set_prolog_flag(debug, off),
+      port_action(Port, Goal, D, Action),
+      set_prolog_flag(debug, on),
+      run_action(Action, Port, D)
+   ;  true
+   ).
+
+show_port(Goal, D) :-
+   debug_mode(Mode),
+   Mode \== off,
+   (  bb_get('$debug_skip', S),
+      integer(S) ->
+      D =< S,
+      bb_put('$debug_skip', none)
+   ;  true
+   ),
+   (  Mode == trace ->
+      true
+   ;  spied(Goal) ->
+      bb_put('$debug_mode', trace)
+   ).
+
+port_action(Port, Goal, D, Action) :-
+   (  '$trace_port_hook'(Port, D, Goal, Action) ->
+      true
+   ;  leashed(Port) ->
+      prompt_action(Port, Goal, D, Action)
+   ;  write_port(Port, Goal, D),
+      nl(user_error),
+      Action = creep
+   ).
+
+prompt_action(Port, Goal, D, Action) :-
+   write_port(Port, Goal, D),
+   write(user_error, ' ? '),
+   flush_output(user_error),
+   get_line_to_chars(user_input, Cs, []),
+   (  member(C, Cs),
+      \+ char_type(C, whitespace) ->
+      (  command(C, Command) ->
+         true
+      ;  Command = help
+      )
+   ;  Command = creep
+   ),
+   (  Command == help ->
+      debugger_help,
+      prompt_action(Port, Goal, D, Action)
+   ;  Action = Command
+   ).
+
+command(c, creep).
+command(s, skip).
+command(l, leap).
+command(r, retry).
+command(a, abort).
+command(n, nodebug).
+command(h, help).
+
+debugger_help :-
+   (  member(Line, ["c, RETURN: creep, going on to the next port",
+                    "s: skip to the next port of this call",
+                    "l: leap to the next spy point",
+                    "r: retry this call",
+                    "a: abort the query",
+                    "n: nodebug, stopping the debugger",
+                    "h: help"]),
+      format(user_error, "   ~s~n", [Line]),
+      false
+   ;  true
+   ).
+
+write_port(Port, Goal, D) :-
+   port_name(Port, Name),
+   format(user_error, "   ~a: (~d) ", [Name, D]),
+   (  Goal = user:Goal0 ->
+      true
+   ;  Goal0 = Goal
+   ),
+   write_term(user_error, Goal0, [quoted(true), max_depth(10)]),
+   (  Port = exception(E) ->
+      write(user_error, ' raised '),
+      write_term(user_error, E, [quoted(true), max_depth(10)])
+   ;  true
+   ).
+
+port_name(call, 'Call').
+port_name(exit, 'Exit').
+port_name(redo, 'Redo').
+port_name(fail, 'Fail').
+port_name(exception(_), 'Exception').
+
+run_action(creep, _, _).
+run_action(skip, Port, D) :-
+   (  ( Port == call ; Port == redo ) ->
+      bb_put('$debug_skip', D)
+   ;  true
+   ).
+run_action(leap, _, _) :-
+   bb_put('$debug_mode', debug).
+run_action(retry, _, D) :-
+   throw('$debug_retry'(D)).
+run_action(abort, _, _) :-
+   bb_put('$debug_skip', none),
+   throw('$aborted').
+run_action(nodebug, _, _) :-
+   nodebug.
@@ -74,6 +74,7 @@ Some things that are still missing:
 :- use_module(library(iso_ext)).
 :- use_module(library(lists)).
 :- use_module(library(pio)).
+:- use_module(library(threads)).
 :- use_module(library(time)).
 
 %% http_listen(+Port, +Handlers).
@@ -96,6 +97,8 @@ http_listen(Port, Module:Handlers0) :-
 % - `tls_key(+Key)` - a TLS key for HTTPS (string)
 % - `tls_cert(+Cert)` - a TLS cert for HTTPS (string)
 % - `content_length_limit(+Limit)` - maximum length (in bytes) for the incoming bodies. By default, 32KB.
+% - `workers(+N)` - number of threads handling requests in parallel. By default, 1. Clauses
+%   asserted or retracted by a handler are seen by all of the workers.
 %
 % In order to have a HTTPS server (instead of plain HTTP), both `tls_key` and `tls_cert` options must be provided.
 http_listen(Port, Module:Handlers0, Options) :-
@@ -110,17 +113,25 @@ module_qualification(M, H0, H) :-
     H =.. [Method, Path, M:Goal].
 
 http_listen_(Port, Handlers, Options) :-
-    parse_options(Options, TLSKey, TLSCert, ContentLengthLimit),
+    parse_options(Options, TLSKey, TLSCert, ContentLengthLimit, Workers),
     phrase(format_("0.0.0.0:~d", [Port]), Addr),
     '$http_listen'(Addr, HttpListener, TLSKey, TLSCert, ContentLengthLimit),!,
     format("Listening at ~s\n", [Addr]),
+    Workers1 is Workers - 1,
+    start_workers(Workers1, HttpListener, Handlers),
     http_loop(HttpListener, Handlers).
 
-parse_options(Options, TLSKey, TLSCert, ContentLengthLimit) :-
+parse_options(Options, TLSKey, TLSCert, ContentLengthLimit, Workers) :-
     member_option_default(tls_key, Options, "", TLSKey),
     member_option_default(tls_cert, Options, "", TLSCert),
     member_option_default(content_length_limit, Options, 32768, ContentLengthLimit),
-    must_be(integer, ContentLengthLimit).
+    must_be(integer, ContentLengthLimit),
+    member_option_default(workers, Options, 1, Workers),
+    must_be(integer, Workers),
+    (   Workers < 1 ->
+        domain_error(positive_integer, Workers, http_listen/3)
+    ;   true
+    ).
 
 member_option_default(Key, List, _Default, Value) :-
     X =.. [Key, Value],
@@ -130,6 +141,13 @@ member_option_default(Key, List, Default, Default) :-
     \+ member(X, List).
 	
 
+% the calling thread is the first worker.
+start_workers(0, _, _) :- !.
+start_workers(N, HttpListener, Handlers) :-
+    thread_create(http_loop(HttpListener, Handlers), _, [detached(true)]),
+    N1 is N - 1,
+    start_workers(N1, HttpListener, Handlers).
+
 http_loop(HttpListener, Handlers) :-
     '$http_accept'(HttpListener, RequestMethod, RequestPath, RequestHeaders, RequestQuery, RequestStream, ResponseHandle),
     current_time(Time),
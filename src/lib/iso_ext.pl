@@ -19,11 +19,15 @@ but they're not part of the ISO Prolog standard at the moment.
                     call_nth/2,
                     countall/2,
                     copy_term_nat/2,
-		    copy_term/3]).
+		    copy_term/3,
+                    garbage_collect/0,
+                    garbage_collect_atoms/0,
+                    statistics/2]).
 
 :- use_module(library(error), [can_be/2,
                                domain_error/3,
                                instantiation_error/1,
+                               must_be/2,
                                type_error/3]).
 
 :- use_module(library(lists), [maplist/3]).
@@ -406,3 +410,50 @@ call_residue_vars(Goal, Vars) :-
     '$get_attr_var_queue_delim'(B),
     call(Goal),
     '$get_attr_var_queue_beyond'(B, Vars).
+
+%% garbage_collect.
+%
+% Reclaims all heap cells that are no longer reachable. Collections
+% otherwise happen automatically once the heap grows past a threshold.
+garbage_collect :-
+    '$garbage_collect'.
+
+%% garbage_collect_atoms.
+%
+% Reclaims all atoms that are no longer referenced by a term, a clause
+% or any other part of the system. Atoms are not reclaimed while a file
+% is being consulted or while other machines share the atom table.
+garbage_collect_atoms :-
+    '$garbage_collect_atoms'.
+
+%% statistics(+Key, -Value).
+%
+% Value is the current value of the system statistic Key. Supported keys:
+%
+%  - `garbage_collection`: `[Count, BytesReclaimed, Milliseconds, BytesUsed]`,
+%    the number of heap collections so far, the bytes they reclaimed in
+%    total, the CPU time they took and the current size of the heap.
+%  - `globalused`: the current size of the heap in bytes.
+%  - `atom_garbage_collection`: `[Count, AtomsReclaimed, Milliseconds]`,
+%    the number of atom collections so far, the atoms they reclaimed in
+%    total and the CPU time they took.
+%  - `atoms`: the number of atoms created at runtime that are currently
+%    in the atom table.
+statistics(Key, Value) :-
+    must_be(atom, Key),
+    (  statistics_(Key, Value0) ->
+       Value = Value0
+    ;  domain_error(statistics_key, Key, statistics/2)
+    ).
+
+statistics_(garbage_collection, [Count, Reclaimed, Ms, Used]) :-
+    '$gc_statistics'(Count, ReclaimedCells, Ms, HeapCells),
+    Reclaimed is ReclaimedCells * 8,
+    Used is HeapCells * 8.
+statistics_(globalused, Used) :-
+    '$gc_statistics'(_, _, _, HeapCells),
+    Used is HeapCells * 8.
+statistics_(atom_garbage_collection, [Count, Reclaimed, Ms]) :-
+    '$atom_gc_statistics'(Count, Reclaimed, Ms, _).
+statistics_(atoms, Atoms) :-
+    '$atom_gc_statistics'(_, _, _, Atoms).
@@ -14,8 +14,9 @@
 
 /** Predicates for reasoning about the operating system (OS) environment.
 
-This includes predicates about environment variables, calls to shell and
-finding out the PID of the running system.
+This includes predicates about environment variables, calls to shell,
+running other programs with their standard streams piped to and from
+Prolog, and finding out the PID of the running system.
 */
 
 :- module(os, [getenv/2,
@@ -23,6 +24,10 @@ finding out the PID of the running system.
                unsetenv/1,
                shell/1,
                shell/2,
+               process_create/3,
+               process_wait/2,
+               process_kill/1,
+               process_kill/2,
                pid/1,
 	       raw_argv/1,
 	       argv/1]).
@@ -83,6 +88,125 @@ shell(Command, Status) :-
     can_be(integer, Status),
     '$shell'(Command, Status).
 
+%% process_create(+Exe, +Args, +Options).
+%
+% Runs the program Exe with the arguments Args, a list of strings,
+% without going through a shell, so arguments need no quoting. Exe is
+% looked up in `PATH` unless it contains a path separator. Options are:
+%
+%  * `stdin(Spec)`, `stdout(Spec)` and `stderr(Spec)`: What the standard
+%    streams of the process are connected to. Spec is `std` (the default)
+%    to share those of Scryer, `null` to discard them, or `pipe(S)` to
+%    unify S with a new stream to or from the process. Close the stream of
+%    `stdin(pipe(S))` to signal the end of the input to the process.
+%  * `env(Pairs)`: Run the process with only the environment variables
+%    `Name=Value` in Pairs.
+%  * `environment(Pairs)`: Run the process with the environment of Scryer
+%    extended by the variables in Pairs.
+%  * `cwd(Dir)`: Run the process in the directory Dir.
+%  * `process(PID)`: Unify PID with the process id, to wait for the process
+%    with `process_wait/2` or stop it with `process_kill/2`.
+%
+% An `existence_error` is raised if Exe or Dir does not exist, and a
+% `system_error(Message)` if the process cannot be started otherwise.
+%
+% Example:
+%
+% ```
+% ?- process_create("ls", ["-a", "/"], [stdout(pipe(S)), process(PID)]),
+%    get_n_chars(S, 3, Cs), close(S), process_wait(PID, Status).
+%    S = '$stream'(0x600000e3c000), PID = 20137, Cs = ".\n.", Status = exit(0).
+% ```
+process_create(Exe, Args, Options) :-
+    must_be_chars(Exe),
+    must_be(list, Args),
+    maplist(must_be_chars, Args),
+    must_be(list, Options),
+    maplist(must_be_process_option, Options),
+    process_option(stdin(Stdin), Options, std),
+    process_option(stdout(Stdout), Options, std),
+    process_option(stderr(Stderr), Options, std),
+    (   memberchk(env(Pairs), Options) ->
+        Env = env(Pairs)
+    ;   process_option(environment(Pairs), Options, []),
+        Env = environment(Pairs)
+    ),
+    process_option(cwd(Cwd), Options, []),
+    process_option(process(PID), Options, _),
+    '$process_create'(Exe, Args, Env, Cwd, Stdin, Stdout, Stderr, PID).
+
+process_option(Option, Options, Default) :-
+    (   memberchk(Option, Options) ->
+        true
+    ;   arg(1, Option, Default)
+    ).
+
+must_be_process_option(Option) :-
+    (   var(Option) -> instantiation_error(process_create/3)
+    ;   process_option_(Option) -> true
+    ;   domain_error(process_create_option, Option, process_create/3)
+    ).
+
+process_option_(stdin(Spec)) :- must_be_stdio_spec(Spec).
+process_option_(stdout(Spec)) :- must_be_stdio_spec(Spec).
+process_option_(stderr(Spec)) :- must_be_stdio_spec(Spec).
+process_option_(env(Pairs)) :- must_be_env_pairs(Pairs).
+process_option_(environment(Pairs)) :- must_be_env_pairs(Pairs).
+process_option_(cwd(Dir)) :- must_be_chars(Dir).
+process_option_(process(PID)) :- can_be(integer, PID).
+
+must_be_stdio_spec(Spec) :-
+    (   var(Spec) -> instantiation_error(process_create/3)
+    ;   Spec == std -> true
+    ;   Spec == null -> true
+    ;   Spec = pipe(S) -> must_be(var, S)
+    ;   domain_error(process_stream, Spec, process_create/3)
+    ).
+
+must_be_env_pairs(Pairs) :-
+    must_be(list, Pairs),
+    maplist(must_be_env_pair, Pairs).
+
+must_be_env_pair(Pair) :-
+    (   var(Pair) -> instantiation_error(process_create/3)
+    ;   Pair = (Name=Value) ->
+        must_be_env_var(Name),
+        must_be_chars(Value)
+    ;   type_error(pair, Pair, process_create/3)
+    ).
+
+%% process_wait(+PID, -Status).
+%
+% Waits for the process PID started by `process_create/3` to terminate.
+% Status is `exit(Code)` if the process exited with the exit code Code,
+% and `killed(Signal)` if it was terminated by the signal Signal.
+process_wait(PID, Status) :-
+    must_be(integer, PID),
+    '$process_wait'(PID, Status).
+
+%% process_kill(+PID).
+%
+% Equivalent to `process_kill(PID, term)`.
+process_kill(PID) :- process_kill(PID, term).
+
+%% process_kill(+PID, +Signal).
+%
+% Sends the signal Signal to the process PID started by `process_create/3`.
+% Signal is a signal number between 0 and 2147483647 or one of `hup`,
+% `int`, `quit`, `kill`, `term`, `usr1`, `usr2`, `stop` and `cont`.
+% Systems without signals can only kill the process, whatever the signal.
+process_kill(PID, Signal) :-
+    must_be(integer, PID),
+    (   var(Signal) -> instantiation_error(process_kill/2)
+    ;   integer(Signal) ->
+        (   Signal >= 0, Signal =< 2147483647 -> true
+        ;   domain_error(signal, Signal, process_kill/2)
+        )
+    ;   memberchk(Signal, [hup, int, quit, kill, term, usr1, usr2, stop, cont]) -> true
+    ;   domain_error(signal, Signal, process_kill/2)
+    ),
+    '$process_kill'(PID, Signal).
+
 %% pid(-PID).
 %
 % True iff PID is the process identification number of current Scryer Prolog instance.
@@ -0,0 +1,207 @@
+/** Execution profiles of goals.
+
+`profile/1` runs a goal as `once/1` does while counting the calls to
+each predicate, and then prints the predicates in which the most time
+was spent:
+
+```
+?- profile(nrev(Xs, Ys)).
+```
+
+For each predicate, the profile gives:
+
+- its calls.
+- the inferences made by its clauses.
+- the choice points created by its clauses.
+- the share of the time spent in its clauses, or _self_ time.
+- the share of the time spent in it and in the predicates it calls,
+  or _total_ time.
+
+The times are sampled: the call stack is looked at when a call is made
+if some time passed since it was last looked at, and that time is
+given to it. The last call of a clause replaces the clause on the call
+stack, so its time is not in the total time of the clause's
+predicate. The sampled call stacks can be written in the folded
+format read by flame graph tools such as `flamegraph.pl` and
+`inferno-flamegraph`, with one line for each stack of the form
+`Module:Name/Arity;...;Module:Name/Arity Microseconds`, outermost
+predicate first.
+*/
+
+:- module(profile, [profile/1, profile/2, profile_data/1]).
+
+:- use_module(library(error)).
+:- use_module(library(format)).
+:- use_module(library(iso_ext), [setup_call_cleanup/3]).
+:- use_module(library(lists)).
+
+:- meta_predicate(profile(0)).
+:- meta_predicate(profile(0, +)).
+
+%% profile(:Goal).
+%
+% Equivalent to `profile(Goal, [])`.
+
+profile(Goal) :-
+    profile(Goal, []).
+
+%% profile(:Goal, +Options).
+%
+% Runs Goal as `once/1` does, and prints the profile of the run.
+% Options is a list of:
+%
+% - `sort(Key)` - the predicates are printed by decreasing Key, one of
+%   `calls`, `inferences`, `choice_points`, `self` and `total`. By
+%   default, `self`.
+% - `top(N)` - only the first N predicates are printed. By default, 20.
+%   Nothing is printed if N is 0.
+% - `folded(File)` - the sampled call stacks are written to File in the
+%   folded format of flame graphs.
+%
+% The profile is printed if Goal fails or raises an exception, too.
+
+profile(Goal, Options) :-
+    must_be(list, Options),
+    maplist(must_be_option, Options),
+    (   member(sort(Key), Options) -> true
+    ;   Key = self
+    ),
+    (   member(top(N), Options) -> true
+    ;   N = 20
+    ),
+    catch(profile_goal(Goal, Result),
+          E,
+          profile_exception(E, Result)),
+    (   member(folded(File), Options) ->
+        profile_stacks(Stacks),
+        write_folded(File, Stacks)
+    ;   true
+    ),
+    profile_data(Data),
+    print_profile(Data, Key, N),
+    profile_result(Result).
+
+must_be_option(Option) :-
+    (   var(Option) ->
+        instantiation_error(profile/2)
+    ;   Option = sort(Key) ->
+        must_be(atom, Key),
+        (   sort_key(Key, _, _) -> true
+        ;   domain_error(profile_sort_key, Key, profile/2)
+        )
+    ;   Option = top(N) ->
+        must_be(integer, N),
+        (   N >= 0 -> true
+        ;   domain_error(not_less_than_zero, N, profile/2)
+        )
+    ;   Option = folded(File) ->
+        (   atom(File) -> true
+        ;   must_be(chars, File)
+        )
+    ;   domain_error(profile_option, Option, profile/2)
+    ).
+
+profile_goal(Goal, Result) :-
+    '$start_profiler',
+    (   call(Goal) ->
+        '$stop_profiler',
+        Result = true
+    ;   '$stop_profiler',
+        Result = false
+    ).
+
+profile_exception(E, exception(E)) :-
+    '$stop_profiler'.
+
+profile_result(true).
+profile_result(false) :-
+    false.
+profile_result(exception(E)) :-
+    throw(E).
+
+%% profile_data(-Data).
+%
+% Data is the profile of the last run of `profile/2`, a list of pairs
+% `Module:Name/Arity-profile(Calls, Inferences, ChoicePoints, Self,
+% Total)` with the self and total times in seconds.
+
+profile_data(Data) :-
+    '$profile_data'(Data0, _),
+    exclude_profiler(Data0, Data).
+
+% the profiler's own predicates call the goal profiled.
+exclude_profiler([], []).
+exclude_profiler([X|Xs0], Xs) :-
+    (   (   X = profile:_
+        ;   X = (profile:_)-_
+        ) ->
+        Xs = Xs1
+    ;   Xs = [X|Xs1]
+    ),
+    exclude_profiler(Xs0, Xs1).
+
+profile_stacks(Stacks) :-
+    '$profile_data'(_, Stacks0),
+    exclude_profiler_frames(Stacks0, Stacks).
+
+exclude_profiler_frames([], []).
+exclude_profiler_frames([Frames0-Time|Stacks0], Stacks) :-
+    exclude_profiler(Frames0, Frames),
+    (   Frames == [] ->
+        Stacks = Stacks1
+    ;   Stacks = [Frames-Time|Stacks1]
+    ),
+    exclude_profiler_frames(Stacks0, Stacks1).
+
+write_folded(File, Stacks) :-
+    setup_call_cleanup(open(File, write, Stream),
+                       maplist(write_folded_stack(Stream), Stacks),
+                       close(Stream)).
+
+write_folded_stack(Stream, [PI|PIs]-Time) :-
+    format(Stream, "~q", [PI]),
+    maplist(write_folded_frame(Stream), PIs),
+    format(Stream, " ~d~n", [Time]).
+
+write_folded_frame(Stream, PI) :-
+    format(Stream, ";~q", [PI]).
+
+sort_key(calls, Calls, profile(Calls, _, _, _, _)).
+sort_key(inferences, Inferences, profile(_, Inferences, _, _, _)).
+sort_key(choice_points, ChoicePoints, profile(_, _, ChoicePoints, _, _)).
+sort_key(self, Self, profile(_, _, _, Self, _)).
+sort_key(total, Total, profile(_, _, _, _, Total)).
+
+print_profile(Data, Key, N) :-
+    foldl(add_self_time, Data, 0, Time),
+    maplist(sort_pair(Key), Data, Pairs0),
+    keysort(Pairs0, Pairs),
+    (   length(Top, N),
+        append(Top, _, Pairs) ->
+        true
+    ;   Top = Pairs
+    ),
+    (   Top == [] ->
+        true
+    ;   format("~w~t~40|~t~w~50|~t~w~62|~t~w~77|~t~w~85|~t~w~93|~n",
+               ['Predicate', 'Calls', 'Inferences', 'Choice points', 'Self', 'Total']),
+        maplist(print_predicate(Time), Top)
+    ).
+
+add_self_time(_-profile(_, _, _, Self, _), Time0, Time) :-
+    Time is Time0 + Self.
+
+% predicates with equal keys are sorted by their indicators.
+sort_pair(Key, PI-Profile, (Order-PI)-(PI-Profile)) :-
+    sort_key(Key, Value, Profile),
+    Order is -Value.
+
+print_predicate(Time, _-(PI-profile(Calls, Inferences, ChoicePoints, Self, Total))) :-
+    (   Time > 0 ->
+        SelfShare is 100 * Self / Time,
+        TotalShare is 100 * Total / Time
+    ;   SelfShare = 0.0,
+        TotalShare = 0.0
+    ),
+    format("~q~t~40|~t~d~50|~t~d~62|~t~d~77|~t~1f%~85|~t~1f%~93|~n",
+           [PI, Calls, Inferences, ChoicePoints, SelfShare, TotalShare]).
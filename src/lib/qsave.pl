@@ -0,0 +1,93 @@
+/** Saved states and stand-alone executables.
+
+A saved state is a snapshot of the compiled program of the running
+machine: its code, modules, operators and flags, including its dynamic
+clauses. scryer-prolog starts from a saved state without consulting
+any source:
+
+```
+$ scryer-prolog -x app.state
+```
+
+A stand-alone executable is a copy of the scryer-prolog executable
+that starts from the saved state appended to it. Its program reads its
+command-line arguments with `raw_argv/1`, or with `argv/1` if they
+follow `--`. Global variables and open streams aren't saved.
+*/
+
+:- module(qsave, [qsave_program/1, qsave_program/2]).
+
+:- use_module(library(error)).
+:- use_module(library(iso_ext)).
+:- use_module(library(lists)).
+
+:- meta_predicate(qsave_program(+, :)).
+
+%% qsave_program(+File).
+%
+% Equivalent to `qsave_program(File, [])`.
+
+qsave_program(File) :-
+    qsave_program(File, []).
+
+%% qsave_program(+File, :Options).
+%
+% Saves the state of the program to File, an atom or a list of
+% characters. Options is a list of:
+%
+% - `goal(:Goal)` - Goal is run once when the state starts. By
+%   default, `true`.
+% - `toplevel(:Toplevel)` - what the state does once Goal succeeds:
+%   `prolog` to load the files and run the goals of its command-line
+%   arguments and start the interactive toplevel, `halt` to exit, or
+%   a goal to run before exiting. By default, `prolog`.
+% - `stand_alone(+Bool)` - if `true`, File is a stand-alone
+%   executable. By default, `false`.
+%
+% A state exits with status 1 if Goal or Toplevel fails or raises an
+% exception.
+
+qsave_program(File0, Options0) :-
+    loader:strip_subst_module(Options0, user, M, Options),
+    must_be(list, Options),
+    (   atom(File0) ->
+        File = File0
+    ;   must_be(chars, File0),
+        atom_chars(File, File0)
+    ),
+    (   member(goal(Goal0), Options) ->
+        must_be_callable(Goal0),
+        Goal = M:Goal0
+    ;   Goal = true
+    ),
+    (   member(toplevel(Toplevel0), Options) ->
+        must_be_callable(Toplevel0),
+        state_toplevel(Toplevel0, M, Toplevel)
+    ;   Toplevel = prolog
+    ),
+    (   member(stand_alone(StandAlone), Options) ->
+        must_be(boolean, StandAlone)
+    ;   StandAlone = false
+    ),
+    setup_call_cleanup(state_init(Goal, Toplevel),
+                       save_state(File, StandAlone),
+                       retractall('$toplevel':'$state_init'(_, _))).
+
+must_be_callable(Goal) :-
+    (   var(Goal) ->
+        instantiation_error(qsave_program/2)
+    ;   callable(Goal) ->
+        true
+    ;   type_error(callable, Goal, qsave_program/2)
+    ).
+
+state_toplevel(prolog, _, prolog) :- !.
+state_toplevel(halt, _, halt) :- !.
+state_toplevel(Goal, M, M:Goal).
+
+save_state(File, StandAlone) :-
+    '$save_state'(File, StandAlone).
+
+state_init(Goal, Toplevel) :-
+    retractall('$toplevel':'$state_init'(_, _)),
+    assertz('$toplevel':'$state_init'(Goal, Toplevel)).
@@ -22,230 +22,349 @@
     strongest arguments for keeping to the pure core of Prolog as far
     as possible.
 
-    Scryer Prolog implements tabling as described by Desouter et al. in [*Tabling as a Library with Delimited Control*](https://www.ijcai.org/Proceedings/16/Papers/619.pdf).
+    A tabled call is answered from the table of its variant, which is
+    created and evaluated to a fixpoint on the first such call. The
+    tables and their answers are kept by the engine, which completes
+    the tables that call each other together.
+
+    Instead of keeping every answer, a table can keep one answer for
+    each instance of the arguments that aren't moded, aggregating the
+    moded argument over its answers. The mode of the argument is given
+    in place of the argument in the `(table)/1` directive:
+
+```
+:- table path(_,_,min).
+```
+
+    The modes are `min` and `max` (in the standard order of terms),
+    `first`, `last`, `lattice(PI)`, keeping the join of the answers by
+    `PI`, a predicate of arity 3, and `po(PI)`, keeping an answer
+    replacing the previous one if `PI`, a predicate of arity 2, holds
+    for the previous and the new answer.
+
+    `tnot/1` is the negation of a tabled goal under the well-founded
+    semantics. The answers depending on a negative loop are neither
+    true nor false but undefined, and are reported with the delayed
+    goals they depend on. `call_delays/2` and `call_residual_program/2`
+    give these goals and the program they are undefined in.
+
+    The tables of a predicate declared with `:- table p/1 as
+    incremental` are removed when a dynamic predicate declared with
+    `:- dynamic q/1 as incremental` that they depend on changes, so
+    that they are evaluated anew when next called.
 */
 
 :- module(tabling,
-	  [ start_tabling/2,		% +Wrapper, :Worker.
+	  [ start_tabling/2,		% +Wrapper, :Worker
+	    start_tabling/3,		% +Wrapper, :Worker, +Options
+
+	    tnot/1,
+	    undefined/0,
+	    call_delays/2,
+	    call_residual_program/2,
 
 	    abolish_all_tables/0,
 
-%	    (table)/1,			% +PI ...
-	    op(1150, fx, table)
+	    op(1150, fx, table),
+	    op(1150, fx, dynamic),
+	    op(1100, xfx, as)
 	  ]).
 
-:- use_module(library(tabling/double_linked_list)).
-:- use_module(library(tabling/table_data_structure)).
-:- use_module(library(tabling/batched_worklist)).
-:- use_module(library(tabling/global_worklist)).
-:- use_module(library(tabling/table_link_manager)).
 :- use_module(library(tabling/wrapper)).
 
-:- use_module(library(cont)).
-:- use_module(library(lists)).
-%:- use_module(library(debug)).
+:- use_module(library(atts)).
+:- use_module(library(between)).
+:- use_module(library(dcgs)).
+:- use_module(library(error)).
 :- use_module(library(iso_ext)).
+:- use_module(library(lists)).
+:- use_module(library(pairs)).
 
-%% :- meta_predicate
-%% 	start_tabling(+, 0).
+:- attribute delay/1.
 
-%%	user:exception(+Exception, +Var, -Action)
+%%	abolish_all_tables
 %
-%	Realises lazy initialization of table variables.
-
-%% user:exception(undefined_global_variable, Var, retry) :-
-%%   (   table_gvar(Var)
-%%   ->  true
-%%   ;   format('Creating global var ~q~n', [Var]),
-%%       nb_setval(Var, [])
-%%   ).
-/*
-table_gvar(trie_table_link) :-
-  table_datastructure_initialize.
-table_gvar(newly_created_table_identifiers) :-
-  table_datastructure_initialize.
-table_gvar(table_global_worklist) :-
-  bb_put(table_global_worklist, []).
-table_gvar(table_leader) :-
-  bb_put(table_leader, []).
-*/
+%	Remove all complete tables.
 
-%%	abolish_all_tables
+abolish_all_tables :-
+    '$tbl_abolish_all'.
+
+
+:- meta_predicate start_tabling(?, :).
+:- meta_predicate start_tabling(?, :, ?).
+
+%%	start_tabling(+Wrapper, :Worker)
+%
+%	Equivalent to `start_tabling(Wrapper, Worker, table_options([], false))`.
+
+start_tabling(Wrapper, Worker) :-
+    start_tabling(Wrapper, Worker, table_options([], false)).
+
+%%	start_tabling(+Wrapper, :Worker, +Options)
+%
+%	Answers the tabled call Wrapper, a Module:Head term, from its
+%	table, evaluating it by Worker first if needed. Options is
+%	`table_options(Moded, Incremental)`, where Moded lists the moded
+%	argument of Head as `N-Mode`.
+
+start_tabling(Wrapper, Worker, Options) :-
+    evaluated_table(Wrapper, Worker, Options, T),
+    Wrapper = _:Head,
+    table_answer(T, Head).
+
+evaluated_table(Wrapper, Worker, table_options(Moded, Incremental), T) :-
+    table_variant(Moded, Wrapper, Worker, Variant, VariantWorker),
+    pairs_keys(Moded, Positions),
+    '$tbl_variant_table'(Variant, Positions, Incremental, T),
+    Variant = _:Head,
+    evaluate_table(T, Head, VariantWorker, Moded).
+
+% the moded argument of a call is left out of the variant whose table
+% answers it.
+table_variant([], Wrapper, Worker, Wrapper, Worker).
+table_variant([N-_], M:Head, WM:WorkerHead, M:VHead, WM:VWorkerHead) :-
+    Head =.. [Name|Args],
+    functor(WorkerHead, WorkerName, _),
+    N0 is N - 1,
+    length(Prefix, N0),
+    append(Prefix, [_|Suffix], Args),
+    append(Prefix, [_|Suffix], VArgs),
+    VHead =.. [Name|VArgs],
+    VWorkerHead =.. [WorkerName|VArgs].
+
+evaluate_table(T, Head, Worker, Moded) :-
+    '$tbl_enter'(T, Action),
+    (  Action == evaluate ->
+       catch(complete_table(T, Head, Worker, Moded),
+             E,
+             abandon_table(T, E))
+    ;  true
+    ).
+
+abandon_table(T, E) :-
+    '$tbl_abandon'(T),
+    throw(E).
+
+complete_table(T, Head, Worker, Moded) :-
+    '$tbl_begin_pass'(T),
+    (  call_residue_vars(Worker, Vars),
+       delays(Vars, Delays),
+       add_answer(Moded, T, Head, Delays),
+       false
+    ;  '$tbl_end_pass'(T, Action)
+    ),
+    (  Action == again ->
+       complete_table(T, Head, Worker, Moded)
+    ;  true
+    ).
+
+add_answer([], T, Head, Delays) :-
+    '$tbl_add_answer'(T, Head, Delays).
+add_answer([N-Mode], T, Head, Delays) :-
+    (  '$tbl_moded_answer'(T, Head, OldHead) ->
+       arg(N, OldHead, Old),
+       arg(N, Head, New),
+       join(Mode, Old, New, Joined),
+       Joined \== Old,
+       Head =.. [Name|Args],
+       N0 is N - 1,
+       length(Prefix, N0),
+       append(Prefix, [_|Suffix], Args),
+       append(Prefix, [Joined|Suffix], JoinedArgs),
+       Answer =.. [Name|JoinedArgs]
+    ;  Answer = Head
+    ),
+    '$tbl_add_answer'(T, Answer, Delays).
+
+join(min, Old, New, Joined) :-
+    (  New @< Old ->
+       Joined = New
+    ;  Joined = Old
+    ).
+join(max, Old, New, Joined) :-
+    (  New @> Old ->
+       Joined = New
+    ;  Joined = Old
+    ).
+join(first, Old, _, Old).
+join(last, _, New, New).
+join(lattice(PI), Old, New, Joined) :-
+    call(PI, Old, New, Joined).
+join(po(PI), Old, New, Joined) :-
+    (  call(PI, Old, New) ->
+       Joined = New
+    ;  Joined = Old
+    ).
+
+table_answer(T, Head) :-
+    '$tbl_answer_count'(T, N),
+    N0 is N - 1,
+    between(0, N0, I),
+    '$tbl_answer'(T, I, Head, Conditional),
+    (  Conditional == true ->
+       delay(answer(T, I))
+    ;  true
+    ).
+
+
+%%	tnot(:Goal)
 %
-%	Remove all tables.  Should not be called when tabling is in
-%	progress.
+%	True if the tabled Goal has no answer, under the well-founded
+%	semantics. If Goal is neither known to have an answer nor known
+%	to have none, as in a negative loop, tnot/1 succeeds with
+%	`tnot(Goal)` delayed, which is reported along with the answer.
+
+:- meta_predicate tnot(0).
+
+tnot(Goal0) :-
+    loader:strip_module(Goal0, M0, Goal),
+    (  var(M0) ->
+       M = user
+    ;  M = M0
+    ),
+    (  var(Goal) ->
+       instantiation_error(tnot/1)
+    ;  tabled_worker(Goal, M, TM, Worker, Options) ->
+       evaluated_table(TM:Goal, TM:Worker, Options, T),
+       '$tbl_negation'(T, Result),
+       (  Result == true ->
+          true
+       ;  Result == delay ->
+          delay(tnot(T))
+       )
+    ;  functor(Goal, Name, Arity),
+       throw(error(permission_error(tnot, non_tabled_procedure, M:Name/Arity), tnot/1))
+    ).
+
+tabled_worker(Goal, M, M, Worker, Options) :-
+    table_wrapper:tabled(Goal, M, Worker, Options),
+    !.
+tabled_worker(Goal, _, TM, Worker, Options) :-
+    table_wrapper:tabled(Goal, TM, Worker, Options),
+    !.
+
+
+%%	undefined
 %
-%	@bug	Check whether tabling is in progress
+%	Neither true nor false under the well-founded semantics.
 
+:- multifile(table_wrapper:tabled/4).
 
-abolish_all_tables :-
-  bb_put(trie_table_link, []),
-  bb_put(newly_created_table_identifiers, []),
-  bb_put(table_global_worklist,[]),
-  bb_put(table_leader, []).
+undefined :-
+    start_tabling(tabling:undefined, 'undefined tabled', table_options([], false)).
+
+'undefined tabled' :-
+    tnot(undefined).
+
+table_wrapper:tabled(undefined, tabling, 'undefined tabled', table_options([], false)).
 
 
-% Find table and status for the given call variant.
+%%	call_delays(:Goal, -Delays)
 %
-table_and_status_for_variant(V,T,S) :-
-  % Order of the two calls really important: first create, then get status
-  table_for_variant(V,T),
-  tbd_table_status(T,S).
+%	True if Goal is true on the condition of the conjunction Delays of
+%	delayed goals, which is `true` if the answer of Goal isn't
+%	undefined.
 
+:- meta_predicate call_delays(0, ?).
 
-:- meta_predicate start_tabling(?, :).
+call_delays(Goal, Delays) :-
+    call_residue_vars(Goal, Vars),
+    delays(Vars, Delays0),
+    maplist(delay_goal, Delays0, Goals),
+    list_conjunction(Goals, Delays).
+
+%%	call_residual_program(:Goal, -Program)
+%
+%	True if Goal is true on the condition of the delayed goals of its
+%	answer, where Program is the list of clauses `Answer :- Body`
+%	defining the undefined answers these depend on.
+
+:- meta_predicate call_residual_program(0, ?).
+
+call_residual_program(Goal, Program) :-
+    call_residue_vars(Goal, Vars),
+    delays(Vars, Delays),
+    residual_program(Delays, [], Program).
+
+residual_program([], _, []).
+residual_program([Delay|Delays], Ts, Program) :-
+    delay_table(Delay, T),
+    (  memberchk(T, Ts) ->
+       residual_program(Delays, Ts, Program)
+    ;  findall(Clause-Conditions, residual_clause(T, Clause, Conditions), Pairs),
+       pairs_keys_values(Pairs, Clauses, Conditionss),
+       append([Delays|Conditionss], Delays1),
+       append(Clauses, Program0, Program),
+       residual_program(Delays1, [T|Ts], Program0)
+    ).
+
+delay_table(tnot(T), T).
+delay_table(answer(T, _), T).
+
+residual_clause(T, (Answer :- Body), Delays) :-
+    '$tbl_answer_count'(T, N),
+    N0 is N - 1,
+    between(0, N0, I),
+    '$tbl_answer_conditions'(T, I, Conditions),
+    Conditions = [_|_],
+    '$tbl_answer'(T, I, Answer, _),
+    append(Conditions, Delays),
+    conditions_goal(Conditions, Body).
+
+conditions_goal([Delays|Conditions], Goal) :-
+    maplist(delay_goal, Delays, Goals),
+    list_conjunction(Goals, Goal0),
+    (  Conditions == [] ->
+       Goal = Goal0
+    ;  Goal = (Goal0 ; Goal1),
+       conditions_goal(Conditions, Goal1)
+    ).
+
+delay_goal(tnot(T), tnot(Goal)) :-
+    '$tbl_variant'(T, _:Goal).
+delay_goal(answer(T, I), Answer) :-
+    '$tbl_answer'(T, I, Answer, _).
+
+list_conjunction([], true).
+list_conjunction([Goal|Goals], Conjunction) :-
+    list_conjunction_(Goals, Goal, Conjunction).
+
+list_conjunction_([], Goal, Goal).
+list_conjunction_([Goal1|Goals], Goal0, (Goal0, Conjunction)) :-
+    list_conjunction_(Goals, Goal1, Conjunction).
+
+
+% a delayed goal is kept in an attribute of a fresh variable, and so
+% is collected by call_residue_vars/2 from the goal it was delayed in.
+delay(Delay) :-
+    put_atts(_, delay(Delay)).
+
+delays([], []).
+delays([Var|Vars], Delays0) :-
+    (  var(Var),
+       get_atts(Var, delay(Delay)) ->
+       Delays0 = [Delay|Delays]
+    ;  Delays0 = Delays
+    ),
+    delays(Vars, Delays).
+
+verify_attributes(_, _, []).
+
+attribute_goals(Var) -->
+    { get_atts(Var, delay(Delay)),
+      put_atts(Var, -delay(_))
+    },
+    delay_goals(Delay).
+
+% a delayed answer is reported by the conditions it is undefined on.
+delay_goals(tnot(T)) -->
+    { delay_goal(tnot(T), Goal) },
+    [Goal].
+delay_goals(answer(T, I)) -->
+    (  { '$tbl_answer_conditions'(T, I, Conditions),
+         Conditions = [_|_] } ->
+       { conditions_goal(Conditions, Goal) },
+       [Goal]
+    ;  []
+    ).
 
-start_tabling(Wrapper,Worker) :-
-  put_new_trie_table_link,
-  put_new_global_worklist,
-  put_new_table_identifiers,
-  table_and_status_for_variant(Wrapper,T,S),
-  ( S == complete ->
-    get_answer(T,Wrapper)
-  ;
-    ( exists_scheduling_component ->
-      run_leader(Wrapper,Worker,T),
-      % Now answer the original query!
-      get_answer(T,Wrapper)
-    ;
-      run_follower(S,Wrapper,Worker,T)
-    )
-  ).
-
-run_follower(fresh,Wrapper,Worker,T) :-
-  activate(Wrapper,Worker,T),
-  shift(call_info(Wrapper,T)).
-
-run_follower(active,Wrapper,_Worker,T) :-
-  shift(call_info(Wrapper,T)).
-
-run_leader(Wrapper,Worker,T) :-
-  create_scheduling_component,
-  activate(Wrapper,Worker,T),
-  completion,
-  unset_scheduling_component.
-
-exists_scheduling_component :-
-  bb_get(table_leader, Leader),
-  Leader == [].
-
-create_scheduling_component :-
-  bb_b_put(table_leader, leaderCreated).
-
-unset_scheduling_component :-
-  bb_put(table_leader, []).
-
-set_all_complete :-
-  get_newly_created_table_identifiers(Ts, _),
-  set_all_complete_(Ts).
-
-set_all_complete_([]).
-set_all_complete_([T|Ts]) :-
-  set_complete_status(T),
-  set_all_complete_(Ts).
-
-cleanup_all_complete :-
-  get_newly_created_table_identifiers(Ts,_),
-  cleanup_all_complete_(Ts).
-
-cleanup_all_complete_([]).
-cleanup_all_complete_([T|Ts]) :-
-  cleanup_after_complete(T),
-  cleanup_all_complete_(Ts).
-
-activate(Wrapper,Worker,T) :-
-    set_active_status(T),
-  (
-    delim(Wrapper,Worker,T),
-    fail
-  ;
-    true
-  ).
-
-delim(Wrapper,Worker,Table) :-
-%   debug(tabling, 'ACT: ~p on ~p', [Wrapper, Table]),
-    catch(reset(Worker,SourceCall,Continuation),
-          _,
-          fail),
-   ( Continuation = none ->
-     (	 add_answer(Table,Wrapper)
-     ->	 true %debug(tabling, 'ADD: ~p', [Wrapper])
-     ;	 %debug(tabling, 'DUP: ~p', [Wrapper]),
-	 fail
-     )
-   ;
-     Continuation = cont(Cont),
-     SourceCall = call_info(_,SourceTable),
-     TargetCall = call_info(Wrapper,Table),
-     Dependency = dependency(SourceCall,Cont,TargetCall),
-     %debug(tabling, 'DEP: ~p: ~p', [SourceTable,Dependency]),
-     store_dependency(SourceTable,Dependency)
-   ).
-
-completion :-
-  ( worklist_empty ->
-    set_all_complete,
-    cleanup_all_complete,
-    % The place of the call to reset is really important: it must happen after the completion. If you do it before, you will wrongly remove yourself from the list of newly created table identifiers. On starting hProlog there are no newly created table identifiers, and nb_getval gives [] which is the perfect value.
-    reset_newly_created_table_identifiers
-  ;
-    pop_worklist(Table),
-    completion_step(Table),
-    completion
-  ).
-
-completion_step(SourceTableID) :-
-  bb_get(SourceTableID, Table),
-  get_nb_identifiers(Table, NBWorklistID, _),
-  (
-    table_get_work(NBWorklistID,Answer,dependency(Source,Continuation,Target)),
-    Source = call_info(Answer,_),
-    Target = call_info(Wrapper,TargetTable),
-    delim(Wrapper,Continuation,TargetTable),
-    fail
-  ;
-    true
-  ).
-
-table_get_work(NBWorklistID,Answer,Dependency) :-
-  % get_worklist(Table, Worklist),
-  % NOT IN PAPER (could be part of the definition of pop_worklist):
-  bb_get(NBWorklistID, table_nb_worklist(Worklist)),
-  unset_global_worklist_presence_flag(Worklist),
-  set_flag_executing_all_work(Worklist),
-  bb_put(NBWorklistID, table_nb_worklist(Worklist)),
-  table_get_work_(NBWorklistID,Answer,Dependency).
-
-table_get_work_(NBWorklistID,Answer,Dependency) :-
-  worklist_do_all_work(NBWorklistID,Answer,Dependency0), % This will eventually fail
-  copy_term(Dependency0,Dependency).
-
-table_get_work_(NBWorklistID,_Answer,_Dependency) :-
-  bb_get(NBWorklistID, table_nb_worklist(Worklist)),
-  unset_flag_executing_all_work(Worklist),
-  bb_put(NBWorklistID, table_nb_worklist(Worklist)),
-  fail.
-
-worklist_do_all_work(NBWorklistID,Answer,Dependency) :-
-  ( bb_get(NBWorklistID, table_nb_worklist(Worklist)),
-    wkl_worklist_work_done(Worklist) ->
-    fail
-  ;
-    worklist_do_step(NBWorklistID,Answer,Dependency)
-  ;
-    worklist_do_all_work(NBWorklistID,Answer,Dependency)
-  ).
-
-worklist_do_step(NBWorklistID,Answer,Dependency) :-
-  bb_get(NBWorklistID, table_nb_worklist(Worklist)),
-  wkl_p_get_rightmost_inner_answer_cluster_pointer(Worklist,ACP),
-  wkl_p_swap_answer_continuation(Worklist,ACP,SCP),
-  dll_get_data(ACP,wkl_answer_cluster(AListFlag)),
-  dll_get_data(SCP,wkl_suspension_cluster(SListFlag)),
-  get_atts(AListFlag, batched_worklist, wkl_answer_cluster(AList)),
-  get_atts(SListFlag, batched_worklist, wkl_suspension_cluster(SList)),
-  bb_put(NBWorklistID, table_nb_worklist(Worklist)),
-  member(Answer,AList),
-  member(Dependency,SList).
-
-:- initialization(bb_put(table_leader, [])).
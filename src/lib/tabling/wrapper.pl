@@ -34,58 +34,145 @@
 
 :- module(table_wrapper,
 	  [ %(table)/1,			% +Predicates
-	    op(1150, fx, table)
+	    op(1150, fx, table),
+	    op(1100, xfx, as)
 	  ]).
 
 :- use_module(library(dcgs)).
 :- use_module(library(error)).
+:- use_module(library(lists)).
 
-:- multifile(tabled/2).
-
-%%:- multifile
-%%	system:term_expansion/2,
-%%	tabled/2.
-%%:- dynamic
-%%	system:term_expansion/2.
+:- multifile(tabled/4).
 
 %%	table(+PredicateIndicators)
 %
 %	Prepare the given PredicateIndicators for tabling.  Can only
-%	be used as a directive.
-
-%% table(PIList) :-
-%% 	throw(error(context_error(nodirective, table(PIList)), _)).
-
-%% instantiation_error(Var) :-
-%%         throw(error(instantiation_error(Var), _)).
-
-wrappers(Var) -->
+%	be used as a directive. A predicate indicator may be replaced by
+%	a head whose arguments are variables or `index`, except for one
+%	argument giving the mode of its answers. The predicate
+%	indicators are followed by `as incremental` if their tables
+%	depend on dynamic predicates declared incremental.
+
+table_specs(Var, _, _) :-
+	var(Var), !,
+	instantiation_error((table)/1).
+table_specs((Specs as Options), Specs, Incremental) :- !,
+	(   has_option(incremental, Options)
+	->  Incremental = true
+	;   Incremental = false
+	).
+table_specs(Specs, Specs, false).
+
+has_option(_, Var) :-
+	var(Var), !,
+	instantiation_error((table)/1).
+has_option(Option, (A, B)) :- !,
+	(   has_option(Option, A)
+	->  true
+	;   has_option(Option, B)
+	).
+has_option(Option, Option).
+
+wrappers(Var, _) -->
 	{ var(Var), !,
-	  instantiation_error(Var)
+	  instantiation_error((table)/1)
 	}.
-wrappers((A,B)) --> !,
-	wrappers(A),
-	wrappers(B).
-wrappers(Name//Arity) -->
+wrappers((A,B), Incremental) --> !,
+	wrappers(A, Incremental),
+	wrappers(B, Incremental).
+wrappers(Name//Arity, Incremental) -->
 	{ atom(Name), integer(Arity), Arity >= 0, !,
 	  Arity1 is Arity+2
 	},
-	wrappers(Name/Arity1).
-wrappers(Name/Arity) -->
-	{ atom(Name), integer(Arity), Arity >= 0, !,
-	  functor(Head, Name, Arity),
+	wrappers(Name/Arity1, Incremental).
+wrappers(Name/Arity, Incremental) -->
+	{ atom(Name), integer(Arity), Arity >= 0, !
+	},
+	wrapper(Name, Arity, [], Incremental).
+wrappers(ModedHead, Incremental) -->
+	{ compound(ModedHead), !,
+	  functor(ModedHead, Name, Arity),
+	  prolog_load_context(module, Module),
+	  ModedHead =.. [_|Modes],
+	  moded_args(Modes, 1, Module, Moded),
+	  (   Moded = [_,_|_]
+	  ->  domain_error(single_moded_argument, ModedHead, (table)/1)
+	  ;   true
+	  )
+	},
+	wrapper(Name, Arity, Moded, Incremental).
+wrappers(Spec, _) -->
+	{ type_error(predicate_indicator, Spec, (table)/1) }.
+
+wrapper(Name, Arity, Moded, Incremental) -->
+	{ functor(Head, Name, Arity),
 	  atom_concat(Name, ' tabled', WrapName),
 	  Head =.. [Name|Args],
 	  WrappedHead =.. [WrapName|Args],
-	  prolog_load_context(module, Module)
+	  prolog_load_context(module, Module),
+	  Options = table_options(Moded, Incremental)
 	},
 	[ (   Head :-
-		 start_tabling(Module:Head, WrappedHead)
+		 start_tabling(Module:Head, WrappedHead, Options)
 	  ),
-	  (:- multifile(table_wrapper:tabled/2)),
-	  table_wrapper:tabled(Head, Module)
+	  (:- multifile(table_wrapper:tabled/4)),
+	  table_wrapper:tabled(Head, Module, WrappedHead, Options)
 	].
 
+moded_args([], _, _, []).
+moded_args([Mode|Modes], N, Module, Moded) :-
+	(   ( var(Mode) ; Mode == index )
+	->  Moded = Moded1
+	;   table_mode(Mode, Module, TableMode)
+	->  Moded = [N-TableMode|Moded1]
+	;   domain_error(table_mode, Mode, (table)/1)
+	),
+	N1 is N+1,
+	moded_args(Modes, N1, Module, Moded1).
+
+table_mode(min, _, min).
+table_mode(max, _, max).
+table_mode(first, _, first).
+table_mode(last, _, last).
+table_mode(lattice(PI), Module, lattice(Pred)) :-
+	qualified_predicate(PI, 3, Module, Pred).
+table_mode(po(PI), Module, po(Pred)) :-
+	qualified_predicate(PI, 2, Module, Pred).
+
+qualified_predicate(PI, _, _, _) :-
+	var(PI), !,
+	instantiation_error((table)/1).
+qualified_predicate(Module:PI, Arity, _, Pred) :- !,
+	qualified_predicate(PI, Arity, Module, Pred).
+qualified_predicate(Name/Arity, Arity, Module, Module:Name) :-
+	atom(Name), !.
+qualified_predicate(Name, _, Module, Module:Name) :-
+	atom(Name).
+
+%%	incremental_dynamic(+PredicateIndicators)
+%
+%	Declares the dynamic PredicateIndicators incremental, so that the
+%	incremental tables depending on them are removed when they
+%	change.
+
+incremental_dynamic(Var) :-
+	var(Var), !,
+	instantiation_error((dynamic)/1).
+incremental_dynamic((A, B)) :- !,
+	incremental_dynamic(A),
+	incremental_dynamic(B).
+incremental_dynamic([]) :- !.
+incremental_dynamic([PI|PIs]) :- !,
+	incremental_dynamic(PI),
+	incremental_dynamic(PIs).
+incremental_dynamic(_:PI) :- !,
+	incremental_dynamic(PI).
+incremental_dynamic(Name/Arity) :-
+	atom(Name), integer(Arity), Arity >= 0, !,
+	'$tbl_incremental_dynamic'(Name, Arity).
+incremental_dynamic(PI) :-
+	type_error(predicate_indicator, PI, (dynamic)/1).
+
 rename(M:Term0, M:Term, _) :-
 	atom(M), !,
 	rename(Term0, Term, M).
@@ -95,13 +182,13 @@ rename((Head --> Body), (NewHead --> Body), Module) :- !,
 	functor(Head, Name, Arity),
 	PlainArity is Arity+1,
 	functor(PlainHead, Name, PlainArity),
-	catch(table_wrapper:tabled(PlainHead, Module),
-          error(existence_error(procedure, tabled/2), _),
+	catch(table_wrapper:tabled(PlainHead, Module, _, _),
+          error(existence_error(procedure, tabled/4), _),
           false),
 	rename_term(Head, NewHead).
 rename(Head, NewHead, Module) :-
-	catch(table_wrapper:tabled(Head, Module),
-          error(existence_error(procedure, tabled/2), _),
+	catch(table_wrapper:tabled(Head, Module, _, _),
+          error(existence_error(procedure, tabled/4), _),
           false),
     !,
 	rename_term(Head, NewHead).
@@ -118,7 +205,17 @@ rename_term(Name, WrapName) :-
 user:term_expansion(Term0, Clauses) :-
     nonvar(Term0),
 	Term0 = (:- table Preds),
-	phrase(wrappers(Preds), Clauses).
+	table_specs(Preds, Specs, Incremental),
+	phrase(wrappers(Specs, Incremental), Clauses).
+user:term_expansion(Term0, (:- dynamic(Specs))) :-
+    nonvar(Term0),
+	Term0 = (:- dynamic(Preds)),
+	nonvar(Preds),
+	Preds = (Specs as Options),
+	(   has_option(incremental, Options)
+	->  incremental_dynamic(Specs)
+	;   true
+	).
 user:term_expansion(Clause, NewClause) :-
     nonvar(Clause),
     prolog_load_context(module, Module),
@@ -0,0 +1,185 @@
+/** Native threads, message queues and mutexes.
+
+Each thread runs its own machine, with its own heap, global variables
+and streams, and threads run in parallel. A new thread starts with a
+copy of the program of the thread creating it. From then on, clauses
+asserted, retracted or abolished by one thread are seen by every
+other, while files loaded by a thread only change its own program.
+
+Threads communicate by sending each other messages. Messages, goals,
+exit statuses and asserted clauses are copied between threads, and so
+can't contain streams.
+
+```
+?- thread_create(X is 2 + 2, Id), thread_join(Id, Status).
+   Id = '$thread'(1), Status = true.
+```
+*/
+
+:- module(threads, [thread_create/2,
+                    thread_create/3,
+                    thread_join/2,
+                    thread_self/1,
+                    thread_send_message/2,
+                    thread_get_message/1,
+                    thread_peek_message/1,
+                    mutex_create/1,
+                    mutex_lock/1,
+                    mutex_unlock/1,
+                    with_mutex/2]).
+
+:- use_module(library(error)).
+:- use_module(library(iso_ext)).
+:- use_module(library(lists)).
+
+:- meta_predicate(thread_create(0, ?)).
+:- meta_predicate(thread_create(0, ?, +)).
+:- meta_predicate(with_mutex(+, 0)).
+
+%% thread_create(:Goal, -Id).
+%
+% Equivalent to `thread_create(Goal, Id, [])`.
+
+thread_create(Goal, Id) :-
+    thread_create(Goal, Id, []).
+
+%% thread_create(:Goal, -Id, +Options).
+%
+% Runs Goal once in a new thread, whose identifier is Id. Options is a
+% list of:
+%
+% - `alias(+Alias)` - the thread is identified by the atom Alias
+%   rather than by an opaque term.
+% - `detached(+Bool)` - if `true`, the thread is forgotten once it
+%   terminates and can't be joined. By default, `false`.
+
+thread_create(Goal, Id, Options) :-
+    must_be(list, Options),
+    (   member(alias(Alias), Options) ->
+        must_be(atom, Alias)
+    ;   true
+    ),
+    (   member(detached(Detached), Options) ->
+        must_be(boolean, Detached)
+    ;   Detached = false
+    ),
+    '$thread_create'(Goal, Alias, Detached, Id).
+
+%% thread_join(+Id, -Status).
+%
+% Waits for the thread Id to terminate and unifies Status with how it
+% terminated: `true` if its goal succeeded, `false` if it failed,
+% `exception(E)` if it raised E and `exited(halt)` if it called
+% `halt/0`.
+
+thread_join(Id, Status) :-
+    must_be_bound(Id, thread_join/2),
+    '$thread_join'(Id, Status).
+
+%% thread_self(-Id).
+%
+% Id is the identifier of the calling thread. The identifier of the
+% initial thread is `main`.
+
+thread_self(Id) :-
+    '$thread_self'(Id).
+
+%% thread_send_message(+Id, +Message).
+%
+% Appends a copy of Message to the message queue of the thread Id.
+
+thread_send_message(Id, Message) :-
+    must_be_bound(Id, thread_send_message/2),
+    '$thread_send_message'(Id, Message).
+
+%% thread_get_message(?Message).
+%
+% Removes the first message unifying with Message from the queue of
+% the calling thread, waiting for one to arrive if there is none.
+
+thread_get_message(Message) :-
+    get_message(0, Message).
+
+get_message(N, Message) :-
+    '$thread_peek_message'(N, Message0, true),
+    (   Message0 = Message ->
+        '$thread_remove_message'(N)
+    ;   N1 is N + 1,
+        get_message(N1, Message)
+    ).
+
+%% thread_peek_message(?Message).
+%
+% True if the queue of the calling thread holds a message unifying
+% with Message, which is left in the queue.
+
+thread_peek_message(Message) :-
+    peek_message(0, Message).
+
+peek_message(N, Message) :-
+    '$thread_peek_message'(N, Message0, false),
+    (   Message0 = Message ->
+        true
+    ;   N1 is N + 1,
+        peek_message(N1, Message)
+    ).
+
+%% mutex_create(?Mutex).
+%
+% Creates a mutex. If Mutex is an atom, it names the mutex. Otherwise,
+% Mutex is unified with an opaque identifier. Mutexes named by atoms
+% are also created when first locked.
+
+mutex_create(Mutex) :-
+    (   var(Mutex) ->
+        true
+    ;   must_be(atom, Mutex)
+    ),
+    '$mutex_create'(Mutex).
+
+%% mutex_lock(+Mutex).
+%
+% Locks Mutex, waiting for other threads to unlock it first. A thread
+% may lock a mutex it holds again, and must then unlock it as many
+% times.
+
+mutex_lock(Mutex) :-
+    must_be_bound(Mutex, mutex_lock/1),
+    '$mutex_lock'(Mutex).
+
+%% mutex_unlock(+Mutex).
+%
+% Unlocks Mutex, which must be held by the calling thread.
+
+mutex_unlock(Mutex) :-
+    must_be_bound(Mutex, mutex_unlock/1),
+    '$mutex_unlock'(Mutex).
+
+%% with_mutex(+Mutex, :Goal).
+%
+% Runs Goal once while holding Mutex.
+
+with_mutex(Mutex, Goal) :-
+    setup_call_cleanup(mutex_lock(Mutex), once(Goal), mutex_unlock(Mutex)).
+
+must_be_bound(Term, Context) :-
+    (   var(Term) ->
+        instantiation_error(Context)
+    ;   true
+    ).
+
+'$thread_start'(Goal) :-
+    (   catch(Goal, Exception, true) ->
+        (   var(Exception) ->
+            Status = true
+        ;   Status = exception(Exception)
+        )
+    ;   Status = false
+    ),
+    % the status can't be sent if it holds a stream.
+    catch(thread_finish(Status),
+          Error,
+          thread_finish(exception(Error))).
+
+thread_finish(Status) :-
+    '$thread_finish'(Status).
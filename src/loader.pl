@@ -5,6 +5,7 @@
                    load/1,
                    predicate_property/2,
                    prolog_load_context/2,
+                   qlf_compile/1,
                    strip_module/3,
                    use_module/1,
                    use_module/2,
@@ -157,6 +158,44 @@ file_load(Stream, Path, Evacuable) :-
     '$pop_load_context'.
 
 
+% Loads the file at Path from the .qlf file at QlfPath written by a
+% previous qlf_compile/1. Stream is the source of the file, which
+% becomes the stream of the load context.
+qlf_file_load(Stream, Path, QlfPath, Evacuable) :-
+    create_file_load_context(Stream, Path, Evacuable),
+    '$add_in_situ_filename_module'(Evacuable),
+    catch(loader:qlf_file_load_init(Stream, QlfPath, Evacuable),
+          E,
+          loader:file_load_cleanup(Evacuable, E)),
+    '$pop_load_context'.
+
+qlf_file_load_init(Stream, QlfPath, Evacuable) :-
+    '$open_qlf'(QlfPath),
+    qlf_load_loop(Stream, Evacuable),
+    run_initialization_goals.
+
+qlf_load_loop(Stream, Evacuable) :-
+    '$next_qlf_term'(Evacuable, Term),
+    (  Term == end_of_file ->
+       close(Stream),
+       '$conclude_load'(Evacuable)
+    ;  compile_dispatch_or_clause(list(Term), Term, Evacuable),
+       qlf_load_loop(Stream, Evacuable)
+    ).
+
+% Loads the file at Path as file_load/3 does, recording its load to
+% its .qlf file.
+qlf_compile_file_load(Stream, Path, Evacuable) :-
+    create_file_load_context(Stream, Path, Evacuable),
+    '$start_qlf_recording',
+    '$add_in_situ_filename_module'(Evacuable),
+    catch(loader:file_load_init(Stream, Evacuable),
+          E,
+          loader:file_load_cleanup(Evacuable, E)),
+    '$save_qlf',
+    '$pop_load_context'.
+
+
 load(Stream) :-
     create_load_context(Stream, Evacuable),
     catch(loader:file_load_init(Stream, Evacuable),
@@ -203,7 +242,11 @@ warn_about_singletons([Singleton|Singletons], LinesRead) :-
 load_loop(Stream, Evacuable) :-
     (  '$devour_whitespace'(Stream) ->
        stream_property(Stream, position(position_and_lines_read(_, LinesRead))),
-       read_term(Stream, Term, [singletons(Singletons)])
+       Line is LinesRead + 1,
+       '$set_load_context_line'(Line),
+       catch(read_term(Stream, Term, [singletons(Singletons)]),
+             error(syntax_error(Error), Context),
+             loader:throw_load_syntax_error(Error, Context))
     ;  Term = end_of_file
     ),
     (  Term == end_of_file ->
@@ -217,6 +260,23 @@ load_loop(Stream, Evacuable) :-
     ).
 
 
+% '$file_exists'/1 is denied to sandboxed machines, which then report
+% syntax errors without the file.
+source_file_exists(File) :-
+    '$file_exists'(File).
+
+%% adds the file being loaded to the location of a syntax error in
+%% it.
+throw_load_syntax_error(Error, Context0) :-
+    (  Context0 = read_term/3:Line,
+       prolog_load_context(source, File),
+       catch(source_file_exists(File), error(permission_error(call, sandboxed, _), _), false) ->
+       Context = context(read_term/3, file(File, Line))
+    ;  Context = Context0
+    ),
+    throw(error(syntax_error(Error), Context)).
+
+
 compile_term(Term, Evacuable) :-
     expand_terms_and_goals(Term, Terms),
     !,
@@ -340,6 +400,7 @@ compile_dispatch_or_clause(OrigTerm, Term, Evacuable) :-
     (  var(Term) ->
        instantiation_error(load/1)
     ;  compile_dispatch(Term, Evacuable) ->
+       '$record_qlf_term'(Term),
        '$flush_term_queue'(Evacuable)
     ;  compile_clause(OrigTerm, Term, Evacuable)
     ).
@@ -625,12 +686,38 @@ use_module(Module, Exports, Evacuable) :-
           load_context_path(ModulePath, Path),
           open_file(Path, Stream),
           stream_property(Stream, file_name(PathFileName)),
-          file_load(Stream, PathFileName, Subevacuable),
+          (  '$qlf_file'(PathFileName, QlfPath) ->
+             qlf_file_load(Stream, PathFileName, QlfPath, Subevacuable)
+          ;  file_load(Stream, PathFileName, Subevacuable)
+          ),
           '$use_module'(Evacuable, Subevacuable, Exports)
        ;  type_error(atom, Module, load/1)
        )
     ).
 
+%% qlf_compile(+File)
+%
+% Loads File and writes its compiled predicates to a .qlf file beside
+% it, which use_module/1 loads in place of File for as long as File is
+% older than it.
+qlf_compile(File) :-
+    '$push_load_state_payload'(Evacuable),
+    catch('$call'(loader:qlf_compile(File, Evacuable)),
+          file_load_error,
+          '$call'(builtins:false)).
+
+qlf_compile(File, Evacuable) :-
+    (  var(File) ->
+       instantiation_error(qlf_compile/1)
+    ;  path_atom(File, FilePath) ->
+       load_context_path(FilePath, Path),
+       open_file(Path, Stream),
+       stream_property(Stream, file_name(PathFileName)),
+       qlf_compile_file_load(Stream, PathFileName, Subevacuable),
+       '$use_module'(Evacuable, Subevacuable, [])
+    ;  type_error(atom, File, qlf_compile/1)
+    ).
+
 consult_stream(Stream, PathFileName) :-
    '$push_load_state_payload'(Evacuable),
     file_load(Stream, PathFileName, Subevacuable),
@@ -1,6 +1,6 @@
-use dashu::base::{Abs, Gcd, Signed, UnsignedAbs};
+use dashu::base::{Abs, BitTest, Gcd, Signed, UnsignedAbs};
 use dashu::integer::fast_div::ConstDivisor;
-use dashu::integer::IBig;
+use dashu::integer::{IBig, UBig};
 use divrem::*;
 use num_order::NumOrd;
 
@@ -13,11 +13,14 @@
 use crate::machine::machine_state::*;
 use crate::parser::ast::*;
 use crate::parser::dashu::{Integer, Rational};
+use crate::parser::decimal::{round_rational, Decimal};
 use crate::types::*;
 
 use crate::fixnum;
 
 use ordered_float::*;
+use rand::rngs::StdRng;
+use rand::Rng;
 
 use std::cmp;
 use std::convert::TryFrom;
@@ -69,6 +72,19 @@ fn undefined_eval_error(stub_gen: impl Fn() -> FunctorStub + 'static) -> Machine
     })
 }
 
+fn numerical_domain_error(
+    domain: DomainErrorType,
+    n: Number,
+    stub_gen: impl Fn() -> FunctorStub + 'static,
+) -> MachineStubGen {
+    Box::new(move |machine_st| {
+        let domain_error = machine_st.domain_error(domain, n);
+        let stub = stub_gen();
+
+        machine_st.error_form(domain_error, stub)
+    })
+}
+
 fn numerical_type_error(
     valid_type: ValidType,
     n: Number,
@@ -128,7 +144,12 @@ fn isize_gcd(n1: isize, n2: isize) -> Option<isize> {
     Some(n1 << shift as isize)
 }
 
-pub(crate) fn add(lhs: Number, rhs: Number, arena: &mut Arena) -> Result<Number, EvalError> {
+pub(crate) fn add(
+    lhs: Number,
+    rhs: Number,
+    flags: FloatFlags,
+    arena: &mut Arena,
+) -> Result<Number, EvalError> {
     match (lhs, rhs) {
         (Number::Fixnum(n1), Number::Fixnum(n2)) => Ok(
             if let Some(result) = n1.get_num().checked_add(n2.get_num()) {
@@ -153,26 +174,43 @@ pub(crate) fn add(lhs: Number, rhs: Number, arena: &mut Arena) -> Result<Number,
             ))
         }
         (Number::Fixnum(n1), Number::Float(OrderedFloat(n2)))
-        | (Number::Float(OrderedFloat(n2)), Number::Fixnum(n1)) => {
-            Ok(Number::Float(add_f(float_fn_to_f(n1.get_num())?, n2)?))
-        }
+        | (Number::Float(OrderedFloat(n2)), Number::Fixnum(n1)) => Ok(Number::Float(add_f(
+            float_fn_to_f(n1.get_num(), flags)?,
+            n2,
+            flags,
+        )?)),
         (Number::Integer(n1), Number::Integer(n2)) => {
             Ok(Number::arena_from(&*n1 + &*n2, arena)) // add_i
         }
         (Number::Integer(n1), Number::Float(OrderedFloat(n2)))
         | (Number::Float(OrderedFloat(n2)), Number::Integer(n1)) => {
-            Ok(Number::Float(add_f(float_i_to_f(&n1)?, n2)?))
+            Ok(Number::Float(add_f(float_i_to_f(&n1, flags)?, n2, flags)?))
         }
         (Number::Integer(n1), Number::Rational(n2))
         | (Number::Rational(n2), Number::Integer(n1)) => Ok(Number::arena_from(&*n1 + &*n2, arena)),
         (Number::Rational(n1), Number::Float(OrderedFloat(n2)))
         | (Number::Float(OrderedFloat(n2)), Number::Rational(n1)) => {
-            Ok(Number::Float(add_f(float_r_to_f(&n1)?, n2)?))
+            Ok(Number::Float(add_f(float_r_to_f(&n1, flags)?, n2, flags)?))
         }
         (Number::Float(OrderedFloat(f1)), Number::Float(OrderedFloat(f2))) => {
-            Ok(Number::Float(add_f(f1, f2)?))
+            Ok(Number::Float(add_f(f1, f2, flags)?))
         }
         (Number::Rational(r1), Number::Rational(r2)) => Ok(Number::arena_from(&*r1 + &*r2, arena)),
+        (Number::Decimal(d), Number::Float(OrderedFloat(f)))
+        | (Number::Float(OrderedFloat(f)), Number::Decimal(d)) => {
+            Ok(Number::Float(add_f(float_d_to_f(&d, flags)?, f, flags)?))
+        }
+        (Number::Decimal(d), Number::Rational(r)) | (Number::Rational(r), Number::Decimal(d)) => {
+            Ok(Number::arena_from(d.to_rational() + &*r, arena))
+        }
+        (Number::Decimal(d1), n2) => {
+            let d2 = decimal_operand(&n2).unwrap();
+            Ok(Number::arena_from(d1.add(&d2), arena))
+        }
+        (n1, Number::Decimal(d2)) => {
+            let d1 = decimal_operand(&n1).unwrap();
+            Ok(Number::arena_from(d1.add(&d2), arena))
+        }
     }
 }
 
@@ -194,6 +232,7 @@ pub(crate) fn neg(n: Number, arena: &mut Arena) -> Number {
             let r_clone: Rational = (*r).clone();
             Number::arena_from(-Rational::from(r_clone), arena)
         }
+        Number::Decimal(d) => Number::arena_from(d.neg(), arena),
     }
 }
 
@@ -216,16 +255,27 @@ pub(crate) fn abs(n: Number, arena: &mut Arena) -> Number {
             let r_clone: Rational = (*r).clone();
             Number::arena_from(Rational::from(r_clone.abs()), arena)
         }
+        Number::Decimal(d) => Number::arena_from(d.abs(), arena),
     }
 }
 
 #[inline]
-pub(crate) fn sub(lhs: Number, rhs: Number, arena: &mut Arena) -> Result<Number, EvalError> {
+pub(crate) fn sub(
+    lhs: Number,
+    rhs: Number,
+    flags: FloatFlags,
+    arena: &mut Arena,
+) -> Result<Number, EvalError> {
     let neg_result = neg(rhs, arena);
-    add(lhs, neg_result, arena)
+    add(lhs, neg_result, flags, arena)
 }
 
-pub(crate) fn mul(lhs: Number, rhs: Number, arena: &mut Arena) -> Result<Number, EvalError> {
+pub(crate) fn mul(
+    lhs: Number,
+    rhs: Number,
+    flags: FloatFlags,
+    arena: &mut Arena,
+) -> Result<Number, EvalError> {
     match (lhs, rhs) {
         (Number::Fixnum(n1), Number::Fixnum(n2)) => Ok(
             if let Some(result) = n1.get_num().checked_mul(n2.get_num()) {
@@ -250,16 +300,18 @@ pub(crate) fn mul(lhs: Number, rhs: Number, arena: &mut Arena) -> Result<Number,
             ))
         }
         (Number::Fixnum(n1), Number::Float(OrderedFloat(n2)))
-        | (Number::Float(OrderedFloat(n2)), Number::Fixnum(n1)) => {
-            Ok(Number::Float(mul_f(float_fn_to_f(n1.get_num())?, n2)?))
-        }
+        | (Number::Float(OrderedFloat(n2)), Number::Fixnum(n1)) => Ok(Number::Float(mul_f(
+            float_fn_to_f(n1.get_num(), flags)?,
+            n2,
+            flags,
+        )?)),
         (Number::Integer(n1), Number::Integer(n2)) => {
             let n1_clone: Integer = (*n1).clone();
             Ok(Number::arena_from(Integer::from(n1_clone) * &*n2, arena)) // mul_i
         }
         (Number::Integer(n1), Number::Float(OrderedFloat(n2)))
         | (Number::Float(OrderedFloat(n2)), Number::Integer(n1)) => {
-            Ok(Number::Float(mul_f(float_i_to_f(&n1)?, n2)?))
+            Ok(Number::Float(mul_f(float_i_to_f(&n1, flags)?, n2, flags)?))
         }
         (Number::Integer(n1), Number::Rational(n2))
         | (Number::Rational(n2), Number::Integer(n1)) => {
@@ -268,31 +320,61 @@ pub(crate) fn mul(lhs: Number, rhs: Number, arena: &mut Arena) -> Result<Number,
         }
         (Number::Rational(n1), Number::Float(OrderedFloat(n2)))
         | (Number::Float(OrderedFloat(n2)), Number::Rational(n1)) => {
-            Ok(Number::Float(mul_f(float_r_to_f(&n1)?, n2)?))
+            Ok(Number::Float(mul_f(float_r_to_f(&n1, flags)?, n2, flags)?))
         }
         (Number::Float(OrderedFloat(f1)), Number::Float(OrderedFloat(f2))) => {
-            Ok(Number::Float(mul_f(f1, f2)?))
+            Ok(Number::Float(mul_f(f1, f2, flags)?))
         }
         (Number::Rational(r1), Number::Rational(r2)) => {
             let r1_clone: Rational = (*r1).clone();
             Ok(Number::arena_from(Rational::from(r1_clone) * &*r2, arena))
         }
+        (Number::Decimal(d), Number::Float(OrderedFloat(f)))
+        | (Number::Float(OrderedFloat(f)), Number::Decimal(d)) => {
+            Ok(Number::Float(mul_f(float_d_to_f(&d, flags)?, f, flags)?))
+        }
+        (Number::Decimal(d), Number::Rational(r)) | (Number::Rational(r), Number::Decimal(d)) => {
+            Ok(Number::arena_from(d.to_rational() * &*r, arena))
+        }
+        (Number::Decimal(d1), n2) => {
+            let d2 = decimal_operand(&n2).unwrap();
+            Ok(Number::arena_from(d1.mul(&d2), arena))
+        }
+        (n1, Number::Decimal(d2)) => {
+            let d1 = decimal_operand(&n1).unwrap();
+            Ok(Number::arena_from(d1.mul(&d2), arena))
+        }
     }
 }
 
-pub(crate) fn div(n1: Number, n2: Number) -> Result<Number, MachineStubGen> {
+pub(crate) fn div(
+    n1: Number,
+    n2: Number,
+    flags: FloatFlags,
+    decimal_flags: DecimalFlags,
+    arena: &mut Arena,
+) -> Result<Number, MachineStubGen> {
     let stub_gen = || functor_stub(atom!("/"), 2);
 
-    if n2.is_zero() {
-        Err(zero_divisor_eval_error(stub_gen))
-    } else {
-        try_numeric_result!(n1 / n2, stub_gen)
+    // decimals divide to decimals, unless the other operand is a float
+    // or a rational.
+    if matches!(n1, Number::Decimal(_)) || matches!(n2, Number::Decimal(_)) {
+        if let (Some(d1), Some(d2)) = (decimal_operand(&n1), decimal_operand(&n2)) {
+            let result = try_numeric_result!(decimal_div(&d1, &d2, decimal_flags), stub_gen)?;
+            return Ok(Number::arena_from(result, arena));
+        }
     }
+
+    try_numeric_result!(float_div(n1, n2, flags), stub_gen)
 }
 
-pub(crate) fn float_pow(n1: Number, n2: Number) -> Result<Number, MachineStubGen> {
-    let f1 = result_f(&n1);
-    let f2 = result_f(&n2);
+pub(crate) fn float_pow(
+    n1: Number,
+    n2: Number,
+    flags: FloatFlags,
+) -> Result<Number, MachineStubGen> {
+    let f1 = result_f(&n1, flags);
+    let f2 = result_f(&n2, flags);
 
     let stub_gen = || {
         let pow_atom = atom!("**");
@@ -302,14 +384,20 @@ pub(crate) fn float_pow(n1: Number, n2: Number) -> Result<Number, MachineStubGen
     let f1 = try_numeric_result!(f1, stub_gen)?;
     let f2 = try_numeric_result!(f2, stub_gen)?;
 
-    let result = result_f(&Number::Float(OrderedFloat(f1.powf(f2))));
+    let result = float_op_result(f1.powf(f2), &[f1, f2], flags);
 
     Ok(Number::Float(OrderedFloat(try_numeric_result!(
         result, stub_gen
     )?)))
 }
 
-pub(crate) fn int_pow(n1: Number, n2: Number, arena: &mut Arena) -> Result<Number, MachineStubGen> {
+pub(crate) fn int_pow(
+    n1: Number,
+    n2: Number,
+    flags: FloatFlags,
+    decimal_flags: DecimalFlags,
+    arena: &mut Arena,
+) -> Result<Number, MachineStubGen> {
     if n1.is_zero() && n2.is_negative() {
         let stub_gen = || {
             let is_atom = atom!("is");
@@ -375,50 +463,74 @@ pub(crate) fn int_pow(n1: Number, n2: Number, arena: &mut Arena) -> Result<Numbe
                 Ok(Number::arena_from(binary_pow((*n1).clone(), &n2), arena))
             }
         }
+        (Number::Decimal(d), Number::Fixnum(n2)) => {
+            let n2 = Integer::from(n2.get_num());
+            let result = try_numeric_result!(decimal_pow(&d, &n2, decimal_flags), stub_gen)?;
+
+            Ok(Number::arena_from(result, arena))
+        }
+        (Number::Decimal(d), Number::Integer(n2)) => {
+            let result = try_numeric_result!(decimal_pow(&d, &n2, decimal_flags), stub_gen)?;
+            Ok(Number::arena_from(result, arena))
+        }
         (n1, Number::Integer(n2)) => {
-            let f1 = float(n1)?;
-            let f2 = float(Number::Integer(n2))?;
+            let f1 = float(n1, flags)?;
+            let f2 = float(Number::Integer(n2), flags)?;
 
-            unary_float_fn_template(Number::Float(OrderedFloat(f1)), |f| f.powf(f2))
+            unary_float_fn_template(Number::Float(OrderedFloat(f1)), flags, |f| f.powf(f2))
                 .map(|f| Number::Float(OrderedFloat(f)))
         }
         (n1, n2) => {
-            let f2 = float(n2)?;
+            let f2 = float(n2, flags)?;
 
-            if n1.is_negative() && f2 != f2.floor() {
+            if n1.is_negative() && f2 != f2.floor() && !flags.undefined_to_nan {
                 return Err(undefined_eval_error(stub_gen));
             }
 
-            let f1 = float(n1)?;
+            let f1 = float(n1, flags)?;
 
-            unary_float_fn_template(Number::Float(OrderedFloat(f1)), |f| f.powf(f2))
+            unary_float_fn_template(Number::Float(OrderedFloat(f1)), flags, |f| f.powf(f2))
                 .map(|f| Number::Float(OrderedFloat(f)))
         }
     }
 }
 
-pub(crate) fn pow(n1: Number, n2: Number, culprit: Atom) -> Result<Number, MachineStubGen> {
+pub(crate) fn pow(
+    n1: Number,
+    n2: Number,
+    culprit: Atom,
+    flags: FloatFlags,
+) -> Result<Number, MachineStubGen> {
     if n2.is_negative() && n1.is_zero() {
+        // a pole, which is infinite only if divisions by zero are.
+        if flags.zero_div_to_infinity {
+            let f1 = float(n1, flags)?;
+            let f2 = float(n2, flags)?;
+
+            return Ok(Number::Float(OrderedFloat(f1.powf(f2))));
+        }
+
         let stub_gen = move || functor_stub(culprit, 2);
         return Err(undefined_eval_error(stub_gen));
     }
 
-    float_pow(n1, n2)
+    float_pow(n1, n2, flags)
 }
 
 #[inline]
-pub(crate) fn float(n: Number) -> Result<f64, MachineStubGen> {
+pub(crate) fn float(n: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
     let stub_gen = || {
         let is_atom = atom!("is");
         functor_stub(is_atom, 2)
     };
 
-    try_numeric_result!(result_f(&n), stub_gen)
+    try_numeric_result!(result_f(&n, flags), stub_gen)
 }
 
 #[inline]
 pub(crate) fn unary_float_fn_template<FloatFn>(
     n1: Number,
+    flags: FloatFlags,
     f: FloatFn,
 ) -> Result<f64, MachineStubGen>
 where
@@ -429,13 +541,22 @@ pub(crate) fn unary_float_fn_template<FloatFn>(
         functor_stub(is_atom, 2)
     };
 
-    let f1 = try_numeric_result!(result_f(&n1), stub_gen)?;
-    let f1 = result_f(&Number::Float(OrderedFloat(f(f1))));
+    let f1 = try_numeric_result!(result_f(&n1, flags), stub_gen)?;
+    let f1 = float_op_result(f(f1), &[f1], flags);
 
     try_numeric_result!(f1, stub_gen)
 }
 
-pub(crate) fn max(n1: Number, n2: Number) -> Result<Number, MachineStubGen> {
+// true iff one of n1 and n2 is a decimal and neither is a float, so
+// that max and min can pick one exactly.
+fn is_exact_decimal_pair(n1: &Number, n2: &Number) -> bool {
+    let is_decimal = |n: &Number| matches!(n, Number::Decimal(_));
+    let is_float = |n: &Number| matches!(n, Number::Float(_));
+
+    (is_decimal(n1) || is_decimal(n2)) && !is_float(n1) && !is_float(n2)
+}
+
+pub(crate) fn max(n1: Number, n2: Number, flags: FloatFlags) -> Result<Number, MachineStubGen> {
     match (n1, n2) {
         (Number::Fixnum(n1), Number::Fixnum(n2)) => {
             if n1.get_num() > n2.get_num() {
@@ -465,21 +586,22 @@ pub(crate) fn max(n1: Number, n2: Number) -> Result<Number, MachineStubGen> {
                 Ok(Number::Integer(n2))
             }
         }
+        (n1, n2) if is_exact_decimal_pair(&n1, &n2) => Ok(cmp::max(n1, n2)),
         (n1, n2) => {
             let stub_gen = || {
                 let max_atom = atom!("max");
                 functor_stub(max_atom, 2)
             };
 
-            let f1 = try_numeric_result!(result_f(&n1), stub_gen)?;
-            let f2 = try_numeric_result!(result_f(&n2), stub_gen)?;
+            let f1 = try_numeric_result!(result_f(&n1, flags), stub_gen)?;
+            let f2 = try_numeric_result!(result_f(&n2, flags), stub_gen)?;
 
             Ok(Number::Float(cmp::max(OrderedFloat(f1), OrderedFloat(f2))))
         }
     }
 }
 
-pub(crate) fn min(n1: Number, n2: Number) -> Result<Number, MachineStubGen> {
+pub(crate) fn min(n1: Number, n2: Number, flags: FloatFlags) -> Result<Number, MachineStubGen> {
     match (n1, n2) {
         (Number::Fixnum(n1), Number::Fixnum(n2)) => {
             if n1.get_num() < n2.get_num() {
@@ -509,14 +631,15 @@ pub(crate) fn min(n1: Number, n2: Number) -> Result<Number, MachineStubGen> {
                 Ok(Number::Integer(n2))
             }
         }
+        (n1, n2) if is_exact_decimal_pair(&n1, &n2) => Ok(cmp::min(n1, n2)),
         (n1, n2) => {
             let stub_gen = || {
                 let min_atom = atom!("min");
                 functor_stub(min_atom, 2)
             };
 
-            let f1 = try_numeric_result!(result_f(&n1), stub_gen)?;
-            let f2 = try_numeric_result!(result_f(&n2), stub_gen)?;
+            let f1 = try_numeric_result!(result_f(&n1, flags), stub_gen)?;
+            let f2 = try_numeric_result!(result_f(&n2, flags), stub_gen)?;
 
             Ok(Number::Float(cmp::min(OrderedFloat(f1), OrderedFloat(f2))))
         }
@@ -544,6 +667,7 @@ pub fn rational_from_number(
             let n_clone: Integer = (*n).clone();
             Ok(arena_alloc!(Rational::from(n_clone), arena))
         }
+        Number::Decimal(d) => Ok(arena_alloc!(d.to_rational(), arena)),
     }
 }
 
@@ -621,7 +745,7 @@ pub(crate) fn int_floor_div(
     };
 
     let modulus = modulus(n1, n2, arena)?;
-    let n1 = try_numeric_result!(sub(n1, modulus, arena), stub_gen)?;
+    let n1 = try_numeric_result!(sub(n1, modulus, FloatFlags::default(), arena), stub_gen)?;
 
     idiv(n1, n2, arena)
 }
@@ -967,11 +1091,19 @@ pub(crate) fn gcd(n1: Number, n2: Number, arena: &mut Arena) -> Result<Number, M
             let n = Number::Rational(r);
             Err(numerical_type_error(ValidType::Integer, n, stub_gen))
         }
+        (Number::Decimal(d), _) | (_, Number::Decimal(d)) => {
+            let n = Number::Decimal(d);
+            Err(numerical_type_error(ValidType::Integer, n, stub_gen))
+        }
     }
 }
 
-pub(crate) fn atan2(n1: Number, n2: Number) -> Result<f64, MachineStubGen> {
+pub(crate) fn atan2(n1: Number, n2: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
     if n1.is_zero() && n2.is_zero() {
+        if flags.undefined_to_nan {
+            return Ok(f64::NAN);
+        }
+
         let stub_gen = || {
             let is_atom = atom!("is");
             functor_stub(is_atom, 2)
@@ -979,112 +1111,156 @@ pub(crate) fn atan2(n1: Number, n2: Number) -> Result<f64, MachineStubGen> {
 
         Err(undefined_eval_error(stub_gen))
     } else {
-        let f1 = float(n1)?;
-        let f2 = float(n2)?;
+        let f1 = float(n1, flags)?;
+        let f2 = float(n2, flags)?;
 
-        unary_float_fn_template(Number::Float(OrderedFloat(f1)), |f| f.atan2(f2))
+        unary_float_fn_template(Number::Float(OrderedFloat(f1)), flags, |f| f.atan2(f2))
     }
 }
 
 #[inline]
-pub(crate) fn sin(n1: Number) -> Result<f64, MachineStubGen> {
-    unary_float_fn_template(n1, |f| f.sin())
+pub(crate) fn sin(n1: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    unary_float_fn_template(n1, flags, |f| f.sin())
+}
+
+#[inline]
+pub(crate) fn cos(n1: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    unary_float_fn_template(n1, flags, |f| f.cos())
 }
 
 #[inline]
-pub(crate) fn cos(n1: Number) -> Result<f64, MachineStubGen> {
-    unary_float_fn_template(n1, |f| f.cos())
+pub(crate) fn tan(n1: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    unary_float_fn_template(n1, flags, |f| f.tan())
 }
 
 #[inline]
-pub(crate) fn tan(n1: Number) -> Result<f64, MachineStubGen> {
-    unary_float_fn_template(n1, |f| f.tan())
+// logarithms have a pole at zero, which is infinite only if divisions
+// by zero are.
+fn log_pole(n1: &Number, flags: FloatFlags) -> Option<Result<f64, MachineStubGen>> {
+    if !n1.is_zero() {
+        return None;
+    }
+
+    if flags.zero_div_to_infinity {
+        Some(Ok(f64::NEG_INFINITY))
+    } else {
+        let stub_gen = || {
+            let is_atom = atom!("is");
+            functor_stub(is_atom, 2)
+        };
+
+        Some(Err(undefined_eval_error(stub_gen)))
+    }
 }
 
 #[inline]
-pub(crate) fn log(n1: Number) -> Result<f64, MachineStubGen> {
-    unary_float_fn_template(n1, |f| f.log(f64::consts::E))
+pub(crate) fn log(n1: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    if let Some(result) = log_pole(&n1, flags) {
+        return result;
+    }
+
+    unary_float_fn_template(n1, flags, |f| f.log(f64::consts::E))
 }
 
 #[inline]
-pub(crate) fn exp(n1: Number) -> Result<f64, MachineStubGen> {
-    unary_float_fn_template(n1, |f| f.exp())
+pub(crate) fn exp(n1: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    unary_float_fn_template(n1, flags, |f| f.exp())
 }
 
 #[inline]
-pub(crate) fn asin(n1: Number) -> Result<f64, MachineStubGen> {
-    unary_float_fn_template(n1, |f| f.asin())
+pub(crate) fn asin(n1: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    unary_float_fn_template(n1, flags, |f| f.asin())
 }
 
 #[inline]
-pub(crate) fn acos(n1: Number) -> Result<f64, MachineStubGen> {
-    unary_float_fn_template(n1, |f| f.acos())
+pub(crate) fn acos(n1: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    unary_float_fn_template(n1, flags, |f| f.acos())
 }
 
 #[inline]
-pub(crate) fn atan(n1: Number) -> Result<f64, MachineStubGen> {
-    unary_float_fn_template(n1, |f| f.atan())
+pub(crate) fn atan(n1: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    unary_float_fn_template(n1, flags, |f| f.atan())
 }
 
 #[inline]
-pub(crate) fn float_fractional_part(n1: Number) -> Result<f64, MachineStubGen> {
-    unary_float_fn_template(n1, |f| f.fract())
+pub(crate) fn float_fractional_part(n1: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    unary_float_fn_template(n1, flags, |f| f.fract())
 }
 
 #[inline]
-pub(crate) fn float_integer_part(n1: Number) -> Result<f64, MachineStubGen> {
-    unary_float_fn_template(n1, |f| f.trunc())
+pub(crate) fn float_integer_part(n1: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    unary_float_fn_template(n1, flags, |f| f.trunc())
 }
 
 #[inline]
-pub(crate) fn sqrt(n1: Number) -> Result<f64, MachineStubGen> {
-    if n1.is_negative() {
-        let stub_gen = || {
-            let is_atom = atom!("is");
-            functor_stub(is_atom, 2)
-        };
+pub(crate) fn sqrt(n1: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    let stub_gen = || {
+        let is_atom = atom!("is");
+        functor_stub(is_atom, 2)
+    };
 
-        return Err(undefined_eval_error(stub_gen));
-    }
+    let f1 = try_numeric_result!(result_f(&n1, flags), stub_gen)?;
 
-    unary_float_fn_template(n1, |f| f.sqrt())
+    try_numeric_result!(sqrt_f(f1, flags), stub_gen)
 }
 
 #[inline]
-pub(crate) fn floor(n1: Number, arena: &mut Arena) -> Number {
-    rnd_i(&n1, arena)
+pub(crate) fn floor(n1: Number, arena: &mut Arena) -> Result<Number, MachineStubGen> {
+    // infinities and NaNs have no integer counterpart.
+    if let Number::Float(OrderedFloat(f)) = n1 {
+        if !f.is_finite() {
+            let stub_gen = || {
+                let is_atom = atom!("is");
+                functor_stub(is_atom, 2)
+            };
+
+            return Err(undefined_eval_error(stub_gen));
+        }
+    }
+
+    Ok(rnd_i(&n1, arena))
 }
 
 #[inline]
-pub(crate) fn ceiling(n1: Number, arena: &mut Arena) -> Number {
+pub(crate) fn ceiling(n1: Number, arena: &mut Arena) -> Result<Number, MachineStubGen> {
     let n1 = neg(n1, arena);
-    let n1 = floor(n1, arena);
+    let n1 = floor(n1, arena)?;
 
-    neg(n1, arena)
+    Ok(neg(n1, arena))
 }
 
 #[inline]
-pub(crate) fn truncate(n: Number, arena: &mut Arena) -> Number {
+pub(crate) fn truncate(n: Number, arena: &mut Arena) -> Result<Number, MachineStubGen> {
     if n.is_negative() {
         let n = abs(n, arena);
-        let n = floor(n, arena);
+        let n = floor(n, arena)?;
 
-        neg(n, arena)
+        Ok(neg(n, arena))
     } else {
         floor(n, arena)
     }
 }
 
 pub(crate) fn round(n: Number, arena: &mut Arena) -> Result<Number, MachineStubGen> {
+    if let Number::Decimal(d) = n {
+        let n = round_rational(&d.to_rational(), DecimalRounding::HalfAwayFromZero);
+        return Ok(rnd_i(&Number::arena_from(n, arena), arena));
+    }
+
     let stub_gen = || {
         let is_atom = atom!("is");
         functor_stub(is_atom, 2)
     };
 
-    let result = add(n, Number::Float(OrderedFloat(0.5f64)), arena);
+    let result = add(
+        n,
+        Number::Float(OrderedFloat(0.5f64)),
+        FloatFlags::default(),
+        arena,
+    );
     let result = try_numeric_result!(result, stub_gen)?;
 
-    Ok(floor(result, arena))
+    floor(result, arena)
 }
 
 pub(crate) fn bitwise_complement(n1: Number, arena: &mut Arena) -> Result<Number, MachineStubGen> {
@@ -1102,6 +1278,393 @@ pub(crate) fn bitwise_complement(n1: Number, arena: &mut Arena) -> Result<Number
     }
 }
 
+pub(crate) fn msb(n1: Number, arena: &mut Arena) -> Result<Number, MachineStubGen> {
+    let stub_gen = || functor_stub(atom!("msb"), 1);
+
+    match n1 {
+        Number::Fixnum(n) if n.get_num() > 0 => Ok(Number::arena_from(
+            63 - n.get_num().leading_zeros() as i64,
+            arena,
+        )),
+        Number::Integer(n) if n.is_positive() => Ok(Number::arena_from(n.bit_len() - 1, arena)),
+        Number::Fixnum(_) | Number::Integer(_) => Err(numerical_domain_error(
+            DomainErrorType::NotLessThanOne,
+            n1,
+            stub_gen,
+        )),
+        _ => Err(numerical_type_error(ValidType::Integer, n1, stub_gen)),
+    }
+}
+
+pub(crate) fn lsb(n1: Number, arena: &mut Arena) -> Result<Number, MachineStubGen> {
+    let stub_gen = || functor_stub(atom!("lsb"), 1);
+
+    match n1 {
+        Number::Fixnum(n) if n.get_num() > 0 => Ok(Number::arena_from(
+            n.get_num().trailing_zeros() as i64,
+            arena,
+        )),
+        Number::Integer(n) if n.is_positive() => {
+            Ok(Number::arena_from(n.trailing_zeros().unwrap_or(0), arena))
+        }
+        Number::Fixnum(_) | Number::Integer(_) => Err(numerical_domain_error(
+            DomainErrorType::NotLessThanOne,
+            n1,
+            stub_gen,
+        )),
+        _ => Err(numerical_type_error(ValidType::Integer, n1, stub_gen)),
+    }
+}
+
+pub(crate) fn popcount(n1: Number, arena: &mut Arena) -> Result<Number, MachineStubGen> {
+    let stub_gen = || functor_stub(atom!("popcount"), 1);
+
+    match n1 {
+        Number::Fixnum(n) if n.get_num() >= 0 => {
+            Ok(Number::arena_from(n.get_num().count_ones() as i64, arena))
+        }
+        Number::Integer(n) if !n.is_negative() => Ok(Number::arena_from(
+            (*n).clone().unsigned_abs().count_ones(),
+            arena,
+        )),
+        Number::Fixnum(_) | Number::Integer(_) => Err(numerical_domain_error(
+            DomainErrorType::NotLessThanZero,
+            n1,
+            stub_gen,
+        )),
+        _ => Err(numerical_type_error(ValidType::Integer, n1, stub_gen)),
+    }
+}
+
+fn number_to_integer(n: Number) -> Option<Integer> {
+    match n {
+        Number::Fixnum(n) => Some(Integer::from(n.get_num())),
+        Number::Integer(n) => Some((*n).clone()),
+        _ => None,
+    }
+}
+
+// (Base ^ Expo) mod Mod, computed without the power.
+pub(crate) fn powm(
+    base: Number,
+    expo: Number,
+    modulus: Number,
+    arena: &mut Arena,
+) -> Result<Number, MachineStubGen> {
+    let stub_gen = || functor_stub(atom!("powm"), 3);
+
+    let [b, e, m] = [base, expo, modulus].map(|n| number_to_integer(n).ok_or(n));
+
+    let (b, e, m) = match (b, e, m) {
+        (Ok(b), Ok(e), Ok(m)) => (b, e, m),
+        (Err(n), ..) | (_, Err(n), _) | (.., Err(n)) => {
+            return Err(numerical_type_error(ValidType::Integer, n, stub_gen));
+        }
+    };
+
+    if e.is_negative() {
+        return Err(numerical_domain_error(
+            DomainErrorType::NotLessThanZero,
+            expo,
+            stub_gen,
+        ));
+    }
+
+    if m.is_zero() {
+        return Err(zero_divisor_eval_error(stub_gen));
+    }
+
+    let divisor = m.clone().unsigned_abs();
+
+    // every integer is congruent to 0 modulo 1, a ring dashu
+    // doesn't represent.
+    if divisor.is_one() {
+        return Ok(Number::Fixnum(Fixnum::build_with(0)));
+    }
+
+    let ring = ConstDivisor::new(divisor);
+    let residue = IBig::from(ring.reduce(b).pow(&e.unsigned_abs()).residue());
+
+    // the result has the sign of Mod, as with mod/2.
+    if m.is_negative() && !residue.is_zero() {
+        Ok(Number::arena_from(residue + m, arena))
+    } else {
+        Ok(Number::arena_from(residue, arena))
+    }
+}
+
+// an integer drawn uniformly from [0, N).
+pub(crate) fn random(
+    n1: Number,
+    rng: &mut StdRng,
+    arena: &mut Arena,
+) -> Result<Number, MachineStubGen> {
+    let stub_gen = || functor_stub(atom!("random"), 1);
+
+    match n1 {
+        Number::Fixnum(n) if n.get_num() > 0 => {
+            Ok(Number::arena_from(rng.gen_range(0..n.get_num()), arena))
+        }
+        Number::Integer(n) if n.is_positive() => {
+            let bound = (*n).clone().unsigned_abs();
+            let mut bytes = vec![0u8; bound.bit_len().div_ceil(8)];
+            let excess_bits = bytes.len() * 8 - bound.bit_len();
+
+            loop {
+                rng.fill(&mut bytes[..]);
+
+                if let Some(last) = bytes.last_mut() {
+                    *last >>= excess_bits;
+                }
+
+                let r = UBig::from_le_bytes(&bytes);
+
+                if r < bound {
+                    return Ok(Number::arena_from(Integer::from(r), arena));
+                }
+            }
+        }
+        Number::Fixnum(_) | Number::Integer(_) => Err(numerical_domain_error(
+            DomainErrorType::NotLessThanOne,
+            n1,
+            stub_gen,
+        )),
+        _ => Err(numerical_type_error(ValidType::Integer, n1, stub_gen)),
+    }
+}
+
+// a float drawn uniformly from the open interval (0.0, 1.0).
+#[inline]
+pub(crate) fn random_float(rng: &mut StdRng) -> f64 {
+    loop {
+        let f: f64 = rng.gen();
+
+        if f > 0.0 {
+            return f;
+        }
+    }
+}
+
+#[inline]
+pub(crate) fn sinh(n1: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    unary_float_fn_template(n1, flags, |f| f.sinh())
+}
+
+#[inline]
+pub(crate) fn cosh(n1: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    unary_float_fn_template(n1, flags, |f| f.cosh())
+}
+
+#[inline]
+pub(crate) fn tanh(n1: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    unary_float_fn_template(n1, flags, |f| f.tanh())
+}
+
+#[inline]
+pub(crate) fn asinh(n1: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    unary_float_fn_template(n1, flags, |f| f.asinh())
+}
+
+#[inline]
+pub(crate) fn acosh(n1: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    unary_float_fn_template(n1, flags, |f| f.acosh())
+}
+
+#[inline]
+pub(crate) fn atanh(n1: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    unary_float_fn_template(n1, flags, |f| f.atanh())
+}
+
+pub(crate) fn log2(n1: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    if let Some(result) = log_pole(&n1, flags) {
+        return result;
+    }
+
+    match n1 {
+        // bignums can be too large for floats but their logarithms
+        // are not.
+        Number::Integer(n) if n.is_positive() => {
+            let shift = n.bit_len().saturating_sub(f64::MANTISSA_DIGITS as usize);
+            let mantissa = (&*n >> shift).to_f64().value();
+
+            Ok(mantissa.log2() + shift as f64)
+        }
+        _ => unary_float_fn_template(n1, flags, |f| f.log2()),
+    }
+}
+
+#[inline]
+pub(crate) fn cot(n1: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    unary_float_fn_template(n1, flags, |f| 1.0 / f.tan())
+}
+
+#[inline]
+pub(crate) fn acot(n1: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    unary_float_fn_template(n1, flags, |f| (1.0 / f).atan())
+}
+
+// the sine of f degrees, reduced to the first quadrant so that
+// multiples of 30 and 45 degrees are exact.
+fn sin_degrees(f: f64) -> f64 {
+    let f = f.rem_euclid(360.0);
+    let (f, sign) = if f > 180.0 {
+        (f - 180.0, -1.0)
+    } else {
+        (f, 1.0)
+    };
+    let f = if f > 90.0 { 180.0 - f } else { f };
+
+    let sin = if f == 0.0 {
+        0.0
+    } else if f == 30.0 {
+        0.5
+    } else if f == 90.0 {
+        1.0
+    } else if f <= 45.0 {
+        f.to_radians().sin()
+    } else {
+        (90.0 - f).to_radians().cos()
+    };
+
+    sign * sin
+}
+
+#[inline]
+pub(crate) fn sind(n1: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    unary_float_fn_template(n1, flags, sin_degrees)
+}
+
+#[inline]
+pub(crate) fn cosd(n1: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    unary_float_fn_template(n1, flags, |f| sin_degrees(f + 90.0))
+}
+
+#[inline]
+pub(crate) fn tand(n1: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    unary_float_fn_template(n1, flags, |f| {
+        let cos = sin_degrees(f + 90.0);
+
+        // the tangent is undefined at odd multiples of 90 degrees.
+        if cos == 0.0 {
+            f64::NAN
+        } else {
+            sin_degrees(f) / cos
+        }
+    })
+}
+
+#[inline]
+pub(crate) fn asind(n1: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    unary_float_fn_template(n1, flags, |f| f.asin().to_degrees())
+}
+
+#[inline]
+pub(crate) fn acosd(n1: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    unary_float_fn_template(n1, flags, |f| f.acos().to_degrees())
+}
+
+#[inline]
+pub(crate) fn atand(n1: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    unary_float_fn_template(n1, flags, |f| f.atan().to_degrees())
+}
+
+pub(crate) fn atan2d(n1: Number, n2: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    atan2(n1, n2, flags).map(f64::to_degrees)
+}
+
+pub(crate) fn copysign(n1: Number, n2: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    let f2 = float(n2, flags)?;
+    unary_float_fn_template(n1, flags, |f| f.copysign(f2))
+}
+
+// the float next to f1 in the direction of f2.
+pub(crate) fn nexttoward(n1: Number, n2: Number, flags: FloatFlags) -> Result<f64, MachineStubGen> {
+    let f2 = float(n2, flags)?;
+
+    unary_float_fn_template(n1, flags, |f1| {
+        if f1.is_nan() || f2.is_nan() {
+            f64::NAN
+        } else if f1 == f2 {
+            f2
+        } else if f1 == 0.0 {
+            f64::from_bits(1).copysign(f2)
+        } else if (f1 < f2) == (f1 > 0.0) {
+            f64::from_bits(f1.to_bits() + 1)
+        } else {
+            f64::from_bits(f1.to_bits() - 1)
+        }
+    })
+}
+
+// -1, 0 or 1 as n1 is less than, equal to or greater than n2.
+#[inline]
+pub(crate) fn cmpflags(n1: Number, n2: Number) -> Number {
+    Number::Fixnum(Fixnum::build_with(n1.cmp(&n2) as i64))
+}
+
+// the integer nearest to n1, rounding halfway cases away from zero.
+pub(crate) fn integer(n1: Number, arena: &mut Arena) -> Result<Number, MachineStubGen> {
+    match n1 {
+        Number::Fixnum(_) | Number::Integer(_) => Ok(n1),
+        Number::Float(OrderedFloat(f)) => floor(Number::Float(OrderedFloat(f.round())), arena),
+        Number::Rational(r) => {
+            let half = Rational::from_parts(Integer::ONE, UBig::from(2u8));
+            let n = ((*r).clone().abs() + half).floor();
+
+            if r.is_negative() {
+                Ok(Number::arena_from(-n, arena))
+            } else {
+                Ok(Number::arena_from(n, arena))
+            }
+        }
+        Number::Decimal(_) => round(n1, arena),
+    }
+}
+
+// the decimal of n1, as by decimal/1.
+pub(crate) fn decimal(
+    n1: Number,
+    flags: DecimalFlags,
+    arena: &mut Arena,
+) -> Result<Number, MachineStubGen> {
+    let stub_gen = || functor_stub(atom!("decimal"), 1);
+    let d = try_numeric_result!(to_decimal(&n1, flags), stub_gen)?;
+
+    Ok(Number::arena_from(d, arena))
+}
+
+// the decimal of n1 with n2 digits after its decimal point, as by
+// decimal/2.
+pub(crate) fn decimal_scaled(
+    n1: Number,
+    n2: Number,
+    flags: DecimalFlags,
+    arena: &mut Arena,
+) -> Result<Number, MachineStubGen> {
+    let stub_gen = || functor_stub(atom!("decimal"), 2);
+
+    let scale = match n2 {
+        Number::Fixnum(n) => usize::try_from(n.get_num()).ok(),
+        Number::Integer(ref n) => usize::try_from(&**n).ok(),
+        _ => return Err(numerical_type_error(ValidType::Integer, n2, stub_gen)),
+    };
+
+    let scale = match scale {
+        Some(scale) => scale,
+        None => {
+            return Err(numerical_domain_error(
+                DomainErrorType::NotLessThanZero,
+                n2,
+                stub_gen,
+            ))
+        }
+    };
+
+    let d = try_numeric_result!(to_decimal(&n1, flags), stub_gen)?;
+
+    Ok(Number::arena_from(d.rescale(scale, flags.rounding), arena))
+}
+
 impl MachineState {
     #[inline]
     pub fn get_number(&mut self, at: &ArithmeticTerm) -> Result<Number, MachineStub> {
@@ -1183,32 +1746,32 @@ pub(crate) fn arith_eval_by_metacall(
                             atom!("+") => self.interms.push(drop_iter_on_err!(
                                 self,
                                 iter,
-                                try_numeric_result!(add(a1, a2, &mut self.arena), stub_gen)
+                                try_numeric_result!(add(a1, a2, self.flags.float_flags, &mut self.arena), stub_gen)
                             )),
                             atom!("-") => self.interms.push(drop_iter_on_err!(
                                 self,
                                 iter,
-                                try_numeric_result!(sub(a1, a2, &mut self.arena), stub_gen)
+                                try_numeric_result!(sub(a1, a2, self.flags.float_flags, &mut self.arena), stub_gen)
                             )),
                             atom!("*") => self.interms.push(drop_iter_on_err!(
                                 self,
                                 iter,
-                                try_numeric_result!(mul(a1, a2, &mut self.arena), stub_gen)
+                                try_numeric_result!(mul(a1, a2, self.flags.float_flags, &mut self.arena), stub_gen)
                             )),
                             atom!("/") => self.interms.push(
-                                drop_iter_on_err!(self, iter, div(a1, a2))
+                                drop_iter_on_err!(self, iter, div(a1, a2, self.flags.float_flags, self.flags.decimal_flags, &mut self.arena))
                             ),
                             atom!("**") => self.interms.push(
-                                drop_iter_on_err!(self, iter, pow(a1, a2, atom!("is")))
+                                drop_iter_on_err!(self, iter, pow(a1, a2, atom!("is"), self.flags.float_flags))
                             ),
                             atom!("^") => self.interms.push(
-                                drop_iter_on_err!(self, iter, int_pow(a1, a2, &mut self.arena))
+                                drop_iter_on_err!(self, iter, int_pow(a1, a2, self.flags.float_flags, self.flags.decimal_flags, &mut self.arena))
                             ),
                             atom!("max") => self.interms.push(
-                                drop_iter_on_err!(self, iter, max(a1, a2))
+                                drop_iter_on_err!(self, iter, max(a1, a2, self.flags.float_flags))
                             ),
                             atom!("min") => self.interms.push(
-                                drop_iter_on_err!(self, iter, min(a1, a2))
+                                drop_iter_on_err!(self, iter, min(a1, a2, self.flags.float_flags))
                             ),
                             atom!("rdiv") => {
                                 let r1 = drop_iter_on_err!(
@@ -1258,11 +1821,26 @@ pub(crate) fn arith_eval_by_metacall(
                                 drop_iter_on_err!(self, iter, remainder(a1, a2, &mut self.arena))
                             ),
                             atom!("atan2") => self.interms.push(Number::Float(OrderedFloat(
-                                drop_iter_on_err!(self, iter, atan2(a1, a2))
+                                drop_iter_on_err!(self, iter, atan2(a1, a2, self.flags.float_flags))
                             ))),
                             atom!("gcd") => self.interms.push(
                                 drop_iter_on_err!(self, iter, gcd(a1, a2, &mut self.arena))
                             ),
+                            atom!("atan2d") => self.interms.push(Number::Float(OrderedFloat(
+                                drop_iter_on_err!(self, iter, atan2d(a1, a2, self.flags.float_flags))
+                            ))),
+                            atom!("copysign") => self.interms.push(Number::Float(OrderedFloat(
+                                drop_iter_on_err!(self, iter, copysign(a1, a2, self.flags.float_flags))
+                            ))),
+                            atom!("nexttoward") => self.interms.push(Number::Float(OrderedFloat(
+                                drop_iter_on_err!(self, iter, nexttoward(a1, a2, self.flags.float_flags))
+                            ))),
+                            atom!("cmpflags") => self.interms.push(cmpflags(a1, a2)),
+                            atom!("decimal") => self.interms.push(drop_iter_on_err!(
+                                self,
+                                iter,
+                                decimal_scaled(a1, a2, self.flags.decimal_flags, &mut self.arena)
+                            )),
                             _ => {
                                 let evaluable_stub = functor_stub(name, 2);
                                 let stub = stub_gen();
@@ -1282,50 +1860,114 @@ pub(crate) fn arith_eval_by_metacall(
                             atom!("-") => self.interms.push(neg(a1, &mut self.arena)),
                             atom!("+") => self.interms.push(a1),
                             atom!("cos") => self.interms.push(Number::Float(OrderedFloat(
-                                drop_iter_on_err!(self, iter, cos(a1))
+                                drop_iter_on_err!(self, iter, cos(a1, self.flags.float_flags))
                             ))),
                             atom!("sin") => self.interms.push(Number::Float(OrderedFloat(
-                                drop_iter_on_err!(self, iter, sin(a1))
+                                drop_iter_on_err!(self, iter, sin(a1, self.flags.float_flags))
                             ))),
                             atom!("tan") => self.interms.push(Number::Float(OrderedFloat(
-                                drop_iter_on_err!(self, iter, tan(a1))
+                                drop_iter_on_err!(self, iter, tan(a1, self.flags.float_flags))
                             ))),
                             atom!("float_fractional_part") => self.interms.push(Number::Float(OrderedFloat(
-                                drop_iter_on_err!(self, iter, float_fractional_part(a1))
+                                drop_iter_on_err!(self, iter, float_fractional_part(a1, self.flags.float_flags))
                             ))),
                             atom!("float_integer_part") => self.interms.push(Number::Float(OrderedFloat(
-                                drop_iter_on_err!(self, iter, float_integer_part(a1))
+                                drop_iter_on_err!(self, iter, float_integer_part(a1, self.flags.float_flags))
                             ))),
                             atom!("sqrt") => self.interms.push(Number::Float(OrderedFloat(
-                                drop_iter_on_err!(self, iter, sqrt(a1))
+                                drop_iter_on_err!(self, iter, sqrt(a1, self.flags.float_flags))
                             ))),
                             atom!("log") => self.interms.push(Number::Float(OrderedFloat(
-                                drop_iter_on_err!(self, iter, log(a1))
+                                drop_iter_on_err!(self, iter, log(a1, self.flags.float_flags))
                             ))),
                             atom!("exp") => self.interms.push(Number::Float(OrderedFloat(
-                                drop_iter_on_err!(self, iter, exp(a1))
+                                drop_iter_on_err!(self, iter, exp(a1, self.flags.float_flags))
                             ))),
                             atom!("acos") => self.interms.push(Number::Float(OrderedFloat(
-                                drop_iter_on_err!(self, iter, acos(a1))
+                                drop_iter_on_err!(self, iter, acos(a1, self.flags.float_flags))
                             ))),
                             atom!("asin") => self.interms.push(Number::Float(OrderedFloat(
-                                drop_iter_on_err!(self, iter, asin(a1))
+                                drop_iter_on_err!(self, iter, asin(a1, self.flags.float_flags))
                             ))),
                             atom!("atan") => self.interms.push(Number::Float(OrderedFloat(
-                                drop_iter_on_err!(self, iter, atan(a1))
+                                drop_iter_on_err!(self, iter, atan(a1, self.flags.float_flags))
                             ))),
                             atom!("abs") => self.interms.push(abs(a1, &mut self.arena)),
                             atom!("float") => self.interms.push(Number::Float(OrderedFloat(
-                                drop_iter_on_err!(self, iter, float(a1))
+                                drop_iter_on_err!(self, iter, float(a1, self.flags.float_flags))
                             ))),
-                            atom!("truncate") => self.interms.push(truncate(a1, &mut self.arena)),
+                            atom!("truncate") => self.interms.push(drop_iter_on_err!(self, iter, truncate(a1, &mut self.arena))),
                             atom!("round") => self.interms.push(drop_iter_on_err!(self, iter, round(a1, &mut self.arena))),
-                            atom!("ceiling") => self.interms.push(ceiling(a1, &mut self.arena)),
-                            atom!("floor") => self.interms.push(floor(a1, &mut self.arena)),
+                            atom!("ceiling") => self.interms.push(drop_iter_on_err!(self, iter, ceiling(a1, &mut self.arena))),
+                            atom!("floor") => self.interms.push(drop_iter_on_err!(self, iter, floor(a1, &mut self.arena))),
                             atom!("\\") => self.interms.push(
                                 drop_iter_on_err!(self, iter, bitwise_complement(a1, &mut self.arena))
                             ),
                             atom!("sign") => self.interms.push(a1.sign()),
+                            atom!("sinh") => self.interms.push(Number::Float(OrderedFloat(
+                                drop_iter_on_err!(self, iter, sinh(a1, self.flags.float_flags))
+                            ))),
+                            atom!("cosh") => self.interms.push(Number::Float(OrderedFloat(
+                                drop_iter_on_err!(self, iter, cosh(a1, self.flags.float_flags))
+                            ))),
+                            atom!("tanh") => self.interms.push(Number::Float(OrderedFloat(
+                                drop_iter_on_err!(self, iter, tanh(a1, self.flags.float_flags))
+                            ))),
+                            atom!("asinh") => self.interms.push(Number::Float(OrderedFloat(
+                                drop_iter_on_err!(self, iter, asinh(a1, self.flags.float_flags))
+                            ))),
+                            atom!("acosh") => self.interms.push(Number::Float(OrderedFloat(
+                                drop_iter_on_err!(self, iter, acosh(a1, self.flags.float_flags))
+                            ))),
+                            atom!("atanh") => self.interms.push(Number::Float(OrderedFloat(
+                                drop_iter_on_err!(self, iter, atanh(a1, self.flags.float_flags))
+                            ))),
+                            atom!("log2") => self.interms.push(Number::Float(OrderedFloat(
+                                drop_iter_on_err!(self, iter, log2(a1, self.flags.float_flags))
+                            ))),
+                            atom!("cot") => self.interms.push(Number::Float(OrderedFloat(
+                                drop_iter_on_err!(self, iter, cot(a1, self.flags.float_flags))
+                            ))),
+                            atom!("acot") => self.interms.push(Number::Float(OrderedFloat(
+                                drop_iter_on_err!(self, iter, acot(a1, self.flags.float_flags))
+                            ))),
+                            atom!("sind") => self.interms.push(Number::Float(OrderedFloat(
+                                drop_iter_on_err!(self, iter, sind(a1, self.flags.float_flags))
+                            ))),
+                            atom!("cosd") => self.interms.push(Number::Float(OrderedFloat(
+                                drop_iter_on_err!(self, iter, cosd(a1, self.flags.float_flags))
+                            ))),
+                            atom!("tand") => self.interms.push(Number::Float(OrderedFloat(
+                                drop_iter_on_err!(self, iter, tand(a1, self.flags.float_flags))
+                            ))),
+                            atom!("asind") => self.interms.push(Number::Float(OrderedFloat(
+                                drop_iter_on_err!(self, iter, asind(a1, self.flags.float_flags))
+                            ))),
+                            atom!("acosd") => self.interms.push(Number::Float(OrderedFloat(
+                                drop_iter_on_err!(self, iter, acosd(a1, self.flags.float_flags))
+                            ))),
+                            atom!("atand") => self.interms.push(Number::Float(OrderedFloat(
+                                drop_iter_on_err!(self, iter, atand(a1, self.flags.float_flags))
+                            ))),
+                            atom!("msb") => self.interms.push(
+                                drop_iter_on_err!(self, iter, msb(a1, &mut self.arena))
+                            ),
+                            atom!("lsb") => self.interms.push(
+                                drop_iter_on_err!(self, iter, lsb(a1, &mut self.arena))
+                            ),
+                            atom!("popcount") => self.interms.push(
+                                drop_iter_on_err!(self, iter, popcount(a1, &mut self.arena))
+                            ),
+                            atom!("trunc") => self.interms.push(drop_iter_on_err!(self, iter, truncate(a1, &mut self.arena))),
+                            atom!("integer") => self.interms.push(drop_iter_on_err!(self, iter, integer(a1, &mut self.arena))),
+                            atom!("decimal") => self.interms.push(drop_iter_on_err!(
+                                self,
+                                iter,
+                                decimal(a1, self.flags.decimal_flags, &mut self.arena)
+                            )),
+                            atom!("random") => self.interms.push(
+                                drop_iter_on_err!(self, iter, random(a1, &mut self.rng, &mut self.arena))
+                            ),
                             _ => {
                                 let evaluable_stub = functor_stub(name, 1);
                                 std::mem::drop(iter);
@@ -1340,6 +1982,16 @@ pub(crate) fn arith_eval_by_metacall(
                             }
                         }
 
+                        continue;
+                    } else if arity == 3 && name == atom!("powm") {
+                        let a3 = self.interms.pop().unwrap();
+                        let a2 = self.interms.pop().unwrap();
+                        let a1 = self.interms.pop().unwrap();
+
+                        self.interms.push(
+                            drop_iter_on_err!(self, iter, powm(a1, a2, a3, &mut self.arena))
+                        );
+
                         continue;
                     } else if arity == 0 {
                         match name {
@@ -1355,6 +2007,19 @@ pub(crate) fn arith_eval_by_metacall(
                                 self.interms.push(Number::Float(OrderedFloat(f64::EPSILON)));
                                 continue;
                             }
+                            atom!("inf") => {
+                                self.interms.push(Number::Float(OrderedFloat(f64::INFINITY)));
+                                continue;
+                            }
+                            atom!("nan") => {
+                                self.interms.push(Number::Float(OrderedFloat(f64::NAN)));
+                                continue;
+                            }
+                            atom!("random_float") => {
+                                let f = random_float(&mut self.rng);
+                                self.interms.push(Number::Float(OrderedFloat(f)));
+                                continue;
+                            }
                             _ => {
                             }
                         }
@@ -1381,6 +2046,9 @@ pub(crate) fn arith_eval_by_metacall(
                          (ArenaHeaderTag::Rational, r) => {
                              self.interms.push(Number::Rational(r));
                          }
+                         (ArenaHeaderTag::Decimal, d) => {
+                             self.interms.push(Number::Decimal(d));
+                         }
                          _ => {
                              std::mem::drop(iter);
 
@@ -1458,5 +2126,26 @@ fn arith_eval_by_metacall_tests() {
             wam.arith_eval_by_metacall(heap_loc_as_cell!(term_write_result.heap_loc)),
             Ok(Number::Fixnum(Fixnum::build_with(-1)))
         );
+
+        wam.heap.clear();
+
+        let term_write_result =
+            parse_and_write_parsed_term_to_heap(&mut wam, "powm(3, 100, 7).", &op_dir).unwrap();
+
+        assert_eq!(
+            wam.arith_eval_by_metacall(heap_loc_as_cell!(term_write_result.heap_loc)),
+            Ok(Number::Fixnum(Fixnum::build_with(4)))
+        );
+
+        wam.heap.clear();
+
+        let term_write_result =
+            parse_and_write_parsed_term_to_heap(&mut wam, "msb(1000) + popcount(255).", &op_dir)
+                .unwrap();
+
+        assert_eq!(
+            wam.arith_eval_by_metacall(heap_loc_as_cell!(term_write_result.heap_loc)),
+            Ok(Number::Fixnum(Fixnum::build_with(17)))
+        );
     }
 }
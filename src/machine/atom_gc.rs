@@ -0,0 +1,386 @@
+use crate::arena::ArenaHeaderTag;
+use crate::atom_table::*;
+use crate::forms::*;
+use crate::instructions::*;
+use crate::machine::gc::DEFAULT_ATOM_GC_THRESHOLD;
+use crate::machine::loader::CompilationTarget;
+use crate::machine::machine_indices::*;
+use crate::machine::qlf::QlfLoad;
+use crate::machine::threads;
+use crate::machine::Machine;
+use crate::parser::ast::*;
+use crate::types::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+use cpu_time::ProcessTime;
+
+use std::sync::Arc;
+
+#[inline]
+fn mark_cell(marks: &mut AtomMarks, cell: HeapCellValue) {
+    match cell.get_tag() {
+        HeapCellValueTag::Atom | HeapCellValueTag::PStr | HeapCellValueTag::CStr => {
+            marks.mark(cell_as_atom_cell!(cell).get_name());
+        }
+        _ => {}
+    }
+}
+
+// a consult in progress keeps its load state, which holds atoms in
+// Rust structures, on the heap between loader calls.
+#[inline]
+fn is_load_state(cell: HeapCellValue) -> bool {
+    read_heap_cell!(cell,
+        (HeapCellValueTag::Cons, ptr) => {
+            matches!(
+                ptr.get_tag(),
+                ArenaHeaderTag::LiveLoadState | ArenaHeaderTag::InactiveLoadState
+            )
+        }
+        _ => {
+            false
+        }
+    )
+}
+
+fn mark_literal(marks: &mut AtomMarks, literal: &Literal) {
+    match literal {
+        Literal::Atom(atom) | Literal::String(atom) => marks.mark(*atom),
+        _ => {}
+    }
+}
+
+fn mark_instruction(marks: &mut AtomMarks, instr: &Instruction) {
+    match instr {
+        Instruction::GetConstant(_, cell, _)
+        | Instruction::PutConstant(_, cell, _)
+        | Instruction::UnifyConstant(cell)
+        | Instruction::SetConstant(cell) => {
+            mark_cell(marks, *cell);
+        }
+        Instruction::GetPartialString(_, atom, ..)
+        | Instruction::PutPartialString(_, atom, ..)
+        | Instruction::GetStructure(_, atom, ..)
+        | Instruction::PutStructure(atom, ..)
+        | Instruction::CallNamed(_, atom, _)
+        | Instruction::ExecuteNamed(_, atom, _)
+        | Instruction::DefaultCallNamed(_, atom, _)
+        | Instruction::DefaultExecuteNamed(_, atom, _) => {
+            marks.mark(*atom);
+        }
+        Instruction::IndexingCode(indexing_lines) => {
+            for indexing_line in indexing_lines {
+                match indexing_line {
+                    IndexingLine::Indexing(IndexingInstruction::SwitchOnConstant(constants)) => {
+                        for literal in constants.keys() {
+                            mark_literal(marks, literal);
+                        }
+                    }
+                    IndexingLine::Indexing(IndexingInstruction::SwitchOnStructure(structures)) => {
+                        for (name, _) in structures.keys() {
+                            marks.mark(*name);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn mark_clause_index_info(marks: &mut AtomMarks, clause_index_info: &ClauseIndexInfo) {
+    match &clause_index_info.opt_arg_index_key {
+        OptArgIndexKey::Literal(_, _, literal, alternatives) => {
+            mark_literal(marks, literal);
+
+            for literal in alternatives {
+                mark_literal(marks, literal);
+            }
+        }
+        OptArgIndexKey::Structure(_, _, name, _) => {
+            marks.mark(*name);
+        }
+        OptArgIndexKey::List(..) | OptArgIndexKey::None => {}
+    }
+}
+
+fn mark_local_skeleton(marks: &mut AtomMarks, skeleton: &LocalPredicateSkeleton) {
+    if let Some(retracted_clauses) = &skeleton.retracted_dynamic_clauses {
+        for clause_index_info in retracted_clauses {
+            mark_clause_index_info(marks, clause_index_info);
+        }
+    }
+}
+
+fn mark_predicate_tables(
+    marks: &mut AtomMarks,
+    code_dir: &CodeDir,
+    op_dir: &OpDir,
+    meta_predicates: &MetaPredicateDir,
+    extensible_predicates: &ExtensiblePredicates,
+    local_extensible_predicates: &LocalExtensiblePredicates,
+) {
+    for (name, _) in code_dir.keys() {
+        marks.mark(*name);
+    }
+
+    for (name, _) in op_dir.keys() {
+        marks.mark(*name);
+    }
+
+    for (name, _) in meta_predicates.keys() {
+        marks.mark(*name);
+    }
+
+    for ((name, _), skeleton) in extensible_predicates {
+        marks.mark(*name);
+        mark_local_skeleton(marks, &skeleton.core);
+
+        for clause_index_info in &skeleton.clauses {
+            mark_clause_index_info(marks, clause_index_info);
+        }
+    }
+
+    for ((compilation_target, (name, _)), skeleton) in local_extensible_predicates {
+        if let CompilationTarget::Module(module_name) = compilation_target {
+            marks.mark(*module_name);
+        }
+
+        marks.mark(*name);
+        mark_local_skeleton(marks, skeleton);
+    }
+}
+
+fn mark_module(marks: &mut AtomMarks, module: &Module) {
+    marks.mark(module.module_decl.name);
+
+    for export in &module.module_decl.exports {
+        match export {
+            ModuleExport::OpDecl(op_decl) => marks.mark(op_decl.name),
+            ModuleExport::PredicateKey((name, _)) => marks.mark(*name),
+        }
+    }
+
+    mark_predicate_tables(
+        marks,
+        &module.code_dir,
+        &module.op_dir,
+        &module.meta_predicates,
+        &module.extensible_predicates,
+        &module.local_extensible_predicates,
+    );
+
+    if let ListingSource::File(file_name, _) = &module.listing_src {
+        marks.mark(*file_name);
+    }
+}
+
+impl Machine {
+    // marks the atoms reachable from the machine. the heap must have
+    // just been collected so that every cell left on it is live.
+    // returns None if a file is being consulted.
+    fn mark_atoms(&mut self, arity: usize) -> Option<AtomMarks> {
+        let mut marks = AtomMarks::new(&self.machine_st.atom_tbl);
+        let mut loading = false;
+
+        for cell in self.machine_st.heap.iter() {
+            loading |= is_load_state(*cell);
+            mark_cell(&mut marks, *cell);
+        }
+
+        for cell in self.machine_st.lifted_heap.iter() {
+            mark_cell(&mut marks, *cell);
+        }
+
+        for ball in std::iter::once(&self.machine_st.ball).chain(&self.machine_st.ball_stack) {
+            for cell in ball.stub.iter() {
+                mark_cell(&mut marks, *cell);
+            }
+        }
+
+        self.machine_st
+            .for_each_root(&mut self.indices.global_variables, arity, |_, cell| {
+                loading |= is_load_state(*cell);
+                mark_cell(&mut marks, *cell)
+            });
+
+        if loading {
+            return None;
+        }
+
+        // the keys of trailed global variables, which are restored on
+        // backtracking even if the variable was since removed.
+        let trail = &self.machine_st.trail;
+        let mut idx = 0;
+
+        while idx < trail.len() {
+            match trail[idx].get_tag() {
+                TrailEntryTag::TrailedBlackboardEntry => {
+                    marks.mark(Atom::from(trail[idx].get_value()));
+                }
+                TrailEntryTag::TrailedBlackboardOffset => {
+                    marks.mark(Atom::from(trail[idx].get_value()));
+                    // skip the raw value cell, which was visited as a root.
+                    idx += 1;
+                }
+                _ => {}
+            }
+
+            idx += 1;
+        }
+
+        for (key, (ball, _)) in self.indices.global_variables.iter() {
+            marks.mark(*key);
+
+            for cell in ball.stub.iter() {
+                mark_cell(&mut marks, *cell);
+            }
+        }
+
+        for instr in self.code.iter() {
+            mark_instruction(&mut marks, instr);
+        }
+
+        mark_predicate_tables(
+            &mut marks,
+            &self.indices.code_dir,
+            &self.indices.op_dir,
+            &self.indices.meta_predicates,
+            &self.indices.extensible_predicates,
+            &self.indices.local_extensible_predicates,
+        );
+
+        for (name, _) in self.indices.goal_expansion_indices.iter() {
+            marks.mark(*name);
+        }
+
+        for atom in self.machine_st.clause_locations.atoms() {
+            marks.mark(atom);
+        }
+
+        for atom in self.tracer.atoms() {
+            marks.mark(atom);
+        }
+
+        for atom in self.machine_st.prolog_flags.atoms() {
+            marks.mark(atom);
+        }
+
+        for atom in self.indices.tables.atoms() {
+            marks.mark(atom);
+        }
+
+        for cell in self.indices.tables.cells() {
+            mark_cell(&mut marks, cell);
+        }
+
+        for (module_name, module) in self.indices.modules.iter() {
+            marks.mark(*module_name);
+            mark_module(&mut marks, module);
+        }
+
+        for stream in self.indices.streams.iter() {
+            for atom in stream.atoms() {
+                marks.mark(atom);
+            }
+        }
+
+        for (alias, stream) in self.indices.stream_aliases.iter() {
+            marks.mark(*alias);
+
+            for atom in stream.atoms() {
+                marks.mark(atom);
+            }
+        }
+
+        for stream in [&self.user_input, &self.user_output, &self.user_error] {
+            for atom in stream.atoms() {
+                marks.mark(atom);
+            }
+        }
+
+        for load_context in self.load_contexts.iter() {
+            marks.mark(load_context.module);
+
+            for atom in load_context.stream.atoms() {
+                marks.mark(atom);
+            }
+
+            if let Some(QlfLoad::Recording(recorder)) = &load_context.qlf {
+                for (module_name, key, code) in recorder.predicates() {
+                    marks.mark(module_name);
+                    marks.mark(key.0);
+
+                    for instr in code {
+                        mark_instruction(&mut marks, instr);
+                    }
+                }
+            }
+        }
+
+        #[cfg(feature = "ffi")]
+        for atom in self.foreign_function_table.atoms() {
+            marks.mark(atom);
+        }
+
+        // messages and exit statuses outlive the threads that sent them.
+        threads::for_each_shared_cell(|cell| mark_cell(&mut marks, cell));
+
+        // as do the database updates other machines are yet to replay.
+        if let Some(shared_database) = &self.shared_database {
+            shared_database.for_each_pending_cell(|cell| mark_cell(&mut marks, cell));
+        }
+
+        Some(marks)
+    }
+
+    // expects the heap to have just been collected.
+    fn try_collect_atoms(&mut self, arity: usize) -> Option<usize> {
+        // atoms are shared by every machine in the process.
+        if Arc::strong_count(&self.machine_st.atom_tbl) > 1 {
+            return None;
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let start_time = ProcessTime::now();
+
+        let marks = self.mark_atoms(arity)?;
+        let reclaimed = AtomTable::collect(&self.machine_st.atom_tbl, &marks);
+        let gc = &mut self.machine_st.gc;
+
+        gc.atom_collections += 1;
+        gc.reclaimed_atoms += reclaimed as u64;
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            gc.atom_time += start_time.elapsed();
+        }
+
+        Some(reclaimed)
+    }
+
+    pub(super) fn collect_atoms(&mut self, arity: usize) -> usize {
+        let reclaimed = self.try_collect_atoms(arity).unwrap_or(0);
+
+        // the threshold is raised even if the atoms couldn't be
+        // collected, so that the attempt isn't repeated on every call.
+        if self.machine_st.gc.atom_threshold > 0 {
+            let live_atoms = self.machine_st.atom_tbl.num_dynamic_atoms();
+            self.machine_st.gc.atom_threshold =
+                std::cmp::max(DEFAULT_ATOM_GC_THRESHOLD, 2 * live_atoms);
+        }
+
+        reclaimed
+    }
+
+    /// Collects the heap and then reclaims the atoms no longer
+    /// referenced from the heap, the code area, the predicate, operator
+    /// and flag tables or stream aliases. Returns the number of atoms
+    /// reclaimed, which is zero if other machines share the atom table
+    /// or a file is being consulted.
+    pub fn garbage_collect_atoms(&mut self) -> usize {
+        self.garbage_collect();
+        self.collect_atoms(0)
+    }
+}
@@ -0,0 +1,84 @@
+use crate::machine::machine_state::MachineState;
+use crate::machine::parsed_results::Term;
+use crate::machine::profiler::*;
+use crate::machine::Machine;
+
+use indexmap::IndexMap;
+
+// the most calls recorded in the backtrace of an error.
+const MAX_BACKTRACE_DEPTH: usize = 64;
+
+impl MachineState {
+    /// The code of the calls active at p, innermost first: p, the
+    /// call it returns to and the calls the environments on the stack
+    /// return to.
+    pub(super) fn backtrace_sites(&self) -> Vec<usize> {
+        let mut sites = vec![self.p];
+
+        // cp is the continuation of the innermost environment too if
+        // no call was made since it was allocated.
+        if self.e == 0 || self.cp != self.stack.index_and_frame(self.e).prelude.cp {
+            sites.extend(self.cp.checked_sub(1));
+        }
+
+        let mut e = self.e;
+
+        while e != 0 && sites.len() < MAX_BACKTRACE_DEPTH {
+            let and_frame = self.stack.index_and_frame(e);
+
+            // cp follows the call it returns from.
+            sites.extend(and_frame.prelude.cp.checked_sub(1));
+            e = and_frame.prelude.e;
+        }
+
+        sites
+    }
+}
+
+impl Machine {
+    // the call at `site` as `clause(Module:Name/Arity, ClauseNum, File,
+    // Line)` if its clause was compiled from a file, else as
+    // `Module:Name/Arity`.
+    fn backtrace_frame(&self, starts: &[(usize, ProfiledPredicate)], site: usize) -> Option<Term> {
+        if let Some(location) = self.machine_st.clause_locations.get(site) {
+            return Some(Term::compound(
+                "clause",
+                [
+                    predicate_indicator((location.module_name, location.key)),
+                    Term::integer(location.clause_num as u64),
+                    Term::atom(location.file.as_str().to_string()),
+                    Term::integer(location.line as u64),
+                ],
+            ));
+        }
+
+        predicate_at(starts, site).map(predicate_indicator)
+    }
+
+    /// Unifies register 1 with the backtrace of the ball, the list of
+    /// the calls it was thrown from, innermost first, which is empty
+    /// unless the `backtrace` flag was set when it was thrown.
+    pub(crate) fn ball_backtrace(&mut self) {
+        let starts = if self.machine_st.ball.backtrace.is_empty() {
+            vec![]
+        } else {
+            code_starts(&self.indices)
+        };
+
+        let mut frames: Vec<Term> = self
+            .machine_st
+            .ball
+            .backtrace
+            .iter()
+            .filter_map(|&site| self.backtrace_frame(&starts, site))
+            .collect();
+
+        // p and a stale cp may both be in the clause thrown from.
+        if frames.len() > 1 && frames[0] == frames[1] {
+            frames.remove(1);
+        }
+
+        let frames = self.term_to_heap(&Term::list(frames), &mut IndexMap::new());
+        unify!(self.machine_st, frames, self.machine_st.registers[1]);
+    }
+}
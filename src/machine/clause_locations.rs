@@ -0,0 +1,170 @@
+use crate::atom_table::*;
+use crate::forms::PredicateKey;
+use crate::machine::machine_state::MachineState;
+use crate::parser::ast::Fixnum;
+use crate::types::*;
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// The source of a clause compiled from a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ClauseLocation {
+    pub(crate) module_name: Atom,
+    pub(crate) key: PredicateKey,
+    /// The position of the clause in its predicate when it was
+    /// compiled, counting from 1.
+    pub(crate) clause_num: usize,
+    pub(crate) file: Atom,
+    pub(crate) line: usize,
+}
+
+/// The locations of the clauses compiled from files, by the code they
+/// were compiled to.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ClauseLocations {
+    // the end of the code of each clause and its location, by the
+    // start of its code.
+    clauses: BTreeMap<usize, (usize, ClauseLocation)>,
+}
+
+impl ClauseLocations {
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.clauses.is_empty()
+    }
+
+    pub(crate) fn insert(&mut self, code: Range<usize>, location: ClauseLocation) {
+        self.clauses.insert(code.start, (code.end, location));
+    }
+
+    /// The location of the clause compiled to the code at `p`.
+    pub(crate) fn get(&self, p: usize) -> Option<&ClauseLocation> {
+        let (_, (end, location)) = self.clauses.range(..=p).next_back()?;
+
+        if p < *end {
+            Some(location)
+        } else {
+            None
+        }
+    }
+
+    /// The clauses compiled to code within `code`.
+    pub(crate) fn within(
+        &self,
+        code: Range<usize>,
+    ) -> impl Iterator<Item = (Range<usize>, &ClauseLocation)> {
+        let end = code.end;
+
+        self.clauses
+            .range(code)
+            .filter(move |(_, (clause_end, _))| *clause_end <= end)
+            .map(|(start, (end, location))| (*start..*end, location))
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (Range<usize>, &ClauseLocation)> {
+        self.within(0..usize::MAX)
+    }
+
+    pub(crate) fn atoms(&self) -> impl Iterator<Item = Atom> + '_ {
+        self.clauses
+            .values()
+            .flat_map(|(_, location)| [location.module_name, location.key.0, location.file])
+    }
+}
+
+impl MachineState {
+    // the code of the innermost call made from a clause compiled from
+    // a file among the call at p and the calls it returns to.
+    fn located_call_site(&self) -> Option<usize> {
+        if self.clause_locations.get(self.p).is_some() {
+            return Some(self.p);
+        }
+
+        let mut e = self.e;
+        let mut cp = self.cp;
+
+        loop {
+            // cp follows the call it returns from.
+            if let Some(site) = cp.checked_sub(1) {
+                if self.clause_locations.get(site).is_some() {
+                    return Some(site);
+                }
+            }
+
+            if e == 0 {
+                return None;
+            }
+
+            let and_frame = self.stack.index_and_frame(e);
+
+            cp = and_frame.prelude.cp;
+            e = and_frame.prelude.e;
+        }
+    }
+
+    /// Rewrites the context `Context` of the `error(_, Context)` ball
+    /// at `h` to `context(Context, file(File, Line))`, the file and
+    /// line of the clause of the failing call, if it was compiled from
+    /// a file. Returns the code of the call.
+    pub(super) fn add_call_location(&mut self, h: usize) -> Option<usize> {
+        if self.clause_locations.is_empty() || self.heap[h] != atom_as_cell!(atom!("error"), 2) {
+            return None;
+        }
+
+        let site = self.located_call_site()?;
+        let location = *self.clause_locations.get(site)?;
+
+        let context = self.heap[h + 2];
+        let c = self.heap.len();
+
+        self.heap.extend([
+            atom_as_cell!(atom!("context"), 2),
+            context,
+            str_loc_as_cell!(c + 3),
+            atom_as_cell!(atom!("file"), 2),
+            atom_as_cell!(location.file),
+            fixnum_as_cell!(Fixnum::build_with(location.line as i64)),
+        ]);
+
+        self.heap[h + 2] = str_loc_as_cell!(c);
+
+        Some(site)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(line: usize) -> ClauseLocation {
+        ClauseLocation {
+            module_name: atom!("user"),
+            key: (atom!("f"), 1),
+            clause_num: 1,
+            file: atom!("f.pl"),
+            line,
+        }
+    }
+
+    #[test]
+    fn clause_locations_cover_their_code() {
+        let mut clause_locations = ClauseLocations::default();
+
+        clause_locations.insert(10..20, location(1));
+        clause_locations.insert(25..30, location(4));
+
+        assert_eq!(clause_locations.get(9), None);
+        assert_eq!(clause_locations.get(10), Some(&location(1)));
+        assert_eq!(clause_locations.get(19), Some(&location(1)));
+        assert_eq!(clause_locations.get(20), None);
+        assert_eq!(clause_locations.get(29), Some(&location(4)));
+
+        let within: Vec<_> = clause_locations
+            .within(0..25)
+            .map(|(code, _)| code)
+            .collect();
+
+        assert_eq!(within, vec![10..20]);
+    }
+}
@@ -3,10 +3,12 @@
 use crate::forms::*;
 use crate::indexing::{merge_clause_index, remove_index};
 use crate::instructions::*;
+use crate::machine::clause_locations::ClauseLocation;
 use crate::machine::load_state::*;
 use crate::machine::loader::*;
 use crate::machine::machine_errors::*;
 use crate::machine::preprocessor::*;
+use crate::machine::qlf::ClauseLine;
 use crate::machine::term_stream::*;
 use crate::machine::*;
 use crate::parser::ast::*;
@@ -1268,16 +1270,38 @@ fn compile(
         let mut code_ptr = code_len;
 
         let mut clauses = vec![];
+        let mut lines = vec![];
         let mut preprocessor = Preprocessor::new(settings);
 
-        for term in predicates.predicates.drain(0..) {
+        for (term, line) in predicates.predicates.drain(0..) {
             clauses.push(self.try_term_to_tl(term, &mut preprocessor)?);
+            lines.push(line);
         }
 
         let mut cg = CodeGenerator::new(&LS::machine_st(&mut self.payload).atom_tbl, settings);
 
         let mut code = cg.compile_predicate(clauses)?;
 
+        let first_clause_num = if settings.is_extensible {
+            self.wam_prelude
+                .indices
+                .get_predicate_skeleton(&predicates.compilation_target, &key)
+                .map_or(1, |skeleton| skeleton.clauses.len() + 1)
+        } else {
+            1
+        };
+
+        let clause_locations: Vec<_> = cg
+            .clause_code_ranges
+            .iter()
+            .zip(first_clause_num..)
+            .zip(lines)
+            .filter_map(|((range, clause_num), line)| {
+                let code = code_len + range.start..code_len + range.end;
+                line.map(|line| (code, clause_num, line))
+            })
+            .collect();
+
         if settings.is_extensible {
             let mut clause_clause_locs = VecDeque::new();
 
@@ -1356,9 +1380,140 @@ fn compile(
         );
 
         self.wam_prelude.code.extend(code);
+        self.add_clause_locations(predicates.compilation_target, key, clause_locations);
+
         Ok(code_index)
     }
 
+    // records each range of code of a clause of `key` with the
+    // clause's number and its line in the file being loaded.
+    fn add_clause_locations(
+        &mut self,
+        compilation_target: CompilationTarget,
+        key: PredicateKey,
+        clauses: Vec<(Range<usize>, usize, usize)>,
+    ) {
+        if clauses.is_empty() {
+            return;
+        }
+
+        let file = match self.listing_src_file_name() {
+            Some(file) => file,
+            None => return,
+        };
+
+        let clause_locations = &mut LS::machine_st(&mut self.payload).clause_locations;
+
+        for (code, clause_num, line) in clauses {
+            let location = ClauseLocation {
+                module_name: compilation_target.module_name(),
+                key,
+                clause_num,
+                file,
+                line,
+            };
+
+            clause_locations.insert(code, location);
+        }
+    }
+
+    fn add_to_listing_src_module(&mut self, key: PredicateKey, code_index: CodeIndex) {
+        if let Some(filename) = self.listing_src_file_name() {
+            if let Some(ref mut module) = self.wam_prelude.indices.modules.get_mut(&filename) {
+                let index_ptr = code_index.get();
+                let code_index = *module.code_dir.entry(key).or_insert(code_index);
+
+                set_code_index(
+                    &mut self.payload.retraction_info,
+                    &CompilationTarget::Module(filename),
+                    key,
+                    code_index,
+                    index_ptr,
+                );
+            }
+        }
+    }
+
+    // records the code of a static predicate compiled at `code_len`
+    // if the file being loaded is compiled to a .qlf file.
+    fn record_qlf_predicate(
+        &mut self,
+        compilation_target: CompilationTarget,
+        key: PredicateKey,
+        code_len: usize,
+    ) {
+        let recorder = self
+            .wam_prelude
+            .load_contexts
+            .last_mut()
+            .and_then(LoadContext::qlf_recorder);
+
+        if let Some(recorder) = recorder {
+            let code_end = self.wam_prelude.code.len();
+            let clause_lines = LS::machine_st(&mut self.payload)
+                .clause_locations
+                .within(code_len..code_end)
+                .map(|(code, location)| ClauseLine {
+                    code: code.start - code_len..code.end - code_len,
+                    clause_num: location.clause_num,
+                    line: location.line,
+                })
+                .collect();
+
+            recorder.record_predicate(
+                compilation_target.module_name(),
+                key,
+                self.wam_prelude.code[code_len..].to_vec(),
+                clause_lines,
+            );
+        }
+    }
+
+    /// Installs the code of a static predicate read from a .qlf file.
+    pub(crate) fn submit_precompiled_predicate(
+        &mut self,
+        module_name: Atom,
+        key: PredicateKey,
+        code: Vec<Instruction>,
+        clause_lines: Vec<ClauseLine>,
+    ) -> Result<(), SessionError> {
+        let compilation_target = match module_name {
+            atom!("user") => CompilationTarget::User,
+            _ => CompilationTarget::Module(module_name),
+        };
+
+        let code_index = self.get_or_insert_code_index(key, compilation_target);
+
+        LS::err_on_builtin_overwrite(self, key)?;
+
+        print_overwrite_warning(&compilation_target, code_index.get(), key, false);
+
+        set_code_index(
+            &mut self.payload.retraction_info,
+            &compilation_target,
+            key,
+            code_index,
+            IndexPtr::index(self.wam_prelude.code.len()),
+        );
+
+        let code_len = self.wam_prelude.code.len();
+
+        self.wam_prelude.code.extend(code);
+        self.add_to_listing_src_module(key, code_index);
+
+        let clause_locations = clause_lines
+            .into_iter()
+            .map(|clause_line| {
+                let code = code_len + clause_line.code.start..code_len + clause_line.code.end;
+                (code, clause_line.clause_num, clause_line.line)
+            })
+            .collect();
+
+        self.add_clause_locations(compilation_target, key, clause_locations);
+
+        Ok(())
+    }
+
     fn extend_local_predicate_skeleton(
         &mut self,
         compilation_target: &CompilationTarget,
@@ -1470,6 +1625,55 @@ fn push_back_to_local_predicate_skeleton(
         }
     }
 
+    /// Invalidates the just-in-time indices of the clauses of `key` in
+    /// `positions`, given the number of its clauses, ahead of changing
+    /// the subsequences they're in.
+    fn invalidate_jit_indices(
+        &mut self,
+        compilation_target: &CompilationTarget,
+        key: &PredicateKey,
+        positions: impl FnOnce(usize) -> Range<usize>,
+    ) {
+        if self.wam_prelude.indices.jit_indices.is_empty() {
+            return;
+        }
+
+        let chain_loc = self
+            .wam_prelude
+            .indices
+            .get_predicate_code_index(key.0, key.1, compilation_target.module_name())
+            .and_then(|code_index| code_index.local());
+
+        if let Some(chain_loc) = chain_loc {
+            self.wam_prelude
+                .indices
+                .jit_indices
+                .invalidate_chain(chain_loc);
+        }
+
+        let index_locs: Vec<usize> = match self
+            .wam_prelude
+            .indices
+            .get_predicate_skeleton(compilation_target, key)
+        {
+            Some(skeleton) => positions(skeleton.clauses.len())
+                .filter_map(|pos| skeleton.clauses.get(pos))
+                .filter_map(|clause| clause.opt_arg_index_key.switch_on_term_loc())
+                .collect(),
+            None => return,
+        };
+
+        let machine_st = LS::machine_st(&mut self.payload);
+
+        for index_loc in index_locs {
+            self.wam_prelude.indices.jit_indices.invalidate(
+                index_loc,
+                self.wam_prelude.code,
+                machine_st,
+            );
+        }
+    }
+
     pub(super) fn incremental_compile_clause(
         &mut self,
         key: PredicateKey,
@@ -1478,6 +1682,8 @@ pub(super) fn incremental_compile_clause(
         non_counted_bt: bool,
         append_or_prepend: AppendOrPrepend,
     ) -> Result<CodeIndex, SessionError> {
+        self.wam_prelude.indices.tables.invalidate_dynamic(&key);
+
         let settings = match self
             .wam_prelude
             .indices
@@ -1523,6 +1729,13 @@ pub(super) fn incremental_compile_clause(
 
         standalone_skeleton.clauses[0].clause_start += code_len;
 
+        // the clause joins the subsequence of the last clause, or of
+        // the first if it's prepended.
+        self.invalidate_jit_indices(&compilation_target, &key, |len| match append_or_prepend {
+            AppendOrPrepend::Append => len.saturating_sub(1)..len,
+            AppendOrPrepend::Prepend => 0..1,
+        });
+
         let skeleton = match self
             .wam_prelude
             .indices
@@ -1616,6 +1829,8 @@ pub(super) fn incremental_compile_clause(
     }
 
     pub(super) fn retract_dynamic_clause(&mut self, key: PredicateKey, target_pos: usize) -> usize {
+        self.wam_prelude.indices.tables.invalidate_dynamic(&key);
+
         let skeleton = match self
             .wam_prelude
             .indices
@@ -1658,6 +1873,12 @@ pub(super) fn retract_dynamic_clause(&mut self, key: PredicateKey, target_pos: u
 
     pub(super) fn retract_clause(&mut self, key: PredicateKey, target_pos: usize) -> usize {
         let payload_compilation_target = self.payload.compilation_target;
+
+        // removing the clause may merge the subsequences around it.
+        self.invalidate_jit_indices(&payload_compilation_target, &key, |_| {
+            target_pos.saturating_sub(1)..target_pos + 2
+        });
+
         let code_index = self.get_or_insert_code_index(key, payload_compilation_target);
 
         let skeleton = self
@@ -2166,14 +2387,31 @@ pub(super) fn compile_and_submit(&mut self) -> Result<(), SessionError> {
         if predicate_info.compile_incrementally() {
             let predicates = self.payload.predicates.take();
 
-            for term in predicates.predicates {
+            for (term, line) in predicates.predicates {
+                let compilation_target = self.payload.predicates.compilation_target;
+                let code_len = self.wam_prelude.code.len();
+
                 self.incremental_compile_clause(
                     key,
                     term,
-                    self.payload.predicates.compilation_target,
+                    compilation_target,
                     non_counted_bt,
                     AppendOrPrepend::Append,
                 )?;
+
+                let clause_num = self
+                    .wam_prelude
+                    .indices
+                    .get_predicate_skeleton(&compilation_target, &key)
+                    .map_or(1, |skeleton| skeleton.clauses.len());
+
+                let code_end = self.wam_prelude.code.len();
+                let clauses = line
+                    .map(|line| (code_len..code_end, clause_num, line))
+                    .into_iter()
+                    .collect();
+
+                self.add_clause_locations(compilation_target, key, clauses);
             }
         } else {
             if is_cross_module_clause && !local_predicate_info.is_extensible {
@@ -2229,22 +2467,15 @@ pub(super) fn compile_and_submit(&mut self) -> Result<(), SessionError> {
             };
 
             let predicates = self.payload.predicates.take();
+            let compilation_target = predicates.compilation_target;
+            let code_len = self.wam_prelude.code.len();
             let code_index = self.compile(key, predicates, settings)?;
 
-            if let Some(filename) = self.listing_src_file_name() {
-                if let Some(ref mut module) = self.wam_prelude.indices.modules.get_mut(&filename) {
-                    let index_ptr = code_index.get();
-                    let code_index = *module.code_dir.entry(key).or_insert(code_index);
-
-                    set_code_index(
-                        &mut self.payload.retraction_info,
-                        &CompilationTarget::Module(filename),
-                        key,
-                        code_index,
-                        index_ptr,
-                    );
-                }
+            if !settings.is_extensible && !is_cross_module_clause {
+                self.record_qlf_predicate(compilation_target, key, code_len);
             }
+
+            self.add_to_listing_src_module(key, code_index);
         }
 
         if predicate_info.is_dynamic {
@@ -1,6 +1,17 @@
+use crate::machine::gc::DEFAULT_GC_THRESHOLD;
+use crate::machine::limits::ResourceLimits;
+use crate::machine::sandbox::SandboxPolicy;
+
 pub struct MachineConfig {
     pub streams: StreamConfig,
     pub toplevel: &'static str,
+    /// heap size, in cells, at which the garbage collector first runs.
+    /// zero disables automatic collection.
+    pub gc_threshold: usize,
+    /// limits on the resources used by queries, none by default.
+    pub limits: ResourceLimits,
+    /// the capabilities allowed to queries, all of them by default.
+    pub sandbox: SandboxPolicy,
 }
 
 pub enum StreamConfig {
@@ -13,6 +24,9 @@ fn default() -> Self {
         MachineConfig {
             streams: StreamConfig::Stdio,
             toplevel: include_str!("../toplevel.pl"),
+            gc_threshold: DEFAULT_GC_THRESHOLD,
+            limits: ResourceLimits::default(),
+            sandbox: SandboxPolicy::default(),
         }
     }
 }
@@ -29,4 +43,19 @@ pub fn with_toplevel(mut self, toplevel: &'static str) -> Self {
         self.toplevel = toplevel;
         self
     }
+
+    pub fn with_gc_threshold(mut self, gc_threshold: usize) -> Self {
+        self.gc_threshold = gc_threshold;
+        self
+    }
+
+    pub fn with_resource_limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn with_sandbox(mut self, sandbox: SandboxPolicy) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
 }
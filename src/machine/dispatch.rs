@@ -4,7 +4,9 @@
 use crate::machine::arithmetic_ops::*;
 use crate::machine::machine_errors::*;
 use crate::machine::machine_state::*;
+use crate::machine::sandbox::Capability;
 use crate::machine::*;
+use crate::parser::decimal::Decimal;
 use crate::types::*;
 
 use crate::try_numeric_result;
@@ -57,6 +59,12 @@ macro_rules! try_or_throw_gen {
     }};
 }
 
+macro_rules! check_capability {
+    ($s:expr, $capability:expr) => {
+        try_or_throw!($s.machine_st, $s.check_capability($capability))
+    };
+}
+
 static INSTRUCTIONS_PER_INTERRUPT_POLL: usize = 256;
 
 impl MachineState {
@@ -182,6 +190,7 @@ fn is(&mut self, r: RegType, at: ArithmeticTerm) -> CallResult {
             }
             Number::Integer(n) => self.unify_big_int(n, n1),
             Number::Rational(n) => self.unify_rational(n, n1),
+            Number::Decimal(n) => self.unify_decimal(n, n1),
         }
 
         Ok(())
@@ -230,7 +239,7 @@ pub(crate) fn select_switch_on_term_index(
             }
             (HeapCellValueTag::Cons, ptr) => {
                 match ptr.get_tag() {
-                    ArenaHeaderTag::Rational | ArenaHeaderTag::Integer => {
+                    ArenaHeaderTag::Rational | ArenaHeaderTag::Integer | ArenaHeaderTag::Decimal => {
                         c
                     }
                     _ => {
@@ -268,6 +277,9 @@ pub(crate) fn constant_to_literal(&self, addr: HeapCellValue) -> Literal {
                     (ArenaHeaderTag::Rational, r) => {
                         Literal::Rational(r)
                     }
+                    (ArenaHeaderTag::Decimal, d) => {
+                        Literal::Decimal(d)
+                    }
                     (ArenaHeaderTag::Integer, n) => {
                         let result = (&*n).try_into();
 
@@ -403,6 +415,37 @@ pub(super) fn find_living_dynamic(
 
     #[inline(always)]
     fn execute_switch_on_term(&mut self) {
+        let switch_arg = match &self.code[self.machine_st.p] {
+            Instruction::IndexingCode(indexing_lines) => match &indexing_lines[0] {
+                &IndexingLine::Indexing(IndexingInstruction::SwitchOnTerm(arg, ..)) => arg,
+                _ => {
+                    unreachable!()
+                }
+            },
+            _ => {
+                unreachable!()
+            }
+        };
+
+        let mut index = 0;
+        let mut addr = self
+            .machine_st
+            .store(self.machine_st.deref(self.machine_st.registers[switch_arg]));
+
+        // switch on the other bound arguments and the subterms of the
+        // bound arguments too if an index on them helps.
+        if let Some((jit_index, jit_addr)) = self.select_jit_index(switch_arg, addr) {
+            index = jit_index;
+            addr = jit_addr;
+        }
+
+        self.execute_indexing_lines(index, addr);
+    }
+
+    // dispatches on `addr` from line `index` of the indexing code at
+    // the current `p`.
+    #[inline(always)]
+    fn execute_indexing_lines(&mut self, mut index: usize, addr: HeapCellValue) {
         #[inline(always)]
         fn dynamic_external_of_clause_is_valid(machine: &mut Machine, p: usize) -> bool {
             if let Instruction::DynamicInternalElse(..) = machine.code[p] {
@@ -420,16 +463,6 @@ fn dynamic_external_of_clause_is_valid(machine: &mut Machine, p: usize) -> bool
 
         let indexing_lines = self.code[self.machine_st.p].to_indexing_line_mut().unwrap();
 
-        let mut index = 0;
-        let addr = match &indexing_lines[0] {
-            &IndexingLine::Indexing(IndexingInstruction::SwitchOnTerm(arg, ..)) => self
-                .machine_st
-                .store(self.machine_st.deref(self.machine_st.registers[arg])),
-            _ => {
-                unreachable!()
-            }
-        };
-
         loop {
             match &indexing_lines[index] {
                 &IndexingLine::Indexing(IndexingInstruction::SwitchOnTerm(_, v, c, l, s)) => {
@@ -599,7 +632,15 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
 
                         self.machine_st.interms[t - 1] = try_or_throw_gen!(
                             &mut self.machine_st,
-                            try_numeric_result!(add(n1, n2, &mut self.machine_st.arena), stub_gen)
+                            try_numeric_result!(
+                                add(
+                                    n1,
+                                    n2,
+                                    self.machine_st.flags.float_flags,
+                                    &mut self.machine_st.arena
+                                ),
+                                stub_gen
+                            )
                         );
 
                         self.machine_st.p += 1;
@@ -612,7 +653,15 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
 
                         self.machine_st.interms[t - 1] = try_or_throw_gen!(
                             &mut self.machine_st,
-                            try_numeric_result!(sub(n1, n2, &mut self.machine_st.arena), stub_gen)
+                            try_numeric_result!(
+                                sub(
+                                    n1,
+                                    n2,
+                                    self.machine_st.flags.float_flags,
+                                    &mut self.machine_st.arena
+                                ),
+                                stub_gen
+                            )
                         );
 
                         self.machine_st.p += 1;
@@ -625,7 +674,15 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
 
                         self.machine_st.interms[t - 1] = try_or_throw_gen!(
                             &mut self.machine_st,
-                            try_numeric_result!(mul(n1, n2, &mut self.machine_st.arena), stub_gen)
+                            try_numeric_result!(
+                                mul(
+                                    n1,
+                                    n2,
+                                    self.machine_st.flags.float_flags,
+                                    &mut self.machine_st.arena
+                                ),
+                                stub_gen
+                            )
                         );
 
                         self.machine_st.p += 1;
@@ -634,8 +691,10 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                         let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
                         let n2 = try_or_throw!(self.machine_st, self.machine_st.get_number(a2));
 
-                        self.machine_st.interms[t - 1] =
-                            try_or_throw_gen!(&mut self.machine_st, max(n1, n2));
+                        self.machine_st.interms[t - 1] = try_or_throw_gen!(
+                            &mut self.machine_st,
+                            max(n1, n2, self.machine_st.flags.float_flags)
+                        );
 
                         self.machine_st.p += 1;
                     }
@@ -643,8 +702,10 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                         let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
                         let n2 = try_or_throw!(self.machine_st, self.machine_st.get_number(a2));
 
-                        self.machine_st.interms[t - 1] =
-                            try_or_throw_gen!(&mut self.machine_st, min(n1, n2));
+                        self.machine_st.interms[t - 1] = try_or_throw_gen!(
+                            &mut self.machine_st,
+                            min(n1, n2, self.machine_st.flags.float_flags)
+                        );
 
                         self.machine_st.p += 1;
                     }
@@ -654,7 +715,13 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
 
                         self.machine_st.interms[t - 1] = try_or_throw_gen!(
                             &mut self.machine_st,
-                            int_pow(n1, n2, &mut self.machine_st.arena)
+                            int_pow(
+                                n1,
+                                n2,
+                                self.machine_st.flags.float_flags,
+                                self.machine_st.flags.decimal_flags,
+                                &mut self.machine_st.arena
+                            )
                         );
 
                         self.machine_st.p += 1;
@@ -674,8 +741,10 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                         let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
                         let n2 = try_or_throw!(self.machine_st, self.machine_st.get_number(a2));
 
-                        self.machine_st.interms[t - 1] =
-                            try_or_throw_gen!(&mut self.machine_st, pow(n1, n2, atom!("**")));
+                        self.machine_st.interms[t - 1] = try_or_throw_gen!(
+                            &mut self.machine_st,
+                            pow(n1, n2, atom!("**"), self.machine_st.flags.float_flags)
+                        );
 
                         self.machine_st.p += 1;
                     }
@@ -752,8 +821,16 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                         let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
                         let n2 = try_or_throw!(self.machine_st, self.machine_st.get_number(a2));
 
-                        self.machine_st.interms[t - 1] =
-                            try_or_throw_gen!(&mut self.machine_st, div(n1, n2));
+                        self.machine_st.interms[t - 1] = try_or_throw_gen!(
+                            &mut self.machine_st,
+                            div(
+                                n1,
+                                n2,
+                                self.machine_st.flags.float_flags,
+                                self.machine_st.flags.decimal_flags,
+                                &mut self.machine_st.arena,
+                            )
+                        );
 
                         self.machine_st.p += 1;
                     }
@@ -837,81 +914,99 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                     &Instruction::Cos(ref a1, t) => {
                         let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
 
-                        self.machine_st.interms[t - 1] = Number::Float(OrderedFloat(
-                            try_or_throw_gen!(&mut self.machine_st, cos(n1)),
-                        ));
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                cos(n1, self.machine_st.flags.float_flags)
+                            )));
 
                         self.machine_st.p += 1;
                     }
                     &Instruction::Sin(ref a1, t) => {
                         let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
 
-                        self.machine_st.interms[t - 1] = Number::Float(OrderedFloat(
-                            try_or_throw_gen!(&mut self.machine_st, sin(n1)),
-                        ));
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                sin(n1, self.machine_st.flags.float_flags)
+                            )));
 
                         self.machine_st.p += 1;
                     }
                     &Instruction::Tan(ref a1, t) => {
                         let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
 
-                        self.machine_st.interms[t - 1] = Number::Float(OrderedFloat(
-                            try_or_throw_gen!(&mut self.machine_st, tan(n1)),
-                        ));
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                tan(n1, self.machine_st.flags.float_flags)
+                            )));
 
                         self.machine_st.p += 1;
                     }
                     &Instruction::Sqrt(ref a1, t) => {
                         let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
 
-                        self.machine_st.interms[t - 1] = Number::Float(OrderedFloat(
-                            try_or_throw_gen!(&mut self.machine_st, sqrt(n1)),
-                        ));
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                sqrt(n1, self.machine_st.flags.float_flags)
+                            )));
 
                         self.machine_st.p += 1;
                     }
                     &Instruction::Log(ref a1, t) => {
                         let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
 
-                        self.machine_st.interms[t - 1] = Number::Float(OrderedFloat(
-                            try_or_throw_gen!(&mut self.machine_st, log(n1)),
-                        ));
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                log(n1, self.machine_st.flags.float_flags)
+                            )));
 
                         self.machine_st.p += 1;
                     }
                     &Instruction::Exp(ref a1, t) => {
                         let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
 
-                        self.machine_st.interms[t - 1] = Number::Float(OrderedFloat(
-                            try_or_throw_gen!(&mut self.machine_st, exp(n1)),
-                        ));
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                exp(n1, self.machine_st.flags.float_flags)
+                            )));
 
                         self.machine_st.p += 1;
                     }
                     &Instruction::ACos(ref a1, t) => {
                         let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
 
-                        self.machine_st.interms[t - 1] = Number::Float(OrderedFloat(
-                            try_or_throw_gen!(&mut self.machine_st, acos(n1)),
-                        ));
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                acos(n1, self.machine_st.flags.float_flags)
+                            )));
 
                         self.machine_st.p += 1;
                     }
                     &Instruction::ASin(ref a1, t) => {
                         let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
 
-                        self.machine_st.interms[t - 1] = Number::Float(OrderedFloat(
-                            try_or_throw_gen!(&mut self.machine_st, asin(n1)),
-                        ));
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                asin(n1, self.machine_st.flags.float_flags)
+                            )));
 
                         self.machine_st.p += 1;
                     }
                     &Instruction::ATan(ref a1, t) => {
                         let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
 
-                        self.machine_st.interms[t - 1] = Number::Float(OrderedFloat(
-                            try_or_throw_gen!(&mut self.machine_st, atan(n1)),
-                        ));
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                atan(n1, self.machine_st.flags.float_flags)
+                            )));
 
                         self.machine_st.p += 1;
                     }
@@ -919,25 +1014,32 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                         let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
                         let n2 = try_or_throw!(self.machine_st, self.machine_st.get_number(a2));
 
-                        self.machine_st.interms[t - 1] = Number::Float(OrderedFloat(
-                            try_or_throw_gen!(&mut self.machine_st, atan2(n1, n2)),
-                        ));
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                atan2(n1, n2, self.machine_st.flags.float_flags)
+                            )));
 
                         self.machine_st.p += 1;
                     }
                     &Instruction::Float(ref a1, t) => {
                         let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
 
-                        self.machine_st.interms[t - 1] = Number::Float(OrderedFloat(
-                            try_or_throw_gen!(&mut self.machine_st, float(n1)),
-                        ));
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                float(n1, self.machine_st.flags.float_flags)
+                            )));
 
                         self.machine_st.p += 1;
                     }
                     &Instruction::Truncate(ref a1, t) => {
                         let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
 
-                        self.machine_st.interms[t - 1] = truncate(n1, &mut self.machine_st.arena);
+                        self.machine_st.interms[t - 1] = try_or_throw_gen!(
+                            &mut self.machine_st,
+                            truncate(n1, &mut self.machine_st.arena)
+                        );
                         self.machine_st.p += 1;
                     }
                     &Instruction::Round(ref a1, t) => {
@@ -953,30 +1055,343 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                     &Instruction::Ceiling(ref a1, t) => {
                         let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
 
-                        self.machine_st.interms[t - 1] = ceiling(n1, &mut self.machine_st.arena);
+                        self.machine_st.interms[t - 1] = try_or_throw_gen!(
+                            &mut self.machine_st,
+                            ceiling(n1, &mut self.machine_st.arena)
+                        );
                         self.machine_st.p += 1;
                     }
                     &Instruction::Floor(ref a1, t) => {
                         let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
 
-                        self.machine_st.interms[t - 1] = floor(n1, &mut self.machine_st.arena);
+                        self.machine_st.interms[t - 1] = try_or_throw_gen!(
+                            &mut self.machine_st,
+                            floor(n1, &mut self.machine_st.arena)
+                        );
                         self.machine_st.p += 1;
                     }
                     &Instruction::FloatFractionalPart(ref a1, t) => {
                         let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
 
-                        self.machine_st.interms[t - 1] = Number::Float(OrderedFloat(
-                            try_or_throw_gen!(&mut self.machine_st, float_fractional_part(n1)),
-                        ));
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                float_fractional_part(n1, self.machine_st.flags.float_flags)
+                            )));
 
                         self.machine_st.p += 1;
                     }
                     &Instruction::FloatIntegerPart(ref a1, t) => {
                         let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
 
-                        self.machine_st.interms[t - 1] = Number::Float(OrderedFloat(
-                            try_or_throw_gen!(&mut self.machine_st, float_integer_part(n1)),
-                        ));
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                float_integer_part(n1, self.machine_st.flags.float_flags)
+                            )));
+
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::Msb(ref a1, t) => {
+                        let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
+
+                        self.machine_st.interms[t - 1] = try_or_throw_gen!(
+                            &mut self.machine_st,
+                            msb(n1, &mut self.machine_st.arena)
+                        );
+
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::Lsb(ref a1, t) => {
+                        let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
+
+                        self.machine_st.interms[t - 1] = try_or_throw_gen!(
+                            &mut self.machine_st,
+                            lsb(n1, &mut self.machine_st.arena)
+                        );
+
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::PopCount(ref a1, t) => {
+                        let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
+
+                        self.machine_st.interms[t - 1] = try_or_throw_gen!(
+                            &mut self.machine_st,
+                            popcount(n1, &mut self.machine_st.arena)
+                        );
+
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::Sinh(ref a1, t) => {
+                        let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
+
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                sinh(n1, self.machine_st.flags.float_flags)
+                            )));
+
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::Cosh(ref a1, t) => {
+                        let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
+
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                cosh(n1, self.machine_st.flags.float_flags)
+                            )));
+
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::Tanh(ref a1, t) => {
+                        let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
+
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                tanh(n1, self.machine_st.flags.float_flags)
+                            )));
+
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::ASinh(ref a1, t) => {
+                        let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
+
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                asinh(n1, self.machine_st.flags.float_flags)
+                            )));
+
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::ACosh(ref a1, t) => {
+                        let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
+
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                acosh(n1, self.machine_st.flags.float_flags)
+                            )));
+
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::ATanh(ref a1, t) => {
+                        let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
+
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                atanh(n1, self.machine_st.flags.float_flags)
+                            )));
+
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::Log2(ref a1, t) => {
+                        let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
+
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                log2(n1, self.machine_st.flags.float_flags)
+                            )));
+
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::Cot(ref a1, t) => {
+                        let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
+
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                cot(n1, self.machine_st.flags.float_flags)
+                            )));
+
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::ACot(ref a1, t) => {
+                        let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
+
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                acot(n1, self.machine_st.flags.float_flags)
+                            )));
+
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::SinD(ref a1, t) => {
+                        let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
+
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                sind(n1, self.machine_st.flags.float_flags)
+                            )));
+
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::CosD(ref a1, t) => {
+                        let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
+
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                cosd(n1, self.machine_st.flags.float_flags)
+                            )));
+
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::TanD(ref a1, t) => {
+                        let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
+
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                tand(n1, self.machine_st.flags.float_flags)
+                            )));
+
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::ASinD(ref a1, t) => {
+                        let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
+
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                asind(n1, self.machine_st.flags.float_flags)
+                            )));
+
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::ACosD(ref a1, t) => {
+                        let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
+
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                acosd(n1, self.machine_st.flags.float_flags)
+                            )));
+
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::ATanD(ref a1, t) => {
+                        let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
+
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                atand(n1, self.machine_st.flags.float_flags)
+                            )));
+
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::ATan2D(ref a1, ref a2, t) => {
+                        let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
+                        let n2 = try_or_throw!(self.machine_st, self.machine_st.get_number(a2));
+
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                atan2d(n1, n2, self.machine_st.flags.float_flags)
+                            )));
+
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::CopySign(ref a1, ref a2, t) => {
+                        let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
+                        let n2 = try_or_throw!(self.machine_st, self.machine_st.get_number(a2));
+
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                copysign(n1, n2, self.machine_st.flags.float_flags)
+                            )));
+
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::NextToward(ref a1, ref a2, t) => {
+                        let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
+                        let n2 = try_or_throw!(self.machine_st, self.machine_st.get_number(a2));
+
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(try_or_throw_gen!(
+                                &mut self.machine_st,
+                                nexttoward(n1, n2, self.machine_st.flags.float_flags)
+                            )));
+
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::CmpFlags(ref a1, ref a2, t) => {
+                        let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
+                        let n2 = try_or_throw!(self.machine_st, self.machine_st.get_number(a2));
+
+                        self.machine_st.interms[t - 1] = cmpflags(n1, n2);
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::ToInteger(ref a1, t) => {
+                        let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
+
+                        self.machine_st.interms[t - 1] = try_or_throw_gen!(
+                            &mut self.machine_st,
+                            integer(n1, &mut self.machine_st.arena)
+                        );
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::ToDecimal(ref a1, t) => {
+                        let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
+
+                        self.machine_st.interms[t - 1] = try_or_throw_gen!(
+                            &mut self.machine_st,
+                            decimal(
+                                n1,
+                                self.machine_st.flags.decimal_flags,
+                                &mut self.machine_st.arena
+                            )
+                        );
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::ToDecimalScaled(ref a1, ref a2, t) => {
+                        let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
+                        let n2 = try_or_throw!(self.machine_st, self.machine_st.get_number(a2));
+
+                        self.machine_st.interms[t - 1] = try_or_throw_gen!(
+                            &mut self.machine_st,
+                            decimal_scaled(
+                                n1,
+                                n2,
+                                self.machine_st.flags.decimal_flags,
+                                &mut self.machine_st.arena
+                            )
+                        );
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::PowM(ref a1, ref a2, ref a3, t) => {
+                        let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
+                        let n2 = try_or_throw!(self.machine_st, self.machine_st.get_number(a2));
+                        let n3 = try_or_throw!(self.machine_st, self.machine_st.get_number(a3));
+
+                        self.machine_st.interms[t - 1] = try_or_throw_gen!(
+                            &mut self.machine_st,
+                            powm(n1, n2, n3, &mut self.machine_st.arena)
+                        );
+
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::Random(ref a1, t) => {
+                        let n1 = try_or_throw!(self.machine_st, self.machine_st.get_number(a1));
+
+                        self.machine_st.interms[t - 1] = try_or_throw_gen!(
+                            &mut self.machine_st,
+                            random(n1, &mut self.machine_st.rng, &mut self.machine_st.arena)
+                        );
+
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::RandomFloat(t) => {
+                        self.machine_st.interms[t - 1] =
+                            Number::Float(OrderedFloat(random_float(&mut self.machine_st.rng)));
 
                         self.machine_st.p += 1;
                     }
@@ -1139,7 +1554,21 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                         }
                     }
                     &Instruction::TryMeElse(offset) => {
-                        self.try_me_else(offset);
+                        // a predicate chaining subsequences has no
+                        // indexing code of its own to switch on the
+                        // bound arguments from.
+                        match self.select_jit_chain_index() {
+                            Some((index, addr)) => {
+                                self.execute_indexing_lines(index, addr);
+
+                                if self.machine_st.fail {
+                                    self.machine_st.backtrack();
+                                }
+                            }
+                            None => {
+                                self.try_me_else(offset);
+                            }
+                        }
                     }
                     &Instruction::DefaultRetryMeElse(offset) => {
                         self.retry_me_else(offset);
@@ -2631,9 +3060,7 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                             }
                         }
                     }
-                    &Instruction::CallNamed(arity, name, ref idx) => {
-                        let idx = idx.get();
-
+                    &Instruction::CallNamed(arity, name, idx) => {
                         try_or_throw!(self.machine_st, self.try_call(name, arity, idx));
 
                         if self.machine_st.fail {
@@ -2642,9 +3069,7 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                             increment_call_count!(self.machine_st);
                         }
                     }
-                    &Instruction::ExecuteNamed(arity, name, ref idx) => {
-                        let idx = idx.get();
-
+                    &Instruction::ExecuteNamed(arity, name, idx) => {
                         try_or_throw!(self.machine_st, self.try_execute(name, arity, idx));
 
                         if self.machine_st.fail {
@@ -2653,18 +3078,14 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                             increment_call_count!(self.machine_st);
                         }
                     }
-                    &Instruction::DefaultCallNamed(arity, name, ref idx) => {
-                        let idx = idx.get();
-
+                    &Instruction::DefaultCallNamed(arity, name, idx) => {
                         try_or_throw!(self.machine_st, self.try_call(name, arity, idx));
 
                         if self.machine_st.fail {
                             self.machine_st.backtrack();
                         }
                     }
-                    &Instruction::DefaultExecuteNamed(arity, name, ref idx) => {
-                        let idx = idx.get();
-
+                    &Instruction::DefaultExecuteNamed(arity, name, idx) => {
                         try_or_throw!(self.machine_st, self.try_execute(name, arity, idx));
 
                         if self.machine_st.fail {
@@ -3364,34 +3785,42 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
                     &Instruction::CallDirectoryFiles => {
+                        check_capability!(self, Capability::FileSystem);
                         try_or_throw!(self.machine_st, self.directory_files());
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteDirectoryFiles => {
+                        check_capability!(self, Capability::FileSystem);
                         try_or_throw!(self.machine_st, self.directory_files());
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
                     &Instruction::CallFileSize => {
+                        check_capability!(self, Capability::FileSystem);
                         self.file_size();
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteFileSize => {
+                        check_capability!(self, Capability::FileSystem);
                         self.file_size();
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
                     &Instruction::CallFileExists => {
+                        check_capability!(self, Capability::FileSystem);
                         self.file_exists();
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteFileExists => {
+                        check_capability!(self, Capability::FileSystem);
                         self.file_exists();
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
                     &Instruction::CallDirectoryExists => {
+                        check_capability!(self, Capability::FileSystem);
                         self.directory_exists();
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteDirectoryExists => {
+                        check_capability!(self, Capability::FileSystem);
                         self.directory_exists();
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
@@ -3404,74 +3833,92 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
                     &Instruction::CallMakeDirectory => {
+                        check_capability!(self, Capability::FileSystem);
                         self.make_directory();
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteMakeDirectory => {
+                        check_capability!(self, Capability::FileSystem);
                         self.make_directory();
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
                     &Instruction::CallMakeDirectoryPath => {
+                        check_capability!(self, Capability::FileSystem);
                         self.make_directory_path();
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteMakeDirectoryPath => {
+                        check_capability!(self, Capability::FileSystem);
                         self.make_directory_path();
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
                     &Instruction::CallDeleteFile => {
+                        check_capability!(self, Capability::FileSystem);
                         self.delete_file();
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteDeleteFile => {
+                        check_capability!(self, Capability::FileSystem);
                         self.delete_file();
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
                     &Instruction::CallRenameFile => {
+                        check_capability!(self, Capability::FileSystem);
                         self.rename_file();
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteRenameFile => {
+                        check_capability!(self, Capability::FileSystem);
                         self.rename_file();
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
                     &Instruction::CallFileCopy => {
+                        check_capability!(self, Capability::FileSystem);
                         self.file_copy();
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteFileCopy => {
+                        check_capability!(self, Capability::FileSystem);
                         self.file_copy();
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
                     &Instruction::CallWorkingDirectory => {
+                        check_capability!(self, Capability::FileSystem);
                         try_or_throw!(self.machine_st, self.working_directory());
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteWorkingDirectory => {
+                        check_capability!(self, Capability::FileSystem);
                         try_or_throw!(self.machine_st, self.working_directory());
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
                     &Instruction::CallDeleteDirectory => {
+                        check_capability!(self, Capability::FileSystem);
                         self.delete_directory();
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteDeleteDirectory => {
+                        check_capability!(self, Capability::FileSystem);
                         self.delete_directory();
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
                     &Instruction::CallPathCanonical => {
+                        check_capability!(self, Capability::FileSystem);
                         try_or_throw!(self.machine_st, self.path_canonical());
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecutePathCanonical => {
+                        check_capability!(self, Capability::FileSystem);
                         try_or_throw!(self.machine_st, self.path_canonical());
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
                     &Instruction::CallFileTime => {
+                        check_capability!(self, Capability::FileSystem);
                         self.file_time();
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteFileTime => {
+                        check_capability!(self, Capability::FileSystem);
                         self.file_time();
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
@@ -3769,10 +4216,12 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                         self.machine_st.p = self.machine_st.cp;
                     }
                     &Instruction::CallOpen => {
+                        check_capability!(self, Capability::FileSystem);
                         try_or_throw!(self.machine_st, self.open());
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteOpen => {
+                        check_capability!(self, Capability::FileSystem);
                         try_or_throw!(self.machine_st, self.open());
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
@@ -3784,6 +4233,14 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                         try_or_throw!(self.machine_st, self.set_stream_options());
                         self.machine_st.p = self.machine_st.cp;
                     }
+                    &Instruction::CallSetStreamEncoding => {
+                        try_or_throw!(self.machine_st, self.set_stream_encoding());
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::ExecuteSetStreamEncoding => {
+                        try_or_throw!(self.machine_st, self.set_stream_encoding());
+                        self.machine_st.p = self.machine_st.cp;
+                    }
                     &Instruction::CallNextStream => {
                         self.next_stream();
                         step_or_fail!(self, self.machine_st.p += 1);
@@ -4013,6 +4470,14 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                         self.get_ball();
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
+                    &Instruction::CallBallBacktrace => {
+                        self.ball_backtrace();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteBallBacktrace => {
+                        self.ball_backtrace();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
                     &Instruction::CallGetCurrentBlock => {
                         self.get_current_block();
                         step_or_fail!(self, self.machine_st.p += 1);
@@ -4037,22 +4502,6 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                         self.get_cut_point();
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
-                    &Instruction::CallGetDoubleQuotes => {
-                        self.get_double_quotes();
-                        step_or_fail!(self, self.machine_st.p += 1);
-                    }
-                    &Instruction::ExecuteGetDoubleQuotes => {
-                        self.get_double_quotes();
-                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
-                    }
-                    &Instruction::CallGetUnknown => {
-                        self.get_unknown();
-                        step_or_fail!(self, self.machine_st.p += 1);
-                    }
-                    &Instruction::ExecuteGetUnknown => {
-                        self.get_unknown();
-                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
-                    }
                     &Instruction::CallInstallNewBlock => {
                         self.machine_st
                             .install_new_block(self.machine_st.registers[1]);
@@ -4079,6 +4528,150 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                         self.cpu_now();
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
+                    &Instruction::CallGarbageCollect => {
+                        self.garbage_collect();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteGarbageCollect => {
+                        self.garbage_collect();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallGCStatistics => {
+                        self.unify_gc_statistics();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteGCStatistics => {
+                        self.unify_gc_statistics();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallGarbageCollectAtoms => {
+                        self.garbage_collect_atoms();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteGarbageCollectAtoms => {
+                        self.garbage_collect_atoms();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallAtomGCStatistics => {
+                        self.unify_atom_gc_statistics();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteAtomGCStatistics => {
+                        self.unify_atom_gc_statistics();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallThreadCreate => {
+                        try_or_throw!(self.machine_st, self.thread_create());
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteThreadCreate => {
+                        try_or_throw!(self.machine_st, self.thread_create());
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallThreadJoin => {
+                        try_or_throw!(self.machine_st, self.thread_join());
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteThreadJoin => {
+                        try_or_throw!(self.machine_st, self.thread_join());
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallThreadSelf => {
+                        self.thread_self();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteThreadSelf => {
+                        self.thread_self();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallThreadFinish => {
+                        try_or_throw!(self.machine_st, self.thread_finish());
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteThreadFinish => {
+                        try_or_throw!(self.machine_st, self.thread_finish());
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallThreadSendMessage => {
+                        try_or_throw!(self.machine_st, self.thread_send_message());
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteThreadSendMessage => {
+                        try_or_throw!(self.machine_st, self.thread_send_message());
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallThreadPeekMessage => {
+                        self.thread_peek_message();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteThreadPeekMessage => {
+                        self.thread_peek_message();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallThreadRemoveMessage => {
+                        self.thread_remove_message();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteThreadRemoveMessage => {
+                        self.thread_remove_message();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallMutexCreate => {
+                        try_or_throw!(self.machine_st, self.mutex_create());
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteMutexCreate => {
+                        try_or_throw!(self.machine_st, self.mutex_create());
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallMutexLock => {
+                        try_or_throw!(self.machine_st, self.mutex_lock());
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteMutexLock => {
+                        try_or_throw!(self.machine_st, self.mutex_lock());
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallMutexUnlock => {
+                        try_or_throw!(self.machine_st, self.mutex_unlock());
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteMutexUnlock => {
+                        try_or_throw!(self.machine_st, self.mutex_unlock());
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallForeignPredicateCall => {
+                        self.call_foreign_predicate();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteForeignPredicateCall => {
+                        self.call_foreign_predicate();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallOpenForeignSolutions => {
+                        self.open_foreign_solutions();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteOpenForeignSolutions => {
+                        self.open_foreign_solutions();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallNextForeignSolution => {
+                        self.next_foreign_solution();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteNextForeignSolution => {
+                        self.next_foreign_solution();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallCloseForeignSolutions => {
+                        self.close_foreign_solutions();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteCloseForeignSolutions => {
+                        self.close_foreign_solutions();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
                     &Instruction::CallDeterministicLengthRundown => {
                         try_or_throw!(self.machine_st, self.det_length_rundown());
                         step_or_fail!(self, self.machine_st.p += 1);
@@ -4088,71 +4681,85 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
                     &Instruction::CallHttpOpen => {
+                        check_capability!(self, Capability::Http);
                         #[cfg(feature = "http")]
                         try_or_throw!(self.machine_st, self.http_open());
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteHttpOpen => {
+                        check_capability!(self, Capability::Http);
                         #[cfg(feature = "http")]
                         try_or_throw!(self.machine_st, self.http_open());
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
                     &Instruction::CallHttpListen => {
+                        check_capability!(self, Capability::Http);
                         #[cfg(feature = "http")]
                         try_or_throw!(self.machine_st, self.http_listen());
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteHttpListen => {
+                        check_capability!(self, Capability::Http);
                         #[cfg(feature = "http")]
                         try_or_throw!(self.machine_st, self.http_listen());
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
                     &Instruction::CallHttpAccept => {
+                        check_capability!(self, Capability::Http);
                         #[cfg(feature = "http")]
                         try_or_throw!(self.machine_st, self.http_accept());
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteHttpAccept => {
+                        check_capability!(self, Capability::Http);
                         #[cfg(feature = "http")]
                         try_or_throw!(self.machine_st, self.http_accept());
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
                     &Instruction::CallHttpAnswer => {
+                        check_capability!(self, Capability::Http);
                         #[cfg(feature = "http")]
                         try_or_throw!(self.machine_st, self.http_answer());
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteHttpAnswer => {
+                        check_capability!(self, Capability::Http);
                         #[cfg(feature = "http")]
                         try_or_throw!(self.machine_st, self.http_answer());
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
                     &Instruction::CallLoadForeignLib => {
+                        check_capability!(self, Capability::ForeignFunctions);
                         #[cfg(feature = "ffi")]
                         try_or_throw!(self.machine_st, self.load_foreign_lib());
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteLoadForeignLib => {
+                        check_capability!(self, Capability::ForeignFunctions);
                         #[cfg(feature = "ffi")]
                         try_or_throw!(self.machine_st, self.load_foreign_lib());
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
                     &Instruction::CallForeignCall => {
+                        check_capability!(self, Capability::ForeignFunctions);
                         #[cfg(feature = "ffi")]
                         try_or_throw!(self.machine_st, self.foreign_call());
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteForeignCall => {
+                        check_capability!(self, Capability::ForeignFunctions);
                         #[cfg(feature = "ffi")]
                         try_or_throw!(self.machine_st, self.foreign_call());
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
                     &Instruction::CallDefineForeignStruct => {
+                        check_capability!(self, Capability::ForeignFunctions);
                         #[cfg(feature = "ffi")]
                         try_or_throw!(self.machine_st, self.define_foreign_struct());
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteDefineForeignStruct => {
+                        check_capability!(self, Capability::ForeignFunctions);
                         #[cfg(feature = "ffi")]
                         try_or_throw!(self.machine_st, self.define_foreign_struct());
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
@@ -4265,22 +4872,6 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                         self.set_cut_point_by_default(r);
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
-                    &Instruction::CallSetDoubleQuotes => {
-                        self.set_double_quotes();
-                        step_or_fail!(self, self.machine_st.p += 1);
-                    }
-                    &Instruction::ExecuteSetDoubleQuotes => {
-                        self.set_double_quotes();
-                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
-                    }
-                    &Instruction::CallSetUnknown => {
-                        self.set_unknown();
-                        step_or_fail!(self, self.machine_st.p += 1);
-                    }
-                    &Instruction::ExecuteSetUnknown => {
-                        self.set_unknown();
-                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
-                    }
                     &Instruction::CallSetSeed => {
                         self.set_seed();
                         step_or_fail!(self, self.machine_st.p += 1);
@@ -4306,26 +4897,32 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                         self.machine_st.p = self.machine_st.cp;
                     }
                     &Instruction::CallSocketClientOpen => {
+                        check_capability!(self, Capability::Network);
                         try_or_throw!(self.machine_st, self.socket_client_open());
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteSocketClientOpen => {
+                        check_capability!(self, Capability::Network);
                         try_or_throw!(self.machine_st, self.socket_client_open());
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
                     &Instruction::CallSocketServerOpen => {
+                        check_capability!(self, Capability::Network);
                         try_or_throw!(self.machine_st, self.socket_server_open());
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteSocketServerOpen => {
+                        check_capability!(self, Capability::Network);
                         try_or_throw!(self.machine_st, self.socket_server_open());
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
                     &Instruction::CallSocketServerAccept => {
+                        check_capability!(self, Capability::Network);
                         try_or_throw!(self.machine_st, self.socket_server_accept());
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteSocketServerAccept => {
+                        check_capability!(self, Capability::Network);
                         try_or_throw!(self.machine_st, self.socket_server_accept());
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
@@ -4338,21 +4935,25 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                         self.machine_st.p = self.machine_st.cp;
                     }
                     &Instruction::CallTLSAcceptClient => {
+                        check_capability!(self, Capability::Network);
                         #[cfg(feature = "tls")]
                         try_or_throw!(self.machine_st, self.tls_accept_client());
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteTLSAcceptClient => {
+                        check_capability!(self, Capability::Network);
                         #[cfg(feature = "tls")]
                         try_or_throw!(self.machine_st, self.tls_accept_client());
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
                     &Instruction::CallTLSClientConnect => {
+                        check_capability!(self, Capability::Network);
                         #[cfg(feature = "tls")]
                         try_or_throw!(self.machine_st, self.tls_client_connect());
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteTLSClientConnect => {
+                        check_capability!(self, Capability::Network);
                         #[cfg(feature = "tls")]
                         try_or_throw!(self.machine_st, self.tls_client_connect());
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
@@ -4582,37 +5183,75 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
                     &Instruction::CallGetEnv => {
+                        check_capability!(self, Capability::Environment);
                         self.get_env();
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteGetEnv => {
+                        check_capability!(self, Capability::Environment);
                         self.get_env();
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
                     &Instruction::CallSetEnv => {
+                        check_capability!(self, Capability::Environment);
                         self.set_env();
                         self.machine_st.p += 1;
                     }
                     &Instruction::ExecuteSetEnv => {
+                        check_capability!(self, Capability::Environment);
                         self.set_env();
                         self.machine_st.p = self.machine_st.cp;
                     }
                     &Instruction::CallUnsetEnv => {
+                        check_capability!(self, Capability::Environment);
                         self.unset_env();
                         self.machine_st.p += 1;
                     }
                     &Instruction::ExecuteUnsetEnv => {
+                        check_capability!(self, Capability::Environment);
                         self.unset_env();
                         self.machine_st.p = self.machine_st.cp;
                     }
                     &Instruction::CallShell => {
+                        check_capability!(self, Capability::Shell);
                         self.shell();
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteShell => {
+                        check_capability!(self, Capability::Shell);
                         self.shell();
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
+                    &Instruction::CallProcessCreate => {
+                        check_capability!(self, Capability::Shell);
+                        try_or_throw!(self.machine_st, self.process_create());
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteProcessCreate => {
+                        check_capability!(self, Capability::Shell);
+                        try_or_throw!(self.machine_st, self.process_create());
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallProcessWait => {
+                        check_capability!(self, Capability::Shell);
+                        try_or_throw!(self.machine_st, self.process_wait());
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteProcessWait => {
+                        check_capability!(self, Capability::Shell);
+                        try_or_throw!(self.machine_st, self.process_wait());
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallProcessKill => {
+                        check_capability!(self, Capability::Shell);
+                        try_or_throw!(self.machine_st, self.process_kill());
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteProcessKill => {
+                        check_capability!(self, Capability::Shell);
+                        try_or_throw!(self.machine_st, self.process_kill());
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
                     &Instruction::CallPID => {
                         self.pid();
                         step_or_fail!(self, self.machine_st.p += 1);
@@ -4637,43 +5276,13 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                         try_or_throw!(self.machine_st, self.devour_whitespace());
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
-                    &Instruction::CallIsSTOEnabled => {
-                        self.is_sto_enabled();
-                        step_or_fail!(self, self.machine_st.p += 1);
-                    }
-                    &Instruction::ExecuteIsSTOEnabled => {
-                        self.is_sto_enabled();
-                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
-                    }
-                    &Instruction::CallSetSTOAsUnify => {
-                        self.set_sto_as_unify();
-                        self.machine_st.p += 1;
-                    }
-                    &Instruction::ExecuteSetSTOAsUnify => {
-                        self.set_sto_as_unify();
-                        self.machine_st.p = self.machine_st.cp;
-                    }
-                    &Instruction::CallSetNSTOAsUnify => {
-                        self.set_nsto_as_unify();
-                        self.machine_st.p += 1;
-                    }
-                    &Instruction::ExecuteSetNSTOAsUnify => {
-                        self.set_nsto_as_unify();
-                        self.machine_st.p = self.machine_st.cp;
-                    }
-                    &Instruction::CallSetSTOWithErrorAsUnify => {
-                        self.set_sto_with_error_as_unify();
-                        self.machine_st.p += 1;
-                    }
-                    &Instruction::ExecuteSetSTOWithErrorAsUnify => {
-                        self.set_sto_with_error_as_unify();
-                        self.machine_st.p = self.machine_st.cp;
-                    }
                     &Instruction::CallHomeDirectory => {
+                        check_capability!(self, Capability::Environment);
                         self.home_directory();
                         step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteHomeDirectory => {
+                        check_capability!(self, Capability::Environment);
                         self.home_directory();
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
@@ -4685,14 +5294,6 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                         self.debug_hook();
                         self.machine_st.p = self.machine_st.cp;
                     }
-                    &Instruction::CallPopCount => {
-                        self.pop_count();
-                        step_or_fail!(self, self.machine_st.p += 1);
-                    }
-                    &Instruction::ExecutePopCount => {
-                        self.pop_count();
-                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
-                    }
                     &Instruction::CallAddDiscontiguousPredicate => {
                         try_or_throw!(self.machine_st, self.add_discontiguous_predicate());
                         self.machine_st.p += 1;
@@ -4821,6 +5422,196 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                         self.load_context_stream();
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
+                    &Instruction::CallSetLoadContextLine => {
+                        self.set_load_context_line();
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::ExecuteSetLoadContextLine => {
+                        self.set_load_context_line();
+                        self.machine_st.p = self.machine_st.cp;
+                    }
+                    &Instruction::CallInvokeTraced => {
+                        self.invoke_traced(false);
+                    }
+                    &Instruction::ExecuteInvokeTraced => {
+                        self.invoke_traced(true);
+                    }
+                    &Instruction::CallTracePortHook => {
+                        self.trace_port_hook();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteTracePortHook => {
+                        self.trace_port_hook();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallStartProfiler => {
+                        self.start_profiler();
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::ExecuteStartProfiler => {
+                        self.start_profiler();
+                        self.machine_st.p = self.machine_st.cp;
+                    }
+                    &Instruction::CallStopProfiler => {
+                        self.stop_profiler();
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::ExecuteStopProfiler => {
+                        self.stop_profiler();
+                        self.machine_st.p = self.machine_st.cp;
+                    }
+                    &Instruction::CallProfileData => {
+                        self.profile_data();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteProfileData => {
+                        self.profile_data();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallPrologFlags => {
+                        self.prolog_flags();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecutePrologFlags => {
+                        self.prolog_flags();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallPrologFlag => {
+                        try_or_throw!(self.machine_st, self.prolog_flag());
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecutePrologFlag => {
+                        try_or_throw!(self.machine_st, self.prolog_flag());
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallSetPrologFlag => {
+                        try_or_throw!(self.machine_st, self.set_prolog_flag());
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::ExecuteSetPrologFlag => {
+                        try_or_throw!(self.machine_st, self.set_prolog_flag());
+                        self.machine_st.p = self.machine_st.cp;
+                    }
+                    &Instruction::CallCreatePrologFlag => {
+                        try_or_throw!(self.machine_st, self.create_prolog_flag());
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::ExecuteCreatePrologFlag => {
+                        try_or_throw!(self.machine_st, self.create_prolog_flag());
+                        self.machine_st.p = self.machine_st.cp;
+                    }
+                    &Instruction::CallTableVariantTable => {
+                        try_or_throw!(self.machine_st, self.table_variant_table());
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteTableVariantTable => {
+                        try_or_throw!(self.machine_st, self.table_variant_table());
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallTableEnter => {
+                        self.table_enter();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteTableEnter => {
+                        self.table_enter();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallTableBeginPass => {
+                        self.table_begin_pass();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteTableBeginPass => {
+                        self.table_begin_pass();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallTableEndPass => {
+                        self.table_end_pass();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteTableEndPass => {
+                        self.table_end_pass();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallTableAddAnswer => {
+                        self.table_add_answer();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteTableAddAnswer => {
+                        self.table_add_answer();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallTableModedAnswer => {
+                        self.table_moded_answer();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteTableModedAnswer => {
+                        self.table_moded_answer();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallTableAnswerCount => {
+                        self.table_answer_count();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteTableAnswerCount => {
+                        self.table_answer_count();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallTableAnswer => {
+                        self.table_answer();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteTableAnswer => {
+                        self.table_answer();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallTableNegation => {
+                        self.table_negation();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteTableNegation => {
+                        self.table_negation();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallTableAbandon => {
+                        self.table_abandon();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteTableAbandon => {
+                        self.table_abandon();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallTableAbolishAll => {
+                        self.table_abolish_all();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteTableAbolishAll => {
+                        self.table_abolish_all();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallTableIncrementalDynamic => {
+                        self.table_incremental_dynamic();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteTableIncrementalDynamic => {
+                        self.table_incremental_dynamic();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallTableVariant => {
+                        self.table_variant();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteTableVariant => {
+                        self.table_variant();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallTableAnswerConditions => {
+                        self.table_answer_conditions();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteTableAnswerConditions => {
+                        self.table_answer_conditions();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
                     &Instruction::CallPopLoadContext => {
                         self.pop_load_context();
                         self.machine_st.p += 1;
@@ -4861,6 +5652,70 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                         try_or_throw!(self.machine_st, self.use_module());
                         self.machine_st.p = self.machine_st.cp;
                     }
+                    &Instruction::CallQlfFile => {
+                        check_capability!(self, Capability::FileSystem);
+                        self.qlf_file();
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteQlfFile => {
+                        check_capability!(self, Capability::FileSystem);
+                        self.qlf_file();
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallOpenQlf => {
+                        check_capability!(self, Capability::FileSystem);
+                        try_or_throw!(self.machine_st, self.open_qlf());
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteOpenQlf => {
+                        check_capability!(self, Capability::FileSystem);
+                        try_or_throw!(self.machine_st, self.open_qlf());
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallNextQlfTerm => {
+                        try_or_throw!(self.machine_st, self.next_qlf_term());
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteNextQlfTerm => {
+                        try_or_throw!(self.machine_st, self.next_qlf_term());
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallStartQlfRecording => {
+                        self.start_qlf_recording();
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::ExecuteStartQlfRecording => {
+                        self.start_qlf_recording();
+                        self.machine_st.p = self.machine_st.cp;
+                    }
+                    &Instruction::CallRecordQlfTerm => {
+                        self.record_qlf_term();
+                        self.machine_st.p += 1;
+                    }
+                    &Instruction::ExecuteRecordQlfTerm => {
+                        self.record_qlf_term();
+                        self.machine_st.p = self.machine_st.cp;
+                    }
+                    &Instruction::CallSaveQlf => {
+                        check_capability!(self, Capability::FileSystem);
+                        try_or_throw!(self.machine_st, self.save_qlf());
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteSaveQlf => {
+                        check_capability!(self, Capability::FileSystem);
+                        try_or_throw!(self.machine_st, self.save_qlf());
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
+                    &Instruction::CallSaveState => {
+                        check_capability!(self, Capability::FileSystem);
+                        try_or_throw!(self.machine_st, self.save_state());
+                        step_or_fail!(self, self.machine_st.p += 1);
+                    }
+                    &Instruction::ExecuteSaveState => {
+                        check_capability!(self, Capability::FileSystem);
+                        try_or_throw!(self.machine_st, self.save_state());
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
+                    }
                     &Instruction::CallBuiltInProperty => {
                         let key = self.machine_st.read_predicate_key(
                             self.machine_st.registers[1],
@@ -4912,48 +5767,48 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                         step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
                     &Instruction::CallAbolishClause => {
-                        try_or_throw!(self.machine_st, self.abolish_clause());
+                        try_or_throw!(self.machine_st, self.database_abolish());
                         self.machine_st.p += 1;
                     }
                     &Instruction::ExecuteAbolishClause => {
-                        try_or_throw!(self.machine_st, self.abolish_clause());
+                        try_or_throw!(self.machine_st, self.database_abolish());
                         self.machine_st.p = self.machine_st.cp;
                     }
                     &Instruction::CallAsserta => {
                         try_or_throw!(
                             self.machine_st,
-                            self.compile_assert(AppendOrPrepend::Prepend)
+                            self.database_assert(AppendOrPrepend::Prepend)
                         );
                         self.machine_st.p += 1;
                     }
                     &Instruction::ExecuteAsserta => {
                         try_or_throw!(
                             self.machine_st,
-                            self.compile_assert(AppendOrPrepend::Prepend)
+                            self.database_assert(AppendOrPrepend::Prepend)
                         );
                         self.machine_st.p = self.machine_st.cp;
                     }
                     &Instruction::CallAssertz => {
                         try_or_throw!(
                             self.machine_st,
-                            self.compile_assert(AppendOrPrepend::Append)
+                            self.database_assert(AppendOrPrepend::Append)
                         );
                         self.machine_st.p += 1;
                     }
                     &Instruction::ExecuteAssertz => {
                         try_or_throw!(
                             self.machine_st,
-                            self.compile_assert(AppendOrPrepend::Append)
+                            self.database_assert(AppendOrPrepend::Append)
                         );
                         self.machine_st.p = self.machine_st.cp;
                     }
                     &Instruction::CallRetract => {
-                        try_or_throw!(self.machine_st, self.retract_clause());
-                        self.machine_st.p += 1;
+                        try_or_throw!(self.machine_st, self.database_retract());
+                        step_or_fail!(self, self.machine_st.p += 1);
                     }
                     &Instruction::ExecuteRetract => {
-                        try_or_throw!(self.machine_st, self.retract_clause());
-                        self.machine_st.p = self.machine_st.cp;
+                        try_or_throw!(self.machine_st, self.database_retract());
+                        step_or_fail!(self, self.machine_st.p = self.machine_st.cp);
                     }
                     &Instruction::CallIsConsistentWithTermQueue => {
                         try_or_throw!(self.machine_st, self.is_consistent_with_term_queue());
@@ -5228,6 +6083,10 @@ pub(super) fn dispatch_loop(&mut self) -> std::process::ExitCode {
                 }
             }
 
+            if self.check_resource_limits() {
+                self.machine_st.backtrack();
+            }
+
             let interrupted = INTERRUPT.load(std::sync::atomic::Ordering::Relaxed);
 
             match INTERRUPT.compare_exchange(
@@ -0,0 +1,633 @@
+use crate::atom_table::*;
+use crate::machine::heap::*;
+use crate::machine::machine_errors::*;
+use crate::machine::machine_state::*;
+use crate::machine::parsed_results::Term;
+use crate::machine::{CallResult, Machine};
+use crate::parser::ast::*;
+use crate::types::*;
+
+use indexmap::IndexMap;
+use std::collections::HashMap;
+
+/// The values a Prolog flag can take.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum FlagType {
+    Boolean,
+    Atom,
+    Integer,
+    Float,
+    Term,
+    /// One of the atoms.
+    OneOf(Vec<Atom>),
+    /// A non-negative integer of at most `usize::MAX`.
+    Natural,
+}
+
+impl FlagType {
+    // the type named by the type/1 option of create_prolog_flag/3.
+    fn from_atom(name: Atom) -> Option<Self> {
+        match name {
+            atom!("boolean") => Some(FlagType::Boolean),
+            atom!("atom") => Some(FlagType::Atom),
+            atom!("integer") => Some(FlagType::Integer),
+            atom!("float") => Some(FlagType::Float),
+            atom!("term") => Some(FlagType::Term),
+            _ => None,
+        }
+    }
+
+    fn accepts(&self, value: &Term) -> bool {
+        match self {
+            FlagType::Boolean => {
+                matches!(value, Term::Atom(atom) if atom == "true" || atom == "false")
+            }
+            FlagType::Atom => matches!(value, Term::Atom(_)),
+            FlagType::Integer => matches!(value, Term::Integer(_)),
+            FlagType::Float => matches!(value, Term::Float(_)),
+            FlagType::Term => true,
+            FlagType::OneOf(atoms) => matches!(
+                value,
+                Term::Atom(name) if atoms.iter().any(|atom| *atom.as_str() == *name)
+            ),
+            FlagType::Natural => natural_value(value).is_some(),
+        }
+    }
+}
+
+fn natural_value(value: &Term) -> Option<usize> {
+    match value {
+        Term::Integer(n) => usize::try_from(n).ok(),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FlagAccess {
+    ReadOnly,
+    ReadWrite,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum FlagScope {
+    Global,
+    /// The flag has a value of its own in each module, which is its
+    /// default value in the modules that didn't set it.
+    Module {
+        default: Term,
+        values: IndexMap<Atom, Term>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct PrologFlag {
+    pub(crate) flag_type: FlagType,
+    pub(crate) access: FlagAccess,
+    pub(crate) scope: FlagScope,
+    /// Whether the flag is one of the system's, which
+    /// `create_prolog_flag/3` can't redefine.
+    pub(crate) system: bool,
+    /// The value of the flag, in user if it is scoped by module. None
+    /// if it has none, as `max_integer` as integers are unbounded.
+    pub(crate) value: Option<Term>,
+}
+
+impl PrologFlag {
+    fn system(flag_type: FlagType, access: FlagAccess, value: Option<Term>) -> Self {
+        PrologFlag {
+            flag_type,
+            access,
+            scope: FlagScope::Global,
+            system: true,
+            value,
+        }
+    }
+}
+
+/// The Prolog flags of `current_prolog_flag/2`, `set_prolog_flag/2`
+/// and `create_prolog_flag/3`, in the order they're enumerated.
+#[derive(Debug, Clone)]
+pub(crate) struct PrologFlags {
+    flags: IndexMap<Atom, PrologFlag>,
+}
+
+impl Default for PrologFlags {
+    fn default() -> Self {
+        use FlagAccess::*;
+
+        let boolean = |value: bool| Some(Term::atom(if value { "true" } else { "false" }));
+        let atom = |name: &str| Some(Term::atom(name));
+
+        let mut flags = IndexMap::new();
+
+        flags.insert(
+            atom!("bounded"),
+            PrologFlag::system(FlagType::Boolean, ReadOnly, boolean(false)),
+        );
+        flags.insert(
+            atom!("max_integer"),
+            PrologFlag::system(FlagType::Integer, ReadOnly, None),
+        );
+        flags.insert(
+            atom!("min_integer"),
+            PrologFlag::system(FlagType::Integer, ReadOnly, None),
+        );
+        flags.insert(
+            atom!("integer_rounding_function"),
+            PrologFlag::system(
+                FlagType::OneOf(vec![atom!("down"), atom!("toward_zero")]),
+                ReadOnly,
+                atom("toward_zero"),
+            ),
+        );
+        flags.insert(
+            atom!("char_conversion"),
+            PrologFlag::system(FlagType::OneOf(vec![atom!("off")]), ReadWrite, atom("off")),
+        );
+        flags.insert(
+            atom!("debug"),
+            PrologFlag::system(
+                FlagType::OneOf(vec![atom!("on"), atom!("off")]),
+                ReadWrite,
+                atom("off"),
+            ),
+        );
+        flags.insert(
+            atom!("max_arity"),
+            PrologFlag::system(
+                FlagType::Integer,
+                ReadOnly,
+                Some(Term::integer(MAX_ARITY as u64)),
+            ),
+        );
+        flags.insert(
+            atom!("unknown"),
+            PrologFlag::system(
+                FlagType::OneOf(vec![atom!("error"), atom!("fail"), atom!("warning")]),
+                ReadWrite,
+                atom("error"),
+            ),
+        );
+        flags.insert(
+            atom!("double_quotes"),
+            PrologFlag {
+                flag_type: FlagType::OneOf(vec![atom!("atom"), atom!("chars"), atom!("codes")]),
+                access: ReadWrite,
+                scope: FlagScope::Module {
+                    default: Term::atom("chars"),
+                    values: IndexMap::new(),
+                },
+                system: true,
+                value: atom("chars"),
+            },
+        );
+        flags.insert(
+            atom!("occurs_check"),
+            PrologFlag::system(
+                FlagType::OneOf(vec![atom!("true"), atom!("false"), atom!("error")]),
+                ReadWrite,
+                atom("false"),
+            ),
+        );
+        flags.insert(
+            atom!("last_call_optimisation"),
+            PrologFlag::system(FlagType::Boolean, ReadOnly, boolean(true)),
+        );
+        flags.insert(
+            atom!("answer_write_options"),
+            PrologFlag::system(FlagType::Term, ReadWrite, Some(Term::List(vec![]))),
+        );
+        flags.insert(
+            atom!("backtrace"),
+            PrologFlag::system(FlagType::Boolean, ReadWrite, boolean(false)),
+        );
+        flags.insert(
+            atom!("float_overflow"),
+            PrologFlag::system(
+                FlagType::OneOf(vec![atom!("error"), atom!("infinity")]),
+                ReadWrite,
+                atom("error"),
+            ),
+        );
+        flags.insert(
+            atom!("float_zero_div"),
+            PrologFlag::system(
+                FlagType::OneOf(vec![atom!("error"), atom!("infinity")]),
+                ReadWrite,
+                atom("error"),
+            ),
+        );
+        flags.insert(
+            atom!("float_undefined"),
+            PrologFlag::system(
+                FlagType::OneOf(vec![atom!("error"), atom!("nan")]),
+                ReadWrite,
+                atom("error"),
+            ),
+        );
+        flags.insert(
+            atom!("float_rounding"),
+            PrologFlag::system(
+                FlagType::OneOf(vec![
+                    atom!("to_nearest"),
+                    atom!("to_positive"),
+                    atom!("to_negative"),
+                    atom!("to_zero"),
+                ]),
+                ReadWrite,
+                atom("to_nearest"),
+            ),
+        );
+        flags.insert(
+            atom!("decimal_precision"),
+            PrologFlag::system(FlagType::Natural, ReadWrite, Some(Term::integer(20u64))),
+        );
+        flags.insert(
+            atom!("decimal_rounding"),
+            PrologFlag::system(
+                FlagType::OneOf(vec![
+                    atom!("half_even"),
+                    atom!("half_away_from_zero"),
+                    atom!("to_zero"),
+                    atom!("away_from_zero"),
+                    atom!("to_positive"),
+                    atom!("to_negative"),
+                ]),
+                ReadWrite,
+                atom("half_even"),
+            ),
+        );
+        flags.insert(
+            atom!("dialect"),
+            PrologFlag::system(FlagType::Atom, ReadOnly, atom("scryer")),
+        );
+
+        PrologFlags { flags }
+    }
+}
+
+impl PrologFlags {
+    #[inline]
+    pub(crate) fn get(&self, name: Atom) -> Option<&PrologFlag> {
+        self.flags.get(&name)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&Atom, &PrologFlag)> {
+        self.flags.iter()
+    }
+
+    pub(crate) fn insert(&mut self, name: Atom, flag: PrologFlag) {
+        self.flags.insert(name, flag);
+    }
+
+    /// The value of the flag `name` in `module`.
+    pub(crate) fn value(&self, name: Atom, module: Atom) -> Option<&Term> {
+        let flag = self.flags.get(&name)?;
+
+        match &flag.scope {
+            FlagScope::Module { default, values } if module != atom!("user") => {
+                Some(values.get(&module).unwrap_or(default))
+            }
+            _ => flag.value.as_ref(),
+        }
+    }
+
+    /// The value of the flag `name` in `module` if it is an atom.
+    pub(crate) fn atom_value(&self, name: Atom, module: Atom) -> Option<&str> {
+        match self.value(name, module)? {
+            Term::Atom(atom) => Some(atom),
+            _ => None,
+        }
+    }
+
+    /// Sets the value of the flag `name` in `module`, which must be
+    /// valid for it.
+    pub(crate) fn set_value(&mut self, name: Atom, module: Atom, value: Term) {
+        if let Some(flag) = self.flags.get_mut(&name) {
+            match &mut flag.scope {
+                FlagScope::Module { values, .. } if module != atom!("user") => {
+                    values.insert(module, value);
+                }
+                _ => flag.value = Some(value),
+            }
+        }
+    }
+
+    /// Restores the flags of `module` to their defaults, as when it is
+    /// declared anew.
+    pub(crate) fn reset_module(&mut self, module: Atom) {
+        for flag in self.flags.values_mut() {
+            if let FlagScope::Module { values, .. } = &mut flag.scope {
+                values.shift_remove(&module);
+            }
+        }
+    }
+
+    pub(crate) fn atoms(&self) -> impl Iterator<Item = Atom> + '_ {
+        self.flags.iter().flat_map(|(name, flag)| {
+            let atoms = match &flag.flag_type {
+                FlagType::OneOf(atoms) => &atoms[..],
+                _ => &[],
+            };
+
+            let modules: Vec<Atom> = match &flag.scope {
+                FlagScope::Module { values, .. } => values.keys().copied().collect(),
+                FlagScope::Global => vec![],
+            };
+
+            std::iter::once(*name)
+                .chain(atoms.iter().copied())
+                .chain(modules)
+        })
+    }
+}
+
+impl Machine {
+    // the module whose flags are read and set: that of the file being
+    // loaded, else user.
+    fn flag_module(&self) -> Atom {
+        self.load_contexts
+            .last()
+            .map(|load_context| load_context.module)
+            .unwrap_or(atom!("user"))
+    }
+
+    /// Brings the flags scoped by module in line with the module of
+    /// the file being loaded, which changes as loads start and end.
+    pub(crate) fn sync_module_flags(&mut self) {
+        self.apply_prolog_flag(atom!("double_quotes"));
+    }
+
+    // makes the value of the flag `name` take effect.
+    pub(crate) fn apply_prolog_flag(&mut self, name: Atom) {
+        let module = self.flag_module();
+
+        if name == atom!("decimal_precision") {
+            let value = self.machine_st.prolog_flags.value(name, module);
+
+            if let Some(precision) = value.and_then(natural_value) {
+                self.machine_st.flags.decimal_flags.precision = precision;
+            }
+
+            return;
+        }
+
+        let value = match self.machine_st.prolog_flags.atom_value(name, module) {
+            Some(value) => value,
+            None => return,
+        };
+
+        match name {
+            atom!("double_quotes") => {
+                self.machine_st.flags.double_quotes = match value {
+                    "atom" => DoubleQuotes::Atom,
+                    "codes" => DoubleQuotes::Codes,
+                    _ => DoubleQuotes::Chars,
+                };
+            }
+            atom!("unknown") => {
+                self.machine_st.flags.unknown = match value {
+                    "fail" => Unknown::Fail,
+                    "warning" => Unknown::Warn,
+                    _ => Unknown::Error,
+                };
+            }
+            atom!("occurs_check") => match value {
+                "true" => {
+                    self.machine_st.unify_fn = MachineState::unify_with_occurs_check;
+                    self.machine_st.bind_fn = MachineState::bind_with_occurs_check_wrapper;
+                }
+                "error" => {
+                    self.machine_st.unify_fn = MachineState::unify_with_occurs_check_with_error;
+                    self.machine_st.bind_fn =
+                        MachineState::bind_with_occurs_check_with_error_wrapper;
+                }
+                _ => {
+                    self.machine_st.unify_fn = MachineState::unify;
+                    self.machine_st.bind_fn = MachineState::bind;
+                }
+            },
+            atom!("debug") => {
+                self.tracer.enabled = value == "on";
+            }
+            atom!("backtrace") => {
+                self.machine_st.flags.backtrace = value == "true";
+            }
+            atom!("float_overflow") => {
+                self.machine_st.flags.float_flags.overflow_to_infinity = value == "infinity";
+            }
+            atom!("float_zero_div") => {
+                self.machine_st.flags.float_flags.zero_div_to_infinity = value == "infinity";
+            }
+            atom!("float_undefined") => {
+                self.machine_st.flags.float_flags.undefined_to_nan = value == "nan";
+            }
+            atom!("float_rounding") => {
+                self.machine_st.flags.float_flags.rounding = match value {
+                    "to_positive" => FloatRounding::ToPositive,
+                    "to_negative" => FloatRounding::ToNegative,
+                    "to_zero" => FloatRounding::ToZero,
+                    _ => FloatRounding::ToNearest,
+                };
+            }
+            atom!("decimal_rounding") => {
+                self.machine_st.flags.decimal_flags.rounding = match value {
+                    "half_away_from_zero" => DecimalRounding::HalfAwayFromZero,
+                    "to_zero" => DecimalRounding::ToZero,
+                    "away_from_zero" => DecimalRounding::AwayFromZero,
+                    "to_positive" => DecimalRounding::ToPositive,
+                    "to_negative" => DecimalRounding::ToNegative,
+                    _ => DecimalRounding::HalfEven,
+                };
+            }
+            _ => {}
+        }
+    }
+
+    /// Makes the values of all the flags take effect, as after they
+    /// were restored.
+    pub(crate) fn apply_prolog_flags(&mut self) {
+        let names: Vec<Atom> = self
+            .machine_st
+            .prolog_flags
+            .iter()
+            .map(|(name, _)| *name)
+            .collect();
+
+        for name in names {
+            self.apply_prolog_flag(name);
+        }
+    }
+
+    /// Unifies register 1 with the list of the flags which have a
+    /// value.
+    pub(crate) fn prolog_flags(&mut self) {
+        let module = self.flag_module();
+        let prolog_flags = &self.machine_st.prolog_flags;
+
+        let names: Vec<HeapCellValue> = prolog_flags
+            .iter()
+            .filter(|(name, _)| prolog_flags.value(**name, module).is_some())
+            .map(|(name, _)| atom_as_cell!(name))
+            .collect();
+
+        let list = heap_loc_as_cell!(iter_to_heap_list(
+            &mut self.machine_st.heap,
+            names.into_iter()
+        ));
+
+        unify!(self.machine_st, list, self.machine_st.registers[1]);
+    }
+
+    // the error of a flag name which isn't that of a flag.
+    fn prolog_flag_domain_error(&mut self, name: Atom, stub: FunctorStub) -> MachineStub {
+        let err = self
+            .machine_st
+            .domain_error(DomainErrorType::PrologFlag, atom_as_cell!(name));
+
+        self.machine_st.error_form(err, stub)
+    }
+
+    // the error of a value inappropriate for a flag, whose culprit is
+    // Flag + Value.
+    fn flag_value_domain_error(
+        &mut self,
+        name: Atom,
+        value: HeapCellValue,
+        stub: FunctorStub,
+    ) -> MachineStub {
+        let h = self.machine_st.heap.len();
+
+        self.machine_st
+            .heap
+            .extend([atom_as_cell!(atom!("+"), 2), atom_as_cell!(name), value]);
+
+        let err = self
+            .machine_st
+            .domain_error(DomainErrorType::FlagValue, str_loc_as_cell!(h));
+
+        self.machine_st.error_form(err, stub)
+    }
+
+    /// Unifies register 2 with the value of the flag named in register
+    /// 1, failing if it has none.
+    pub(crate) fn prolog_flag(&mut self) -> CallResult {
+        let name = cell_as_atom!(self.deref_register(1));
+        let module = self.flag_module();
+
+        if self.machine_st.prolog_flags.get(name).is_none() {
+            let stub = functor_stub(atom!("current_prolog_flag"), 2);
+            return Err(self.prolog_flag_domain_error(name, stub));
+        }
+
+        match self.machine_st.prolog_flags.value(name, module).cloned() {
+            Some(value) => {
+                let value = self.term_to_heap(&value, &mut IndexMap::new());
+                unify!(self.machine_st, value, self.machine_st.registers[2]);
+            }
+            None => {
+                self.machine_st.fail = true;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sets the flag named in register 1 to the value of register 2,
+    /// in the module of the file being loaded if the flag is scoped
+    /// by module.
+    pub(crate) fn set_prolog_flag(&mut self) -> CallResult {
+        let name = cell_as_atom!(self.deref_register(1));
+        let value_cell = self.deref_register(2);
+        let stub = functor_stub(atom!("set_prolog_flag"), 2);
+
+        let (flag_type, access) = match self.machine_st.prolog_flags.get(name) {
+            Some(flag) => (flag.flag_type.clone(), flag.access),
+            None => return Err(self.prolog_flag_domain_error(name, stub)),
+        };
+
+        let value = self.term_from_heap(value_cell, &mut HashMap::new());
+
+        if !flag_type.accepts(&value) {
+            return Err(self.flag_value_domain_error(name, value_cell, stub));
+        }
+
+        if access == FlagAccess::ReadOnly {
+            let err = self
+                .machine_st
+                .permission_error(Permission::Modify, atom!("flag"), name);
+
+            return Err(self.machine_st.error_form(err, stub));
+        }
+
+        let module = self.flag_module();
+
+        self.machine_st.prolog_flags.set_value(name, module, value);
+        self.apply_prolog_flag(name);
+
+        Ok(())
+    }
+
+    /// Creates the flag named in register 1 with the value of register
+    /// 2, the access of register 3 and the type of register 4, or the
+    /// type of its value if that is unbound. If register 5 is `true`,
+    /// a flag of that name is left as it is.
+    pub(crate) fn create_prolog_flag(&mut self) -> CallResult {
+        let name = cell_as_atom!(self.deref_register(1));
+        let value_cell = self.deref_register(2);
+        let access = cell_as_atom!(self.deref_register(3));
+        let type_cell = self.deref_register(4);
+        let keep = cell_as_atom!(self.deref_register(5)) == atom!("true");
+        let stub = functor_stub(atom!("create_prolog_flag"), 3);
+
+        if let Some(flag) = self.machine_st.prolog_flags.get(name) {
+            if flag.system {
+                let err = self
+                    .machine_st
+                    .permission_error(Permission::Modify, atom!("flag"), name);
+
+                return Err(self.machine_st.error_form(err, stub));
+            } else if keep {
+                return Ok(());
+            }
+        }
+
+        let value = self.term_from_heap(value_cell, &mut HashMap::new());
+
+        let flag_type = if type_cell.is_var() {
+            match &value {
+                Term::Atom(atom) if atom == "true" || atom == "false" => FlagType::Boolean,
+                Term::Atom(_) => FlagType::Atom,
+                Term::Integer(_) => FlagType::Integer,
+                Term::Float(_) => FlagType::Float,
+                _ => FlagType::Term,
+            }
+        } else {
+            // the type is checked by create_prolog_flag/3.
+            FlagType::from_atom(cell_as_atom!(type_cell)).unwrap_or(FlagType::Term)
+        };
+
+        if !flag_type.accepts(&value) {
+            return Err(self.flag_value_domain_error(name, value_cell, stub));
+        }
+
+        let access = if access == atom!("read_only") {
+            FlagAccess::ReadOnly
+        } else {
+            FlagAccess::ReadWrite
+        };
+
+        self.machine_st.prolog_flags.insert(
+            name,
+            PrologFlag {
+                flag_type,
+                access,
+                scope: FlagScope::Global,
+                system: false,
+                value: Some(value),
+            },
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,536 @@
+use crate::arena::*;
+use crate::atom_table::*;
+use crate::forms::*;
+use crate::instructions::*;
+use crate::machine::machine_indices::*;
+use crate::machine::parsed_results::Term;
+use crate::machine::streams::*;
+use crate::machine::Machine;
+use crate::parser::ast::Fixnum;
+use crate::types::*;
+
+use indexmap::IndexMap;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// The outcome of a call to a deterministic foreign predicate: one
+/// term per argument to unify it with, `None` to fail, or an `Err` to
+/// throw.
+pub type ForeignResult = Result<Option<Vec<Term>>, Term>;
+
+/// The solutions of a nondeterministic foreign predicate, each of one
+/// term per argument to unify it with. An `Err` is thrown once reached.
+pub type ForeignSolutions = Box<dyn Iterator<Item = Result<Vec<Term>, Term>>>;
+
+type DetPredicate = dyn Fn(&[Term]) -> ForeignResult + Send + Sync;
+type NondetPredicate = dyn Fn(&[Term]) -> ForeignSolutions + Send + Sync;
+
+#[derive(Clone)]
+enum ForeignPredicate {
+    Det(Arc<DetPredicate>),
+    Nondet(Arc<NondetPredicate>),
+}
+
+#[derive(Default)]
+pub(crate) struct ForeignPredicateTable {
+    predicates: Vec<ForeignPredicate>,
+    // the index into `predicates` of the predicate of each module and
+    // key.
+    indices: IndexMap<(Atom, PredicateKey), usize>,
+    // the pending solutions of nondeterministic predicates, by handle.
+    solutions: HashMap<usize, ForeignSolutions>,
+    next_handle: usize,
+}
+
+impl fmt::Debug for ForeignPredicateTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ForeignPredicateTable")
+            .field("indices", &self.indices)
+            .field("solutions", &self.solutions.len())
+            .finish()
+    }
+}
+
+impl ForeignPredicateTable {
+    // the predicates of a new thread, which has no pending solutions.
+    pub(crate) fn for_thread(&self) -> Self {
+        ForeignPredicateTable {
+            predicates: self.predicates.clone(),
+            indices: self.indices.clone(),
+            solutions: HashMap::new(),
+            next_handle: 0,
+        }
+    }
+}
+
+// the predicates of the `$foreign` module called by the code of the
+// foreign predicates with the index of their closure and their goal.
+const FOREIGN_HELPERS: &str = r#"
+:- module('$foreign', []).
+
+:- use_module(library(iso_ext), [setup_call_cleanup/3]).
+
+'$call_foreign_det'(Idx, Goal) :-
+    '$call_foreign_predicate'(Idx, Goal, Result),
+    '$foreign_result'(Result, Goal).
+
+'$call_foreign_nondet'(Idx, Goal) :-
+    setup_call_cleanup('$foreign_solutions_open'(Idx, Goal, Handle),
+                       '$foreign_solutions'(Handle, Goal),
+                       '$foreign_solutions_close'(Handle)).
+
+'$foreign_result'(true(Solution), Solution).
+'$foreign_result'(throw(Exception), _) :- throw(Exception).
+
+'$foreign_solutions_open'(Idx, Goal, Handle) :-
+    '$open_foreign_solutions'(Idx, Goal, Handle).
+
+'$foreign_solutions'(Handle, Goal) :-
+    '$next_foreign_solution'(Handle, Goal, Result),
+    (   '$foreign_result'(Result, Goal)
+    ;   '$foreign_solutions'(Handle, Goal)
+    ).
+
+'$foreign_solutions_close'(Handle) :-
+    '$close_foreign_solutions'(Handle).
+"#;
+
+// the code of the foreign predicate `name/arity`, which calls `helper`
+// with the index `idx` of its closure and its goal.
+fn foreign_predicate_code(
+    name: Atom,
+    arity: usize,
+    idx: usize,
+    helper: (Atom, CodeIndex),
+) -> Vec<Instruction> {
+    let mut code = vec![];
+
+    if arity == 0 {
+        code.push(Instruction::PutConstant(
+            Level::Shallow,
+            atom_as_cell!(name),
+            temp_v!(2),
+        ));
+    } else {
+        let goal = temp_v!(arity + 1);
+
+        code.push(Instruction::PutStructure(name, arity, goal));
+        code.extend((1..=arity).map(|i| Instruction::SetLocalValue(temp_v!(i))));
+        code.push(Instruction::PutValue(goal, 2));
+    }
+
+    code.push(Instruction::PutConstant(
+        Level::Shallow,
+        fixnum_as_cell!(Fixnum::build_with(idx as i64)),
+        temp_v!(1),
+    ));
+
+    code.push(Instruction::ExecuteNamed(2, helper.0, helper.1));
+    code
+}
+
+// points the entry of `key` in `code_dir` at `ptr`, adding the entry
+// if there is none.
+fn set_foreign_code_index(
+    code_dir: &mut CodeDir,
+    key: PredicateKey,
+    ptr: IndexPtr,
+    arena: &mut Arena,
+) {
+    code_dir
+        .entry(key)
+        .or_insert_with(|| CodeIndex::default(arena))
+        .set(ptr);
+}
+
+impl Machine {
+    /// Registers `pred` as the deterministic predicate
+    /// `module:name/arity`, which is also imported into `user`. `pred`
+    /// is called with the arguments of each call, unbound variables
+    /// among them named in the order they occur.
+    pub fn register_det_predicate<F>(&mut self, module: &str, name: &str, arity: usize, pred: F)
+    where
+        F: Fn(&[Term]) -> ForeignResult + Send + Sync + 'static,
+    {
+        self.register_foreign_predicate(module, name, arity, ForeignPredicate::Det(Arc::new(pred)));
+    }
+
+    /// Registers `pred` as the nondeterministic predicate
+    /// `module:name/arity`, like [`Machine::register_det_predicate`].
+    /// The solutions are drawn from the iterator returned by `pred` as
+    /// the predicate is backtracked into, and the iterator is dropped
+    /// once the predicate is exhausted or cut.
+    pub fn register_nondet_predicate<F, I>(
+        &mut self,
+        module: &str,
+        name: &str,
+        arity: usize,
+        pred: F,
+    ) where
+        F: Fn(&[Term]) -> I + Send + Sync + 'static,
+        I: IntoIterator<Item = Result<Vec<Term>, Term>> + 'static,
+    {
+        let pred = move |args: &[Term]| -> ForeignSolutions { Box::new(pred(args).into_iter()) };
+        self.register_foreign_predicate(
+            module,
+            name,
+            arity,
+            ForeignPredicate::Nondet(Arc::new(pred)),
+        );
+    }
+
+    // the predicate `name/2` of the `$foreign` module, which is loaded
+    // by the first registration.
+    fn foreign_helper(&mut self, name: Atom) -> (Atom, CodeIndex) {
+        if !self.indices.modules.contains_key(&atom!("$foreign")) {
+            let stream = Stream::from_static_string(FOREIGN_HELPERS, &mut self.machine_st.arena);
+            self.load_file("$foreign", stream);
+        }
+
+        let code_index = self
+            .indices
+            .get_predicate_code_index(name, 2, atom!("$foreign"))
+            .unwrap();
+
+        (name, code_index)
+    }
+
+    fn register_foreign_predicate(
+        &mut self,
+        module: &str,
+        name: &str,
+        arity: usize,
+        pred: ForeignPredicate,
+    ) {
+        let helper = self.foreign_helper(match pred {
+            ForeignPredicate::Det(_) => atom!("$call_foreign_det"),
+            ForeignPredicate::Nondet(_) => atom!("$call_foreign_nondet"),
+        });
+
+        let module = AtomTable::build_with(&self.machine_st.atom_tbl, module);
+        let name = AtomTable::build_with(&self.machine_st.atom_tbl, name);
+        let key = (name, arity);
+
+        // a predicate registered again has its closure replaced.
+        let table = &mut self.foreign_predicates;
+        let idx = match table.indices.get(&(module, key)) {
+            Some(&idx) => {
+                table.predicates[idx] = pred;
+                idx
+            }
+            None => {
+                let idx = table.predicates.len();
+                table.predicates.push(pred);
+                table.indices.insert((module, key), idx);
+                idx
+            }
+        };
+
+        let ptr = IndexPtr::index(self.code.len());
+
+        self.code
+            .extend(foreign_predicate_code(name, arity, idx, helper));
+
+        let arena = &mut self.machine_st.arena;
+
+        if module != atom!("user") {
+            let module = self.indices.modules.entry(module).or_insert_with(|| {
+                Module::new(
+                    ModuleDecl {
+                        name: module,
+                        exports: vec![],
+                    },
+                    ListingSource::DynamicallyGenerated,
+                )
+            });
+
+            let export = ModuleExport::PredicateKey(key);
+
+            if !module.module_decl.exports.contains(&export) {
+                module.module_decl.exports.push(export);
+            }
+
+            set_foreign_code_index(&mut module.code_dir, key, ptr, arena);
+        }
+
+        set_foreign_code_index(&mut self.indices.code_dir, key, ptr, arena);
+    }
+
+    // the arguments of the goal in register 2, along with the names
+    // given to its variables.
+    fn foreign_goal(&mut self) -> (Atom, Vec<Term>, IndexMap<String, HeapCellValue>) {
+        let goal = self.deref_register(2);
+        let mut var_names = HashMap::new();
+
+        let (name, args) = read_heap_cell!(goal,
+            (HeapCellValueTag::Str, s) => {
+                let (name, arity) = cell_as_atom_cell!(self.machine_st.heap[s])
+                    .get_name_and_arity();
+
+                let args = (s + 1..=s + arity)
+                    .map(|h| self.term_from_heap(self.machine_st.heap[h], &mut var_names))
+                    .collect();
+
+                (name, args)
+            }
+            (HeapCellValueTag::Atom, (name, _arity)) => {
+                (name, vec![])
+            }
+            _ => {
+                unreachable!()
+            }
+        );
+
+        let vars = var_names
+            .into_iter()
+            .map(|(h, name)| (name, heap_loc_as_cell!(h)))
+            .collect();
+
+        (name, args, vars)
+    }
+
+    fn foreign_index(&self, r: usize) -> usize {
+        match Number::try_from(self.deref_register(r)) {
+            Ok(Number::Fixnum(n)) => n.get_num() as usize,
+            _ => unreachable!(),
+        }
+    }
+
+    // unifies register `r` with `true(Goal)`, where `Goal` is the goal
+    // named `name` with the arguments `solution`, or with `throw(E)`.
+    fn unify_foreign_result(
+        &mut self,
+        r: usize,
+        name: Atom,
+        result: Result<Vec<Term>, Term>,
+        mut vars: IndexMap<String, HeapCellValue>,
+    ) {
+        let result = match result {
+            Ok(solution) => Term::compound(
+                "true",
+                [Term::compound(name.as_str().to_string(), solution)],
+            ),
+            Err(exception) => Term::compound("throw", [exception]),
+        };
+
+        let result = self.term_to_heap(&result, &mut vars);
+        unify!(self.machine_st, result, self.machine_st.registers[r]);
+    }
+
+    pub(crate) fn call_foreign_predicate(&mut self) {
+        let idx = self.foreign_index(1);
+        let (name, args, vars) = self.foreign_goal();
+
+        let pred = match &self.foreign_predicates.predicates[idx] {
+            ForeignPredicate::Det(pred) => pred.clone(),
+            ForeignPredicate::Nondet(_) => unreachable!(),
+        };
+
+        match pred(&args) {
+            Ok(Some(solution)) => self.unify_foreign_result(3, name, Ok(solution), vars),
+            Ok(None) => self.machine_st.fail = true,
+            Err(exception) => self.unify_foreign_result(3, name, Err(exception), vars),
+        }
+    }
+
+    pub(crate) fn open_foreign_solutions(&mut self) {
+        let idx = self.foreign_index(1);
+        let (_, args, _) = self.foreign_goal();
+
+        let pred = match &self.foreign_predicates.predicates[idx] {
+            ForeignPredicate::Nondet(pred) => pred.clone(),
+            ForeignPredicate::Det(_) => unreachable!(),
+        };
+
+        let solutions = pred(&args);
+        let table = &mut self.foreign_predicates;
+        let handle = table.next_handle;
+
+        table.next_handle += 1;
+        table.solutions.insert(handle, solutions);
+
+        let handle = fixnum_as_cell!(Fixnum::build_with(handle as i64));
+        unify!(self.machine_st, handle, self.machine_st.registers[3]);
+    }
+
+    pub(crate) fn next_foreign_solution(&mut self) {
+        let handle = self.foreign_index(1);
+
+        let solution = match self.foreign_predicates.solutions.get_mut(&handle) {
+            Some(solutions) => solutions.next(),
+            None => None,
+        };
+
+        match solution {
+            Some(solution) => {
+                // NOTE: the goal is as it was when the solutions were
+                // opened, so its variables are named as they were.
+                let (name, _, vars) = self.foreign_goal();
+                self.unify_foreign_result(3, name, solution, vars);
+            }
+            None => {
+                self.foreign_predicates.solutions.remove(&handle);
+                self.machine_st.fail = true;
+            }
+        }
+    }
+
+    pub(crate) fn close_foreign_solutions(&mut self) {
+        let handle = self.foreign_index(1);
+        self.foreign_predicates.solutions.remove(&handle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::LeafAnswer;
+
+    use maplit::btreemap;
+
+    fn double(args: &[Term]) -> ForeignResult {
+        match &args[0] {
+            Term::Integer(n) if *n < 0.into() => Ok(None),
+            Term::Integer(n) => Ok(Some(vec![args[0].clone(), Term::Integer(n * 2)])),
+            culprit => Err(Term::compound(
+                "error",
+                [
+                    Term::compound("type_error", [Term::atom("integer"), culprit.clone()]),
+                    Term::atom("double/2"),
+                ],
+            )),
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "blocked on streams.rs UB")]
+    fn det_foreign_predicates() {
+        let mut machine = Machine::new_lib();
+
+        machine.register_det_predicate("app", "double", 2, double);
+        machine.register_det_predicate("app", "swap", 2, |args| {
+            Ok(Some(vec![args[1].clone(), args[0].clone()]))
+        });
+
+        let answers: Vec<_> = machine.query("double(21, X), app:double(X, Y).").collect();
+
+        assert_eq!(
+            answers,
+            [Ok(LeafAnswer::LeafAnswer {
+                bindings: btreemap! {
+                    "X".to_string() => Term::integer(42),
+                    "Y".to_string() => Term::integer(84),
+                },
+                residual_goals: vec![],
+            })]
+        );
+
+        let answers: Vec<_> = machine.query("double(-1, X).").collect();
+        assert_eq!(answers, [Ok(LeafAnswer::False)]);
+
+        let answers: Vec<_> = machine
+            .query("catch(double(a, _), error(type_error(T, C), _), true).")
+            .collect();
+
+        assert_eq!(
+            answers,
+            [Ok(LeafAnswer::LeafAnswer {
+                bindings: btreemap! {
+                    "T".to_string() => Term::atom("integer"),
+                    "C".to_string() => Term::atom("a"),
+                },
+                residual_goals: vec![],
+            })]
+        );
+
+        // variables passed to foreign predicates keep their identity.
+        let answers: Vec<_> = machine.query("swap(f(X), Y), Y = f(a).").collect();
+
+        assert_eq!(
+            answers,
+            [Ok(LeafAnswer::LeafAnswer {
+                bindings: btreemap! {
+                    "X".to_string() => Term::atom("a"),
+                    "Y".to_string() => Term::compound("f", [Term::atom("a")]),
+                },
+                residual_goals: vec![],
+            })]
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "blocked on streams.rs UB")]
+    fn reregistered_foreign_predicates() {
+        let mut machine = Machine::new_lib();
+
+        machine.register_det_predicate("app", "answer", 1, |_| Ok(Some(vec![Term::integer(1)])));
+
+        // a call compiled before the predicate is registered again
+        // calls the new closure.
+        machine.load_module_string("facts", "answer_twice(X) :- answer(X).".to_string());
+        machine.register_det_predicate("app", "answer", 1, |_| Ok(Some(vec![Term::integer(2)])));
+
+        let answers: Vec<_> = machine.query("answer_twice(X), app:answer(Y).").collect();
+
+        assert_eq!(
+            answers,
+            [Ok(LeafAnswer::LeafAnswer {
+                bindings: btreemap! {
+                    "X".to_string() => Term::integer(2),
+                    "Y".to_string() => Term::integer(2),
+                },
+                residual_goals: vec![],
+            })]
+        );
+
+        assert_eq!(machine.foreign_predicates.predicates.len(), 1);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "blocked on streams.rs UB")]
+    fn nondet_foreign_predicates() {
+        let mut machine = Machine::new_lib();
+
+        machine.register_nondet_predicate("app", "upto", 2, |args| {
+            let n = match &args[0] {
+                Term::Integer(n) => i64::try_from(n).unwrap(),
+                _ => 0,
+            };
+
+            (1..=n).map(move |i| Ok(vec![Term::integer(n), Term::integer(i)]))
+        });
+
+        let answers: Vec<_> = machine.query("upto(3, X).").collect();
+
+        assert_eq!(
+            answers,
+            [1, 2, 3]
+                .map(|i| Ok(LeafAnswer::LeafAnswer {
+                    bindings: btreemap! {
+                        "X".to_string() => Term::integer(i),
+                    },
+                    residual_goals: vec![],
+                }))
+                .into_iter()
+                .chain([Ok(LeafAnswer::False)])
+                .collect::<Vec<_>>()
+        );
+
+        let answers: Vec<_> = machine.query("upto(3, X), X > 1, !.").collect();
+
+        assert_eq!(
+            answers,
+            [Ok(LeafAnswer::LeafAnswer {
+                bindings: btreemap! {
+                    "X".to_string() => Term::integer(2),
+                },
+                residual_goals: vec![],
+            })]
+        );
+
+        // the solutions of cut and exhausted calls are dropped.
+        assert!(machine.foreign_predicates.solutions.is_empty());
+    }
+}
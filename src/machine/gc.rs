@@ -1,7 +1,16 @@
 use crate::atom_table::*;
 use crate::machine::heap::*;
+use crate::machine::machine_indices::GlobalVarDir;
+use crate::machine::machine_state::MachineState;
+use crate::machine::{get_structure_index, Machine};
 use crate::types::*;
 
+#[cfg(not(target_arch = "wasm32"))]
+use cpu_time::ProcessTime;
+
+use std::collections::BTreeSet;
+use std::time::Duration;
+
 #[cfg(test)]
 use crate::heap_iter::{FocusedHeapIter, HeapOrStackTag, IterStackLoc};
 
@@ -363,6 +372,556 @@ pub fn mark_cells(heap: &mut Heap, start: usize) {
     while iter.forward().is_some() {}
 }
 
+/// The heap size, in cells, at which the first automatic collection
+/// is triggered unless `MachineConfig` says otherwise.
+pub const DEFAULT_GC_THRESHOLD: usize = 1 << 21;
+
+/// The number of dynamic atoms at which the first automatic atom
+/// collection is triggered.
+pub const DEFAULT_ATOM_GC_THRESHOLD: usize = 1 << 16;
+
+#[derive(Debug)]
+pub(crate) struct GCState {
+    /// heap length at which the next automatic collection runs. zero
+    /// disables automatic collection.
+    pub(crate) threshold: usize,
+    /// the configured threshold. after each collection the threshold
+    /// is reset to twice the surviving heap, but never below this.
+    pub(crate) min_threshold: usize,
+    pub(crate) collections: u64,
+    pub(crate) reclaimed_cells: u64,
+    pub(crate) time: Duration,
+    /// cells held outside the WAM (e.g. by the embedding API) that
+    /// must survive collections. they are relocated in place.
+    pub(crate) roots: Vec<HeapCellValue>,
+    /// number of dynamic atoms at which the next automatic atom
+    /// collection runs, checked after each heap collection. zero
+    /// disables automatic atom collection.
+    pub(crate) atom_threshold: usize,
+    pub(crate) atom_collections: u64,
+    pub(crate) reclaimed_atoms: u64,
+    pub(crate) atom_time: Duration,
+}
+
+impl GCState {
+    pub(crate) fn new(threshold: usize) -> Self {
+        GCState {
+            threshold,
+            min_threshold: threshold,
+            collections: 0,
+            reclaimed_cells: 0,
+            time: Duration::ZERO,
+            roots: vec![],
+            atom_threshold: if threshold > 0 {
+                DEFAULT_ATOM_GC_THRESHOLD
+            } else {
+                0
+            },
+            atom_collections: 0,
+            reclaimed_atoms: 0,
+            atom_time: Duration::ZERO,
+        }
+    }
+}
+
+/// A snapshot of the collector's running totals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GCStatistics {
+    pub collections: u64,
+    pub reclaimed_cells: u64,
+    pub time: Duration,
+    pub heap_cells: usize,
+    pub atom_collections: u64,
+    pub reclaimed_atoms: u64,
+    pub atom_time: Duration,
+    pub dynamic_atoms: usize,
+}
+
+#[inline]
+fn is_heap_ref(tag: HeapCellValueTag) -> bool {
+    matches!(
+        tag,
+        HeapCellValueTag::Var
+            | HeapCellValueTag::AttrVar
+            | HeapCellValueTag::Str
+            | HeapCellValueTag::Lis
+            | HeapCellValueTag::PStrLoc
+            | HeapCellValueTag::PStrOffset
+    )
+}
+
+// roots taken from stack frames and registers may be stale, i.e. left
+// over from a binding that was undone by backtracking. only roots
+// that describe a well-formed term in the current heap are handed to
+// the marker, which otherwise assumes its input is consistent.
+fn markable_root(heap: &Heap, cell: HeapCellValue) -> Option<HeapCellValue> {
+    let len = heap.len();
+
+    read_heap_cell!(unmark_cell_bits!(cell),
+        (HeapCellValueTag::Var | HeapCellValueTag::AttrVar, h) => {
+            // attributed variables are entered through a plain
+            // variable so the marker finds their attribute lists on
+            // the heap rather than past the root cell.
+            (h < len).then(|| heap_loc_as_cell!(h))
+        }
+        (HeapCellValueTag::Str, s) => {
+            if s >= len || heap[s].get_tag() != HeapCellValueTag::Atom {
+                return None;
+            }
+
+            // expanded goals of arity 0 are still written as structures.
+            let arity = cell_as_atom_cell!(heap[s]).get_arity();
+            (s + arity < len).then(|| str_loc_as_cell!(s))
+        }
+        (HeapCellValueTag::Lis, l) => {
+            (l + 1 < len).then(|| list_loc_as_cell!(l))
+        }
+        (HeapCellValueTag::PStrLoc, h) => {
+            let well_formed = h + 1 < len
+                && matches!(
+                    heap[h].get_tag(),
+                    HeapCellValueTag::PStr | HeapCellValueTag::PStrOffset
+                );
+
+            well_formed.then(|| pstr_loc_as_cell!(h))
+        }
+        (HeapCellValueTag::PStrOffset, h) => {
+            let well_formed = h < len
+                && match heap[h].get_tag() {
+                    HeapCellValueTag::PStr => h + 1 < len,
+                    HeapCellValueTag::CStr => true,
+                    _ => false,
+                };
+
+            well_formed.then(|| pstr_offset_as_cell!(h))
+        }
+        _ => {
+            None
+        }
+    )
+}
+
+fn mark_root(heap: &mut Heap, cell: HeapCellValue) {
+    if let Some(root) = markable_root(heap, cell) {
+        heap.push(root);
+
+        let start = heap.len() - 1;
+        mark_cells(heap, start);
+
+        heap.pop();
+    }
+}
+
+/// Maps the index of each marked cell to its index after compaction.
+/// The mark bits are moved out of the heap into a bitmap with a running
+/// count of live cells per word, so the new address of any cell is
+/// found without touching the heap.
+struct ForwardingTable {
+    words: Vec<u64>,
+    counts: Vec<usize>,
+    live: usize,
+}
+
+impl ForwardingTable {
+    fn build(heap: &mut Heap) -> Self {
+        let mut words = vec![0u64; heap.len().div_ceil(64)];
+        let mut counts = Vec::with_capacity(words.len());
+        let mut live = 0;
+
+        for (word_idx, word) in words.iter_mut().enumerate() {
+            counts.push(live);
+
+            let base = word_idx * 64;
+            let end = std::cmp::min(base + 64, heap.len());
+
+            for (offset, cell) in heap[base..end].iter_mut().enumerate() {
+                if cell.get_mark_bit() {
+                    *word |= 1 << offset;
+                    cell.set_mark_bit(false);
+                }
+            }
+
+            live += word.count_ones() as usize;
+        }
+
+        ForwardingTable {
+            words,
+            counts,
+            live,
+        }
+    }
+
+    #[inline]
+    fn is_live(&self, h: usize) -> bool {
+        self.words[h >> 6] & (1 << (h & 63)) != 0
+    }
+
+    #[inline]
+    fn forward(&self, h: usize) -> usize {
+        let word_idx = h >> 6;
+
+        if word_idx >= self.words.len() {
+            return self.live;
+        }
+
+        let below = self.words[word_idx] & ((1 << (h & 63)) - 1);
+        self.counts[word_idx] + below.count_ones() as usize
+    }
+
+    #[inline]
+    fn relocate(&self, cell: &mut HeapCellValue, old_len: usize) {
+        if is_heap_ref(cell.get_tag()) {
+            let h = cell.get_value() as usize;
+
+            if h < old_len {
+                cell.set_value(self.forward(h) as u64);
+            }
+        }
+    }
+}
+
+impl MachineState {
+    pub(super) fn for_each_root(
+        &mut self,
+        global_variables: &mut GlobalVarDir,
+        arity: usize,
+        mut visit: impl FnMut(&mut Heap, &mut HeapCellValue),
+    ) {
+        let MachineState {
+            heap,
+            stack,
+            registers,
+            trail,
+            attr_var_init,
+            cont_pts,
+            gc,
+            b,
+            e,
+            ..
+        } = self;
+
+        for r in registers[1..arity + 1].iter_mut() {
+            visit(heap, r);
+        }
+
+        // environments are shared between the continuation chain and
+        // the environments saved by choice points, so each is visited
+        // once. offset 0 holds the stub frame reserved in
+        // Machine::new and terminates every chain.
+        let mut visited_envs = BTreeSet::new();
+        let mut env_chains = vec![*e];
+        let mut b = *b;
+
+        while b > 0 {
+            let or_frame = stack.index_or_frame_mut(b);
+
+            for idx in 0..or_frame.prelude.num_cells {
+                visit(heap, &mut or_frame[idx]);
+            }
+
+            env_chains.push(or_frame.prelude.e);
+            b = or_frame.prelude.b;
+        }
+
+        for mut e in env_chains {
+            while e > 0 && visited_envs.insert(e) {
+                let and_frame = stack.index_and_frame_mut(e);
+
+                for idx in 1..and_frame.prelude.num_cells + 1 {
+                    visit(heap, &mut and_frame[idx]);
+                }
+
+                e = and_frame.prelude.e;
+            }
+        }
+
+        let mut idx = 0;
+
+        while idx < trail.len() {
+            let tag = trail[idx].get_tag();
+
+            match tag {
+                TrailEntryTag::TrailedHeapVar
+                | TrailEntryTag::TrailedAttrVar
+                | TrailEntryTag::TrailedAttrVarListLink
+                | TrailEntryTag::TrailedAttachedValue => {
+                    let h = trail[idx].get_value() as usize;
+
+                    if tag == TrailEntryTag::TrailedAttrVar {
+                        // unwinding restores the variable, which
+                        // needs its attribute list back as well.
+                        visit(heap, &mut heap_loc_as_cell!(h + 1));
+                    }
+
+                    let mut cell = heap_loc_as_cell!(h);
+                    visit(heap, &mut cell);
+                    trail[idx] = TrailEntry::build_with(tag, cell.get_value());
+                }
+                TrailEntryTag::TrailedBlackboardOffset => {
+                    // the next entry is the raw value cell, not a tagged entry.
+                    idx += 1;
+
+                    let mut cell = HeapCellValue::from(u64::from(trail[idx]));
+                    visit(heap, &mut cell);
+                    trail[idx] = TrailEntry::from_bytes(cell.into_bytes());
+                }
+                TrailEntryTag::TrailedStackVar | TrailEntryTag::TrailedBlackboardEntry => {}
+            }
+
+            idx += 1;
+        }
+
+        for h in attr_var_init.attr_var_queue.iter_mut() {
+            let mut cell = heap_loc_as_cell!(*h);
+            visit(heap, &mut cell);
+            *h = cell.get_value() as usize;
+        }
+
+        for (h, value) in attr_var_init.bindings.iter_mut() {
+            let mut cell = heap_loc_as_cell!(*h);
+            visit(heap, &mut cell);
+            *h = cell.get_value() as usize;
+
+            visit(heap, value);
+        }
+
+        for (cell, _, _) in cont_pts.iter_mut() {
+            visit(heap, cell);
+        }
+
+        for (_, (_, value)) in global_variables.iter_mut() {
+            if let Some(cell) = value {
+                visit(heap, cell);
+            }
+        }
+
+        for cell in gc.roots.iter_mut() {
+            visit(heap, cell);
+        }
+    }
+
+    /// Removes the trail entries of heap cells that are discarded anyway
+    /// when the choice point owning the entry is backtracked into, i.e.
+    /// cells allocated after that choice point. Without this, bindings
+    /// made under a since-cut choice point keep their cells alive.
+    fn tidy_trail(&mut self) {
+        // (tr, h) of every choice point, oldest first.
+        let mut segments = vec![];
+        let mut b = self.b;
+
+        while b > 0 {
+            let or_frame = self.stack.index_or_frame(b);
+
+            segments.push((or_frame.prelude.tr, or_frame.prelude.h));
+            b = or_frame.prelude.b;
+        }
+
+        segments.reverse();
+
+        let mut kept_before = Vec::with_capacity(self.trail.len() + 1);
+        let mut segment = 0;
+        let mut idx = 0;
+        let mut dest = 0;
+
+        while idx < self.trail.len() {
+            while segment < segments.len() && segments[segment].0 <= idx {
+                segment += 1;
+            }
+
+            // entries below the oldest choice point are never unwound.
+            let boundary = if segment == 0 {
+                0
+            } else {
+                segments[segment - 1].1
+            };
+
+            let entry = self.trail[idx];
+            let width = match entry.get_tag() {
+                TrailEntryTag::TrailedAttrVarListLink | TrailEntryTag::TrailedBlackboardOffset => 2,
+                _ => 1,
+            };
+
+            let keep = match entry.get_tag() {
+                TrailEntryTag::TrailedHeapVar
+                | TrailEntryTag::TrailedAttrVar
+                | TrailEntryTag::TrailedAttrVarListLink => (entry.get_value() as usize) < boundary,
+                _ => true,
+            };
+
+            for offset in 0..width {
+                kept_before.push(dest);
+
+                if keep {
+                    self.trail[dest] = self.trail[idx + offset];
+                    dest += 1;
+                }
+            }
+
+            idx += width;
+        }
+
+        kept_before.push(dest);
+
+        self.trail.truncate(dest);
+        self.tr = kept_before[std::cmp::min(self.tr, kept_before.len() - 1)];
+
+        let mut b = self.b;
+
+        while b > 0 {
+            let or_frame = self.stack.index_or_frame_mut(b);
+
+            or_frame.prelude.tr = kept_before[or_frame.prelude.tr];
+            b = or_frame.prelude.b;
+        }
+    }
+
+    /// Reclaims every heap cell not reachable from the machine roots,
+    /// sliding the survivors down in address order so that the heap
+    /// segments delimited by choice points are preserved. Argument
+    /// registers above `arity` are treated as dead. Returns the number
+    /// of cells reclaimed.
+    pub(crate) fn collect_garbage(
+        &mut self,
+        global_variables: &mut GlobalVarDir,
+        arity: usize,
+    ) -> usize {
+        #[cfg(not(target_arch = "wasm32"))]
+        let start_time = ProcessTime::now();
+
+        let old_len = self.heap.len();
+
+        self.tidy_trail();
+        self.for_each_root(global_variables, arity, |heap, cell| mark_root(heap, *cell));
+
+        // cells the marker doesn't reach but which must move along
+        // with the cells they trail: the offset of a partial string
+        // location and the code index cached behind a goal structure.
+        for h in 0..old_len {
+            if !self.heap[h].get_mark_bit() {
+                continue;
+            }
+
+            read_heap_cell!(self.heap[h],
+                (HeapCellValueTag::PStrOffset) => {
+                    if h + 1 < old_len {
+                        self.heap[h + 1].set_mark_bit(true);
+                    }
+                }
+                (HeapCellValueTag::Atom, (_name, arity)) => {
+                    if h + arity + 1 < old_len {
+                        let cell = self.heap[h + arity + 1];
+
+                        if get_structure_index(cell).is_some() {
+                            self.heap[h + arity + 1].set_mark_bit(true);
+                        }
+                    }
+                }
+                _ => {
+                }
+            );
+        }
+
+        let table = ForwardingTable::build(&mut self.heap);
+
+        self.for_each_root(global_variables, arity, |_, cell| {
+            table.relocate(cell, old_len)
+        });
+
+        self.hb = table.forward(self.hb);
+
+        let mut b = self.b;
+
+        while b > 0 {
+            let or_frame = self.stack.index_or_frame_mut(b);
+
+            or_frame.prelude.h = table.forward(or_frame.prelude.h);
+            b = or_frame.prelude.b;
+        }
+
+        let mut dest = 0;
+
+        for h in 0..old_len {
+            if table.is_live(h) {
+                let mut cell = self.heap[h];
+
+                table.relocate(&mut cell, old_len);
+                self.heap[dest] = cell;
+
+                dest += 1;
+            }
+        }
+
+        self.heap.truncate(dest);
+
+        if self.heap.capacity() > 4 * std::cmp::max(dest, self.gc.min_threshold) {
+            self.heap
+                .shrink_to(2 * std::cmp::max(dest, self.gc.min_threshold));
+        }
+
+        let reclaimed = old_len - dest;
+
+        self.gc.collections += 1;
+        self.gc.reclaimed_cells += reclaimed as u64;
+
+        if self.gc.min_threshold > 0 {
+            self.gc.threshold = std::cmp::max(self.gc.min_threshold, 2 * dest);
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.gc.time += start_time.elapsed();
+        }
+
+        reclaimed
+    }
+}
+
+impl Machine {
+    /// Collects the heap if it has grown past the collection threshold,
+    /// and the atom table along with it if the number of atoms has.
+    /// Called at predicate entry, where only the first `arity` argument
+    /// registers are live.
+    #[inline(always)]
+    pub(super) fn maybe_garbage_collect(&mut self, arity: usize) {
+        let gc = &self.machine_st.gc;
+
+        let collect_heap = gc.threshold > 0 && self.machine_st.heap.len() >= gc.threshold;
+        let collect_atoms = gc.atom_threshold > 0
+            && self.machine_st.atom_tbl.num_dynamic_atoms() >= gc.atom_threshold;
+
+        if collect_heap || collect_atoms {
+            // atoms are marked on the heap, which must hold only live
+            // cells.
+            self.machine_st
+                .collect_garbage(&mut self.indices.global_variables, arity);
+
+            if collect_atoms {
+                self.collect_atoms(arity);
+            }
+        }
+    }
+
+    /// Runs a full collection of the heap immediately, regardless of
+    /// the collection threshold.
+    pub fn garbage_collect(&mut self) {
+        self.machine_st
+            .collect_garbage(&mut self.indices.global_variables, 0);
+    }
+
+    pub fn gc_statistics(&self) -> GCStatistics {
+        GCStatistics {
+            collections: self.machine_st.gc.collections,
+            reclaimed_cells: self.machine_st.gc.reclaimed_cells,
+            time: self.machine_st.gc.time,
+            heap_cells: self.machine_st.heap.len(),
+            atom_collections: self.machine_st.gc.atom_collections,
+            reclaimed_atoms: self.machine_st.gc.reclaimed_atoms,
+            atom_time: self.machine_st.gc.atom_time,
+            dynamic_atoms: self.machine_st.atom_tbl.num_dynamic_atoms(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1925,4 +2484,111 @@ fn heap_marking_tests() {
             fixnum_as_cell!(Fixnum::build_with(4))
         );
     }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "blocked on streams.rs UB")]
+    fn heap_compaction_tests() {
+        let mut wam = MockWAM::new();
+        let mut global_variables = GlobalVarDir::default();
+
+        // f(a), unreachable.
+        wam.machine_st.heap.push(str_loc_as_cell!(1));
+        wam.machine_st.heap.push(atom_as_cell!(atom!("f"), 1));
+        wam.machine_st.heap.push(atom_as_cell!(atom!("a")));
+
+        // g(X, [1]), reachable from the first register.
+        wam.machine_st.heap.push(atom_as_cell!(atom!("g"), 2));
+        wam.machine_st.heap.push(heap_loc_as_cell!(4));
+        wam.machine_st.heap.push(list_loc_as_cell!(6));
+        wam.machine_st
+            .heap
+            .push(fixnum_as_cell!(Fixnum::build_with(1)));
+        wam.machine_st.heap.push(empty_list_as_cell!());
+
+        wam.machine_st.registers[1] = str_loc_as_cell!(3);
+        wam.machine_st.registers[2] = str_loc_as_cell!(1);
+        wam.machine_st.hb = 8;
+
+        let reclaimed = wam.machine_st.collect_garbage(&mut global_variables, 1);
+
+        assert_eq!(reclaimed, 3);
+        assert_eq!(wam.machine_st.heap.len(), 5);
+        assert_eq!(wam.machine_st.hb, 5);
+
+        all_cells_unmarked(&wam.machine_st.heap);
+
+        assert_eq!(wam.machine_st.registers[1], str_loc_as_cell!(0));
+        assert_eq!(wam.machine_st.heap[0], atom_as_cell!(atom!("g"), 2));
+        assert_eq!(wam.machine_st.heap[1], heap_loc_as_cell!(1));
+        assert_eq!(wam.machine_st.heap[2], list_loc_as_cell!(3));
+        assert_eq!(
+            wam.machine_st.heap[3],
+            fixnum_as_cell!(Fixnum::build_with(1))
+        );
+        assert_eq!(wam.machine_st.heap[4], empty_list_as_cell!());
+
+        // with no live registers and nothing else rooting it, the
+        // whole heap is garbage.
+        let reclaimed = wam.machine_st.collect_garbage(&mut global_variables, 0);
+
+        assert_eq!(reclaimed, 5);
+        assert!(wam.machine_st.heap.is_empty());
+        assert_eq!(wam.machine_st.gc.collections, 2);
+        assert_eq!(wam.machine_st.gc.reclaimed_cells, 8);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "blocked on streams.rs UB")]
+    fn heap_compaction_trail_tests() {
+        let mut wam = MockWAM::new();
+        let mut global_variables = GlobalVarDir::default();
+
+        // reserve offset 0 as Machine::new does.
+        wam.machine_st.stack.allocate_and_frame(0);
+
+        // garbage, then a variable older than the choice point.
+        wam.machine_st.heap.push(atom_as_cell!(atom!("garbage")));
+        wam.machine_st.heap.push(heap_loc_as_cell!(1));
+
+        let b = wam.machine_st.stack.allocate_or_frame(0);
+        let or_frame = wam.machine_st.stack.index_or_frame_mut(b);
+
+        or_frame.prelude.b = 0;
+        or_frame.prelude.e = 0;
+        or_frame.prelude.tr = 0;
+        or_frame.prelude.h = 2;
+
+        wam.machine_st.b = b;
+        wam.machine_st.hb = 2;
+
+        // bind the old variable to a new structure and trail it, then
+        // bind a new variable, whose trail entry is superfluous.
+        wam.machine_st.heap.push(heap_loc_as_cell!(2));
+        wam.machine_st.heap.push(str_loc_as_cell!(4));
+        wam.machine_st.heap.push(atom_as_cell!(atom!("h"), 1));
+        wam.machine_st.heap.push(heap_loc_as_cell!(2));
+        wam.machine_st.heap[1] = str_loc_as_cell!(4);
+
+        wam.machine_st.trail(TrailRef::Ref(Ref::heap_cell(1)));
+        wam.machine_st.hb = 6;
+        wam.machine_st.trail(TrailRef::Ref(Ref::heap_cell(2)));
+
+        assert_eq!(wam.machine_st.tr, 2);
+
+        wam.machine_st.collect_garbage(&mut global_variables, 0);
+
+        // the garbage atom and the unused cell at 3 are gone.
+        assert_eq!(wam.machine_st.heap.len(), 4);
+        assert_eq!(wam.machine_st.hb, 4);
+        assert_eq!(wam.machine_st.stack.index_or_frame(b).prelude.h, 1);
+
+        assert_eq!(wam.machine_st.tr, 1);
+        assert_eq!(wam.machine_st.trail.len(), 1);
+        assert_eq!(wam.machine_st.trail[0].get_value(), 0);
+
+        assert_eq!(wam.machine_st.heap[0], str_loc_as_cell!(2));
+        assert_eq!(wam.machine_st.heap[1], heap_loc_as_cell!(1));
+        assert_eq!(wam.machine_st.heap[2], atom_as_cell!(atom!("h"), 1));
+        assert_eq!(wam.machine_st.heap[3], heap_loc_as_cell!(1));
+    }
 }
@@ -7,6 +7,7 @@
 use crate::types::*;
 
 use crate::parser::dashu::{Integer, Rational};
+use crate::parser::decimal::Decimal;
 
 use std::convert::TryFrom;
 
@@ -28,6 +29,9 @@ fn from(literal: Literal) -> Self {
             Literal::Rational(bigint_ptr) => {
                 typed_arena_ptr_as_cell!(bigint_ptr)
             }
+            Literal::Decimal(decimal_ptr) => {
+                typed_arena_ptr_as_cell!(decimal_ptr)
+            }
             Literal::Float(f) => HeapCellValue::from(f.as_ptr()),
             Literal::String(s) => {
                 if s == atom!("") {
@@ -69,6 +73,9 @@ fn try_from(value: HeapCellValue) -> Result<Literal, ()> {
                      (ArenaHeaderTag::Rational, n) => {
                          Ok(Literal::Rational(n))
                      }
+                     (ArenaHeaderTag::Decimal, n) => {
+                         Ok(Literal::Decimal(n))
+                     }
                      (ArenaHeaderTag::IndexPtr, _ip) => {
                          Ok(Literal::CodeIndex(CodeIndex::from(cons_ptr)))
                      }
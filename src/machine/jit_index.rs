@@ -0,0 +1,1239 @@
+use crate::arena::*;
+use crate::atom_table::*;
+use crate::forms::*;
+use crate::indexing::*;
+use crate::instructions::*;
+use crate::machine::machine_state::MachineState;
+use crate::machine::Machine;
+use crate::parser::ast::*;
+use crate::types::*;
+
+use fxhash::FxBuildHasher;
+use indexmap::{IndexMap, IndexSet};
+
+use std::iter::once;
+use std::ops::Range;
+
+/// The fewest clauses a subsequence of a predicate must have for it
+/// to be indexed just in time. Smaller subsequences are tried in turn.
+const JIT_INDEX_MIN_CLAUSES: usize = 8;
+
+#[derive(Debug)]
+struct JitIndex {
+    // whether the subsequence can be indexed just in time at all.
+    indexable: bool,
+    // the first line of the index built for each set of bound
+    // arguments, or `None` if none improves on the subsequence's own.
+    roots: IndexMap<u64, Option<usize>, FxBuildHasher>,
+    // the positions the SwitchOnTerm lines of the indices switch on.
+    switches: IndexMap<usize, ArgPosition, FxBuildHasher>,
+    // the lines of the indices, appended to those of the indexing
+    // code they were built for.
+    lines: Range<usize>,
+    // the clauses of a chain of subsequences, by the start of their
+    // code, if the indexing code was built for the chain.
+    chain: Option<Vec<usize>>,
+}
+
+/// The indices built just in time for subsequences of clauses, by the
+/// location of the subsequence's `IndexingCode`.
+///
+/// The first time a subsequence is called with a set of arguments
+/// bound, an index is built that switches on those arguments in turn,
+/// and on the subterms of those where the clauses agree on a principal
+/// functor, as `PositionIndices` does. It is used by calls binding the
+/// same arguments unless it only switches on the first argument the
+/// subsequence is indexed on already. Only the arguments the clauses
+/// have a constant, list or structure at are switched on. The lines of
+/// the indices are appended to the subsequence's indexing code and
+/// dispatched to by `execute_switch_on_term`. The loader invalidates
+/// the indices of a subsequence before adding clauses to it or
+/// removing clauses from it. Retracting dynamic clauses leaves the
+/// indices valid, since the clauses are kept in them and skipped once
+/// dead. The clauses of `'$clause'/2` are never indexed just in time.
+///
+/// A static predicate split into several subsequences, e.g. because
+/// its clauses are indexed on different arguments, has no indexing code
+/// of its own, only the choice instructions chaining its subsequences.
+/// The first call to bind an argument of such a chain appends indexing
+/// code for it to the code area, followed by a `RevJmpBy` to each of
+/// its clauses, and later calls binding arguments go through the
+/// indices of that code as they would through a subsequence's.
+#[derive(Debug, Default)]
+pub(crate) struct JitIndices {
+    indices: IndexMap<usize, JitIndex, FxBuildHasher>,
+    // the location of the indexing code appended for each chain, by
+    // the location of its first choice instruction, or `None` if the
+    // chain can't be indexed.
+    chains: IndexMap<usize, Option<usize>, FxBuildHasher>,
+}
+
+impl JitIndices {
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.indices.is_empty() && self.chains.is_empty()
+    }
+
+    /// Forgets every index without removing its lines, for when the
+    /// indexing code may have been replaced.
+    pub(crate) fn clear(&mut self) {
+        self.indices.clear();
+        self.chains.clear();
+    }
+
+    /// Drops the index of the chain starting at `chain_loc`, whose
+    /// subsequences are about to change. The code appended for it is
+    /// left to the choice points iterating over it.
+    pub(crate) fn invalidate_chain(&mut self, chain_loc: usize) {
+        if let Some(Some(index_loc)) = self.chains.swap_remove(&chain_loc) {
+            self.indices.swap_remove(&index_loc);
+        }
+    }
+
+    /// Drops the indices of the subsequence at `index_loc`. Their lines
+    /// are removed from the indexing code unless a choice point still
+    /// iterates over them, in which case they're left to it.
+    pub(crate) fn invalidate(
+        &mut self,
+        index_loc: usize,
+        code: &mut Code,
+        machine_st: &MachineState,
+    ) {
+        let Some(jit_index) = self.indices.swap_remove(&index_loc) else {
+            return;
+        };
+
+        let Instruction::IndexingCode(ref mut indexing_lines) = &mut code[index_loc] else {
+            return;
+        };
+
+        if indexing_lines.len() != jit_index.lines.end {
+            return;
+        }
+
+        let mut b = machine_st.b;
+
+        while b > 0 {
+            let or_frame = machine_st.stack.index_or_frame(b);
+
+            if or_frame.prelude.bp == index_loc
+                && or_frame.prelude.boip as usize >= jit_index.lines.start
+            {
+                return;
+            }
+
+            b = or_frame.prelude.b;
+        }
+
+        indexing_lines.truncate(jit_index.lines.start);
+    }
+}
+
+fn index_ptr_target(ptr: IndexingCodePtr, index: usize, lines: &mut Vec<usize>) -> Option<usize> {
+    match ptr {
+        IndexingCodePtr::External(o) | IndexingCodePtr::DynamicExternal(o) => Some(o),
+        IndexingCodePtr::Internal(o) => {
+            lines.push(index + o);
+            None
+        }
+        IndexingCodePtr::Fail => None,
+    }
+}
+
+fn mentions_register(instr: &Instruction, r: RegType) -> bool {
+    match instr {
+        &Instruction::UnifyValue(s) => s == r,
+        instr => instr.registers().contains(&r),
+    }
+}
+
+impl MachineState {
+    fn constant_key(&self, c: HeapCellValue) -> Option<ArgIndexKey> {
+        read_heap_cell!(c,
+            (HeapCellValueTag::Atom, (_name, arity)) => {
+                (arity == 0).then(|| ArgIndexKey::Constant(self.constant_to_literal(c)))
+            }
+            (HeapCellValueTag::Char | HeapCellValueTag::Fixnum | HeapCellValueTag::F64) => {
+                Some(ArgIndexKey::Constant(self.constant_to_literal(c)))
+            }
+            (HeapCellValueTag::Cons, ptr) => {
+                match ptr.get_tag() {
+                    ArenaHeaderTag::Integer | ArenaHeaderTag::Rational | ArenaHeaderTag::Decimal => {
+                        Some(ArgIndexKey::Constant(self.constant_to_literal(c)))
+                    }
+                    _ => None,
+                }
+            }
+            _ => {
+                None
+            }
+        )
+    }
+
+    /// The key of the clause whose code starts at `p` at `position`,
+    /// read from the head instruction unifying the argument of the
+    /// position and from those unifying its subterms on the way to the
+    /// position. `None` if the position holds a variable, doesn't
+    /// exist or its key can't be told from the code.
+    fn clause_key(&self, code: &Code, mut p: usize, position: &ArgPosition) -> Option<ArgIndexKey> {
+        if let Instruction::Allocate(_) = &code[p] {
+            p += 1;
+        }
+
+        let arg_reg = RegType::Temp(position.arg);
+
+        loop {
+            if !code[p].is_head_instr() {
+                return None;
+            }
+
+            match &code[p] {
+                &Instruction::GetConstant(Level::Shallow, _, r)
+                | &Instruction::GetList(Level::Shallow, r)
+                | &Instruction::GetPartialString(Level::Shallow, _, r, _)
+                | &Instruction::GetStructure(Level::Shallow, _, _, r)
+                    if r == arg_reg =>
+                {
+                    break;
+                }
+                instr if mentions_register(instr, arg_reg) => {
+                    return None;
+                }
+                _ => {
+                    p += 1;
+                }
+            }
+        }
+
+        for &n in &position.path {
+            let arity = match &code[p] {
+                Instruction::GetList(..) => 2,
+                &Instruction::GetStructure(_, _, arity, _) => arity,
+                _ => return None,
+            };
+
+            if n > arity {
+                return None;
+            }
+
+            // the instruction unifying the nth subterm.
+            let mut subterm = 1;
+            p += 1;
+
+            loop {
+                match code[p] {
+                    Instruction::UnifyVoid(vars) if n < subterm + vars => return None,
+                    Instruction::UnifyVoid(vars) => subterm += vars,
+                    _ if n == subterm => break,
+                    _ => subterm += 1,
+                }
+
+                p += 1;
+            }
+
+            if let &Instruction::UnifyVariable(r) = &code[p] {
+                // the subterm is unified by the first instruction to
+                // use its register after it, if by any.
+                p += 1;
+
+                loop {
+                    if !code[p].is_head_instr() {
+                        return None;
+                    }
+
+                    match &code[p] {
+                        &Instruction::GetConstant(_, _, s)
+                        | &Instruction::GetList(_, s)
+                        | &Instruction::GetPartialString(_, _, s, _)
+                        | &Instruction::GetStructure(_, _, _, s)
+                            if s == r =>
+                        {
+                            break;
+                        }
+                        instr if mentions_register(instr, r) => {
+                            return None;
+                        }
+                        _ => {
+                            p += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        match &code[p] {
+            &Instruction::GetConstant(_, c, _) | &Instruction::UnifyConstant(c) => {
+                self.constant_key(c)
+            }
+            Instruction::GetList(..) | Instruction::GetPartialString(..) => Some(ArgIndexKey::List),
+            &Instruction::GetStructure(_, name, arity, _) => {
+                Some(ArgIndexKey::Structure(name, arity))
+            }
+            _ => None,
+        }
+    }
+
+    /// The term at `position` in the arguments of the current call, or
+    /// the variable it's inside of. `None` if the terms on the way to
+    /// the position have too few arguments for it to exist.
+    fn call_position_cell(&self, position: &ArgPosition) -> Option<HeapCellValue> {
+        let mut cell = self.store(self.deref(self.registers[position.arg]));
+
+        for &n in &position.path {
+            if cell.is_var() {
+                return Some(cell);
+            }
+
+            let subterm = read_heap_cell!(cell,
+                (HeapCellValueTag::Str, s) => {
+                    let arity = cell_as_atom_cell!(self.heap[s]).get_arity();
+
+                    if n > arity {
+                        return None;
+                    }
+
+                    self.heap[s + n]
+                }
+                (HeapCellValueTag::Lis, l) => {
+                    if n > 2 {
+                        return None;
+                    }
+
+                    self.heap[l + n - 1]
+                }
+                _ => {
+                    return None;
+                }
+            );
+
+            cell = self.store(self.deref(subterm));
+        }
+
+        Some(cell)
+    }
+}
+
+impl Machine {
+    /// The clauses of the subsequence at `index_loc` in order, by the
+    /// start of their code, and whether they're dynamic. `None` if the
+    /// clauses threaded by its choice instructions aren't those of its
+    /// indexing code.
+    fn jit_index_clauses(&self, index_loc: usize) -> Option<(Vec<usize>, bool)> {
+        let Instruction::IndexingCode(ref indexing_lines) = &self.code[index_loc] else {
+            return None;
+        };
+
+        let (var_offset, is_dynamic) = match &indexing_lines[0] {
+            &IndexingLine::Indexing(IndexingInstruction::SwitchOnTerm(_, v, ..)) => match v {
+                IndexingCodePtr::External(o) => (o, false),
+                IndexingCodePtr::DynamicExternal(o) => (o, true),
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        // the clauses the indexing code reaches.
+        let mut targets = IndexSet::with_hasher(FxBuildHasher::default());
+        let mut lines = vec![0];
+
+        while let Some(index) = lines.pop() {
+            match &indexing_lines[index] {
+                &IndexingLine::Indexing(IndexingInstruction::SwitchOnTerm(_, _, c, l, s)) => {
+                    for ptr in [c, l, s] {
+                        targets.extend(index_ptr_target(ptr, index, &mut lines));
+                    }
+                }
+                IndexingLine::Indexing(IndexingInstruction::SwitchOnConstant(hm)) => {
+                    for &ptr in hm.values() {
+                        targets.extend(index_ptr_target(ptr, index, &mut lines));
+                    }
+                }
+                IndexingLine::Indexing(IndexingInstruction::SwitchOnStructure(hm)) => {
+                    for &ptr in hm.values() {
+                        targets.extend(index_ptr_target(ptr, index, &mut lines));
+                    }
+                }
+                IndexingLine::IndexedChoice(indexed_choice) => {
+                    targets.extend(indexed_choice.iter().map(|instr| instr.offset()));
+                }
+                IndexingLine::DynamicIndexedChoice(offsets) => {
+                    targets.extend(offsets.iter().copied());
+                }
+            }
+        }
+
+        let targets: IndexSet<usize, FxBuildHasher> = targets
+            .into_iter()
+            .map(|o| match &self.code[index_loc + o] {
+                Instruction::DynamicInternalElse(..) => index_loc + o + 1,
+                _ => index_loc + o,
+            })
+            .collect();
+
+        // the clauses threaded by the choice instructions, in order.
+        let mut clauses = vec![];
+        let mut p = index_loc + var_offset;
+
+        while clauses.len() <= targets.len() {
+            match &self.code[p] {
+                &Instruction::TryMeElse(o)
+                | &Instruction::RetryMeElse(o)
+                | &Instruction::DefaultRetryMeElse(o)
+                    if !is_dynamic =>
+                {
+                    clauses.push(p + 1);
+
+                    if o == 0 {
+                        break;
+                    }
+
+                    p += o;
+                }
+                Instruction::TrustMe(_) | Instruction::DefaultTrustMe(_) if !is_dynamic => {
+                    clauses.push(p + 1);
+                    break;
+                }
+                &Instruction::DynamicInternalElse(_, _, next_or_fail) if is_dynamic => {
+                    clauses.push(p + 1);
+
+                    match next_or_fail {
+                        NextOrFail::Next(i) if i > 0 => p += i,
+                        _ => break,
+                    }
+                }
+                &Instruction::RevJmpBy(o) => {
+                    p -= o;
+                }
+                _ => {
+                    return None;
+                }
+            }
+        }
+
+        let is_consistent =
+            clauses.len() == targets.len() && clauses.iter().all(|clause| targets.contains(clause));
+
+        is_consistent.then_some((clauses, is_dynamic))
+    }
+
+    /// Whether `index_loc` is the indexing code of a `'$clause'/2`
+    /// predicate, the choice points of which `get_clause_p` reads as
+    /// those of its own index.
+    fn is_clause_clause_index(&self, index_loc: usize) -> bool {
+        let key = (atom!("$clause"), 2);
+
+        once(&self.indices.code_dir)
+            .chain(self.indices.modules.values().map(|module| &module.code_dir))
+            .filter_map(|code_dir| code_dir.get(&key))
+            .filter_map(|code_index| code_index.local())
+            .any(|mut p| {
+                while let &Instruction::RevJmpBy(offset) = &self.code[p] {
+                    p -= offset;
+                }
+
+                p == index_loc
+            })
+    }
+
+    fn non_counted_backtracking(&self, clauses: &[usize]) -> bool {
+        clauses.iter().any(|&clause| {
+            matches!(
+                self.code[clause - 1],
+                Instruction::DefaultRetryMeElse(_) | Instruction::DefaultTrustMe(_)
+            )
+        })
+    }
+
+    // indexes `clauses` on `candidates` by the code the indexing code
+    // at `index_loc` jumps to for each, in `targets`.
+    fn jit_index_lines<I: Indexer>(
+        &self,
+        index_loc: usize,
+        clauses: &[usize],
+        targets: &[usize],
+        candidates: Vec<ArgPosition>,
+    ) -> Option<PositionIndex> {
+        let non_counted_bt = self.non_counted_backtracking(clauses);
+        let indices: Vec<usize> = targets
+            .iter()
+            .map(|&target| target - index_loc - 1)
+            .collect();
+        let clause_of: IndexMap<usize, usize, FxBuildHasher> =
+            indices.iter().copied().zip(clauses.iter().copied()).collect();
+
+        let keys = |index: usize, position: &ArgPosition| {
+            self.machine_st
+                .clause_key(&self.code, clause_of[&index], position)
+        };
+
+        PositionIndices::<I, _>::new(keys, &self.machine_st.atom_tbl, non_counted_bt)
+            .compute_indices(&indices, candidates)
+    }
+
+    /// Builds the index of the subsequence at `index_loc` for calls
+    /// binding `bound_args`, returning its first line, or `None` if it
+    /// does no better than switching on `switch_arg`, the argument the
+    /// subsequence is indexed on already, if any. If the subsequence
+    /// can't be indexed just in time, `Err(())`.
+    fn build_jit_index(
+        &mut self,
+        index_loc: usize,
+        switch_arg: Option<usize>,
+        bound_args: u64,
+    ) -> Result<Option<usize>, ()> {
+        let chain = self.indices.jit_indices.indices[&index_loc].chain.clone();
+
+        let (clauses, targets, is_dynamic) = match chain {
+            Some(clauses) => {
+                let targets = (index_loc + 1..).take(clauses.len()).collect();
+                (clauses, targets, false)
+            }
+            None => match self.jit_index_clauses(index_loc) {
+                Some((clauses, _)) if clauses.len() < JIT_INDEX_MIN_CLAUSES => return Err(()),
+                Some((clauses, is_dynamic)) => (clauses.clone(), clauses, is_dynamic),
+                None => return Err(()),
+            },
+        };
+
+        let candidates = (1..=u64::BITS as usize)
+            .filter(|&arg| bound_args & (1 << (arg - 1)) != 0)
+            .map(ArgPosition::arg)
+            .collect();
+
+        let index = if is_dynamic {
+            self.jit_index_lines::<DynamicCodeIndices>(index_loc, &clauses, &targets, candidates)
+        } else {
+            self.jit_index_lines::<StaticCodeIndices>(index_loc, &clauses, &targets, candidates)
+        };
+
+        let Some((index_lines, switches)) = index else {
+            return Ok(None);
+        };
+
+        if let (Some(switch_arg), [(_, position)]) = (switch_arg, switches.as_slice()) {
+            if *position == ArgPosition::arg(switch_arg) {
+                return Ok(None);
+            }
+        }
+
+        let indexing_lines = self.code[index_loc].to_indexing_line_mut().unwrap();
+
+        let start = indexing_lines.len();
+        indexing_lines.extend(index_lines);
+        let end = indexing_lines.len();
+
+        let jit_index = &mut self.indices.jit_indices.indices[&index_loc];
+
+        jit_index.lines.end = end;
+        jit_index.switches.extend(
+            switches
+                .into_iter()
+                .map(|(line, position)| (start + line, position)),
+        );
+
+        Ok(Some(start))
+    }
+
+    /// Follows the index of the subsequence at the current `p` from
+    /// its first line `root` down to the last `SwitchOnTerm` line the
+    /// current call reaches, returning the line and the term at its
+    /// position for `execute_switch_on_term` to dispatch on. If the
+    /// position doesn't exist in the call, the line of its clauses is
+    /// returned instead, with `arg_addr`.
+    fn follow_jit_index(&self, root: usize, arg_addr: HeapCellValue) -> (usize, HeapCellValue) {
+        let index_loc = self.machine_st.p;
+        let indexing_lines = self.code[index_loc].to_indexing_line().unwrap();
+        let switches = &self.indices.jit_indices.indices[&index_loc].switches;
+
+        let mut line = root;
+
+        loop {
+            let (v, c, l, s) = match &indexing_lines[line] {
+                &IndexingLine::Indexing(IndexingInstruction::SwitchOnTerm(_, v, c, l, s)) => {
+                    (v, c, l, s)
+                }
+                _ => {
+                    unreachable!()
+                }
+            };
+
+            let Some(addr) = self.machine_st.call_position_cell(&switches[&line]) else {
+                // no clause can match, which trying the clauses of the
+                // line as for an unbound position settles as well as
+                // failing.
+                let IndexingCodePtr::Internal(o) = v else {
+                    unreachable!()
+                };
+
+                if switches.contains_key(&(line + o)) {
+                    line += o;
+                    continue;
+                }
+
+                return (line + o, arg_addr);
+            };
+
+            let mut next = line;
+            let mut ptr = self
+                .machine_st
+                .select_switch_on_term_index(addr, v, c, l, s);
+
+            if let IndexingCodePtr::Internal(o) = ptr {
+                next += o;
+
+                ptr = match &indexing_lines[next] {
+                    IndexingLine::Indexing(IndexingInstruction::SwitchOnConstant(hm)) => {
+                        let lit = self.machine_st.constant_to_literal(addr);
+                        hm.get(&lit).cloned().unwrap_or(IndexingCodePtr::Fail)
+                    }
+                    IndexingLine::Indexing(IndexingInstruction::SwitchOnStructure(hm)) => {
+                        self.machine_st.select_switch_on_structure_index(addr, hm)
+                    }
+                    _ => IndexingCodePtr::Internal(0),
+                };
+            }
+
+            match ptr {
+                IndexingCodePtr::Internal(o) if switches.contains_key(&(next + o)) => {
+                    line = next + o;
+                }
+                _ => {
+                    return (line, addr);
+                }
+            }
+        }
+    }
+
+    // the arguments bound by the current call, as a bit set.
+    fn bound_args(&self) -> u64 {
+        let arity = std::cmp::min(self.machine_st.num_of_args, u64::BITS as usize);
+        let mut bound_args = 0u64;
+
+        for arg in 1..=arity {
+            let addr = self
+                .machine_st
+                .store(self.machine_st.deref(self.machine_st.registers[arg]));
+
+            if !addr.is_var() {
+                bound_args |= 1 << (arg - 1);
+            }
+        }
+
+        bound_args
+    }
+
+    /// The first line of the index of the subsequence at `index_loc`
+    /// for calls binding `bound_args`, building it if need be, unless
+    /// none does better than switching on `switch_arg`.
+    fn jit_index_root(
+        &mut self,
+        index_loc: usize,
+        switch_arg: Option<usize>,
+        bound_args: u64,
+    ) -> Option<usize> {
+        let (indexable, root) = match self.indices.jit_indices.indices.get(&index_loc) {
+            Some(jit_index) => (
+                jit_index.indexable,
+                jit_index.roots.get(&bound_args).cloned(),
+            ),
+            None => {
+                let indexable = !self.is_clause_clause_index(index_loc);
+                let end = self.code[index_loc].to_indexing_line().unwrap().len();
+
+                let jit_index = JitIndex {
+                    indexable,
+                    roots: IndexMap::with_hasher(FxBuildHasher::default()),
+                    switches: IndexMap::with_hasher(FxBuildHasher::default()),
+                    lines: end..end,
+                    chain: None,
+                };
+
+                self.indices
+                    .jit_indices
+                    .indices
+                    .insert(index_loc, jit_index);
+                (indexable, None)
+            }
+        };
+
+        if !indexable {
+            return None;
+        }
+
+        match root {
+            Some(root) => root,
+            None => {
+                let root = self.build_jit_index(index_loc, switch_arg, bound_args);
+                let jit_index = &mut self.indices.jit_indices.indices[&index_loc];
+
+                match root {
+                    Ok(root) => {
+                        jit_index.roots.insert(bound_args, root);
+                        root
+                    }
+                    Err(()) => {
+                        jit_index.indexable = false;
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    /// Selects an index of the subsequence at the current `p` for the
+    /// arguments bound by the current call, building it if need be,
+    /// unless the subsequence's own index on `switch_arg`, bound to
+    /// `addr`, is as good. Returns the line to dispatch from and the
+    /// term it switches on.
+    pub(super) fn select_jit_index(
+        &mut self,
+        switch_arg: usize,
+        addr: HeapCellValue,
+    ) -> Option<(usize, HeapCellValue)> {
+        let bound_args = self.bound_args();
+
+        let is_compound = matches!(
+            addr.get_tag(),
+            HeapCellValueTag::Str | HeapCellValueTag::Lis
+        );
+
+        if bound_args == 0 || (bound_args == 1 << (switch_arg - 1) && !is_compound) {
+            return None;
+        }
+
+        let root = self.jit_index_root(self.machine_st.p, Some(switch_arg), bound_args)?;
+        Some(self.follow_jit_index(root, addr))
+    }
+
+    // whether `p` is where the code of a predicate starts.
+    fn is_predicate_entry(&self, p: usize) -> bool {
+        once(&self.indices.code_dir)
+            .chain(self.indices.modules.values().map(|module| &module.code_dir))
+            .flat_map(|code_dir| code_dir.values())
+            .any(|code_index| code_index.local() == Some(p))
+    }
+
+    // whether the choice instruction at `p` chains the clauses of a
+    // subsequence reached from its indexing code.
+    fn is_subsequence_chain(&self, p: usize) -> bool {
+        match p.checked_sub(1).map(|p| &self.code[p]) {
+            Some(Instruction::IndexingCode(_)) => true,
+            Some(Instruction::TryMeElse(0)) => {
+                matches!(self.code[p - 2], Instruction::IndexingCode(_))
+            }
+            _ => false,
+        }
+    }
+
+    /// The clauses of the chain of subsequences starting at `chain_loc`
+    /// in order, by the start of their code. `None` unless they're all
+    /// static and the chain is as compiled.
+    fn jit_chain_clauses(&self, chain_loc: usize) -> Option<Vec<usize>> {
+        // disjunctions are chained by the same instructions.
+        if self.is_subsequence_chain(chain_loc) || !self.is_predicate_entry(chain_loc) {
+            return None;
+        }
+
+        let mut clauses = vec![];
+        let mut p = chain_loc;
+
+        loop {
+            let (segment, next) = match &self.code[p] {
+                &Instruction::TryMeElse(o)
+                | &Instruction::RetryMeElse(o)
+                | &Instruction::DefaultRetryMeElse(o)
+                    if o > 0 =>
+                {
+                    (p + 1, Some(p + o))
+                }
+                Instruction::TrustMe(_) | Instruction::DefaultTrustMe(_) => (p + 1, None),
+                _ => return None,
+            };
+
+            match &self.code[segment] {
+                Instruction::IndexingCode(_) => match self.jit_index_clauses(segment)? {
+                    (segment_clauses, false) => clauses.extend(segment_clauses),
+                    (_, true) => return None,
+                },
+                Instruction::TryMeElse(_)
+                | Instruction::RetryMeElse(_)
+                | Instruction::DefaultRetryMeElse(_)
+                | Instruction::TrustMe(_)
+                | Instruction::DefaultTrustMe(_)
+                | Instruction::RevJmpBy(_) => {
+                    return None;
+                }
+                _ => clauses.push(segment),
+            }
+
+            match next {
+                Some(next) => p = next,
+                None => break,
+            }
+        }
+
+        Some(clauses)
+    }
+
+    // appends indexing code for the chain at `chain_loc` to the code
+    // area, returning its location.
+    fn add_jit_chain_index(&mut self, chain_loc: usize) -> Option<usize> {
+        let clauses = self.jit_chain_clauses(chain_loc)?;
+
+        if clauses.len() < JIT_INDEX_MIN_CLAUSES {
+            return None;
+        }
+
+        let index_loc = self.code.len();
+
+        // never dispatched from, since the chain has no index of its
+        // own.
+        self.code.push(Instruction::IndexingCode(vec![IndexingLine::from(
+            IndexingInstruction::SwitchOnTerm(
+                1,
+                IndexingCodePtr::Fail,
+                IndexingCodePtr::Fail,
+                IndexingCodePtr::Fail,
+                IndexingCodePtr::Fail,
+            ),
+        )]));
+
+        for (i, &clause) in clauses.iter().enumerate() {
+            self.code.push(Instruction::RevJmpBy(index_loc + 1 + i - clause));
+        }
+
+        let jit_index = JitIndex {
+            indexable: true,
+            roots: IndexMap::with_hasher(FxBuildHasher::default()),
+            switches: IndexMap::with_hasher(FxBuildHasher::default()),
+            lines: 1..1,
+            chain: Some(clauses),
+        };
+
+        self.indices
+            .jit_indices
+            .indices
+            .insert(index_loc, jit_index);
+
+        Some(index_loc)
+    }
+
+    /// Selects an index of the chain of subsequences at the current
+    /// `p` for the arguments bound by the current call, building it if
+    /// need be. If there is one, `p` is moved to its indexing code and
+    /// the line to dispatch from and the term it switches on are
+    /// returned.
+    pub(super) fn select_jit_chain_index(&mut self) -> Option<(usize, HeapCellValue)> {
+        let chain_loc = self.machine_st.p;
+
+        let index_loc = match self.indices.jit_indices.chains.get(&chain_loc) {
+            Some(&index_loc) => index_loc?,
+            None => {
+                if self.bound_args() == 0 {
+                    return None;
+                }
+
+                let index_loc = self.add_jit_chain_index(chain_loc);
+
+                self.indices
+                    .jit_indices
+                    .chains
+                    .insert(chain_loc, index_loc);
+
+                index_loc?
+            }
+        };
+
+        let bound_args = self.bound_args();
+
+        if bound_args == 0 {
+            return None;
+        }
+
+        let root = self.jit_index_root(index_loc, None, bound_args)?;
+
+        self.machine_st.p = index_loc;
+
+        let addr = self
+            .machine_st
+            .store(self.machine_st.deref(self.machine_st.registers[1]));
+
+        Some(self.follow_jit_index(root, addr))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::atom_table::AtomTable;
+    use crate::instructions::*;
+    use crate::machine::config::MachineConfig;
+    use crate::machine::loader::CompilationTarget;
+    use crate::machine::parsed_results::{LeafAnswer, Term};
+    use crate::machine::Machine;
+
+    use std::fmt::Write;
+
+    fn table(name: &str, rows: usize) -> String {
+        let mut program = String::new();
+
+        for n in 0..rows {
+            writeln!(program, "{name}({n}, k{}, f(k{})).", n % 50, n % 7).unwrap();
+        }
+
+        program
+    }
+
+    fn machine_with(program: &str) -> Machine {
+        let mut machine = Machine::new(MachineConfig::in_memory());
+        machine.consult_module_string("user", program.to_string());
+
+        let answer = machine.query("use_module(library(iso_ext)).").next();
+        assert!(matches!(answer, Some(Ok(_))));
+
+        machine
+    }
+
+    fn binding(machine: &mut Machine, query: &str, var: &str) -> Option<Term> {
+        match machine.query(query).next() {
+            Some(Ok(LeafAnswer::LeafAnswer { bindings, .. })) => bindings.get(var).cloned(),
+            _ => None,
+        }
+    }
+
+    fn succeeds(machine: &mut Machine, query: &str) -> bool {
+        matches!(machine.query(query).next(), Some(Ok(_)))
+    }
+
+    fn integers(ns: impl IntoIterator<Item = i64>) -> Term {
+        Term::list(ns.into_iter().map(Term::integer))
+    }
+
+    // the positions switched on just in time by the subsequence of the
+    // predicate `name/arity`, as arguments and paths, and the number of
+    // lines of the indexing code they were built for.
+    fn jit_index(
+        machine: &Machine,
+        name: &str,
+        arity: usize,
+    ) -> Option<(Vec<(usize, Vec<usize>)>, usize)> {
+        let key = (
+            AtomTable::build_with(&machine.machine_st.atom_tbl, name),
+            arity,
+        );
+        // only dynamic predicates keep their skeletons.
+        let index_loc = match machine
+            .indices
+            .get_predicate_skeleton(&CompilationTarget::User, &key)
+        {
+            Some(skeleton) => skeleton
+                .clauses
+                .iter()
+                .find_map(|clause| clause.opt_arg_index_key.switch_on_term_loc())?,
+            None => machine.indices.code_dir.get(&key)?.local()?,
+        };
+        let index_loc = match &machine.code[index_loc] {
+            Instruction::TryMeElse(_) => (*machine.indices.jit_indices.chains.get(&index_loc)?)?,
+            _ => index_loc,
+        };
+        let jit_index = machine.indices.jit_indices.indices.get(&index_loc)?;
+
+        let mut positions: Vec<(usize, Vec<usize>)> = jit_index
+            .switches
+            .values()
+            .map(|position| (position.arg, position.path.clone()))
+            .collect();
+
+        positions.sort();
+        positions.dedup();
+
+        match &machine.code[index_loc] {
+            Instruction::IndexingCode(indexing_lines) if !positions.is_empty() => {
+                Some((positions, indexing_lines.len()))
+            }
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn calls_on_other_arguments_are_indexed() {
+        let mut machine = machine_with(&table("t", 200));
+
+        assert_eq!(
+            binding(&mut machine, "t(N, k42, _).", "N"),
+            Some(Term::integer(42))
+        );
+        assert_eq!(
+            binding(&mut machine, "findall(N, t(N, k7, _), Ns).", "Ns"),
+            Some(integers([7, 57, 107, 157]))
+        );
+        assert_eq!(
+            binding(&mut machine, "findall(N, t(N, k8, f(k1)), Ns).", "Ns"),
+            Some(integers([8]))
+        );
+        assert_eq!(
+            binding(&mut machine, "findall(N, t(N, k50, _), Ns).", "Ns"),
+            Some(Term::atom("[]"))
+        );
+        assert_eq!(
+            binding(&mut machine, "findall(N, t(N, _, f(k3)), Ns).", "Ns"),
+            Some(integers((3..200).step_by(7)))
+        );
+
+        assert_eq!(
+            jit_index(&machine, "t", 3).unwrap().0,
+            [(2, vec![]), (3, vec![1])]
+        );
+    }
+
+    #[test]
+    fn small_predicates_and_variable_arguments_are_not_indexed() {
+        let mut program = table("t", 20);
+        program.push_str("t(20, _, f(k0)).\n");
+        program.push_str(&table("u", 5));
+
+        let mut machine = machine_with(&program);
+
+        assert_eq!(
+            binding(&mut machine, "findall(N, t(N, k3, _), Ns).", "Ns"),
+            Some(integers([3, 20]))
+        );
+        assert_eq!(
+            binding(&mut machine, "findall(N, t(N, _, f(k6)), Ns).", "Ns"),
+            Some(integers([6, 13]))
+        );
+        assert_eq!(
+            binding(&mut machine, "findall(N, u(N, k3, _), Ns).", "Ns"),
+            Some(integers([3]))
+        );
+
+        assert_eq!(jit_index(&machine, "t", 3).unwrap().0, [(3, vec![1])]);
+        assert!(jit_index(&machine, "u", 3).is_none());
+    }
+
+    #[test]
+    fn dynamic_indices_follow_assertz_and_retract() {
+        let mut program = String::from(":- dynamic(t/3).\n");
+        program.push_str(&table("t", 100));
+
+        let mut machine = machine_with(&program);
+
+        assert_eq!(
+            binding(&mut machine, "findall(N, t(N, k5, _), Ns).", "Ns"),
+            Some(integers([5, 55]))
+        );
+
+        assert!(succeeds(&mut machine, "assertz(t(100, k5, f(k0)))."));
+        assert!(jit_index(&machine, "t", 3).is_none());
+
+        assert_eq!(
+            binding(&mut machine, "findall(N, t(N, k5, _), Ns).", "Ns"),
+            Some(integers([5, 55, 100]))
+        );
+
+        assert!(succeeds(&mut machine, "retract(t(55, _, _))."));
+
+        assert_eq!(
+            binding(&mut machine, "findall(N, t(N, k5, _), Ns).", "Ns"),
+            Some(integers([5, 100]))
+        );
+
+        assert!(succeeds(&mut machine, "asserta(t(-1, k5, f(k0)))."));
+
+        assert_eq!(
+            binding(&mut machine, "findall(N, t(N, k5, _), Ns).", "Ns"),
+            Some(integers([-1, 5, 100]))
+        );
+    }
+
+    #[test]
+    fn rebuilt_indices_replace_their_predecessors() {
+        let mut program = String::from(":- dynamic(t/3).\n");
+        program.push_str(&table("t", 100));
+
+        let mut machine = machine_with(&program);
+
+        assert!(succeeds(&mut machine, "findall(N, t(N, k5, _), _)."));
+        let lines = jit_index(&machine, "t", 3).unwrap().1;
+
+        for n in 100..120 {
+            assert!(succeeds(
+                &mut machine,
+                &format!("assertz(t({n}, k5, f(k0))).")
+            ));
+            assert!(succeeds(&mut machine, "findall(N, t(N, k5, _), _)."));
+        }
+
+        assert_eq!(jit_index(&machine, "t", 3).unwrap().1, lines);
+    }
+
+    #[test]
+    fn dynamic_indices_keep_the_logical_update_view() {
+        let mut program = String::from(":- dynamic(t/3).\n");
+        program.push_str(&table("t", 100));
+
+        let mut machine = machine_with(&program);
+
+        assert!(succeeds(
+            &mut machine,
+            "forall(t(N, k9, _), (M is N + 100, assertz(t(M, k9, f(k0)))))."
+        ));
+        assert_eq!(
+            binding(&mut machine, "findall(N, t(N, k9, _), Ns).", "Ns"),
+            Some(integers([9, 59, 109, 159]))
+        );
+
+        assert!(succeeds(
+            &mut machine,
+            "forall(t(N, k9, _), retract(t(N, k9, _)))."
+        ));
+        assert_eq!(
+            binding(&mut machine, "findall(N, t(N, k9, _), Ns).", "Ns"),
+            Some(Term::atom("[]"))
+        );
+        assert_eq!(
+            binding(&mut machine, "findall(N, t(N, k8, _), Ns).", "Ns"),
+            Some(integers([8, 58]))
+        );
+    }
+
+    fn edges(rows: usize) -> String {
+        let mut program = String::new();
+
+        for n in 0..rows {
+            writeln!(program, "edge(node(a{}, {n}), {}).", n % 10, n + 1).unwrap();
+        }
+
+        program
+    }
+
+    fn triples(rows: usize) -> String {
+        let mut program = String::from(":- dynamic(rdf/3).\n");
+
+        for n in 0..rows {
+            writeln!(program, "rdf(s{}, p{}, {n}).", n % 5, n % 4).unwrap();
+        }
+
+        program
+    }
+
+    #[test]
+    fn subterms_of_bound_arguments_are_indexed() {
+        let mut machine = machine_with(&edges(100));
+
+        assert_eq!(
+            binding(&mut machine, "findall(X, edge(node(a3, 13), X), Xs).", "Xs"),
+            Some(integers([14]))
+        );
+        assert_eq!(
+            binding(&mut machine, "findall(N, edge(node(a3, N), _), Ns).", "Ns"),
+            Some(integers((3..100).step_by(10)))
+        );
+        assert_eq!(
+            binding(
+                &mut machine,
+                "findall(A-X, edge(node(A, 42), X), Ps).",
+                "Ps"
+            ),
+            Some(Term::list([Term::compound(
+                "-",
+                [Term::atom("a2"), Term::integer(43)]
+            )]))
+        );
+        assert_eq!(
+            binding(&mut machine, "findall(X, edge(node(a3, 14), X), Xs).", "Xs"),
+            Some(Term::atom("[]"))
+        );
+        assert_eq!(
+            binding(&mut machine, "findall(X, edge(node(a3), X), Xs).", "Xs"),
+            Some(Term::atom("[]"))
+        );
+        assert_eq!(
+            binding(&mut machine, "findall(X, edge(leaf(a3, 13), X), Xs).", "Xs"),
+            Some(Term::atom("[]"))
+        );
+        assert_eq!(
+            binding(&mut machine, "findall(X, edge(node(_, _), X), Xs).", "Xs"),
+            Some(integers(1..=100))
+        );
+
+        assert_eq!(
+            jit_index(&machine, "edge", 2).unwrap().0,
+            [(1, vec![1]), (1, vec![2])]
+        );
+    }
+
+    #[test]
+    fn keys_of_several_arguments_combine() {
+        let mut machine = machine_with(&triples(200));
+
+        assert_eq!(
+            binding(&mut machine, "findall(O, rdf(s1, p2, O), Os).", "Os"),
+            Some(integers((6..200).step_by(20)))
+        );
+        assert_eq!(
+            binding(&mut machine, "findall(S-P, rdf(S, P, 42), Ps).", "Ps"),
+            Some(Term::list([Term::compound(
+                "-",
+                [Term::atom("s2"), Term::atom("p2")]
+            )]))
+        );
+
+        assert_eq!(
+            jit_index(&machine, "rdf", 3).unwrap().0,
+            [(1, vec![]), (2, vec![]), (3, vec![])]
+        );
+
+        assert!(succeeds(&mut machine, "assertz(rdf(s1, p2, 200))."));
+        assert!(succeeds(&mut machine, "retract(rdf(s1, p2, 186))."));
+
+        assert_eq!(
+            binding(&mut machine, "findall(O, rdf(s1, p2, O), Os).", "Os"),
+            Some(integers((6..186).step_by(20).chain([200])))
+        );
+    }
+
+    // a table whose clauses are indexed on the first and the second
+    // argument in turn, and so is split into subsequences of one
+    // clause each, with no indexing code of its own.
+    fn alternating(rows: usize) -> String {
+        let mut program = String::new();
+
+        for n in 0..rows {
+            if n % 2 == 0 {
+                writeln!(program, "c({n}, k{n}).").unwrap();
+            } else {
+                writeln!(program, "c(_, k{n}).").unwrap();
+            }
+        }
+
+        program
+    }
+
+    #[test]
+    fn predicates_without_indexing_code_are_indexed() {
+        let mut machine = machine_with(&alternating(40));
+
+        assert_eq!(
+            binding(&mut machine, "c(N, k12).", "N"),
+            Some(Term::integer(12))
+        );
+        assert!(succeeds(
+            &mut machine,
+            "setup_call_cleanup(true, c(_, k13), Det = true), Det == true."
+        ));
+        assert_eq!(
+            binding(&mut machine, "findall(N, c(N, k40), Ns).", "Ns"),
+            Some(Term::atom("[]"))
+        );
+        assert_eq!(
+            binding(&mut machine, "findall(K, c(4, K), Ks).", "Ks"),
+            Some(Term::list(
+                (0..40)
+                    .filter(|n| n % 2 == 1 || *n == 4)
+                    .map(|n| Term::atom(format!("k{n}")))
+            ))
+        );
+
+        assert_eq!(jit_index(&machine, "c", 2).unwrap().0, [(2, vec![])]);
+    }
+}
@@ -1,19 +1,24 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::Arc;
 
-use crate::atom_table;
+use crate::atom_table::{self, AtomTable};
+use crate::forms::{ArenaFrom, Number};
 use crate::heap_print::{HCPrinter, HCValueOutputter, PrinterOutputter};
-use crate::machine::machine_indices::VarKey;
+use crate::machine::heap::{iter_to_heap_list, put_complete_string};
+use crate::machine::machine_errors::CompilationError;
+use crate::machine::machine_indices::{HeapVarDict, VarKey};
 use crate::machine::mock_wam::CompositeOpDir;
+use crate::machine::parsed_results::Term;
+use crate::machine::partial_string::HeapPStrIter;
 use crate::machine::{BREAK_FROM_DISPATCH_LOOP_LOC, LIB_QUERY_SUCCESS};
 use crate::parser::ast::{Var, VarPtr};
 use crate::parser::parser::{Parser, Tokens};
-use crate::read::write_term_to_heap;
+use crate::read::write_term_to_heap_with_bindings;
 use indexmap::IndexMap;
 
 use super::{
-    streams::Stream, Atom, AtomCell, HeapCellValue, HeapCellValueTag, Machine, MachineConfig,
-    QueryResolution, QueryResolutionLine, QueryResult, Value,
+    streams::Stream, Atom, AtomCell, HeapCellValue, HeapCellValueTag, LeafAnswer, Machine,
+    MachineConfig, PrologError, QueryResolution, QueryResolutionLine, QueryResult, Value,
 };
 
 impl Machine {
@@ -61,41 +66,119 @@ fn allocate_stub_choice_point(&mut self) {
         self.machine_st.block = stub_b;
     }
 
-    pub fn run_query(&mut self, query: String) -> QueryResult {
-        // println!("Query: {}", query);
-        // Parse the query so we can analyze and then call the term
+    /// Runs `query`, returning an iterator over its answers. Answers
+    /// are searched for as the iterator is advanced, and dropping it
+    /// discards those not yet found. A syntax error in `query` or an
+    /// uncaught exception is returned as the last item.
+    pub fn query(&mut self, query: impl Into<String>) -> QueryState<'_> {
+        self.query_with(query, std::iter::empty::<(String, Term)>())
+    }
+
+    /// Runs `query` like [`Machine::query`], with the variables of
+    /// `query` named in `params` bound to their values. The values are
+    /// written to the heap as they are rather than formatted into
+    /// `query`, and variables they hold are query variables alongside
+    /// those of `query`. Bound variables aren't reported in answers.
+    pub fn query_with<K: Into<String>>(
+        &mut self,
+        query: impl Into<String>,
+        params: impl IntoIterator<Item = (K, Term)>,
+    ) -> QueryState<'_> {
         let mut parser = Parser::new(
-            Stream::from_owned_string(query, &mut self.machine_st.arena),
+            Stream::from_owned_string(query.into(), &mut self.machine_st.arena),
             &mut self.machine_st,
         );
         let op_dir = CompositeOpDir::new(&self.indices.op_dir, None);
-        let term = parser
-            .read_term(&op_dir, Tokens::Default)
-            .expect("Failed to parse query");
 
-        self.allocate_stub_choice_point();
+        let term = match parser.read_term(&op_dir, Tokens::Default) {
+            Ok(term) => term,
+            Err(err) => return QueryState::from_error(self, CompilationError::from(err)),
+        };
 
-        // Write parsed term to heap
-        let term_write_result =
-            write_term_to_heap(&term, &mut self.machine_st.heap, &self.machine_st.atom_tbl)
-                .expect("couldn't write term to heap");
+        self.allocate_stub_choice_point();
 
-        let var_names: IndexMap<_, _> = term_write_result
-            .var_dict
-            .iter()
-            .map(|(var_key, cell)| match var_key {
-                // NOTE: not the intention behind Var::InSitu here but
-                // we can hijack it to store anonymous variables
-                // without creating problems.
-                VarKey::AnonVar(h) => (*cell, VarPtr::from(Var::InSitu(*h))),
-                VarKey::VarPtr(var_ptr) => (*cell, var_ptr.clone()),
+        let mut vars = IndexMap::new();
+        let bindings = params
+            .into_iter()
+            .map(|(name, value)| {
+                let cell = self.term_to_heap(&value, &mut vars);
+                (VarKey::VarPtr(VarPtr::from(Var::Named(name.into()))), cell)
             })
             .collect();
 
-        // Write term to heap
-        self.machine_st.registers[1] = self.machine_st.heap[term_write_result.heap_loc];
+        // Write parsed term to heap
+        let term_write_result = match write_term_to_heap_with_bindings(
+            &term,
+            &mut self.machine_st.heap,
+            &self.machine_st.atom_tbl,
+            bindings,
+            named_var_dict(vars),
+        ) {
+            Ok(term_write_result) => term_write_result,
+            Err(err) => {
+                self.trust_me();
+                return QueryState::from_error(self, err);
+            }
+        };
+
+        let goal = self.machine_st.heap[term_write_result.heap_loc];
+        self.call_query(goal, term_write_result.var_dict)
+    }
+
+    /// Runs `goal` like [`Machine::query`]. The variables of `goal`
+    /// are the query variables, and the host values it holds are
+    /// written to the heap as they are.
+    pub fn query_term(&mut self, goal: &Term) -> QueryState<'_> {
+        self.allocate_stub_choice_point();
+
+        let mut vars = IndexMap::new();
+        let goal = self.term_to_heap(goal, &mut vars);
+
+        self.call_query(goal, named_var_dict(vars))
+    }
+
+    // calls `goal`, which was written to the heap above a newly
+    // allocated stub choice point.
+    fn call_query(&mut self, goal: HeapCellValue, var_dict: HeapVarDict) -> QueryState<'_> {
+        let stub_b = self.machine_st.b;
 
-        self.machine_st.cp = LIB_QUERY_SUCCESS; // BREAK_FROM_DISPATCH_LOOP_LOC;
+        // NOTE: the goal is called as
+        // '$project_atts':'$call_query'(Goal, Vars, ResidualGoals)
+        // so that the residual goals of each solution are gathered
+        // alongside it.
+        let vars = heap_loc_as_cell!(iter_to_heap_list(
+            &mut self.machine_st.heap,
+            var_dict.values().copied()
+        ));
+
+        let residual_goals = heap_loc_as_cell!(self.machine_st.heap.len());
+        self.machine_st.heap.push(residual_goals);
+
+        let h = self.machine_st.heap.len();
+
+        self.machine_st
+            .heap
+            .push(atom_as_cell!(atom!("$call_query"), 3));
+        self.machine_st.heap.push(goal);
+        self.machine_st.heap.push(vars);
+        self.machine_st.heap.push(residual_goals);
+        self.machine_st.heap.push(atom_as_cell!(atom!(":"), 2));
+        self.machine_st
+            .heap
+            .push(atom_as_cell!(atom!("$project_atts")));
+        self.machine_st.heap.push(str_loc_as_cell!(h));
+
+        // NOTE: the query variables outlive each run of the dispatch
+        // loop, so the garbage collector must know to relocate them.
+        let gc_roots_base = self.machine_st.gc.roots.len();
+
+        self.machine_st.gc.roots.extend(var_dict.values().copied());
+
+        self.machine_st.gc.roots.push(residual_goals);
+
+        self.machine_st.registers[1] = str_loc_as_cell!(h + 4);
+
+        self.machine_st.cp = LIB_QUERY_SUCCESS;
         let call_index_p = self
             .indices
             .code_dir
@@ -105,128 +188,622 @@ pub fn run_query(&mut self, query: String) -> QueryResult {
             .unwrap();
 
         self.machine_st.execute_at_index(1, call_index_p);
+        self.start_limited_query();
+
+        QueryState {
+            machine: self,
+            var_dict,
+            residual_goals,
+            stub_b: Some(stub_b),
+            gc_roots_base,
+            called: false,
+            error: None,
+        }
+    }
 
-        let stub_b = self.machine_st.b;
-
+    pub fn run_query(&mut self, query: String) -> QueryResult {
         let mut matches: Vec<QueryResolutionLine> = Vec::new();
-        // Call the term
-        loop {
-            self.dispatch_loop();
-
-            //println!("b: {}", self.machine_st.b);
-            //println!("stub_b: {}", stub_b);
-            //println!("fail: {}", self.machine_st.fail);
-
-            if !self.machine_st.ball.stub.is_empty() {
-                // NOTE: this means an exception was thrown, at which
-                // point we backtracked to the stub choice point.
-                // this should halt the search for solutions as it
-                // does in the Scryer top-level. the exception term is
-                // contained in self.machine_st.ball.
-                let error_string = self
-                    .machine_st
-                    .ball
-                    .stub
-                    .iter()
-                    .filter(|h| {
-                        matches!(
-                            h.get_tag(),
-                            HeapCellValueTag::Atom | HeapCellValueTag::Fixnum
-                        )
-                    })
-                    .map(|h| match h.get_tag() {
-                        HeapCellValueTag::Atom => {
-                            let (name, _) = cell_as_atom_cell!(h).get_name_and_arity();
-                            name.as_str().to_string()
-                        }
-                        HeapCellValueTag::Fixnum => h.get_value().clone().to_string(),
-                        _ => unreachable!(),
-                    })
-                    .collect::<Vec<String>>()
-                    .join(" ");
-
-                return Err(error_string);
-            }
+        let mut query_state = self.query(query);
 
-            /*
-            if self.machine_st.fail {
-                // NOTE: only print results on success
-                self.machine_st.fail = false;
-                println!("fail!");
-                matches.push(QueryResolutionLine::False);
-                break;
-            };
-            */
-
-            if self.machine_st.p == LIB_QUERY_SUCCESS {
-                if term_write_result.var_dict.is_empty() {
+        while let Some(step) = query_state.advance() {
+            match step {
+                Ok(true) if query_state.var_dict.is_empty() => {
                     matches.push(QueryResolutionLine::True);
                     break;
                 }
-            } else if self.machine_st.p == BREAK_FROM_DISPATCH_LOOP_LOC {
-                // NOTE: only print results on success
-                // self.machine_st.fail = false;
-                // println!("b == stub_b");
-                matches.push(QueryResolutionLine::False);
-                break;
+                Ok(true) => {
+                    let bindings = query_state.machine.print_bindings(&query_state.var_dict);
+                    matches.push(QueryResolutionLine::Match(bindings));
+                }
+                Ok(false) => {
+                    matches.push(QueryResolutionLine::False);
+                }
+                Err(err) => {
+                    return Err(error_string(err));
+                }
+            }
+        }
+
+        Ok(QueryResolution::from(matches))
+    }
+
+    fn print_bindings(&mut self, var_dict: &HeapVarDict) -> BTreeMap<String, Value> {
+        let var_names: IndexMap<_, _> = var_dict
+            .iter()
+            .map(|(var_key, cell)| match var_key {
+                // NOTE: not the intention behind Var::InSitu here but
+                // we can hijack it to store anonymous variables
+                // without creating problems.
+                VarKey::AnonVar(h) => (*cell, VarPtr::from(Var::InSitu(*h))),
+                VarKey::VarPtr(var_ptr) => (*cell, var_ptr.clone()),
+            })
+            .collect();
+
+        let mut bindings: BTreeMap<String, Value> = BTreeMap::new();
+
+        for (var_key, term_to_be_printed) in var_dict {
+            if var_key.to_string().starts_with('_') {
+                continue;
+            }
+            let mut printer = HCPrinter::new(
+                &mut self.machine_st.heap,
+                Arc::clone(&self.machine_st.atom_tbl),
+                &mut self.machine_st.stack,
+                &self.indices.op_dir,
+                PrinterOutputter::new(),
+                *term_to_be_printed,
+            );
+
+            printer.ignore_ops = false;
+            printer.numbervars = true;
+            printer.quoted = true;
+            printer.max_depth = 1000; // NOTE: set this to 0 for unbounded depth
+            printer.double_quotes = true;
+            printer.var_names = var_names.clone();
+
+            let outputter = printer.print();
+
+            let output: String = outputter.result();
+
+            if var_key.to_string() != output {
+                bindings.insert(
+                    var_key.to_string(),
+                    Value::try_from(output).expect("Couldn't convert Houtput to Value"),
+                );
+            }
+        }
+
+        bindings
+    }
+
+    // copies the exception term out of the ball and resets it.
+    fn take_exception(&mut self) -> Term {
+        let h = self.machine_st.heap.len();
+        let stub = self.machine_st.ball.copy_and_align(h);
+
+        self.machine_st.heap.extend(stub);
+        self.machine_st.ball.reset();
+
+        self.term_from_heap(heap_loc_as_cell!(h), &mut HashMap::new())
+    }
+
+    // reads the term at `cell`. unbound variables are named by
+    // `var_names`, which maps heap addresses to variable names. other
+    // variables are named in the order they're read and added to it.
+    // the subterms cycles lead back to are read as variables, whose
+    // values are dropped (see `CycleNames`).
+    pub(super) fn term_from_heap(
+        &mut self,
+        cell: HeapCellValue,
+        var_names: &mut HashMap<usize, String>,
+    ) -> Term {
+        let mut cycles = CycleNames::default();
+        self.term_from_heap_with_cycles(cell, var_names, &mut cycles)
+    }
+
+    pub(super) fn term_from_heap_with_cycles(
+        &mut self,
+        cell: HeapCellValue,
+        var_names: &mut HashMap<usize, String>,
+        cycles: &mut CycleNames,
+    ) -> Term {
+        if cycles.path.is_none() && self.machine_st.is_cyclic_term(cell) {
+            cycles.path = Some(HashSet::new());
+            let term = self.read_heap_term(cell, var_names, cycles);
+            cycles.path = None;
+            term
+        } else {
+            self.read_heap_term(cell, var_names, cycles)
+        }
+    }
+
+    fn read_heap_term(
+        &mut self,
+        cell: HeapCellValue,
+        var_names: &mut HashMap<usize, String>,
+        cycles: &mut CycleNames,
+    ) -> Term {
+        let cell = self.machine_st.store(self.machine_st.deref(cell));
+
+        match Number::try_from(cell) {
+            Ok(Number::Fixnum(n)) => return Term::integer(n.get_num()),
+            Ok(Number::Integer(n)) => return Term::Integer((*n).clone()),
+            Ok(Number::Rational(r)) => return Term::Rational((*r).clone()),
+            Ok(Number::Float(f)) => return Term::Float(f),
+            Ok(Number::Decimal(d)) => return Term::Decimal((*d).clone()),
+            Err(_) => {}
+        }
+
+        read_heap_cell!(cell,
+            (HeapCellValueTag::Atom, (name, _arity)) => {
+                Term::atom(name.as_str().to_string())
+            }
+            (HeapCellValueTag::Char, c) => {
+                Term::atom(c.to_string())
+            }
+            (HeapCellValueTag::Str, s) => {
+                if let Some(var) = cycles.enter(s) {
+                    return var;
+                }
+
+                let (name, arity) = cell_as_atom_cell!(self.machine_st.heap[s])
+                    .get_name_and_arity();
+
+                let args: Vec<_> = (s + 1..=s + arity)
+                    .map(|h| self.read_heap_term(self.machine_st.heap[h], var_names, cycles))
+                    .collect();
+
+                let term = Term::compound(name.as_str().to_string(), args);
+                cycles.leave(s, || term.clone());
+                term
             }
+            (HeapCellValueTag::Lis, l) => {
+                if let Some(var) = cycles.enter(l) {
+                    return var;
+                }
+
+                let mut spine = vec![l];
+                let mut items = vec![self.read_heap_term(self.machine_st.heap[l], var_names, cycles)];
+                let mut tail = self.machine_st.store(self.machine_st.deref(self.machine_st.heap[l + 1]));
+
+                while tail.get_tag() == HeapCellValueTag::Lis {
+                    let l = tail.get_value() as usize;
+
+                    if cycles.enter_list_cell(l) {
+                        break;
+                    }
+
+                    spine.push(l);
+                    items.push(self.read_heap_term(self.machine_st.heap[l], var_names, cycles));
+                    tail = self.machine_st.store(self.machine_st.deref(self.machine_st.heap[l + 1]));
+                }
+
+                let tail = self.read_heap_term(tail, var_names, cycles);
 
-            let mut bindings: BTreeMap<String, Value> = BTreeMap::new();
+                for (i, &l) in spine.iter().enumerate().rev() {
+                    cycles.leave(l, || Term::partial_list(items[i..].to_vec(), tail.clone()));
+                }
 
-            for (var_key, term_to_be_printed) in &term_write_result.var_dict {
-                if var_key.to_string().starts_with('_') {
-                    continue;
+                Term::partial_list(items, tail)
+            }
+            (HeapCellValueTag::CStr, cstr_atom) => {
+                Term::string(cstr_atom.as_str().to_string())
+            }
+            (HeapCellValueTag::PStrLoc, pstr_loc) => {
+                if let Some(var) = cycles.enter(pstr_loc) {
+                    return var;
                 }
-                let mut printer = HCPrinter::new(
+
+                let h = self.machine_st.heap.len();
+                self.machine_st.heap.push(cell);
+
+                let mut iter = HeapPStrIter::new(&self.machine_st.heap, h);
+                let string = iter.to_string();
+                let at_terminator = iter.at_string_terminator();
+                let tail = iter.focus;
+
+                self.machine_st.heap.pop();
+
+                let term = if at_terminator {
+                    Term::String(string)
+                } else {
+                    // NOTE: a partial string is read as a partial
+                    // list of its characters.
+                    let chars: Vec<_> = string.chars().map(|c| Term::atom(c.to_string())).collect();
+                    let tail = self.read_heap_term(tail, var_names, cycles);
+
+                    Term::partial_list(chars, tail)
+                };
+
+                cycles.leave(pstr_loc, || term.clone());
+                term
+            }
+            (HeapCellValueTag::Var | HeapCellValueTag::AttrVar, h) => {
+                let n = var_names.len();
+                let name = var_names.entry(h).or_insert_with(|| format!("_G{}", n));
+                Term::variable(name.clone())
+            }
+            _ => {
+                Term::variable(format!("_{}", cell.get_value()))
+            }
+        )
+    }
+
+    // writes `term` to the heap. the variables named in `vars` share
+    // their cells, while each occurrence of `_` is a fresh variable.
+    pub(super) fn term_to_heap(
+        &mut self,
+        term: &Term,
+        vars: &mut IndexMap<String, HeapCellValue>,
+    ) -> HeapCellValue {
+        let arena = &mut self.machine_st.arena;
+
+        match term {
+            Term::Integer(n) => {
+                let n = match i64::try_from(n) {
+                    Ok(n) => Number::arena_from(n, arena),
+                    Err(_) => Number::arena_from(n.clone(), arena),
+                };
+
+                HeapCellValue::arena_from(n, arena)
+            }
+            Term::Rational(r) if r.is_int() => {
+                self.term_to_heap(&Term::Integer(r.numerator().clone()), vars)
+            }
+            Term::Rational(r) => {
+                HeapCellValue::arena_from(Number::arena_from(r.clone(), arena), arena)
+            }
+            Term::Float(f) => HeapCellValue::from(float_alloc!(f.into_inner(), arena)),
+            Term::Decimal(d) => {
+                HeapCellValue::arena_from(Number::arena_from(d.clone(), arena), arena)
+            }
+            Term::Atom(name) => {
+                atom_as_cell!(AtomTable::build_with(&self.machine_st.atom_tbl, name))
+            }
+            Term::String(s) => {
+                put_complete_string(&mut self.machine_st.heap, s, &self.machine_st.atom_tbl)
+            }
+            Term::List(items) => {
+                let items: Vec<_> = items
+                    .iter()
+                    .map(|item| self.term_to_heap(item, vars))
+                    .collect();
+
+                heap_loc_as_cell!(iter_to_heap_list(
                     &mut self.machine_st.heap,
-                    Arc::clone(&self.machine_st.atom_tbl),
-                    &mut self.machine_st.stack,
-                    &self.indices.op_dir,
-                    PrinterOutputter::new(),
-                    *term_to_be_printed,
-                );
+                    items.into_iter()
+                ))
+            }
+            Term::Compound(name, args) => {
+                let args: Vec<_> = args
+                    .iter()
+                    .map(|arg| self.term_to_heap(arg, vars))
+                    .collect();
 
-                printer.ignore_ops = false;
-                printer.numbervars = true;
-                printer.quoted = true;
-                printer.max_depth = 1000; // NOTE: set this to 0 for unbounded depth
-                printer.double_quotes = true;
-                printer.var_names = var_names.clone();
+                if args.is_empty() {
+                    return self.term_to_heap(&Term::Atom(name.clone()), vars);
+                }
 
-                let outputter = printer.print();
+                let h = self.machine_st.heap.len();
+                let name = AtomTable::build_with(&self.machine_st.atom_tbl, name);
 
-                let output: String = outputter.result();
-                // println!("Result: {} = {}", var_key.to_string(), output);
+                self.machine_st.heap.push(atom_as_cell!(name, args.len()));
+                self.machine_st.heap.extend(args);
 
-                if var_key.to_string() != output {
-                    bindings.insert(
-                        var_key.to_string(),
-                        Value::try_from(output).expect("Couldn't convert Houtput to Value"),
-                    );
+                str_loc_as_cell!(h)
+            }
+            Term::Var(name) => {
+                if let Some(cell) = vars.get(name) {
+                    return *cell;
                 }
+
+                let h = self.machine_st.heap.len();
+                self.machine_st.heap.push(heap_loc_as_cell!(h));
+
+                if name != "_" {
+                    vars.insert(name.clone(), heap_loc_as_cell!(h));
+                }
+
+                heap_loc_as_cell!(h)
             }
+        }
+    }
+}
+
+/// Names the compound subterms of a cyclic term that its cycles lead
+/// back to, so that the term can be read as a finite `Term`. A cycle is
+/// read as the variable naming the subterm it leads back to. The
+/// subterms bound to query variables are named after them, and the
+/// others `_S0`, `_S1`, ..., with their values kept in `bindings`.
+#[derive(Debug, Default)]
+pub(super) struct CycleNames {
+    // the addresses of the compound terms being read, if the term
+    // being read is cyclic.
+    path: Option<HashSet<usize>>,
+    query_names: HashMap<usize, String>,
+    names: HashMap<usize, String>,
+    pub(super) bindings: Vec<(String, Term)>,
+}
+
+impl CycleNames {
+    // names the compound term at `addr` after the query variable
+    // `name` it is bound to.
+    pub(super) fn name_after(&mut self, addr: usize, name: String) {
+        self.query_names.entry(addr).or_insert(name);
+    }
+
+    // starts reading the compound term at `addr`, returning the
+    // variable naming it if a cycle leads back to it.
+    fn enter(&mut self, addr: usize) -> Option<Term> {
+        let path = self.path.as_mut()?;
+
+        if path.insert(addr) {
+            return None;
+        }
+
+        if let Some(name) = self.query_names.get(&addr) {
+            return Some(Term::Var(name.clone()));
+        }
+
+        let n = self.names.len();
+        let name = self
+            .names
+            .entry(addr)
+            .or_insert_with(|| format!("_S{n}"));
+
+        Some(Term::Var(name.clone()))
+    }
 
-            matches.push(QueryResolutionLine::Match(bindings));
+    // starts reading the list cell at `addr` following another in a
+    // list, returning true if a cycle leads back to it instead. it is
+    // then read as the tail of the list.
+    fn enter_list_cell(&mut self, addr: usize) -> bool {
+        match self.path.as_mut() {
+            Some(path) => !path.insert(addr),
+            None => false,
+        }
+    }
+
+    // finishes reading the compound term at `addr`, keeping its value
+    // if a cycle led back to it.
+    fn leave(&mut self, addr: usize, term: impl FnOnce() -> Term) {
+        let Some(path) = self.path.as_mut() else {
+            return;
+        };
 
-            // NOTE: there are outstanding choicepoints, backtrack
-            // through them for further solutions. if
-            // self.machine_st.b == stub_b we've backtracked to the stub
-            // choice point, so we should break.
-            self.machine_st.backtrack();
+        path.remove(&addr);
 
-            if self.machine_st.b <= stub_b {
-                // NOTE: out of choicepoints to backtrack through, no
-                // more solutions to gather.
-                break;
+        if let Some(name) = self.names.get(&addr) {
+            if self.bindings.iter().all(|(bound, _)| bound != name) {
+                self.bindings.push((name.clone(), term()));
             }
         }
+    }
+}
+
+fn named_var_dict(vars: IndexMap<String, HeapCellValue>) -> HeapVarDict {
+    vars.into_iter()
+        .map(|(name, cell)| (VarKey::VarPtr(VarPtr::from(Var::Named(name))), cell))
+        .collect()
+}
 
-        // NOTE: deallocate stub choice point
-        if self.machine_st.b == stub_b {
-            self.trust_me();
+// the words of the atoms and integers of an exception, in the order
+// they're written.
+fn error_string(err: PrologError) -> String {
+    fn push_words(term: &Term, words: &mut Vec<String>) {
+        match term {
+            Term::Atom(atom) => words.push(atom.clone()),
+            Term::Integer(n) => words.push(n.to_string()),
+            Term::List(items) => {
+                items.iter().for_each(|item| push_words(item, words));
+                words.push("[]".to_string());
+            }
+            Term::Compound(name, args) => {
+                words.push(name.clone());
+                args.iter().for_each(|arg| push_words(arg, words));
+            }
+            _ => {}
         }
+    }
 
-        Ok(QueryResolution::from(matches))
+    let mut words = vec![];
+
+    match err {
+        PrologError::SyntaxError { kind, .. } => {
+            words.extend(["error", "syntax_error", &*kind.as_str()].map(String::from));
+        }
+        PrologError::Exception(exception) => {
+            push_words(&exception, &mut words);
+        }
+    }
+
+    words.join(" ")
+}
+
+/// The answers to a query, as returned by [`Machine::query`] and
+/// [`Machine::query_term`].
+pub struct QueryState<'a> {
+    machine: &'a mut Machine,
+    var_dict: HeapVarDict,
+    // the list of residual goals of the query variables, which is
+    // bound once a solution is found.
+    residual_goals: HeapCellValue,
+    // the choice point below those of the query, which is removed
+    // along with them once the query is done.
+    stub_b: Option<usize>,
+    gc_roots_base: usize,
+    called: bool,
+    error: Option<PrologError>,
+}
+
+impl<'a> QueryState<'a> {
+    fn from_error(machine: &'a mut Machine, err: CompilationError) -> Self {
+        let kind = match &err {
+            CompilationError::ParserError(err) => err.as_atom(),
+            _ => cell_as_atom!(err.as_functor()[0]),
+        };
+
+        QueryState {
+            machine,
+            var_dict: HeapVarDict::default(),
+            residual_goals: empty_list_as_cell!(),
+            stub_b: None,
+            gc_roots_base: 0,
+            called: false,
+            error: Some(PrologError::SyntaxError {
+                kind,
+                position: err.line_and_col_num(),
+            }),
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Some(stub_b) = self.stub_b.take() {
+            self.machine
+                .machine_st
+                .gc
+                .roots
+                .truncate(self.gc_roots_base);
+
+            // NOTE: cut the choice points of the query and deallocate
+            // the stub choice point.
+            self.machine.machine_st.b = stub_b;
+            self.machine.trust_me();
+        }
+    }
+
+    // searches for the next solution, returning `Ok(true)` once one
+    // is found and `Ok(false)` if there are none left.
+    fn advance(&mut self) -> Option<Result<bool, PrologError>> {
+        if let Some(err) = self.error.take() {
+            return Some(Err(err));
+        }
+
+        let stub_b = self.stub_b?;
+
+        if self.called {
+            // NOTE: if self.machine_st.b == stub_b, there are no
+            // choicepoints left to backtrack through for further
+            // solutions.
+            if self.machine.machine_st.b <= stub_b {
+                self.finish();
+                return None;
+            }
+
+            self.machine.machine_st.backtrack();
+        }
+
+        self.called = true;
+        self.machine.dispatch_loop();
+
+        let roots = &self.machine.machine_st.gc.roots[self.gc_roots_base..];
+
+        for (cell, root) in self.var_dict.values_mut().zip(roots) {
+            *cell = *root;
+        }
+
+        self.residual_goals = roots[roots.len() - 1];
+
+        if !self.machine.machine_st.ball.stub.is_empty() {
+            // NOTE: this means an exception was thrown, at which
+            // point we backtracked to the stub choice point.
+            let exception = self.machine.take_exception();
+            self.finish();
+            return Some(Err(PrologError::Exception(exception)));
+        }
+
+        if self.machine.machine_st.p != LIB_QUERY_SUCCESS {
+            self.finish();
+            return Some(Ok(false));
+        }
+
+        Some(Ok(true))
+    }
+
+    fn leaf_answer(&mut self) -> LeafAnswer {
+        // NOTE: unbound query variables are named after the first
+        // query variable they're bound to.
+        let mut var_names = HashMap::new();
+        // likewise, the subterms of cyclic terms bound to query
+        // variables are named after them.
+        let mut cycles = CycleNames::default();
+
+        for (var_key, cell) in &self.var_dict {
+            if let VarKey::VarPtr(_) = var_key {
+                let cell = self
+                    .machine
+                    .machine_st
+                    .store(self.machine.machine_st.deref(*cell));
+
+                if cell.is_var() {
+                    var_names
+                        .entry(cell.get_value() as usize)
+                        .or_insert_with(|| var_key.to_string());
+                } else if matches!(
+                    cell.get_tag(),
+                    HeapCellValueTag::Str | HeapCellValueTag::Lis | HeapCellValueTag::PStrLoc
+                ) && !var_key.to_string().starts_with('_')
+                {
+                    cycles.name_after(cell.get_value() as usize, var_key.to_string());
+                }
+            }
+        }
+
+        let mut bindings = BTreeMap::new();
+
+        for (var_key, cell) in &self.var_dict {
+            let name = var_key.to_string();
+
+            if name.starts_with('_') {
+                continue;
+            }
+
+            let term = self
+                .machine
+                .term_from_heap_with_cycles(*cell, &mut var_names, &mut cycles);
+
+            if term != Term::Var(name.clone()) {
+                bindings.insert(name, term);
+            }
+        }
+
+        let residual_goals = match self.machine.term_from_heap_with_cycles(
+            self.residual_goals,
+            &mut var_names,
+            &mut cycles,
+        ) {
+            Term::List(goals) => goals,
+            _ => vec![],
+        };
+
+        bindings.extend(cycles.bindings);
+
+        if bindings.is_empty() && residual_goals.is_empty() {
+            LeafAnswer::True
+        } else {
+            LeafAnswer::LeafAnswer {
+                bindings,
+                residual_goals,
+            }
+        }
+    }
+}
+
+impl Iterator for QueryState<'_> {
+    type Item = Result<LeafAnswer, PrologError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.advance()? {
+            Ok(true) => Some(Ok(self.leaf_answer())),
+            Ok(false) => Some(Ok(LeafAnswer::False)),
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+impl Drop for QueryState<'_> {
+    fn drop(&mut self) {
+        self.finish();
     }
 }
 
@@ -235,7 +812,8 @@ mod tests {
     use ordered_float::OrderedFloat;
 
     use super::*;
-    use crate::machine::{QueryMatch, QueryResolution, Value};
+    use crate::machine::parsed_results::{QueryMatch, Term};
+    use crate::machine::{LeafAnswer, PrologError, QueryResolution, Value};
 
     #[test]
     #[cfg_attr(miri, ignore = "blocked on streams.rs UB")]
@@ -630,4 +1208,408 @@ fn issue_2341() {
 
         assert_eq!(output, Ok(QueryResolution::False));
     }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "blocked on streams.rs UB")]
+    fn query_answers_are_lazy() {
+        let mut machine = Machine::new_lib();
+
+        machine.load_module_string(
+            "facts",
+            String::from(
+                r#"
+                nat(0).
+                nat(N) :- nat(N0), N is N0 + 1.
+        "#,
+            ),
+        );
+
+        let answers: Vec<_> = machine.query("nat(N).").take(3).collect();
+
+        assert_eq!(
+            answers,
+            [0, 1, 2].map(|n| Ok(LeafAnswer::LeafAnswer {
+                bindings: btreemap! {
+                    "N".to_string() => Term::integer(n),
+                },
+                residual_goals: vec![],
+            }))
+        );
+
+        // the dropped query leaves nothing behind.
+        let answers: Vec<_> = machine.query("X = a.").collect();
+
+        assert_eq!(
+            answers,
+            [Ok(LeafAnswer::LeafAnswer {
+                bindings: btreemap! {
+                    "X".to_string() => Term::atom("a"),
+                },
+                residual_goals: vec![],
+            })]
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "blocked on streams.rs UB")]
+    fn query_answers_end_like_the_toplevel() {
+        let mut machine = Machine::new_lib();
+
+        machine.load_module_string(
+            "facts",
+            String::from(
+                r#"
+                letter(a).
+                letter(b).
+        "#,
+            ),
+        );
+
+        let answers: Vec<_> = machine.query("letter(X), X \\== b.").collect();
+
+        assert_eq!(
+            answers,
+            [
+                Ok(LeafAnswer::LeafAnswer {
+                    bindings: btreemap! {
+                        "X".to_string() => Term::atom("a"),
+                    },
+                    residual_goals: vec![],
+                }),
+                Ok(LeafAnswer::False),
+            ]
+        );
+
+        let answers: Vec<_> = machine.query("true.").collect();
+        assert_eq!(answers, [Ok(LeafAnswer::True)]);
+
+        let answers: Vec<_> = machine.query("fail.").collect();
+        assert_eq!(answers, [Ok(LeafAnswer::False)]);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "blocked on streams.rs UB")]
+    fn cyclic_answers_are_read_as_finite_terms() {
+        let mut machine = Machine::new_lib();
+
+        let answers: Vec<_> = machine.query("X = [1|X].").collect();
+
+        assert_eq!(
+            answers,
+            [Ok(LeafAnswer::LeafAnswer {
+                bindings: btreemap! {
+                    "X".to_string() => Term::partial_list([Term::integer(1)], Term::variable("X")),
+                },
+                residual_goals: vec![],
+            })]
+        );
+
+        // a cyclic partial string is unfolded until its iterator
+        // finds the cycle.
+        let answers: Vec<_> = machine.query("X = [a|X].").collect();
+
+        assert_eq!(
+            answers,
+            [Ok(LeafAnswer::LeafAnswer {
+                bindings: btreemap! {
+                    "X".to_string() => Term::partial_list(
+                        [Term::atom("a"), Term::atom("a")],
+                        Term::variable("X"),
+                    ),
+                },
+                residual_goals: vec![],
+            })]
+        );
+
+        let answers: Vec<_> = machine.query("X = f(Y), Y = g(Y).").collect();
+
+        assert_eq!(
+            answers,
+            [Ok(LeafAnswer::LeafAnswer {
+                bindings: btreemap! {
+                    "X".to_string() => Term::compound("f", [Term::compound("g", [Term::variable("Y")])]),
+                    "Y".to_string() => Term::compound("g", [Term::variable("Y")]),
+                },
+                residual_goals: vec![],
+            })]
+        );
+
+        let answers: Vec<_> = machine.query("X = f(_Y), _Y = g(_Y).").collect();
+
+        assert_eq!(
+            answers,
+            [Ok(LeafAnswer::LeafAnswer {
+                bindings: btreemap! {
+                    "X".to_string() => Term::compound("f", [Term::compound("g", [Term::variable("_S0")])]),
+                    "_S0".to_string() => Term::compound("g", [Term::variable("_S0")]),
+                },
+                residual_goals: vec![],
+            })]
+        );
+
+        let answers: Vec<_> = machine.query("X = f(X), throw(X).").collect();
+
+        assert_eq!(
+            answers,
+            [Err(PrologError::Exception(Term::compound(
+                "f",
+                [Term::variable("_S0")]
+            )))]
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "blocked on streams.rs UB")]
+    fn query_errors_are_structured() {
+        let mut machine = Machine::new_lib();
+
+        let answers: Vec<_> = machine.query("foo(.").collect();
+
+        assert!(matches!(
+            answers[..],
+            [Err(PrologError::SyntaxError { .. })]
+        ));
+
+        let answers: Vec<_> = machine
+            .query("X = 1 ; throw(error(instantiation_error, [1, f(\"s\")])).")
+            .collect();
+
+        assert_eq!(
+            answers,
+            [
+                Ok(LeafAnswer::LeafAnswer {
+                    bindings: btreemap! {
+                        "X".to_string() => Term::integer(1),
+                    },
+                    residual_goals: vec![],
+                }),
+                Err(PrologError::Exception(Term::compound(
+                    "error",
+                    [
+                        Term::atom("instantiation_error"),
+                        Term::list([Term::integer(1), Term::compound("f", [Term::string("s")]),]),
+                    ]
+                ))),
+            ]
+        );
+
+        // the machine is left usable.
+        assert_eq!(
+            machine.run_query(String::from("true.")),
+            Ok(QueryResolution::True)
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "blocked on streams.rs UB")]
+    fn query_variables_survive_garbage_collection() {
+        let mut machine = Machine::new(MachineConfig::in_memory().with_gc_threshold(4096));
+
+        machine.load_module_string(
+            "facts",
+            String::from(
+                r#"
+                countdown(0, []) :- !.
+                countdown(N, [N|Ns]) :- N1 is N - 1, countdown(N1, Ns).
+
+                churn(0) :- !.
+                churn(N) :- countdown(50, _), N1 is N - 1, churn(N1).
+
+                choice(1).
+                choice(2).
+        "#,
+            ),
+        );
+
+        let query = String::from(r#"X = f(Y), churn(2000), Y = [1]."#);
+        let output = machine.run_query(query);
+
+        assert_eq!(
+            output,
+            Ok(QueryResolution::Matches(vec![QueryMatch::from(
+                btreemap! {
+                    "X" => Value::from("f([1])"),
+                    "Y" => Value::List(vec![Value::Float(OrderedFloat(1.0))]),
+                }
+            )]))
+        );
+
+        let query = String::from(r#"choice(X), churn(1000)."#);
+        let output = machine.run_query(query);
+
+        assert_eq!(
+            output,
+            Ok(QueryResolution::Matches(vec![
+                QueryMatch::from(btreemap! {
+                    "X" => Value::Float(OrderedFloat(1.0)),
+                }),
+                QueryMatch::from(btreemap! {
+                    "X" => Value::Float(OrderedFloat(2.0)),
+                }),
+            ]))
+        );
+
+        assert!(machine.gc_statistics().collections > 0);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "blocked on streams.rs UB")]
+    fn query_answers_are_terms() {
+        let mut machine = Machine::new_lib();
+
+        let answers: Vec<_> = machine
+            .query(r#"A = 18446744073709551616, B = 1.5, C = "ab", D = [a, 1], E = [a|T], F = f(G, G)."#)
+            .collect();
+
+        let big: dashu::Integer = "18446744073709551616".parse().unwrap();
+
+        assert_eq!(
+            answers,
+            [Ok(LeafAnswer::LeafAnswer {
+                bindings: btreemap! {
+                    "A".to_string() => Term::integer(big),
+                    "B".to_string() => Term::float(1.5),
+                    "C".to_string() => Term::string("ab"),
+                    "D".to_string() => Term::list([Term::atom("a"), Term::integer(1)]),
+                    "E".to_string() => Term::partial_list([Term::atom("a")], Term::variable("T")),
+                    "F".to_string() => Term::compound("f", [Term::variable("G"), Term::variable("G")]),
+                },
+                residual_goals: vec![],
+            })]
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "blocked on streams.rs UB")]
+    fn query_answers_hold_residual_goals() {
+        let mut machine = Machine::new_lib();
+
+        machine.load_module_string(
+            "facts",
+            String::from(
+                r#"
+                :- use_module(library(dif)).
+                not_a(X) :- dif(X, a).
+        "#,
+            ),
+        );
+
+        let answers: Vec<_> = machine.query("not_a(X).").collect();
+
+        assert_eq!(
+            answers,
+            [Ok(LeafAnswer::LeafAnswer {
+                bindings: btreemap! {},
+                residual_goals: vec![Term::compound(
+                    ":",
+                    [
+                        Term::atom("dif"),
+                        Term::compound("dif", [Term::variable("X"), Term::atom("a")]),
+                    ]
+                )],
+            })]
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "blocked on streams.rs UB")]
+    fn query_terms_are_written_to_the_heap() {
+        let mut machine = Machine::new_lib();
+
+        let big: dashu::Integer = "-18446744073709551616".parse().unwrap();
+        let value = Term::compound(
+            "f",
+            [
+                Term::integer(big),
+                Term::rational(dashu::Rational::from_parts(1.into(), 3u8.into())),
+                Term::float(0.25),
+                Term::string("héllo"),
+                Term::list([Term::integer(1), Term::atom("[]")]),
+                Term::variable("Y"),
+            ],
+        );
+
+        let answers: Vec<_> = machine
+            .query_term(&Term::compound("=", [Term::variable("X"), value.clone()]))
+            .collect();
+
+        assert_eq!(
+            answers,
+            [Ok(LeafAnswer::LeafAnswer {
+                bindings: btreemap! {
+                    "X".to_string() => value,
+                },
+                residual_goals: vec![],
+            })]
+        );
+
+        // each occurrence of `_` is distinct.
+        let goal = Term::compound(
+            "=",
+            [
+                Term::compound("g", [Term::variable("_"), Term::variable("_")]),
+                Term::compound("g", [Term::integer(1), Term::integer(2)]),
+            ],
+        );
+
+        let answers: Vec<_> = machine.query_term(&goal).collect();
+        assert_eq!(answers, [Ok(LeafAnswer::True)]);
+
+        let goal = Term::compound(
+            "=",
+            [
+                Term::compound("g", [Term::variable("Z"), Term::variable("Z")]),
+                Term::compound("g", [Term::integer(1), Term::integer(2)]),
+            ],
+        );
+
+        let answers: Vec<_> = machine.query_term(&goal).collect();
+        assert_eq!(answers, [Ok(LeafAnswer::False)]);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "blocked on streams.rs UB")]
+    fn query_parameters_are_bound() {
+        let mut machine = Machine::new_lib();
+
+        let answers: Vec<_> = machine
+            .query_with(
+                "atom_length(A, N), sort(L, [M|_]).",
+                [
+                    ("A", Term::atom("a'), halt, ('")),
+                    ("L", Term::list((0..10000).rev().map(Term::integer))),
+                ],
+            )
+            .collect();
+
+        assert_eq!(
+            answers,
+            [Ok(LeafAnswer::LeafAnswer {
+                bindings: btreemap! {
+                    "N".to_string() => Term::integer(13),
+                    "M".to_string() => Term::integer(0),
+                },
+                residual_goals: vec![],
+            })]
+        );
+
+        // variables of parameters are query variables.
+        let answers: Vec<_> = machine
+            .query_with(
+                "X = f(Y), Z = a.",
+                [("X", Term::compound("f", [Term::variable("Z")]))],
+            )
+            .collect();
+
+        assert_eq!(
+            answers,
+            [Ok(LeafAnswer::LeafAnswer {
+                bindings: btreemap! {
+                    "Y".to_string() => Term::atom("a"),
+                    "Z".to_string() => Term::atom("a"),
+                },
+                residual_goals: vec![],
+            })]
+        );
+    }
 }
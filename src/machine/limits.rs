@@ -0,0 +1,315 @@
+use crate::atom_table::*;
+use crate::machine::machine_errors::*;
+use crate::machine::Machine;
+use crate::parser::dashu::Integer;
+use crate::types::*;
+
+use std::time::{Duration, Instant};
+
+/// Hard limits on the resources a query may use, set through
+/// `MachineConfig::with_resource_limits`. A limit of `None` is not
+/// enforced.
+///
+/// Limits are checked each time the dispatch loop polls for
+/// interrupts, so a query may overrun one by the work of a few hundred
+/// instructions before it is stopped. A query exceeding a limit raises
+/// `error(resource_error(R), _)`, where `R` is the name of the
+/// [`ResourceLimit`]. Catching the error doesn't lift the limit: the
+/// inference and time limits are raised again at the next poll for as
+/// long as the query runs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// heap size, in cells. the heap is measured before it is
+    /// collected, so this is best set well above the `gc_threshold`.
+    pub heap_cells: Option<usize>,
+    /// size of the stack of environments and choice points, in cells.
+    pub stack_cells: Option<usize>,
+    /// number of trail entries.
+    pub trail_entries: Option<usize>,
+    /// number of atoms interned at runtime. the atom table is shared
+    /// by every machine of the process, so their atoms count alike.
+    pub atoms: Option<usize>,
+    /// number of inferences made since the start of the query.
+    pub inferences: Option<u64>,
+    /// wall-clock time since the start of the query.
+    pub time: Option<Duration>,
+}
+
+impl ResourceLimits {
+    pub fn with_heap_cells(mut self, heap_cells: usize) -> Self {
+        self.heap_cells = Some(heap_cells);
+        self
+    }
+
+    pub fn with_stack_cells(mut self, stack_cells: usize) -> Self {
+        self.stack_cells = Some(stack_cells);
+        self
+    }
+
+    pub fn with_trail_entries(mut self, trail_entries: usize) -> Self {
+        self.trail_entries = Some(trail_entries);
+        self
+    }
+
+    pub fn with_atoms(mut self, atoms: usize) -> Self {
+        self.atoms = Some(atoms);
+        self
+    }
+
+    pub fn with_inferences(mut self, inferences: u64) -> Self {
+        self.inferences = Some(inferences);
+        self
+    }
+
+    pub fn with_time(mut self, time: Duration) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == ResourceLimits::default()
+    }
+}
+
+/// A resource whose limit was exceeded, named by the argument of the
+/// `resource_error/1` term raised for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResourceLimit {
+    Heap,
+    Stack,
+    Trail,
+    Atoms,
+    Inferences,
+    Time,
+}
+
+impl ResourceLimit {
+    pub(crate) fn as_atom(self) -> Atom {
+        match self {
+            ResourceLimit::Heap => atom!("heap"),
+            ResourceLimit::Stack => atom!("stack"),
+            ResourceLimit::Trail => atom!("trail"),
+            ResourceLimit::Atoms => atom!("atoms"),
+            ResourceLimit::Inferences => atom!("inferences"),
+            ResourceLimit::Time => atom!("time"),
+        }
+    }
+
+    /// The limit named `name`, as it appears in `resource_error/1`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "heap" => Some(ResourceLimit::Heap),
+            "stack" => Some(ResourceLimit::Stack),
+            "trail" => Some(ResourceLimit::Trail),
+            "atoms" => Some(ResourceLimit::Atoms),
+            "inferences" => Some(ResourceLimit::Inferences),
+            "time" => Some(ResourceLimit::Time),
+            _ => None,
+        }
+    }
+}
+
+/// The limits of a machine and the start of the query they're
+/// counted from.
+#[derive(Debug, Default)]
+pub(crate) struct LimitsState {
+    pub(crate) limits: ResourceLimits,
+    // the inference count and time at the start of the running
+    // query. `None` until a query is started through the embedding
+    // API, so that the inference and time limits don't apply to
+    // consulting and the toplevel.
+    query_start: Option<(Integer, Option<Instant>)>,
+}
+
+impl LimitsState {
+    pub(crate) fn new(limits: ResourceLimits) -> Self {
+        LimitsState {
+            limits,
+            query_start: None,
+        }
+    }
+}
+
+impl Machine {
+    /// Restarts the count of inferences and time of the limits.
+    pub(crate) fn start_limited_query(&mut self) {
+        let limits = &mut self.machine_st.limits;
+
+        if limits.limits.is_empty() {
+            return;
+        }
+
+        let start_time = limits.limits.time.map(|_| Instant::now());
+        limits.query_start = Some((self.machine_st.cwil.global_count.clone(), start_time));
+    }
+
+    fn exceeded_limit(&self) -> Option<ResourceLimit> {
+        let machine_st = &self.machine_st;
+        let limits = &machine_st.limits.limits;
+
+        let exceeds = |limit: Option<usize>, size: usize| limit.is_some_and(|limit| size > limit);
+        let stack_cells = machine_st.stack.top() / std::mem::size_of::<HeapCellValue>();
+
+        if exceeds(limits.heap_cells, machine_st.heap.len()) {
+            return Some(ResourceLimit::Heap);
+        }
+
+        if exceeds(limits.stack_cells, stack_cells) {
+            return Some(ResourceLimit::Stack);
+        }
+
+        if exceeds(limits.trail_entries, machine_st.trail.len()) {
+            return Some(ResourceLimit::Trail);
+        }
+
+        if exceeds(limits.atoms, machine_st.atom_tbl.num_dynamic_atoms()) {
+            return Some(ResourceLimit::Atoms);
+        }
+
+        let (start_count, start_time) = machine_st.limits.query_start.as_ref()?;
+
+        if let Some(inferences) = limits.inferences {
+            if &machine_st.cwil.global_count - start_count > Integer::from(inferences) {
+                return Some(ResourceLimit::Inferences);
+            }
+        }
+
+        if let (Some(time), Some(start_time)) = (limits.time, start_time) {
+            if start_time.elapsed() > time {
+                return Some(ResourceLimit::Time);
+            }
+        }
+
+        None
+    }
+
+    /// Throws a resource error if a limit was exceeded, returning
+    /// whether one was.
+    pub(super) fn check_resource_limits(&mut self) -> bool {
+        if self.machine_st.limits.limits.is_empty() {
+            return false;
+        }
+
+        match self.exceeded_limit() {
+            Some(limit) => {
+                let err = self.machine_st.resource_error(ResourceError::Limit(limit));
+                let err = self
+                    .machine_st
+                    .error_form(err, functor_stub(atom!("call"), 1));
+
+                self.machine_st.throw_exception(err);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::machine::config::MachineConfig;
+    use crate::machine::limits::*;
+    use crate::machine::parsed_results::{PrologError, Term};
+
+    const PROGRAM: &str = "
+        count(0).
+        count(N) :- N > 0, M is N - 1, count(M).
+
+        deep(0).
+        deep(N) :- N > 0, M is N - 1, deep(M), true.
+
+        grow(L) :- grow([x|L]).
+    ";
+
+    fn limited_machine(limits: ResourceLimits) -> Machine {
+        let mut machine = Machine::new(MachineConfig::in_memory().with_resource_limits(limits));
+        machine.consult_module_string("user", PROGRAM.to_string());
+        machine
+    }
+
+    fn limit_error(machine: &mut Machine, query: &str) -> Option<ResourceLimit> {
+        match machine.query(query).next() {
+            Some(Err(err)) => err.resource_limit(),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn runaway_queries_are_stopped() {
+        let mut machine =
+            limited_machine(ResourceLimits::default().with_time(Duration::from_millis(200)));
+        assert_eq!(
+            limit_error(&mut machine, "repeat, fail."),
+            Some(ResourceLimit::Time)
+        );
+
+        let mut machine = limited_machine(ResourceLimits::default().with_inferences(10_000));
+        assert_eq!(
+            limit_error(&mut machine, "repeat, atom_length(abc, _), fail."),
+            Some(ResourceLimit::Inferences)
+        );
+
+        let mut machine = limited_machine(ResourceLimits::default().with_heap_cells(1 << 22));
+        assert_eq!(
+            limit_error(&mut machine, "grow([])."),
+            Some(ResourceLimit::Heap)
+        );
+
+        let mut machine = limited_machine(ResourceLimits::default().with_stack_cells(1 << 16));
+        assert_eq!(limit_error(&mut machine, "deep(10)."), None);
+        assert_eq!(
+            limit_error(&mut machine, "deep(1000000)."),
+            Some(ResourceLimit::Stack)
+        );
+    }
+
+    #[test]
+    fn limits_hold_against_catch() {
+        let mut machine =
+            limited_machine(ResourceLimits::default().with_time(Duration::from_millis(200)));
+
+        assert_eq!(
+            limit_error(&mut machine, "catch((repeat, fail), _, (repeat, fail))."),
+            Some(ResourceLimit::Time)
+        );
+    }
+
+    #[test]
+    fn limits_count_from_each_query() {
+        let mut machine = limited_machine(ResourceLimits::default().with_inferences(100_000));
+
+        for _ in 0..3 {
+            let answer = machine.query("count(10000).").next();
+            assert!(matches!(answer, Some(Ok(_))));
+        }
+
+        assert_eq!(
+            limit_error(&mut machine, "count(1000000)."),
+            Some(ResourceLimit::Inferences)
+        );
+
+        let answer = machine.query("X = ok.").next();
+        assert!(matches!(answer, Some(Ok(_))));
+    }
+
+    #[test]
+    fn resource_limits_are_read_from_errors() {
+        let err = PrologError::Exception(Term::compound(
+            "error",
+            [
+                Term::compound("resource_error", [Term::atom("time")]),
+                Term::atom("foo"),
+            ],
+        ));
+        assert_eq!(err.resource_limit(), Some(ResourceLimit::Time));
+
+        let err = PrologError::Exception(Term::compound(
+            "error",
+            [
+                Term::compound("resource_error", [Term::atom("memory")]),
+                Term::atom("foo"),
+            ],
+        ));
+        assert_eq!(err.resource_limit(), None);
+    }
+}
@@ -177,19 +177,20 @@ pub(crate) fn module_name(&self) -> Atom {
 }
 
 pub struct PredicateQueue {
-    pub(super) predicates: Vec<Term>,
+    // each clause and the line of the file it was read from, if any.
+    pub(super) predicates: Vec<(Term, Option<usize>)>,
     pub(super) compilation_target: CompilationTarget,
 }
 
 impl PredicateQueue {
     #[inline]
-    pub(super) fn push(&mut self, clause: Term) {
-        self.predicates.push(clause);
+    pub(super) fn push(&mut self, clause: Term, line: Option<usize>) {
+        self.predicates.push((clause, line));
     }
 
     #[inline]
     pub(crate) fn first(&self) -> Option<&Term> {
-        self.predicates.first()
+        self.predicates.first().map(|(clause, _)| clause)
     }
 
     #[inline]
@@ -215,7 +216,7 @@ pub(super) fn len(&self) -> usize {
 macro_rules! predicate_queue {
     [$($v:expr),*] => (
         PredicateQueue {
-            predicates: vec![$($v,)*],
+            predicates: vec![$(($v, None),)*],
             compilation_target: CompilationTarget::default(),
         }
     )
@@ -523,7 +524,13 @@ fn dequeue_terms(&mut self) -> Result<Option<Declaration>, SessionError> {
                 term => term,
             };
 
-            self.payload.predicates.push(term);
+            let line = self
+                .wam_prelude
+                .load_contexts
+                .last()
+                .and_then(|load_context| load_context.line);
+
+            self.payload.predicates.push(term, line);
         }
 
         Ok(None)
@@ -565,6 +572,12 @@ pub(super) fn load_decl(&mut self, decl: Declaration) -> Result<(), SessionError
     }
 
     fn reset_machine(&mut self) {
+        // the indexing code the indices were built for may be replaced
+        // by an earlier version.
+        if !self.payload.retraction_info.records.is_empty() {
+            self.wam_prelude.indices.jit_indices.clear();
+        }
+
         while let Some(record) = self.payload.retraction_info.records.pop() {
             match record {
                 RetractionRecord::AddedMetaPredicate(target_module_name, key) => {
@@ -1550,7 +1563,13 @@ pub(crate) fn declare_module(&mut self) -> CallResult {
         };
 
         let result = declare_module();
-        self.restore_load_state_payload(result)
+        self.restore_load_state_payload(result)?;
+
+        // the flags a module sets hold from its declaration on.
+        self.machine_st.prolog_flags.reset_module(module_name);
+        self.sync_module_flags();
+
+        Ok(())
     }
 
     #[inline]
@@ -1800,6 +1819,7 @@ pub(crate) fn pop_load_state_payload(&mut self) {
     #[inline]
     pub(crate) fn pop_load_context(&mut self) {
         self.load_contexts.pop();
+        self.sync_module_flags();
     }
 
     pub(crate) fn push_load_context(&mut self) -> CallResult {
@@ -1814,6 +1834,8 @@ pub(crate) fn push_load_context(&mut self) -> CallResult {
 
         self.load_contexts
             .push(LoadContext::new(&path.as_str(), stream));
+        self.sync_module_flags();
+
         Ok(())
     }
 
@@ -1869,7 +1891,10 @@ pub(crate) fn scoped_clause_to_evacuable(&mut self) -> CallResult {
         };
 
         let result = loader.read_and_enqueue_term(temp_v!(2), compilation_target);
-        self.restore_load_state_payload(result)
+        self.restore_load_state_payload(result)?;
+
+        self.record_qlf_clause(temp_v!(2), module_name, Some(module_name));
+        Ok(())
     }
 
     pub(crate) fn clause_to_evacuable(&mut self) -> CallResult {
@@ -1877,7 +1902,10 @@ pub(crate) fn clause_to_evacuable(&mut self) -> CallResult {
         let compilation_target = loader.payload.compilation_target;
 
         let result = loader.read_and_enqueue_term(temp_v!(1), compilation_target);
-        self.restore_load_state_payload(result)
+        self.restore_load_state_payload(result)?;
+
+        self.record_qlf_clause(temp_v!(1), compilation_target.module_name(), None);
+        Ok(())
     }
 
     pub(crate) fn conclude_load(&mut self) -> CallResult {
@@ -1964,6 +1992,14 @@ pub(crate) fn load_context_stream(&mut self) {
         }
     }
 
+    pub(crate) fn set_load_context_line(&mut self) {
+        let line = cell_as_fixnum!(self.deref_register(1)).get_num() as usize;
+
+        if let Some(load_context) = self.load_contexts.last_mut() {
+            load_context.line = Some(line);
+        }
+    }
+
     pub(crate) fn compile_assert(&mut self, append_or_prepend: AppendOrPrepend) -> CallResult {
         let module_name = cell_as_atom!(self.deref_register(1));
 
@@ -2376,6 +2412,8 @@ pub(crate) fn meta_predicate_property(&mut self) {
     }
 
     pub(crate) fn dynamic_property(&mut self) {
+        self.sync_database();
+
         let module_name = cell_as_atom!(self
             .machine_st
             .store(self.machine_st.deref(self.machine_st.registers[1])));
@@ -6,6 +6,7 @@
 use crate::ffi::FFIError;
 use crate::forms::*;
 use crate::machine::heap::*;
+use crate::machine::limits::ResourceLimit;
 use crate::machine::loader::CompilationTarget;
 use crate::machine::machine_state::*;
 use crate::machine::streams::*;
@@ -81,6 +82,8 @@ pub(crate) fn as_atom(self) -> Atom {
 pub(crate) enum ResourceError {
     FiniteMemory(HeapCellValue),
     OutOfFiles,
+    Limit(ResourceLimit),
+    Threads,
 }
 
 pub(crate) trait TypeError {
@@ -314,6 +317,17 @@ pub(super) fn evaluation_error(&mut self, eval_error: EvalError) -> MachineError
         }
     }
 
+    pub(super) fn system_error(&mut self, err: &std::io::Error) -> MachineError {
+        let message = AtomTable::build_with(&self.atom_tbl, &err.to_string());
+        let stub = functor!(atom!("system_error"), [atom(message)]);
+
+        MachineError {
+            stub,
+            location: None,
+            from: ErrorProvenance::Received,
+        }
+    }
+
     pub(super) fn resource_error(&mut self, err: ResourceError) -> MachineError {
         let stub = match err {
             ResourceError::FiniteMemory(size_requested) => {
@@ -325,6 +339,12 @@ pub(super) fn resource_error(&mut self, err: ResourceError) -> MachineError {
             ResourceError::OutOfFiles => {
                 functor!(atom!("resource_error"), [atom(atom!("file_descriptors"))])
             }
+            ResourceError::Limit(limit) => {
+                functor!(atom!("resource_error"), [atom(limit.as_atom())])
+            }
+            ResourceError::Threads => {
+                functor!(atom!("resource_error"), [atom(atom!("threads"))])
+            }
         };
 
         MachineError {
@@ -426,6 +446,54 @@ pub(super) fn existence_error(&mut self, err: ExistenceError) -> MachineError {
                     [atom(atom!("stream")), cell(culprit)]
                 );
 
+                MachineError {
+                    stub,
+                    location: None,
+                    from: ErrorProvenance::Received,
+                }
+            }
+            ExistenceError::Thread(culprit) => {
+                let stub = functor!(
+                    atom!("existence_error"),
+                    [atom(atom!("thread")), cell(culprit)]
+                );
+
+                MachineError {
+                    stub,
+                    location: None,
+                    from: ErrorProvenance::Received,
+                }
+            }
+            ExistenceError::Mutex(culprit) => {
+                let stub = functor!(
+                    atom!("existence_error"),
+                    [atom(atom!("mutex")), cell(culprit)]
+                );
+
+                MachineError {
+                    stub,
+                    location: None,
+                    from: ErrorProvenance::Received,
+                }
+            }
+            ExistenceError::Process(culprit) => {
+                let stub = functor!(
+                    atom!("existence_error"),
+                    [atom(atom!("process")), cell(culprit)]
+                );
+
+                MachineError {
+                    stub,
+                    location: None,
+                    from: ErrorProvenance::Received,
+                }
+            }
+            ExistenceError::Directory(culprit) => {
+                let stub = functor!(
+                    atom!("existence_error"),
+                    [atom(atom!("directory")), cell(culprit)]
+                );
+
                 MachineError {
                     stub,
                     location: None,
@@ -499,6 +567,9 @@ pub(super) fn session_error(&mut self, err: SessionError) -> MachineError {
                 self.permission_error(Permission::Modify, atom!("static_module"), module)
             }
             SessionError::ExistenceError(err) => self.existence_error(err),
+            SessionError::InvalidQlfFile(path) => {
+                self.domain_error(DomainErrorType::QlfFile, atom_as_cell!(path))
+            }
             SessionError::ModuleDoesNotContainExport(module_name, key) => {
                 let functor_stub = functor_stub(key.0, key.1);
 
@@ -661,7 +732,20 @@ pub(super) fn throw_exception(&mut self, err: MachineStub) {
             str_loc_as_cell!(h)
         };
 
+        let call_site = if err_len > 1 {
+            self.add_call_location(h)
+        } else {
+            None
+        };
+
         self.set_ball();
+
+        // without the backtrace flag, the failing call is the only
+        // frame of the backtrace.
+        if self.ball.backtrace.is_empty() {
+            self.ball.backtrace.extend(call_site);
+        }
+
         self.unwind_stack();
     }
 }
@@ -778,12 +862,16 @@ pub(crate) fn as_functor(&self) -> MachineStub {
 #[derive(Debug, Clone, Copy)]
 pub(crate) enum Permission {
     Access,
+    Call,
     Create,
     InputStream,
+    Join,
     Modify,
     Open,
     OutputStream,
     Reposition,
+    Send,
+    Unlock,
 }
 
 impl Permission {
@@ -791,21 +879,29 @@ impl Permission {
     pub(crate) fn as_atom(self) -> Atom {
         match self {
             Permission::Access => atom!("access"),
+            Permission::Call => atom!("call"),
             Permission::Create => atom!("create"),
             Permission::InputStream => atom!("input"),
+            Permission::Join => atom!("join"),
             Permission::Modify => atom!("modify"),
             Permission::Open => atom!("open"),
             Permission::OutputStream => atom!("output"),
             Permission::Reposition => atom!("reposition"),
+            Permission::Send => atom!("send"),
+            Permission::Unlock => atom!("unlock"),
         }
     }
 }
 
 #[derive(Debug, Clone, Copy)]
 pub(crate) enum DomainErrorType {
+    FlagValue,
     IOMode,
+    NotLessThanOne,
     NotLessThanZero,
     Order,
+    PrologFlag,
+    QlfFile,
     SourceSink,
     Stream,
     StreamOrAlias,
@@ -814,9 +910,13 @@ pub(crate) enum DomainErrorType {
 impl DomainErrorType {
     pub(crate) fn as_atom(self) -> Atom {
         match self {
+            DomainErrorType::FlagValue => atom!("flag_value"),
             DomainErrorType::IOMode => atom!("io_mode"),
+            DomainErrorType::NotLessThanOne => atom!("not_less_than_one"),
             DomainErrorType::NotLessThanZero => atom!("not_less_than_zero"),
             DomainErrorType::Order => atom!("order"),
+            DomainErrorType::PrologFlag => atom!("prolog_flag"),
+            DomainErrorType::QlfFile => atom!("qlf_file"),
             DomainErrorType::SourceSink => atom!("source_sink"),
             DomainErrorType::Stream => atom!("stream"),
             DomainErrorType::StreamOrAlias => atom!("stream_or_alias"),
@@ -1005,6 +1105,10 @@ pub enum ExistenceError {
     },
     SourceSink(HeapCellValue),
     Stream(HeapCellValue),
+    Thread(HeapCellValue),
+    Mutex(HeapCellValue),
+    Process(HeapCellValue),
+    Directory(HeapCellValue),
 }
 
 #[derive(Debug)]
@@ -1014,6 +1118,7 @@ pub enum SessionError {
     CannotOverwriteBuiltInModule(Atom),
     CannotOverwriteStaticProcedure(PredicateKey),
     ExistenceError(ExistenceError),
+    InvalidQlfFile(Atom),
     ModuleDoesNotContainExport(Atom, PredicateKey),
     ModuleCannotImportSelf(Atom),
     NamelessEntry,
@@ -3,9 +3,11 @@
 use crate::arena::*;
 use crate::atom_table::*;
 use crate::forms::*;
+use crate::machine::jit_index::JitIndices;
 use crate::machine::loader::*;
 use crate::machine::machine_state::*;
 use crate::machine::streams::Stream;
+use crate::machine::tabling::Tables;
 use crate::machine::ClauseType;
 
 use fxhash::FxBuildHasher;
@@ -16,6 +18,7 @@
 use std::cmp::Ordering;
 use std::collections::BTreeSet;
 use std::ops::{Deref, DerefMut};
+use std::process::Child;
 
 use crate::types::*;
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -26,6 +29,7 @@
 pub enum TermOrderCategory {
     Variable,
     FloatingPoint,
+    Decimal,
     Integer,
     Atom,
     Compound,
@@ -247,6 +251,10 @@ pub(crate) fn is_anon(&self) -> bool {
 pub(crate) type StreamAliasDir = IndexMap<Atom, Stream, FxBuildHasher>;
 pub(crate) type StreamDir = BTreeSet<Stream>;
 
+/// The processes started by `process_create/3` that haven't been
+/// waited for, by process id.
+pub(crate) type ProcessDir = IndexMap<u32, Child, FxBuildHasher>;
+
 pub(crate) type MetaPredicateDir = IndexMap<PredicateKey, Vec<MetaSpec>, FxBuildHasher>;
 
 pub(crate) type ExtensiblePredicates = IndexMap<PredicateKey, PredicateSkeleton, FxBuildHasher>;
@@ -270,6 +278,9 @@ pub struct IndexStore {
     pub(super) op_dir: OpDir,
     pub(super) streams: StreamDir,
     pub(super) stream_aliases: StreamAliasDir,
+    pub(super) processes: ProcessDir,
+    pub(super) jit_indices: JitIndices,
+    pub(super) tables: Tables,
 }
 
 impl IndexStore {
@@ -4,20 +4,26 @@
 use crate::heap_iter::*;
 use crate::heap_print::*;
 use crate::machine::attributed_variables::*;
+use crate::machine::clause_locations::ClauseLocations;
 use crate::machine::copier::*;
+use crate::machine::flags::PrologFlags;
+use crate::machine::gc::GCState;
 use crate::machine::heap::*;
+use crate::machine::limits::LimitsState;
 use crate::machine::machine_errors::*;
 use crate::machine::machine_indices::*;
+use crate::machine::sandbox::SandboxPolicy;
 use crate::machine::stack::*;
 use crate::machine::streams::*;
 use crate::machine::Machine;
 use crate::parser::ast::*;
-use crate::read::TermWriteResult;
+use crate::read::{write_term_to_heap, TermWriteResult};
 use crate::types::*;
 
 use crate::parser::dashu::Integer;
 
 use indexmap::IndexMap;
+use rand::rngs::StdRng;
 
 use std::convert::TryFrom;
 use std::fmt;
@@ -90,12 +96,18 @@ pub struct MachineState {
     pub(super) cont_pts: Vec<(HeapCellValue, usize, usize)>,
     pub(super) cwil: CWIL,
     pub(crate) flags: MachineFlags,
+    pub(crate) prolog_flags: PrologFlags,
     pub(crate) cc: usize,
     pub(crate) global_clock: usize,
     pub(crate) dynamic_mode: FirstOrNext,
     pub(crate) unify_fn: fn(&mut MachineState),
     pub(crate) bind_fn: fn(&mut MachineState, Ref, HeapCellValue),
     pub(crate) run_cleaners_fn: fn(&mut Machine) -> bool,
+    pub(crate) gc: GCState,
+    pub(crate) limits: LimitsState,
+    pub(crate) sandbox: SandboxPolicy,
+    pub(crate) clause_locations: ClauseLocations,
+    pub(crate) rng: StdRng,
 }
 
 impl fmt::Debug for MachineState {
@@ -126,9 +138,14 @@ fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
             .field("lifted_heap", &self.lifted_heap)
             .field("interms", &self.interms)
             .field("flags", &self.flags)
+            .field("prolog_flags", &self.prolog_flags)
             .field("cc", &self.cc)
             .field("global_clock", &self.global_clock)
             .field("dynamic_mode", &self.dynamic_mode)
+            .field("gc", &self.gc)
+            .field("limits", &self.limits)
+            .field("sandbox", &self.sandbox)
+            .field("rng", &self.rng)
             .field(
                 "unify_fn",
                 if self.unify_fn as usize == MachineState::unify as usize {
@@ -225,6 +242,11 @@ fn push_var_eq_functors<'a>(
 pub struct Ball {
     pub(super) boundary: usize,
     pub(super) stub: Heap,
+    /// The code of the calls the error in the ball was thrown from,
+    /// innermost first, if the `backtrace` flag was set. Otherwise,
+    /// the code of the failing call if the error was raised by the
+    /// machine from a clause compiled from a file.
+    pub(super) backtrace: Vec<usize>,
 }
 
 impl Ball {
@@ -232,12 +254,14 @@ pub(super) fn new() -> Self {
         Ball {
             boundary: 0,
             stub: Heap::new(),
+            backtrace: vec![],
         }
     }
 
     pub(super) fn reset(&mut self) {
         self.boundary = 0;
         self.stub.clear();
+        self.backtrace.clear();
     }
 
     pub(super) fn copy_and_align(&self, h: usize) -> Heap {
@@ -735,9 +759,36 @@ pub fn read_term(
             return Ok(());
         }
 
+        // the positions(SubtermPositions, TermPosition, Comments) of
+        // the term are kept if they are asked for.
+        let keep_positions = self.store(self.deref(self.registers[6])).get_tag() == HeapCellValueTag::Str;
+
         loop {
-            match self.read(stream, &indices.op_dir) {
-                Ok(term_write_result) => return self.read_term_body(term_write_result),
+            match self.read_with_positions(stream, &indices.op_dir, keep_positions) {
+                Ok((term_write_result, positions)) => {
+                    self.read_term_body(term_write_result)?;
+
+                    if let Some(positions) = positions {
+                        if !self.fail {
+                            let term = positions.to_term(&self.atom_tbl);
+                            let term_write_result =
+                                write_term_to_heap(&term, &mut self.heap, &self.atom_tbl)
+                                    .map_err(|err| {
+                                        let stub = functor_stub(atom!("read_term"), 3);
+                                        let err = self.syntax_error(err);
+                                        self.error_form(err, stub)
+                                    })?;
+
+                            unify!(
+                                self,
+                                heap_loc_as_cell!(term_write_result.heap_loc),
+                                self.registers[6]
+                            );
+                        }
+                    }
+
+                    return Ok(());
+                }
                 Err(err) => {
                     match &err {
                         CompilationError::ParserError(e) if e.is_unexpected_eof() => {
@@ -3,19 +3,27 @@
 use crate::forms::*;
 use crate::heap_iter::*;
 use crate::machine::attributed_variables::*;
+use crate::machine::clause_locations::ClauseLocations;
 use crate::machine::copier::*;
+use crate::machine::flags::PrologFlags;
+use crate::machine::gc::GCState;
 use crate::machine::heap::*;
+use crate::machine::limits::LimitsState;
 use crate::machine::machine_errors::*;
 use crate::machine::machine_indices::*;
 use crate::machine::machine_state::*;
 use crate::machine::partial_string::*;
+use crate::machine::sandbox::SandboxPolicy;
 use crate::machine::stack::*;
 use crate::machine::unify::*;
 use crate::parser::ast::*;
 use crate::parser::dashu::{Integer, Rational};
+use crate::parser::decimal::Decimal;
 use crate::types::*;
 
 use indexmap::IndexSet;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 
 use std::cmp::Ordering;
 use std::convert::TryFrom;
@@ -54,12 +62,18 @@ pub(crate) fn new() -> Self {
             cont_pts: Vec::with_capacity(256),
             cwil: CWIL::new(),
             flags: MachineFlags::default(),
+            prolog_flags: PrologFlags::default(),
             cc: 0,
             global_clock: 0,
             dynamic_mode: FirstOrNext::First,
             unify_fn: MachineState::unify,
             bind_fn: MachineState::bind,
             run_cleaners_fn: |_| false,
+            gc: GCState::new(0),
+            limits: LimitsState::default(),
+            sandbox: SandboxPolicy::default(),
+            clause_locations: ClauseLocations::default(),
+            rng: StdRng::from_entropy(),
         }
     }
 
@@ -301,6 +315,11 @@ pub fn unify_rational(&mut self, n1: TypedArenaPtr<Rational>, value: HeapCellVal
         unifier.unify_big_rational(n1, value);
     }
 
+    pub fn unify_decimal(&mut self, d1: TypedArenaPtr<Decimal>, value: HeapCellValue) {
+        let mut unifier = DefaultUnifier::from(self);
+        unifier.unify_big_decimal(d1, value);
+    }
+
     pub fn unify_f64(&mut self, f1: F64Ptr, value: HeapCellValue) {
         let mut unifier = DefaultUnifier::from(self);
         unifier.unify_f64(f1, value);
@@ -343,6 +362,21 @@ pub(super) fn set_ball(&mut self) {
             addr,
             AttrVarPolicy::DeepCopy,
         );
+
+        if self.flags.backtrace {
+            let is_error = read_heap_cell!(self.store(self.deref(addr)),
+                (HeapCellValueTag::Str, s) => {
+                    self.heap[s] == atom_as_cell!(atom!("error"), 2)
+                }
+                _ => {
+                    false
+                }
+            );
+
+            if is_error {
+                self.ball.backtrace = self.backtrace_sites();
+            }
+        }
     }
 
     #[inline(always)]
@@ -478,6 +512,17 @@ pub fn compare_term_test(&mut self, var_comparison: VarComparison) -> Option<Ord
                         return Some(v1.cmp(&v2));
                     }
                 }
+                Some(TermOrderCategory::Decimal) => {
+                    let ordering = match (Number::try_from(v1), Number::try_from(v2)) {
+                        (Ok(Number::Decimal(d1)), Ok(Number::Decimal(d2))) => d1.cmp_as_terms(&d2),
+                        _ => unreachable!(),
+                    };
+
+                    if ordering != Ordering::Equal {
+                        self.pdl.clear();
+                        return Some(ordering);
+                    }
+                }
                 Some(TermOrderCategory::Integer) => {
                     let v1 = Number::try_from(v1).unwrap();
                     let v2 = Number::try_from(v2).unwrap();
@@ -1040,6 +1085,9 @@ pub(super) fn write_literal_to_var(&mut self, deref_v: HeapCellValue, lit: HeapC
                      (ArenaHeaderTag::Rational, r) => {
                          self.unify_rational(r, store_v);
                      }
+                     (ArenaHeaderTag::Decimal, d) => {
+                         self.unify_decimal(d, store_v);
+                     }
                      _ => {
                          self.fail = true;
                      }
@@ -1389,6 +1437,9 @@ pub fn try_functor(&mut self) -> CallResult {
                     Ok(Number::Rational(n)) if !n.denominator().is_one() => {
                         return type_error(arity);
                     }
+                    Ok(Number::Decimal(_)) => {
+                        return type_error(arity);
+                    }
                     Ok(n) if n > MAX_ARITY => {
                         // 8.5.1.3 f)
                         let err = self.representation_error(RepFlag::MaxArity);
@@ -1,6 +1,9 @@
 pub mod args;
 pub mod arithmetic_ops;
+pub mod atom_gc;
 pub mod attributed_variables;
+pub mod backtrace;
+pub mod clause_locations;
 pub mod code_walker;
 #[macro_use]
 pub mod loader;
@@ -10,9 +13,13 @@
 pub mod cycle_detection;
 pub mod disjuncts;
 pub mod dispatch;
+pub mod flags;
+pub mod foreign_predicates;
 pub mod gc;
 pub mod heap;
+pub mod jit_index;
 pub mod lib_machine;
+pub mod limits;
 pub mod load_state;
 pub mod machine_errors;
 pub mod machine_indices;
@@ -22,10 +29,18 @@
 pub mod parsed_results;
 pub mod partial_string;
 pub mod preprocessor;
+pub mod profiler;
+pub mod qlf;
+pub mod sandbox;
+pub mod saved_state;
+pub mod shared_database;
 pub mod stack;
 pub mod streams;
 pub mod system_calls;
+pub mod tabling;
 pub mod term_stream;
+pub mod threads;
+pub mod tracer;
 pub mod unify;
 
 use crate::arena::*;
@@ -38,13 +53,19 @@
 use crate::machine::args::*;
 use crate::machine::compile::*;
 use crate::machine::copier::*;
+use crate::machine::foreign_predicates::ForeignPredicateTable;
+use crate::machine::gc::*;
 use crate::machine::heap::*;
+use crate::machine::limits::LimitsState;
 use crate::machine::loader::*;
 use crate::machine::machine_errors::*;
 use crate::machine::machine_indices::*;
 use crate::machine::machine_state::*;
+use crate::machine::profiler::Profiler;
+use crate::machine::shared_database::SharedDatabase;
 use crate::machine::stack::*;
 use crate::machine::streams::*;
+use crate::machine::tracer::Tracer;
 use crate::parser::ast::*;
 use crate::parser::dashu::{Integer, Rational};
 use crate::types::*;
@@ -53,8 +74,6 @@
 use lazy_static::lazy_static;
 use ordered_float::OrderedFloat;
 
-use rand::rngs::StdRng;
-use rand::SeedableRng;
 use std::cmp::Ordering;
 use std::env;
 use std::io::Read;
@@ -62,7 +81,9 @@
 use std::sync::atomic::AtomicBool;
 
 use self::config::MachineConfig;
-use self::parsed_results::*;
+use self::parsed_results::{
+    LeafAnswer, PrologError, QueryResolution, QueryResolutionLine, QueryResult, Value,
+};
 
 lazy_static! {
     pub static ref INTERRUPT: AtomicBool = AtomicBool::new(false);
@@ -79,7 +100,10 @@ pub struct Machine {
     pub(super) load_contexts: Vec<LoadContext>,
     #[cfg(feature = "ffi")]
     pub(super) foreign_function_table: ForeignFunctionTable,
-    pub(super) rng: StdRng,
+    pub(super) foreign_predicates: ForeignPredicateTable,
+    pub(super) tracer: Tracer,
+    pub(super) profiler: Profiler,
+    pub(super) shared_database: Option<SharedDatabase>,
 }
 
 #[derive(Debug)]
@@ -87,6 +111,9 @@ pub struct LoadContext {
     pub(super) path: PathBuf,
     pub(super) stream: Stream,
     pub(super) module: Atom,
+    pub(super) qlf: Option<qlf::QlfLoad>,
+    // the line of the file at which the term being loaded starts.
+    pub(super) line: Option<usize>,
 }
 
 impl LoadContext {
@@ -104,6 +131,8 @@ fn new(path: &str, stream: Stream) -> Self {
             path: path_buf,
             stream,
             module: atom!("user"),
+            qlf: None,
+            line: None,
         }
     }
 }
@@ -180,6 +209,22 @@ pub(crate) fn import_builtin_impls(code_dir: &CodeDir, builtins: &mut Module) {
     }
 }
 
+// the user_input, user_output and user_error streams of a new machine.
+fn user_streams(streams: &config::StreamConfig, arena: &mut Arena) -> (Stream, Stream, Stream) {
+    match streams {
+        config::StreamConfig::Stdio => (
+            Stream::stdin(arena, MachineArgs::new().add_history),
+            Stream::stdout(arena),
+            Stream::stderr(arena),
+        ),
+        config::StreamConfig::Memory => (
+            Stream::Null(StreamOptions::default()),
+            Stream::from_owned_string("".to_owned(), arena),
+            Stream::stderr(arena),
+        ),
+    }
+}
+
 #[inline]
 pub(crate) fn get_structure_index(value: HeapCellValue) -> Option<CodeIndex> {
     read_heap_cell!(value,
@@ -450,21 +495,17 @@ pub(crate) fn add_impls_to_indices(&mut self) {
     pub fn new(config: MachineConfig) -> Self {
         use ref_thread_local::RefThreadLocal;
 
-        let args = MachineArgs::new();
         let mut machine_st = MachineState::new();
 
-        let (user_input, user_output, user_error) = match config.streams {
-            config::StreamConfig::Stdio => (
-                Stream::stdin(&mut machine_st.arena, args.add_history),
-                Stream::stdout(&mut machine_st.arena),
-                Stream::stderr(&mut machine_st.arena),
-            ),
-            config::StreamConfig::Memory => (
-                Stream::Null(StreamOptions::default()),
-                Stream::from_owned_string("".to_owned(), &mut machine_st.arena),
-                Stream::stderr(&mut machine_st.arena),
-            ),
-        };
+        // reserve offset 0 of the stack for an empty frame so that
+        // e == 0 and b == 0 unambiguously mean "no frame".
+        machine_st.stack.allocate_and_frame(0);
+        machine_st.gc = GCState::new(config.gc_threshold);
+        machine_st.limits = LimitsState::new(config.limits);
+        machine_st.sandbox = config.sandbox;
+
+        let (user_input, user_output, user_error) =
+            user_streams(&config.streams, &mut machine_st.arena);
 
         let mut wam = Machine {
             machine_st,
@@ -476,7 +517,10 @@ pub fn new(config: MachineConfig) -> Self {
             load_contexts: vec![],
             #[cfg(feature = "ffi")]
             foreign_function_table: Default::default(),
-            rng: StdRng::from_entropy(),
+            foreign_predicates: Default::default(),
+            tracer: Default::default(),
+            profiler: Default::default(),
+            shared_database: None,
         };
 
         let mut lib_path = current_dir();
@@ -869,6 +913,7 @@ pub(super) fn try_me_else(&mut self, offset: usize) {
             }
 
             self.machine_st.hb = self.machine_st.heap.len();
+            self.profile_choicepoint();
         }
 
         self.machine_st.p += 1;
@@ -901,6 +946,7 @@ pub(super) fn indexed_try(&mut self, offset: usize) {
             }
 
             self.machine_st.hb = self.machine_st.heap.len();
+            self.profile_choicepoint();
 
             // self.machine_st.oip = 0;
             // self.machine_st.iip = 0;
@@ -1118,7 +1164,14 @@ fn undefined_procedure(&mut self, name: Atom, arity: usize) -> CallResult {
     }
 
     #[inline(always)]
-    fn try_call(&mut self, name: Atom, arity: usize, idx: IndexPtr) -> CallResult {
+    fn try_call(&mut self, name: Atom, arity: usize, idx: CodeIndex) -> CallResult {
+        // only dynamic and undefined predicates change with the
+        // updates of other machines.
+        if idx.get_tag() != IndexPtrTag::Index {
+            self.sync_database();
+        }
+
+        let idx = idx.get();
         let compiled_tl_index = idx.p() as usize;
 
         match idx.tag() {
@@ -1129,11 +1182,22 @@ fn try_call(&mut self, name: Atom, arity: usize, idx: IndexPtr) -> CallResult {
                 return self.undefined_procedure(name, arity);
             }
             IndexPtrTag::DynamicIndex => {
-                self.machine_st.dynamic_mode = FirstOrNext::First;
-                self.machine_st.call_at_index(arity, compiled_tl_index);
+                self.maybe_garbage_collect(arity);
+                self.profile_call(compiled_tl_index, false);
+                self.indices.tables.record_dynamic_call((name, arity));
+
+                if !self.divert_to_debugger(name, arity, compiled_tl_index, true, false) {
+                    self.machine_st.dynamic_mode = FirstOrNext::First;
+                    self.machine_st.call_at_index(arity, compiled_tl_index);
+                }
             }
             IndexPtrTag::Index => {
-                self.machine_st.call_at_index(arity, compiled_tl_index);
+                self.maybe_garbage_collect(arity);
+                self.profile_call(compiled_tl_index, false);
+
+                if !self.divert_to_debugger(name, arity, compiled_tl_index, false, false) {
+                    self.machine_st.call_at_index(arity, compiled_tl_index);
+                }
             }
         }
 
@@ -1141,7 +1205,14 @@ fn try_call(&mut self, name: Atom, arity: usize, idx: IndexPtr) -> CallResult {
     }
 
     #[inline(always)]
-    fn try_execute(&mut self, name: Atom, arity: usize, idx: IndexPtr) -> CallResult {
+    fn try_execute(&mut self, name: Atom, arity: usize, idx: CodeIndex) -> CallResult {
+        // only dynamic and undefined predicates change with the
+        // updates of other machines.
+        if idx.get_tag() != IndexPtrTag::Index {
+            self.sync_database();
+        }
+
+        let idx = idx.get();
         let compiled_tl_index = idx.p() as usize;
 
         match idx.tag() {
@@ -1152,10 +1223,23 @@ fn try_execute(&mut self, name: Atom, arity: usize, idx: IndexPtr) -> CallResult
                 return self.undefined_procedure(name, arity);
             }
             IndexPtrTag::DynamicIndex => {
-                self.machine_st.dynamic_mode = FirstOrNext::First;
-                self.machine_st.execute_at_index(arity, compiled_tl_index);
+                self.maybe_garbage_collect(arity);
+                self.profile_call(compiled_tl_index, true);
+                self.indices.tables.record_dynamic_call((name, arity));
+
+                if !self.divert_to_debugger(name, arity, compiled_tl_index, true, true) {
+                    self.machine_st.dynamic_mode = FirstOrNext::First;
+                    self.machine_st.execute_at_index(arity, compiled_tl_index);
+                }
+            }
+            IndexPtrTag::Index => {
+                self.maybe_garbage_collect(arity);
+                self.profile_call(compiled_tl_index, true);
+
+                if !self.divert_to_debugger(name, arity, compiled_tl_index, false, true) {
+                    self.machine_st.execute_at_index(arity, compiled_tl_index);
+                }
             }
-            IndexPtrTag::Index => self.machine_st.execute_at_index(arity, compiled_tl_index),
         }
 
         Ok(())
@@ -1165,15 +1249,17 @@ fn try_execute(&mut self, name: Atom, arity: usize, idx: IndexPtr) -> CallResult
     fn call_clause(&mut self, module_name: Atom, key: PredicateKey) -> CallResult {
         let (name, arity) = key;
 
+        self.sync_database();
+
         if module_name == atom!("user") {
             if let Some(idx) = self.indices.code_dir.get(&(name, arity)).cloned() {
-                self.try_call(name, arity, idx.get())
+                self.try_call(name, arity, idx)
             } else {
                 Err(self.machine_st.throw_undefined_error(name, arity))
             }
         } else if let Some(module) = self.indices.modules.get(&module_name) {
             if let Some(idx) = module.code_dir.get(&(name, arity)).cloned() {
-                self.try_call(name, arity, idx.get())
+                self.try_call(name, arity, idx)
             } else {
                 self.undefined_procedure(name, arity)
             }
@@ -1195,15 +1281,17 @@ fn call_clause(&mut self, module_name: Atom, key: PredicateKey) -> CallResult {
     fn execute_clause(&mut self, module_name: Atom, key: PredicateKey) -> CallResult {
         let (name, arity) = key;
 
+        self.sync_database();
+
         if module_name == atom!("user") {
             if let Some(idx) = self.indices.code_dir.get(&(name, arity)).cloned() {
-                self.try_execute(name, arity, idx.get())
+                self.try_execute(name, arity, idx)
             } else {
                 self.undefined_procedure(name, arity)
             }
         } else if let Some(module) = self.indices.modules.get(&module_name) {
             if let Some(idx) = module.code_dir.get(&(name, arity)).cloned() {
-                self.try_execute(name, arity, idx.get())
+                self.try_execute(name, arity, idx)
             } else {
                 self.undefined_procedure(name, arity)
             }
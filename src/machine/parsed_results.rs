@@ -1,4 +1,7 @@
 use crate::atom_table::*;
+use crate::machine::limits::ResourceLimit;
+use crate::machine::sandbox::Capability;
+use crate::parser::decimal::Decimal;
 use dashu::*;
 use ordered_float::OrderedFloat;
 use std::collections::BTreeMap;
@@ -96,6 +99,160 @@ pub struct QueryMatch {
     pub bindings: BTreeMap<String, Value>,
 }
 
+/// An answer to a query run by `Machine::query`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LeafAnswer {
+    /// The query succeeded without binding its variables or leaving
+    /// residual goals.
+    True,
+    /// The query has no further solutions.
+    False,
+    /// The query succeeded, binding the variables named in `bindings`
+    /// and constraining variables by `residual_goals`. Variables left
+    /// unbound and those whose names start with `_` are omitted from
+    /// `bindings`.
+    LeafAnswer {
+        bindings: BTreeMap<String, Term>,
+        residual_goals: Vec<Term>,
+    },
+}
+
+/// An error ending a query run by `Machine::query`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrologError {
+    /// The query couldn't be read. `kind` is the argument of the
+    /// `syntax_error/1` term a call to `read/1` would have raised, and
+    /// `position` the line and column of the error, if known.
+    SyntaxError {
+        kind: Atom,
+        position: Option<(usize, usize)>,
+    },
+    /// The query raised an exception it didn't catch.
+    Exception(Term),
+}
+
+impl PrologError {
+    /// The limit of `MachineConfig::limits` the query exceeded, if
+    /// this is the `resource_error/1` raised for one.
+    pub fn resource_limit(&self) -> Option<ResourceLimit> {
+        match self {
+            PrologError::Exception(Term::Compound(name, args)) if name == "error" => {
+                match args.first() {
+                    Some(Term::Compound(name, args)) if name == "resource_error" => {
+                        match args.as_slice() {
+                            [Term::Atom(limit)] => ResourceLimit::from_name(limit),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// The capability denied to the query by `MachineConfig::sandbox`,
+    /// if this is the `permission_error/3` raised for it.
+    pub fn sandbox_violation(&self) -> Option<Capability> {
+        match self {
+            PrologError::Exception(Term::Compound(name, args)) if name == "error" => {
+                match args.first() {
+                    Some(Term::Compound(name, args)) if name == "permission_error" => {
+                        match args.as_slice() {
+                            [Term::Atom(action), Term::Atom(kind), Term::Atom(capability)]
+                                if action == "call" && kind == "sandboxed" =>
+                            {
+                                Capability::from_name(capability)
+                            }
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A Prolog term, as read from or written to the heap of a machine.
+///
+/// Variables are named, and occurrences of a variable share its name.
+/// Variables of answers that aren't query variables are given names
+/// starting with `_`. When written to the heap, each occurrence of the
+/// variable `_` is distinct.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Term {
+    Integer(Integer),
+    Rational(Rational),
+    Float(OrderedFloat<f64>),
+    /// A decimal such as `1.50d`, which keeps its digits after the
+    /// decimal point.
+    Decimal(Decimal),
+    Atom(String),
+    /// A list of characters held by the machine as a string, such as
+    /// `"abc"` with the `double_quotes` flag set to `chars`.
+    String(String),
+    /// A proper list. Partial lists are compounds `'.'(Head, Tail)`.
+    List(Vec<Term>),
+    Compound(String, Vec<Term>),
+    Var(String),
+}
+
+impl Term {
+    pub fn integer(n: impl Into<Integer>) -> Self {
+        Term::Integer(n.into())
+    }
+
+    pub fn rational(r: impl Into<Rational>) -> Self {
+        Term::Rational(r.into())
+    }
+
+    pub fn float(f: f64) -> Self {
+        Term::Float(OrderedFloat(f))
+    }
+
+    pub fn atom(name: impl Into<String>) -> Self {
+        Term::Atom(name.into())
+    }
+
+    pub fn string(s: impl Into<String>) -> Self {
+        Term::String(s.into())
+    }
+
+    pub fn list(items: impl IntoIterator<Item = Term>) -> Self {
+        Term::List(items.into_iter().collect())
+    }
+
+    /// A compound term, or an atom if `args` is empty.
+    pub fn compound(name: impl Into<String>, args: impl IntoIterator<Item = Term>) -> Self {
+        let args: Vec<_> = args.into_iter().collect();
+
+        if args.is_empty() {
+            Term::Atom(name.into())
+        } else {
+            Term::Compound(name.into(), args)
+        }
+    }
+
+    pub fn variable(name: impl Into<String>) -> Self {
+        Term::Var(name.into())
+    }
+
+    /// Builds a partial list of `items` followed by `tail`.
+    pub fn partial_list(items: impl IntoIterator<Item = Term>, tail: Term) -> Self {
+        let items: Vec<_> = items.into_iter().collect();
+
+        match tail {
+            Term::Atom(ref name) if name == "[]" => Term::List(items),
+            Term::List(tail) => Term::List(items.into_iter().chain(tail).collect()),
+            tail => items.into_iter().rev().fold(tail, |tail, item| {
+                Term::Compound(".".to_string(), vec![item, tail])
+            }),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum QueryResolutionLine {
     True,
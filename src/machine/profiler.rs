@@ -0,0 +1,346 @@
+use crate::atom_table::*;
+use crate::forms::*;
+use crate::machine::machine_indices::*;
+use crate::machine::parsed_results::Term;
+use crate::machine::Machine;
+use crate::parser::dashu::Integer;
+
+use indexmap::IndexMap;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// the least time between two samples of the call stack.
+const SAMPLE_INTERVAL: Duration = Duration::from_micros(10);
+
+// the most call sites of a sampled call stack.
+const MAX_STACK_DEPTH: usize = 512;
+
+#[derive(Debug, Default, Clone, Copy)]
+struct CallCounts {
+    calls: u64,
+    // the inferences made since the previous call.
+    inferences: u64,
+}
+
+/// The profiler of `profile/1`, in library(profile). Every call to a
+/// predicate is counted, and the call stack is sampled at calls to
+/// attribute the time between samples to it.
+#[derive(Debug, Default)]
+pub(crate) struct Profiler {
+    pub(crate) enabled: bool,
+    // the environment of the clause which started the profiler, where
+    // the sampled stacks end.
+    base_e: usize,
+    inferences: Integer,
+    last_sample: Option<Instant>,
+    // the calls from each call site to the code of each predicate.
+    calls: HashMap<(usize, usize), CallCounts>,
+    // the choice points created by the code at each address.
+    choicepoints: HashMap<usize, u64>,
+    // the time of the samples of each call stack, given by its call
+    // sites, innermost first.
+    samples: HashMap<Vec<usize>, Duration>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct PredicateProfile {
+    calls: u64,
+    inferences: u64,
+    choicepoints: u64,
+    self_time: Duration,
+    total_time: Duration,
+}
+
+pub(super) type ProfiledPredicate = (Atom, PredicateKey);
+
+// the predicates of `code_dir` and the code they start at.
+fn code_ptrs(code_dir: &CodeDir) -> impl Iterator<Item = (PredicateKey, usize)> + '_ {
+    code_dir
+        .iter()
+        .filter_map(|(key, idx)| match idx.get().tag() {
+            IndexPtrTag::Index | IndexPtrTag::DynamicIndex => Some((*key, idx.get().p() as usize)),
+            _ => None,
+        })
+}
+
+// the predicates of the code area by the addresses their clauses start
+// at, in order. the code of a predicate is in the code directories of
+// the modules importing it, too, so it is of the module exporting it,
+// else of user if it is there, else of a module defining it.
+pub(super) fn code_starts(indices: &IndexStore) -> Vec<(usize, ProfiledPredicate)> {
+    let mut predicates: HashMap<usize, (usize, ProfiledPredicate)> = HashMap::new();
+
+    let mut add_predicate = |p: usize, rank: usize, predicate: ProfiledPredicate| {
+        let entry = predicates.entry(p).or_insert((rank, predicate));
+
+        if rank < entry.0 {
+            *entry = (rank, predicate);
+        }
+    };
+
+    for (module_name, module) in indices.modules.iter() {
+        for (key, p) in code_ptrs(&module.code_dir) {
+            let exported = module.module_decl.exports.iter().any(
+                |export| matches!(export, ModuleExport::PredicateKey(export) if *export == key),
+            );
+
+            // the modules made for the files consulted import the
+            // predicates of the modules they declare.
+            let rank = if exported {
+                0
+            } else if let ListingSource::DynamicallyGenerated = module.listing_src {
+                3
+            } else {
+                2
+            };
+
+            add_predicate(p, rank, (*module_name, key));
+        }
+    }
+
+    for (key, p) in code_ptrs(&indices.code_dir) {
+        add_predicate(p, 1, (atom!("user"), key));
+    }
+
+    let mut starts: Vec<_> = predicates
+        .into_iter()
+        .map(|(p, (_, predicate))| (p, predicate))
+        .collect();
+
+    // the clauses added to dynamic predicates are compiled apart from
+    // the rest of their code.
+    let skeletons = indices
+        .extensible_predicates
+        .iter()
+        .map(|(key, skeleton)| (atom!("user"), key, skeleton))
+        .chain(indices.modules.iter().flat_map(|(module_name, module)| {
+            module
+                .extensible_predicates
+                .iter()
+                .map(move |(key, skeleton)| (*module_name, key, skeleton))
+        }));
+
+    for (module_name, key, skeleton) in skeletons {
+        for clause in &skeleton.clauses {
+            starts.push((clause.clause_start, (module_name, *key)));
+        }
+    }
+
+    starts.sort_unstable_by_key(|(p, _)| *p);
+    starts
+}
+
+pub(super) fn predicate_at(
+    starts: &[(usize, ProfiledPredicate)],
+    p: usize,
+) -> Option<ProfiledPredicate> {
+    let idx = starts.partition_point(|(start, _)| *start <= p);
+    idx.checked_sub(1).map(|idx| starts[idx].1)
+}
+
+pub(super) fn predicate_indicator((module_name, (name, arity)): ProfiledPredicate) -> Term {
+    Term::compound(
+        ":",
+        [
+            Term::atom(module_name.as_str().to_string()),
+            Term::compound(
+                "/",
+                [Term::atom(name.as_str().to_string()), Term::integer(arity)],
+            ),
+        ],
+    )
+}
+
+impl Machine {
+    pub(crate) fn start_profiler(&mut self) {
+        self.profiler = Profiler {
+            enabled: true,
+            base_e: self.machine_st.e,
+            inferences: self.machine_st.cwil.global_count.clone(),
+            last_sample: Some(Instant::now()),
+            ..Profiler::default()
+        };
+    }
+
+    pub(crate) fn stop_profiler(&mut self) {
+        self.profiler.enabled = false;
+    }
+
+    /// Counts the call to the predicate starting at `p` from the call
+    /// site at P if the profiler is on.
+    #[inline(always)]
+    pub(super) fn profile_call(&mut self, p: usize, last_call: bool) {
+        if self.profiler.enabled {
+            self.record_call(p, last_call);
+        }
+    }
+
+    /// Counts the choice point created at P if the profiler is on.
+    #[inline(always)]
+    pub(super) fn profile_choicepoint(&mut self) {
+        if self.profiler.enabled {
+            *self
+                .profiler
+                .choicepoints
+                .entry(self.machine_st.p)
+                .or_default() += 1;
+        }
+    }
+
+    fn record_call(&mut self, p: usize, last_call: bool) {
+        let site = self.machine_st.p;
+        let inferences = &self.machine_st.cwil.global_count - &self.profiler.inferences;
+        let counts = self.profiler.calls.entry((site, p)).or_default();
+
+        counts.calls += 1;
+        counts.inferences += u64::try_from(&inferences).unwrap_or(0);
+
+        self.profiler.inferences = self.machine_st.cwil.global_count.clone();
+
+        let now = Instant::now();
+        let elapsed = match self.profiler.last_sample {
+            Some(last_sample) => now - last_sample,
+            None => Duration::ZERO,
+        };
+
+        if elapsed >= SAMPLE_INTERVAL {
+            let stack = self.call_stack(site, last_call);
+
+            *self.profiler.samples.entry(stack).or_default() += elapsed;
+            self.profiler.last_sample = Some(now);
+        }
+    }
+
+    // the call site of the call being made and those of the calls it
+    // returns through, up to the clause which started the profiler. the
+    // environments of the clauses it called are newer, and so above
+    // it on the stack.
+    fn call_stack(&self, site: usize, last_call: bool) -> Vec<usize> {
+        let machine_st = &self.machine_st;
+        let mut stack = vec![site];
+
+        // a last call returns to the continuation of its caller, whose
+        // environment is deallocated.
+        let (mut cp, mut e) = if last_call {
+            (machine_st.cp, machine_st.e)
+        } else if machine_st.e <= self.profiler.base_e {
+            return stack;
+        } else {
+            let and_frame = machine_st.stack.index_and_frame(machine_st.e);
+            (and_frame.prelude.cp, and_frame.prelude.e)
+        };
+
+        while stack.len() < MAX_STACK_DEPTH && e > self.profiler.base_e {
+            // cp follows the call it returns from.
+            stack.push(cp - 1);
+
+            let and_frame = machine_st.stack.index_and_frame(e);
+
+            cp = and_frame.prelude.cp;
+            e = and_frame.prelude.e;
+        }
+
+        stack
+    }
+
+    fn profiled_predicates(
+        &self,
+    ) -> (
+        IndexMap<ProfiledPredicate, PredicateProfile>,
+        IndexMap<Vec<ProfiledPredicate>, Duration>,
+    ) {
+        let starts = code_starts(&self.indices);
+        let mut predicates: IndexMap<ProfiledPredicate, PredicateProfile> = IndexMap::new();
+        let mut stacks: IndexMap<Vec<ProfiledPredicate>, Duration> = IndexMap::new();
+
+        for (&(site, p), counts) in self.profiler.calls.iter() {
+            if let Some(predicate) = predicate_at(&starts, p) {
+                predicates.entry(predicate).or_default().calls += counts.calls;
+            }
+
+            if let Some(predicate) = predicate_at(&starts, site) {
+                predicates.entry(predicate).or_default().inferences += counts.inferences;
+            }
+        }
+
+        for (&p, &choicepoints) in self.profiler.choicepoints.iter() {
+            if let Some(predicate) = predicate_at(&starts, p) {
+                predicates.entry(predicate).or_default().choicepoints += choicepoints;
+            }
+        }
+
+        for (sites, &time) in self.profiler.samples.iter() {
+            let mut stack: Vec<_> = sites
+                .iter()
+                .filter_map(|&site| predicate_at(&starts, site))
+                .collect();
+
+            if let Some(&predicate) = stack.first() {
+                predicates.entry(predicate).or_default().self_time += time;
+            }
+
+            // recursive calls are on the stack more than once.
+            for (idx, &predicate) in stack.iter().enumerate() {
+                if !stack[..idx].contains(&predicate) {
+                    predicates.entry(predicate).or_default().total_time += time;
+                }
+            }
+
+            stack.reverse();
+            *stacks.entry(stack).or_default() += time;
+        }
+
+        (predicates, stacks)
+    }
+
+    /// Unifies register 1 with the predicates profiled by the last
+    /// run of the profiler, as a list of pairs `Module:Name/Arity -
+    /// profile(Calls, Inferences, ChoicePoints, SelfTime, TotalTime)`
+    /// with the times in seconds, and register 2 with its sampled call
+    /// stacks, as a list of pairs of the predicates of each stack,
+    /// outermost first, and its time in microseconds.
+    pub(crate) fn profile_data(&mut self) {
+        let (predicates, stacks) = self.profiled_predicates();
+
+        let predicates = Term::list(predicates.into_iter().map(|(predicate, profile)| {
+            Term::compound(
+                "-",
+                [
+                    predicate_indicator(predicate),
+                    Term::compound(
+                        "profile",
+                        [
+                            Term::integer(profile.calls),
+                            Term::integer(profile.inferences),
+                            Term::integer(profile.choicepoints),
+                            Term::float(profile.self_time.as_secs_f64()),
+                            Term::float(profile.total_time.as_secs_f64()),
+                        ],
+                    ),
+                ],
+            )
+        }));
+
+        let stacks = Term::list(stacks.into_iter().map(|(stack, time)| {
+            Term::compound(
+                "-",
+                [
+                    Term::list(stack.into_iter().map(predicate_indicator)),
+                    Term::integer(time.as_micros() as u64),
+                ],
+            )
+        }));
+
+        let mut vars = IndexMap::new();
+
+        let predicates = self.term_to_heap(&predicates, &mut vars);
+        unify!(self.machine_st, predicates, self.machine_st.registers[1]);
+
+        if self.machine_st.fail {
+            return;
+        }
+
+        let stacks = self.term_to_heap(&stacks, &mut vars);
+        unify!(self.machine_st, stacks, self.machine_st.registers[2]);
+    }
+}
@@ -104,3 +104,17 @@ term_residual_goals(Term,Rs) :-
     '$term_attributed_variables'(Term, Vs),
     phrase(gather_residual_goals(Vs), Rs),
     maplist(delete_all_attributes_from_var, Vs).
+
+% Calls the goal of a query run from Rust through Machine::query,
+% unifying Goals with the residual goals constraining the variables of
+% Vars in each solution.
+'$call_query'(Goal, Vars0, Goals) :-
+    '$get_attr_var_queue_delim'(B),
+    call(user:Goal),
+    '$get_attr_var_queue_beyond'(B, AttrVars),
+    term_variables(Vars0, Vars),
+    project_attributes(Vars, AttrVars),
+    % the copy shares the variables of the query, as in copy_term/3.
+    findall([Vars, AttrVars]-Gs,
+            term_residual_goals([Vars, AttrVars], Gs),
+            [[Vars, AttrVars]-Goals]).
@@ -0,0 +1,1463 @@
+//! Bytecode caches of loaded files, written by `qlf_compile/1` and read
+//! back by `use_module/1` in place of the source.
+//!
+//! A `.qlf` file replays the load of its source file. Directives and the
+//! clauses of dynamic, multifile and discontiguous predicates are kept
+//! as terms and compiled as they were, after term and goal expansion,
+//! while the static predicates are stored as their compiled
+//! instructions. Module declarations, exports, operators and
+//! meta-predicate declarations are restored by replaying the
+//! directives that made them.
+
+use crate::arena::*;
+use crate::atom_table::*;
+use crate::forms::*;
+use crate::instructions::*;
+use crate::machine::loader::*;
+use crate::machine::machine_errors::*;
+use crate::machine::machine_indices::*;
+use crate::machine::parsed_results::Term;
+use crate::machine::{CallResult, LoadContext, Machine};
+use crate::parser::ast::{Fixnum, Literal, RegType};
+use crate::parser::dashu::{Integer, Natural, Rational};
+use crate::parser::decimal::Decimal;
+use crate::types::*;
+
+use fxhash::FxBuildHasher;
+use indexmap::{IndexMap, IndexSet};
+use ordered_float::OrderedFloat;
+
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::hash::Hash;
+use std::io::Read;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+const QLF_MAGIC: &[u8; 8] = b"SCRYQLF\0";
+
+/// The version of the `.qlf` format, bumped whenever the layout of
+/// its records changes. Changes to the instructions are caught by the
+/// instruction fingerprint in the header instead.
+pub(crate) const QLF_VERSION: usize = 2;
+
+const RECORD_END: u8 = 0;
+const RECORD_TERM: u8 = 1;
+const RECORD_PREDICATE: u8 = 2;
+
+#[derive(Debug)]
+pub(crate) enum QlfError {
+    BadHeader,
+    Truncated,
+    InvalidNumber,
+    InvalidUtf8,
+    UnknownInstruction,
+    UnknownTag,
+    // a constant of the code has no serialized form.
+    UnencodableConstant,
+    // a code index of the code belongs to no module.
+    UnresolvedCodeIndex,
+}
+
+/// The state a `.qlf` file needs to be decoded into a running machine.
+pub(crate) trait QlfContext {
+    fn atom(&mut self, name: &str) -> Atom;
+    fn arena(&mut self) -> &mut Arena;
+    fn read_code_index(&mut self, dec: &mut QlfDecoder) -> Result<CodeIndex, QlfError>;
+}
+
+impl<'a, LS: LoadState<'a>> QlfContext for Loader<'a, LS> {
+    fn atom(&mut self, name: &str) -> Atom {
+        AtomTable::build_with(&LS::machine_st(&mut self.payload).atom_tbl, name)
+    }
+
+    fn arena(&mut self) -> &mut Arena {
+        &mut LS::machine_st(&mut self.payload).arena
+    }
+
+    fn read_code_index(&mut self, dec: &mut QlfDecoder) -> Result<CodeIndex, QlfError> {
+        let module_name = Atom::qlf_decode(dec, self)?;
+        let key = PredicateKey::qlf_decode(dec, self)?;
+
+        Ok(self.get_or_insert_qualified_code_index(module_name, key))
+    }
+}
+
+/// The modules and predicates of the code indices of a machine, by
+/// their addresses.
+pub(crate) type CodeIndexKeys = HashMap<usize, (Atom, PredicateKey)>;
+
+/// How code indices are written.
+pub(crate) enum CodeIndexNames<'a> {
+    // by the module and key of a predicate, to be looked up by the
+    // loader of a .qlf file.
+    Qualified(&'a CodeIndexKeys),
+    // by the order in which they're first written.
+    Numbered(IndexMap<usize, CodeIndex, FxBuildHasher>),
+}
+
+pub(crate) struct QlfEncoder<'a> {
+    pub(crate) bytes: Vec<u8>,
+    pub(crate) code_index_names: CodeIndexNames<'a>,
+}
+
+impl<'a> QlfEncoder<'a> {
+    pub(crate) fn new(code_index_names: CodeIndexNames<'a>) -> Self {
+        Self {
+            bytes: vec![],
+            code_index_names,
+        }
+    }
+
+    /// Writes the header of a file of the kind `magic` and the format
+    /// `version`, which includes the fingerprint of the instruction
+    /// set, as instructions are written by their position in it.
+    #[inline]
+    pub(crate) fn write_header(&mut self, magic: &[u8; 8], version: usize) {
+        self.bytes.extend(magic);
+        self.write_usize(version);
+        self.write_u64(INSTRUCTION_FINGERPRINT);
+        self.write_str(env!("CARGO_PKG_VERSION"));
+    }
+
+    pub(crate) fn write_u8(&mut self, byte: u8) {
+        self.bytes.push(byte);
+    }
+
+    pub(crate) fn write_usize(&mut self, mut n: usize) {
+        while n >= 0x80 {
+            self.bytes.push((n as u8) | 0x80);
+            n >>= 7;
+        }
+
+        self.bytes.push(n as u8);
+    }
+
+    pub(crate) fn write_i64(&mut self, n: i64) {
+        self.write_usize(((n << 1) ^ (n >> 63)) as u64 as usize);
+    }
+
+    pub(crate) fn write_u64(&mut self, n: u64) {
+        self.bytes.extend(n.to_le_bytes());
+    }
+
+    pub(crate) fn write_f64(&mut self, f: f64) {
+        self.write_u64(f.to_bits());
+    }
+
+    pub(crate) fn write_str(&mut self, s: &str) {
+        self.write_usize(s.len());
+        self.bytes.extend(s.as_bytes());
+    }
+}
+
+pub(crate) struct QlfDecoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> QlfDecoder<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    #[inline]
+    pub(crate) fn read_u8(&mut self) -> Result<u8, QlfError> {
+        let byte = *self.bytes.get(self.pos).ok_or(QlfError::Truncated)?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    pub(crate) fn read_usize(&mut self) -> Result<usize, QlfError> {
+        let mut n = 0usize;
+        let mut shift = 0;
+
+        loop {
+            let byte = self.read_u8()?;
+
+            if shift >= usize::BITS {
+                return Err(QlfError::InvalidNumber);
+            }
+
+            n |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+    }
+
+    pub(crate) fn read_i64(&mut self) -> Result<i64, QlfError> {
+        let n = self.read_usize()? as u64;
+        Ok((n >> 1) as i64 ^ -((n & 1) as i64))
+    }
+
+    pub(crate) fn read_u64(&mut self) -> Result<u64, QlfError> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + 8)
+            .ok_or(QlfError::Truncated)?;
+
+        self.pos += 8;
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub(crate) fn read_f64(&mut self) -> Result<f64, QlfError> {
+        self.read_u64().map(f64::from_bits)
+    }
+
+    pub(crate) fn read_str(&mut self) -> Result<&'a str, QlfError> {
+        let len = self.read_usize()?;
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + len)
+            .ok_or(QlfError::Truncated)?;
+
+        self.pos += len;
+        std::str::from_utf8(bytes).map_err(|_| QlfError::InvalidUtf8)
+    }
+
+    // checks that the bytes were written by this build of scryer-prolog
+    // to a file of the given kind and version, with the same
+    // instruction set.
+    pub(crate) fn read_header(&mut self, magic: &[u8; 8], version: usize) -> Result<(), QlfError> {
+        if self.bytes.get(0..magic.len()) != Some(&magic[..]) {
+            return Err(QlfError::BadHeader);
+        }
+
+        self.pos = magic.len();
+
+        if self.read_usize()? != version
+            || self.read_u64()? != INSTRUCTION_FINGERPRINT
+            || self.read_str()? != env!("CARGO_PKG_VERSION")
+        {
+            return Err(QlfError::BadHeader);
+        }
+
+        Ok(())
+    }
+}
+
+/// A type with a serialized form in `.qlf` files.
+pub(crate) trait QlfCode: Sized {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError>;
+    fn qlf_decode(dec: &mut QlfDecoder, ctx: &mut dyn QlfContext) -> Result<Self, QlfError>;
+}
+
+impl QlfCode for usize {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        enc.write_usize(*self);
+        Ok(())
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, _ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        dec.read_usize()
+    }
+}
+
+impl QlfCode for bool {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        enc.write_u8(*self as u8);
+        Ok(())
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, _ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        Ok(dec.read_u8()? != 0)
+    }
+}
+
+impl QlfCode for Atom {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        enc.write_str(&self.as_str());
+        Ok(())
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        let name = dec.read_str()?;
+        Ok(ctx.atom(name))
+    }
+}
+
+impl<A: QlfCode, B: QlfCode> QlfCode for (A, B) {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        self.0.qlf_encode(enc)?;
+        self.1.qlf_encode(enc)
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        let a = A::qlf_decode(dec, ctx)?;
+        let b = B::qlf_decode(dec, ctx)?;
+
+        Ok((a, b))
+    }
+}
+
+impl<T: QlfCode> QlfCode for Vec<T> {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        enc.write_usize(self.len());
+        self.iter().try_for_each(|item| item.qlf_encode(enc))
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        let len = dec.read_usize()?;
+        (0..len).map(|_| T::qlf_decode(dec, ctx)).collect()
+    }
+}
+
+impl<T: QlfCode> QlfCode for VecDeque<T> {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        enc.write_usize(self.len());
+        self.iter().try_for_each(|item| item.qlf_encode(enc))
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        let len = dec.read_usize()?;
+        (0..len).map(|_| T::qlf_decode(dec, ctx)).collect()
+    }
+}
+
+impl<K: QlfCode + Hash + Eq, V: QlfCode> QlfCode for IndexMap<K, V, FxBuildHasher> {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        enc.write_usize(self.len());
+
+        for (key, value) in self.iter() {
+            key.qlf_encode(enc)?;
+            value.qlf_encode(enc)?;
+        }
+
+        Ok(())
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        let len = dec.read_usize()?;
+        let mut map = IndexMap::with_capacity_and_hasher(len, FxBuildHasher::default());
+
+        for _ in 0..len {
+            let key = K::qlf_decode(dec, ctx)?;
+            let value = V::qlf_decode(dec, ctx)?;
+
+            map.insert(key, value);
+        }
+
+        Ok(map)
+    }
+}
+
+impl<K: QlfCode + Hash + Eq> QlfCode for IndexSet<K, FxBuildHasher> {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        enc.write_usize(self.len());
+        self.iter().try_for_each(|key| key.qlf_encode(enc))
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        let len = dec.read_usize()?;
+        let mut set = IndexSet::with_capacity_and_hasher(len, FxBuildHasher::default());
+
+        for _ in 0..len {
+            set.insert(K::qlf_decode(dec, ctx)?);
+        }
+
+        Ok(set)
+    }
+}
+
+impl<T: QlfCode> QlfCode for Option<T> {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        match self {
+            Some(item) => {
+                enc.write_u8(1);
+                item.qlf_encode(enc)
+            }
+            None => {
+                enc.write_u8(0);
+                Ok(())
+            }
+        }
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        match dec.read_u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(T::qlf_decode(dec, ctx)?)),
+            _ => Err(QlfError::UnknownTag),
+        }
+    }
+}
+
+impl QlfCode for RegType {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        match *self {
+            RegType::Perm(r) => {
+                enc.write_u8(0);
+                enc.write_usize(r);
+            }
+            RegType::Temp(r) => {
+                enc.write_u8(1);
+                enc.write_usize(r);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, _ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        match dec.read_u8()? {
+            0 => Ok(RegType::Perm(dec.read_usize()?)),
+            1 => Ok(RegType::Temp(dec.read_usize()?)),
+            _ => Err(QlfError::UnknownTag),
+        }
+    }
+}
+
+impl QlfCode for Level {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        enc.write_u8(match self {
+            Level::Deep => 0,
+            Level::Root => 1,
+            Level::Shallow => 2,
+        });
+
+        Ok(())
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, _ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        match dec.read_u8()? {
+            0 => Ok(Level::Deep),
+            1 => Ok(Level::Root),
+            2 => Ok(Level::Shallow),
+            _ => Err(QlfError::UnknownTag),
+        }
+    }
+}
+
+impl QlfCode for NextOrFail {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        match *self {
+            NextOrFail::Next(o) => {
+                enc.write_u8(0);
+                enc.write_usize(o);
+            }
+            NextOrFail::Fail(o) => {
+                enc.write_u8(1);
+                enc.write_usize(o);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, _ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        match dec.read_u8()? {
+            0 => Ok(NextOrFail::Next(dec.read_usize()?)),
+            1 => Ok(NextOrFail::Fail(dec.read_usize()?)),
+            _ => Err(QlfError::UnknownTag),
+        }
+    }
+}
+
+impl QlfCode for Death {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        match *self {
+            Death::Finite(n) => {
+                enc.write_u8(0);
+                enc.write_usize(n);
+            }
+            Death::Infinity => enc.write_u8(1),
+        }
+
+        Ok(())
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, _ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        match dec.read_u8()? {
+            0 => Ok(Death::Finite(dec.read_usize()?)),
+            1 => Ok(Death::Infinity),
+            _ => Err(QlfError::UnknownTag),
+        }
+    }
+}
+
+fn encode_integer(enc: &mut QlfEncoder, n: &Integer) {
+    enc.write_str(&n.to_string());
+}
+
+fn decode_integer(dec: &mut QlfDecoder) -> Result<Integer, QlfError> {
+    dec.read_str()?.parse().map_err(|_| QlfError::InvalidNumber)
+}
+
+fn encode_rational(enc: &mut QlfEncoder, r: &Rational) {
+    enc.write_str(&r.numerator().to_string());
+    enc.write_str(&r.denominator().to_string());
+}
+
+fn decode_rational(dec: &mut QlfDecoder) -> Result<Rational, QlfError> {
+    let numerator = decode_integer(dec)?;
+    let denominator: Natural = dec
+        .read_str()?
+        .parse()
+        .map_err(|_| QlfError::InvalidNumber)?;
+
+    Ok(Rational::from_parts(numerator, denominator))
+}
+
+fn encode_decimal(enc: &mut QlfEncoder, d: &Decimal) {
+    encode_integer(enc, &d.significand());
+    enc.write_usize(d.scale());
+}
+
+fn decode_decimal(dec: &mut QlfDecoder) -> Result<Decimal, QlfError> {
+    let significand = decode_integer(dec)?;
+    let scale = dec.read_usize()?;
+
+    Ok(Decimal::new(significand, scale))
+}
+
+impl QlfCode for Number {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        match self {
+            Number::Float(f) => {
+                enc.write_u8(0);
+                enc.write_f64(f.into_inner());
+            }
+            Number::Integer(n) => {
+                enc.write_u8(1);
+                encode_integer(enc, n);
+            }
+            Number::Rational(r) => {
+                enc.write_u8(2);
+                encode_rational(enc, r);
+            }
+            Number::Fixnum(n) => {
+                enc.write_u8(3);
+                enc.write_i64(n.get_num());
+            }
+            Number::Decimal(d) => {
+                enc.write_u8(4);
+                encode_decimal(enc, d);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        match dec.read_u8()? {
+            0 => Ok(Number::Float(OrderedFloat(dec.read_f64()?))),
+            1 => {
+                let n = decode_integer(dec)?;
+                Ok(Number::Integer(arena_alloc!(n, ctx.arena())))
+            }
+            2 => {
+                let r = decode_rational(dec)?;
+                Ok(Number::Rational(arena_alloc!(r, ctx.arena())))
+            }
+            3 => Fixnum::build_with_checked(dec.read_i64()?)
+                .map(Number::Fixnum)
+                .map_err(|_| QlfError::InvalidNumber),
+            4 => {
+                let d = decode_decimal(dec)?;
+                Ok(Number::Decimal(arena_alloc!(d, ctx.arena())))
+            }
+            _ => Err(QlfError::UnknownTag),
+        }
+    }
+}
+
+impl QlfCode for crate::arithmetic::ArithmeticTerm {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        use crate::arithmetic::ArithmeticTerm;
+
+        match self {
+            ArithmeticTerm::Reg(r) => {
+                enc.write_u8(0);
+                r.qlf_encode(enc)
+            }
+            ArithmeticTerm::Interm(i) => {
+                enc.write_u8(1);
+                enc.write_usize(*i);
+                Ok(())
+            }
+            ArithmeticTerm::Number(n) => {
+                enc.write_u8(2);
+                n.qlf_encode(enc)
+            }
+        }
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        use crate::arithmetic::ArithmeticTerm;
+
+        match dec.read_u8()? {
+            0 => Ok(ArithmeticTerm::Reg(RegType::qlf_decode(dec, ctx)?)),
+            1 => Ok(ArithmeticTerm::Interm(dec.read_usize()?)),
+            2 => Ok(ArithmeticTerm::Number(Number::qlf_decode(dec, ctx)?)),
+            _ => Err(QlfError::UnknownTag),
+        }
+    }
+}
+
+impl QlfCode for CodeIndex {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        match &mut enc.code_index_names {
+            CodeIndexNames::Qualified(code_index_keys) => {
+                let (module_name, key) = *code_index_keys
+                    .get(&(self.as_ptr() as usize))
+                    .ok_or(QlfError::UnresolvedCodeIndex)?;
+
+                module_name.qlf_encode(enc)?;
+                key.qlf_encode(enc)
+            }
+            CodeIndexNames::Numbered(code_indices) => {
+                let (id, _) = code_indices.insert_full(self.as_ptr() as usize, *self);
+                enc.write_usize(id);
+                Ok(())
+            }
+        }
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        ctx.read_code_index(dec)
+    }
+}
+
+impl QlfCode for Literal {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        match self {
+            Literal::Atom(name) => {
+                enc.write_u8(0);
+                name.qlf_encode(enc)
+            }
+            Literal::Char(c) => {
+                enc.write_u8(1);
+                enc.write_usize(*c as usize);
+                Ok(())
+            }
+            Literal::CodeIndex(code_index) => {
+                enc.write_u8(2);
+                code_index.qlf_encode(enc)
+            }
+            Literal::Fixnum(n) => {
+                enc.write_u8(3);
+                enc.write_i64(n.get_num());
+                Ok(())
+            }
+            Literal::Integer(n) => {
+                enc.write_u8(4);
+                encode_integer(enc, n);
+                Ok(())
+            }
+            Literal::Rational(r) => {
+                enc.write_u8(5);
+                encode_rational(enc, r);
+                Ok(())
+            }
+            Literal::Float(f) => {
+                enc.write_u8(6);
+                enc.write_f64(f.as_ptr().into_inner());
+                Ok(())
+            }
+            Literal::String(s) => {
+                enc.write_u8(7);
+                s.qlf_encode(enc)
+            }
+            Literal::Decimal(d) => {
+                enc.write_u8(8);
+                encode_decimal(enc, d);
+                Ok(())
+            }
+        }
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        match dec.read_u8()? {
+            0 => Ok(Literal::Atom(Atom::qlf_decode(dec, ctx)?)),
+            1 => u32::try_from(dec.read_usize()?)
+                .ok()
+                .and_then(char::from_u32)
+                .map(Literal::Char)
+                .ok_or(QlfError::InvalidNumber),
+            2 => Ok(Literal::CodeIndex(CodeIndex::qlf_decode(dec, ctx)?)),
+            3 => Fixnum::build_with_checked(dec.read_i64()?)
+                .map(Literal::Fixnum)
+                .map_err(|_| QlfError::InvalidNumber),
+            4 => {
+                let n = decode_integer(dec)?;
+                Ok(Literal::Integer(arena_alloc!(n, ctx.arena())))
+            }
+            5 => {
+                let r = decode_rational(dec)?;
+                Ok(Literal::Rational(arena_alloc!(r, ctx.arena())))
+            }
+            6 => {
+                let f = dec.read_f64()?;
+                Ok(Literal::from(float_alloc!(f, ctx.arena())))
+            }
+            7 => Ok(Literal::String(Atom::qlf_decode(dec, ctx)?)),
+            8 => {
+                let d = decode_decimal(dec)?;
+                Ok(Literal::Decimal(arena_alloc!(d, ctx.arena())))
+            }
+            _ => Err(QlfError::UnknownTag),
+        }
+    }
+}
+
+impl QlfCode for HeapCellValue {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        Literal::try_from(*self)
+            .map_err(|_| QlfError::UnencodableConstant)?
+            .qlf_encode(enc)
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        Ok(HeapCellValue::from(Literal::qlf_decode(dec, ctx)?))
+    }
+}
+
+impl QlfCode for IndexingCodePtr {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        match *self {
+            IndexingCodePtr::External(o) => {
+                enc.write_u8(0);
+                enc.write_usize(o);
+            }
+            IndexingCodePtr::DynamicExternal(o) => {
+                enc.write_u8(1);
+                enc.write_usize(o);
+            }
+            IndexingCodePtr::Fail => enc.write_u8(2),
+            IndexingCodePtr::Internal(o) => {
+                enc.write_u8(3);
+                enc.write_usize(o);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, _ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        match dec.read_u8()? {
+            0 => Ok(IndexingCodePtr::External(dec.read_usize()?)),
+            1 => Ok(IndexingCodePtr::DynamicExternal(dec.read_usize()?)),
+            2 => Ok(IndexingCodePtr::Fail),
+            3 => Ok(IndexingCodePtr::Internal(dec.read_usize()?)),
+            _ => Err(QlfError::UnknownTag),
+        }
+    }
+}
+
+impl QlfCode for IndexedChoiceInstruction {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        enc.write_u8(match self {
+            IndexedChoiceInstruction::Retry(_) => 0,
+            IndexedChoiceInstruction::DefaultRetry(_) => 1,
+            IndexedChoiceInstruction::Trust(_) => 2,
+            IndexedChoiceInstruction::DefaultTrust(_) => 3,
+            IndexedChoiceInstruction::Try(_) => 4,
+        });
+
+        enc.write_usize(self.offset());
+        Ok(())
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, _ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        let tag = dec.read_u8()?;
+        let offset = dec.read_usize()?;
+
+        match tag {
+            0 => Ok(IndexedChoiceInstruction::Retry(offset)),
+            1 => Ok(IndexedChoiceInstruction::DefaultRetry(offset)),
+            2 => Ok(IndexedChoiceInstruction::Trust(offset)),
+            3 => Ok(IndexedChoiceInstruction::DefaultTrust(offset)),
+            4 => Ok(IndexedChoiceInstruction::Try(offset)),
+            _ => Err(QlfError::UnknownTag),
+        }
+    }
+}
+
+impl QlfCode for IndexingInstruction {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        match self {
+            IndexingInstruction::SwitchOnTerm(arg, vars, constants, lists, structures) => {
+                enc.write_u8(0);
+                enc.write_usize(*arg);
+
+                for ptr in [vars, constants, lists, structures] {
+                    ptr.qlf_encode(enc)?;
+                }
+
+                Ok(())
+            }
+            IndexingInstruction::SwitchOnConstant(constants) => {
+                enc.write_u8(1);
+                constants.qlf_encode(enc)
+            }
+            IndexingInstruction::SwitchOnStructure(structures) => {
+                enc.write_u8(2);
+                structures.qlf_encode(enc)
+            }
+        }
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        match dec.read_u8()? {
+            0 => Ok(IndexingInstruction::SwitchOnTerm(
+                dec.read_usize()?,
+                IndexingCodePtr::qlf_decode(dec, ctx)?,
+                IndexingCodePtr::qlf_decode(dec, ctx)?,
+                IndexingCodePtr::qlf_decode(dec, ctx)?,
+                IndexingCodePtr::qlf_decode(dec, ctx)?,
+            )),
+            1 => Ok(IndexingInstruction::SwitchOnConstant(QlfCode::qlf_decode(
+                dec, ctx,
+            )?)),
+            2 => Ok(IndexingInstruction::SwitchOnStructure(QlfCode::qlf_decode(
+                dec, ctx,
+            )?)),
+            _ => Err(QlfError::UnknownTag),
+        }
+    }
+}
+
+impl QlfCode for IndexingLine {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        match self {
+            IndexingLine::Indexing(instr) => {
+                enc.write_u8(0);
+                instr.qlf_encode(enc)
+            }
+            IndexingLine::IndexedChoice(instrs) => {
+                enc.write_u8(1);
+                instrs.qlf_encode(enc)
+            }
+            IndexingLine::DynamicIndexedChoice(offsets) => {
+                enc.write_u8(2);
+                offsets.qlf_encode(enc)
+            }
+        }
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        match dec.read_u8()? {
+            0 => Ok(IndexingLine::Indexing(IndexingInstruction::qlf_decode(
+                dec, ctx,
+            )?)),
+            1 => Ok(IndexingLine::IndexedChoice(QlfCode::qlf_decode(dec, ctx)?)),
+            2 => Ok(IndexingLine::DynamicIndexedChoice(QlfCode::qlf_decode(
+                dec, ctx,
+            )?)),
+            _ => Err(QlfError::UnknownTag),
+        }
+    }
+}
+
+fn encode_term(enc: &mut QlfEncoder, term: &Term) {
+    match term {
+        Term::Integer(n) => {
+            enc.write_u8(0);
+            encode_integer(enc, n);
+        }
+        Term::Rational(r) => {
+            enc.write_u8(1);
+            encode_rational(enc, r);
+        }
+        Term::Float(f) => {
+            enc.write_u8(2);
+            enc.write_f64(f.into_inner());
+        }
+        Term::Atom(name) => {
+            enc.write_u8(3);
+            enc.write_str(name);
+        }
+        Term::String(s) => {
+            enc.write_u8(4);
+            enc.write_str(s);
+        }
+        Term::List(items) => {
+            enc.write_u8(5);
+            enc.write_usize(items.len());
+            items.iter().for_each(|item| encode_term(enc, item));
+        }
+        Term::Compound(name, args) => {
+            enc.write_u8(6);
+            enc.write_str(name);
+            enc.write_usize(args.len());
+            args.iter().for_each(|arg| encode_term(enc, arg));
+        }
+        Term::Var(name) => {
+            enc.write_u8(7);
+            enc.write_str(name);
+        }
+        Term::Decimal(d) => {
+            enc.write_u8(8);
+            encode_decimal(enc, d);
+        }
+    }
+}
+
+fn decode_term(dec: &mut QlfDecoder) -> Result<Term, QlfError> {
+    match dec.read_u8()? {
+        0 => Ok(Term::Integer(decode_integer(dec)?)),
+        1 => Ok(Term::Rational(decode_rational(dec)?)),
+        2 => Ok(Term::float(dec.read_f64()?)),
+        3 => Ok(Term::atom(dec.read_str()?)),
+        4 => Ok(Term::string(dec.read_str()?)),
+        5 => {
+            let len = dec.read_usize()?;
+            let items = (0..len)
+                .map(|_| decode_term(dec))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(Term::List(items))
+        }
+        6 => {
+            let name = dec.read_str()?;
+            let len = dec.read_usize()?;
+            let args = (0..len)
+                .map(|_| decode_term(dec))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(Term::compound(name, args))
+        }
+        7 => Ok(Term::variable(dec.read_str()?)),
+        8 => Ok(Term::Decimal(decode_decimal(dec)?)),
+        _ => Err(QlfError::UnknownTag),
+    }
+}
+
+// the predicate defined by a clause.
+fn clause_key(clause: &Term) -> Option<(&str, usize)> {
+    let head = match clause {
+        Term::Compound(name, args) if name == ":-" && args.len() == 2 => &args[0],
+        clause => clause,
+    };
+
+    match head {
+        Term::Atom(name) => Some((name, 0)),
+        Term::Compound(name, args) => Some((name, args.len())),
+        _ => None,
+    }
+}
+
+/// The code of a clause of a compiled predicate, relative to the
+/// start of the code of the predicate, and the line it was read from.
+#[derive(Debug, Clone)]
+pub(crate) struct ClauseLine {
+    pub(crate) code: Range<usize>,
+    pub(crate) clause_num: usize,
+    pub(crate) line: usize,
+}
+
+impl QlfCode for ClauseLine {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        enc.write_usize(self.code.start);
+        enc.write_usize(self.code.end);
+        enc.write_usize(self.clause_num);
+        enc.write_usize(self.line);
+
+        Ok(())
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, _ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        Ok(ClauseLine {
+            code: dec.read_usize()?..dec.read_usize()?,
+            clause_num: dec.read_usize()?,
+            line: dec.read_usize()?,
+        })
+    }
+}
+
+// writes the line a term record was read from as an Option<usize>.
+fn encode_line(enc: &mut QlfEncoder, line: Option<usize>) {
+    match line {
+        Some(line) => {
+            enc.write_u8(1);
+            enc.write_usize(line);
+        }
+        None => enc.write_u8(0),
+    }
+}
+
+#[derive(Debug)]
+enum QlfEntry {
+    Term(Term),
+    Clause(Atom, PredicateKey, Term, Option<usize>),
+    Predicate(Atom, PredicateKey, Vec<Instruction>, Vec<ClauseLine>),
+}
+
+/// The terms and compiled predicates of a file load, in the order the
+/// loader saw them.
+#[derive(Debug, Default)]
+pub(crate) struct QlfRecorder {
+    entries: Vec<QlfEntry>,
+}
+
+impl QlfRecorder {
+    pub(crate) fn record_term(&mut self, term: Term) {
+        self.entries.push(QlfEntry::Term(term));
+    }
+
+    pub(crate) fn record_clause(
+        &mut self,
+        module_name: Atom,
+        key: PredicateKey,
+        clause: Term,
+        line: Option<usize>,
+    ) {
+        self.entries
+            .push(QlfEntry::Clause(module_name, key, clause, line));
+    }
+
+    pub(crate) fn record_predicate(
+        &mut self,
+        module_name: Atom,
+        key: PredicateKey,
+        code: Vec<Instruction>,
+        clause_lines: Vec<ClauseLine>,
+    ) {
+        self.entries
+            .push(QlfEntry::Predicate(module_name, key, code, clause_lines));
+    }
+
+    pub(crate) fn predicates(&self) -> impl Iterator<Item = (Atom, PredicateKey, &[Instruction])> {
+        self.entries.iter().filter_map(|entry| match entry {
+            QlfEntry::Predicate(module_name, key, code, _) => Some((*module_name, *key, &code[..])),
+            _ => None,
+        })
+    }
+
+    fn into_bytes(self, code_index_keys: &CodeIndexKeys) -> Vec<u8> {
+        let mut compiled_counts: HashMap<(Atom, PredicateKey), usize> = HashMap::new();
+
+        for entry in &self.entries {
+            if let QlfEntry::Predicate(module_name, key, ..) = entry {
+                *compiled_counts.entry((*module_name, *key)).or_default() += 1;
+            }
+        }
+
+        // the code of a predicate replaces its clauses only if it was
+        // compiled once, and all of its constants can be written.
+        let mut predicates = HashMap::new();
+
+        for entry in &self.entries {
+            if let QlfEntry::Predicate(module_name, key, code, clause_lines) = entry {
+                if compiled_counts[&(*module_name, *key)] != 1 {
+                    continue;
+                }
+
+                let mut enc = QlfEncoder::new(CodeIndexNames::Qualified(code_index_keys));
+
+                if code.qlf_encode(&mut enc).is_ok() && clause_lines.qlf_encode(&mut enc).is_ok() {
+                    predicates.insert((*module_name, *key), enc.bytes);
+                }
+            }
+        }
+
+        let mut enc = QlfEncoder::new(CodeIndexNames::Qualified(code_index_keys));
+
+        enc.write_header(QLF_MAGIC, QLF_VERSION);
+
+        for entry in self.entries {
+            match entry {
+                QlfEntry::Term(term) => {
+                    enc.write_u8(RECORD_TERM);
+                    encode_term(&mut enc, &term);
+                    encode_line(&mut enc, None);
+                }
+                QlfEntry::Clause(module_name, key, clause, line) => {
+                    if !predicates.contains_key(&(module_name, key)) {
+                        enc.write_u8(RECORD_TERM);
+                        encode_term(&mut enc, &clause);
+                        encode_line(&mut enc, line);
+                    }
+                }
+                QlfEntry::Predicate(module_name, key, ..) => {
+                    if let Some(code) = predicates.remove(&(module_name, key)) {
+                        enc.write_u8(RECORD_PREDICATE);
+                        enc.write_str(&module_name.as_str());
+                        enc.write_str(&key.0.as_str());
+                        enc.write_usize(key.1);
+                        enc.bytes.extend(code);
+                    }
+                }
+            }
+        }
+
+        enc.write_u8(RECORD_END);
+        enc.bytes
+    }
+}
+
+pub(crate) enum QlfItem {
+    Term(Term, Option<usize>),
+    Predicate(Atom, PredicateKey, Vec<Instruction>, Vec<ClauseLine>),
+    End,
+}
+
+/// A `.qlf` file being replayed.
+#[derive(Debug)]
+pub(crate) struct QlfReader {
+    bytes: Vec<u8>,
+    pos: usize,
+}
+
+impl QlfReader {
+    fn open(path: &Path) -> Result<Self, QlfError> {
+        let bytes = fs::read(path).map_err(|_| QlfError::Truncated)?;
+        let mut dec = QlfDecoder::new(&bytes);
+
+        dec.read_header(QLF_MAGIC, QLF_VERSION)?;
+        let pos = dec.pos;
+
+        Ok(QlfReader { bytes, pos })
+    }
+
+    pub(crate) fn next_item(&mut self, ctx: &mut dyn QlfContext) -> Result<QlfItem, QlfError> {
+        let mut dec = QlfDecoder {
+            bytes: &self.bytes,
+            pos: self.pos,
+        };
+
+        let item = match dec.read_u8()? {
+            RECORD_END => QlfItem::End,
+            RECORD_TERM => {
+                let term = decode_term(&mut dec)?;
+                let line = Option::<usize>::qlf_decode(&mut dec, ctx)?;
+
+                QlfItem::Term(term, line)
+            }
+            RECORD_PREDICATE => {
+                let module_name = Atom::qlf_decode(&mut dec, ctx)?;
+                let key = PredicateKey::qlf_decode(&mut dec, ctx)?;
+                let code = Vec::<Instruction>::qlf_decode(&mut dec, ctx)?;
+                let clause_lines = Vec::<ClauseLine>::qlf_decode(&mut dec, ctx)?;
+
+                QlfItem::Predicate(module_name, key, code, clause_lines)
+            }
+            _ => return Err(QlfError::UnknownTag),
+        };
+
+        self.pos = dec.pos;
+        Ok(item)
+    }
+}
+
+/// The `.qlf` state of a load context.
+#[derive(Debug)]
+pub(crate) enum QlfLoad {
+    Recording(QlfRecorder),
+    Replaying(QlfReader),
+}
+
+impl LoadContext {
+    pub(crate) fn qlf_recorder(&mut self) -> Option<&mut QlfRecorder> {
+        match &mut self.qlf {
+            Some(QlfLoad::Recording(recorder)) => Some(recorder),
+            _ => None,
+        }
+    }
+}
+
+/// The path of the `.qlf` file of a source file.
+pub fn qlf_path(source: &Path) -> PathBuf {
+    source.with_extension("qlf")
+}
+
+// the .qlf file of `source`, if it's newer than `source` and was
+// written by this build.
+fn fresh_qlf_path(source: &Path) -> Option<PathBuf> {
+    let qlf_path = qlf_path(source);
+
+    let source_modified = fs::metadata(source).and_then(|m| m.modified()).ok()?;
+    let qlf_modified = fs::metadata(&qlf_path).and_then(|m| m.modified()).ok()?;
+
+    if qlf_modified <= source_modified {
+        return None;
+    }
+
+    let mut header = vec![];
+
+    fs::File::open(&qlf_path)
+        .ok()?
+        .take(64 + env!("CARGO_PKG_VERSION").len() as u64)
+        .read_to_end(&mut header)
+        .ok()?;
+
+    QlfDecoder::new(&header)
+        .read_header(QLF_MAGIC, QLF_VERSION)
+        .ok()?;
+    Some(qlf_path)
+}
+
+impl Machine {
+    // the code indices of the machine by address, preferring those of
+    // `module_name`, then of user, to those of other modules.
+    fn code_index_keys(&self, module_name: Atom) -> CodeIndexKeys {
+        let mut keys = CodeIndexKeys::new();
+
+        let mut add_code_dir = |module_name: Atom, code_dir: &CodeDir| {
+            for (key, code_index) in code_dir.iter() {
+                keys.entry(code_index.as_ptr() as usize)
+                    .or_insert((module_name, *key));
+            }
+        };
+
+        if let Some(module) = self.indices.modules.get(&module_name) {
+            add_code_dir(module_name, &module.code_dir);
+        }
+
+        add_code_dir(atom!("user"), &self.indices.code_dir);
+
+        let (in_situ_modules, modules): (Vec<_>, Vec<_>) =
+            self.indices.modules.iter().partition(|(_, module)| {
+                matches!(module.listing_src, ListingSource::DynamicallyGenerated)
+            });
+
+        for (name, module) in modules.into_iter().chain(in_situ_modules) {
+            add_code_dir(*name, &module.code_dir);
+        }
+
+        keys
+    }
+
+    pub(crate) fn qlf_file(&mut self) {
+        let source = cell_as_atom!(self.deref_register(1));
+
+        match fresh_qlf_path(Path::new(&*source.as_str())) {
+            Some(qlf_path) => {
+                let qlf_path =
+                    AtomTable::build_with(&self.machine_st.atom_tbl, &qlf_path.to_string_lossy());
+
+                self.machine_st
+                    .unify_atom(qlf_path, self.machine_st.registers[2]);
+            }
+            None => {
+                self.machine_st.fail = true;
+            }
+        }
+    }
+
+    pub(crate) fn open_qlf(&mut self) -> CallResult {
+        let qlf_path = cell_as_atom!(self.deref_register(1));
+
+        match QlfReader::open(Path::new(&*qlf_path.as_str())) {
+            Ok(reader) => {
+                if let Some(load_context) = self.load_contexts.last_mut() {
+                    load_context.qlf = Some(QlfLoad::Replaying(reader));
+                }
+
+                Ok(())
+            }
+            Err(_) => {
+                let err = self
+                    .machine_st
+                    .session_error(SessionError::InvalidQlfFile(qlf_path));
+                let stub = functor_stub(atom!("load"), 1);
+
+                Err(self.machine_st.error_form(err, stub))
+            }
+        }
+    }
+
+    // installs the compiled predicates of the .qlf file of the load
+    // context up to its next term, which is unified with the second
+    // register, or end_of_file.
+    pub(crate) fn next_qlf_term(&mut self) -> CallResult {
+        let mut reader = match self.load_contexts.last_mut().and_then(|lc| lc.qlf.take()) {
+            Some(QlfLoad::Replaying(reader)) => reader,
+            qlf => {
+                if let Some(load_context) = self.load_contexts.last_mut() {
+                    load_context.qlf = qlf;
+                }
+
+                self.machine_st
+                    .unify_atom(atom!("end_of_file"), self.machine_st.registers[2]);
+                return Ok(());
+            }
+        };
+
+        let qlf_path = self
+            .load_contexts
+            .last()
+            .map(|lc| qlf_path(&lc.path))
+            .unwrap_or_default();
+
+        let qlf_path =
+            AtomTable::build_with(&self.machine_st.atom_tbl, &qlf_path.to_string_lossy());
+
+        let mut item = None;
+        let mut loader = self.loader_from_heap_evacuable(temp_v!(1));
+
+        let next_item = || loop {
+            match reader.next_item(&mut loader) {
+                Ok(QlfItem::Predicate(module_name, key, code, clause_lines)) => {
+                    loader.submit_precompiled_predicate(module_name, key, code, clause_lines)?;
+                }
+                Ok(next) => {
+                    item = Some(next);
+                    return LiveLoadAndMachineState::evacuate(loader);
+                }
+                Err(_) => return Err(SessionError::InvalidQlfFile(qlf_path)),
+            }
+        };
+
+        let result = next_item();
+        self.restore_load_state_payload(result)?;
+
+        match item {
+            Some(QlfItem::Term(term, line)) => {
+                if let Some(load_context) = self.load_contexts.last_mut() {
+                    load_context.qlf = Some(QlfLoad::Replaying(reader));
+                    load_context.line = line;
+                }
+
+                let value = self.term_to_heap(&term, &mut IndexMap::new());
+                unify!(self.machine_st, value, self.machine_st.registers[2]);
+            }
+            _ => {
+                self.machine_st
+                    .unify_atom(atom!("end_of_file"), self.machine_st.registers[2]);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn start_qlf_recording(&mut self) {
+        if let Some(load_context) = self.load_contexts.last_mut() {
+            load_context.qlf = Some(QlfLoad::Recording(QlfRecorder::default()));
+        }
+    }
+
+    fn is_recording_qlf(&mut self) -> bool {
+        self.load_contexts
+            .last_mut()
+            .and_then(LoadContext::qlf_recorder)
+            .is_some()
+    }
+
+    pub(crate) fn record_qlf_term(&mut self) {
+        if self.is_recording_qlf() {
+            let term = self.term_from_heap(self.machine_st.registers[1], &mut HashMap::new());
+
+            if let Some(recorder) = self
+                .load_contexts
+                .last_mut()
+                .and_then(LoadContext::qlf_recorder)
+            {
+                recorder.record_term(term);
+            }
+        }
+    }
+
+    // records the clause at `r` after it was enqueued for compilation
+    // into `module_name`. a `Some(module_name)` qualifies its head.
+    pub(crate) fn record_qlf_clause(
+        &mut self,
+        r: RegType,
+        module_name: Atom,
+        qualifier: Option<Atom>,
+    ) {
+        if !self.is_recording_qlf() {
+            return;
+        }
+
+        let mut clause = self.term_from_heap(self.machine_st[r], &mut HashMap::new());
+
+        if let Some(qualifier) = qualifier {
+            let qualify = |head| Term::compound(":", [Term::atom(&*qualifier.as_str()), head]);
+
+            clause = match clause {
+                Term::Compound(name, mut args) if name == ":-" && args.len() == 2 => {
+                    let body = args.pop().unwrap();
+                    let head = args.pop().unwrap();
+
+                    Term::compound(":-", [qualify(head), body])
+                }
+                head => qualify(head),
+            };
+        }
+
+        let key = clause_key(&clause).map(|(name, arity)| {
+            (
+                AtomTable::build_with(&self.machine_st.atom_tbl, name),
+                arity,
+            )
+        });
+
+        let line = self
+            .load_contexts
+            .last()
+            .and_then(|load_context| load_context.line);
+
+        if let Some(recorder) = self
+            .load_contexts
+            .last_mut()
+            .and_then(LoadContext::qlf_recorder)
+        {
+            match key {
+                Some(key) => recorder.record_clause(module_name, key, clause, line),
+                None => recorder.record_term(clause),
+            }
+        }
+    }
+
+    pub(crate) fn save_qlf(&mut self) -> CallResult {
+        let (recorder, source, module_name) = match self.load_contexts.last_mut() {
+            Some(load_context) => match load_context.qlf.take() {
+                Some(QlfLoad::Recording(recorder)) => {
+                    (recorder, load_context.path.clone(), load_context.module)
+                }
+                qlf => {
+                    load_context.qlf = qlf;
+                    return Ok(());
+                }
+            },
+            None => return Ok(()),
+        };
+
+        let code_index_keys = self.code_index_keys(module_name);
+        let qlf_path = qlf_path(&source);
+
+        if fs::write(&qlf_path, recorder.into_bytes(&code_index_keys)).is_err() {
+            let qlf_path =
+                AtomTable::build_with(&self.machine_st.atom_tbl, &qlf_path.to_string_lossy());
+
+            return Err(self.machine_st.open_permission_error(
+                atom_as_cell!(qlf_path),
+                atom!("qlf_compile"),
+                1,
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers_check_the_instruction_fingerprint() {
+        let keys = CodeIndexKeys::new();
+        let mut enc = QlfEncoder::new(CodeIndexNames::Qualified(&keys));
+
+        enc.write_header(QLF_MAGIC, QLF_VERSION);
+
+        let mut bytes = enc.bytes;
+
+        assert!(QlfDecoder::new(&bytes)
+            .read_header(QLF_MAGIC, QLF_VERSION)
+            .is_ok());
+
+        // the fingerprint follows the magic and the version.
+        bytes[QLF_MAGIC.len() + 1] ^= 1;
+
+        assert!(matches!(
+            QlfDecoder::new(&bytes).read_header(QLF_MAGIC, QLF_VERSION),
+            Err(QlfError::BadHeader)
+        ));
+    }
+}
@@ -0,0 +1,259 @@
+use crate::atom_table::*;
+use crate::machine::machine_errors::*;
+use crate::machine::{CallResult, Machine};
+
+/// A class of system calls reaching outside the machine, which a
+/// [`SandboxPolicy`] allows or denies as a whole.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// opening, inspecting and changing files and directories, and
+    /// saving program states and `.qlf` files.
+    FileSystem,
+    /// TCP and TLS sockets.
+    Network,
+    /// HTTP clients and servers.
+    Http,
+    /// running shell commands and starting, waiting for and killing
+    /// processes.
+    Shell,
+    /// reading and changing environment variables.
+    Environment,
+    /// loading and calling foreign libraries. Predicates registered
+    /// by the host through `Machine::register_det_predicate` and
+    /// `Machine::register_nondet_predicate` are always allowed.
+    ForeignFunctions,
+}
+
+impl Capability {
+    pub(crate) fn as_atom(self) -> Atom {
+        match self {
+            Capability::FileSystem => atom!("file_system"),
+            Capability::Network => atom!("network"),
+            Capability::Http => atom!("http"),
+            Capability::Shell => atom!("shell"),
+            Capability::Environment => atom!("environment"),
+            Capability::ForeignFunctions => atom!("foreign_functions"),
+        }
+    }
+
+    /// The capability named `name`, as it appears in the
+    /// `permission_error/3` raised when it is denied.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "file_system" => Some(Capability::FileSystem),
+            "network" => Some(Capability::Network),
+            "http" => Some(Capability::Http),
+            "shell" => Some(Capability::Shell),
+            "environment" => Some(Capability::Environment),
+            "foreign_functions" => Some(Capability::ForeignFunctions),
+            _ => None,
+        }
+    }
+
+    const ALL: [Capability; 6] = [
+        Capability::FileSystem,
+        Capability::Network,
+        Capability::Http,
+        Capability::Shell,
+        Capability::Environment,
+        Capability::ForeignFunctions,
+    ];
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// The capabilities a machine is allowed, set through
+/// `MachineConfig::with_sandbox`. Pure computation and in-memory
+/// streams are always allowed, as are the libraries bundled with the
+/// machine.
+///
+/// The policy is checked in the system calls themselves, so it holds
+/// whichever predicate reaches them. A denied call raises
+/// `error(permission_error(call, sandboxed, C), N/A)`, where `C` is
+/// the name of the [`Capability`] and `N/A` the system call. Threads
+/// created by a sandboxed machine inherit its policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SandboxPolicy {
+    allowed: u8,
+}
+
+impl Default for SandboxPolicy {
+    /// Allows every capability.
+    fn default() -> Self {
+        SandboxPolicy::allow_all()
+    }
+}
+
+impl SandboxPolicy {
+    /// Allows every capability, i.e. no sandbox at all.
+    pub fn allow_all() -> Self {
+        SandboxPolicy {
+            allowed: Capability::ALL.iter().fold(0, |bits, c| bits | c.bit()),
+        }
+    }
+
+    /// Denies every capability. Allow those needed with
+    /// [`SandboxPolicy::allow`].
+    pub fn deny_all() -> Self {
+        SandboxPolicy { allowed: 0 }
+    }
+
+    pub fn allow(mut self, capability: Capability) -> Self {
+        self.allowed |= capability.bit();
+        self
+    }
+
+    pub fn deny(mut self, capability: Capability) -> Self {
+        self.allowed &= !capability.bit();
+        self
+    }
+
+    #[inline]
+    pub fn allows(&self, capability: Capability) -> bool {
+        self.allowed & capability.bit() != 0
+    }
+}
+
+impl Machine {
+    /// Raises a permission error from the system call at `p` unless
+    /// the sandbox allows `capability`.
+    #[inline]
+    pub(super) fn check_capability(&mut self, capability: Capability) -> CallResult {
+        if self.machine_st.sandbox.allows(capability) {
+            return Ok(());
+        }
+
+        let (name, arity) = self.code[self.machine_st.p].to_name_and_arity();
+        let err = self.machine_st.permission_error(
+            Permission::Call,
+            atom!("sandboxed"),
+            capability.as_atom(),
+        );
+
+        Err(self.machine_st.error_form(err, functor_stub(name, arity)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::machine::config::MachineConfig;
+    use crate::machine::parsed_results::{LeafAnswer, Term};
+    use crate::machine::sandbox::*;
+
+    fn sandboxed_machine(policy: SandboxPolicy) -> Machine {
+        Machine::new(MachineConfig::in_memory().with_sandbox(policy))
+    }
+
+    fn denied(machine: &mut Machine, query: &str) -> Option<Capability> {
+        match machine.query(query).next() {
+            Some(Err(err)) => err.sandbox_violation(),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn policies_allow_what_they_list() {
+        let policy = SandboxPolicy::deny_all().allow(Capability::Environment);
+
+        assert!(policy.allows(Capability::Environment));
+        assert!(!policy.allows(Capability::FileSystem));
+        assert!(SandboxPolicy::default().allows(Capability::Shell));
+        assert!(!SandboxPolicy::allow_all()
+            .deny(Capability::Shell)
+            .allows(Capability::Shell));
+    }
+
+    #[test]
+    fn denied_system_calls_raise_permission_errors() {
+        let mut machine = sandboxed_machine(SandboxPolicy::deny_all());
+
+        for library in ["files", "os", "sockets"] {
+            let answer = machine
+                .query(format!("use_module(library({library}))."))
+                .next();
+            assert!(matches!(answer, Some(Ok(_))));
+        }
+
+        assert_eq!(
+            denied(&mut machine, "open('sandbox_test.txt', write, _)."),
+            Some(Capability::FileSystem)
+        );
+        assert_eq!(
+            denied(&mut machine, "file_exists(\"Cargo.toml\")."),
+            Some(Capability::FileSystem)
+        );
+        assert_eq!(
+            denied(&mut machine, "getenv(\"HOME\", _)."),
+            Some(Capability::Environment)
+        );
+        assert_eq!(
+            denied(&mut machine, "shell(\"true\")."),
+            Some(Capability::Shell)
+        );
+        assert_eq!(
+            denied(&mut machine, "process_create(\"true\", [], [])."),
+            Some(Capability::Shell)
+        );
+        assert_eq!(
+            denied(&mut machine, "socket_client_open(localhost:1, _, [])."),
+            Some(Capability::Network)
+        );
+
+        let answer = machine
+            .query("catch(open(f, read, _), error(E, _), true).")
+            .next();
+        assert!(
+            matches!(answer, Some(Ok(LeafAnswer::LeafAnswer { bindings, .. }))
+            if bindings["E"] == Term::compound("permission_error", [
+                Term::atom("call"),
+                Term::atom("sandboxed"),
+                Term::atom("file_system"),
+            ]))
+        );
+    }
+
+    #[test]
+    fn system_calls_are_checked_whoever_calls_them() {
+        let mut machine = sandboxed_machine(SandboxPolicy::deny_all());
+
+        machine.consult_module_string("user", "run(Command) :- '$shell'(Command, _).".to_string());
+
+        assert_eq!(
+            denied(&mut machine, "run(\"true\")."),
+            Some(Capability::Shell)
+        );
+    }
+
+    #[test]
+    fn sandboxes_allow_pure_computation_and_memory_streams() {
+        let mut machine = sandboxed_machine(SandboxPolicy::deny_all());
+
+        for library in ["lists", "charsio"] {
+            let answer = machine
+                .query(format!("use_module(library({library}))."))
+                .next();
+            assert!(matches!(answer, Some(Ok(_))));
+        }
+
+        let answer = machine
+            .query("read_from_chars(\"[1, 2, 3].\", L), sum_list(L, S), write_term_to_chars(S, [], Cs).")
+            .next();
+        assert!(
+            matches!(answer, Some(Ok(LeafAnswer::LeafAnswer { bindings, .. }))
+            if bindings["Cs"] == Term::string("6"))
+        );
+
+        let mut machine =
+            sandboxed_machine(SandboxPolicy::deny_all().allow(Capability::Environment));
+
+        let answer = machine.query("use_module(library(os)).").next();
+        assert!(matches!(answer, Some(Ok(_))));
+
+        let answer = machine
+            .query("setenv(\"SANDBOX_TEST\", \"1\"), getenv(\"SANDBOX_TEST\", V).")
+            .next();
+        assert!(matches!(answer, Some(Ok(_))));
+    }
+}
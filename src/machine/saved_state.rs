@@ -0,0 +1,1058 @@
+//! Saved states: snapshots of the code area, indices and flags of a
+//! machine, written by `qsave_program/2` and restored without
+//! consulting any source.
+//!
+//! A state shares its encoding of instructions and constants with
+//! `.qlf` files, but names code indices by number rather than by
+//! predicate, as it holds all of them.
+
+use crate::arena::*;
+use crate::atom_table::*;
+use crate::forms::*;
+use crate::instructions::*;
+use crate::machine::clause_locations::*;
+use crate::machine::config::MachineConfig;
+use crate::machine::flags::*;
+use crate::machine::gc::GCState;
+use crate::machine::limits::LimitsState;
+use crate::machine::loader::CompilationTarget;
+use crate::machine::machine_errors::*;
+use crate::machine::machine_indices::*;
+use crate::machine::machine_state::*;
+use crate::machine::parsed_results::Term;
+use crate::machine::qlf::*;
+use crate::machine::streams::Stream;
+use crate::machine::{user_streams, CallResult, Machine};
+use crate::parser::ast::*;
+use crate::parser::decimal::Decimal;
+use crate::types::*;
+
+use fxhash::FxBuildHasher;
+use indexmap::IndexMap;
+
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const STATE_MAGIC: &[u8; 8] = b"SCRYSAV\0";
+
+/// The version of the saved state format, bumped whenever its layout
+/// changes. Its header has the instruction fingerprint of `.qlf` files
+/// too, as the code is saved the same way.
+pub(crate) const STATE_VERSION: usize = 3;
+
+// a stand-alone executable is the scryer-prolog executable followed
+// by a state, its length as a little-endian u64, and this.
+const TRAILER_MAGIC: &[u8; 8] = b"SCRYEXE\0";
+const TRAILER_LEN: u64 = 16;
+
+/// The reasons a saved state can't be restored.
+#[derive(Debug)]
+pub enum SavedStateError {
+    /// The state file couldn't be read.
+    Io(PathBuf, io::Error),
+    /// The state wasn't saved by this build of scryer-prolog, or isn't
+    /// a saved state at all.
+    Incompatible,
+    /// The state is truncated or otherwise corrupt.
+    Corrupt,
+}
+
+impl fmt::Display for SavedStateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SavedStateError::Io(path, err) => {
+                write!(f, "could not read saved state {}: {}", path.display(), err)
+            }
+            SavedStateError::Incompatible => {
+                write!(f, "not a saved state of this version of scryer-prolog")
+            }
+            SavedStateError::Corrupt => write!(f, "the saved state is corrupt"),
+        }
+    }
+}
+
+impl Error for SavedStateError {}
+
+impl From<QlfError> for SavedStateError {
+    fn from(err: QlfError) -> Self {
+        match err {
+            QlfError::BadHeader => SavedStateError::Incompatible,
+            _ => SavedStateError::Corrupt,
+        }
+    }
+}
+
+// the state of a decoded saved state, whose code indices are
+// allocated before its code.
+struct StateContext<'a> {
+    atom_tbl: &'a Arc<AtomTable>,
+    arena: &'a mut Arena,
+    code_indices: Vec<CodeIndex>,
+}
+
+impl QlfContext for StateContext<'_> {
+    fn atom(&mut self, name: &str) -> Atom {
+        AtomTable::build_with(self.atom_tbl, name)
+    }
+
+    fn arena(&mut self) -> &mut Arena {
+        self.arena
+    }
+
+    fn read_code_index(&mut self, dec: &mut QlfDecoder) -> Result<CodeIndex, QlfError> {
+        let id = dec.read_usize()?;
+
+        self.code_indices
+            .get(id)
+            .copied()
+            .ok_or(QlfError::UnresolvedCodeIndex)
+    }
+}
+
+impl QlfCode for IndexPtr {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        let tag = match self.tag() {
+            IndexPtrTag::DynamicUndefined => 0,
+            IndexPtrTag::DynamicIndex => 1,
+            IndexPtrTag::Index => 2,
+            IndexPtrTag::Undefined => 3,
+        };
+
+        enc.write_u8(tag);
+        enc.write_usize(self.p() as usize);
+
+        Ok(())
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, _ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        let tag = dec.read_u8()?;
+        let p = dec.read_usize()?;
+
+        match tag {
+            0 => Ok(IndexPtr::dynamic_undefined()),
+            1 => Ok(IndexPtr::dynamic_index(p)),
+            2 => Ok(IndexPtr::index(p)),
+            3 => Ok(IndexPtr::undefined()),
+            _ => Err(QlfError::UnknownTag),
+        }
+    }
+}
+
+impl QlfCode for Fixity {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        enc.write_u8(match self {
+            Fixity::In => 0,
+            Fixity::Post => 1,
+            Fixity::Pre => 2,
+        });
+
+        Ok(())
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, _ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        match dec.read_u8()? {
+            0 => Ok(Fixity::In),
+            1 => Ok(Fixity::Post),
+            2 => Ok(Fixity::Pre),
+            _ => Err(QlfError::UnknownTag),
+        }
+    }
+}
+
+impl QlfCode for OpDesc {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        enc.write_usize(self.get_prec() as usize);
+        enc.write_u8(self.get_spec());
+
+        Ok(())
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, _ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        let prec = u16::try_from(dec.read_usize()?).map_err(|_| QlfError::InvalidNumber)?;
+        let spec = dec.read_u8()?;
+
+        Ok(OpDesc::build_with(prec, spec))
+    }
+}
+
+impl QlfCode for OpDecl {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        self.op_desc.qlf_encode(enc)?;
+        self.name.qlf_encode(enc)
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        let op_desc = OpDesc::qlf_decode(dec, ctx)?;
+        let name = Atom::qlf_decode(dec, ctx)?;
+
+        Ok(OpDecl::new(op_desc, name))
+    }
+}
+
+impl QlfCode for MetaSpec {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        match *self {
+            MetaSpec::Minus => enc.write_u8(0),
+            MetaSpec::Plus => enc.write_u8(1),
+            MetaSpec::Either => enc.write_u8(2),
+            MetaSpec::Colon => enc.write_u8(3),
+            MetaSpec::RequiresExpansionWithArgument(n) => {
+                enc.write_u8(4);
+                enc.write_usize(n);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, _ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        match dec.read_u8()? {
+            0 => Ok(MetaSpec::Minus),
+            1 => Ok(MetaSpec::Plus),
+            2 => Ok(MetaSpec::Either),
+            3 => Ok(MetaSpec::Colon),
+            4 => Ok(MetaSpec::RequiresExpansionWithArgument(dec.read_usize()?)),
+            _ => Err(QlfError::UnknownTag),
+        }
+    }
+}
+
+impl QlfCode for CompilationTarget {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        match self {
+            CompilationTarget::Module(module_name) => {
+                enc.write_u8(0);
+                module_name.qlf_encode(enc)
+            }
+            CompilationTarget::User => {
+                enc.write_u8(1);
+                Ok(())
+            }
+        }
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        match dec.read_u8()? {
+            0 => Ok(CompilationTarget::Module(Atom::qlf_decode(dec, ctx)?)),
+            1 => Ok(CompilationTarget::User),
+            _ => Err(QlfError::UnknownTag),
+        }
+    }
+}
+
+impl QlfCode for OptArgIndexKey {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        match self {
+            OptArgIndexKey::Literal(arg, loc, lit, alts) => {
+                enc.write_u8(0);
+                enc.write_usize(*arg);
+                enc.write_usize(*loc);
+                lit.qlf_encode(enc)?;
+                alts.qlf_encode(enc)
+            }
+            OptArgIndexKey::List(arg, loc) => {
+                enc.write_u8(1);
+                enc.write_usize(*arg);
+                enc.write_usize(*loc);
+                Ok(())
+            }
+            OptArgIndexKey::None => {
+                enc.write_u8(2);
+                Ok(())
+            }
+            OptArgIndexKey::Structure(arg, loc, name, arity) => {
+                enc.write_u8(3);
+                enc.write_usize(*arg);
+                enc.write_usize(*loc);
+                name.qlf_encode(enc)?;
+                enc.write_usize(*arity);
+                Ok(())
+            }
+        }
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        match dec.read_u8()? {
+            0 => {
+                let arg = dec.read_usize()?;
+                let loc = dec.read_usize()?;
+                let lit = Literal::qlf_decode(dec, ctx)?;
+                let alts = Vec::<Literal>::qlf_decode(dec, ctx)?;
+
+                Ok(OptArgIndexKey::Literal(arg, loc, lit, alts))
+            }
+            1 => {
+                let arg = dec.read_usize()?;
+                let loc = dec.read_usize()?;
+
+                Ok(OptArgIndexKey::List(arg, loc))
+            }
+            2 => Ok(OptArgIndexKey::None),
+            3 => {
+                let arg = dec.read_usize()?;
+                let loc = dec.read_usize()?;
+                let name = Atom::qlf_decode(dec, ctx)?;
+                let arity = dec.read_usize()?;
+
+                Ok(OptArgIndexKey::Structure(arg, loc, name, arity))
+            }
+            _ => Err(QlfError::UnknownTag),
+        }
+    }
+}
+
+impl QlfCode for ClauseIndexInfo {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        enc.write_usize(self.clause_start);
+        self.opt_arg_index_key.qlf_encode(enc)
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        let mut info = ClauseIndexInfo::new(dec.read_usize()?);
+        info.opt_arg_index_key = OptArgIndexKey::qlf_decode(dec, ctx)?;
+
+        Ok(info)
+    }
+}
+
+impl QlfCode for LocalPredicateSkeleton {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        self.is_discontiguous.qlf_encode(enc)?;
+        self.is_dynamic.qlf_encode(enc)?;
+        self.is_multifile.qlf_encode(enc)?;
+        self.clause_clause_locs.qlf_encode(enc)?;
+        self.clause_assert_margin.qlf_encode(enc)?;
+        self.retracted_dynamic_clauses.qlf_encode(enc)
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        let mut skeleton = LocalPredicateSkeleton::new();
+
+        skeleton.is_discontiguous = bool::qlf_decode(dec, ctx)?;
+        skeleton.is_dynamic = bool::qlf_decode(dec, ctx)?;
+        skeleton.is_multifile = bool::qlf_decode(dec, ctx)?;
+        skeleton.clause_clause_locs = QlfCode::qlf_decode(dec, ctx)?;
+        skeleton.clause_assert_margin = usize::qlf_decode(dec, ctx)?;
+        skeleton.retracted_dynamic_clauses = QlfCode::qlf_decode(dec, ctx)?;
+
+        Ok(skeleton)
+    }
+}
+
+impl QlfCode for PredicateSkeleton {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        self.core.qlf_encode(enc)?;
+        self.clauses.qlf_encode(enc)
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        let mut skeleton = PredicateSkeleton::new();
+
+        skeleton.core = LocalPredicateSkeleton::qlf_decode(dec, ctx)?;
+        skeleton.clauses = QlfCode::qlf_decode(dec, ctx)?;
+
+        Ok(skeleton)
+    }
+}
+
+impl QlfCode for ModuleExport {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        match self {
+            ModuleExport::OpDecl(op_decl) => {
+                enc.write_u8(0);
+                op_decl.qlf_encode(enc)
+            }
+            ModuleExport::PredicateKey(key) => {
+                enc.write_u8(1);
+                key.qlf_encode(enc)
+            }
+        }
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        match dec.read_u8()? {
+            0 => Ok(ModuleExport::OpDecl(OpDecl::qlf_decode(dec, ctx)?)),
+            1 => Ok(ModuleExport::PredicateKey(QlfCode::qlf_decode(dec, ctx)?)),
+            _ => Err(QlfError::UnknownTag),
+        }
+    }
+}
+
+impl QlfCode for ListingSource {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        match self {
+            ListingSource::DynamicallyGenerated => enc.write_u8(0),
+            ListingSource::File(name, path) => {
+                enc.write_u8(1);
+                name.qlf_encode(enc)?;
+                enc.write_str(&path.to_string_lossy());
+            }
+            ListingSource::User => enc.write_u8(2),
+        }
+
+        Ok(())
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        match dec.read_u8()? {
+            0 => Ok(ListingSource::DynamicallyGenerated),
+            1 => {
+                let name = Atom::qlf_decode(dec, ctx)?;
+                let path = PathBuf::from(dec.read_str()?);
+
+                Ok(ListingSource::from_file_and_path(name, path))
+            }
+            2 => Ok(ListingSource::User),
+            _ => Err(QlfError::UnknownTag),
+        }
+    }
+}
+
+impl QlfCode for Module {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        self.module_decl.name.qlf_encode(enc)?;
+        self.module_decl.exports.qlf_encode(enc)?;
+        self.code_dir.qlf_encode(enc)?;
+        self.op_dir.qlf_encode(enc)?;
+        self.meta_predicates.qlf_encode(enc)?;
+        self.extensible_predicates.qlf_encode(enc)?;
+        self.local_extensible_predicates.qlf_encode(enc)?;
+        self.listing_src.qlf_encode(enc)
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        let module_decl = ModuleDecl {
+            name: Atom::qlf_decode(dec, ctx)?,
+            exports: QlfCode::qlf_decode(dec, ctx)?,
+        };
+
+        let code_dir = QlfCode::qlf_decode(dec, ctx)?;
+        let op_dir = QlfCode::qlf_decode(dec, ctx)?;
+        let meta_predicates = QlfCode::qlf_decode(dec, ctx)?;
+        let extensible_predicates = QlfCode::qlf_decode(dec, ctx)?;
+        let local_extensible_predicates = QlfCode::qlf_decode(dec, ctx)?;
+        let listing_src = ListingSource::qlf_decode(dec, ctx)?;
+
+        Ok(Module {
+            module_decl,
+            code_dir,
+            op_dir,
+            meta_predicates,
+            extensible_predicates,
+            local_extensible_predicates,
+            listing_src,
+        })
+    }
+}
+
+impl QlfCode for ClauseLocations {
+    fn qlf_encode(&self, enc: &mut QlfEncoder) -> Result<(), QlfError> {
+        let clauses: Vec<_> = self.iter().collect();
+
+        enc.write_usize(clauses.len());
+
+        for (code, location) in clauses {
+            enc.write_usize(code.start);
+            enc.write_usize(code.end);
+            location.module_name.qlf_encode(enc)?;
+            location.key.qlf_encode(enc)?;
+            enc.write_usize(location.clause_num);
+            location.file.qlf_encode(enc)?;
+            enc.write_usize(location.line);
+        }
+
+        Ok(())
+    }
+
+    fn qlf_decode(dec: &mut QlfDecoder, ctx: &mut dyn QlfContext) -> Result<Self, QlfError> {
+        let mut clause_locations = ClauseLocations::default();
+
+        for _ in 0..dec.read_usize()? {
+            let code = dec.read_usize()?..dec.read_usize()?;
+            let location = ClauseLocation {
+                module_name: Atom::qlf_decode(dec, ctx)?,
+                key: QlfCode::qlf_decode(dec, ctx)?,
+                clause_num: dec.read_usize()?,
+                file: Atom::qlf_decode(dec, ctx)?,
+                line: dec.read_usize()?,
+            };
+
+            clause_locations.insert(code, location);
+        }
+
+        Ok(clause_locations)
+    }
+}
+
+fn encode_term(enc: &mut QlfEncoder, term: &Term) {
+    match term {
+        Term::Integer(n) => {
+            enc.write_u8(0);
+            enc.write_str(&n.to_string());
+        }
+        Term::Rational(r) => {
+            enc.write_u8(1);
+            enc.write_str(&r.to_string());
+        }
+        Term::Float(f) => {
+            enc.write_u8(2);
+            enc.write_f64(f.into_inner());
+        }
+        Term::Atom(name) => {
+            enc.write_u8(3);
+            enc.write_str(name);
+        }
+        Term::String(string) => {
+            enc.write_u8(4);
+            enc.write_str(string);
+        }
+        Term::List(terms) => {
+            enc.write_u8(5);
+            enc.write_usize(terms.len());
+
+            for term in terms {
+                encode_term(enc, term);
+            }
+        }
+        Term::Compound(name, args) => {
+            enc.write_u8(6);
+            enc.write_str(name);
+            enc.write_usize(args.len());
+
+            for arg in args {
+                encode_term(enc, arg);
+            }
+        }
+        Term::Var(name) => {
+            enc.write_u8(7);
+            enc.write_str(name);
+        }
+        Term::Decimal(d) => {
+            enc.write_u8(8);
+            enc.write_str(&d.significand().to_string());
+            enc.write_usize(d.scale());
+        }
+    }
+}
+
+fn decode_term(dec: &mut QlfDecoder) -> Result<Term, QlfError> {
+    Ok(match dec.read_u8()? {
+        0 => Term::Integer(dec.read_str()?.parse().map_err(|_| QlfError::UnknownTag)?),
+        1 => Term::Rational(dec.read_str()?.parse().map_err(|_| QlfError::UnknownTag)?),
+        2 => Term::float(dec.read_f64()?),
+        3 => Term::atom(dec.read_str()?),
+        4 => Term::string(dec.read_str()?),
+        5 => {
+            let len = dec.read_usize()?;
+            Term::List(
+                (0..len)
+                    .map(|_| decode_term(dec))
+                    .collect::<Result<_, _>>()?,
+            )
+        }
+        6 => {
+            let name = dec.read_str()?.to_string();
+            let arity = dec.read_usize()?;
+
+            Term::Compound(
+                name,
+                (0..arity)
+                    .map(|_| decode_term(dec))
+                    .collect::<Result<_, _>>()?,
+            )
+        }
+        7 => Term::variable(dec.read_str()?),
+        8 => {
+            let significand = dec.read_str()?.parse().map_err(|_| QlfError::UnknownTag)?;
+            Term::Decimal(Decimal::new(significand, dec.read_usize()?))
+        }
+        _ => return Err(QlfError::UnknownTag),
+    })
+}
+
+// the flags are written whole, as create_prolog_flag/3 adds to them.
+fn encode_flags(enc: &mut QlfEncoder, machine_st: &MachineState) {
+    let flags: Vec<_> = machine_st.prolog_flags.iter().collect();
+
+    enc.write_usize(flags.len());
+
+    for (name, flag) in flags {
+        enc.write_str(&name.as_str());
+
+        match &flag.flag_type {
+            FlagType::Boolean => enc.write_u8(0),
+            FlagType::Atom => enc.write_u8(1),
+            FlagType::Integer => enc.write_u8(2),
+            FlagType::Float => enc.write_u8(3),
+            FlagType::Term => enc.write_u8(4),
+            FlagType::OneOf(atoms) => {
+                enc.write_u8(5);
+                enc.write_usize(atoms.len());
+
+                for atom in atoms {
+                    enc.write_str(&atom.as_str());
+                }
+            }
+            FlagType::Natural => enc.write_u8(6),
+        }
+
+        enc.write_u8(match flag.access {
+            FlagAccess::ReadOnly => 0,
+            FlagAccess::ReadWrite => 1,
+        });
+
+        enc.write_u8(flag.system as u8);
+
+        match &flag.scope {
+            FlagScope::Global => enc.write_u8(0),
+            FlagScope::Module { default, values } => {
+                enc.write_u8(1);
+                encode_term(enc, default);
+                enc.write_usize(values.len());
+
+                for (module_name, value) in values {
+                    enc.write_str(&module_name.as_str());
+                    encode_term(enc, value);
+                }
+            }
+        }
+
+        match &flag.value {
+            Some(value) => {
+                enc.write_u8(1);
+                encode_term(enc, value);
+            }
+            None => enc.write_u8(0),
+        }
+    }
+}
+
+fn decode_flags(dec: &mut QlfDecoder, machine_st: &mut MachineState) -> Result<(), QlfError> {
+    let atom_tbl = machine_st.atom_tbl.clone();
+    let atom = |name: &str| AtomTable::build_with(&atom_tbl, name);
+
+    for _ in 0..dec.read_usize()? {
+        let name = atom(dec.read_str()?);
+
+        let flag_type = match dec.read_u8()? {
+            0 => FlagType::Boolean,
+            1 => FlagType::Atom,
+            2 => FlagType::Integer,
+            3 => FlagType::Float,
+            4 => FlagType::Term,
+            5 => {
+                let len = dec.read_usize()?;
+                let mut atoms = Vec::with_capacity(len);
+
+                for _ in 0..len {
+                    atoms.push(atom(dec.read_str()?));
+                }
+
+                FlagType::OneOf(atoms)
+            }
+            6 => FlagType::Natural,
+            _ => return Err(QlfError::UnknownTag),
+        };
+
+        let access = match dec.read_u8()? {
+            0 => FlagAccess::ReadOnly,
+            1 => FlagAccess::ReadWrite,
+            _ => return Err(QlfError::UnknownTag),
+        };
+
+        let system = dec.read_u8()? != 0;
+
+        let scope = match dec.read_u8()? {
+            0 => FlagScope::Global,
+            1 => {
+                let default = decode_term(dec)?;
+                let mut values = IndexMap::new();
+
+                for _ in 0..dec.read_usize()? {
+                    let module_name = atom(dec.read_str()?);
+                    values.insert(module_name, decode_term(dec)?);
+                }
+
+                FlagScope::Module { default, values }
+            }
+            _ => return Err(QlfError::UnknownTag),
+        };
+
+        let value = match dec.read_u8()? {
+            0 => None,
+            1 => Some(decode_term(dec)?),
+            _ => return Err(QlfError::UnknownTag),
+        };
+
+        machine_st.prolog_flags.insert(
+            name,
+            PrologFlag {
+                flag_type,
+                access,
+                scope,
+                system,
+                value,
+            },
+        );
+    }
+
+    Ok(())
+}
+
+// the length of the state ending `file`, a stand-alone executable of
+// `file_len` bytes whose last bytes are `trailer`.
+fn embedded_state_len(trailer: &[u8], file_len: u64) -> Option<u64> {
+    let (len, magic) = trailer.split_at(8);
+
+    if magic != TRAILER_MAGIC {
+        return None;
+    }
+
+    let len = u64::from_le_bytes(len.try_into().ok()?);
+    (len <= file_len - TRAILER_LEN).then_some(len)
+}
+
+fn embedded_state(exe: &Path) -> io::Result<Option<Vec<u8>>> {
+    let mut file = fs::File::open(exe)?;
+    let file_len = file.metadata()?.len();
+
+    if file_len < TRAILER_LEN {
+        return Ok(None);
+    }
+
+    let mut trailer = [0; TRAILER_LEN as usize];
+
+    file.seek(SeekFrom::End(-(TRAILER_LEN as i64)))?;
+    file.read_exact(&mut trailer)?;
+
+    let Some(len) = embedded_state_len(&trailer, file_len) else {
+        return Ok(None);
+    };
+
+    let mut state = vec![0; len as usize];
+
+    file.seek(SeekFrom::Start(file_len - TRAILER_LEN - len))?;
+    file.read_exact(&mut state)?;
+
+    Ok(Some(state))
+}
+
+// the running executable, without any state appended to it.
+fn executable_image() -> io::Result<Vec<u8>> {
+    let mut image = fs::read(env::current_exe()?)?;
+    let file_len = image.len() as u64;
+
+    if file_len >= TRAILER_LEN {
+        let trailer = &image[(file_len - TRAILER_LEN) as usize..];
+
+        if let Some(len) = embedded_state_len(trailer, file_len) {
+            image.truncate((file_len - TRAILER_LEN - len) as usize);
+        }
+    }
+
+    Ok(image)
+}
+
+/// The saved state scryer-prolog starts from: one appended to its
+/// executable by `qsave_program/2`, or else the file given by the
+/// `-x FILE` (or `--state FILE`) option, if any.
+pub fn startup_state() -> Result<Option<Vec<u8>>, SavedStateError> {
+    if let Ok(exe) = env::current_exe() {
+        if let Ok(Some(state)) = embedded_state(&exe) {
+            return Ok(Some(state));
+        }
+    }
+
+    let args: Vec<String> = env::args().skip(1).take_while(|arg| arg != "--").collect();
+
+    let path = args
+        .iter()
+        .position(|arg| arg == "-x" || arg == "--state")
+        .and_then(|i| args.get(i + 1));
+
+    match path {
+        Some(path) => fs::read(path)
+            .map(Some)
+            .map_err(|err| SavedStateError::Io(PathBuf::from(path), err)),
+        None => Ok(None),
+    }
+}
+
+impl Machine {
+    /// Restores a machine from a state saved by `qsave_program/2`,
+    /// with the streams and garbage collection threshold of `config`.
+    /// The toplevel of `config` is unused, as the state has its own.
+    ///
+    /// Global variables and streams aren't part of a state, and
+    /// foreign predicates must be registered again before they're
+    /// called.
+    pub fn restore(config: MachineConfig, state: &[u8]) -> Result<Machine, SavedStateError> {
+        let mut machine_st = MachineState::new();
+
+        machine_st.gc = GCState::new(config.gc_threshold);
+        machine_st.limits = LimitsState::new(config.limits);
+        machine_st.sandbox = config.sandbox;
+
+        let machine = Machine::from_state(machine_st, state, |arena| {
+            user_streams(&config.streams, arena)
+        })?;
+
+        Ok(machine)
+    }
+
+    // decodes `state` into a machine running on `machine_st`, with the
+    // user streams built by `streams`.
+    pub(super) fn from_state(
+        mut machine_st: MachineState,
+        state: &[u8],
+        streams: impl FnOnce(&mut Arena) -> (Stream, Stream, Stream),
+    ) -> Result<Machine, QlfError> {
+        machine_st.stack.allocate_and_frame(0);
+
+        let mut dec = QlfDecoder::new(state);
+
+        dec.read_header(STATE_MAGIC, STATE_VERSION)?;
+        decode_flags(&mut dec, &mut machine_st)?;
+
+        machine_st.global_clock = dec.read_usize()?;
+        machine_st.attr_var_init.verify_attrs_loc = dec.read_usize()?;
+
+        let atom_tbl = machine_st.atom_tbl.clone();
+        let mut ctx = StateContext {
+            atom_tbl: &atom_tbl,
+            arena: &mut machine_st.arena,
+            code_indices: vec![],
+        };
+
+        for _ in 0..dec.read_usize()? {
+            let ptr = IndexPtr::qlf_decode(&mut dec, &mut ctx)?;
+            let code_index = CodeIndex::new(ptr, ctx.arena);
+
+            ctx.code_indices.push(code_index);
+        }
+
+        let code = Code::qlf_decode(&mut dec, &mut ctx)?;
+        let mut indices = IndexStore::new();
+
+        indices.code_dir = QlfCode::qlf_decode(&mut dec, &mut ctx)?;
+        indices.extensible_predicates = QlfCode::qlf_decode(&mut dec, &mut ctx)?;
+        indices.local_extensible_predicates = QlfCode::qlf_decode(&mut dec, &mut ctx)?;
+        indices.goal_expansion_indices = QlfCode::qlf_decode(&mut dec, &mut ctx)?;
+        indices.meta_predicates = QlfCode::qlf_decode(&mut dec, &mut ctx)?;
+        indices.op_dir = QlfCode::qlf_decode(&mut dec, &mut ctx)?;
+        indices.modules = QlfCode::qlf_decode(&mut dec, &mut ctx)?;
+
+        let clause_locations = ClauseLocations::qlf_decode(&mut dec, &mut ctx)?;
+        machine_st.clause_locations = clause_locations;
+
+        let (user_input, user_output, user_error) = streams(&mut machine_st.arena);
+
+        let mut machine = Machine {
+            machine_st,
+            indices,
+            code,
+            user_input,
+            user_output,
+            user_error,
+            load_contexts: vec![],
+            #[cfg(feature = "ffi")]
+            foreign_function_table: Default::default(),
+            foreign_predicates: Default::default(),
+            tracer: Default::default(),
+            profiler: Default::default(),
+            shared_database: None,
+        };
+
+        machine.apply_prolog_flags();
+        machine.configure_streams();
+        Ok(machine)
+    }
+
+    pub(super) fn saved_state(&self) -> Result<Vec<u8>, QlfError> {
+        let mut body = QlfEncoder::new(CodeIndexNames::Numbered(IndexMap::default()));
+
+        self.code.qlf_encode(&mut body)?;
+        self.indices.code_dir.qlf_encode(&mut body)?;
+        self.indices.extensible_predicates.qlf_encode(&mut body)?;
+        self.indices
+            .local_extensible_predicates
+            .qlf_encode(&mut body)?;
+        self.indices.goal_expansion_indices.qlf_encode(&mut body)?;
+        self.indices.meta_predicates.qlf_encode(&mut body)?;
+        self.indices.op_dir.qlf_encode(&mut body)?;
+        self.indices.modules.qlf_encode(&mut body)?;
+        self.machine_st.clause_locations.qlf_encode(&mut body)?;
+
+        let code_indices: IndexMap<usize, CodeIndex, FxBuildHasher> = match std::mem::replace(
+            &mut body.code_index_names,
+            CodeIndexNames::Numbered(IndexMap::default()),
+        ) {
+            CodeIndexNames::Numbered(code_indices) => code_indices,
+            CodeIndexNames::Qualified(_) => unreachable!(),
+        };
+
+        let mut enc = QlfEncoder::new(CodeIndexNames::Numbered(IndexMap::default()));
+
+        enc.write_header(STATE_MAGIC, STATE_VERSION);
+        encode_flags(&mut enc, &self.machine_st);
+
+        enc.write_usize(self.machine_st.global_clock);
+        enc.write_usize(self.machine_st.attr_var_init.verify_attrs_loc);
+
+        // the code indices precede the code so they can be allocated
+        // before being read.
+        enc.write_usize(code_indices.len());
+
+        for code_index in code_indices.values() {
+            code_index.get().qlf_encode(&mut enc)?;
+        }
+
+        enc.bytes.extend(body.bytes);
+        Ok(enc.bytes)
+    }
+
+    // writes the state of the machine to the file of the first
+    // register, after a copy of the running executable if the second
+    // is true.
+    pub(crate) fn save_state(&mut self) -> CallResult {
+        let path = cell_as_atom!(self.deref_register(1));
+        let stand_alone = cell_as_atom!(self.deref_register(2)) == atom!("true");
+
+        let state = match self.saved_state() {
+            Ok(state) => state,
+            Err(_) => {
+                let err = self.machine_st.representation_error(RepFlag::Term);
+                let stub = functor_stub(atom!("qsave_program"), 2);
+
+                return Err(self.machine_st.error_form(err, stub));
+            }
+        };
+
+        let result = if stand_alone {
+            executable_image().and_then(|mut image| {
+                let len = state.len() as u64;
+
+                image.extend(state);
+                image.extend(len.to_le_bytes());
+                image.extend(TRAILER_MAGIC);
+
+                fs::write(&*path.as_str(), image)?;
+
+                let permissions = fs::metadata(env::current_exe()?)?.permissions();
+                fs::set_permissions(&*path.as_str(), permissions)
+            })
+        } else {
+            fs::write(&*path.as_str(), state)
+        };
+
+        if result.is_err() {
+            return Err(self.machine_st.open_permission_error(
+                atom_as_cell!(path),
+                atom!("qsave_program"),
+                2,
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::parsed_results::Term;
+    use crate::machine::LeafAnswer;
+    use crate::parser::dashu::Integer;
+
+    use maplit::btreemap;
+
+    #[test]
+    #[cfg_attr(miri, ignore = "blocked on streams.rs UB")]
+    fn restored_machine_runs_saved_program() {
+        let mut machine = Machine::new_lib();
+
+        machine.consult_module_string(
+            "facts",
+            String::from(
+                r#"
+                :- use_module(library(lists)).
+                :- dynamic(counter/1).
+                :- op(700, xfx, ===>).
+
+                counter(0).
+                big(123456789012345678901234567890).
+                rule(X ===> Y) :- member(X-Y, [a-1, b-2.5, c-"str"]).
+                "#,
+            ),
+        );
+
+        let answers: Vec<_> = machine
+            .query(
+                "retract(counter(_)), assertz(counter(1)), set_prolog_flag(double_quotes, atom), \
+                 create_prolog_flag(saved_level, 7, []).",
+            )
+            .collect();
+        assert_eq!(answers.len(), 1);
+
+        let state = machine.saved_state().unwrap();
+        let mut restored = Machine::restore(MachineConfig::in_memory(), &state).unwrap();
+
+        let answers: Vec<_> = restored
+            .query(
+                "counter(N), big(B), findall(Y, rule(_ ===> Y), Ys), X = \"dq\", \
+                 current_prolog_flag(saved_level, L).",
+            )
+            .collect();
+
+        assert_eq!(
+            answers,
+            [Ok(LeafAnswer::LeafAnswer {
+                bindings: btreemap! {
+                    "N".to_string() => Term::integer(1),
+                    "B".to_string() => Term::integer(
+                        "123456789012345678901234567890".parse::<Integer>().unwrap()
+                    ),
+                    "Ys".to_string() => Term::list([
+                        Term::integer(1),
+                        Term::float(2.5),
+                        Term::string("str"),
+                    ]),
+                    "X".to_string() => Term::atom("dq"),
+                    "L".to_string() => Term::integer(7),
+                },
+                residual_goals: vec![],
+            })]
+        );
+    }
+
+    #[test]
+    fn restoring_rejects_bad_states() {
+        let machine = Machine::new_lib();
+        let state = machine.saved_state().unwrap();
+
+        assert!(matches!(
+            Machine::restore(MachineConfig::in_memory(), b"not a state"),
+            Err(SavedStateError::Incompatible)
+        ));
+
+        assert!(matches!(
+            Machine::restore(MachineConfig::in_memory(), &state[..state.len() / 2]),
+            Err(SavedStateError::Corrupt)
+        ));
+
+        // a state written with other instructions has another
+        // fingerprint, which follows the magic and the version.
+        let mut state = state;
+        state[STATE_MAGIC.len() + 1] ^= 1;
+
+        assert!(matches!(
+            Machine::restore(MachineConfig::in_memory(), &state),
+            Err(SavedStateError::Incompatible)
+        ));
+    }
+}
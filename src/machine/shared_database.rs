@@ -0,0 +1,494 @@
+//! The dynamic database shared by the machines of a process.
+//!
+//! Each thread runs a machine of its own, with its own code area and
+//! indices, so that threads run in parallel. Clauses asserted,
+//! retracted or abolished by any of them are recorded in a log shared
+//! by all of them, under a lock, and every machine replays the updates
+//! it hasn't seen before it calls a dynamic or undefined predicate.
+//!
+//! A machine links to the log once it creates a thread, and a thread
+//! starts with a copy of the program of its creator at the generation
+//! the creator caught up to, so that it replays the updates logged
+//! after the copy and none before it.
+
+use crate::atom_table::*;
+use crate::forms::*;
+use crate::machine::heap::*;
+use crate::machine::loader::CompilationTarget;
+use crate::machine::machine_errors::*;
+use crate::machine::threads::PortableTerm;
+use crate::machine::{CallResult, Machine};
+use crate::parser::ast::*;
+use crate::types::*;
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+enum DatabaseUpdate {
+    Assert {
+        module_name: Atom,
+        key: PredicateKey,
+        // the asserted clause, as (Head :- Body).
+        clause: PortableTerm,
+        append_or_prepend: AppendOrPrepend,
+    },
+    Retract {
+        module_name: Atom,
+        key: PredicateKey,
+        target_pos: usize,
+    },
+    Abolish {
+        module_name: Atom,
+        key: PredicateKey,
+    },
+}
+
+impl DatabaseUpdate {
+    fn predicate(&self) -> (Atom, PredicateKey) {
+        match self {
+            DatabaseUpdate::Assert {
+                module_name, key, ..
+            }
+            | DatabaseUpdate::Retract {
+                module_name, key, ..
+            }
+            | DatabaseUpdate::Abolish { module_name, key } => (*module_name, *key),
+        }
+    }
+
+    // the position of the clause at `target_pos` of the predicate once
+    // this update is applied, or `None` if the update removes it.
+    fn shift(&self, module_name: Atom, key: PredicateKey, target_pos: usize) -> Option<usize> {
+        if self.predicate() != (module_name, key) {
+            return Some(target_pos);
+        }
+
+        match self {
+            DatabaseUpdate::Assert {
+                append_or_prepend: AppendOrPrepend::Prepend,
+                ..
+            } => Some(target_pos + 1),
+            DatabaseUpdate::Assert { .. } => Some(target_pos),
+            DatabaseUpdate::Retract {
+                target_pos: retracted_pos,
+                ..
+            } => match retracted_pos.cmp(&target_pos) {
+                std::cmp::Ordering::Less => Some(target_pos - 1),
+                std::cmp::Ordering::Equal => None,
+                std::cmp::Ordering::Greater => Some(target_pos),
+            },
+            DatabaseUpdate::Abolish { .. } => None,
+        }
+    }
+}
+
+struct LogState {
+    // the generation of the first update kept.
+    first: usize,
+    updates: VecDeque<DatabaseUpdate>,
+    // the generation each machine linked to the log has caught up to.
+    machines: HashMap<usize, usize>,
+    next_id: usize,
+}
+
+impl LogState {
+    #[inline]
+    fn end(&self) -> usize {
+        self.first + self.updates.len()
+    }
+
+    // drops the updates every machine has seen.
+    fn trim(&mut self) {
+        let seen = self.machines.values().copied().min().unwrap_or(self.end());
+
+        while self.first < seen {
+            self.updates.pop_front();
+            self.first += 1;
+        }
+    }
+
+    fn register(&mut self, generation: usize) -> usize {
+        let id = self.next_id;
+
+        self.next_id += 1;
+        self.machines.insert(id, generation);
+
+        id
+    }
+}
+
+struct DatabaseLog {
+    // the generation after the last update, read without the lock to
+    // tell whether a machine is behind.
+    generation: AtomicUsize,
+    state: Mutex<LogState>,
+}
+
+/// The link of a machine to the log of dynamic database updates.
+pub(crate) struct SharedDatabase {
+    log: Arc<DatabaseLog>,
+    id: usize,
+    generation: usize,
+}
+
+impl fmt::Debug for SharedDatabase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SharedDatabase")
+            .field("id", &self.id)
+            .field("generation", &self.generation)
+            .finish()
+    }
+}
+
+impl SharedDatabase {
+    fn new() -> Self {
+        let mut state = LogState {
+            first: 0,
+            updates: VecDeque::new(),
+            machines: HashMap::new(),
+            next_id: 0,
+        };
+
+        let id = state.register(0);
+
+        SharedDatabase {
+            log: Arc::new(DatabaseLog {
+                generation: AtomicUsize::new(0),
+                state: Mutex::new(state),
+            }),
+            id,
+            generation: 0,
+        }
+    }
+
+    // links another machine to the log at the generation of this one,
+    // which must be locked by `state`.
+    fn fork(&self, state: &mut LogState) -> Self {
+        SharedDatabase {
+            log: self.log.clone(),
+            id: state.register(self.generation),
+            generation: self.generation,
+        }
+    }
+
+    #[inline(always)]
+    fn is_current(&self) -> bool {
+        self.log.generation.load(Ordering::Acquire) == self.generation
+    }
+
+    fn record(&mut self, state: &mut LogState, update: DatabaseUpdate) {
+        state.updates.push_back(update);
+
+        self.generation = state.end();
+        state.machines.insert(self.id, self.generation);
+
+        self.log
+            .generation
+            .store(self.generation, Ordering::Release);
+    }
+
+    /// Passes the cells of the updates not yet seen by every machine to
+    /// `f`, as replaying them may bring their atoms back into use.
+    pub(super) fn for_each_pending_cell(&self, mut f: impl FnMut(HeapCellValue)) {
+        let state = self.log.state.lock().unwrap();
+
+        for update in &state.updates {
+            let (module_name, (name, _arity)) = update.predicate();
+
+            f(atom_as_cell!(module_name));
+            f(atom_as_cell!(name));
+
+            if let DatabaseUpdate::Assert { clause, .. } = update {
+                clause.cells().for_each(&mut f);
+            }
+        }
+    }
+}
+
+impl Drop for SharedDatabase {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.log.state.lock() {
+            state.machines.remove(&self.id);
+            state.trim();
+        }
+    }
+}
+
+impl Machine {
+    /// Replays the updates made to the dynamic database by other
+    /// machines since this one last caught up.
+    #[inline(always)]
+    pub(crate) fn sync_database(&mut self) {
+        if self
+            .shared_database
+            .as_ref()
+            .is_some_and(|database| !database.is_current())
+        {
+            self.with_database_log(|machine, database, state| machine.catch_up(database, state));
+        }
+    }
+
+    // calls `f` with the log locked. the link is taken out of the
+    // machine meanwhile, so that the updates `f` replays or makes
+    // aren't logged or synced again. returns `None` if the machine
+    // isn't linked to a log.
+    fn with_database_log<R>(
+        &mut self,
+        f: impl FnOnce(&mut Machine, &mut SharedDatabase, &mut LogState) -> R,
+    ) -> Option<R> {
+        let mut database = self.shared_database.take()?;
+        let log = database.log.clone();
+        let mut state = log.state.lock().unwrap();
+
+        let result = f(self, &mut database, &mut state);
+
+        drop(state);
+        self.shared_database = Some(database);
+
+        Some(result)
+    }
+
+    fn catch_up(&mut self, database: &mut SharedDatabase, state: &mut LogState) {
+        let start = database.generation - state.first;
+
+        for update in state.updates.range(start..) {
+            self.replay(update);
+        }
+
+        database.generation = state.end();
+        state.machines.insert(database.id, database.generation);
+        state.trim();
+    }
+
+    // applies an update logged by another machine. an update the
+    // program of this machine no longer admits, e.g. because it was
+    // loaded differently, is skipped.
+    fn replay(&mut self, update: &DatabaseUpdate) {
+        let registers = self.machine_st.registers;
+        let fail = self.machine_st.fail;
+
+        let result = match update {
+            DatabaseUpdate::Assert {
+                module_name,
+                clause,
+                append_or_prepend,
+                ..
+            } => {
+                let clause = clause.write_to_heap(&mut self.machine_st);
+                let clause = self.machine_st.store(self.machine_st.deref(clause));
+                let s = clause.get_value() as usize;
+
+                self.machine_st.registers[1] = atom_as_cell!(*module_name);
+                self.machine_st.registers[2] = self.machine_st.heap[s + 1];
+                self.machine_st.registers[3] = self.machine_st.heap[s + 2];
+
+                self.compile_assert(*append_or_prepend)
+            }
+            &DatabaseUpdate::Retract {
+                module_name,
+                key,
+                target_pos,
+            } => {
+                let compilation_target = match module_name {
+                    atom!("user") => CompilationTarget::User,
+                    _ => CompilationTarget::Module(module_name),
+                };
+
+                let retractable = self
+                    .indices
+                    .get_predicate_skeleton(&compilation_target, &key)
+                    .is_some_and(|skeleton| {
+                        skeleton.core.is_dynamic && target_pos < skeleton.clauses.len()
+                    });
+
+                if retractable {
+                    self.machine_st.registers[1] = atom_as_cell!(key.0);
+                    self.machine_st.registers[2] =
+                        fixnum_as_cell!(Fixnum::build_with(key.1 as i64));
+                    self.machine_st.registers[3] =
+                        fixnum_as_cell!(Fixnum::build_with(target_pos as i64));
+                    self.machine_st.registers[4] = atom_as_cell!(module_name);
+
+                    self.retract_clause()
+                } else {
+                    Ok(())
+                }
+            }
+            &DatabaseUpdate::Abolish { module_name, key } => {
+                if self.indices.is_dynamic_predicate(module_name, key) {
+                    self.machine_st.registers[1] = atom_as_cell!(module_name);
+                    self.machine_st.registers[2] = atom_as_cell!(key.0);
+                    self.machine_st.registers[3] =
+                        fixnum_as_cell!(Fixnum::build_with(key.1 as i64));
+
+                    self.abolish_clause()
+                } else {
+                    Ok(())
+                }
+            }
+        };
+
+        // the error was raised by the machine that made the update,
+        // if at all.
+        drop(result);
+
+        self.machine_st.registers = registers;
+        self.machine_st.fail = fail;
+    }
+
+    /// Calls `f` with the dynamic database of this machine caught up
+    /// and locked, e.g. to copy its program to a new machine, which is
+    /// linked to the log at the same generation.
+    pub(super) fn fork_database<R>(
+        &mut self,
+        f: impl FnOnce(&mut Machine) -> R,
+    ) -> (R, SharedDatabase) {
+        if self.shared_database.is_none() {
+            self.shared_database = Some(SharedDatabase::new());
+        }
+
+        self.with_database_log(|machine, database, state| {
+            machine.catch_up(database, state);
+            (f(machine), database.fork(state))
+        })
+        .unwrap()
+    }
+
+    pub(crate) fn database_assert(&mut self, append_or_prepend: AppendOrPrepend) -> CallResult {
+        if self.shared_database.is_none() {
+            return self.compile_assert(append_or_prepend);
+        }
+
+        let module_name = cell_as_atom!(self.deref_register(1));
+        let head = self.machine_st.registers[2];
+        let body = self.machine_st.registers[3];
+
+        let clause = functor!(atom!(":-"), [cell(head), cell(body)]);
+        let h = self.machine_st.heap.len();
+
+        self.machine_st.heap.extend(clause);
+
+        let clause = PortableTerm::new(&mut self.machine_st, str_loc_as_cell!(h)).map_err(
+            |(kind, culprit)| {
+                let stub = match append_or_prepend {
+                    AppendOrPrepend::Append => functor_stub(atom!("assertz"), 1),
+                    AppendOrPrepend::Prepend => functor_stub(atom!("asserta"), 1),
+                };
+
+                let err = self
+                    .machine_st
+                    .permission_error(Permission::Send, kind, culprit);
+
+                self.machine_st.error_form(err, stub)
+            },
+        )?;
+
+        self.with_database_log(|machine, database, state| {
+            machine.catch_up(database, state);
+            machine.compile_assert(append_or_prepend)?;
+
+            if machine.machine_st.fail {
+                return Ok(());
+            }
+
+            if let Some(key) = machine.machine_st.name_and_arity_from_heap(head) {
+                database.record(
+                    state,
+                    DatabaseUpdate::Assert {
+                        module_name,
+                        key,
+                        clause,
+                        append_or_prepend,
+                    },
+                );
+            }
+
+            Ok(())
+        })
+        .unwrap()
+    }
+
+    /// Retracts the clause at the position of the third register, as
+    /// found before catching up with the updates of other machines. It
+    /// fails if one of them removed the clause meanwhile.
+    pub(crate) fn database_retract(&mut self) -> CallResult {
+        if self.shared_database.is_none() {
+            return self.retract_clause();
+        }
+
+        let key = self
+            .machine_st
+            .read_predicate_key(self.machine_st.registers[1], self.machine_st.registers[2]);
+
+        let target_pos = match Number::try_from(self.deref_register(3)) {
+            Ok(Number::Fixnum(n)) => usize::try_from(n.get_num()).unwrap(),
+            _ => unreachable!(),
+        };
+
+        let module_name = cell_as_atom!(self.deref_register(4));
+
+        self.with_database_log(|machine, database, state| {
+            let start = database.generation - state.first;
+            let target_pos = state
+                .updates
+                .range(start..)
+                .try_fold(target_pos, |target_pos, update| {
+                    update.shift(module_name, key, target_pos)
+                });
+
+            let target_pos = match target_pos {
+                Some(target_pos) => target_pos,
+                None => {
+                    machine.machine_st.fail = true;
+                    return Ok(());
+                }
+            };
+
+            machine.catch_up(database, state);
+
+            machine.machine_st.registers[3] =
+                fixnum_as_cell!(Fixnum::build_with(target_pos as i64));
+            machine.retract_clause()?;
+
+            database.record(
+                state,
+                DatabaseUpdate::Retract {
+                    module_name,
+                    key,
+                    target_pos,
+                },
+            );
+
+            Ok(())
+        })
+        .unwrap()
+    }
+
+    pub(crate) fn database_abolish(&mut self) -> CallResult {
+        if self.shared_database.is_none() {
+            return self.abolish_clause();
+        }
+
+        let module_name = cell_as_atom!(self.deref_register(1));
+        let key = self
+            .machine_st
+            .read_predicate_key(self.machine_st.registers[2], self.machine_st.registers[3]);
+
+        self.with_database_log(|machine, database, state| {
+            machine.catch_up(database, state);
+
+            // another machine may have abolished the predicate first.
+            if !machine.indices.is_dynamic_predicate(module_name, key) {
+                return Ok(());
+            }
+
+            machine.abolish_clause()?;
+            database.record(state, DatabaseUpdate::Abolish { module_name, key });
+
+            Ok(())
+        })
+        .unwrap()
+    }
+}
@@ -28,6 +28,7 @@
 use std::net::{Shutdown, TcpStream};
 use std::ops::{Deref, DerefMut};
 use std::path::PathBuf;
+use std::process::{ChildStderr, ChildStdin, ChildStdout};
 use std::ptr;
 
 #[cfg(feature = "tls")]
@@ -107,6 +108,135 @@ pub(crate) fn as_atom(&self) -> Atom {
     }
 }
 
+/// The text encoding of a stream, which its characters are read from
+/// and written as. Binary streams are always `Octet`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Encoding {
+    Octet,
+    Ascii,
+    IsoLatin1,
+    Windows1252,
+    #[default]
+    Utf8,
+    Utf16BE,
+    Utf16LE,
+}
+
+// the characters of the bytes 0x80 to 0x9f in Windows-1252. The five
+// bytes it leaves undefined are read as the C1 controls they are in
+// ISO-8859-1.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20ac}', '\u{81}', '\u{201a}', '\u{192}', '\u{201e}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2c6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8d}', '\u{17d}', '\u{8f}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201c}', '\u{201d}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2dc}', '\u{2122}', '\u{161}', '\u{203a}', '\u{153}', '\u{9d}', '\u{17e}', '\u{178}',
+];
+
+impl Encoding {
+    #[inline]
+    pub(crate) fn as_atom(self) -> Atom {
+        match self {
+            Encoding::Octet => atom!("octet"),
+            Encoding::Ascii => atom!("ascii"),
+            Encoding::IsoLatin1 => atom!("iso_latin_1"),
+            Encoding::Windows1252 => atom!("windows_1252"),
+            Encoding::Utf8 => atom!("utf8"),
+            Encoding::Utf16BE => atom!("utf16be"),
+            Encoding::Utf16LE => atom!("utf16le"),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn from_atom(name: Atom) -> Option<Self> {
+        match name {
+            atom!("octet") => Some(Encoding::Octet),
+            atom!("ascii") => Some(Encoding::Ascii),
+            atom!("iso_latin_1") => Some(Encoding::IsoLatin1),
+            atom!("windows_1252") => Some(Encoding::Windows1252),
+            atom!("utf8") => Some(Encoding::Utf8),
+            atom!("utf16be") => Some(Encoding::Utf16BE),
+            atom!("utf16le") => Some(Encoding::Utf16LE),
+            _ => None,
+        }
+    }
+
+    /// The character of `byte` in the single byte encodings, if it
+    /// has one.
+    pub(crate) fn byte_char(self, byte: u8) -> Option<char> {
+        match self {
+            Encoding::Ascii if byte >= 0x80 => None,
+            Encoding::Windows1252 if (0x80..0xa0).contains(&byte) => {
+                Some(WINDOWS_1252_HIGH[byte as usize - 0x80])
+            }
+            _ => Some(byte as char),
+        }
+    }
+
+    /// The number of bytes `c` is written as.
+    pub(crate) fn encoded_len(self, c: char) -> usize {
+        match self {
+            Encoding::Utf8 => c.len_utf8(),
+            Encoding::Utf16BE | Encoding::Utf16LE => c.len_utf16() * 2,
+            _ => 1,
+        }
+    }
+
+    /// Appends the bytes of `c` to `bytes`. Characters the encoding
+    /// can't represent are written as `?`.
+    pub(crate) fn encode(self, c: char, bytes: &mut Vec<u8>) {
+        let byte = match self {
+            Encoding::Utf8 => {
+                bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                return;
+            }
+            Encoding::Utf16BE | Encoding::Utf16LE => {
+                for unit in c.encode_utf16(&mut [0; 2]) {
+                    if self == Encoding::Utf16BE {
+                        bytes.extend_from_slice(&unit.to_be_bytes());
+                    } else {
+                        bytes.extend_from_slice(&unit.to_le_bytes());
+                    }
+                }
+
+                return;
+            }
+            Encoding::Ascii if (c as u32) < 0x80 => Some(c as u8),
+            Encoding::Octet | Encoding::IsoLatin1 if (c as u32) < 0x100 => Some(c as u8),
+            Encoding::Windows1252 if (c as u32) < 0x80 || (0xa0..0x100).contains(&(c as u32)) => {
+                Some(c as u8)
+            }
+            Encoding::Windows1252 => WINDOWS_1252_HIGH
+                .iter()
+                .position(|&high| high == c)
+                .map(|idx| 0x80 + idx as u8),
+            _ => None,
+        };
+
+        bytes.push(byte.unwrap_or(b'?'));
+    }
+
+    /// The byte order mark of the encoding, if it has one.
+    pub(crate) fn bom(self) -> Option<&'static [u8]> {
+        match self {
+            Encoding::Utf8 => Some(&[0xef, 0xbb, 0xbf]),
+            Encoding::Utf16BE => Some(&[0xfe, 0xff]),
+            Encoding::Utf16LE => Some(&[0xff, 0xfe]),
+            _ => None,
+        }
+    }
+
+    /// The encoding of the byte order mark `bytes` start with, and
+    /// its length.
+    pub(crate) fn from_bom(bytes: &[u8]) -> Option<(Self, usize)> {
+        [Encoding::Utf8, Encoding::Utf16BE, Encoding::Utf16LE]
+            .into_iter()
+            .find_map(|encoding| {
+                let bom = encoding.bom()?;
+                bytes.starts_with(bom).then_some((encoding, bom.len()))
+            })
+    }
+}
+
 #[derive(Debug)]
 pub struct ByteStream(Cursor<Vec<u8>>);
 
@@ -343,6 +473,44 @@ fn drop(&mut self) {
     }
 }
 
+/// The read end of a pipe from the standard output or the standard
+/// error of a process started by `process_create/3`.
+#[derive(Debug)]
+pub enum PipeInputStream {
+    Stdout(ChildStdout),
+    Stderr(ChildStderr),
+}
+
+impl Read for PipeInputStream {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            PipeInputStream::Stdout(stdout) => stdout.read(buf),
+            PipeInputStream::Stderr(stderr) => stderr.read(buf),
+        }
+    }
+}
+
+/// The write end of a pipe to the standard input of a process started
+/// by `process_create/3`. Closing it signals the end of the input to
+/// the process.
+#[derive(Debug)]
+pub struct PipeOutputStream {
+    stdin: ChildStdin,
+}
+
+impl Write for PipeOutputStream {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.stdin.write(buf)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.stdin.flush()
+    }
+}
+
 #[derive(Debug)]
 pub struct StandardOutputStream {}
 
@@ -420,6 +588,13 @@ fn default() -> Self {
 pub struct StreamLayout<T> {
     pub options: StreamOptions,
     pub lines_read: usize,
+    /// The position in the stream that terms were last read up to.
+    pub read_position: ReadPosition,
+    /// The encoding characters are read from and written as.
+    pub encoding: Encoding,
+    /// Whether the stream began with a byte order mark, or was opened
+    /// to write one.
+    pub bom: bool,
     past_end_of_stream: bool,
     stream: T,
 }
@@ -430,6 +605,9 @@ pub fn new(stream: T) -> Self {
         Self {
             options: StreamOptions::default(),
             lines_read: 0,
+            read_position: ReadPosition::default(),
+            encoding: Encoding::default(),
+            bom: false,
             past_end_of_stream: false,
             stream,
         }
@@ -469,6 +647,8 @@ fn tag() -> ArenaHeaderTag {
 arena_allocated_impl_for_stream!(CharReader<InputFileStream>, InputFileStream);
 arena_allocated_impl_for_stream!(OutputFileStream, OutputFileStream);
 arena_allocated_impl_for_stream!(CharReader<NamedTcpStream>, NamedTcpStream);
+arena_allocated_impl_for_stream!(CharReader<PipeInputStream>, PipeInputStream);
+arena_allocated_impl_for_stream!(PipeOutputStream, PipeOutputStream);
 #[cfg(feature = "tls")]
 arena_allocated_impl_for_stream!(CharReader<NamedTlsStream>, NamedTlsStream);
 #[cfg(feature = "http")]
@@ -493,6 +673,8 @@ pub enum Stream {
     HttpRead(TypedArenaPtr<StreamLayout<CharReader<HttpReadStream>>>),
     #[cfg(feature = "http")]
     HttpWrite(TypedArenaPtr<StreamLayout<CharReader<HttpWriteStream>>>),
+    PipeInput(TypedArenaPtr<StreamLayout<CharReader<PipeInputStream>>>),
+    PipeOutput(TypedArenaPtr<StreamLayout<PipeOutputStream>>),
     Null(StreamOptions),
     Readline(TypedArenaPtr<StreamLayout<ReadlineStream>>),
     StandardOutput(TypedArenaPtr<StreamLayout<StandardOutputStream>>),
@@ -554,6 +736,10 @@ pub fn from_tag(tag: ArenaHeaderTag, ptr: *const u8) -> Self {
             ArenaHeaderTag::HttpReadStream => Stream::HttpRead(TypedArenaPtr::new(ptr as *mut _)),
             #[cfg(feature = "http")]
             ArenaHeaderTag::HttpWriteStream => Stream::HttpWrite(TypedArenaPtr::new(ptr as *mut _)),
+            ArenaHeaderTag::PipeInputStream => Stream::PipeInput(TypedArenaPtr::new(ptr as *mut _)),
+            ArenaHeaderTag::PipeOutputStream => {
+                Stream::PipeOutput(TypedArenaPtr::new(ptr as *mut _))
+            }
             ArenaHeaderTag::ReadlineStream => Stream::Readline(TypedArenaPtr::new(ptr as *mut _)),
             ArenaHeaderTag::StaticStringStream => {
                 Stream::StaticString(TypedArenaPtr::new(ptr as *mut _))
@@ -599,6 +785,8 @@ pub fn as_ptr(&self) -> *const ArenaHeader {
             Stream::HttpRead(ptr) => ptr.header_ptr(),
             #[cfg(feature = "http")]
             Stream::HttpWrite(ptr) => ptr.header_ptr(),
+            Stream::PipeInput(ptr) => ptr.header_ptr(),
+            Stream::PipeOutput(ptr) => ptr.header_ptr(),
             Stream::Null(_) => ptr::null(),
             Stream::Readline(ptr) => ptr.header_ptr(),
             Stream::StandardOutput(ptr) => ptr.header_ptr(),
@@ -619,6 +807,8 @@ pub fn options(&self) -> &StreamOptions {
             Stream::HttpRead(ref ptr) => &ptr.options,
             #[cfg(feature = "http")]
             Stream::HttpWrite(ref ptr) => &ptr.options,
+            Stream::PipeInput(ref ptr) => &ptr.options,
+            Stream::PipeOutput(ref ptr) => &ptr.options,
             Stream::Null(ref options) => options,
             Stream::Readline(ref ptr) => &ptr.options,
             Stream::StandardOutput(ref ptr) => &ptr.options,
@@ -639,6 +829,8 @@ pub fn options_mut(&mut self) -> &mut StreamOptions {
             Stream::HttpRead(ref mut ptr) => &mut ptr.options,
             #[cfg(feature = "http")]
             Stream::HttpWrite(ref mut ptr) => &mut ptr.options,
+            Stream::PipeInput(ref mut ptr) => &mut ptr.options,
+            Stream::PipeOutput(ref mut ptr) => &mut ptr.options,
             Stream::Null(ref mut options) => options,
             Stream::Readline(ref mut ptr) => &mut ptr.options,
             Stream::StandardOutput(ref mut ptr) => &mut ptr.options,
@@ -660,6 +852,8 @@ pub(crate) fn add_lines_read(&mut self, incr_num_lines_read: usize) {
             Stream::HttpRead(ptr) => ptr.lines_read += incr_num_lines_read,
             #[cfg(feature = "http")]
             Stream::HttpWrite(_) => {}
+            Stream::PipeInput(ptr) => ptr.lines_read += incr_num_lines_read,
+            Stream::PipeOutput(ptr) => ptr.lines_read += incr_num_lines_read,
             Stream::Null(_) => {}
             Stream::Readline(ptr) => ptr.lines_read += incr_num_lines_read,
             Stream::StandardOutput(ptr) => ptr.lines_read += incr_num_lines_read,
@@ -681,6 +875,8 @@ pub(crate) fn set_lines_read(&mut self, value: usize) {
             Stream::HttpRead(ptr) => ptr.lines_read = value,
             #[cfg(feature = "http")]
             Stream::HttpWrite(_) => {}
+            Stream::PipeInput(ptr) => ptr.lines_read = value,
+            Stream::PipeOutput(ptr) => ptr.lines_read = value,
             Stream::Null(_) => {}
             Stream::Readline(ptr) => ptr.lines_read = value,
             Stream::StandardOutput(ptr) => ptr.lines_read = value,
@@ -688,6 +884,52 @@ pub(crate) fn set_lines_read(&mut self, value: usize) {
         }
     }
 
+    #[inline]
+    pub(crate) fn set_read_position(&mut self, position: ReadPosition) {
+        match self {
+            Stream::Byte(ptr) => ptr.read_position = position,
+            Stream::InputFile(ptr) => ptr.read_position = position,
+            Stream::OutputFile(ptr) => ptr.read_position = position,
+            Stream::StaticString(ptr) => ptr.read_position = position,
+            Stream::NamedTcp(ptr) => ptr.read_position = position,
+            #[cfg(feature = "tls")]
+            Stream::NamedTls(ptr) => ptr.read_position = position,
+            #[cfg(feature = "http")]
+            Stream::HttpRead(ptr) => ptr.read_position = position,
+            #[cfg(feature = "http")]
+            Stream::HttpWrite(_) => {}
+            Stream::PipeInput(ptr) => ptr.read_position = position,
+            Stream::PipeOutput(ptr) => ptr.read_position = position,
+            Stream::Null(_) => {}
+            Stream::Readline(ptr) => ptr.read_position = position,
+            Stream::StandardOutput(ptr) => ptr.read_position = position,
+            Stream::StandardError(ptr) => ptr.read_position = position,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn read_position(&self) -> ReadPosition {
+        match self {
+            Stream::Byte(ptr) => ptr.read_position,
+            Stream::InputFile(ptr) => ptr.read_position,
+            Stream::OutputFile(ptr) => ptr.read_position,
+            Stream::StaticString(ptr) => ptr.read_position,
+            Stream::NamedTcp(ptr) => ptr.read_position,
+            #[cfg(feature = "tls")]
+            Stream::NamedTls(ptr) => ptr.read_position,
+            #[cfg(feature = "http")]
+            Stream::HttpRead(ptr) => ptr.read_position,
+            #[cfg(feature = "http")]
+            Stream::HttpWrite(_) => ReadPosition::default(),
+            Stream::PipeInput(ptr) => ptr.read_position,
+            Stream::PipeOutput(ptr) => ptr.read_position,
+            Stream::Null(_) => ReadPosition::default(),
+            Stream::Readline(ptr) => ptr.read_position,
+            Stream::StandardOutput(ptr) => ptr.read_position,
+            Stream::StandardError(ptr) => ptr.read_position,
+        }
+    }
+
     #[inline]
     pub(crate) fn lines_read(&self) -> usize {
         match self {
@@ -702,12 +944,173 @@ pub(crate) fn lines_read(&self) -> usize {
             Stream::HttpRead(ptr) => ptr.lines_read,
             #[cfg(feature = "http")]
             Stream::HttpWrite(_) => 0,
+            Stream::PipeInput(ptr) => ptr.lines_read,
+            Stream::PipeOutput(ptr) => ptr.lines_read,
             Stream::Null(_) => 0,
             Stream::Readline(ptr) => ptr.lines_read,
             Stream::StandardOutput(ptr) => ptr.lines_read,
             Stream::StandardError(ptr) => ptr.lines_read,
         }
     }
+
+    /// The encoding of the stream's characters.
+    #[inline]
+    pub(crate) fn encoding(&self) -> Encoding {
+        match self {
+            Stream::Byte(ptr) => ptr.encoding,
+            Stream::InputFile(ptr) => ptr.encoding,
+            Stream::OutputFile(ptr) => ptr.encoding,
+            Stream::StaticString(ptr) => ptr.encoding,
+            Stream::NamedTcp(ptr) => ptr.encoding,
+            #[cfg(feature = "tls")]
+            Stream::NamedTls(ptr) => ptr.encoding,
+            #[cfg(feature = "http")]
+            Stream::HttpRead(ptr) => ptr.encoding,
+            #[cfg(feature = "http")]
+            Stream::HttpWrite(ptr) => ptr.encoding,
+            Stream::PipeInput(ptr) => ptr.encoding,
+            Stream::PipeOutput(ptr) => ptr.encoding,
+            Stream::Null(_) => Encoding::Utf8,
+            Stream::Readline(ptr) => ptr.encoding,
+            Stream::StandardOutput(ptr) => ptr.encoding,
+            Stream::StandardError(ptr) => ptr.encoding,
+        }
+    }
+
+    /// Sets the encoding of the characters read and written after
+    /// those already read and written. The characters of binary
+    /// streams are read as bytes whatever the encoding is.
+    pub(crate) fn set_encoding(&mut self, encoding: Encoding) {
+        let decoding = match self.options().stream_type() {
+            StreamType::Text => encoding,
+            StreamType::Binary => Encoding::Utf8,
+        };
+
+        match self {
+            Stream::Byte(ptr) => {
+                ptr.encoding = encoding;
+                ptr.stream.set_encoding(decoding);
+            }
+            Stream::InputFile(ptr) => {
+                ptr.encoding = encoding;
+                ptr.stream.set_encoding(decoding);
+            }
+            Stream::OutputFile(ptr) => ptr.encoding = encoding,
+            Stream::StaticString(ptr) => ptr.encoding = encoding,
+            Stream::NamedTcp(ptr) => {
+                ptr.encoding = encoding;
+                ptr.stream.set_encoding(decoding);
+            }
+            #[cfg(feature = "tls")]
+            Stream::NamedTls(ptr) => {
+                ptr.encoding = encoding;
+                ptr.stream.set_encoding(decoding);
+            }
+            #[cfg(feature = "http")]
+            Stream::HttpRead(ptr) => {
+                ptr.encoding = encoding;
+                ptr.stream.set_encoding(decoding);
+            }
+            #[cfg(feature = "http")]
+            Stream::HttpWrite(ptr) => {
+                ptr.encoding = encoding;
+                ptr.stream.set_encoding(decoding);
+            }
+            Stream::PipeInput(ptr) => {
+                ptr.encoding = encoding;
+                ptr.stream.set_encoding(decoding);
+            }
+            Stream::PipeOutput(ptr) => ptr.encoding = encoding,
+            Stream::Null(_) => {}
+            Stream::Readline(ptr) => ptr.encoding = encoding,
+            Stream::StandardOutput(ptr) => ptr.encoding = encoding,
+            Stream::StandardError(ptr) => ptr.encoding = encoding,
+        }
+    }
+
+    /// Whether the stream has a byte order mark.
+    #[inline]
+    pub(crate) fn bom(&self) -> bool {
+        match self {
+            Stream::Byte(ptr) => ptr.bom,
+            Stream::InputFile(ptr) => ptr.bom,
+            Stream::OutputFile(ptr) => ptr.bom,
+            Stream::StaticString(ptr) => ptr.bom,
+            Stream::NamedTcp(ptr) => ptr.bom,
+            #[cfg(feature = "tls")]
+            Stream::NamedTls(ptr) => ptr.bom,
+            #[cfg(feature = "http")]
+            Stream::HttpRead(ptr) => ptr.bom,
+            #[cfg(feature = "http")]
+            Stream::HttpWrite(ptr) => ptr.bom,
+            Stream::PipeInput(ptr) => ptr.bom,
+            Stream::PipeOutput(ptr) => ptr.bom,
+            Stream::Null(_) => false,
+            Stream::Readline(ptr) => ptr.bom,
+            Stream::StandardOutput(ptr) => ptr.bom,
+            Stream::StandardError(ptr) => ptr.bom,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn set_bom(&mut self, bom: bool) {
+        match self {
+            Stream::Byte(ptr) => ptr.bom = bom,
+            Stream::InputFile(ptr) => ptr.bom = bom,
+            Stream::OutputFile(ptr) => ptr.bom = bom,
+            Stream::StaticString(ptr) => ptr.bom = bom,
+            Stream::NamedTcp(ptr) => ptr.bom = bom,
+            #[cfg(feature = "tls")]
+            Stream::NamedTls(ptr) => ptr.bom = bom,
+            #[cfg(feature = "http")]
+            Stream::HttpRead(ptr) => ptr.bom = bom,
+            #[cfg(feature = "http")]
+            Stream::HttpWrite(ptr) => ptr.bom = bom,
+            Stream::PipeInput(ptr) => ptr.bom = bom,
+            Stream::PipeOutput(ptr) => ptr.bom = bom,
+            Stream::Null(_) => {}
+            Stream::Readline(ptr) => ptr.bom = bom,
+            Stream::StandardOutput(ptr) => ptr.bom = bom,
+            Stream::StandardError(ptr) => ptr.bom = bom,
+        }
+    }
+
+    /// Reads the byte order mark an input file starts with, if it has
+    /// one, and sets the stream's encoding to the one it marks.
+    pub(crate) fn detect_bom(&mut self) -> std::io::Result<()> {
+        let found = match self {
+            Stream::InputFile(stream_layout) => {
+                let file = &mut stream_layout.stream.get_mut().file;
+                let mut bytes = Vec::with_capacity(3);
+
+                Read::by_ref(file).take(3).read_to_end(&mut bytes)?;
+
+                let found = Encoding::from_bom(&bytes);
+                file.seek(SeekFrom::Start(found.map_or(0, |(_, len)| len as u64)))?;
+
+                found
+            }
+            _ => None,
+        };
+
+        if let Some((encoding, _)) = found {
+            self.set_encoding(encoding);
+            self.set_bom(true);
+        }
+
+        Ok(())
+    }
+
+    /// Writes the byte order mark of the stream's encoding, if it has
+    /// one.
+    pub(crate) fn write_bom(&mut self) -> std::io::Result<()> {
+        if let Some(bom) = self.encoding().bom() {
+            self.write_all_bytes(bom)?;
+            self.set_bom(true);
+        }
+
+        Ok(())
+    }
 }
 
 impl CharRead for Stream {
@@ -719,6 +1122,7 @@ fn peek_char(&mut self) -> Option<std::io::Result<char>> {
             Stream::NamedTls(tls_stream) => (*tls_stream).peek_char(),
             #[cfg(feature = "http")]
             Stream::HttpRead(http_stream) => (*http_stream).peek_char(),
+            Stream::PipeInput(pipe) => (*pipe).peek_char(),
             Stream::Readline(rl_stream) => (*rl_stream).peek_char(),
             Stream::StaticString(src) => (*src).peek_char(),
             Stream::Byte(cursor) => (*cursor).peek_char(),
@@ -728,6 +1132,7 @@ fn peek_char(&mut self) -> Option<std::io::Result<char>> {
                 StreamError::ReadFromOutputStream,
             ))),
             Stream::OutputFile(_)
+            | Stream::PipeOutput(_)
             | Stream::StandardError(_)
             | Stream::StandardOutput(_)
             | Stream::Null(_) => Some(Err(std::io::Error::new(
@@ -745,6 +1150,7 @@ fn read_char(&mut self) -> Option<std::io::Result<char>> {
             Stream::NamedTls(tls_stream) => (*tls_stream).read_char(),
             #[cfg(feature = "http")]
             Stream::HttpRead(http_stream) => (*http_stream).read_char(),
+            Stream::PipeInput(pipe) => (*pipe).read_char(),
             Stream::Readline(rl_stream) => (*rl_stream).read_char(),
             Stream::StaticString(src) => (*src).read_char(),
             Stream::Byte(cursor) => (*cursor).read_char(),
@@ -754,6 +1160,7 @@ fn read_char(&mut self) -> Option<std::io::Result<char>> {
                 StreamError::ReadFromOutputStream,
             ))),
             Stream::OutputFile(_)
+            | Stream::PipeOutput(_)
             | Stream::StandardError(_)
             | Stream::StandardOutput(_)
             | Stream::Null(_) => Some(Err(std::io::Error::new(
@@ -771,12 +1178,14 @@ fn put_back_char(&mut self, c: char) {
             Stream::NamedTls(tls_stream) => tls_stream.put_back_char(c),
             #[cfg(feature = "http")]
             Stream::HttpRead(http_stream) => http_stream.put_back_char(c),
+            Stream::PipeInput(pipe) => pipe.put_back_char(c),
             Stream::Readline(rl_stream) => rl_stream.put_back_char(c),
             Stream::StaticString(src) => src.put_back_char(c),
             Stream::Byte(cursor) => cursor.put_back_char(c),
             #[cfg(feature = "http")]
             Stream::HttpWrite(_) => {}
             Stream::OutputFile(_)
+            | Stream::PipeOutput(_)
             | Stream::StandardError(_)
             | Stream::StandardOutput(_)
             | Stream::Null(_) => {}
@@ -791,12 +1200,14 @@ fn consume(&mut self, nread: usize) {
             Stream::NamedTls(ref mut tls_stream) => tls_stream.consume(nread),
             #[cfg(feature = "http")]
             Stream::HttpRead(ref mut http_stream) => http_stream.consume(nread),
+            Stream::PipeInput(ref mut pipe) => pipe.consume(nread),
             Stream::Readline(ref mut rl_stream) => rl_stream.consume(nread),
             Stream::StaticString(ref mut src) => src.consume(nread),
             Stream::Byte(ref mut cursor) => cursor.consume(nread),
             #[cfg(feature = "http")]
             Stream::HttpWrite(_) => {}
             Stream::OutputFile(_)
+            | Stream::PipeOutput(_)
             | Stream::StandardError(_)
             | Stream::StandardOutput(_)
             | Stream::Null(_) => {}
@@ -814,6 +1225,7 @@ fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
             Stream::NamedTls(tls_stream) => (*tls_stream).read(buf),
             #[cfg(feature = "http")]
             Stream::HttpRead(http_stream) => (*http_stream).read(buf),
+            Stream::PipeInput(pipe) => (*pipe).read(buf),
             Stream::Readline(rl_stream) => (*rl_stream).read(buf),
             Stream::StaticString(src) => (*src).read(buf),
             Stream::Byte(cursor) => (*cursor).read(buf),
@@ -823,6 +1235,7 @@ fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
                 StreamError::ReadFromOutputStream,
             )),
             Stream::OutputFile(_)
+            | Stream::PipeOutput(_)
             | Stream::StandardError(_)
             | Stream::StandardOutput(_)
             | Stream::Null(_) => Err(std::io::Error::new(
@@ -833,8 +1246,9 @@ fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
     }
 }
 
-impl Write for Stream {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+impl Stream {
+    // writes buf to the underlying stream as it is.
+    fn write_bytes(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         match self {
             Stream::OutputFile(ref mut file) => file.write(buf),
             Stream::NamedTcp(ref mut tcp_stream) => tcp_stream.get_mut().write(buf),
@@ -843,6 +1257,7 @@ fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
             Stream::Byte(ref mut cursor) => cursor.get_mut().write(buf),
             Stream::StandardOutput(stream) => stream.write(buf),
             Stream::StandardError(stream) => stream.write(buf),
+            Stream::PipeOutput(ref mut pipe) => pipe.write(buf),
             #[cfg(feature = "http")]
             Stream::HttpWrite(ref mut stream) => stream.get_mut().write(buf),
             #[cfg(feature = "http")]
@@ -853,6 +1268,7 @@ fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
             Stream::StaticString(_)
             | Stream::Readline(_)
             | Stream::InputFile(..)
+            | Stream::PipeInput(_)
             | Stream::Null(_) => Err(std::io::Error::new(
                 ErrorKind::PermissionDenied,
                 StreamError::WriteToInputStream,
@@ -860,6 +1276,51 @@ fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         }
     }
 
+    fn write_all_bytes(&mut self, mut buf: &[u8]) -> std::io::Result<()> {
+        while !buf.is_empty() {
+            match self.write_bytes(buf) {
+                Ok(0) => return Err(std::io::Error::from(ErrorKind::WriteZero)),
+                Ok(n) => buf = &buf[n..],
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let encoding = self.encoding();
+
+        if encoding == Encoding::Utf8 || self.options().stream_type() == StreamType::Binary {
+            return self.write_bytes(buf);
+        }
+
+        // buf holds UTF-8, which is transcoded up to any incomplete
+        // character at its end, left to the next write.
+        let text = match std::str::from_utf8(buf) {
+            Ok(text) => text,
+            Err(e) if e.valid_up_to() > 0 => std::str::from_utf8(&buf[..e.valid_up_to()]).unwrap(),
+            Err(_) => {
+                return Err(std::io::Error::new(
+                    ErrorKind::InvalidData,
+                    StreamError::InvalidUtf8,
+                ))
+            }
+        };
+
+        let mut bytes = Vec::with_capacity(text.len());
+
+        for c in text.chars() {
+            encoding.encode(c, &mut bytes);
+        }
+
+        self.write_all_bytes(&bytes)?;
+        Ok(text.len())
+    }
+
     fn flush(&mut self) -> std::io::Result<()> {
         match self {
             Stream::OutputFile(ref mut file) => file.stream.flush(),
@@ -869,6 +1330,7 @@ fn flush(&mut self) -> std::io::Result<()> {
             Stream::Byte(ref mut cursor) => cursor.stream.get_mut().flush(),
             Stream::StandardError(stream) => stream.stream.flush(),
             Stream::StandardOutput(stream) => stream.stream.flush(),
+            Stream::PipeOutput(ref mut pipe) => pipe.stream.flush(),
             #[cfg(feature = "http")]
             Stream::HttpWrite(ref mut stream) => stream.stream.get_mut().flush(),
             #[cfg(feature = "http")]
@@ -879,6 +1341,7 @@ fn flush(&mut self) -> std::io::Result<()> {
             Stream::StaticString(_)
             | Stream::Readline(_)
             | Stream::InputFile(_)
+            | Stream::PipeInput(_)
             | Stream::Null(_) => Err(std::io::Error::new(
                 ErrorKind::PermissionDenied,
                 StreamError::FlushToInputStream,
@@ -898,6 +1361,7 @@ enum StreamError {
     ReadFromOutputStream,
     WriteToInputStream,
     FlushToInputStream,
+    InvalidUtf8,
 }
 
 impl fmt::Display for StreamError {
@@ -924,6 +1388,9 @@ fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
             StreamError::FlushToInputStream => {
                 write!(f, "attempted to flush a read-only stream")
             }
+            StreamError::InvalidUtf8 => {
+                write!(f, "attempted to write invalid UTF-8 to a text stream")
+            }
         }
     }
 }
@@ -982,7 +1449,7 @@ pub(crate) fn position(&mut self) -> Option<(u64, usize)> {
             Stream::InputFile(file_stream) => file_stream.position(),
             #[cfg(feature = "tls")]
             Stream::NamedTls(..) => Some(0),
-            Stream::NamedTcp(..) | Stream::Readline(..) => Some(0),
+            Stream::NamedTcp(..) | Stream::PipeInput(..) | Stream::Readline(..) => Some(0),
             _ => None,
         };
 
@@ -1025,6 +1492,8 @@ pub(crate) fn past_end_of_stream(&self) -> bool {
             Stream::HttpRead(stream) => stream.past_end_of_stream,
             #[cfg(feature = "http")]
             Stream::HttpWrite(stream) => stream.past_end_of_stream,
+            Stream::PipeInput(stream) => stream.past_end_of_stream,
+            Stream::PipeOutput(stream) => stream.past_end_of_stream,
             Stream::Null(_) => false,
             Stream::Readline(stream) => stream.past_end_of_stream,
             Stream::StandardOutput(stream) => stream.past_end_of_stream,
@@ -1051,6 +1520,8 @@ pub(crate) fn set_past_end_of_stream(&mut self, value: bool) {
             Stream::HttpRead(stream) => stream.past_end_of_stream = value,
             #[cfg(feature = "http")]
             Stream::HttpWrite(stream) => stream.past_end_of_stream = value,
+            Stream::PipeInput(stream) => stream.past_end_of_stream = value,
+            Stream::PipeOutput(stream) => stream.past_end_of_stream = value,
             Stream::Null(_) => {}
             Stream::Readline(stream) => stream.past_end_of_stream = value,
             Stream::StandardOutput(stream) => stream.past_end_of_stream = value,
@@ -1132,6 +1603,21 @@ pub(crate) fn file_name(&self) -> Option<Atom> {
         }
     }
 
+    /// The atoms the stream refers to outside of the heap: its file
+    /// name or address, its URL and its alias.
+    pub(crate) fn atoms(&self) -> impl Iterator<Item = Atom> {
+        let url = match self {
+            #[cfg(feature = "http")]
+            Stream::HttpRead(http) => Some(http.stream.get_ref().url),
+            _ => None,
+        };
+
+        self.file_name()
+            .into_iter()
+            .chain(url)
+            .chain(self.options().get_alias())
+    }
+
     #[inline]
     pub(crate) fn mode(&self) -> Atom {
         match self {
@@ -1142,14 +1628,16 @@ pub(crate) fn mode(&self) -> Atom {
             Stream::Byte(_)
             | Stream::Readline(_)
             | Stream::StaticString(_)
-            | Stream::InputFile(..) => atom!("read"),
+            | Stream::InputFile(..)
+            | Stream::PipeInput(..) => atom!("read"),
             Stream::NamedTcp(..) => atom!("read_append"),
             Stream::OutputFile(file) if file.is_append => atom!("append"),
             #[cfg(feature = "http")]
             Stream::HttpWrite(_) => atom!("write"),
-            Stream::OutputFile(_) | Stream::StandardError(_) | Stream::StandardOutput(_) => {
-                atom!("write")
-            }
+            Stream::OutputFile(_)
+            | Stream::PipeOutput(_)
+            | Stream::StandardError(_)
+            | Stream::StandardOutput(_) => atom!("write"),
             Stream::Null(_) => atom!(""),
         }
     }
@@ -1235,6 +1723,30 @@ pub(crate) fn from_http_sender(
         ))
     }
 
+    #[inline]
+    pub(crate) fn from_child_stdout(stdout: ChildStdout, arena: &mut Arena) -> Self {
+        Stream::PipeInput(arena_alloc!(
+            StreamLayout::new(CharReader::new(PipeInputStream::Stdout(stdout))),
+            arena
+        ))
+    }
+
+    #[inline]
+    pub(crate) fn from_child_stderr(stderr: ChildStderr, arena: &mut Arena) -> Self {
+        Stream::PipeInput(arena_alloc!(
+            StreamLayout::new(CharReader::new(PipeInputStream::Stderr(stderr))),
+            arena
+        ))
+    }
+
+    #[inline]
+    pub(crate) fn from_child_stdin(stdin: ChildStdin, arena: &mut Arena) -> Self {
+        Stream::PipeOutput(arena_alloc!(
+            StreamLayout::new(PipeOutputStream { stdin }),
+            arena
+        ))
+    }
+
     #[inline]
     pub(crate) fn from_file_as_output(
         file_name: Atom,
@@ -1307,6 +1819,25 @@ pub(crate) fn close(&mut self) -> Result<(), std::io::Error> {
 
                 Ok(())
             }
+            Stream::PipeInput(mut pipe_stream) => {
+                // close the stream by dropping the inner pipe.
+                unsafe {
+                    pipe_stream.set_tag(ArenaHeaderTag::Dropped);
+                    std::ptr::drop_in_place(pipe_stream.inner_mut() as *mut PipeInputStream);
+                }
+
+                Ok(())
+            }
+            Stream::PipeOutput(mut pipe_stream) => {
+                // dropping the pipe is what signals the end of the
+                // input to the process.
+                unsafe {
+                    pipe_stream.set_tag(ArenaHeaderTag::Dropped);
+                    std::ptr::drop_in_place(&mut pipe_stream.stdin as *mut _);
+                }
+
+                Ok(())
+            }
             _ => Ok(()),
         }
     }
@@ -1327,6 +1858,7 @@ pub(crate) fn is_input_stream(&self) -> bool {
             | Stream::Byte(_)
             | Stream::Readline(_)
             | Stream::StaticString(_)
+            | Stream::PipeInput(..)
             | Stream::InputFile(..) => true,
             _ => false,
         }
@@ -1343,6 +1875,7 @@ pub(crate) fn is_output_stream(&self) -> bool {
             | Stream::StandardOutput(_)
             | Stream::NamedTcp(..)
             | Stream::Byte(_)
+            | Stream::PipeOutput(..)
             | Stream::OutputFile(..) => true,
             _ => false,
         }
@@ -1352,6 +1885,7 @@ pub(crate) fn is_output_stream(&self) -> bool {
     #[inline]
     pub(super) fn reset(&mut self) -> bool {
         self.set_lines_read(0);
+        self.set_read_position(ReadPosition::default());
         self.set_past_end_of_stream(false);
 
         match self {
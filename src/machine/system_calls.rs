@@ -1,7 +1,7 @@
 use crate::parser::ast::*;
 use crate::parser::parser::*;
 
-use dashu::integer::{Sign, UBig};
+use dashu::integer::UBig;
 use lazy_static::lazy_static;
 use num_order::NumOrd;
 
@@ -60,6 +60,7 @@
 use std::net::{TcpListener, TcpStream};
 use std::num::NonZeroU32;
 use std::ops::Sub;
+use std::path::PathBuf;
 use std::process;
 #[cfg(feature = "http")]
 use std::str::FromStr;
@@ -915,6 +916,9 @@ pub(crate) fn parse_number_from_string(
                         Ok(Term::Literal(_, Literal::Rational(n))) => {
                             self.unify_rational(n, nx);
                         }
+                        Ok(Term::Literal(_, Literal::Decimal(d))) => {
+                            self.unify_decimal(d, nx);
+                        }
                         Ok(Term::Literal(_, Literal::Float(n))) => {
                             self.unify_f64(n.as_ptr(), nx);
                         }
@@ -1277,9 +1281,12 @@ pub(crate) fn deref_register(&self, i: usize) -> HeapCellValue {
     pub(crate) fn fast_call(
         &mut self,
         arity: usize,
-        call_at_index: impl Fn(&mut Machine, Atom, usize, IndexPtr) -> CallResult,
+        call_at_index: impl Fn(&mut Machine, Atom, usize, CodeIndex) -> CallResult,
     ) -> CallResult {
         let arity = arity - 1;
+
+        self.sync_database();
+
         let (mut module_name, mut goal) = self
             .machine_st
             .strip_module(self.machine_st.registers[1], heap_loc_as_cell!(0));
@@ -1383,7 +1390,7 @@ pub(crate) fn fast_call(
             if !code_index.is_undefined() {
                 load_registers(&mut self.machine_st, goal, goal_arity);
                 self.machine_st.neck_cut();
-                return call_at_index(self, name, arity, code_index.get());
+                return call_at_index(self, name, arity, code_index);
             }
         }
 
@@ -2761,6 +2768,7 @@ pub(crate) fn number_to_chars(&mut self) {
                 // must be 1.
                 r.numerator().to_string()
             }
+            Ok(Number::Decimal(d)) => d.to_string(),
             _ => {
                 unreachable!()
             }
@@ -2776,6 +2784,7 @@ pub(crate) fn number_to_codes(&mut self) {
         let chs = self.machine_st.registers[2];
 
         let string = match Number::try_from(n) {
+            Ok(Number::Float(OrderedFloat(n))) if !n.is_finite() => fmt_float(n),
             Ok(Number::Float(OrderedFloat(n))) => {
                 format!("{0:<20?}", n)
             }
@@ -2787,6 +2796,7 @@ pub(crate) fn number_to_codes(&mut self) {
                 // denominator must be 1.
                 r.numerator().to_string()
             }
+            Ok(Number::Decimal(d)) => d.to_string(),
             _ => {
                 unreachable!()
             }
@@ -3763,6 +3773,8 @@ pub(crate) fn get_single_char(&mut self) -> CallResult {
 
     #[inline(always)]
     pub(crate) fn head_is_dynamic(&mut self) {
+        self.sync_database();
+
         let module_name = cell_as_atom!(self.deref_register(1));
 
         match self
@@ -3885,6 +3897,8 @@ pub(crate) fn lookup_db_ref(&mut self) {
 
     #[inline(always)]
     pub(crate) fn get_db_refs(&mut self) {
+        self.sync_database();
+
         let name_match: fn(Atom, Atom) -> bool;
         let arity_match: fn(usize, usize) -> bool;
 
@@ -4184,7 +4198,7 @@ fn write_op_functors_to_heap(
 
     #[inline(always)]
     pub(crate) fn maybe(&mut self) {
-        self.machine_st.fail = self.rng.gen();
+        self.machine_st.fail = self.machine_st.rng.gen();
     }
 
     #[cfg(not(target_arch = "wasm32"))]
@@ -4210,6 +4224,41 @@ pub(crate) fn cpu_now(&mut self) {
         self.machine_st.unify_f64(secs, self.deref_register(1));
     }
 
+    #[inline(always)]
+    pub(crate) fn unify_gc_statistics(&mut self) {
+        let stats = self.gc_statistics();
+
+        self.unify_fixnum_registers([
+            stats.collections as i64,
+            stats.reclaimed_cells as i64,
+            stats.time.as_millis() as i64,
+            stats.heap_cells as i64,
+        ]);
+    }
+
+    #[inline(always)]
+    pub(crate) fn unify_atom_gc_statistics(&mut self) {
+        let stats = self.gc_statistics();
+
+        self.unify_fixnum_registers([
+            stats.atom_collections as i64,
+            stats.reclaimed_atoms as i64,
+            stats.atom_time.as_millis() as i64,
+            stats.dynamic_atoms as i64,
+        ]);
+    }
+
+    fn unify_fixnum_registers<const N: usize>(&mut self, values: [i64; N]) {
+        for (idx, value) in values.into_iter().enumerate() {
+            let r = self.machine_st.registers[idx + 1];
+            self.machine_st.unify_fixnum(Fixnum::build_with(value), r);
+
+            if self.machine_st.fail {
+                return;
+            }
+        }
+    }
+
     #[inline(always)]
     pub(crate) fn det_length_rundown(&mut self) -> CallResult {
         let stub_gen = || functor_stub(atom!("length"), 2);
@@ -4518,7 +4567,9 @@ fn get_reader(body: impl Buf + Send + 'static) -> Box<dyn BufRead + Send> {
                 }
             });
 
-            let http_listener = HttpListener { incoming: rx };
+            let http_listener = HttpListener {
+                incoming: Arc::new(Mutex::new(rx)),
+            };
             let http_listener = arena_alloc!(http_listener, &mut self.machine_st.arena);
 
             let addr = self.deref_register(2);
@@ -4544,7 +4595,8 @@ pub(crate) fn http_accept(&mut self) -> CallResult {
         match_untyped_arena_ptr!(cons_ptr,
             (ArenaHeaderTag::HttpListener, http_listener) => {
             loop {
-                match http_listener.incoming.recv_timeout(std::time::Duration::from_millis(200)) {
+                let incoming = http_listener.incoming.lock().unwrap().recv_timeout(std::time::Duration::from_millis(200));
+                match incoming {
                 Ok(request) => {
                     let method_atom = match request.request_data.method {
                     Method::GET => atom!("get"),
@@ -5010,6 +5062,30 @@ pub(crate) fn open(&mut self) -> CallResult {
                     .stream_from_file_spec(file_spec, &mut self.indices, &options)?;
 
             *stream.options_mut() = options;
+
+            let bom = cell_as_atom!(self.deref_register(8));
+            let encoding = match options.stream_type() {
+                StreamType::Binary => Encoding::Octet,
+                StreamType::Text => {
+                    Encoding::from_atom(cell_as_atom!(self.deref_register(9))).unwrap_or_default()
+                }
+            };
+
+            stream.set_encoding(encoding);
+
+            // a text file opened for reading takes the encoding of
+            // its byte order mark unless bom(false) is given. errors
+            // are left to the first read.
+            if options.stream_type() == StreamType::Text {
+                if stream.is_input_stream() {
+                    if bom != atom!("false") {
+                        let _ = stream.detect_bom();
+                    }
+                } else if bom == atom!("true") {
+                    let _ = stream.write_bom();
+                }
+            }
+
             self.indices.streams.insert(stream);
 
             if let Some(alias) = stream.options().get_alias() {
@@ -5112,6 +5188,38 @@ pub(crate) fn set_stream_options(&mut self) -> CallResult {
         Ok(())
     }
 
+    #[inline(always)]
+    pub(crate) fn set_stream_encoding(&mut self) -> CallResult {
+        let mut stream = self.machine_st.get_stream_or_alias(
+            self.machine_st.registers[1],
+            &self.indices.stream_aliases,
+            atom!("set_stream"),
+            2,
+        )?;
+
+        let encoding = Encoding::from_atom(cell_as_atom!(self.deref_register(2))).unwrap();
+
+        // readline and string streams hold characters rather than
+        // bytes, so are always UTF-8.
+        if encoding != Encoding::Utf8
+            && matches!(
+                stream,
+                Stream::Readline(_) | Stream::StaticString(_) | Stream::Null(_)
+            )
+        {
+            return Err(self.machine_st.stream_permission_error(
+                Permission::Modify,
+                atom!("stream"),
+                stream,
+                atom!("set_stream"),
+                2,
+            ));
+        }
+
+        stream.set_encoding(encoding);
+        Ok(())
+    }
+
     #[inline(always)]
     pub(crate) fn truncate_if_no_lifted_heap_growth_diff(&mut self) {
         self.machine_st
@@ -5521,34 +5629,6 @@ pub(crate) fn get_lifted_heap_from_offset(&mut self) {
         }
     }
 
-    #[inline(always)]
-    pub(crate) fn get_double_quotes(&mut self) {
-        let a1 = self.deref_register(1);
-
-        self.machine_st.unify_atom(
-            match self.machine_st.flags.double_quotes {
-                DoubleQuotes::Chars => atom!("chars"),
-                DoubleQuotes::Atom => atom!("atom"),
-                DoubleQuotes::Codes => atom!("codes"),
-            },
-            a1,
-        );
-    }
-
-    #[inline(always)]
-    pub(crate) fn get_unknown(&mut self) {
-        let a1 = self.deref_register(1);
-
-        self.machine_st.unify_atom(
-            match self.machine_st.flags.unknown {
-                Unknown::Error => atom!("error"),
-                Unknown::Fail => atom!("fail"),
-                Unknown::Warn => atom!("warning"),
-            },
-            a1,
-        );
-    }
-
     #[inline(always)]
     pub(crate) fn get_scc_cleaner(&mut self) {
         let dest = self.machine_st.registers[1];
@@ -5687,6 +5767,8 @@ pub(crate) fn predicate_defined(&mut self) -> bool {
 
     #[inline(always)]
     pub(crate) fn no_such_predicate(&mut self) -> CallResult {
+        self.sync_database();
+
         let module_name = cell_as_atom!(self.deref_register(1));
 
         let head = self.deref_register(2);
@@ -5903,36 +5985,6 @@ pub(crate) fn set_output(&mut self) -> CallResult {
         Ok(())
     }
 
-    #[inline(always)]
-    pub(crate) fn set_double_quotes(&mut self) {
-        let atom = cell_as_atom!(self.deref_register(1));
-
-        self.machine_st.flags.double_quotes = match atom {
-            atom!("atom") => DoubleQuotes::Atom,
-            atom!("chars") => DoubleQuotes::Chars,
-            atom!("codes") => DoubleQuotes::Codes,
-            _ => {
-                self.machine_st.fail = true;
-                return;
-            }
-        };
-    }
-
-    #[inline(always)]
-    pub(crate) fn set_unknown(&mut self) {
-        let atom = cell_as_atom!(self.deref_register(1));
-
-        self.machine_st.flags.unknown = match atom {
-            atom!("error") => Unknown::Error,
-            atom!("fail") => Unknown::Fail,
-            atom!("warning") => Unknown::Warn,
-            _ => {
-                self.machine_st.fail = true;
-                return;
-            }
-        };
-    }
-
     #[inline(always)]
     pub(crate) fn inference_level(&mut self) {
         let a1 = self.deref_register(1);
@@ -6339,18 +6391,18 @@ pub(crate) fn set_seed(&mut self) {
             Ok(Number::Fixnum(n)) => {
                 let n: u64 = Integer::from(n).try_into().unwrap();
                 let rng: StdRng = SeedableRng::seed_from_u64(n);
-                self.rng = rng;
+                self.machine_st.rng = rng;
             }
             Ok(Number::Integer(n)) => {
                 let n: u64 = (&*n).try_into().unwrap();
                 let rng: StdRng = SeedableRng::seed_from_u64(n);
-                self.rng = rng;
+                self.machine_st.rng = rng;
             }
             Ok(Number::Rational(n)) => {
                 if n.denominator() == &UBig::ONE {
                     let n: u64 = n.numerator().try_into().unwrap();
                     let rng: StdRng = SeedableRng::seed_from_u64(n);
-                    self.rng = rng;
+                    self.machine_st.rng = rng;
                 }
             }
             _ => {
@@ -6872,6 +6924,12 @@ pub(crate) fn stream_property(&mut self) -> CallResult {
             atom!("type") => {
                 atom_as_cell!(stream.options().stream_type().as_property_atom())
             }
+            atom!("encoding") => atom_as_cell!(stream.encoding().as_atom()),
+            atom!("bom") => atom_as_cell!(if stream.bom() {
+                atom!("true")
+            } else {
+                atom!("false")
+            }),
             _ => {
                 unreachable!()
             }
@@ -7941,6 +7999,274 @@ fn command_result(
         };
     }
 
+    // the string of a chars list argument, which is empty if the
+    // argument is [].
+    fn process_string(&mut self, value: HeapCellValue) -> String {
+        let value = self.machine_st.store(self.machine_st.deref(value));
+
+        if value == empty_list_as_cell!() {
+            String::new()
+        } else {
+            self.machine_st
+                .value_to_str_like(value)
+                .unwrap()
+                .as_str()
+                .to_string()
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn process_create(&mut self) -> CallResult {
+        let stub_gen = || functor_stub(atom!("process_create"), 3);
+
+        let exe = self.deref_register(1);
+        let mut command = process::Command::new(self.process_string(exe));
+
+        for arg in self
+            .machine_st
+            .try_from_list(self.machine_st.registers[2], stub_gen)?
+        {
+            command.arg(self.process_string(arg));
+        }
+
+        // the environment is either env(Pairs), replacing that of
+        // the running system, or environment(Pairs), extending it.
+        let env = self.deref_register(3);
+
+        let (env_name, env_pairs) = read_heap_cell!(env,
+            (HeapCellValueTag::Str, s) => {
+                let name = cell_as_atom_cell!(self.machine_st.heap[s]).get_name();
+                (name, self.machine_st.heap[s + 1])
+            }
+            _ => {
+                unreachable!()
+            }
+        );
+
+        if env_name == atom!("env") {
+            command.env_clear();
+        }
+
+        for pair in self.machine_st.try_from_list(env_pairs, stub_gen)? {
+            let pair = self.machine_st.store(self.machine_st.deref(pair));
+
+            read_heap_cell!(pair,
+                (HeapCellValueTag::Str, s) => {
+                    let name = self.process_string(self.machine_st.heap[s + 1]);
+                    let value = self.process_string(self.machine_st.heap[s + 2]);
+
+                    command.env(name, value);
+                }
+                _ => {
+                    unreachable!()
+                }
+            );
+        }
+
+        let cwd = self.deref_register(4);
+        let cwd_path = if cwd != empty_list_as_cell!() {
+            let path = PathBuf::from(self.process_string(cwd));
+            command.current_dir(&path);
+            Some(path)
+        } else {
+            None
+        };
+
+        // each of the standard streams is std, null or pipe(S).
+        let mut pipes = [None; 3];
+
+        for (idx, pipe) in pipes.iter_mut().enumerate() {
+            let spec = self.deref_register(5 + idx);
+
+            let stdio = read_heap_cell!(spec,
+                (HeapCellValueTag::Atom, (name, _arity)) => {
+                    if name == atom!("null") {
+                        process::Stdio::null()
+                    } else {
+                        process::Stdio::inherit()
+                    }
+                }
+                (HeapCellValueTag::Str, s) => {
+                    *pipe = Some(self.machine_st.heap[s + 1]);
+                    process::Stdio::piped()
+                }
+                _ => {
+                    unreachable!()
+                }
+            );
+
+            match idx {
+                0 => command.stdin(stdio),
+                1 => command.stdout(stdio),
+                _ => command.stderr(stdio),
+            };
+        }
+
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            // a missing working directory is also reported as NotFound.
+            Err(_) if cwd_path.as_ref().is_some_and(|path| !path.is_dir()) => {
+                let err = self
+                    .machine_st
+                    .existence_error(ExistenceError::Directory(cwd));
+                return Err(self.machine_st.error_form(err, stub_gen()));
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                let err = self
+                    .machine_st
+                    .existence_error(ExistenceError::SourceSink(exe));
+                return Err(self.machine_st.error_form(err, stub_gen()));
+            }
+            Err(err) if err.kind() == ErrorKind::PermissionDenied => {
+                return Err(self
+                    .machine_st
+                    .open_permission_error(exe, atom!("process_create"), 3));
+            }
+            Err(err) => {
+                let err = self.machine_st.system_error(&err);
+                return Err(self.machine_st.error_form(err, stub_gen()));
+            }
+        };
+
+        let streams = [
+            child
+                .stdin
+                .take()
+                .map(|stdin| Stream::from_child_stdin(stdin, &mut self.machine_st.arena)),
+            child
+                .stdout
+                .take()
+                .map(|stdout| Stream::from_child_stdout(stdout, &mut self.machine_st.arena)),
+            child
+                .stderr
+                .take()
+                .map(|stderr| Stream::from_child_stderr(stderr, &mut self.machine_st.arena)),
+        ];
+
+        for (pipe, stream) in pipes.into_iter().zip(streams) {
+            if let (Some(pipe), Some(stream)) = (pipe, stream) {
+                self.indices.streams.insert(stream);
+                unify!(self.machine_st, pipe, stream_as_cell!(stream));
+            }
+        }
+
+        let pid = child.id();
+        self.indices.processes.insert(pid, child);
+
+        let pid = integer_as_cell!(Number::arena_from(pid, &mut self.machine_st.arena));
+        unify!(self.machine_st, pid, self.machine_st.registers[8]);
+
+        Ok(())
+    }
+
+    // the child process of the process id in register 1.
+    fn process_of_pid(&mut self, caller: Atom) -> Result<u32, MachineStub> {
+        let pid = self.deref_register(1);
+
+        if let Ok(Number::Fixnum(n)) = Number::try_from(pid) {
+            if let Ok(id) = u32::try_from(n.get_num()) {
+                if self.indices.processes.contains_key(&id) {
+                    return Ok(id);
+                }
+            }
+        }
+
+        let err = self
+            .machine_st
+            .existence_error(ExistenceError::Process(pid));
+        Err(self.machine_st.error_form(err, functor_stub(caller, 2)))
+    }
+
+    #[inline(always)]
+    pub(crate) fn process_wait(&mut self) -> CallResult {
+        let pid = self.process_of_pid(atom!("process_wait"))?;
+        let mut child = self.indices.processes.swap_remove(&pid).unwrap();
+
+        let status = match child.wait() {
+            Ok(status) => status,
+            Err(_) => {
+                self.machine_st.fail = true;
+                return Ok(());
+            }
+        };
+
+        #[cfg(unix)]
+        let signal = std::os::unix::process::ExitStatusExt::signal(&status);
+        #[cfg(not(unix))]
+        let signal: Option<i32> = None;
+
+        // the status is exit(Code), or killed(Signal) if the process
+        // was terminated by a signal.
+        let (name, code) = match (status.code(), signal) {
+            (Some(code), _) => (atom!("exit"), code),
+            (None, Some(signal)) => (atom!("killed"), signal),
+            (None, None) => (atom!("killed"), 0),
+        };
+
+        let h = self.machine_st.heap.len();
+
+        self.machine_st.heap.push(atom_as_cell!(name, 1));
+        self.machine_st
+            .heap
+            .push(fixnum_as_cell!(Fixnum::build_with(code as i64)));
+
+        unify!(
+            self.machine_st,
+            str_loc_as_cell!(h),
+            self.machine_st.registers[2]
+        );
+
+        Ok(())
+    }
+
+    #[inline(always)]
+    pub(crate) fn process_kill(&mut self) -> CallResult {
+        let pid = self.process_of_pid(atom!("process_kill"))?;
+
+        let signal = self.deref_register(2);
+
+        #[cfg(unix)]
+        let result = {
+            // os.pl admits only signal numbers in the range of c_int
+            // and the signal names below.
+            let signal = match Number::try_from(signal) {
+                Ok(Number::Fixnum(n)) => libc::c_int::try_from(n.get_num()).unwrap(),
+                Ok(_) => unreachable!(),
+                Err(_) => match cell_as_atom!(signal) {
+                    atom!("hup") => libc::SIGHUP,
+                    atom!("int") => libc::SIGINT,
+                    atom!("quit") => libc::SIGQUIT,
+                    atom!("kill") => libc::SIGKILL,
+                    atom!("term") => libc::SIGTERM,
+                    atom!("usr1") => libc::SIGUSR1,
+                    atom!("usr2") => libc::SIGUSR2,
+                    atom!("stop") => libc::SIGSTOP,
+                    atom!("cont") => libc::SIGCONT,
+                    _ => unreachable!(),
+                },
+            };
+
+            if unsafe { libc::kill(pid as libc::pid_t, signal) } == 0 {
+                Ok(())
+            } else {
+                Err(std::io::Error::last_os_error())
+            }
+        };
+
+        // other systems can only kill the process outright.
+        #[cfg(not(unix))]
+        let result = {
+            let _ = signal;
+            self.indices.processes.get_mut(&pid).unwrap().kill()
+        };
+
+        if result.is_err() {
+            self.machine_st.fail = true;
+        }
+
+        Ok(())
+    }
+
     #[inline(always)]
     pub(crate) fn chars_base64(&mut self) -> CallResult {
         let padding = cell_as_atom!(self.deref_register(3));
@@ -8047,7 +8373,13 @@ pub(crate) fn devour_whitespace(&mut self) -> CallResult {
 
         let mut parser = Parser::new(stream, &mut self.machine_st);
 
-        match devour_whitespace(&mut parser) {
+        parser.lexer.position = stream.read_position();
+
+        let result = devour_whitespace(&mut parser);
+
+        stream.set_read_position(parser.lexer.position);
+
+        match result {
             Ok(false) => {
                 // not at EOF.
                 stream.add_lines_read(parser.lines_read());
@@ -8067,40 +8399,6 @@ pub(crate) fn devour_whitespace(&mut self) -> CallResult {
         Ok(())
     }
 
-    #[inline(always)]
-    pub(crate) fn is_sto_enabled(&mut self) {
-        if self.machine_st.unify_fn as usize == MachineState::unify_with_occurs_check as usize {
-            self.machine_st
-                .unify_atom(atom!("true"), self.machine_st.registers[1]);
-        } else if self.machine_st.unify_fn as usize
-            == MachineState::unify_with_occurs_check_with_error as usize
-        {
-            self.machine_st
-                .unify_atom(atom!("error"), self.machine_st.registers[1]);
-        } else {
-            self.machine_st
-                .unify_atom(atom!("false"), self.machine_st.registers[1]);
-        }
-    }
-
-    #[inline(always)]
-    pub(crate) fn set_sto_as_unify(&mut self) {
-        self.machine_st.unify_fn = MachineState::unify_with_occurs_check;
-        self.machine_st.bind_fn = MachineState::bind_with_occurs_check_wrapper;
-    }
-
-    #[inline(always)]
-    pub(crate) fn set_nsto_as_unify(&mut self) {
-        self.machine_st.unify_fn = MachineState::unify;
-        self.machine_st.bind_fn = MachineState::bind;
-    }
-
-    #[inline(always)]
-    pub(crate) fn set_sto_with_error_as_unify(&mut self) {
-        self.machine_st.unify_fn = MachineState::unify_with_occurs_check_with_error;
-        self.machine_st.bind_fn = MachineState::bind_with_occurs_check_with_error_wrapper;
-    }
-
     #[inline(always)]
     pub(crate) fn home_directory(&mut self) {
         let path = match dirs_next::home_dir() {
@@ -8127,28 +8425,6 @@ pub(crate) fn home_directory(&mut self) {
     pub(crate) fn debug_hook(&mut self) {}
 
     #[inline(always)]
-    pub(crate) fn pop_count(&mut self) {
-        let number = self.deref_register(1);
-        let pop_count = integer_as_cell!(match Number::try_from(number) {
-            Ok(Number::Fixnum(n)) => {
-                Number::Fixnum(Fixnum::build_with(n.get_num().count_ones() as i64))
-            }
-            Ok(Number::Integer(n)) => {
-                let value: usize = if n.sign() == Sign::Positive {
-                    (*n).clone().into_parts().1.count_ones()
-                } else {
-                    0
-                };
-                Number::arena_from(value, &mut self.machine_st.arena)
-            }
-            _ => {
-                unreachable!()
-            }
-        });
-
-        unify!(self.machine_st, self.machine_st.registers[2], pop_count);
-    }
-
     pub(super) fn systemtime_to_timestamp(&mut self, system_time: SystemTime) -> Atom {
         let datetime: DateTime<Local> = system_time.into();
 
@@ -0,0 +1,1103 @@
+use crate::arena::*;
+use crate::atom_table::*;
+use crate::forms::*;
+use crate::machine::copier::*;
+use crate::machine::machine_errors::*;
+use crate::machine::machine_state::*;
+use crate::machine::parsed_results::Term;
+use crate::machine::Machine;
+use crate::parser::ast::*;
+use crate::parser::dashu::{Integer, Rational};
+use crate::parser::decimal::Decimal;
+use crate::types::*;
+
+use indexmap::{IndexMap, IndexSet};
+use std::collections::HashMap;
+
+/// A symbol of a term read in preorder. Two terms are variants of
+/// each other just if they read as the same symbols, their variables
+/// being numbered in order of first occurrence.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum TrieSymbol {
+    Var(usize),
+    Atom(Atom),
+    Functor(Atom, usize),
+    Char(char),
+    Fixnum(i64),
+    Float(u64),
+    Integer(Integer),
+    Rational(Rational),
+    Decimal(Decimal),
+    // a blob such as a stream, which is only ever a variant of itself.
+    Blob(HeapCellValue),
+}
+
+#[derive(Debug, Default)]
+struct TrieNode {
+    children: HashMap<TrieSymbol, usize>,
+    value: Option<usize>,
+}
+
+/// A trie mapping the symbols of terms to values, sharing the nodes
+/// of their common prefixes.
+#[derive(Debug)]
+pub(crate) struct Trie {
+    nodes: Vec<TrieNode>,
+}
+
+impl Default for Trie {
+    fn default() -> Self {
+        Trie {
+            nodes: vec![TrieNode::default()],
+        }
+    }
+}
+
+impl Trie {
+    pub(crate) fn get(&self, symbols: &[TrieSymbol]) -> Option<usize> {
+        let mut node = 0;
+
+        for symbol in symbols {
+            node = *self.nodes[node].children.get(symbol)?;
+        }
+
+        self.nodes[node].value
+    }
+
+    /// The value of `symbols`, adding the nodes missing for them.
+    pub(crate) fn entry(&mut self, symbols: Vec<TrieSymbol>) -> &mut Option<usize> {
+        let mut node = 0;
+
+        for symbol in symbols {
+            node = match self.nodes[node].children.get(&symbol) {
+                Some(&child) => child,
+                None => {
+                    let child = self.nodes.len();
+                    self.nodes.push(TrieNode::default());
+                    self.nodes[node].children.insert(symbol, child);
+                    child
+                }
+            };
+        }
+
+        &mut self.nodes[node].value
+    }
+
+    fn atoms(&self) -> impl Iterator<Item = Atom> + '_ {
+        self.nodes
+            .iter()
+            .flat_map(|node| node.children.keys())
+            .filter_map(|symbol| match symbol {
+                TrieSymbol::Atom(name) | TrieSymbol::Functor(name, _) => Some(*name),
+                _ => None,
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TableStatus {
+    Fresh,
+    // its evaluation is the frame of the evaluation stack at the
+    // depth.
+    Evaluating(usize),
+    // evaluated in the given pass of the leader of its strongly
+    // connected component, at the depth, without completing.
+    Incomplete { leader: usize, pass: u64 },
+    Complete,
+}
+
+/// A literal whose truth an answer was derived on without knowing it.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum Delay {
+    // the `tnot/1` of a table.
+    Negative(usize),
+    // a conditional answer of a table, by its index.
+    Positive(usize, usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AnswerState {
+    Unconditional,
+    // the delay lists of the derivations of the answer.
+    Conditional(Vec<Vec<Delay>>),
+    // all of its derivations were found false once its table and
+    // those it depends on were complete.
+    Deleted,
+}
+
+#[derive(Debug)]
+struct Answer {
+    term: Ball,
+    state: AnswerState,
+}
+
+#[derive(Debug)]
+struct Table {
+    key: Vec<TrieSymbol>,
+    variant: Ball,
+    // the arguments left out of the keys of the answers, which are
+    // aggregated by library(tabling) instead.
+    moded_args: Vec<usize>,
+    incremental: bool,
+    status: TableStatus,
+    answer_keys: Trie,
+    answers: Vec<Answer>,
+    // the incremental tables whose evaluation consumed this table.
+    dependents: IndexSet<usize>,
+}
+
+impl Table {
+    fn has_unconditional_answer(&self) -> bool {
+        self.answers
+            .iter()
+            .any(|answer| answer.state == AnswerState::Unconditional)
+    }
+
+    fn has_answer(&self) -> bool {
+        self.answers
+            .iter()
+            .any(|answer| answer.state != AnswerState::Deleted)
+    }
+}
+
+#[derive(Debug)]
+struct Frame {
+    table: usize,
+    // the least depth of a frame whose table the evaluation consumed
+    // answers from before it was complete.
+    leader: usize,
+    pass: u64,
+    // the number of changes to the tables at the start of the pass.
+    changes: u64,
+    // whether the pass consumed answers of a table that wasn't
+    // complete, so that a later pass may derive more answers.
+    recursive: bool,
+    // the tables completed along with this one if it's a leader.
+    members: IndexSet<usize>,
+}
+
+/// The tables of library(tabling). A tabled call is a variant of the
+/// call its table was created for, and its answers are those of the
+/// table once the table is evaluated.
+///
+/// Tables are evaluated to a fixpoint. The evaluation of a table
+/// consumes the answers found so far for the tables it calls that are
+/// still being evaluated, so a strongly connected component of tables
+/// is evaluated in passes, led by its outermost table, until a pass
+/// derives no new answers. The tables of the component are then
+/// complete together.
+///
+/// Answers derived from `tnot/1` of a table that isn't complete, or
+/// from conditional answers, are conditional on them. Once their
+/// component is complete, the conditions known to be true are
+/// dropped and the answers with a condition known to be false are
+/// deleted. The answers left conditional are undefined under the
+/// well-founded semantics.
+#[derive(Debug, Default)]
+pub(crate) struct Tables {
+    variants: Trie,
+    tables: IndexMap<usize, Table>,
+    next_id: usize,
+    stack: Vec<Frame>,
+    passes: u64,
+    changes: u64,
+    // the dynamic predicates declared incremental and the incremental
+    // tables whose evaluation called them.
+    incremental_dynamic: IndexSet<PredicateKey>,
+    dynamic_dependents: IndexMap<PredicateKey, IndexSet<usize>>,
+}
+
+impl Tables {
+    fn table(&self, id: usize) -> Option<&Table> {
+        self.tables.get(&id)
+    }
+
+    fn new_table(
+        &mut self,
+        key: Vec<TrieSymbol>,
+        variant: Ball,
+        moded_args: Vec<usize>,
+        incremental: bool,
+    ) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        *self.variants.entry(key.clone()) = Some(id);
+
+        self.tables.insert(
+            id,
+            Table {
+                key,
+                variant,
+                moded_args,
+                incremental,
+                status: TableStatus::Fresh,
+                answer_keys: Trie::default(),
+                answers: vec![],
+                dependents: IndexSet::new(),
+            },
+        );
+
+        id
+    }
+
+    fn remove_table(&mut self, id: usize) -> Option<Table> {
+        let table = self.tables.swap_remove(&id)?;
+        *self.variants.entry(table.key.clone()) = None;
+        Some(table)
+    }
+
+    // the evaluation at the top of the stack consumes answers of the
+    // table evaluated at `depth`, which it now depends on.
+    fn depend_on(&mut self, depth: usize) {
+        let top = self.stack.last_mut().unwrap();
+
+        top.leader = std::cmp::min(top.leader, depth);
+        top.recursive = true;
+    }
+
+    /// Returns true if the table `id` must be evaluated before its
+    /// answers are consumed, pushing a frame for its evaluation.
+    fn enter(&mut self, id: usize) -> bool {
+        if let Some(caller) = self.stack.last().map(|frame| frame.table) {
+            if caller != id && self.tables[&caller].incremental {
+                self.tables[&id].dependents.insert(caller);
+            }
+        }
+
+        match self.tables[&id].status {
+            TableStatus::Complete => false,
+            TableStatus::Evaluating(depth) => {
+                self.depend_on(depth);
+                false
+            }
+            TableStatus::Incomplete { leader, pass }
+                if self.stack.get(leader).map(|frame| frame.pass) == Some(pass) =>
+            {
+                // evaluated earlier in the current pass of its leader.
+                self.depend_on(leader);
+                false
+            }
+            TableStatus::Fresh | TableStatus::Incomplete { .. } => {
+                let depth = self.stack.len();
+
+                self.tables[&id].status = TableStatus::Evaluating(depth);
+                self.stack.push(Frame {
+                    table: id,
+                    leader: depth,
+                    pass: 0,
+                    changes: 0,
+                    recursive: false,
+                    members: IndexSet::new(),
+                });
+
+                true
+            }
+        }
+    }
+
+    fn begin_pass(&mut self) {
+        self.passes += 1;
+
+        let frame = self.stack.last_mut().unwrap();
+
+        frame.pass = self.passes;
+        frame.changes = self.changes;
+        frame.recursive = false;
+    }
+
+    /// Ends a pass of the evaluation at the top of the stack, returning
+    /// true if another pass is needed.
+    fn end_pass(&mut self) -> bool {
+        let depth = self.stack.len() - 1;
+        let frame = &self.stack[depth];
+
+        if frame.leader < depth {
+            let frame = self.stack.pop().unwrap();
+            let pass = self.stack[frame.leader].pass;
+
+            self.tables[&frame.table].status = TableStatus::Incomplete {
+                leader: frame.leader,
+                pass,
+            };
+
+            let parent = self.stack.last_mut().unwrap();
+
+            parent.leader = std::cmp::min(parent.leader, frame.leader);
+            parent.recursive = true;
+            parent.members.extend(frame.members);
+            parent.members.insert(frame.table);
+
+            false
+        } else if frame.recursive && frame.changes != self.changes {
+            true
+        } else {
+            let frame = self.stack.pop().unwrap();
+            let mut component: Vec<usize> = frame.members.into_iter().collect();
+
+            component.push(frame.table);
+            component.retain(|id| self.tables.contains_key(id));
+
+            for id in &component {
+                self.tables[id].status = TableStatus::Complete;
+            }
+
+            self.simplify(&component);
+            false
+        }
+    }
+
+    // the truth of `delay` if it's known.
+    fn delay_truth(&self, delay: &Delay) -> Option<bool> {
+        match *delay {
+            Delay::Negative(id) => {
+                let table = self.table(id)?;
+
+                if table.status != TableStatus::Complete {
+                    None
+                } else if table.has_unconditional_answer() {
+                    Some(false)
+                } else if !table.has_answer() {
+                    Some(true)
+                } else {
+                    None
+                }
+            }
+            Delay::Positive(id, idx) => match self.table(id)?.answers.get(idx)?.state {
+                AnswerState::Unconditional => Some(true),
+                AnswerState::Deleted => Some(false),
+                AnswerState::Conditional(_) => None,
+            },
+        }
+    }
+
+    // simplifies the conditional answers of a component that was just
+    // completed until no condition is known to be true or false.
+    fn simplify(&mut self, component: &[usize]) {
+        loop {
+            let mut simplified = false;
+
+            for id in component {
+                for idx in 0..self.tables[id].answers.len() {
+                    let delay_lists = match &self.tables[id].answers[idx].state {
+                        AnswerState::Conditional(delay_lists) => delay_lists.clone(),
+                        _ => continue,
+                    };
+
+                    let mut remaining = vec![];
+                    let mut state = None;
+
+                    'delay_lists: for delays in delay_lists.iter() {
+                        let mut unknown = vec![];
+
+                        for delay in delays {
+                            match self.delay_truth(delay) {
+                                Some(true) => {}
+                                Some(false) => continue 'delay_lists,
+                                None => unknown.push(delay.clone()),
+                            }
+                        }
+
+                        if unknown.is_empty() {
+                            state = Some(AnswerState::Unconditional);
+                            break;
+                        }
+
+                        remaining.push(unknown);
+                    }
+
+                    let state = state.unwrap_or(if remaining.is_empty() {
+                        AnswerState::Deleted
+                    } else {
+                        AnswerState::Conditional(remaining)
+                    });
+
+                    if state != AnswerState::Conditional(delay_lists) {
+                        self.tables[id].answers[idx].state = state;
+                        simplified = true;
+                    }
+                }
+            }
+
+            if !simplified {
+                break;
+            }
+        }
+    }
+
+    /// Drops the evaluation of table `id` and those it started, along
+    /// with the tables they evaluated, ahead of an exception.
+    fn abandon(&mut self, id: usize) {
+        let depth = match self.table(id).map(|table| table.status) {
+            Some(TableStatus::Evaluating(depth)) => depth,
+            _ => return,
+        };
+
+        for frame in self.stack.split_off(depth) {
+            self.remove_table(frame.table);
+
+            for id in frame.members {
+                self.remove_table(id);
+            }
+        }
+    }
+
+    fn abolish_all(&mut self) {
+        let ids: Vec<usize> = self
+            .tables
+            .iter()
+            .filter(|(_, table)| table.status == TableStatus::Complete)
+            .map(|(id, _)| *id)
+            .collect();
+
+        if ids.len() == self.tables.len() {
+            self.variants = Trie::default();
+            self.tables.clear();
+        } else {
+            for id in ids {
+                self.remove_table(id);
+            }
+        }
+
+        self.dynamic_dependents.clear();
+    }
+
+    /// Records the call of the dynamic predicate `key` by the
+    /// evaluation at the top of the stack if both are incremental.
+    #[inline]
+    pub(crate) fn record_dynamic_call(&mut self, key: PredicateKey) {
+        if self.incremental_dynamic.is_empty() {
+            return;
+        }
+
+        if let Some(frame) = self.stack.last() {
+            if self.tables[&frame.table].incremental && self.incremental_dynamic.contains(&key) {
+                self.dynamic_dependents
+                    .entry(key)
+                    .or_default()
+                    .insert(frame.table);
+            }
+        }
+    }
+
+    /// Removes the complete tables depending on the dynamic predicate
+    /// `key`, which was just changed, so that they're evaluated anew
+    /// when next called.
+    pub(crate) fn invalidate_dynamic(&mut self, key: &PredicateKey) {
+        let mut ids: Vec<usize> = match self.dynamic_dependents.swap_remove(key) {
+            Some(ids) => ids.into_iter().collect(),
+            None => return,
+        };
+
+        while let Some(id) = ids.pop() {
+            if self.table(id).map(|table| table.status) != Some(TableStatus::Complete) {
+                continue;
+            }
+
+            if let Some(table) = self.remove_table(id) {
+                ids.extend(table.dependents);
+            }
+        }
+    }
+
+    pub(crate) fn atoms(&self) -> impl Iterator<Item = Atom> + '_ {
+        self.variants
+            .atoms()
+            .chain(
+                self.tables
+                    .values()
+                    .flat_map(|table| table.answer_keys.atoms()),
+            )
+            .chain(self.incremental_dynamic.iter().map(|(name, _)| *name))
+    }
+
+    pub(crate) fn cells(&self) -> impl Iterator<Item = HeapCellValue> + '_ {
+        self.tables.values().flat_map(|table| {
+            table
+                .answers
+                .iter()
+                .flat_map(|answer| answer.term.stub.iter())
+                .chain(table.variant.stub.iter())
+                .copied()
+        })
+    }
+}
+
+impl MachineState {
+    /// The symbols of `term` read in preorder, leaving out the
+    /// arguments of `term` at `skipped_args`.
+    pub(crate) fn variant_symbols(
+        &self,
+        term: HeapCellValue,
+        skipped_args: &[usize],
+    ) -> Vec<TrieSymbol> {
+        let mut symbols = vec![];
+        let mut vars = HashMap::new();
+        let mut cells = vec![term];
+        let mut skipped_args = skipped_args;
+
+        while let Some(cell) = cells.pop() {
+            let cell = self.store(self.deref(cell));
+
+            read_heap_cell!(cell,
+                (HeapCellValueTag::Str, s) => {
+                    let (name, arity) = cell_as_atom_cell!(self.heap[s]).get_name_and_arity();
+
+                    if arity == 0 {
+                        symbols.push(TrieSymbol::Atom(name));
+                    } else {
+                        symbols.push(TrieSymbol::Functor(name, arity));
+                    }
+
+                    for idx in (1..arity + 1).rev() {
+                        if !skipped_args.contains(&idx) {
+                            cells.push(self.heap[s + idx]);
+                        }
+                    }
+                }
+                (HeapCellValueTag::Lis, l) => {
+                    symbols.push(TrieSymbol::Functor(atom!("."), 2));
+                    cells.push(self.heap[l + 1]);
+                    cells.push(self.heap[l]);
+                }
+                (HeapCellValueTag::PStrLoc, h) => {
+                    let (focus, offset) = pstr_loc_and_offset(&self.heap, h);
+                    let offset = offset.get_num() as usize;
+
+                    read_heap_cell!(self.heap[focus],
+                        (HeapCellValueTag::PStr, pstr_atom) => {
+                            push_string_symbols(&mut symbols, &pstr_atom.as_str()[offset..]);
+                            cells.push(self.heap[focus + 1]);
+                        }
+                        (HeapCellValueTag::CStr, cstr_atom) => {
+                            push_string_symbols(&mut symbols, &cstr_atom.as_str()[offset..]);
+                            symbols.push(TrieSymbol::Atom(atom!("[]")));
+                        }
+                        _ => {
+                            unreachable!()
+                        }
+                    );
+                }
+                (HeapCellValueTag::CStr, cstr_atom) => {
+                    push_string_symbols(&mut symbols, &cstr_atom.as_str());
+                    symbols.push(TrieSymbol::Atom(atom!("[]")));
+                }
+                (HeapCellValueTag::Var | HeapCellValueTag::AttrVar | HeapCellValueTag::StackVar) => {
+                    let num_vars = vars.len();
+                    symbols.push(TrieSymbol::Var(*vars.entry(cell).or_insert(num_vars)));
+                }
+                (HeapCellValueTag::Atom, (name, _arity)) => {
+                    symbols.push(TrieSymbol::Atom(name));
+                }
+                (HeapCellValueTag::Char, c) => {
+                    symbols.push(TrieSymbol::Char(c));
+                }
+                (HeapCellValueTag::Fixnum, n) => {
+                    symbols.push(TrieSymbol::Fixnum(n.get_num()));
+                }
+                (HeapCellValueTag::F64, f) => {
+                    symbols.push(TrieSymbol::Float((*f).into_inner().to_bits()));
+                }
+                (HeapCellValueTag::Cons, ptr) => {
+                    match_untyped_arena_ptr!(ptr,
+                        (ArenaHeaderTag::Integer, n) => {
+                            symbols.push(TrieSymbol::Integer((*n).clone()));
+                        }
+                        (ArenaHeaderTag::Rational, r) => {
+                            symbols.push(TrieSymbol::Rational((*r).clone()));
+                        }
+                        (ArenaHeaderTag::Decimal, d) => {
+                            symbols.push(TrieSymbol::Decimal((*d).clone()));
+                        }
+                        _ => {
+                            symbols.push(TrieSymbol::Blob(cell));
+                        }
+                    );
+                }
+                _ => {
+                    symbols.push(TrieSymbol::Blob(cell));
+                }
+            );
+
+            // only the arguments of the term itself are skipped.
+            skipped_args = &[];
+        }
+
+        symbols
+    }
+
+    fn term_to_ball(&mut self, term: HeapCellValue) -> Ball {
+        let mut ball = Ball::new();
+        ball.boundary = self.heap.len();
+
+        copy_term(
+            CopyBallTerm::new(
+                &mut self.attr_var_init.attr_var_queue,
+                &mut self.stack,
+                &mut self.heap,
+                &mut ball.stub,
+            ),
+            term,
+            AttrVarPolicy::StripAttributes,
+        );
+
+        ball
+    }
+
+    fn ball_to_heap(&mut self, ball: &Ball) -> HeapCellValue {
+        let h = self.heap.len();
+        let stub = ball.copy_and_align(h);
+
+        self.heap.extend(stub);
+        heap_loc_as_cell!(h)
+    }
+}
+
+fn push_string_symbols(symbols: &mut Vec<TrieSymbol>, s: &str) {
+    for c in s.chars() {
+        symbols.push(TrieSymbol::Functor(atom!("."), 2));
+        symbols.push(TrieSymbol::Char(c));
+    }
+}
+
+fn delay_term(delay: &Delay) -> Term {
+    match *delay {
+        Delay::Negative(id) => Term::compound("tnot", [Term::integer(id)]),
+        Delay::Positive(id, idx) => {
+            Term::compound("answer", [Term::integer(id), Term::integer(idx)])
+        }
+    }
+}
+
+impl Machine {
+    fn table_id_register(&self, i: usize) -> usize {
+        cell_as_fixnum!(self.deref_register(i)).get_num() as usize
+    }
+
+    fn unify_atom_register(&mut self, i: usize, atom: Atom) {
+        unify!(
+            self.machine_st,
+            self.machine_st.registers[i],
+            atom_as_cell!(atom)
+        );
+    }
+
+    // reads a list of the delays `tnot(T)` and `answer(T, I)` made by
+    // library(tabling), sorted and without duplicates.
+    fn delays_register(&mut self, i: usize) -> Vec<Delay> {
+        let stub_gen = || functor_stub(atom!("$tbl_add_answer"), 3);
+        let cells = self
+            .machine_st
+            .try_from_list(self.machine_st.registers[i], stub_gen)
+            .unwrap_or_default();
+
+        let mut delays: Vec<Delay> = cells
+            .into_iter()
+            .filter_map(|cell| {
+                let cell = self.machine_st.store(self.machine_st.deref(cell));
+
+                read_heap_cell!(cell,
+                    (HeapCellValueTag::Str, s) => {
+                        let (name, arity) = cell_as_atom_cell!(self.machine_st.heap[s])
+                            .get_name_and_arity();
+                        let arg = |n: usize| {
+                            let arg = self.machine_st.store(
+                                self.machine_st.deref(self.machine_st.heap[s + n]),
+                            );
+                            cell_as_fixnum!(arg).get_num() as usize
+                        };
+
+                        match (name, arity) {
+                            (atom!("tnot"), 1) => Some(Delay::Negative(arg(1))),
+                            (atom!("answer"), 2) => Some(Delay::Positive(arg(1), arg(2))),
+                            _ => None,
+                        }
+                    }
+                    _ => {
+                        None
+                    }
+                )
+            })
+            .collect();
+
+        delays.sort();
+        delays.dedup();
+        delays
+    }
+
+    /// `'$tbl_variant_table'(+Variant, +ModedArgs, +Incremental, -Table)`
+    /// unifies `Table` with the table of the variants of `Variant`,
+    /// creating it if there's none. `ModedArgs` lists the arguments of
+    /// `Variant` left out of the keys of its answers.
+    pub(crate) fn table_variant_table(&mut self) -> CallResult {
+        let variant = self.deref_register(1);
+        let key = self.machine_st.variant_symbols(variant, &[]);
+
+        let id = match self.indices.tables.variants.get(&key) {
+            Some(id) => id,
+            None => {
+                let stub_gen = || functor_stub(atom!("$tbl_variant_table"), 4);
+                let moded_args = self
+                    .machine_st
+                    .try_from_list(self.machine_st.registers[2], stub_gen)?
+                    .into_iter()
+                    .map(|cell| {
+                        let cell = self.machine_st.store(self.machine_st.deref(cell));
+                        cell_as_fixnum!(cell).get_num() as usize
+                    })
+                    .collect();
+
+                let incremental = self.deref_register(3) == atom_as_cell!(atom!("true"));
+                let variant = self.machine_st.term_to_ball(variant);
+
+                self.indices
+                    .tables
+                    .new_table(key, variant, moded_args, incremental)
+            }
+        };
+
+        let id = fixnum_as_cell!(Fixnum::build_with(id as i64));
+        unify!(self.machine_st, self.machine_st.registers[4], id);
+
+        Ok(())
+    }
+
+    /// `'$tbl_enter'(+Table, -Action)` unifies `Action` with `evaluate`
+    /// if the caller must evaluate `Table` before consuming its
+    /// answers and with `consume` otherwise.
+    pub(crate) fn table_enter(&mut self) {
+        let id = self.table_id_register(1);
+
+        let action = if self.indices.tables.table(id).is_none() {
+            self.machine_st.fail = true;
+            return;
+        } else if self.indices.tables.enter(id) {
+            atom!("evaluate")
+        } else {
+            atom!("consume")
+        };
+
+        self.unify_atom_register(2, action);
+    }
+
+    /// `'$tbl_begin_pass'(+Table)` begins a pass of the evaluation of
+    /// `Table`, which is at the top of the stack.
+    pub(crate) fn table_begin_pass(&mut self) {
+        self.indices.tables.begin_pass();
+    }
+
+    /// `'$tbl_end_pass'(+Table, -Action)` ends a pass of the evaluation
+    /// of `Table`, unifying `Action` with `again` if another pass is
+    /// needed and with `done` otherwise.
+    pub(crate) fn table_end_pass(&mut self) {
+        let action = if self.indices.tables.end_pass() {
+            atom!("again")
+        } else {
+            atom!("done")
+        };
+
+        self.unify_atom_register(2, action);
+    }
+
+    /// `'$tbl_add_answer'(+Table, +Answer, +Delays)` adds `Answer` to
+    /// `Table` on the condition of `Delays`, replacing the answer with
+    /// the same key if `Table` is moded. Fails unless `Answer` is new.
+    pub(crate) fn table_add_answer(&mut self) {
+        let id = self.table_id_register(1);
+        let answer = self.deref_register(2);
+        let delays = self.delays_register(3);
+
+        let moded_args = match self.indices.tables.table(id) {
+            Some(table) => table.moded_args.clone(),
+            None => {
+                self.machine_st.fail = true;
+                return;
+            }
+        };
+
+        let key = self.machine_st.variant_symbols(answer, &moded_args);
+        let state = if delays.is_empty() {
+            AnswerState::Unconditional
+        } else {
+            AnswerState::Conditional(vec![delays])
+        };
+
+        let tables = &mut self.indices.tables;
+        let table = &mut tables.tables[&id];
+        let slot = table.answer_keys.entry(key);
+
+        match *slot {
+            Some(idx) if moded_args.is_empty() => {
+                match (&mut table.answers[idx].state, state) {
+                    (answer_state @ AnswerState::Conditional(_), AnswerState::Unconditional) => {
+                        *answer_state = AnswerState::Unconditional;
+                    }
+                    (
+                        AnswerState::Conditional(delay_lists),
+                        AnswerState::Conditional(mut delays),
+                    ) => {
+                        let delays = delays.pop().unwrap();
+
+                        if !delay_lists.contains(&delays) {
+                            delay_lists.push(delays);
+                        }
+                    }
+                    _ => {}
+                }
+
+                // the answer can't be consumed anew, so it isn't new.
+                self.machine_st.fail = true;
+            }
+            Some(idx) => {
+                let term = self.machine_st.term_to_ball(answer);
+
+                table.answers[idx] = Answer { term, state };
+                tables.changes += 1;
+            }
+            None => {
+                *slot = Some(table.answers.len());
+
+                let term = self.machine_st.term_to_ball(answer);
+
+                table.answers.push(Answer { term, state });
+                tables.changes += 1;
+            }
+        }
+    }
+
+    /// `'$tbl_moded_answer'(+Table, +Answer, -Old)` unifies `Old` with
+    /// the answer of `Table` whose key is that of `Answer`.
+    pub(crate) fn table_moded_answer(&mut self) {
+        let id = self.table_id_register(1);
+        let answer = self.deref_register(2);
+
+        let table = match self.indices.tables.table(id) {
+            Some(table) => table,
+            None => {
+                self.machine_st.fail = true;
+                return;
+            }
+        };
+
+        let key = self.machine_st.variant_symbols(answer, &table.moded_args);
+
+        match table.answer_keys.get(&key) {
+            Some(idx) => {
+                let old = self.machine_st.ball_to_heap(&table.answers[idx].term);
+                unify!(self.machine_st, self.machine_st.registers[3], old);
+            }
+            None => {
+                self.machine_st.fail = true;
+            }
+        }
+    }
+
+    /// `'$tbl_answer_count'(+Table, -N)` unifies `N` with the number of
+    /// answers added to `Table`, some of which may since be deleted.
+    pub(crate) fn table_answer_count(&mut self) {
+        let id = self.table_id_register(1);
+
+        match self.indices.tables.table(id) {
+            Some(table) => {
+                let n = fixnum_as_cell!(Fixnum::build_with(table.answers.len() as i64));
+                unify!(self.machine_st, self.machine_st.registers[2], n);
+            }
+            None => {
+                self.machine_st.fail = true;
+            }
+        }
+    }
+
+    /// `'$tbl_answer'(+Table, +I, ?Answer, -Conditional)` unifies
+    /// `Answer` with the `I`th answer of `Table`, failing if it was
+    /// deleted, and `Conditional` with `true` if the answer is
+    /// conditional and with `false` otherwise.
+    pub(crate) fn table_answer(&mut self) {
+        let id = self.table_id_register(1);
+        let idx = self.table_id_register(2);
+
+        let answer = match self
+            .indices
+            .tables
+            .table(id)
+            .and_then(|table| table.answers.get(idx))
+        {
+            Some(answer) if answer.state != AnswerState::Deleted => answer,
+            _ => {
+                self.machine_st.fail = true;
+                return;
+            }
+        };
+
+        let conditional = if let AnswerState::Conditional(_) = answer.state {
+            atom!("true")
+        } else {
+            atom!("false")
+        };
+
+        let term = self.machine_st.ball_to_heap(&answer.term);
+        unify!(self.machine_st, self.machine_st.registers[3], term);
+
+        if !self.machine_st.fail {
+            self.unify_atom_register(4, conditional);
+        }
+    }
+
+    /// `'$tbl_negation'(+Table, -Result)` unifies `Result` with `true` if
+    /// the evaluated `Table` is complete without answers, with `false`
+    /// if it has an unconditional answer and with `delay` otherwise.
+    pub(crate) fn table_negation(&mut self) {
+        let id = self.table_id_register(1);
+
+        let result = match self.indices.tables.table(id) {
+            Some(table) if table.has_unconditional_answer() => atom!("false"),
+            Some(table) if table.status == TableStatus::Complete && !table.has_answer() => {
+                atom!("true")
+            }
+            Some(_) => atom!("delay"),
+            None => {
+                self.machine_st.fail = true;
+                return;
+            }
+        };
+
+        self.unify_atom_register(2, result);
+    }
+
+    /// `'$tbl_abandon'(+Table)` drops the evaluation of `Table`, which
+    /// was interrupted by an exception.
+    pub(crate) fn table_abandon(&mut self) {
+        let id = self.table_id_register(1);
+        self.indices.tables.abandon(id);
+    }
+
+    /// `'$tbl_abolish_all'` removes the complete tables.
+    pub(crate) fn table_abolish_all(&mut self) {
+        self.indices.tables.abolish_all();
+    }
+
+    /// `'$tbl_incremental_dynamic'(+Name, +Arity)` declares the dynamic
+    /// predicate `Name/Arity` incremental, so that the incremental
+    /// tables depending on it are removed when it changes.
+    pub(crate) fn table_incremental_dynamic(&mut self) {
+        let name = cell_as_atom!(self.deref_register(1));
+        let arity = self.table_id_register(2);
+
+        self.indices
+            .tables
+            .incremental_dynamic
+            .insert((name, arity));
+    }
+
+    /// `'$tbl_variant'(+Table, -Variant)` unifies `Variant` with the
+    /// call `Table` was created for.
+    pub(crate) fn table_variant(&mut self) {
+        let id = self.table_id_register(1);
+
+        match self.indices.tables.table(id) {
+            Some(table) => {
+                let variant = self.machine_st.ball_to_heap(&table.variant);
+                unify!(self.machine_st, self.machine_st.registers[2], variant);
+            }
+            None => {
+                self.machine_st.fail = true;
+            }
+        }
+    }
+
+    /// `'$tbl_answer_conditions'(+Table, +I, -Conditions)` unifies
+    /// `Conditions` with the delay lists the `I`th answer of `Table`
+    /// is conditional on, which is `[]` if it's unconditional.
+    pub(crate) fn table_answer_conditions(&mut self) {
+        let id = self.table_id_register(1);
+        let idx = self.table_id_register(2);
+
+        let state = match self
+            .indices
+            .tables
+            .table(id)
+            .and_then(|table| table.answers.get(idx))
+        {
+            Some(answer) => &answer.state,
+            None => {
+                self.machine_st.fail = true;
+                return;
+            }
+        };
+
+        let conditions = match state {
+            AnswerState::Conditional(delay_lists) => Term::list(
+                delay_lists
+                    .iter()
+                    .map(|delays| Term::list(delays.iter().map(delay_term))),
+            ),
+            _ => Term::list([]),
+        };
+
+        let conditions = self.term_to_heap(&conditions, &mut IndexMap::new());
+        unify!(self.machine_st, self.machine_st.registers[3], conditions);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::mock_wam::*;
+
+    #[test]
+    fn variant_symbols_number_variables_by_first_occurrence() {
+        let mut wam = MockWAM::new();
+        let machine_st = &mut wam.machine_st;
+
+        let h = machine_st.heap.len();
+
+        machine_st.heap.push(atom_as_cell!(atom!("f"), 3));
+        machine_st.heap.push(heap_loc_as_cell!(h + 1));
+        machine_st.heap.push(heap_loc_as_cell!(h + 2));
+        machine_st.heap.push(heap_loc_as_cell!(h + 1));
+
+        let symbols = machine_st.variant_symbols(str_loc_as_cell!(h), &[]);
+
+        assert_eq!(
+            symbols,
+            vec![
+                TrieSymbol::Functor(atom!("f"), 3),
+                TrieSymbol::Var(0),
+                TrieSymbol::Var(1),
+                TrieSymbol::Var(0),
+            ]
+        );
+
+        let symbols = machine_st.variant_symbols(str_loc_as_cell!(h), &[2]);
+
+        assert_eq!(
+            symbols,
+            vec![
+                TrieSymbol::Functor(atom!("f"), 3),
+                TrieSymbol::Var(0),
+                TrieSymbol::Var(0),
+            ]
+        );
+    }
+
+    #[test]
+    fn tries_share_prefixes() {
+        let mut trie = Trie::default();
+        let f = TrieSymbol::Functor(atom!("f"), 2);
+
+        *trie.entry(vec![f.clone(), TrieSymbol::Fixnum(1), TrieSymbol::Var(0)]) = Some(0);
+        *trie.entry(vec![
+            f.clone(),
+            TrieSymbol::Fixnum(1),
+            TrieSymbol::Fixnum(2),
+        ]) = Some(1);
+
+        assert_eq!(trie.nodes.len(), 5);
+        assert_eq!(
+            trie.get(&[f.clone(), TrieSymbol::Fixnum(1), TrieSymbol::Var(0)]),
+            Some(0)
+        );
+        assert_eq!(trie.get(&[f.clone(), TrieSymbol::Fixnum(1)]), None);
+        assert_eq!(trie.get(&[f, TrieSymbol::Fixnum(2)]), None);
+    }
+}
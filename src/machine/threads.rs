@@ -0,0 +1,813 @@
+use crate::arena::*;
+use crate::atom_table::*;
+use crate::forms::*;
+#[cfg(feature = "http")]
+use crate::http::HttpListener;
+use crate::machine::copier::*;
+use crate::machine::gc::GCState;
+use crate::machine::heap::*;
+use crate::machine::limits::LimitsState;
+use crate::machine::machine_errors::*;
+use crate::machine::machine_indices::*;
+use crate::machine::machine_state::*;
+use crate::machine::parsed_results::Term;
+use crate::machine::qlf::QlfError;
+use crate::machine::streams::*;
+use crate::machine::Machine;
+use crate::parser::ast::*;
+use crate::parser::dashu::{Integer, Rational};
+use crate::parser::decimal::Decimal;
+use crate::types::*;
+
+use lazy_static::lazy_static;
+
+use std::cell::Cell;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+
+const MAIN_THREAD_ID: usize = 0;
+
+lazy_static! {
+    static ref THREADS: Mutex<ThreadTable> = Mutex::new(ThreadTable::new());
+    static ref MUTEXES: Mutex<MutexTable> = Mutex::new(MutexTable::default());
+}
+
+thread_local! {
+    static THREAD_ID: Cell<usize> = const { Cell::new(MAIN_THREAD_ID) };
+}
+
+#[inline]
+fn current_thread_id() -> usize {
+    THREAD_ID.with(|thread_id| thread_id.get())
+}
+
+enum PortableObject {
+    Integer(Integer),
+    Rational(Rational),
+    Decimal(Decimal),
+    #[cfg(feature = "http")]
+    HttpListener(HttpListener),
+}
+
+impl PortableObject {
+    fn new(ptr: UntypedArenaPtr) -> Option<Self> {
+        match_untyped_arena_ptr!(ptr,
+            (ArenaHeaderTag::Integer, n) => {
+                Some(PortableObject::Integer((*n).clone()))
+            }
+            (ArenaHeaderTag::Rational, r) => {
+                Some(PortableObject::Rational((*r).clone()))
+            }
+            (ArenaHeaderTag::Decimal, d) => {
+                Some(PortableObject::Decimal((*d).clone()))
+            }
+            _ => PortableObject::shared_listener(ptr)
+        )
+    }
+
+    // listeners are shared so that several threads can accept
+    // requests on the same port.
+    #[cfg(feature = "http")]
+    fn shared_listener(ptr: UntypedArenaPtr) -> Option<Self> {
+        match_untyped_arena_ptr!(ptr,
+            (ArenaHeaderTag::HttpListener, listener) => {
+                Some(PortableObject::HttpListener((*listener).clone()))
+            }
+            _ => None
+        )
+    }
+
+    #[cfg(not(feature = "http"))]
+    fn shared_listener(_ptr: UntypedArenaPtr) -> Option<Self> {
+        None
+    }
+
+    fn to_cell(&self, arena: &mut Arena) -> HeapCellValue {
+        match self {
+            PortableObject::Integer(n) => typed_arena_ptr_as_cell!(arena_alloc!(n.clone(), arena)),
+            PortableObject::Rational(r) => typed_arena_ptr_as_cell!(arena_alloc!(r.clone(), arena)),
+            PortableObject::Decimal(d) => typed_arena_ptr_as_cell!(arena_alloc!(d.clone(), arena)),
+            #[cfg(feature = "http")]
+            PortableObject::HttpListener(listener) => {
+                typed_arena_ptr_as_cell!(arena_alloc!(listener.clone(), arena))
+            }
+        }
+    }
+}
+
+/// A term copied off the heap of one machine so that it can be
+/// moved to another thread and copied onto the heap of its machine.
+pub(crate) struct PortableTerm {
+    ball: Ball,
+    // the stub cells pointing into the arena of the sending machine,
+    // with the values to allocate in the arena of the receiving one.
+    objects: Vec<(usize, PortableObject)>,
+}
+
+impl PortableTerm {
+    // on failure, returns the kind and cell of the first subterm that
+    // can't leave its machine.
+    pub(super) fn new(
+        machine_st: &mut MachineState,
+        term: HeapCellValue,
+    ) -> Result<Self, (Atom, HeapCellValue)> {
+        let mut ball = Ball::new();
+        ball.boundary = machine_st.heap.len();
+
+        copy_term(
+            CopyBallTerm::new(
+                &mut machine_st.attr_var_init.attr_var_queue,
+                &mut machine_st.stack,
+                &mut machine_st.heap,
+                &mut ball.stub,
+            ),
+            term,
+            AttrVarPolicy::DeepCopy,
+        );
+
+        let mut objects = vec![];
+
+        for (idx, cell) in ball.stub.iter_mut().enumerate() {
+            if cell.get_tag() != HeapCellValueTag::Cons {
+                continue;
+            }
+
+            let ptr = cell_as_untyped_arena_ptr!(*cell);
+
+            if let Some(object) = PortableObject::new(ptr) {
+                objects.push((idx, object));
+                continue;
+            }
+
+            match_untyped_arena_ptr!(ptr,
+                (ArenaHeaderTag::IndexPtr, _ip) => {
+                    // the index trailing an expanded goal points into
+                    // the sender's code area. without it, the goal is
+                    // looked up by name when called.
+                    *cell = empty_list_as_cell!();
+                }
+                (ArenaHeaderTag::Stream, _stream) => {
+                    return Err((atom!("stream"), *cell));
+                }
+                _ => {
+                    return Err((atom!("term"), *cell));
+                }
+            );
+        }
+
+        Ok(PortableTerm { ball, objects })
+    }
+
+    pub(super) fn write_to_heap(&self, machine_st: &mut MachineState) -> HeapCellValue {
+        let h = machine_st.heap.len();
+        let mut stub = self.ball.copy_and_align(h);
+
+        for (idx, object) in &self.objects {
+            stub[*idx] = object.to_cell(&mut machine_st.arena);
+        }
+
+        machine_st.heap.extend(stub);
+        heap_loc_as_cell!(h)
+    }
+
+    pub(super) fn cells(&self) -> impl Iterator<Item = HeapCellValue> + '_ {
+        self.ball.stub.iter().copied()
+    }
+}
+
+#[derive(Default)]
+struct MessageQueue {
+    messages: Mutex<VecDeque<PortableTerm>>,
+    cond: Condvar,
+}
+
+struct ThreadEntry {
+    alias: Option<Atom>,
+    queue: Arc<MessageQueue>,
+    handle: Option<JoinHandle<()>>,
+    status: Option<PortableTerm>,
+    detached: bool,
+}
+
+impl ThreadEntry {
+    fn new(alias: Option<Atom>, detached: bool) -> Self {
+        ThreadEntry {
+            alias,
+            queue: Arc::new(MessageQueue::default()),
+            handle: None,
+            status: None,
+            detached,
+        }
+    }
+}
+
+struct ThreadTable {
+    next_id: usize,
+    threads: HashMap<usize, ThreadEntry>,
+}
+
+impl ThreadTable {
+    fn new() -> Self {
+        let mut threads = HashMap::new();
+        threads.insert(MAIN_THREAD_ID, ThreadEntry::new(Some(atom!("main")), false));
+
+        ThreadTable {
+            next_id: MAIN_THREAD_ID + 1,
+            threads,
+        }
+    }
+
+    fn lookup_alias(&self, alias: Atom) -> Option<usize> {
+        self.threads
+            .iter()
+            .find(|(_, entry)| entry.alias == Some(alias))
+            .map(|(id, _)| *id)
+    }
+}
+
+#[derive(Default)]
+struct MutexState {
+    owner: Option<usize>,
+    count: usize,
+}
+
+// mutexes are recursive: the owning thread may lock them again, and
+// they're released once unlocked as many times as they were locked.
+#[derive(Default)]
+struct ThreadMutex {
+    state: Mutex<MutexState>,
+    cond: Condvar,
+}
+
+impl ThreadMutex {
+    fn lock(&self, thread_id: usize) {
+        let mut state = self.state.lock().unwrap();
+
+        while state.owner.is_some_and(|owner| owner != thread_id) {
+            state = self.cond.wait(state).unwrap();
+        }
+
+        state.owner = Some(thread_id);
+        state.count += 1;
+    }
+
+    fn unlock(&self, thread_id: usize) -> bool {
+        let mut state = self.state.lock().unwrap();
+
+        if state.owner != Some(thread_id) {
+            return false;
+        }
+
+        state.count -= 1;
+
+        if state.count == 0 {
+            state.owner = None;
+            self.cond.notify_one();
+        }
+
+        true
+    }
+}
+
+#[derive(Clone, Copy, Hash, PartialEq, Eq)]
+enum MutexKey {
+    Alias(Atom),
+    Anonymous(usize),
+}
+
+#[derive(Default)]
+struct MutexTable {
+    next_id: usize,
+    mutexes: HashMap<MutexKey, Arc<ThreadMutex>>,
+}
+
+/// Passes the cells of the terms waiting in message queues or as exit
+/// statuses, and the thread and mutex aliases, to `f`. The atoms they
+/// hold aren't otherwise reachable from any machine.
+pub(super) fn for_each_shared_cell(mut f: impl FnMut(HeapCellValue)) {
+    let threads = THREADS.lock().unwrap();
+
+    for entry in threads.threads.values() {
+        if let Some(alias) = entry.alias {
+            f(atom_as_cell!(alias));
+        }
+
+        let messages = entry.queue.messages.lock().unwrap();
+
+        for term in messages.iter().chain(&entry.status) {
+            term.cells().for_each(&mut f);
+        }
+    }
+
+    for key in MUTEXES.lock().unwrap().mutexes.keys() {
+        if let MutexKey::Alias(alias) = key {
+            f(atom_as_cell!(*alias));
+        }
+    }
+}
+
+// a machine is only ever run by one thread at a time. the machine of
+// a new thread shares only the atom table, the log of the dynamic
+// database and the tables of foreign predicates with its creator,
+// which are all thread-safe.
+struct SendMachine(Machine);
+
+unsafe impl Send for SendMachine {}
+
+impl SendMachine {
+    fn into_inner(self) -> Machine {
+        self.0
+    }
+}
+
+impl Machine {
+    // builds the machine of a new thread from a copy of the program of
+    // this one, linked to the same dynamic database.
+    fn new_thread_machine(&mut self) -> Result<Machine, QlfError> {
+        let (state, shared_database) = self.fork_database(|machine| machine.saved_state());
+
+        let mut machine_st = MachineState::new();
+
+        machine_st.gc = GCState::new(self.machine_st.gc.min_threshold);
+        machine_st.limits = LimitsState::new(self.machine_st.limits.limits.clone());
+        machine_st.sandbox = self.machine_st.sandbox;
+
+        let mut machine = Machine::from_state(machine_st, &state?, |arena| {
+            (
+                Stream::stdin(arena, false),
+                Stream::stdout(arena),
+                Stream::stderr(arena),
+            )
+        })?;
+
+        machine.foreign_predicates = self.foreign_predicates.for_thread();
+        machine.tracer = self.tracer.for_thread();
+        machine.shared_database = Some(shared_database);
+
+        // the debugger of a new thread starts off.
+        machine
+            .machine_st
+            .prolog_flags
+            .set_value(atom!("debug"), atom!("user"), Term::atom("off"));
+
+        Ok(machine)
+    }
+
+    fn portable_term(
+        &mut self,
+        term: HeapCellValue,
+        stub_gen: impl Fn() -> FunctorStub,
+    ) -> Result<PortableTerm, MachineStub> {
+        PortableTerm::new(&mut self.machine_st, term).map_err(|(kind, culprit)| {
+            let err = self
+                .machine_st
+                .permission_error(Permission::Send, kind, culprit);
+            self.machine_st.error_form(err, stub_gen())
+        })
+    }
+
+    fn thread_id_cell(&mut self, id: usize, alias: Option<Atom>) -> HeapCellValue {
+        match alias {
+            Some(alias) => atom_as_cell!(alias),
+            None => {
+                let h = self.machine_st.heap.len();
+
+                self.machine_st
+                    .heap
+                    .extend(functor!(atom!("$thread"), [fixnum(id)]));
+
+                str_loc_as_cell!(h)
+            }
+        }
+    }
+
+    // resolves a thread alias or a '$thread'/1 term to a thread id.
+    fn thread_id(&self, cell: HeapCellValue, threads: &ThreadTable) -> Option<usize> {
+        let id = read_heap_cell!(cell,
+            (HeapCellValueTag::Atom, (name, arity)) => {
+                if arity > 0 {
+                    return None;
+                }
+
+                return threads.lookup_alias(name);
+            }
+            (HeapCellValueTag::Str, s) => {
+                let (name, arity) = cell_as_atom_cell!(self.machine_st.heap[s])
+                    .get_name_and_arity();
+
+                if name != atom!("$thread") || arity != 1 {
+                    return None;
+                }
+
+                let arg = self.machine_st.store(self.machine_st.deref(self.machine_st.heap[s + 1]));
+
+                read_heap_cell!(arg,
+                    (HeapCellValueTag::Fixnum, n) => {
+                        usize::try_from(n.get_num()).ok()?
+                    }
+                    _ => {
+                        return None;
+                    }
+                )
+            }
+            _ => {
+                return None;
+            }
+        );
+
+        threads.threads.contains_key(&id).then_some(id)
+    }
+
+    fn thread_existence_error(
+        &mut self,
+        culprit: HeapCellValue,
+        stub_gen: impl Fn() -> FunctorStub,
+    ) -> MachineStub {
+        let err = self
+            .machine_st
+            .existence_error(ExistenceError::Thread(culprit));
+        self.machine_st.error_form(err, stub_gen())
+    }
+
+    fn mutex(
+        &mut self,
+        cell: HeapCellValue,
+        stub_gen: impl Fn() -> FunctorStub,
+    ) -> Result<Arc<ThreadMutex>, MachineStub> {
+        let mut mutexes = MUTEXES.lock().unwrap();
+
+        let key = read_heap_cell!(cell,
+            (HeapCellValueTag::Atom, (name, _arity)) => {
+                // mutexes named by atoms are created on first use.
+                let mutex = mutexes.mutexes.entry(MutexKey::Alias(name)).or_default();
+                return Ok(mutex.clone());
+            }
+            (HeapCellValueTag::Str, s) => {
+                let arg = self.machine_st.store(self.machine_st.deref(self.machine_st.heap[s + 1]));
+
+                read_heap_cell!(arg,
+                    (HeapCellValueTag::Fixnum, n) => {
+                        Some(MutexKey::Anonymous(n.get_num() as usize))
+                    }
+                    _ => {
+                        None
+                    }
+                )
+            }
+            _ => {
+                None
+            }
+        );
+
+        match key.and_then(|key| mutexes.mutexes.get(&key)) {
+            Some(mutex) => Ok(mutex.clone()),
+            None => {
+                let err = self.machine_st.existence_error(ExistenceError::Mutex(cell));
+                Err(self.machine_st.error_form(err, stub_gen()))
+            }
+        }
+    }
+
+    pub(crate) fn thread_create(&mut self) -> CallResult {
+        let stub_gen = || functor_stub(atom!("thread_create"), 3);
+
+        let goal = self.portable_term(self.machine_st.registers[1], stub_gen)?;
+        let alias = self.deref_register(2);
+        let detached = cell_as_atom!(self.deref_register(3)) == atom!("true");
+
+        let alias = read_heap_cell!(alias,
+            (HeapCellValueTag::Atom, (name, _arity)) => {
+                Some(name)
+            }
+            _ => {
+                None
+            }
+        );
+
+        let machine = match self.new_thread_machine() {
+            Ok(machine) => SendMachine(machine),
+            Err(_) => {
+                let err = self.machine_st.representation_error(RepFlag::Term);
+                return Err(self.machine_st.error_form(err, stub_gen()));
+            }
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let runtime = tokio::runtime::Handle::try_current().ok();
+
+        let mut threads = THREADS.lock().unwrap();
+
+        if let Some(alias) = alias {
+            if threads.lookup_alias(alias).is_some() {
+                let culprit = functor!(atom!("alias"), [atom(alias)]);
+                let err =
+                    self.machine_st
+                        .permission_error(Permission::Create, atom!("thread"), culprit);
+
+                return Err(self.machine_st.error_form(err, stub_gen()));
+            }
+        }
+
+        let id = threads.next_id;
+
+        threads.next_id += 1;
+        threads
+            .threads
+            .insert(id, ThreadEntry::new(alias, detached));
+
+        let spawned = std::thread::Builder::new()
+            .name(format!("prolog-thread-{id}"))
+            .spawn(move || {
+                THREAD_ID.with(|thread_id| thread_id.set(id));
+
+                #[cfg(not(target_arch = "wasm32"))]
+                let _guard = runtime.as_ref().map(|runtime| runtime.enter());
+
+                let mut machine = machine.into_inner();
+
+                // a thread is limited as a query of its own.
+                machine.start_limited_query();
+
+                machine.machine_st.registers[1] = goal.write_to_heap(&mut machine.machine_st);
+                machine.run_module_predicate(atom!("threads"), (atom!("$thread_start"), 1));
+
+                drop(machine);
+
+                let mut threads = THREADS.lock().unwrap();
+
+                if threads.threads.get(&id).is_some_and(|entry| entry.detached) {
+                    threads.threads.remove(&id);
+                }
+            });
+
+        let handle = match spawned {
+            Ok(handle) => handle,
+            Err(_) => {
+                threads.threads.remove(&id);
+                drop(threads);
+
+                let err = self.machine_st.resource_error(ResourceError::Threads);
+                return Err(self.machine_st.error_form(err, stub_gen()));
+            }
+        };
+
+        if let Some(entry) = threads.threads.get_mut(&id) {
+            if !detached {
+                entry.handle = Some(handle);
+            }
+        }
+
+        drop(threads);
+
+        let id_cell = self.thread_id_cell(id, alias);
+        unify!(self.machine_st, id_cell, self.machine_st.registers[4]);
+
+        Ok(())
+    }
+
+    pub(crate) fn thread_join(&mut self) -> CallResult {
+        let stub_gen = || functor_stub(atom!("thread_join"), 2);
+        let culprit = self.deref_register(1);
+
+        let (id, handle) = {
+            let mut threads = THREADS.lock().unwrap();
+
+            let id = match self.thread_id(culprit, &threads) {
+                Some(id) => id,
+                None => {
+                    drop(threads);
+                    return Err(self.thread_existence_error(culprit, stub_gen));
+                }
+            };
+
+            let entry = threads.threads.get_mut(&id).unwrap();
+
+            // the main thread, detached threads and threads already
+            // being joined have no handle.
+            match entry.handle.take() {
+                Some(handle) if id != current_thread_id() => (id, handle),
+                handle => {
+                    entry.handle = handle;
+                    drop(threads);
+
+                    let err = self.machine_st.permission_error(
+                        Permission::Join,
+                        atom!("thread"),
+                        culprit,
+                    );
+
+                    return Err(self.machine_st.error_form(err, stub_gen()));
+                }
+            }
+        };
+
+        let _ = handle.join();
+
+        let entry = THREADS.lock().unwrap().threads.remove(&id).unwrap();
+
+        let status = match entry.status {
+            Some(status) => status.write_to_heap(&mut self.machine_st),
+            None => {
+                // the thread halted.
+                let h = self.machine_st.heap.len();
+
+                self.machine_st
+                    .heap
+                    .extend(functor!(atom!("exited"), [atom(atom!("halt"))]));
+
+                str_loc_as_cell!(h)
+            }
+        };
+
+        unify!(self.machine_st, status, self.machine_st.registers[2]);
+
+        Ok(())
+    }
+
+    pub(crate) fn thread_self(&mut self) {
+        let id = current_thread_id();
+        let alias = THREADS
+            .lock()
+            .unwrap()
+            .threads
+            .get(&id)
+            .and_then(|entry| entry.alias);
+
+        let id_cell = self.thread_id_cell(id, alias);
+        unify!(self.machine_st, id_cell, self.machine_st.registers[1]);
+    }
+
+    pub(crate) fn thread_finish(&mut self) -> CallResult {
+        let stub_gen = || functor_stub(atom!("thread_create"), 3);
+        let status = self.portable_term(self.machine_st.registers[1], stub_gen)?;
+
+        if let Some(entry) = THREADS
+            .lock()
+            .unwrap()
+            .threads
+            .get_mut(&current_thread_id())
+        {
+            entry.status = Some(status);
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn thread_send_message(&mut self) -> CallResult {
+        let stub_gen = || functor_stub(atom!("thread_send_message"), 2);
+        let culprit = self.deref_register(1);
+
+        let queue = {
+            let threads = THREADS.lock().unwrap();
+
+            self.thread_id(culprit, &threads)
+                .map(|id| threads.threads[&id].queue.clone())
+        };
+
+        let queue = match queue {
+            Some(queue) => queue,
+            None => return Err(self.thread_existence_error(culprit, stub_gen)),
+        };
+
+        let message = self.portable_term(self.machine_st.registers[2], stub_gen)?;
+
+        queue.messages.lock().unwrap().push_back(message);
+        queue.cond.notify_all();
+
+        Ok(())
+    }
+
+    fn own_message_queue(&self) -> Arc<MessageQueue> {
+        let mut threads = THREADS.lock().unwrap();
+
+        // machines in threads not created by thread_create/3 share the
+        // queue of the main thread.
+        let id = current_thread_id();
+        let id = if threads.threads.contains_key(&id) {
+            id
+        } else {
+            MAIN_THREAD_ID
+        };
+
+        threads
+            .threads
+            .entry(id)
+            .or_insert_with(|| ThreadEntry::new(Some(atom!("main")), false))
+            .queue
+            .clone()
+    }
+
+    // unifies the message at index N of the queue of the calling
+    // thread with Msg, waiting for it to arrive if Block is true and
+    // failing otherwise.
+    pub(crate) fn thread_peek_message(&mut self) {
+        let n = match Number::try_from(self.deref_register(1)) {
+            Ok(Number::Fixnum(n)) => n.get_num() as usize,
+            _ => unreachable!(),
+        };
+
+        let block = cell_as_atom!(self.deref_register(3)) == atom!("true");
+        let queue = self.own_message_queue();
+
+        if block {
+            // only the thread owning the queue takes messages off it,
+            // so the message is still there once the lock is retaken.
+            let mut messages = queue.messages.lock().unwrap();
+
+            while messages.len() <= n {
+                messages = queue.cond.wait(messages).unwrap();
+            }
+        }
+
+        let messages = queue.messages.lock().unwrap();
+
+        if messages.len() <= n {
+            self.machine_st.fail = true;
+            return;
+        }
+
+        let message = messages[n].write_to_heap(&mut self.machine_st);
+        drop(messages);
+
+        unify!(self.machine_st, message, self.machine_st.registers[2]);
+    }
+
+    pub(crate) fn thread_remove_message(&mut self) {
+        let n = match Number::try_from(self.deref_register(1)) {
+            Ok(Number::Fixnum(n)) => n.get_num() as usize,
+            _ => unreachable!(),
+        };
+
+        let queue = self.own_message_queue();
+        let mut messages = queue.messages.lock().unwrap();
+
+        messages.remove(n);
+    }
+
+    pub(crate) fn mutex_create(&mut self) -> CallResult {
+        let stub_gen = || functor_stub(atom!("mutex_create"), 1);
+        let mutex = self.deref_register(1);
+        let mut mutexes = MUTEXES.lock().unwrap();
+
+        if let Some(alias) = mutex.to_atom() {
+            if mutexes.mutexes.contains_key(&MutexKey::Alias(alias)) {
+                drop(mutexes);
+
+                let err =
+                    self.machine_st
+                        .permission_error(Permission::Create, atom!("mutex"), mutex);
+
+                return Err(self.machine_st.error_form(err, stub_gen()));
+            }
+
+            mutexes
+                .mutexes
+                .insert(MutexKey::Alias(alias), Arc::default());
+        } else {
+            let id = mutexes.next_id;
+
+            mutexes.next_id += 1;
+            mutexes
+                .mutexes
+                .insert(MutexKey::Anonymous(id), Arc::default());
+
+            drop(mutexes);
+
+            let h = self.machine_st.heap.len();
+
+            self.machine_st
+                .heap
+                .extend(functor!(atom!("$mutex"), [fixnum(id)]));
+
+            unify!(self.machine_st, str_loc_as_cell!(h), mutex);
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn mutex_lock(&mut self) -> CallResult {
+        let stub_gen = || functor_stub(atom!("mutex_lock"), 1);
+        let mutex = self.mutex(self.deref_register(1), stub_gen)?;
+
+        mutex.lock(current_thread_id());
+
+        Ok(())
+    }
+
+    pub(crate) fn mutex_unlock(&mut self) -> CallResult {
+        let stub_gen = || functor_stub(atom!("mutex_unlock"), 1);
+        let culprit = self.deref_register(1);
+        let mutex = self.mutex(culprit, stub_gen)?;
+
+        if !mutex.unlock(current_thread_id()) {
+            let err = self
+                .machine_st
+                .permission_error(Permission::Unlock, atom!("mutex"), culprit);
+
+            return Err(self.machine_st.error_form(err, stub_gen()));
+        }
+
+        Ok(())
+    }
+}
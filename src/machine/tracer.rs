@@ -0,0 +1,423 @@
+use crate::atom_table::*;
+use crate::forms::*;
+use crate::machine::machine_indices::*;
+use crate::machine::machine_state::*;
+use crate::machine::parsed_results::Term;
+use crate::machine::{Machine, LIBRARIES};
+use crate::parser::ast::Fixnum;
+use crate::types::*;
+
+use ref_thread_local::RefThreadLocal;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// A port of the box of a call to a user predicate.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Port {
+    Call,
+    Exit,
+    Redo,
+    Fail,
+    Exception(Term),
+}
+
+/// An event of the debugger: the call `module:goal` at `depth` passed
+/// through `port`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PortEvent {
+    pub port: Port,
+    pub depth: usize,
+    pub module: String,
+    pub goal: Term,
+}
+
+/// How the debugger carries on from a port, as by the commands of its
+/// prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortAction {
+    /// Carry on to the next port.
+    Creep,
+    /// Carry on to the next port of this call, passing over the ports
+    /// of the calls it makes.
+    Skip,
+    /// Carry on to the next spy point.
+    Leap,
+    /// Run this call again from its call port.
+    Retry,
+    /// Abort the query.
+    Abort,
+}
+
+type TraceHook = dyn Fn(&PortEvent) -> PortAction + Send + Sync;
+
+#[derive(Default)]
+pub(crate) struct Tracer {
+    // calls to user predicates are diverted to the debugger, in
+    // library(debug), while set.
+    pub(crate) enabled: bool,
+    hook: Option<Arc<TraceHook>>,
+    // the modules of the user predicates by the code they start at,
+    // or None for predicates of the system.
+    modules: HashMap<usize, Option<Atom>>,
+}
+
+impl fmt::Debug for Tracer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tracer")
+            .field("enabled", &self.enabled)
+            .field("hook", &self.hook.is_some())
+            .finish()
+    }
+}
+
+impl Tracer {
+    // the tracer of a new thread, which starts with the debugger off.
+    pub(crate) fn for_thread(&self) -> Self {
+        Tracer {
+            enabled: false,
+            hook: self.hook.clone(),
+            modules: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn atoms(&self) -> impl Iterator<Item = Atom> + '_ {
+        self.modules.values().flatten().copied()
+    }
+}
+
+// the libraries and the modules the system is bootstrapped from,
+// whose predicates are not traced.
+fn is_system_module(module_name: Atom) -> bool {
+    if matches!(
+        module_name,
+        atom!("loader") | atom!("$toplevel") | atom!("$atts") | atom!("$project_atts")
+    ) {
+        return true;
+    }
+
+    let module_name = module_name.as_str();
+
+    LIBRARIES
+        .borrow()
+        .keys()
+        .any(|library| library.rsplit('/').next() == Some(&*module_name))
+}
+
+impl Machine {
+    /// Hands the port events of the debugger to `hook` in place of its
+    /// prompt. The debugger is started as usual, by `trace/0`,
+    /// `debug/0` or `spy/1` of library(debug).
+    pub fn set_trace_hook<F>(&mut self, hook: F)
+    where
+        F: Fn(&PortEvent) -> PortAction + Send + Sync + 'static,
+    {
+        self.tracer.hook = Some(Arc::new(hook));
+    }
+
+    /// Restores the prompt of the debugger.
+    pub fn clear_trace_hook(&mut self) {
+        self.tracer.hook = None;
+    }
+
+    // the module of the user predicate starting at p, if it isn't a
+    // predicate of the system.
+    fn traced_module(&mut self, key: PredicateKey, p: usize) -> Option<Atom> {
+        if let Some(module_name) = self.tracer.modules.get(&p) {
+            return *module_name;
+        }
+
+        // the expansion hooks are called by the system.
+        if matches!(
+            key,
+            (atom!("goal_expansion"), 2) | (atom!("term_expansion"), 2)
+        ) {
+            self.tracer.modules.insert(p, None);
+            return None;
+        }
+
+        let defines = |code_dir: &CodeDir| {
+            code_dir
+                .get(&key)
+                .map(|idx| idx.get().p() as usize == p)
+                .unwrap_or(false)
+        };
+
+        // a predicate is of the module exporting it, else of user if
+        // it is defined there, else of the module defining it.
+        let mut module_name = None;
+        let mut exported = false;
+
+        for (name, module) in self.indices.modules.iter() {
+            if !defines(&module.code_dir) {
+                continue;
+            }
+
+            if is_system_module(*name) {
+                self.tracer.modules.insert(p, None);
+                return None;
+            }
+
+            if module.module_decl.exports.iter().any(
+                |export| matches!(export, ModuleExport::PredicateKey(export) if *export == key),
+            ) {
+                module_name = Some(*name);
+                exported = true;
+            } else if module_name.is_none() {
+                module_name = Some(*name);
+            }
+        }
+
+        if !exported && defines(&self.indices.code_dir) {
+            module_name = Some(atom!("user"));
+        }
+
+        self.tracer.modules.insert(p, module_name);
+        module_name
+    }
+
+    /// Diverts the call of the predicate `name/arity` starting at `p`
+    /// to `debug:'$trace_goal'/3` if the debugger is on and it is a
+    /// user predicate, returning whether it was.
+    #[inline(always)]
+    pub(super) fn divert_to_debugger(
+        &mut self,
+        name: Atom,
+        arity: usize,
+        p: usize,
+        dynamic: bool,
+        last_call: bool,
+    ) -> bool {
+        self.tracer.enabled && self.divert_call(name, arity, p, dynamic, last_call)
+    }
+
+    #[cold]
+    fn divert_call(
+        &mut self,
+        name: Atom,
+        arity: usize,
+        p: usize,
+        dynamic: bool,
+        last_call: bool,
+    ) -> bool {
+        let module_name = match self.traced_module((name, arity), p) {
+            Some(module_name) => module_name,
+            None => return false,
+        };
+
+        let handler =
+            match self
+                .indices
+                .get_predicate_code_index(atom!("$trace_goal"), 3, atom!("debug"))
+            {
+                Some(idx) => match idx.get().tag() {
+                    IndexPtrTag::Index => idx.get().p() as usize,
+                    _ => return false,
+                },
+                None => return false,
+            };
+
+        let h = self.machine_st.heap.len();
+
+        let goal = if arity == 0 {
+            atom_as_cell!(name)
+        } else {
+            self.machine_st.heap.push(atom_as_cell!(name, arity));
+
+            for r in 1..=arity {
+                let arg = self.machine_st.registers[r];
+                self.machine_st.heap.push(arg);
+            }
+
+            str_loc_as_cell!(h)
+        };
+
+        let qualified_goal = self.machine_st.heap.len();
+
+        self.machine_st.heap.extend([
+            atom_as_cell!(atom!(":"), 2),
+            atom_as_cell!(module_name),
+            goal,
+        ]);
+
+        self.machine_st.registers[1] = str_loc_as_cell!(qualified_goal);
+        self.machine_st.registers[2] = fixnum_as_cell!(Fixnum::build_with(p as i64));
+        self.machine_st.registers[3] = atom_as_cell!(if dynamic {
+            atom!("true")
+        } else {
+            atom!("false")
+        });
+
+        if last_call {
+            self.machine_st.execute_at_index(3, handler);
+        } else {
+            self.machine_st.call_at_index(3, handler);
+        }
+
+        true
+    }
+
+    /// Calls the goal `Module:Goal` in register 1 at the code at `P` in
+    /// register 2, where its call was diverted from, `Dynamic` in
+    /// register 3 if its predicate is dynamic.
+    pub(crate) fn invoke_traced(&mut self, last_call: bool) {
+        let p = match Number::try_from(self.deref_register(2)) {
+            Ok(Number::Fixnum(n)) => n.get_num() as usize,
+            _ => unreachable!(),
+        };
+
+        if self.deref_register(3) == atom_as_cell!(atom!("true")) {
+            self.machine_st.dynamic_mode = FirstOrNext::First;
+        }
+
+        let goal = self.deref_register(1);
+
+        let goal = read_heap_cell!(goal,
+            (HeapCellValueTag::Str, s) => {
+                self.machine_st.store(self.machine_st.deref(self.machine_st.heap[s + 2]))
+            }
+            _ => {
+                unreachable!()
+            }
+        );
+
+        let arity = read_heap_cell!(goal,
+            (HeapCellValueTag::Str, s) => {
+                let arity = cell_as_atom_cell!(self.machine_st.heap[s]).get_arity();
+
+                for r in 1..=arity {
+                    self.machine_st.registers[r] = self.machine_st.heap[s + r];
+                }
+
+                arity
+            }
+            _ => {
+                0
+            }
+        );
+
+        if last_call {
+            self.machine_st.execute_at_index(arity, p);
+        } else {
+            self.machine_st.call_at_index(arity, p);
+        }
+    }
+
+    /// Hands the port event `'$trace_port_hook'(Port, Depth, Goal,
+    /// Action)` to the trace hook, failing if there is none.
+    pub(crate) fn trace_port_hook(&mut self) {
+        let hook = match &self.tracer.hook {
+            Some(hook) => hook.clone(),
+            None => {
+                self.machine_st.fail = true;
+                return;
+            }
+        };
+
+        let mut var_names = HashMap::new();
+
+        let port = match self.term_from_heap(self.machine_st.registers[1], &mut var_names) {
+            Term::Atom(port) if port == "call" => Port::Call,
+            Term::Atom(port) if port == "exit" => Port::Exit,
+            Term::Atom(port) if port == "redo" => Port::Redo,
+            Term::Atom(port) if port == "fail" => Port::Fail,
+            Term::Compound(_, mut args) if args.len() == 1 => Port::Exception(args.remove(0)),
+            _ => unreachable!(),
+        };
+
+        let depth = match Number::try_from(self.deref_register(2)) {
+            Ok(Number::Fixnum(n)) => n.get_num() as usize,
+            _ => unreachable!(),
+        };
+
+        let (module, goal) = match self.term_from_heap(self.machine_st.registers[3], &mut var_names)
+        {
+            Term::Compound(colon, mut args) if colon == ":" && args.len() == 2 => {
+                let goal = args.pop().unwrap();
+
+                match args.pop() {
+                    Some(Term::Atom(module)) => (module, goal),
+                    _ => unreachable!(),
+                }
+            }
+            _ => unreachable!(),
+        };
+
+        let action = hook(&PortEvent {
+            port,
+            depth,
+            module,
+            goal,
+        });
+
+        let action = match action {
+            PortAction::Creep => atom!("creep"),
+            PortAction::Skip => atom!("skip"),
+            PortAction::Leap => atom!("leap"),
+            PortAction::Retry => atom!("retry"),
+            PortAction::Abort => atom!("abort"),
+        };
+
+        self.machine_st
+            .unify_atom(action, self.machine_st.registers[4]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::LeafAnswer;
+
+    use std::sync::Mutex;
+
+    #[test]
+    #[cfg_attr(miri, ignore = "blocked on streams.rs UB")]
+    fn trace_hook_receives_port_events() {
+        let mut machine = Machine::new_lib();
+
+        machine.load_module_string(
+            "facts",
+            r#"
+            :- use_module(library(debug)).
+
+            f(1).
+            f(2).
+
+            g(X) :- f(X), X > 1.
+            "#
+            .to_string(),
+        );
+
+        let events = Arc::new(Mutex::new(vec![]));
+        let hook_events = events.clone();
+
+        machine.set_trace_hook(move |event| {
+            hook_events
+                .lock()
+                .unwrap()
+                .push((event.port.clone(), event.depth, event.goal.clone()));
+
+            PortAction::Creep
+        });
+
+        let answers: Vec<_> = machine.query("trace, g(X), nodebug.").collect();
+
+        assert!(matches!(&answers[..], [Ok(LeafAnswer::LeafAnswer { .. })]));
+
+        let events = events.lock().unwrap();
+        let g = |x| Term::compound("g", [x]);
+        let f = |x| Term::compound("f", [x]);
+
+        assert_eq!(
+            events[..],
+            [
+                (Port::Call, 1, g(Term::variable("_G0"))),
+                (Port::Call, 2, f(Term::variable("_G0"))),
+                (Port::Exit, 2, f(Term::integer(1))),
+                (Port::Redo, 2, f(Term::integer(1))),
+                (Port::Exit, 2, f(Term::integer(2))),
+                (Port::Exit, 1, g(Term::integer(2))),
+            ]
+        );
+    }
+}
@@ -4,6 +4,7 @@
 use crate::machine::machine_state::*;
 use crate::machine::partial_string::*;
 use crate::machine::*;
+use crate::parser::decimal::Decimal;
 use crate::types::*;
 
 use std::cmp::Ordering;
@@ -519,6 +520,21 @@ fn unify_big_rational(&mut self, n1: TypedArenaPtr<Rational>, value: HeapCellVal
         }
     }
 
+    fn unify_big_decimal(&mut self, d1: TypedArenaPtr<Decimal>, value: HeapCellValue) {
+        if let Some(r) = value.as_var() {
+            Self::bind(self, r, typed_arena_ptr_as_cell!(d1));
+            return;
+        }
+
+        // decimals of different scales are different terms.
+        match Number::try_from(value) {
+            Ok(Number::Decimal(d2)) if *d1 == *d2 => {}
+            _ => {
+                self.fail = true;
+            }
+        }
+    }
+
     fn unify_f64(&mut self, f1: F64Ptr, value: HeapCellValue) {
         if let Some(r) = value.as_var() {
             Self::bind(self, r, HeapCellValue::from(f1));
@@ -527,7 +543,7 @@ fn unify_f64(&mut self, f1: F64Ptr, value: HeapCellValue) {
 
         read_heap_cell!(value,
             (HeapCellValueTag::F64, f2) => {
-                self.fail = **f1 != **f2;
+                self.fail = *f1 != *f2;
             }
             _ => {
                 self.fail = true;
@@ -549,6 +565,9 @@ fn unify_constant(&mut self, ptr: UntypedArenaPtr, value: HeapCellValue) {
              (ArenaHeaderTag::Rational, rat_ptr) => {
                  Self::unify_big_rational(self, rat_ptr, value);
              }
+             (ArenaHeaderTag::Decimal, dec_ptr) => {
+                 Self::unify_big_decimal(self, dec_ptr, value);
+             }
              (ArenaHeaderTag::Stream, stream) => {
                  read_heap_cell!(value,
                      (HeapCellValueTag::AttrVar | HeapCellValueTag::Var | HeapCellValueTag::StackVar) => {
@@ -31,6 +31,7 @@ macro_rules! integer_as_cell {
             Number::Float(_) => unreachable!(),
             Number::Fixnum(n) => fixnum_as_cell!(n),
             Number::Rational(r) => typed_arena_ptr_as_cell!(r),
+            Number::Decimal(d) => typed_arena_ptr_as_cell!(d),
             Number::Integer(n) => typed_arena_ptr_as_cell!(n),
         }
     }};
@@ -252,6 +253,12 @@ macro_rules! match_untyped_arena_ptr_pat_body {
         #[allow(unused_braces)]
         $code
     }};
+    ($ptr:ident, Decimal, $n:ident, $code:expr) => {{
+        let payload_ptr = unsafe { std::mem::transmute::<_, *mut Decimal>($ptr.payload_offset()) };
+        let $n = TypedArenaPtr::new(payload_ptr);
+        #[allow(unused_braces)]
+        $code
+    }};
     ($ptr:ident, OssifiedOpDir, $n:ident, $code:expr) => {{
         let payload_ptr =
             unsafe { std::mem::transmute::<_, *mut OssifiedOpDir>($ptr.payload_offset()) };
@@ -317,6 +324,8 @@ macro_rules! match_untyped_arena_ptr_pat {
             | ArenaHeaderTag::NamedTlsStream
             | ArenaHeaderTag::HttpReadStream
             | ArenaHeaderTag::HttpWriteStream
+            | ArenaHeaderTag::PipeInputStream
+            | ArenaHeaderTag::PipeOutputStream
             | ArenaHeaderTag::ReadlineStream
             | ArenaHeaderTag::StaticStringStream
             | ArenaHeaderTag::ByteStream
@@ -607,6 +616,9 @@ macro_rules! index_store {
             op_dir: $op_dir,
             streams: StreamDir::new(),
             stream_aliases: StreamAliasDir::with_hasher(FxBuildHasher::default()),
+            processes: ProcessDir::with_hasher(FxBuildHasher::default()),
+            jit_indices: JitIndices::default(),
+            tables: Tables::default(),
         }
     };
 }
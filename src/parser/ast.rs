@@ -14,6 +14,7 @@
 use std::vec::Vec;
 
 use crate::parser::dashu::{Integer, Rational};
+use crate::parser::decimal::Decimal;
 
 use fxhash::FxBuildHasher;
 use indexmap::IndexMap;
@@ -303,6 +304,67 @@ pub fn arity(self) -> usize {
 pub struct MachineFlags {
     pub double_quotes: DoubleQuotes,
     pub unknown: Unknown,
+    pub backtrace: bool,
+    pub float_flags: FloatFlags,
+    pub decimal_flags: DecimalFlags,
+}
+
+/// How float arithmetic treats the exceptional results of IEEE 754,
+/// as set by the `float_overflow`, `float_zero_div`,
+/// `float_undefined` and `float_rounding` flags. By default, they're
+/// evaluation errors.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FloatFlags {
+    /// Overflows are infinities rather than `float_overflow` errors.
+    pub overflow_to_infinity: bool,
+    /// Divisions by zero are infinities rather than `zero_divisor`
+    /// errors.
+    pub zero_div_to_infinity: bool,
+    /// Undefined results are NaNs rather than `undefined` errors.
+    pub undefined_to_nan: bool,
+    pub rounding: FloatRounding,
+}
+
+/// The direction in which float arithmetic rounds inexact results.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FloatRounding {
+    #[default]
+    ToNearest,
+    ToPositive,
+    ToNegative,
+    ToZero,
+}
+
+/// How decimal arithmetic rounds the results it can't represent
+/// exactly, as set by the `decimal_precision` and `decimal_rounding`
+/// flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecimalFlags {
+    /// The number of digits after the decimal point of inexact
+    /// quotients and conversions.
+    pub precision: usize,
+    pub rounding: DecimalRounding,
+}
+
+impl Default for DecimalFlags {
+    fn default() -> Self {
+        DecimalFlags {
+            precision: 20,
+            rounding: DecimalRounding::default(),
+        }
+    }
+}
+
+/// The direction in which decimal arithmetic rounds inexact results.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DecimalRounding {
+    #[default]
+    HalfEven,
+    HalfAwayFromZero,
+    ToZero,
+    AwayFromZero,
+    ToPositive,
+    ToNegative,
 }
 
 #[derive(Debug, Default, Clone, Copy, PartialEq)]
@@ -583,6 +645,7 @@ pub enum Literal {
     Fixnum(Fixnum),
     Integer(TypedArenaPtr<Integer>),
     Rational(TypedArenaPtr<Rational>),
+    Decimal(TypedArenaPtr<Decimal>),
     Float(F64Offset),
     String(Atom),
 }
@@ -605,6 +668,7 @@ fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
             Literal::Fixnum(n) => write!(f, "{}", n.get_num()),
             Literal::Integer(ref n) => write!(f, "{}", n),
             Literal::Rational(ref n) => write!(f, "{}", n),
+            Literal::Decimal(ref n) => write!(f, "{}", n),
             Literal::Float(ref n) => write!(f, "{}", *n),
             Literal::String(ref s) => write!(f, "\"{}\"", s.as_str()),
         }
@@ -778,6 +842,138 @@ pub fn arity(&self) -> usize {
     }
 }
 
+/// The span of a term read by the parser and of its subterms, as the
+/// character offsets they start and end at in the stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TermPosition {
+    /// An atom, number or variable.
+    Span(usize, usize),
+    /// A double quoted list.
+    String(usize, usize),
+    /// A `{}/1` term and its argument.
+    Brace(usize, usize, Box<TermPosition>),
+    /// A list, its elements and the tail following its `|`.
+    List(usize, usize, Vec<TermPosition>, Option<Box<TermPosition>>),
+    /// A compound term, its functor and its arguments.
+    Compound {
+        from: usize,
+        to: usize,
+        functor: (usize, usize),
+        args: Vec<TermPosition>,
+    },
+    /// A term in parentheses.
+    Parens(usize, usize, Box<TermPosition>),
+}
+
+impl Default for TermPosition {
+    fn default() -> Self {
+        TermPosition::Span(0, 0)
+    }
+}
+
+impl TermPosition {
+    #[inline]
+    pub fn span(&self) -> (usize, usize) {
+        match self {
+            &TermPosition::Span(from, to)
+            | &TermPosition::String(from, to)
+            | &TermPosition::Brace(from, to, _)
+            | &TermPosition::List(from, to, ..)
+            | &TermPosition::Compound { from, to, .. }
+            | &TermPosition::Parens(from, to, _) => (from, to),
+        }
+    }
+
+    /// The positions of the arguments of a `','/2` term, of which the
+    /// parser unfolds the arguments as it does the term.
+    pub(crate) fn unfold_comma(self) -> Option<(TermPosition, TermPosition)> {
+        match self {
+            TermPosition::Compound { mut args, .. } if args.len() == 2 => {
+                let snd = args.pop().unwrap();
+                let fst = args.pop().unwrap();
+
+                Some((fst, snd))
+            }
+            TermPosition::Parens(_, _, position) => position.unfold_comma(),
+            _ => None,
+        }
+    }
+
+    /// The position as a term of the form of the `subterm_positions`
+    /// option of `read_term/3`.
+    pub fn to_term(&self) -> Term {
+        let offset = |n: usize| {
+            Term::Literal(
+                Cell::default(),
+                Literal::Fixnum(Fixnum::build_with(n as i64)),
+            )
+        };
+
+        let list = |positions: &[TermPosition]| {
+            positions.iter().rev().fold(
+                Term::Literal(Cell::default(), Literal::Atom(atom!("[]"))),
+                |tail, position| {
+                    Term::Cons(
+                        Cell::default(),
+                        Box::new(position.to_term()),
+                        Box::new(tail),
+                    )
+                },
+            )
+        };
+
+        match self {
+            &TermPosition::Span(from, to) => {
+                Term::Clause(Cell::default(), atom!("-"), vec![offset(from), offset(to)])
+            }
+            &TermPosition::String(from, to) => Term::Clause(
+                Cell::default(),
+                atom!("string_position"),
+                vec![offset(from), offset(to)],
+            ),
+            TermPosition::Brace(from, to, arg) => Term::Clause(
+                Cell::default(),
+                atom!("brace_term_position"),
+                vec![offset(*from), offset(*to), arg.to_term()],
+            ),
+            TermPosition::List(from, to, elems, tail) => Term::Clause(
+                Cell::default(),
+                atom!("list_position"),
+                vec![
+                    offset(*from),
+                    offset(*to),
+                    list(elems),
+                    match tail {
+                        Some(tail) => tail.to_term(),
+                        None => Term::Literal(Cell::default(), Literal::Atom(atom!("none"))),
+                    },
+                ],
+            ),
+            TermPosition::Compound {
+                from,
+                to,
+                functor,
+                args,
+            } => Term::Clause(
+                Cell::default(),
+                atom!("term_position"),
+                vec![
+                    offset(*from),
+                    offset(*to),
+                    offset(functor.0),
+                    offset(functor.1),
+                    list(args),
+                ],
+            ),
+            TermPosition::Parens(from, to, position) => Term::Clause(
+                Cell::default(),
+                atom!("parentheses_term_position"),
+                vec![offset(*from), offset(*to), position.to_term()],
+            ),
+        }
+    }
+}
+
 #[inline]
 pub fn source_arity(terms: &[Term]) -> usize {
     if let Some(Term::Literal(_, Literal::CodeIndex(_))) = terms.last() {
@@ -8,8 +8,13 @@
  * wrapping it a Bytes struct.
  *
  * Unlike BufReader, its buffer is peekable as a char.
+ *
+ * Readers of other encodings decode a character at a time into the
+ * buffer, which then holds its UTF-8.
  */
 
+use crate::machine::streams::Encoding;
+
 use smallvec::*;
 
 use std::error::Error;
@@ -22,6 +27,7 @@ pub struct CharReader<R> {
     inner: R,
     buf: SmallVec<[u8; 32]>,
     pos: usize,
+    encoding: Encoding,
 }
 
 /// An error raised when parsing a UTF-8 byte stream fails.
@@ -43,15 +49,56 @@ fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     }
 }
 
+/// An error raised when decoding a byte stream of an encoding other
+/// than UTF-8 fails.
+#[derive(Debug)]
+pub struct BadEncodingError {
+    pub encoding: Encoding,
+    /// The bytes that could not be decoded as a character.
+    pub bytes: Vec<u8>,
+}
+
+impl Error for BadEncodingError {}
+
+impl fmt::Display for BadEncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Bad {:?}: {:?}", self.encoding, self.bytes)
+    }
+}
+
+fn bad_encoding_error(encoding: Encoding, bytes: &[u8]) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        BadEncodingError {
+            encoding,
+            bytes: bytes.to_vec(),
+        },
+    )
+}
+
 impl<R> CharReader<R> {
     pub fn new(inner: R) -> CharReader<R> {
         Self {
             inner,
             buf: SmallVec::new(),
             pos: 0,
+            encoding: Encoding::Utf8,
         }
     }
 
+    #[inline]
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Sets the encoding the characters after those already read
+    /// are decoded from. Binary streams read bytes as they are, which
+    /// is what `Encoding::Utf8` does.
+    #[inline]
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        self.encoding = encoding;
+    }
+
     #[inline]
     pub fn inner(&self) -> &R {
         &self.inner
@@ -64,10 +111,54 @@ pub fn inner_mut(&mut self) -> &mut R {
 
     // Return the number of bytes remaining to be read.  Useful for,
     // e.g., determining the position relative to the end of the
-    // owning stream.
+    // owning stream. These are bytes of the stream's encoding, not of
+    // the UTF-8 in the buffer.
     #[inline]
     pub fn rem_buf_len(&self) -> usize {
-        self.buf.len() - self.pos
+        let buf = &self.buf[self.pos..];
+
+        match (self.encoding, str::from_utf8(buf)) {
+            (Encoding::Utf8, _) | (_, Err(_)) => buf.len(),
+            (encoding, Ok(s)) => s.chars().map(|c| encoding.encoded_len(c)).sum(),
+        }
+    }
+}
+
+/// A position in a character stream, as the characters, bytes and lines
+/// before it and the characters before it on its line.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ReadPosition {
+    pub char_num: usize,
+    pub byte_num: usize,
+    pub line_num: usize,
+    pub line_pos: usize,
+}
+
+impl ReadPosition {
+    /// Moves the position past `c`.
+    pub fn advance(&mut self, c: char) {
+        self.char_num += 1;
+        self.byte_num += c.len_utf8();
+
+        if c == '\n' {
+            self.line_num += 1;
+            self.line_pos = 0;
+        } else {
+            self.line_pos += 1;
+        }
+    }
+
+    /// Moves the position back before `c`, which was the character
+    /// before it. The line position is lost if `c` ended a line.
+    pub fn retreat(&mut self, c: char) {
+        self.char_num = self.char_num.saturating_sub(1);
+        self.byte_num = self.byte_num.saturating_sub(c.len_utf8());
+
+        if c == '\n' {
+            self.line_num = self.line_num.saturating_sub(1);
+        } else {
+            self.line_pos = self.line_pos.saturating_sub(1);
+        }
     }
 }
 
@@ -118,17 +209,94 @@ pub fn refresh_buffer(&mut self) -> io::Result<&[u8]> {
         // to tell the compiler that the pos..cap slice is always valid.
         if self.pos >= self.buf.len() {
             self.buf.clear();
+            self.pos = 0;
 
-            let mut word = [0u8; std::mem::size_of::<char>()];
-            let nread = self.inner.read(&mut word)?;
+            if self.encoding != Encoding::Utf8 {
+                if let Some(c) = self.decode_char()? {
+                    self.buf
+                        .extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+            } else {
+                let mut word = [0u8; std::mem::size_of::<char>()];
+                let nread = self.inner.read(&mut word)?;
 
-            self.buf.extend_from_slice(&word[..nread]);
-            self.pos = 0;
+                self.buf.extend_from_slice(&word[..nread]);
+            }
         }
 
         Ok(&self.buf[self.pos..])
     }
 
+    // fills as much of buf as the stream has bytes left for,
+    // returning the number of bytes read.
+    fn read_inner_bytes(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut nread = 0;
+
+        while nread < buf.len() {
+            match self.inner.read(&mut buf[nread..]) {
+                Ok(0) => break,
+                Ok(n) => nread += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(nread)
+    }
+
+    // the next UTF-16 code unit, unless the stream has ended.
+    fn read_utf16_unit(&mut self) -> io::Result<Option<u16>> {
+        let mut bytes = [0u8; 2];
+
+        match self.read_inner_bytes(&mut bytes)? {
+            0 => Ok(None),
+            1 => Err(bad_encoding_error(self.encoding, &bytes[..1])),
+            _ if self.encoding == Encoding::Utf16BE => Ok(Some(u16::from_be_bytes(bytes))),
+            _ => Ok(Some(u16::from_le_bytes(bytes))),
+        }
+    }
+
+    // decodes the next character of a stream whose encoding isn't
+    // UTF-8, unless the stream has ended.
+    fn decode_char(&mut self) -> io::Result<Option<char>> {
+        match self.encoding {
+            Encoding::Utf16BE | Encoding::Utf16LE => {
+                let unit = match self.read_utf16_unit()? {
+                    Some(unit) => unit,
+                    None => return Ok(None),
+                };
+
+                let c = if (0xd800..0xdc00).contains(&unit) {
+                    match self.read_utf16_unit()? {
+                        Some(low @ 0xdc00..=0xdfff) => char::from_u32(
+                            0x10000 + ((unit as u32 - 0xd800) << 10) + (low as u32 - 0xdc00),
+                        ),
+                        _ => None,
+                    }
+                } else {
+                    char::from_u32(unit as u32)
+                };
+
+                match c {
+                    Some(c) => Ok(Some(c)),
+                    None => Err(bad_encoding_error(self.encoding, &unit.to_be_bytes())),
+                }
+            }
+            encoding => {
+                let mut byte = [0u8; 1];
+
+                if self.read_inner_bytes(&mut byte)? == 0 {
+                    return Ok(None);
+                }
+
+                match encoding.byte_char(byte[0]) {
+                    Some(c) => Ok(Some(c)),
+                    None => Err(bad_encoding_error(encoding, &byte)),
+                }
+            }
+        }
+    }
+
     pub fn peek_byte(&mut self) -> Option<io::Result<u8>> {
         match self.refresh_buffer() {
             Ok(_buf) => _buf.first().cloned().map(Ok),
@@ -139,7 +307,13 @@ pub fn peek_byte(&mut self) -> Option<io::Result<u8>> {
 
 impl<R: Read> CharRead for CharReader<R> {
     fn peek_char(&mut self) -> Option<io::Result<char>> {
+        let decoded = self.encoding != Encoding::Utf8;
+
         match self.refresh_buffer() {
+            Ok(buf) if decoded => {
+                // the buffer holds decoded characters.
+                return str::from_utf8(buf).ok()?.chars().next().map(Ok);
+            }
             Ok(_buf) => {}
             Err(e) => return Some(Err(e)),
         }
@@ -378,6 +552,31 @@ mod tests {
     use crate::parser::char_reader::*;
     use std::io::Cursor;
 
+    #[test]
+    fn read_positions_count_chars_bytes_and_lines() {
+        let mut position = ReadPosition::default();
+
+        for c in "ab\nλx".chars() {
+            position.advance(c);
+        }
+
+        assert_eq!(
+            position,
+            ReadPosition {
+                char_num: 5,
+                byte_num: 6,
+                line_num: 1,
+                line_pos: 2,
+            }
+        );
+
+        position.retreat('x');
+
+        assert_eq!(position.char_num, 4);
+        assert_eq!(position.byte_num, 5);
+        assert_eq!(position.line_pos, 1);
+    }
+
     #[test]
     #[cfg_attr(miri, ignore = "slow and not very relevant")]
     fn plain_string() {
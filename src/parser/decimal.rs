@@ -0,0 +1,312 @@
+use crate::parser::ast::DecimalRounding;
+use crate::parser::dashu::base::{BitTest, Sign, Signed, UnsignedAbs};
+use crate::parser::dashu::{Decimal as DBig, Integer, Natural, Rational};
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+/// A decimal number of arbitrary precision, read and written as
+/// `1.50d`. It keeps its scale, the number of digits after its
+/// decimal point, so it's written as it was read: `1.50d` and `1.5d`
+/// are equal numbers but different terms.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decimal {
+    // always of unlimited precision, so that sums, differences and
+    // products are exact.
+    value: DBig,
+    scale: usize,
+}
+
+impl Hash for Decimal {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self.value.repr().significand().hash(hasher);
+        self.value.repr().exponent().hash(hasher);
+        self.scale.hash(hasher);
+    }
+}
+
+fn power_of_ten(n: usize) -> Natural {
+    Natural::from(10u8).pow(n)
+}
+
+/// Rounds r to an integer in the direction of rounding.
+pub fn round_rational(r: &Rational, rounding: DecimalRounding) -> Integer {
+    let floor = r.floor();
+    let fract = r - Rational::from(floor.clone());
+
+    if fract.is_zero() {
+        return floor;
+    }
+
+    let ceiling = &floor + Integer::ONE;
+    let half = Rational::from_parts(Integer::ONE, Natural::from(2u8));
+    let away_from_zero = |floor, ceiling| if r.is_negative() { floor } else { ceiling };
+
+    match rounding {
+        DecimalRounding::ToNegative => floor,
+        DecimalRounding::ToPositive => ceiling,
+        DecimalRounding::ToZero => {
+            if r.is_negative() {
+                ceiling
+            } else {
+                floor
+            }
+        }
+        DecimalRounding::AwayFromZero => away_from_zero(floor, ceiling),
+        DecimalRounding::HalfEven | DecimalRounding::HalfAwayFromZero => match fract.cmp(&half) {
+            Ordering::Less => floor,
+            Ordering::Greater => ceiling,
+            Ordering::Equal if rounding == DecimalRounding::HalfAwayFromZero => {
+                away_from_zero(floor, ceiling)
+            }
+            Ordering::Equal => {
+                if floor.bit(0) {
+                    ceiling
+                } else {
+                    floor
+                }
+            }
+        },
+    }
+}
+
+impl Decimal {
+    /// The decimal significand / 10^scale.
+    pub fn new(significand: Integer, scale: usize) -> Self {
+        let value = DBig::from_parts(significand, -(scale as isize));
+
+        Decimal {
+            value: value.with_precision(0).value(),
+            scale,
+        }
+    }
+
+    /// Reads the digits of a decimal, without its `d` suffix, as in
+    /// `12.50`.
+    pub fn parse(digits: &str) -> Option<Self> {
+        let (integer_part, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        let significand = format!("{integer_part}{fraction}").parse().ok()?;
+
+        Some(Decimal::new(significand, fraction.len()))
+    }
+
+    /// The decimal r rounded to scale digits after the decimal point.
+    pub fn from_rational(r: &Rational, scale: usize, rounding: DecimalRounding) -> Self {
+        let scaled = r * Rational::from(power_of_ten(scale));
+        Decimal::new(round_rational(&scaled, rounding), scale)
+    }
+
+    /// The decimal r, exact if it has at most precision digits after
+    /// its decimal point and then of the fewest digits at least
+    /// min_scale, and otherwise rounded to precision digits.
+    pub fn from_rational_with_precision(
+        r: &Rational,
+        min_scale: usize,
+        precision: usize,
+        rounding: DecimalRounding,
+    ) -> Self {
+        let scale = std::cmp::max(min_scale, precision);
+        let scaled = r * Rational::from(power_of_ten(scale));
+
+        if !scaled.is_int() {
+            return Decimal::new(round_rational(&scaled, rounding), scale);
+        }
+
+        let mut significand = scaled.floor();
+        let mut scale = scale;
+        let ten = Integer::from(10u8);
+
+        while scale > min_scale && (&significand % &ten).is_zero() {
+            significand /= &ten;
+            scale -= 1;
+        }
+
+        Decimal::new(significand, scale)
+    }
+
+    #[inline]
+    pub fn scale(&self) -> usize {
+        self.scale
+    }
+
+    #[inline]
+    pub fn value(&self) -> &DBig {
+        &self.value
+    }
+
+    /// The integer self * 10^scale.
+    pub fn significand(&self) -> Integer {
+        let repr = self.value.repr();
+        let shift = repr.exponent() + self.scale as isize;
+
+        // the scale always suffices for the digits of the value.
+        repr.significand() * Integer::from(power_of_ten(shift as usize))
+    }
+
+    pub fn to_rational(&self) -> Rational {
+        Rational::from_parts(self.significand(), power_of_ten(self.scale))
+    }
+
+    /// The same number with scale digits after the decimal point,
+    /// rounded if there are fewer than before.
+    pub fn rescale(&self, scale: usize, rounding: DecimalRounding) -> Self {
+        if scale >= self.scale {
+            Decimal {
+                value: self.value.clone(),
+                scale,
+            }
+        } else {
+            Decimal::from_rational(&self.to_rational(), scale, rounding)
+        }
+    }
+
+    #[inline]
+    pub fn sign(&self) -> Sign {
+        self.value.repr().significand().sign()
+    }
+
+    #[inline]
+    pub fn is_zero(&self) -> bool {
+        self.value.repr().significand().is_zero()
+    }
+
+    #[inline]
+    pub fn is_positive(&self) -> bool {
+        !self.is_zero() && self.sign() == Sign::Positive
+    }
+
+    #[inline]
+    pub fn is_negative(&self) -> bool {
+        !self.is_zero() && self.sign() == Sign::Negative
+    }
+
+    pub fn add(&self, rhs: &Decimal) -> Self {
+        Decimal {
+            value: &self.value + &rhs.value,
+            scale: std::cmp::max(self.scale, rhs.scale),
+        }
+    }
+
+    pub fn sub(&self, rhs: &Decimal) -> Self {
+        Decimal {
+            value: &self.value - &rhs.value,
+            scale: std::cmp::max(self.scale, rhs.scale),
+        }
+    }
+
+    pub fn mul(&self, rhs: &Decimal) -> Self {
+        Decimal {
+            value: &self.value * &rhs.value,
+            scale: self.scale + rhs.scale,
+        }
+    }
+
+    pub fn neg(&self) -> Self {
+        Decimal {
+            value: -self.value.clone(),
+            scale: self.scale,
+        }
+    }
+
+    pub fn abs(&self) -> Self {
+        if self.is_negative() {
+            self.neg()
+        } else {
+            self.clone()
+        }
+    }
+
+    /// Compares the values of the decimals and then their scales, as
+    /// the standard order of terms does.
+    pub fn cmp_as_terms(&self, rhs: &Decimal) -> Ordering {
+        self.value
+            .cmp(&rhs.value)
+            .then_with(|| self.scale.cmp(&rhs.scale))
+    }
+}
+
+impl From<Integer> for Decimal {
+    #[inline]
+    fn from(n: Integer) -> Self {
+        Decimal::new(n, 0)
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let significand = self.significand();
+        let is_negative = significand.is_negative();
+        let digits = significand.unsigned_abs().to_string();
+        let digits = format!("{:0>width$}", digits, width = self.scale + 1);
+        let (integer_part, fraction) = digits.split_at(digits.len() - self.scale);
+
+        if is_negative {
+            write!(f, "-")?;
+        }
+
+        if fraction.is_empty() {
+            write!(f, "{}d", integer_part)
+        } else {
+            write!(f, "{}.{}d", integer_part, fraction)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decimals_are_written_as_read() {
+        for digits in [
+            "1.50",
+            "0.001",
+            "100",
+            "-2.50",
+            "123456789012345678901234567890.0",
+        ] {
+            let d = Decimal::parse(digits.trim_start_matches('-')).unwrap();
+            let d = if digits.starts_with('-') { d.neg() } else { d };
+
+            assert_eq!(d.to_string(), format!("{digits}d"));
+        }
+    }
+
+    #[test]
+    fn decimal_arithmetic_keeps_scales() {
+        let a = Decimal::parse("1.10").unwrap();
+        let b = Decimal::parse("2.205").unwrap();
+
+        assert_eq!(a.add(&b).to_string(), "3.305d");
+        assert_eq!(a.sub(&b).to_string(), "-1.105d");
+        assert_eq!(a.mul(&b).to_string(), "2.42550d");
+        assert_eq!(
+            a.rescale(4, DecimalRounding::HalfEven).to_string(),
+            "1.1000d"
+        );
+        assert_eq!(b.rescale(2, DecimalRounding::HalfEven).to_string(), "2.20d");
+        assert_eq!(
+            b.rescale(2, DecimalRounding::HalfAwayFromZero).to_string(),
+            "2.21d"
+        );
+    }
+
+    #[test]
+    fn rationals_round_to_decimals() {
+        let third = Rational::from_parts(Integer::ONE, Natural::from(3u8));
+        let quarter = Rational::from_parts(Integer::ONE, Natural::from(4u8));
+
+        let d = Decimal::from_rational_with_precision(&third, 0, 5, DecimalRounding::HalfEven);
+        assert_eq!(d.to_string(), "0.33333d");
+
+        let d = Decimal::from_rational_with_precision(&quarter, 0, 5, DecimalRounding::HalfEven);
+        assert_eq!(d.to_string(), "0.25d");
+
+        let d = Decimal::from_rational_with_precision(&quarter, 3, 5, DecimalRounding::HalfEven);
+        assert_eq!(d.to_string(), "0.250d");
+
+        let d = Decimal::from_rational(&-third, 2, DecimalRounding::ToNegative);
+        assert_eq!(d.to_string(), "-0.34d");
+    }
+}
@@ -6,6 +6,7 @@
 use crate::parser::ast::*;
 use crate::parser::char_reader::*;
 use crate::parser::dashu::Integer;
+use crate::parser::decimal::Decimal;
 
 use std::convert::TryFrom;
 use std::fmt;
@@ -57,6 +58,14 @@ pub struct Lexer<'a, R> {
     pub(crate) machine_st: &'a mut MachineState,
     pub(crate) line_num: usize,
     pub(crate) col_num: usize,
+    /// The position of the next character in the stream.
+    pub(crate) position: ReadPosition,
+    /// The position of the last token read.
+    pub(crate) token_start: ReadPosition,
+    /// The comments read and their positions, if they are kept.
+    pub(crate) comments: Option<Vec<(ReadPosition, String)>>,
+    // the comment being read, if comments are kept.
+    comment: Option<(ReadPosition, String)>,
 }
 
 impl<'a, R: fmt::Debug> fmt::Debug for Lexer<'a, R> {
@@ -76,6 +85,10 @@ pub fn new(src: R, machine_st: &'a mut MachineState) -> Self {
             machine_st,
             line_num: 0,
             col_num: 0,
+            position: ReadPosition::default(),
+            token_start: ReadPosition::default(),
+            comments: None,
+            comment: None,
         }
     }
 
@@ -88,7 +101,10 @@ pub fn lookahead_char(&mut self) -> Result<char, ParserError> {
 
     pub fn read_char(&mut self) -> Result<char, ParserError> {
         match self.reader.read_char() {
-            Some(Ok(c)) => Ok(c),
+            Some(Ok(c)) => {
+                self.position.advance(c);
+                Ok(c)
+            }
             _ => Err(ParserError::unexpected_eof()),
         }
     }
@@ -96,10 +112,20 @@ pub fn read_char(&mut self) -> Result<char, ParserError> {
     #[inline(always)]
     fn return_char(&mut self, c: char) {
         self.reader.put_back_char(c);
+        self.position.retreat(c);
+
+        if let Some((_, text)) = &mut self.comment {
+            text.pop();
+        }
     }
 
     pub fn skip_char(&mut self, c: char) {
         self.reader.consume(c.len_utf8());
+        self.position.advance(c);
+
+        if let Some((_, text)) = &mut self.comment {
+            text.push(c);
+        }
 
         if new_line_char!(c) {
             self.line_num += 1;
@@ -109,7 +135,27 @@ pub fn skip_char(&mut self, c: char) {
         }
     }
 
+    fn begin_comment(&mut self) {
+        if self.comments.is_some() {
+            self.comment = Some((self.position, String::new()));
+        }
+    }
+
+    fn end_comment(&mut self) {
+        if let (Some(comments), Some((position, mut text))) =
+            (&mut self.comments, self.comment.take())
+        {
+            if text.ends_with('\n') {
+                text.pop();
+            }
+
+            comments.push((position, text));
+        }
+    }
+
     fn single_line_comment(&mut self) -> Result<(), ParserError> {
+        self.begin_comment();
+
         loop {
             if self.reader.peek_char().is_none() {
                 break;
@@ -123,12 +169,15 @@ fn single_line_comment(&mut self) -> Result<(), ParserError> {
             }
         }
 
+        self.end_comment();
+
         Ok(())
     }
 
     fn bracketed_comment(&mut self) -> Result<bool, ParserError> {
         // we have already checked that the current lookahead_char is
         // comment_1_char, just skip it
+        self.begin_comment();
         self.skip_char('/');
 
         let c = self.lookahead_char()?;
@@ -177,11 +226,13 @@ fn bracketed_comment(&mut self) -> Result<bool, ParserError> {
 
             if prolog_char!(c) {
                 self.skip_char(c);
+                self.end_comment();
                 Ok(true)
             } else {
                 Err(ParserError::NonPrologChar(self.line_num, self.col_num))
             }
         } else {
+            self.comment = None;
             self.return_char('/');
             Ok(false)
         }
@@ -646,6 +697,81 @@ fn vacate_with_float(&mut self, mut token: String) -> Result<Token, ParserError>
         ))))
     }
 
+    // the float of token, made an infinity or a NaN by a following Inf
+    // or NaN as in 1.0Inf and 1.5NaN.
+    fn float_token(&mut self, token: &str) -> Result<Token, ParserError> {
+        const FRACTION_MASK: u64 = (1 << 52) - 1;
+
+        let mut n = parse_lossy::<f64, _>(token.as_bytes())?;
+
+        match self.float_suffix() {
+            Some("Inf") => {
+                n = f64::INFINITY;
+            }
+            Some(_) => {
+                let payload = n.to_bits() & FRACTION_MASK;
+
+                n = if payload == 0 {
+                    f64::NAN
+                } else {
+                    f64::from_bits(f64::INFINITY.to_bits() | payload)
+                };
+            }
+            None => {}
+        }
+
+        Ok(Token::Literal(Literal::from(float_alloc!(
+            n,
+            self.machine_st.arena
+        ))))
+    }
+
+    fn float_suffix(&mut self) -> Option<&'static str> {
+        let suffix = match self.lookahead_char() {
+            Ok('I') => "Inf",
+            Ok('N') => "NaN",
+            _ => return None,
+        };
+
+        for (idx, expected) in suffix.char_indices() {
+            match self.lookahead_char() {
+                Ok(c) if c == expected => self.skip_char(c),
+                _ => {
+                    for c in suffix[..idx].chars().rev() {
+                        self.return_char(c);
+                    }
+
+                    return None;
+                }
+            }
+        }
+
+        Some(suffix)
+    }
+
+    // the decimal of token, if it's followed by the d of 1.50d and 10d.
+    fn decimal_token(&mut self, token: &str) -> Option<Token> {
+        if self.lookahead_char().ok()? != 'd' {
+            return None;
+        }
+
+        self.skip_char('d');
+
+        match self.lookahead_char() {
+            Ok(c) if alpha_numeric_char!(c) => {
+                self.return_char('d');
+                None
+            }
+            _ => {
+                let d = Decimal::parse(token)?;
+                Some(Token::Literal(Literal::Decimal(arena_alloc!(
+                    d,
+                    &mut self.machine_st.arena
+                ))))
+            }
+        }
+    }
+
     fn skip_underscore_in_number(&mut self) -> Result<char, ParserError> {
         let mut c = self.lookahead_char()?;
 
@@ -677,6 +803,12 @@ pub fn number_token(&mut self, leading_c: char) -> Result<Token, ParserError> {
             c = self.skip_underscore_in_number()?;
         }
 
+        if c == 'd' {
+            if let Some(token) = self.decimal_token(&token) {
+                return Ok(token);
+            }
+        }
+
         if decimal_point_char!(c) {
             self.skip_char(c);
 
@@ -757,20 +889,14 @@ pub fn number_token(&mut self, leading_c: char) -> Result<Token, ParserError> {
                             }
                         }
 
-                        let n = parse_lossy::<f64, _>(token.as_bytes())?;
-                        Ok(Token::Literal(Literal::from(float_alloc!(
-                            n,
-                            self.machine_st.arena
-                        ))))
+                        self.float_token(&token)
                     } else {
                         return self.vacate_with_float(token);
                     }
+                } else if let Some(token) = self.decimal_token(&token) {
+                    Ok(token)
                 } else {
-                    let n = parse_lossy::<f64, _>(token.as_bytes())?;
-                    Ok(Token::Literal(Literal::from(float_alloc!(
-                        n,
-                        self.machine_st.arena
-                    ))))
+                    self.float_token(&token)
                 }
             } else {
                 self.return_char('.');
@@ -1006,6 +1132,8 @@ pub fn next_token(&mut self) -> Result<Token, ParserError> {
         let layout_inserted = self.scan_for_layout()?;
         let cr = self.lookahead_char();
 
+        self.token_start = self.position;
+
         match cr {
             Ok(c) => {
                 if capital_letter_char!(c) || variable_indicator_char!(c) {
@@ -6,6 +6,7 @@
 // extern crate static_assertions;
 
 pub mod char_reader;
+pub mod decimal;
 #[macro_use]
 pub mod ast;
 #[macro_use]
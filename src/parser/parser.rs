@@ -5,6 +5,7 @@
 use crate::atom_table::*;
 use crate::parser::ast::*;
 use crate::parser::char_reader::*;
+use crate::parser::decimal::Decimal;
 use crate::parser::lexer::*;
 
 use std::cell::Cell;
@@ -59,6 +60,7 @@ struct TokenDesc {
     priority: usize,
     spec: u32,
     unfold_bounds: usize,
+    span: (usize, usize),
 }
 
 pub(crate) fn as_partial_string(
@@ -258,38 +260,15 @@ pub struct CompositeOpDesc {
 pub struct Parser<'a, R> {
     pub lexer: Lexer<'a, R>,
     tokens: Vec<Token>,
+    // the character offsets each token of tokens starts and ends at.
+    token_spans: Vec<(usize, usize)>,
+    // the span of the token being shifted.
+    span: (usize, usize),
     stack: Vec<TokenDesc>,
     terms: Vec<Term>,
-}
-
-fn read_tokens<R: CharRead>(lexer: &mut Lexer<R>) -> Result<Vec<Token>, ParserError> {
-    let mut tokens = vec![];
-
-    loop {
-        match lexer.next_token() {
-            Ok(token) => {
-                let at_end = token.is_end();
-                tokens.push(token);
-
-                if at_end {
-                    break;
-                }
-            }
-            Err(e) if e.is_unexpected_eof() && !tokens.is_empty() => {
-                return Err(ParserError::IncompleteReduction(
-                    lexer.line_num,
-                    lexer.col_num,
-                ));
-            }
-            Err(e) => {
-                return Err(e);
-            }
-        }
-    }
-
-    tokens.reverse();
-
-    Ok(tokens)
+    // the positions of terms, if they are kept.
+    positions: Option<Vec<TermPosition>>,
+    term_start: ReadPosition,
 }
 
 fn atomize_term(atom_tbl: &AtomTable, term: &Term) -> Option<Atom> {
@@ -309,21 +288,108 @@ fn atomize_constant(atom_tbl: &AtomTable, c: Literal) -> Option<Atom> {
 
 impl<'a, R: CharRead> Parser<'a, R> {
     pub fn new(stream: R, machine_st: &'a mut MachineState) -> Self {
-        Parser {
-            lexer: Lexer::new(stream, machine_st),
-            tokens: vec![],
-            stack: vec![],
-            terms: vec![],
-        }
+        Parser::from_lexer(Lexer::new(stream, machine_st))
     }
 
     pub fn from_lexer(lexer: Lexer<'a, R>) -> Self {
         Parser {
             lexer,
             tokens: vec![],
+            token_spans: vec![],
+            span: (0, 0),
             stack: vec![],
             terms: vec![],
+            positions: None,
+            term_start: ReadPosition::default(),
+        }
+    }
+
+    /// Keeps the positions of the terms read and the comments read
+    /// with them.
+    pub fn keep_positions(&mut self) {
+        self.positions = Some(vec![]);
+        self.lexer.comments = Some(vec![]);
+    }
+
+    /// The position of the last term read, if positions are kept.
+    pub fn term_position(&mut self) -> Option<TermPosition> {
+        self.positions.as_mut().and_then(Vec::pop)
+    }
+
+    /// The position of the first token of the last term read.
+    #[inline]
+    pub fn term_start(&self) -> ReadPosition {
+        self.term_start
+    }
+
+    /// The comments read since the last call, if positions are kept.
+    pub fn take_comments(&mut self) -> Vec<(ReadPosition, String)> {
+        self.lexer
+            .comments
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    fn push_position(&mut self, position: TermPosition) {
+        if let Some(positions) = &mut self.positions {
+            positions.push(position);
+        }
+    }
+
+    fn pop_position(&mut self) -> TermPosition {
+        self.term_position().unwrap_or_default()
+    }
+
+    fn drain_positions(&mut self, n: usize) -> Vec<TermPosition> {
+        match &mut self.positions {
+            Some(positions) => {
+                let idx = positions.len().saturating_sub(n);
+                positions.drain(idx..).collect()
+            }
+            None => vec![],
+        }
+    }
+
+    fn read_tokens(&mut self) -> Result<(), ParserError> {
+        self.tokens.clear();
+        self.token_spans.clear();
+
+        loop {
+            match self.lexer.next_token() {
+                Ok(token) => {
+                    let at_end = token.is_end();
+
+                    if self.tokens.is_empty() {
+                        self.term_start = self.lexer.token_start;
+                    }
+
+                    self.tokens.push(token);
+                    self.token_spans.push((
+                        self.lexer.token_start.char_num,
+                        self.lexer.position.char_num,
+                    ));
+
+                    if at_end {
+                        break;
+                    }
+                }
+                Err(e) if e.is_unexpected_eof() && !self.tokens.is_empty() => {
+                    return Err(ParserError::IncompleteReduction(
+                        self.lexer.line_num,
+                        self.lexer.col_num,
+                    ));
+                }
+                Err(e) => {
+                    return Err(e);
+                }
+            }
         }
+
+        self.tokens.reverse();
+        self.token_spans.reverse();
+
+        Ok(())
     }
 
     fn sep_to_atom(&mut self, tt: TokenType) -> Option<Atom> {
@@ -351,12 +417,15 @@ pub fn col_num(&self) -> usize {
         self.lexer.col_num
     }
 
-    fn get_term_name(&mut self, td: TokenDesc) -> Option<Atom> {
+    // the name of the operator of td and the span of its token.
+    fn get_term_name(&mut self, td: TokenDesc) -> Option<(Atom, (usize, usize))> {
         match td.tt {
-            TokenType::HeadTailSeparator => Some(atom!("|")),
-            TokenType::Comma => Some(atom!(",")),
+            TokenType::HeadTailSeparator => Some((atom!("|"), td.span)),
+            TokenType::Comma => Some((atom!(","), td.span)),
             TokenType::Term => match self.terms.pop() {
-                Some(Term::Literal(_, Literal::Atom(atom))) => Some(atom),
+                Some(Term::Literal(_, Literal::Atom(atom))) => {
+                    Some((atom, self.pop_position().span()))
+                }
                 Some(term) => {
                     self.terms.push(term);
                     None
@@ -369,16 +438,27 @@ fn get_term_name(&mut self, td: TokenDesc) -> Option<Atom> {
 
     fn push_binary_op(&mut self, td: TokenDesc, spec: Specifier) {
         if let Some(arg2) = self.terms.pop() {
-            if let Some(name) = self.get_term_name(td) {
+            let pos2 = self.pop_position();
+
+            if let Some((name, functor)) = self.get_term_name(td) {
                 if let Some(arg1) = self.terms.pop() {
+                    let pos1 = self.pop_position();
+                    let span = (pos1.span().0, pos2.span().1);
                     let term = Term::Clause(Cell::default(), name, vec![arg1, arg2]);
 
                     self.terms.push(term);
+                    self.push_position(TermPosition::Compound {
+                        from: span.0,
+                        to: span.1,
+                        functor,
+                        args: vec![pos1, pos2],
+                    });
                     self.stack.push(TokenDesc {
                         tt: TokenType::Term,
                         priority: td.priority,
                         spec,
                         unfold_bounds: 0,
+                        span,
                     });
                 }
             }
@@ -387,20 +467,37 @@ fn push_binary_op(&mut self, td: TokenDesc, spec: Specifier) {
 
     fn push_unary_op(&mut self, td: TokenDesc, spec: Specifier, assoc: u32) {
         if let Some(mut arg1) = self.terms.pop() {
+            let mut arg_pos = self.pop_position();
+
             if let Some(mut name) = self.terms.pop() {
+                let mut name_pos = self.pop_position();
+
                 if is_postfix!(assoc) {
                     mem::swap(&mut arg1, &mut name);
+                    mem::swap(&mut arg_pos, &mut name_pos);
                 }
 
                 if let Term::Literal(_, Literal::Atom(name)) = name {
+                    let functor = name_pos.span();
+                    let span = (
+                        functor.0.min(arg_pos.span().0),
+                        functor.1.max(arg_pos.span().1),
+                    );
                     let term = Term::Clause(Cell::default(), name, vec![arg1]);
 
                     self.terms.push(term);
+                    self.push_position(TermPosition::Compound {
+                        from: span.0,
+                        to: span.1,
+                        functor,
+                        args: vec![arg_pos],
+                    });
                     self.stack.push(TokenDesc {
                         tt: TokenType::Term,
                         priority: td.priority,
                         spec,
                         unfold_bounds: 0,
+                        span,
                     });
                 }
             }
@@ -410,11 +507,13 @@ fn push_unary_op(&mut self, td: TokenDesc, spec: Specifier, assoc: u32) {
     fn promote_atom_op(&mut self, atom: Atom, priority: usize, assoc: u32) {
         self.terms
             .push(Term::Literal(Cell::default(), Literal::Atom(atom)));
+        self.push_position(TermPosition::Span(self.span.0, self.span.1));
         self.stack.push(TokenDesc {
             tt: TokenType::Term,
             priority,
             spec: assoc,
             unfold_bounds: 0,
+            span: self.span,
         });
     }
 
@@ -437,16 +536,19 @@ fn shift(&mut self, token: Token, priority: usize, spec: Specifier) {
                 }
 
                 self.terms.push(list);
+                self.push_position(TermPosition::String(self.span.0, self.span.1));
                 TokenType::Term
             }
             Token::Literal(Literal::String(s))
                 if self.lexer.machine_st.flags.double_quotes.is_chars() =>
             {
                 self.terms.push(Term::CompleteString(Cell::default(), s));
+                self.push_position(TermPosition::String(self.span.0, self.span.1));
                 TokenType::Term
             }
             Token::Literal(c) => {
                 self.terms.push(Term::Literal(Cell::default(), c));
+                self.push_position(TermPosition::Span(self.span.0, self.span.1));
                 TokenType::Term
             }
             Token::Var(v) => {
@@ -456,6 +558,7 @@ fn shift(&mut self, token: Token, priority: usize, spec: Specifier) {
                     self.terms.push(Term::Var(Cell::default(), VarPtr::from(v)));
                 }
 
+                self.push_position(TermPosition::Span(self.span.0, self.span.1));
                 TokenType::Term
             }
             Token::Comma => TokenType::Comma,
@@ -475,6 +578,7 @@ fn shift(&mut self, token: Token, priority: usize, spec: Specifier) {
             priority,
             spec,
             unfold_bounds: 0,
+            span: self.span,
         });
     }
 
@@ -589,12 +693,22 @@ fn reduce_term(&mut self) -> bool {
             self.stack.truncate(stack_len + 1);
 
             let mut subterms: Vec<_> = self.terms.drain(idx..).collect();
+            let args = self.drain_positions(arity);
 
             if let Some(name) = self
                 .terms
                 .pop()
                 .and_then(|t| atomize_term(&self.lexer.machine_st.atom_tbl, &t))
             {
+                let functor = self.pop_position().span();
+
+                self.push_position(TermPosition::Compound {
+                    from: functor.0,
+                    to: self.span.1,
+                    functor,
+                    args,
+                });
+
                 // reduce the '.' functor to a cons cell if it applies.
                 if name == atom!(".") && subterms.len() == 2 {
                     let tail = subterms.pop().unwrap();
@@ -621,6 +735,7 @@ fn reduce_term(&mut self) -> bool {
                     ref mut priority,
                     ref mut spec,
                     ref mut unfold_bounds,
+                    ref mut span,
                 }) = self.stack.last_mut()
                 {
                     if *spec == BTERM {
@@ -631,6 +746,7 @@ fn reduce_term(&mut self) -> bool {
                     *priority = 0;
                     *spec = TERM;
                     *unfold_bounds = 0;
+                    *span = (functor.0, self.span.1);
                 }
 
                 return true;
@@ -653,6 +769,7 @@ fn expand_comma_compacted_terms(&mut self, index: usize) -> usize {
                  * an operator, so expand the
                  * terms it compacted out again. */
                 if let (Some(atom!(",")), 2) = (term.name(), term.arity()) {
+                    let position = self.pop_position();
                     let terms = if op_desc.unfold_bounds == 0 {
                         unfold_by_str(term, atom!(","))
                     } else {
@@ -676,6 +793,26 @@ fn expand_comma_compacted_terms(&mut self, index: usize) -> usize {
                     let arity = terms.len() - 1;
 
                     self.terms.extend(terms);
+
+                    if self.positions.is_some() {
+                        let mut position = position;
+
+                        for _ in 0..arity {
+                            match position.unfold_comma() {
+                                Some((fst, snd)) => {
+                                    self.push_position(fst);
+                                    position = snd;
+                                }
+                                None => {
+                                    position = TermPosition::default();
+                                    self.push_position(TermPosition::default());
+                                }
+                            }
+                        }
+
+                        self.push_position(position);
+                    }
+
                     return arity;
                 }
             }
@@ -724,9 +861,13 @@ fn reduce_list(&mut self) -> Result<bool, ParserError> {
                 td.spec = TERM;
                 td.tt = TokenType::Term;
                 td.priority = 0;
+                td.span.1 = self.span.1;
+
+                let span = td.span;
 
                 self.terms
                     .push(Term::Literal(Cell::default(), Literal::Atom(atom!("[]"))));
+                self.push_position(TermPosition::Span(span.0, span.1));
                 return Ok(true);
             }
         }
@@ -742,6 +883,8 @@ fn reduce_list(&mut self) -> Result<bool, ParserError> {
         let idx = self.stack.len() - 2;
         let list_len = self.stack.len() - 2 * arity;
 
+        let mut tail_position = None;
+
         let end_term = if self.stack[idx].tt != TokenType::HeadTailSeparator {
             Term::Literal(Cell::default(), Literal::Atom(atom!("[]")))
         } else {
@@ -755,6 +898,8 @@ fn reduce_list(&mut self) -> Result<bool, ParserError> {
                 }
             };
 
+            tail_position = Some(Box::new(self.pop_position()));
+
             if self.stack[idx].priority > 1000 {
                 arity += self.expand_comma_compacted_terms(idx);
             }
@@ -777,6 +922,10 @@ fn reduce_list(&mut self) -> Result<bool, ParserError> {
             Term::Cons(Cell::default(), Box::new(t), Box::new(acc))
         });
 
+        let span = (self.stack[list_len].span.0, self.span.1);
+        let elems = self.drain_positions(arity);
+
+        self.push_position(TermPosition::List(span.0, span.1, elems, tail_position));
         self.stack.truncate(list_len);
 
         self.stack.push(TokenDesc {
@@ -784,6 +933,7 @@ fn reduce_list(&mut self) -> Result<bool, ParserError> {
             priority: 0,
             spec: TERM,
             unfold_bounds: 0,
+            span,
         });
 
         self.terms.push(match list {
@@ -813,10 +963,13 @@ fn reduce_curly(&mut self) -> Result<bool, ParserError> {
                 td.tt = TokenType::Term;
                 td.priority = 0;
                 td.spec = TERM;
+                td.span.1 = self.span.1;
 
+                let span = td.span;
                 let term = Term::Literal(Cell::default(), Literal::Atom(atom!("{}")));
 
                 self.terms.push(term);
+                self.push_position(TermPosition::Span(span.0, span.1));
                 return Ok(true);
             }
         }
@@ -834,6 +987,9 @@ fn reduce_curly(&mut self) -> Result<bool, ParserError> {
                         oc.tt = TokenType::Term;
                         oc.priority = 0;
                         oc.spec = TERM;
+                        oc.span.1 = self.span.1;
+
+                        let span = oc.span;
 
                         let term = match self.terms.pop() {
                             Some(term) => term,
@@ -845,8 +1001,11 @@ fn reduce_curly(&mut self) -> Result<bool, ParserError> {
                             }
                         };
 
+                        let position = self.pop_position();
+
                         self.terms
                             .push(Term::Clause(Cell::default(), atom!("{}"), vec![term]));
+                        self.push_position(TermPosition::Brace(span.0, span.1, Box::new(position)));
 
                         return Ok(true);
                     }
@@ -882,13 +1041,25 @@ fn reduce_brackets(&mut self) -> bool {
                 }
 
                 if let Some(atom) = self.sep_to_atom(self.stack[idx].tt) {
+                    let span = self.stack[idx].span;
+
                     self.terms
                         .push(Term::Literal(Cell::default(), Literal::Atom(atom)));
+                    self.push_position(TermPosition::Span(span.0, span.1));
+                } else {
+                    let position = self.pop_position();
+
+                    self.push_position(TermPosition::Parens(
+                        td.span.0,
+                        self.span.1,
+                        Box::new(position),
+                    ));
                 }
 
                 self.stack[idx].spec = BTERM;
                 self.stack[idx].tt = TokenType::Term;
                 self.stack[idx].priority = 0;
+                self.stack[idx].span = (td.span.0, self.span.1);
 
                 true
             }
@@ -960,6 +1131,8 @@ fn negate_number<N, Negator, ToLiteral>(&mut self, n: N, negator: Negator, const
                         self.stack.pop();
                         self.terms.pop();
 
+                        self.span.0 = self.pop_position().span().0;
+
                         let arena = &mut self.lexer.machine_st.arena;
                         let literal = constr(negator(n, arena), arena);
 
@@ -989,6 +1162,11 @@ fn negate_rat_rc(t: TypedArenaPtr<Rational>, arena: &mut Arena) -> TypedArenaPtr
             arena_alloc!(data, arena)
         }
 
+        fn negate_dec_rc(t: TypedArenaPtr<Decimal>, arena: &mut Arena) -> TypedArenaPtr<Decimal> {
+            let data = t.neg();
+            arena_alloc!(data, arena)
+        }
+
         match token {
             Token::Literal(Literal::Fixnum(n)) => {
                 self.negate_number(n, |n, _| -n, |n, _| Literal::Fixnum(n))
@@ -999,6 +1177,9 @@ fn negate_rat_rc(t: TypedArenaPtr<Rational>, arena: &mut Arena) -> TypedArenaPtr
             Token::Literal(Literal::Rational(n)) => {
                 self.negate_number(n, negate_rat_rc, |r, _| Literal::Rational(r))
             }
+            Token::Literal(Literal::Decimal(n)) => {
+                self.negate_number(n, negate_dec_rc, |d, _| Literal::Decimal(d))
+            }
             Token::Literal(Literal::Float(n)) => self.negate_number(
                 **n.as_ptr(),
                 |n, _| -n,
@@ -1103,12 +1284,20 @@ pub fn read_term(
         op_dir: &CompositeOpDir,
         tokens: Tokens,
     ) -> Result<Term, ParserError> {
-        self.tokens = match tokens {
-            Tokens::Default => read_tokens(&mut self.lexer)?,
-            Tokens::Provided(tokens) => tokens,
-        };
+        match tokens {
+            Tokens::Default => self.read_tokens()?,
+            Tokens::Provided(tokens) => {
+                self.tokens = tokens;
+                self.token_spans.clear();
+            }
+        }
+
+        if let Some(positions) = &mut self.positions {
+            positions.clear();
+        }
 
         while let Some(token) = self.tokens.pop() {
+            self.span = self.token_spans.pop().unwrap_or_default();
             self.shift_token(token, op_dir)?;
         }
 
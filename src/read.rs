@@ -23,6 +23,7 @@
 #[cfg(feature = "repl")]
 use rustyline::{Config, Editor};
 
+use std::cell::Cell;
 use std::collections::VecDeque;
 use std::io::{Cursor, Read};
 #[cfg(feature = "repl")]
@@ -59,28 +60,122 @@ pub(crate) fn error_after_read_term<R>(
     CompilationError::from(err)
 }
 
+/// The positions of a term read by `read_term/3`: of its subterms, of
+/// its first token and of the comments read with it.
+#[derive(Debug)]
+pub(crate) struct ReadTermPositions {
+    pub(crate) subterms: TermPosition,
+    pub(crate) start: ReadPosition,
+    pub(crate) comments: Vec<(ReadPosition, String)>,
+}
+
+fn stream_position_term(position: ReadPosition) -> Term {
+    let offset = |n: usize| {
+        Term::Literal(
+            Cell::default(),
+            Literal::Fixnum(Fixnum::build_with(n as i64)),
+        )
+    };
+
+    Term::Clause(
+        Cell::default(),
+        atom!("$stream_position"),
+        vec![
+            offset(position.char_num),
+            offset(position.line_num + 1),
+            offset(position.line_pos),
+            offset(position.byte_num),
+        ],
+    )
+}
+
+impl ReadTermPositions {
+    /// The positions as `positions(SubtermPositions, TermPosition,
+    /// Comments)`, where the positions of the stream are
+    /// `'$stream_position'(CharNo, LineNo, LinePos, ByteNo)` and the
+    /// comments are pairs `StreamPosition-String`.
+    pub(crate) fn to_term(&self, atom_tbl: &AtomTable) -> Term {
+        let comments = self.comments.iter().rev().fold(
+            Term::Literal(Cell::default(), Literal::Atom(atom!("[]"))),
+            |tail, (position, text)| {
+                let text = if text.is_empty() {
+                    Term::Literal(Cell::default(), Literal::Atom(atom!("[]")))
+                } else {
+                    Term::CompleteString(Cell::default(), AtomTable::build_with(atom_tbl, text))
+                };
+
+                let comment = Term::Clause(
+                    Cell::default(),
+                    atom!("-"),
+                    vec![stream_position_term(*position), text],
+                );
+
+                Term::Cons(Cell::default(), Box::new(comment), Box::new(tail))
+            },
+        );
+
+        Term::Clause(
+            Cell::default(),
+            atom!("positions"),
+            vec![
+                self.subterms.to_term(),
+                stream_position_term(self.start),
+                comments,
+            ],
+        )
+    }
+}
+
 impl MachineState {
     pub(crate) fn read(
         &mut self,
-        mut inner: Stream,
+        inner: Stream,
         op_dir: &OpDir,
     ) -> Result<TermWriteResult, CompilationError> {
-        let (term, num_lines_read) = {
+        self.read_with_positions(inner, op_dir, false)
+            .map(|(term_write_result, _)| term_write_result)
+    }
+
+    /// Reads a term from `inner`, and its positions if `keep_positions`
+    /// is set.
+    pub(crate) fn read_with_positions(
+        &mut self,
+        mut inner: Stream,
+        op_dir: &OpDir,
+        keep_positions: bool,
+    ) -> Result<(TermWriteResult, Option<ReadTermPositions>), CompilationError> {
+        let (term, num_lines_read, positions) = {
             let prior_num_lines_read = inner.lines_read();
             let mut parser = Parser::new(inner, self);
             let op_dir = CompositeOpDir::new(op_dir, None);
 
             parser.add_lines_read(prior_num_lines_read);
+            parser.lexer.position = inner.read_position();
+
+            if keep_positions {
+                parser.keep_positions();
+            }
+
+            let result = parser.read_term(&op_dir, Tokens::Default);
 
-            let term = parser
-                .read_term(&op_dir, Tokens::Default)
-                .map_err(|err| error_after_read_term(err, prior_num_lines_read, &parser))?; // CompilationError::from
+            inner.set_read_position(parser.lexer.position);
 
-            (term, parser.lines_read() - prior_num_lines_read)
+            let term =
+                result.map_err(|err| error_after_read_term(err, prior_num_lines_read, &parser))?; // CompilationError::from
+
+            let positions = parser.term_position().map(|subterms| ReadTermPositions {
+                subterms,
+                start: parser.term_start(),
+                comments: parser.take_comments(),
+            });
+
+            (term, parser.lines_read() - prior_num_lines_read, positions)
         };
 
         inner.add_lines_read(num_lines_read);
-        write_term_to_heap(&term, &mut self.heap, &self.atom_tbl)
+
+        let term_write_result = write_term_to_heap(&term, &mut self.heap, &self.atom_tbl)?;
+        Ok((term_write_result, positions))
     }
 }
 
@@ -301,12 +396,33 @@ pub(crate) fn write_term_to_heap(
     term_writer.write_term_to_heap(term)
 }
 
+/// Writes `term` to the heap with the variables keyed in `bindings`
+/// replaced by the cells they map to. Bound variables are left out of
+/// the `var_dict` of the result. `var_dict` holds variables already
+/// written to the heap, which those of `term` with the same names
+/// share.
+pub(crate) fn write_term_to_heap_with_bindings(
+    term: &Term,
+    heap: &mut Heap,
+    atom_tbl: &AtomTable,
+    bindings: HeapVarDict,
+    var_dict: HeapVarDict,
+) -> Result<TermWriteResult, CompilationError> {
+    let mut term_writer = TermWriter::new(heap, atom_tbl);
+
+    term_writer.bindings = bindings;
+    term_writer.var_dict = var_dict;
+
+    term_writer.write_term_to_heap(term)
+}
+
 #[derive(Debug)]
 struct TermWriter<'a, 'b> {
     heap: &'a mut Heap,
     atom_tbl: &'b AtomTable,
     queue: SubtermDeque,
     var_dict: HeapVarDict,
+    bindings: HeapVarDict,
 }
 
 #[derive(Debug)]
@@ -323,6 +439,7 @@ fn new(heap: &'a mut Heap, atom_tbl: &'b AtomTable) -> Self {
             atom_tbl,
             queue: SubtermDeque::new(),
             var_dict: HeapVarDict::with_hasher(FxBuildHasher::default()),
+            bindings: HeapVarDict::with_hasher(FxBuildHasher::default()),
         }
     }
 
@@ -422,8 +539,17 @@ fn write_term_to_heap(mut self, term: &Term) -> Result<TermWriteResult, Compilat
                     self.heap.push(addr);
                 }
                 &TermRef::Var(Level::Root, _, ref var_ptr) => {
-                    let addr = self.term_as_addr(&term, h);
-                    self.var_dict.insert(VarKey::VarPtr(var_ptr.clone()), addr);
+                    let var_key = VarKey::VarPtr(var_ptr.clone());
+
+                    let addr = match self.bindings.get(&var_key).or(self.var_dict.get(&var_key)) {
+                        Some(addr) => *addr,
+                        None => {
+                            let addr = self.term_as_addr(&term, h);
+                            self.var_dict.insert(var_key, addr);
+                            addr
+                        }
+                    };
+
                     self.heap.push(addr);
                 }
                 &TermRef::AnonVar(_) => {
@@ -462,7 +588,9 @@ fn write_term_to_heap(mut self, term: &Term) -> Result<TermWriteResult, Compilat
                     if let Some((arity, site_h)) = self.queue.pop_front() {
                         let var_key = VarKey::VarPtr(var.clone());
 
-                        if let Some(addr) = self.var_dict.get(&var_key).cloned() {
+                        if let Some(addr) = self.bindings.get(&var_key).cloned() {
+                            self.heap[site_h] = addr;
+                        } else if let Some(addr) = self.var_dict.get(&var_key).cloned() {
                             self.heap[site_h] = addr;
                         } else {
                             self.var_dict.insert(var_key, heap_loc_as_cell!(site_h));
@@ -0,0 +1,145 @@
+:- module(arithmetic_functions_tests, []).
+
+:- use_module(library(arithmetic)).
+:- use_module(library(between)).
+:- use_module(library(lists)).
+
+:- use_module(test_framework).
+
+% compiled, rather than metacalled, uses of the functions.
+bits(X, Msb, Lsb, Count) :-
+    Msb is msb(X),
+    Lsb is lsb(X),
+    Count is popcount(X).
+
+modular_power(B, E, M, R) :-
+    R is powm(B, E, M).
+
+random_below(N, R) :-
+    R is random(N).
+
+unit_random(R) :-
+    R is random_float.
+
+naturals_below(N, Rs) :-
+    maplist(natural_below(N), Rs).
+
+natural_below(N, R) :-
+    integer(R),
+    R >= 0,
+    R < N.
+
+unit_floats(Fs) :-
+    maplist(unit_float, Fs).
+
+unit_float(F) :-
+    float(F),
+    F > 0.0,
+    F < 1.0.
+
+degrees(X, S, C) :-
+    S is sind(X),
+    C is cosd(X).
+
+test("msb, lsb and popcount of fixnums and bignums", (
+    bits(1000, 9, 3, 6),
+    bits(1, 0, 0, 1),
+    X is 2^100 - 2^10,
+    bits(X, 99, 10, 90),
+    M is msb(2^200), M =:= 200,
+    P is popcount(2^64 - 1), P =:= 64
+)).
+
+test("msb, lsb and popcount raise errors outside their domains", (
+    throws(bits(0, _, _, _), error(domain_error(not_less_than_one, 0), _)),
+    throws(_ is lsb(-4), error(domain_error(not_less_than_one, -4), _)),
+    throws(_ is popcount(-1), error(domain_error(not_less_than_zero, -1), _)),
+    throws(_ is msb(1.0), error(type_error(integer, 1.0), _))
+)).
+
+test("powm agrees with the power and the modulus", (
+    modular_power(3, 100, 7, 4),
+    R is 3^100 mod 7, R =:= 4,
+    modular_power(2, 10, 1000, 24),
+    modular_power(5, 0, 1, 0),
+    modular_power(3, 100, -7, -3),
+    B is 2^100 + 1,
+    modular_power(B, 3, 2^64, R1),
+    R1 =:= (B^3) mod 2^64
+)).
+
+test("powm raises errors for negative exponents and zero moduli", (
+    throws(modular_power(2, -1, 5, _), error(domain_error(not_less_than_zero, -1), _)),
+    throws(modular_power(2, 3, 0, _), error(evaluation_error(zero_divisor), _)),
+    throws(modular_power(2.0, 3, 5, _), error(type_error(integer, 2.0), _))
+)).
+
+test("hyperbolic functions and their inverses", (
+    X is sinh(0), X =:= 0.0,
+    Y is cosh(0), Y =:= 1.0,
+    Z is tanh(0.5), W is atanh(Z), abs(W - 0.5) < 1.0e-15,
+    A is asinh(sinh(1.5)), abs(A - 1.5) < 1.0e-15,
+    C is acosh(cosh(2.0)), abs(C - 2.0) < 1.0e-15
+)).
+
+test("trigonometry in degrees is exact at multiples of 30 and 45", (
+    degrees(30, 0.5, _),
+    degrees(60, _, 0.5),
+    degrees(90, 1.0, 0.0),
+    degrees(180, 0.0, -1.0),
+    degrees(-30, -0.5, _),
+    T is tand(45), T == 1.0,
+    A is asind(0.5), abs(A - 30) < 1.0e-12,
+    B is acosd(0), B =:= 90.0,
+    C is atand(1), C =:= 45.0,
+    D is atan2d(-1, -1), D =:= -135.0,
+    throws(_ is tand(90), error(evaluation_error(undefined), _))
+)).
+
+test("log2, cot and acot", (
+    L is log2(1024), L =:= 10.0,
+    L2 is log2(2^2000), L2 =:= 2000.0,
+    C is cot(pi/4), abs(C - 1.0) < 1.0e-15,
+    A is acot(1), abs(A - pi/4) < 1.0e-15
+)).
+
+test("copysign, nexttoward and cmpflags", (
+    X is copysign(3, -1.0), X == -3.0,
+    Y is copysign(-2.5, 1), Y == 2.5,
+    Z is nexttoward(1.0, 2), Z > 1.0, Z - 1.0 =:= epsilon,
+    W is nexttoward(1.0, 1), W == 1.0,
+    -1 is cmpflags(1, 2.0),
+    0 is cmpflags(1 rdiv 2, 0.5),
+    1 is cmpflags(2^100, 3)
+)).
+
+test("integer rounds to the nearest integer, halfway cases away from zero", (
+    3 is integer(2.5),
+    -3 is integer(-2.5),
+    2 is integer(2.4),
+    3 is integer(5 rdiv 2),
+    -2 is integer(-7 rdiv 4),
+    X is integer(2^100 + 0.0), X =:= 2^100,
+    7 is integer(7),
+    3 is trunc(3.7),
+    -3 is trunc(-3.7)
+)).
+
+test("random and random_float stay within their ranges", (
+    findall(R, (between(1, 100, _), random_below(10, R)), Rs),
+    naturals_below(10, Rs),
+    B is 2^100,
+    findall(R, (between(1, 20, _), random_below(B, R)), Bs),
+    naturals_below(B, Bs),
+    findall(F, (between(1, 100, _), unit_random(F)), Fs),
+    unit_floats(Fs),
+    G is random_float, float(G),
+    throws(random_below(0, _), error(domain_error(not_less_than_one, 0), _))
+)).
+
+test("library(arithmetic) predicates are backed by the functions", (
+    msb(1000, 9),
+    lsb(1000, 3),
+    popcount(255, 8),
+    expmod(3, 100, 7, 4)
+)).
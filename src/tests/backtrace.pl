@@ -0,0 +1,54 @@
+:- module(backtrace_tests, []).
+
+:- use_module(library(lists)).
+
+:- use_module(test_framework).
+
+outer(X) :- inner(X), true.
+
+inner(X) :- atom_length(X, _), true.
+
+thrower :- throw(error(type_error(integer, a), thrower/0)), true.
+
+backtrace(Goal, E) :-
+    catch_with_backtrace(Goal, E, true).
+
+frame_predicate(clause(PI, _, _, _), PI) :- !.
+frame_predicate(PI, PI).
+
+frame_predicates(Frames, PIs) :-
+    maplist(frame_predicate, Frames, PIs).
+
+test("backtrace flag defaults to false", (
+    current_prolog_flag(backtrace, false)
+)).
+
+test("errors are caught with the calls they were thrown from", (
+    set_prolog_flag(backtrace, true),
+    backtrace(outer(1), E),
+    set_prolog_flag(backtrace, false),
+    E = error(type_error(atom, 1), backtrace(atom_length/2, Frames)),
+    frame_predicates(Frames, PIs),
+    append(_, [backtrace_tests:inner/1, backtrace_tests:outer/1|_], PIs)
+)).
+
+test("frames of clauses loaded from files have their locations", (
+    set_prolog_flag(backtrace, true),
+    backtrace(thrower, E),
+    set_prolog_flag(backtrace, false),
+    E = error(type_error(integer, a), backtrace(thrower/0, Frames)),
+    member(clause(backtrace_tests:thrower/0, 1, File, 11), Frames),
+    atom(File)
+)).
+
+test("errors are caught without backtraces if the flag isn't set", (
+    backtrace(outer(1), E),
+    E == error(type_error(atom, 1), atom_length/2)
+)).
+
+test("exceptions other than errors have no backtraces", (
+    set_prolog_flag(backtrace, true),
+    backtrace(throw(ball), E),
+    set_prolog_flag(backtrace, false),
+    E == ball
+)).
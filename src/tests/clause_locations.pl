@@ -0,0 +1,49 @@
+:- module(clause_locations_tests, []).
+
+:- use_module(library(lists)).
+
+:- use_module(test_framework).
+
+successor(X, Y) :-
+    Y is X + 1.
+
+:- discontiguous(digit/1).
+
+digit(0).
+
+not_a_digit.
+
+digit(D) :-
+    functor(D, _, _),
+    true.
+
+total(Xs, Total) :-
+    sum_list(Xs, Total),
+    true.
+
+source_file(File) :-
+    atom_concat(_, 'clause_locations.pl', File).
+
+test("machine errors name the file and line of the failing call", (
+    catch(successor(a, _), error(type_error(evaluable, a/0), Context), true),
+    Context = context((is)/2, file(File, 7)),
+    source_file(File)
+)).
+
+test("clauses of discontiguous predicates keep their lines", (
+    catch((digit(D), D \== 0), error(instantiation_error, Context), true),
+    Context = context(functor/3, file(File, 16)),
+    source_file(File)
+)).
+
+test("errors raised in library code name the clause that called it", (
+    catch(total([1, a], _), error(type_error(evaluable, a), Context), true),
+    Context = context((is)/2, file(_, 20))
+)).
+
+test("errors thrown with throw/1 are left unchanged", (
+    catch(throw(error(type_error(integer, a), successor/2)),
+          error(type_error(integer, a), Context),
+          true),
+    Context == successor/2
+)).
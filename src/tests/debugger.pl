@@ -0,0 +1,75 @@
+:- module(debugger_tests, []).
+
+:- use_module(library(debug)).
+:- use_module(library(iso_ext)).
+:- use_module(library(lists)).
+
+:- use_module(test_framework).
+
+digit(1).
+digit(2).
+digit(3).
+
+pair(X-Y) :-
+    digit(X),
+    digit(Y),
+    X < Y.
+
+first_digit(X) :-
+    digit(X),
+    !.
+
+odd_digit(X) :-
+    digit(X),
+    (  X mod 2 =:= 0 ->
+       throw(even(X))
+    ;  true
+    ).
+
+:- dynamic(counter/1).
+
+counter(0).
+
+bump(N) :-
+    retract(counter(N0)),
+    N is N0 + 1,
+    assertz(counter(N)).
+
+% the debugger stops at no port in debug mode without spy points.
+debugged(Goal) :-
+    setup_call_cleanup(debug, Goal, nodebug).
+
+test("goals give the same solutions under the debugger", (
+    debugged(findall(P, pair(P), Ps)),
+    Ps == [1-2, 1-3, 2-3]
+)).
+
+test("cuts in predicates under the debugger are local to them", (
+    debugged(findall(X, first_digit(X), Xs)),
+    Xs == [1]
+)).
+
+test("exceptions pass through the calls under the debugger", (
+    debugged(catch(findall(X, odd_digit(X), _), even(E), true)),
+    E == 2
+)).
+
+test("dynamic predicates are run under the debugger", (
+    debugged((bump(N1), bump(N2))),
+    N1 == 1,
+    N2 == 2
+)).
+
+test("spy/1 and nospy/1 check their predicate indicators", (
+    catch(spy(_), error(instantiation_error, _), true),
+    catch(spy(1), error(type_error(atom, 1), _), true),
+    catch(nospy(digit/a), error(type_error(integer, a), _), true),
+    catch(spy(m:_), error(instantiation_error, _), true)
+)).
+
+test("leash/1 checks its ports", (
+    catch(leash(_), error(instantiation_error, _), true),
+    catch(leash([call, door]), error(domain_error(port, door), _), true),
+    leash(half),
+    leash(all)
+)).
@@ -0,0 +1,79 @@
+:- module(decimals_tests, []).
+
+:- use_module(library(charsio)).
+:- use_module(library(lists)).
+
+:- use_module(test_framework).
+
+test("decimals are read and written with their scale", (
+    number(1.50d),
+    \+ integer(1.50d),
+    \+ float(1.50d),
+    number_chars(12.340d, "12.340d"),
+    number_chars(N, "0.05d"),
+    N == 0.05d,
+    write_term_to_chars(f(-1.5d, -(1.5d), a- -1.5d, 10d), [quoted(true)], Cs),
+    Cs == "f(-1.5d,- (1.5d),a- -1.5d,10d)"
+)).
+
+test("decimals unify by scale and compare by value", (
+    1.50d \= 1.5d,
+    1.50d =:= 1.5d,
+    1.5d =:= 3/2,
+    compare(<, 1.5d, 1.50d),
+    compare(<, 2.0, 1.0d),
+    compare(<, 1.0d, 0),
+    sort([2, 1.5d, 1.50d, 1.0, 1d], [1.0, 1d, 1.5d, 1.50d, 2])
+)).
+
+test("decimal sums and products are exact", (
+    X is 1.10d + 2.205d, X == 3.305d,
+    Y is 1.10d - 2.205d, Y == -1.105d,
+    Z is 1.10d * 2.205d, Z == 2.42550d,
+    W is 1.5d + 1, W == 2.5d,
+    V is 1.5d ^ 3, V == 3.375d,
+    U is 2.0d ^ -2, U == 0.25d,
+    T is abs(-1.50d), T == 1.50d
+)).
+
+test("decimals mixed with floats or rationals give floats or rationals", (
+    X is 1.5d + 0.25, X == 1.75,
+    Y is 1.5d + 1 rdiv 3, rational(Y), \+ integer(Y), Y =:= 11 rdiv 6,
+    Z is sqrt(2.25d), Z == 1.5,
+    W is float(1.25d), W == 1.25
+)).
+
+test("decimal division is rounded to decimal_precision digits", (
+    current_prolog_flag(decimal_precision, 20),
+    current_prolog_flag(decimal_rounding, half_even),
+    X is 1d / 3, X == 0.33333333333333333333d,
+    Y is 10.00d / 4, Y == 2.50d,
+    with_flag(decimal_precision, 5, A is 2d / 3), A == 0.66667d,
+    with_flag(decimal_precision, 5,
+              with_flag(decimal_rounding, to_zero, B is 2d / 3)),
+    B == 0.66666d,
+    throws(_ is 1d / 0, error(evaluation_error(zero_divisor), _))
+)).
+
+test("decimal/1 and decimal/2 convert numbers to decimals", (
+    A is decimal(0.1), A == 0.1d,
+    B is decimal(1 rdiv 3), B == 0.33333333333333333333d,
+    C is decimal(2.675, 2), C == 2.68d,
+    D is decimal(10, 2), D == 10.00d,
+    throws(_ is decimal(1, -1), error(domain_error(not_less_than_zero, -1), _)),
+    throws(_ is decimal(inf), error(evaluation_error(undefined), _))
+)).
+
+test("decimals are rounded to integers", (
+    3 is round(2.5d),
+    -3 is round(-2.5d),
+    -3 is floor(-2.5d),
+    3 is ceiling(2.1d),
+    -2 is truncate(-2.7d),
+    throws(_ is 1.5d mod 2, error(type_error(integer, 1.5d), _))
+)).
+
+test("decimal flags only take their documented values", (
+    throws(set_prolog_flag(decimal_precision, -1), error(domain_error(flag_value, _), _)),
+    throws(set_prolog_flag(decimal_rounding, up), error(domain_error(flag_value, _), _))
+)).
@@ -0,0 +1,171 @@
+:- module(encodings_tests, []).
+
+:- use_module(library(files)).
+:- use_module(library(format)).
+:- use_module(library(iso_ext)).
+:- use_module(library(lists)).
+
+:- use_module(test_framework).
+
+encodings_file('encodings_source.txt').
+
+delete_encodings_file :-
+    encodings_file(File),
+    atom_chars(File, Cs),
+    delete_file(Cs).
+
+% the elements Get reads from Stream up to its end.
+stream_elements(Get, Stream, Es) :-
+    call(Get, Stream, E),
+    (   ( E == end_of_file ; E == -1 ) -> Es = []
+    ;   Es = [E|Es0],
+        stream_elements(Get, Stream, Es0)
+    ).
+
+% the bytes of the encodings file.
+file_bytes(Bytes) :-
+    encodings_file(File),
+    setup_call_cleanup(open(File, read, In, [type(binary)]),
+                       stream_elements(get_byte, In, Bytes),
+                       close(In)).
+
+% the characters of the encodings file read with Options.
+file_chars(Options, Cs) :-
+    encodings_file(File),
+    setup_call_cleanup(open(File, read, In, Options),
+                       stream_elements(get_char, In, Cs),
+                       close(In)).
+
+% writes Cs to the encodings file with WriteOptions, and reads back
+% its bytes and, with ReadOptions, its characters.
+write_and_read(Cs, WriteOptions, ReadOptions, Bytes, Cs1) :-
+    encodings_file(File),
+    setup_call_cleanup(open(File, write, Out, WriteOptions),
+                       format(Out, "~s", [Cs]),
+                       close(Out)),
+    file_bytes(Bytes),
+    file_chars(ReadOptions, Cs1),
+    delete_encodings_file.
+
+test("text streams are utf8 and binary streams octet by default", (
+    encodings_file(File),
+    setup_call_cleanup(open(File, write, Out),
+                       stream_property(Out, encoding(E1)),
+                       close(Out)),
+    setup_call_cleanup(open(File, read, In, [type(binary)]),
+                       stream_property(In, encoding(E2)),
+                       close(In)),
+    delete_encodings_file,
+    E1 == utf8,
+    E2 == octet
+)).
+
+test("iso_latin_1 writes a byte per character", (
+    write_and_read("día", [encoding(iso_latin_1)], [encoding(iso_latin_1)], Bytes, Cs),
+    Bytes == [100, 237, 97],
+    Cs == "día"
+)).
+
+test("characters an encoding lacks are written as ?", (
+    write_and_read("a€b", [encoding(ascii)], [encoding(ascii)], Bytes, Cs),
+    Bytes == [97, 63, 98],
+    Cs == "a?b"
+)).
+
+test("windows_1252 has characters where iso_latin_1 has controls", (
+    write_and_read("€5—", [encoding(windows_1252)], [encoding(windows_1252)], Bytes, Cs),
+    Bytes == [128, 53, 151],
+    Cs == "€5—"
+)).
+
+test("utf16le is written without a byte order mark by default", (
+    write_and_read("a€", [encoding(utf16le)], [encoding(utf16le)], Bytes, Cs),
+    Bytes == [97, 0, 172, 32],
+    Cs == "a€"
+)).
+
+test("utf16 characters outside the basic plane are surrogate pairs", (
+    write_and_read("𝄞", [encoding(utf16be)], [encoding(utf16be)], Bytes, Cs),
+    Bytes == [216, 52, 221, 30],
+    Cs == "𝄞"
+)).
+
+test("the byte order mark of a file gives its encoding", (
+    encodings_file(File),
+    setup_call_cleanup(open(File, write, Out, [encoding(utf16be), bom(true)]),
+                       ( write(Out, 'f(é).'), stream_property(Out, bom(B1)) ),
+                       close(Out)),
+    setup_call_cleanup(open(File, read, In),
+                       ( stream_property(In, encoding(E)),
+                         stream_property(In, bom(B2)),
+                         read(In, T) ),
+                       close(In)),
+    delete_encodings_file,
+    B1 == true,
+    B2 == true,
+    E == utf16be,
+    T == f(é)
+)).
+
+test("bom(false) keeps the encoding of a file with a byte order mark", (
+    encodings_file(File),
+    setup_call_cleanup(open(File, write, Out, [encoding(utf16le), bom(true)]),
+                       write(Out, x),
+                       close(Out)),
+    setup_call_cleanup(open(File, read, In, [encoding(iso_latin_1), bom(false)]),
+                       ( stream_property(In, encoding(E)),
+                         stream_property(In, bom(B)) ),
+                       close(In)),
+    file_chars([encoding(iso_latin_1), bom(false)], Cs),
+    delete_encodings_file,
+    E == iso_latin_1,
+    B == false,
+    maplist(char_code, Cs, Codes),
+    Codes == [255, 254, 120, 0]
+)).
+
+test("bytes an encoding lacks are invalid data", (
+    encodings_file(File),
+    setup_call_cleanup(open(File, write, Out, [type(binary)]),
+                       ( put_byte(Out, 104), put_byte(Out, 233) ),
+                       close(Out)),
+    setup_call_cleanup(open(File, read, In, [encoding(ascii)]),
+                       ( get_char(In, C),
+                         catch(get_char(In, _), error(E, _), true) ),
+                       close(In)),
+    delete_encodings_file,
+    C == h,
+    E == syntax_error(invalid_data)
+)).
+
+test("set_stream/2 changes the encoding of later characters", (
+    encodings_file(File),
+    setup_call_cleanup(open(File, write, Out),
+                       ( put_char(Out, é),
+                         set_stream(Out, encoding(iso_latin_1)),
+                         put_char(Out, é) ),
+                       close(Out)),
+    file_bytes(Bytes),
+    delete_encodings_file,
+    Bytes == [195, 169, 233]
+)).
+
+test("encodings are validated", (
+    encodings_file(File),
+    throws(open(File, read, _, [encoding(latin1)]),
+           error(domain_error(stream_option, encoding(latin1)), _)),
+    throws(open(File, read, _, [encoding(_)]),
+           error(instantiation_error, _)),
+    throws(set_stream(user_output, encoding(latin1)),
+           error(domain_error(encoding, latin1), set_stream/2)),
+    throws(set_stream(user_output, type(binary)),
+           error(domain_error(stream_property, type(binary)), set_stream/2)),
+    throws(stream_property(user_output, encoding(latin1)),
+           error(domain_error(stream_property, encoding(latin1)), _))
+)).
+
+test("terminal input is always utf8", (
+    throws(set_stream(user_input, encoding(iso_latin_1)),
+           error(permission_error(modify, stream, user_input), _)),
+    stream_property(user_input, encoding(utf8))
+)).
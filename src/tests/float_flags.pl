@@ -0,0 +1,109 @@
+:- module(float_flags_tests, []).
+
+:- use_module(library(charsio)).
+
+:- use_module(test_framework).
+
+% X, with the rounding mode Mode.
+third(Mode, X) :-
+    with_flag(float_rounding, Mode, X is 1/3).
+
+test("exceptional float results raise errors by default", (
+    current_prolog_flag(float_overflow, error),
+    current_prolog_flag(float_zero_div, error),
+    current_prolog_flag(float_undefined, error),
+    current_prolog_flag(float_rounding, to_nearest),
+    throws(_ is 10.0**400, error(evaluation_error(float_overflow), _)),
+    throws(_ is 1.0e308 * 10, error(evaluation_error(float_overflow), _)),
+    throws(_ is 1 / 0.0, error(evaluation_error(zero_divisor), _)),
+    throws(_ is log(0), error(evaluation_error(undefined), _)),
+    throws(_ is sqrt(-1), error(evaluation_error(undefined), _)),
+    throws(_ is inf - inf, error(evaluation_error(undefined), _))
+)).
+
+test("float_overflow infinity makes overflows infinite", (
+    with_flag(float_overflow, infinity, (
+        X is 10.0**400,
+        Y is -(1.0e308 * 10),
+        Z is exp(1000)
+    )),
+    X =:= inf,
+    Y =:= -inf,
+    Z =:= inf
+)).
+
+test("float_zero_div infinity makes divisions by zero infinite", (
+    with_flag(float_zero_div, infinity, (
+        X is 1 / 0.0,
+        Y is -1 / 0.0,
+        Z is log(0),
+        W is 0 ** -1,
+        throws(_ is 0.0 / 0.0, error(evaluation_error(undefined), _))
+    )),
+    X =:= inf,
+    Y =:= -inf,
+    Z =:= -inf,
+    W =:= inf
+)).
+
+test("float_undefined nan makes undefined results NaN", (
+    with_flag(float_undefined, nan, (
+        X is sqrt(-1),
+        Y is inf - inf,
+        Z is asin(2),
+        W is atan2(0, 0)
+    )),
+    X == 1.5NaN,
+    Y == 1.5NaN,
+    Z == 1.5NaN,
+    W == 1.5NaN
+)).
+
+test("infinities and NaNs carry over without errors", (
+    X is inf + 1, X =:= inf,
+    Y is -inf * 2, Y =:= -inf,
+    Z is nan + 1, Z == 1.5NaN,
+    W is 1 / inf, W =:= 0.0,
+    throws(_ is floor(inf), error(evaluation_error(undefined), _)),
+    throws(_ is integer(nan), error(evaluation_error(undefined), _))
+)).
+
+test("infinities and NaNs are written and read back", (
+    X is inf,
+    Y is -inf,
+    Z is nan,
+    number_chars(X, "1.0Inf"),
+    number_chars(Y, "-1.0Inf"),
+    number_chars(Z, "1.5NaN"),
+    write_term_to_chars(f(X, Y, Z), [quoted(true)], Cs),
+    Cs == "f(1.0Inf,-1.0Inf,1.5NaN)",
+    read_from_chars("f(1.0Inf, -1.0Inf, 1.5NaN, 1.25NaN).", T),
+    T = f(A, B, C, D),
+    A == X,
+    B == Y,
+    C == Z,
+    number_chars(D, "1.25NaN")
+)).
+
+test("float_rounding rounds float results in its direction", (
+    third(to_nearest, N),
+    third(to_positive, P),
+    third(to_negative, M),
+    third(to_zero, Z),
+    P > M,
+    P - M =:= epsilon / 4,
+    Z == M,
+    ( N == P ; N == M ),
+    with_flag(float_rounding, to_positive, X is -1/3),
+    with_flag(float_rounding, to_zero, Y is -1/3),
+    X == Y,
+    with_flag(float_rounding, to_positive, U is float(2^60 + 1)),
+    with_flag(float_rounding, to_negative, V is float(2^60 + 1)),
+    U > V,
+    current_prolog_flag(float_rounding, to_nearest)
+)).
+
+test("float flags only take their documented values", (
+    throws(set_prolog_flag(float_overflow, nan), error(domain_error(flag_value, _), _)),
+    throws(set_prolog_flag(float_rounding, up), error(domain_error(flag_value, _), _))
+)).
@@ -0,0 +1,143 @@
+:- module(gc_tests, []).
+
+:- use_module(library(between)).
+:- use_module(library(dif)).
+:- use_module(library(freeze)).
+:- use_module(library(iso_ext)).
+:- use_module(library(lists)).
+
+:- use_module(test_framework).
+
+garbage(0) :- !.
+garbage(N) :-
+    length(Ls, 100),
+    maplist(=(x), Ls),
+    N1 is N - 1,
+    garbage(N1).
+
+garbage_atoms(N) :-
+    \+ ( between(1, N, I),
+         number_codes(I, Cs),
+         atom_codes(A, Cs),
+         atom_concat(gc_garbage_atom_, A, _),
+         false
+       ).
+
+test("live terms survive a collection", (
+    numlist(1, 1000, Ns),
+    garbage(100),
+    garbage_collect,
+    sum_list(Ns, 500500)
+)).
+
+test("unbound variables survive a collection", (
+    T = f(X, Y, X),
+    garbage_collect,
+    T = f(a, b, Z),
+    X == a,
+    Y == b,
+    Z == a
+)).
+
+test("cyclic terms survive a collection", (
+    A = f(A, "partial"),
+    garbage_collect,
+    A = f(B, S),
+    B == A,
+    S == "partial"
+)).
+
+test("bindings are undone after a collection", (
+    (   X = f(Y),
+        Y = g(_),
+        garbage_collect,
+        fail
+    ;   var(X)
+    )
+)).
+
+test("choice points survive a collection", (
+    findall(X-L, (member(X, [1,2,3]),
+                  garbage(10),
+                  garbage_collect,
+                  length(L, X)),
+            Sols),
+    length(Sols, 3),
+    Sols = [1-[_], 2-[_,_], 3-[_,_,_]]
+)).
+
+test("attributed variables survive a collection", (
+    freeze(X, Y = done),
+    dif(Z, a),
+    garbage_collect,
+    X = 1,
+    Y == done,
+    \+ Z = a
+)).
+
+test("global variables survive a collection", (
+    bb_b_put(gc_test_key, f(abc, [1,2,3])),
+    garbage(10),
+    garbage_collect,
+    bb_get(gc_test_key, f(abc, [1,2,3]))
+)).
+
+test("collections are counted", (
+    statistics(garbage_collection, [C0, _, _, _]),
+    garbage_collect,
+    statistics(garbage_collection, [C1, _, _, _]),
+    C1 > C0,
+    statistics(globalused, Used),
+    integer(Used)
+)).
+
+test("unreferenced atoms are reclaimed", (
+    garbage_atoms(1000),
+    statistics(atoms, A0),
+    garbage_collect_atoms,
+    statistics(atoms, A1),
+    A0 - A1 >= 1000,
+    statistics(atom_garbage_collection, [Count, Reclaimed, _]),
+    Count > 0,
+    Reclaimed >= 1000
+)).
+
+test("referenced atoms survive an atom collection", (
+    atom_concat(gc_live_atom_, '1', A),
+    atom_chars(S, "gc_live_string"),
+    atom_chars(S, Cs),
+    garbage_atoms(1000),
+    garbage_collect_atoms,
+    garbage_atoms(1000),
+    atom_length(A, 14),
+    atom_concat(gc_live_atom_, '1', A),
+    atom_chars(S, Cs)
+)).
+
+test("atoms in clauses survive an atom collection", (
+    atom_concat(gc_clause_atom_, '1', A),
+    assertz(gc_clause(A)),
+    garbage_atoms(1000),
+    garbage_collect_atoms,
+    garbage_atoms(1000),
+    atom_concat(gc_clause_atom_, '1', B),
+    gc_clause(B)
+)).
+
+test("atoms in tables survive an atom collection", (
+    atom_concat(gc_key_, '1', Key),
+    bb_put(Key, value),
+    atom_concat(gc_op_, '1', Op),
+    op(700, xfx, Op),
+    garbage_atoms(1000),
+    garbage_collect_atoms,
+    garbage_atoms(1000),
+    atom_concat(gc_key_, '1', Key1),
+    bb_get(Key1, value),
+    atom_concat(gc_op_, '1', Op1),
+    current_op(700, xfx, Op1)
+)).
+
+test("unknown statistics keys are rejected", (
+    catch(statistics(no_such_key, _), error(domain_error(statistics_key, no_such_key), _), true)
+)).
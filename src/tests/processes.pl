@@ -0,0 +1,100 @@
+:- module(processes_tests, []).
+
+:- use_module(library(charsio)).
+:- use_module(library(lists)).
+:- use_module(library(os)).
+
+:- use_module(test_framework).
+
+% the characters of Stream up to its end.
+stream_chars(Stream, Cs) :-
+    get_char(Stream, C),
+    (   C == end_of_file -> Cs = []
+    ;   Cs = [C|Cs0],
+        stream_chars(Stream, Cs0)
+    ).
+
+test("arguments are passed without a shell", (
+    process_create("echo", ["a  b", "$HOME", ""], [stdout(pipe(S)), process(PID)]),
+    stream_chars(S, Cs),
+    close(S),
+    process_wait(PID, Status),
+    Cs == "a  b $HOME \n",
+    Status == exit(0)
+)).
+
+test("standard input and output are piped", (
+    process_create("cat", [], [stdin(pipe(In)), stdout(pipe(Out)), process(PID)]),
+    stream_property(In, mode(write)),
+    stream_property(Out, mode(read)),
+    format(In, "line(~w).~n", [1]),
+    close(In),
+    read_term(Out, T, []),
+    close(Out),
+    process_wait(PID, exit(0)),
+    T == line(1)
+)).
+
+test("standard error is piped and exit codes are reported", (
+    process_create("sh", ["-c", "echo oops >&2; exit 3"],
+                   [stdout(null), stderr(pipe(Err)), process(PID)]),
+    stream_chars(Err, Cs),
+    close(Err),
+    process_wait(PID, Status),
+    Cs == "oops\n",
+    Status == exit(3)
+)).
+
+test("the environment and working directory are set", (
+    process_create("sh", ["-c", "echo $A$B; pwd"],
+                   [env(["A"="x"]), cwd("/"), stdout(pipe(S1)), process(P1)]),
+    stream_chars(S1, Cs1),
+    process_wait(P1, exit(0)),
+    Cs1 == "x\n/\n",
+    process_create("sh", ["-c", "echo $A; echo $PATH"],
+                   [environment(["A"="y"]), stdout(pipe(S2)), process(P2)]),
+    stream_chars(S2, ['y', '\n', C|_]),
+    process_wait(P2, exit(0)),
+    C \== '\n'
+)).
+
+test("processes are killed by signals", (
+    process_create("sleep", ["10"], [process(P1)]),
+    process_kill(P1),
+    process_wait(P1, killed(S1)),
+    process_create("sleep", ["10"], [process(P2)]),
+    process_kill(P2, kill),
+    process_wait(P2, killed(S2)),
+    S1 \== S2
+)).
+
+test("processes are waited for once", (
+    process_create("true", [], [process(PID)]),
+    process_wait(PID, exit(0)),
+    throws(process_wait(PID, _), error(existence_error(process, PID), _)),
+    throws(process_kill(PID), error(existence_error(process, PID), _))
+)).
+
+test("bad programs and options raise errors", (
+    throws(process_create("no such program", [], []),
+           error(existence_error(source_sink, _), _)),
+    throws(process_create("true", [], [bad]),
+           error(domain_error(process_create_option, bad), _)),
+    throws(process_create("true", [], [stdout(file)]),
+           error(domain_error(process_stream, file), _)),
+    throws(process_create("true", [], [stdout(pipe(s))]),
+           error(uninstantiation_error(s), _)),
+    throws(process_create(_, [], []), error(instantiation_error, _)),
+    throws(process_create("true", [], [cwd("/no such directory")]),
+           error(existence_error(directory, "/no such directory"), _)),
+    throws(process_kill(1, up), error(domain_error(signal, up), _))
+)).
+
+test("signal numbers out of range raise errors", (
+    process_create("sleep", ["10"], [process(PID)]),
+    throws(process_kill(PID, 10000000000000000000),
+           error(domain_error(signal, 10000000000000000000), _)),
+    throws(process_kill(PID, -1), error(domain_error(signal, -1), _)),
+    process_kill(PID, 9),
+    process_wait(PID, killed(9))
+)).
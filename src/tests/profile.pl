@@ -0,0 +1,116 @@
+:- module(profile_tests, []).
+
+:- use_module(library(between)).
+:- use_module(library(charsio)).
+:- use_module(library(files)).
+:- use_module(library(iso_ext)).
+:- use_module(library(lists)).
+:- use_module(library(profile)).
+
+:- use_module(test_framework).
+
+app([], Ys, Ys).
+app([X|Xs], Ys, [X|Zs]) :-
+    app(Xs, Ys, Zs).
+
+nrev([], []).
+nrev([X|Xs], Ys) :-
+    nrev(Xs, Ys0),
+    app(Ys0, [X], Ys).
+
+digit(1).
+digit(2).
+digit(3).
+
+quiet_profile(Goal) :-
+    profile(Goal, [top(0)]).
+
+digits(Xs) :-
+    findall(X, digit(X), Xs).
+
+profile_digits(Xs) :-
+    findall(X, quiet_profile(digit(X)), Xs).
+
+profile_folded(Xs, File) :-
+    profile(nrev(Xs, _), [top(0), folded(File)]).
+
+times_in_seconds([]).
+times_in_seconds([_-profile(_, _, _, Self, Total)|Data]) :-
+    float(Self),
+    float(Total),
+    0.0 =< Self,
+    Self =< Total,
+    times_in_seconds(Data).
+
+profiled(PI, Profile) :-
+    profile_data(Data),
+    member(PI-Profile, Data).
+
+file_chars(File, Cs) :-
+    setup_call_cleanup(open(File, read, S),
+                       get_n_chars(S, _, Cs),
+                       close(S)).
+
+lines([], []).
+lines(Cs, [Line|Lines]) :-
+    append(Line, ['\n'|Cs0], Cs),
+    !,
+    lines(Cs0, Lines).
+
+folded_lines([]).
+folded_lines([Line|Lines]) :-
+    folded_line(Line),
+    folded_lines(Lines).
+
+folded_line(Line) :-
+    append(Frames, [' '|Ds], Line),
+    \+ member(' ', Ds),
+    number_chars(N, Ds),
+    integer(N),
+    Frames = [_|_].
+
+test("profile/2 counts the calls to each predicate", (
+    quiet_profile(nrev([1,2,3], Ys)),
+    Ys == [3,2,1],
+    profiled(profile_tests:nrev/2, profile(4, _, _, _, _)),
+    profiled(profile_tests:app/3, profile(6, _, _, _, _))
+)).
+
+test("profile/2 counts the choice points created by each predicate", (
+    quiet_profile(digits(Xs)),
+    Xs == [1,2,3],
+    profiled(profile_tests:digit/1, profile(1, _, 1, _, _))
+)).
+
+test("profile/2 runs its goal once", (
+    profile_digits(Xs),
+    Xs == [1]
+)).
+
+test("profile/2 fails and raises the exceptions of its goal", (
+    \+ quiet_profile(digit(4)),
+    catch(quiet_profile(throw(ball)), E, true),
+    E == ball
+)).
+
+test("profile/2 gives times in seconds", (
+    quiet_profile(nrev([1,2,3,4,5,6,7,8], _)),
+    profile_data(Data),
+    times_in_seconds(Data)
+)).
+
+test("profile/2 writes folded call stacks", (
+    numlist(1, 100, Xs),
+    profile_folded(Xs, 'profile_test.folded'),
+    file_chars('profile_test.folded', Cs),
+    delete_file("profile_test.folded"),
+    lines(Cs, Lines),
+    Lines = [_|_],
+    folded_lines(Lines)
+)).
+
+test("profile/2 rejects unknown options", (
+    catch(profile(true, [sort(time)]), error(domain_error(profile_sort_key, time), _), true),
+    catch(profile(true, [verbose]), error(domain_error(profile_option, verbose), _), true),
+    catch(profile(true, [_]), error(instantiation_error, _), true)
+)).
@@ -0,0 +1,122 @@
+:- module(prolog_flags_tests, []).
+
+:- use_module(library(files)).
+:- use_module(library(format)).
+:- use_module(library(iso_ext)).
+:- use_module(library(lists)).
+
+:- use_module(test_framework).
+
+error_of(Goal, E) :-
+    catch((Goal, E = none), error(E, _), true).
+
+write_lines(File, Lines) :-
+    setup_call_cleanup(open(File, write, S),
+                       maplist(write_line(S), Lines),
+                       close(S)).
+
+write_line(S, Line) :-
+    format(S, "~s~n", [Line]).
+
+remove_file(File) :-
+    atom_chars(File, Cs),
+    (   file_exists(Cs) ->
+        delete_file(Cs)
+    ;   true
+    ).
+
+flag_names(Flags) :-
+    findall(Flag, current_prolog_flag(Flag, _), Flags).
+
+all_flags(Names, Flags) :-
+    forall(member(Name, Names), memberchk(Name, Flags)).
+
+test("current_prolog_flag/2 enumerates the flags of the system", (
+    flag_names(Flags),
+    all_flags([bounded, integer_rounding_function, char_conversion, debug,
+               max_arity, unknown, double_quotes, occurs_check],
+              Flags),
+    current_prolog_flag(bounded, false),
+    current_prolog_flag(max_arity, 1023),
+    current_prolog_flag(integer_rounding_function, toward_zero)
+)).
+
+test("max_integer and min_integer have no value as integers are unbounded", (
+    \+ current_prolog_flag(max_integer, _),
+    \+ current_prolog_flag(min_integer, _),
+    flag_names(Flags),
+    \+ memberchk(max_integer, Flags)
+)).
+
+test("flags are checked by name", (
+    error_of(current_prolog_flag(no_such_flag, _), domain_error(prolog_flag, no_such_flag)),
+    error_of(set_prolog_flag(no_such_flag, true), domain_error(prolog_flag, no_such_flag)),
+    error_of(current_prolog_flag(1, _), type_error(atom, 1)),
+    error_of(set_prolog_flag(_, true), instantiation_error)
+)).
+
+test("flag values are checked before the flag is found read only", (
+    error_of(set_prolog_flag(bounded, maybe), domain_error(flag_value, bounded+maybe)),
+    error_of(set_prolog_flag(bounded, true), permission_error(modify, flag, bounded)),
+    error_of(set_prolog_flag(double_quotes, text), domain_error(flag_value, double_quotes+text)),
+    error_of(set_prolog_flag(answer_write_options, [bad]),
+             domain_error(flag_value, answer_write_options+[bad]))
+)).
+
+test("occurs_check takes effect when set", (
+    set_prolog_flag(occurs_check, true),
+    current_prolog_flag(occurs_check, true),
+    (   X = f(X) -> Unified = true
+    ;   Unified = false
+    ),
+    set_prolog_flag(occurs_check, false),
+    Unified == false
+)).
+
+test("create_prolog_flag/3 creates flags read and set as others", (
+    create_prolog_flag(tests_level, 1, []),
+    current_prolog_flag(tests_level, 1),
+    set_prolog_flag(tests_level, 2),
+    current_prolog_flag(tests_level, 2),
+    error_of(set_prolog_flag(tests_level, high), domain_error(flag_value, tests_level+high)),
+    create_prolog_flag(tests_level, 3, [keep(true)]),
+    current_prolog_flag(tests_level, 2),
+    flag_names(Flags),
+    memberchk(tests_level, Flags)
+)).
+
+test("create_prolog_flag/3 takes the access and type of flags", (
+    create_prolog_flag(tests_mode, fast, [access(read_only)]),
+    error_of(set_prolog_flag(tests_mode, slow), permission_error(modify, flag, tests_mode)),
+    create_prolog_flag(tests_limit, 10, [type(term)]),
+    set_prolog_flag(tests_limit, limit(20)),
+    current_prolog_flag(tests_limit, limit(20)),
+    error_of(create_prolog_flag(tests_ratio, ten, [type(float)]),
+             domain_error(flag_value, tests_ratio+ten))
+)).
+
+test("create_prolog_flag/3 rejects the flags of the system and bad options", (
+    error_of(create_prolog_flag(bounded, true, []), permission_error(modify, flag, bounded)),
+    error_of(create_prolog_flag(tests_other, 1, [color(red)]),
+             domain_error(prolog_flag_option, color(red))),
+    error_of(create_prolog_flag(tests_other, 1, [type(string)]),
+             domain_error(prolog_flag_option, type(string))),
+    error_of(current_prolog_flag(tests_other, _), domain_error(prolog_flag, tests_other))
+)).
+
+test("double_quotes is scoped by module", (
+    write_lines('flags_test_codes.pl',
+                [":- module(flags_test_codes, [s/1]).",
+                 ":- set_prolog_flag(double_quotes, codes).",
+                 "s(\"ab\")."]),
+    write_lines('flags_test_chars.pl',
+                [":- module(flags_test_chars, [s/1]).",
+                 "s(\"ab\")."]),
+    use_module('flags_test_codes.pl'),
+    use_module('flags_test_chars.pl'),
+    remove_file('flags_test_codes.pl'),
+    remove_file('flags_test_chars.pl'),
+    flags_test_codes:s([0'a, 0'b]),
+    flags_test_chars:s([a, b]),
+    current_prolog_flag(double_quotes, chars)
+)).
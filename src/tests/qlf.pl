@@ -0,0 +1,83 @@
+:- module(qlf_tests, []).
+
+:- use_module(library(files)).
+:- use_module(library(format)).
+:- use_module(library(iso_ext)).
+:- use_module(library(lists)).
+
+:- use_module(test_framework).
+
+write_file(File, Clauses) :-
+    setup_call_cleanup(open(File, write, S),
+                       maplist(portray_clause(S), Clauses),
+                       close(S)).
+
+remove_file(File) :-
+    atom_chars(File, Cs),
+    (   file_exists(Cs) ->
+        delete_file(Cs)
+    ;   true
+    ).
+
+cleanup(Base) :-
+    atom_concat(Base, '.pl', Source),
+    atom_concat(Base, '.qlf', Qlf),
+    remove_file(Source),
+    remove_file(Qlf).
+
+qlf_exists(Base) :-
+    atom_concat(Base, '.qlf', Qlf),
+    atom_chars(Qlf, Cs),
+    file_exists(Cs).
+
+module_source([
+    (:- module(qlf_test_module, [color/1, double/2, big/1, greet/1, count/1])),
+    (:- use_module(library(lists))),
+    (:- dynamic(counter/1)),
+    counter(0),
+    (:- initialization(retract(counter(0)))),
+    (:- initialization(assertz(counter(1)))),
+    (color(C) :- member(C, [red, green, blue])),
+    (double(X, Y) :- Y is X * 2 + 0.5),
+    big(123456789012345678901234567890),
+    greet("hello"),
+    (count(N) :- counter(N))
+]).
+
+stale_source([
+    (:- module(qlf_test_module, [color/1])),
+    color(purple)
+]).
+
+test("a compiled file loads from its .qlf file", (
+    module_source(Cs),
+    write_file('qlf_test_module.pl', Cs),
+    qlf_compile('qlf_test_module.pl'),
+    qlf_exists(qlf_test_module),
+    use_module('qlf_test_module.pl'),
+    findall(C, qlf_test_module:color(C), [red, green, blue]),
+    qlf_test_module:double(2, 4.5),
+    qlf_test_module:big(123456789012345678901234567890),
+    qlf_test_module:greet("hello"),
+    qlf_test_module:count(1),
+    cleanup(qlf_test_module)
+)).
+
+test("a .qlf file older than its source is ignored", (
+    module_source(Cs0),
+    write_file('qlf_test_module.pl', Cs0),
+    qlf_compile('qlf_test_module.pl'),
+    stale_source(Cs),
+    write_file('qlf_test_module.pl', Cs),
+    use_module('qlf_test_module.pl'),
+    findall(C, qlf_test_module:color(C), [purple]),
+    cleanup(qlf_test_module)
+)).
+
+test("a .qlf file with a bad header is ignored", (
+    write_file('qlf_test_corrupt.pl', [(:- module(qlf_test_corrupt, [ok/0])), ok]),
+    write_file('qlf_test_corrupt.qlf', [not_a_qlf_file]),
+    use_module('qlf_test_corrupt.pl'),
+    qlf_test_corrupt:ok,
+    cleanup(qlf_test_corrupt)
+)).
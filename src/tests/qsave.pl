@@ -0,0 +1,38 @@
+:- module(qsave_tests, []).
+
+:- use_module(library(files)).
+:- use_module(library(iso_ext)).
+:- use_module(library(qsave)).
+
+:- use_module(test_framework).
+
+remove_file(File) :-
+    atom_chars(File, Cs),
+    (   file_exists(Cs) ->
+        delete_file(Cs)
+    ;   true
+    ).
+
+test("qsave_program/2 writes a saved state", (
+    qsave_program('qsave_test.state', [goal(true), toplevel(halt)]),
+    file_exists("qsave_test.state"),
+    remove_file('qsave_test.state')
+)).
+
+test("qsave_program/2 leaves no initialization goal behind", (
+    qsave_program("qsave_test.state", [goal(true)]),
+    \+ '$toplevel':'$state_init'(_, _),
+    remove_file('qsave_test.state')
+)).
+
+test("qsave_program/2 rejects goals that aren't callable", (
+    catch(qsave_program('qsave_test.state', [goal(1)]), error(type_error(callable, 1), _), true),
+    catch(qsave_program('qsave_test.state', [toplevel(_)]), error(instantiation_error, _), true),
+    \+ file_exists("qsave_test.state")
+)).
+
+test("qsave_program/2 reports files it can't write", (
+    catch(qsave_program('/nonexistent/qsave_test.state'),
+          error(permission_error(open, source_sink, _), _),
+          true)
+)).
@@ -0,0 +1,76 @@
+:- module(read_term_positions_tests, []).
+
+:- use_module(library(files)).
+:- use_module(library(format)).
+:- use_module(library(iso_ext)).
+:- use_module(library(lists)).
+
+:- use_module(test_framework).
+
+source_file('read_term_positions_source.pl').
+
+% reads the terms of Text with Options, one list of options for each
+% term.
+read_text(Text, Optionss) :-
+    source_file(File),
+    setup_call_cleanup(open(File, write, Out),
+                       format(Out, "~s", [Text]),
+                       close(Out)),
+    setup_call_cleanup(open(File, read, In),
+                       maplist(read_term(In), _, Optionss),
+                       close(In)),
+    atom_chars(File, Cs),
+    delete_file(Cs).
+
+test("atoms, numbers and variables span their tokens", (
+    read_text("foo(X, 12, 'a b').", [[subterm_positions(P)]]),
+    P = term_position(0, 17, 0, 3, [4-5, 7-9, 11-16])
+)).
+
+test("operators are the functors of their terms", (
+    read_text("a :- b, - c.", [[subterm_positions(P)]]),
+    P = term_position(0, 11, 2, 4, [0-1, term_position(5, 11, 6, 7, [5-6, term_position(8, 11, 8, 9, [10-11])])])
+)).
+
+test("lists, strings, curly terms and parentheses have their own positions", (
+    read_text("f([a|T], \"st\", {x}, (y)).", [[subterm_positions(P)]]),
+    P = term_position(0, 24, 0, 1, [list_position(2, 7, [3-4], 5-6),
+                                    string_position(9, 13),
+                                    brace_term_position(15, 18, 16-17),
+                                    parentheses_term_position(20, 23, 21-22)]),
+    read_text("[a, b].", [[subterm_positions(Q)]]),
+    Q = list_position(0, 6, [1-2, 4-5], none)
+)).
+
+test("term positions are those of the first token of each term", (
+    read_text("% comment\na.\n  b.", [[term_position(P1)], [term_position(P2)]]),
+    P1 = '$stream_position'(10, 2, 0, 10),
+    P2 = '$stream_position'(15, 3, 2, 15)
+)).
+
+test("comments read with a term are reported with their positions", (
+    read_text("% first\na /* second */.", [[comments(Cs)]]),
+    Cs = ['$stream_position'(0, 1, 0, 0)-"% first",
+          '$stream_position'(10, 2, 2, 10)-"/* second */"]
+)).
+
+test("byte offsets count the bytes of multibyte characters", (
+    read_text("'é'. b.", [[], [term_position(P), subterm_positions(S)]]),
+    P = '$stream_position'(5, 1, 5, 6),
+    S = 5-6
+)).
+
+test("named variables are reported with the positions of their occurrences", (
+    read_text("f(X, [Y|X], _, {Z}) :- g(Y).",
+              [[variable_positions(Vs), singleton_positions(Ss), variable_names(Ns)]]),
+    Vs = ['X'=[2-3, 8-9], 'Y'=[6-7, 25-26], 'Z'=[16-17]],
+    Ss = ['Z'=16-17],
+    Ns = ['X'=_, 'Y'=_, 'Z'=_]
+)).
+
+test("terms without variables have no variable positions", (
+    read_text("a.", [[variable_positions(Vs), singleton_positions(Ss)], [variable_positions(Es)]]),
+    Vs == [],
+    Ss == [],
+    Es == []
+)).
@@ -0,0 +1,194 @@
+:- module(tabling_tests, []).
+
+:- use_module(library(iso_ext)).
+:- use_module(library(lists)).
+:- use_module(library(tabling)).
+
+:- use_module(test_framework).
+
+edge(a, b).
+edge(b, c).
+edge(c, a).
+edge(c, d).
+
+:- table path/2.
+
+path(X, Y) :- path(X, Z), edge(Z, Y).
+path(X, Y) :- edge(X, Y).
+
+:- table distance(_,_,min).
+
+distance(X, Y, 1) :- edge(X, Y).
+distance(X, Y, N) :- distance(X, Z, N0), edge(Z, Y), N is N0 + 1.
+
+:- table longest(_,max), earliest(_,first), latest(_,last).
+
+longest(x, 1).
+longest(x, 3).
+longest(x, 2).
+
+earliest(x, 1).
+earliest(x, 3).
+earliest(x, 2).
+
+latest(x, 1).
+latest(x, 3).
+latest(x, 2).
+
+:- table route(_,_,lattice(longer_route/3)).
+
+longer_route(Route0, Route1, Route) :-
+    length(Route0, N0),
+    length(Route1, N1),
+    (  N0 >= N1 ->
+       Route = Route0
+    ;  Route = Route1
+    ).
+
+hop(a, b).
+hop(b, c).
+hop(a, c).
+
+route(X, Y, [X,Y]) :- hop(X, Y).
+route(X, Y, [X|Route]) :- hop(X, Z), route(Z, Y, Route).
+
+:- table fib/2.
+
+fib(0, 0).
+fib(1, 1).
+fib(N, F) :-
+    N > 1,
+    N1 is N - 1,
+    N2 is N - 2,
+    fib(N1, F1),
+    fib(N2, F2),
+    F is F1 + F2.
+
+:- table win/1.
+
+move(a, b).
+move(b, a).
+move(b, c).
+move(c, d).
+
+win(X) :- move(X, Y), tnot(win(Y)).
+
+:- table p/0, q/0, r/0, s/0.
+
+p :- tnot(q).
+q :- tnot(p).
+r :- tnot(s).
+s :- fail.
+
+:- table failing/1.
+
+failing(X) :- X = 1 ; throw(failed).
+
+:- dynamic link/2 as incremental.
+:- table reach/2 as incremental.
+
+link(1, 2).
+
+reach(X, Y) :- link(X, Y).
+reach(X, Y) :- reach(X, Z), link(Z, Y).
+
+:- table greeting//0.
+
+greeting --> "hello".
+
+untabled.
+
+delays(Goal, Delays) :-
+    call_delays(Goal, Delays).
+
+residual_program(Goal, Program) :-
+    call_residual_program(Goal, Program).
+
+test("left-recursive tabled predicates terminate", (
+    findall(Y, path(a, Y), Ys),
+    sort(Ys, [a,b,c,d])
+)).
+
+test("tabled predicates answer each variant once", (
+    findall(Y, path(c, Y), Ys),
+    sort(Ys, [a,b,c,d])
+)).
+
+test("min tabling keeps the least answer for each key", (
+    findall(Y-N, distance(a, Y, N), Ds),
+    sort(Ds, [a-3,b-1,c-2,d-3])
+)).
+
+test("min tabling answers calls with a bound moded argument", (
+    distance(a, d, 3),
+    \+ distance(a, d, 4)
+)).
+
+test("max, first and last tabling", (
+    findall(N, longest(x, N), [3]),
+    findall(N, earliest(x, N), [1]),
+    findall(N, latest(x, N), [2])
+)).
+
+test("lattice tabling joins the answers", (
+    findall(R, route(a, c, R), [[a,b,c]])
+)).
+
+test("tabled predicates share the answers of their calls", (
+    fib(100, F),
+    F =:= 354224848179261915075
+)).
+
+test("tnot/1 of a stratified tabled goal", (
+    r,
+    win(c),
+    \+ win(d),
+    delays(win(c), true)
+)).
+
+test("tnot/1 of a negative loop is undefined", (
+    delays(p, Delays),
+    Delays == p,
+    delays(win(a), WinDelays),
+    WinDelays == win(a)
+)).
+
+test("call_residual_program/2 gives the program of the undefined answers", (
+    residual_program(p, Program),
+    sort(Program, [(p :- tnot(q)), (q :- tnot(p))])
+)).
+
+test("undefined/0 is undefined", (
+    delays(undefined, Delays),
+    Delays == undefined
+)).
+
+test("tnot/1 of an untabled goal raises a permission error", (
+    catch(tnot(untabled), error(permission_error(tnot, non_tabled_procedure, _), _), true)
+)).
+
+test("exceptions abandon incomplete tables", (
+    catch(failing(_), E1, true),
+    E1 == failed,
+    catch(failing(_), E2, true),
+    E2 == failed
+)).
+
+test("incremental tables are evaluated anew when their dynamic predicates change", (
+    findall(Y, reach(1, Y), [2]),
+    assertz(link(2, 3)),
+    findall(Y, reach(1, Y), Ys),
+    sort(Ys, [2,3]),
+    retract(link(1, 2)),
+    findall(Y, reach(1, Y), [])
+)).
+
+test("tabled nonterminals", (
+    phrase(greeting, "hello")
+)).
+
+test("abolish_all_tables/0 removes the complete tables", (
+    abolish_all_tables,
+    findall(Y, path(a, Y), Ys),
+    sort(Ys, [a,b,c,d])
+)).
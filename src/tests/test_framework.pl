@@ -1,16 +1,68 @@
-:- module(test_framework, [main/1, main_quiet/1]).
+:- module(test_framework, [main/1, main_quiet/1, throws/2, with_flag/3]).
 
 :- use_module(library(dcgs)).
 :- use_module(library(format)).
+:- use_module(library(iso_ext)).
+
+:- meta_predicate(throws(0, ?)).
+:- meta_predicate(with_flag(+, +, 0)).
+
+% true iff Goal raises an error unifying with Error.
+throws(Goal, Error) :-
+    catch((Goal, false), Error, true).
+
+% Goal, with the flag Flag set to Value until it is done.
+with_flag(Flag, Value, Goal) :-
+    current_prolog_flag(Flag, Value0),
+    setup_call_cleanup(set_prolog_flag(Flag, Value),
+                       Goal,
+                       set_prolog_flag(Flag, Value0)).
+
+% the goal of a test is qualified with its module. the goals passed to
+% the helpers below are qualified explicitly, as they would otherwise
+% be qualified with this module, in which the helpers are defined.
+qualified_goal(M, G, M:G) :-
+    var(G),
+    !.
+qualified_goal(M, (A, B), (QA, QB)) :-
+    !,
+    qualified_goal(M, A, QA),
+    qualified_goal(M, B, QB).
+qualified_goal(M, (A ; B), (QA ; QB)) :-
+    !,
+    qualified_goal(M, A, QA),
+    qualified_goal(M, B, QB).
+qualified_goal(M, (A -> B), (QA -> QB)) :-
+    !,
+    qualified_goal(M, A, QA),
+    qualified_goal(M, B, QB).
+qualified_goal(M, \+ A, \+ QA) :-
+    !,
+    qualified_goal(M, A, QA).
+qualified_goal(M, throws(A, Error), test_framework:throws(QA, Error)) :-
+    !,
+    qualified_goal(M, A, QA).
+qualified_goal(M, with_flag(Flag, Value, A),
+               test_framework:with_flag(Flag, Value, QA)) :-
+    !,
+    qualified_goal(M, A, QA).
+qualified_goal(M, G, M:G).
+
+module_tests(TestModule, Tests) :-
+    findall(test(Name, QGoal),
+            ( TestModule:test(Name, Goal),
+              qualified_goal(TestModule, Goal, QGoal)
+            ),
+            Tests).
 
 main(TestModule) :-
-    findall(test(Name, TestModule:Goal), TestModule:test(Name, Goal), Tests),
+    module_tests(TestModule, Tests),
     run_tests(Tests, Failed),
     show_failed(Failed),
     halt.
 
 main_quiet(TestModule) :-
-    findall(test(Name, TestModule:Goal), TestModule:test(Name, Goal), Tests),
+    module_tests(TestModule, Tests),
     run_tests_quiet(Tests, Failed),
     (   Failed = [] ->
         format("All tests passed", [])
@@ -0,0 +1,187 @@
+:- module(threads_tests, []).
+
+:- use_module(library(lists)).
+:- use_module(library(threads)).
+
+:- use_module(test_framework).
+
+:- dynamic(counter/1).
+:- dynamic(shared_fact/1).
+:- dynamic(token/0).
+
+counter(0).
+
+% goals of tests are called as data, so meta-arguments are passed by
+% helper predicates to be qualified with this module.
+
+thread_create_join(Goal, Status) :-
+    thread_create(Goal, Id),
+    thread_join(Id, Status).
+
+lock_twice(M) :-
+    with_mutex(M, with_mutex(M, true)).
+
+create_worker(Parent, Id) :-
+    thread_create(with_mutex(threads_mutex,
+                             ( thread_send_message(Parent, enter),
+                               thread_send_message(Parent, leave)
+                             )),
+                  Id).
+
+join_worker(Id) :-
+    thread_join(Id, true).
+
+% each worker sends two messages while holding the mutex.
+run_workers(N, Msgs) :-
+    thread_self(Me),
+    length(Ids, N),
+    maplist(create_worker(Me), Ids),
+    maplist(join_worker, Ids),
+    N2 is 2 * N,
+    length(Msgs, N2),
+    maplist(thread_get_message, Msgs).
+
+asserted_by_thread(X) :-
+    from_thread(X).
+
+% the thread reports the facts it sees once it is told to.
+create_reader(Parent, Id) :-
+    thread_create(( thread_get_message(go),
+                    findall(X, shared_fact(X), Xs),
+                    thread_send_message(Parent, facts(Xs))
+                  ),
+                  Id).
+
+create_taker(Parent, Id) :-
+    thread_create(( retract(token) ->
+                    thread_send_message(Parent, taken)
+                  ; thread_send_message(Parent, missed)
+                  ),
+                  Id).
+
+% each taker tries to retract the one token.
+run_takers(N, Msgs) :-
+    thread_self(Me),
+    assertz(token),
+    length(Ids, N),
+    maplist(create_taker(Me), Ids),
+    maplist(join_worker, Ids),
+    length(Msgs, N),
+    maplist(thread_get_message, Msgs).
+
+increment_counter :-
+    retract(counter(N)),
+    N1 is N + 1,
+    assertz(counter(N1)).
+
+test("a joined thread reports how its goal terminated", (
+    thread_create(true, Id1),
+    thread_join(Id1, true),
+    thread_create(fail, Id2),
+    thread_join(Id2, false),
+    thread_create(throw(oops), Id3),
+    thread_join(Id3, exception(oops))
+)).
+
+test("threads have their own bindings", (
+    thread_create(X = a, Id),
+    thread_join(Id, true),
+    var(X)
+)).
+
+test("threads share the dynamic database", (
+    increment_counter,
+    thread_create_join(( counter(1), increment_counter ), true),
+    counter(2)
+)).
+
+test("clauses asserted by a thread are seen by the others", (
+    thread_create_join(assertz(from_thread(1)), true),
+    asserted_by_thread(X),
+    X == 1
+)).
+
+test("a running thread sees clauses asserted and retracted after it started", (
+    thread_self(Me),
+    assertz(shared_fact(a)),
+    create_reader(Me, Id),
+    retract(shared_fact(a)),
+    assertz(shared_fact(b)),
+    thread_send_message(Id, go),
+    thread_get_message(facts(Xs)),
+    thread_join(Id, true),
+    Xs == [b]
+)).
+
+test("a clause retracted by one thread can't be retracted by another", (
+    run_takers(4, Msgs),
+    select(taken, Msgs, Missed),
+    Missed == [missed, missed, missed]
+)).
+
+test("messages are copied between threads", (
+    thread_self(Me),
+    thread_create(( thread_get_message(request(N)),
+                    M is N * 2,
+                    thread_send_message(Me, reply(M, "chars", f(_)))
+                  ), Id),
+    thread_send_message(Id, request(100000000000000000000000)),
+    thread_get_message(reply(M, Cs, T)),
+    thread_join(Id, true),
+    M =:= 200000000000000000000000,
+    Cs == "chars",
+    T = f(V),
+    var(V)
+)).
+
+test("messages are received in order of arrival", (
+    thread_self(Me),
+    thread_send_message(Me, msg(1)),
+    thread_send_message(Me, other),
+    thread_send_message(Me, msg(2)),
+    thread_get_message(msg(X)),
+    thread_get_message(msg(Y)),
+    thread_peek_message(other),
+    thread_get_message(other),
+    \+ thread_peek_message(_),
+    X == 1,
+    Y == 2
+)).
+
+test("streams can't be sent", (
+    thread_self(Me),
+    current_output(S),
+    catch(thread_send_message(Me, S),
+          error(permission_error(send, stream, _), _),
+          true),
+    \+ thread_peek_message(_)
+)).
+
+test("threads can be named by aliases", (
+    thread_create(thread_self(threads_alias), Id, [alias(threads_alias)]),
+    Id == threads_alias,
+    thread_join(threads_alias, true),
+    catch(thread_join(threads_alias, _),
+          error(existence_error(thread, threads_alias), _),
+          true)
+)).
+
+test("the main thread can't be joined", (
+    thread_self(main),
+    catch(thread_join(main, _),
+          error(permission_error(join, thread, main), _),
+          true)
+)).
+
+test("mutexes are recursive", (
+    mutex_create(M),
+    lock_twice(M),
+    catch(mutex_unlock(M),
+          error(permission_error(unlock, mutex, M), _),
+          true)
+)).
+
+test("mutexes serialize threads", (
+    run_workers(4, Msgs),
+    Msgs == [enter, leave, enter, leave, enter, leave, enter, leave]
+)).
@@ -14,6 +14,7 @@
 
 :- dynamic(disabled_init_file/0).
 :- dynamic(started/0).
+:- dynamic('$state_init'/2).
 
 load_scryerrc :-
     (  '$home_directory'(HomeDir) ->
@@ -28,6 +29,10 @@ load_scryerrc :-
 
 '$repl' :-
     asserta('$toplevel':started),
+    (   '$state_init'(Goal, Toplevel) ->
+        run_state_init(Goal, Toplevel)
+    ;   true
+    ),
     raw_argv(Args0),
     (   append(Args1, ["--"|_], Args0) ->
         Args = Args1
@@ -40,12 +45,27 @@ load_scryerrc :-
     (\+ disabled_init_file -> load_scryerrc ; true),
     repl.
 
+% runs the initialization goal and toplevel of a saved state, as set
+% by qsave_program/2. Unless its toplevel is prolog, the state exits
+% without processing its command-line arguments.
+run_state_init(Goal, Toplevel) :-
+    (   catch(Goal, E, (print_exception(E), halt(1))) -> true
+    ;   halt(1)
+    ),
+    (   Toplevel == prolog -> true
+    ;   Toplevel == halt -> halt
+    ;   catch(Toplevel, E, (print_exception(E), halt(1))) -> halt
+    ;   halt(1)
+    ).
+
 args_consults_goals([], [], []).
 args_consults_goals([Arg|Args], Consults, Goals) :-
     arg_consults_goals(Arg, Args, Consults, Goals).
 
 arg_consults_goals(c(Mod), Args, [c(Mod)|Consults], Goals) :-
     args_consults_goals(Args, Consults, Goals).
+arg_consults_goals(q(File), Args, [q(File)|Consults], Goals) :-
+    args_consults_goals(Args, Consults, Goals).
 arg_consults_goals(g(Goal), Args, Consults, [g(Goal)|Goals]) :-
     args_consults_goals(Args, Consults, Goals).
 
@@ -58,10 +78,18 @@ delegate_task([], Goals0) :-
     run_goals(Goals),
     repl.
 
+delegate_task([Arg0|Args0], Goals0) :-
+    member(Arg0, ["-x", "--state"]),
+    !,
+    % the saved state is restored before the toplevel starts.
+    (   Args0 = [_|Args] -> delegate_task(Args, Goals0)
+    ;   print_help, halt
+    ).
 delegate_task([Arg0|Args], Goals0) :-
     (   (   member(Arg0, ["-h", "--help"]) -> print_help
         ;   member(Arg0, ["-v", "--version"]) -> print_version
         ;   member(Arg0, ["-g", "--goal"]) -> gather_goal(g, Args, Goals0)
+        ;   member(Arg0, ["-c", "--compile"]) -> gather_goal(q, Args, Goals0)
         ;   member(Arg0, ["-f"]) -> disable_init_file
         ;   member(Arg0, ["--no-add-history"]) -> ignore_machine_arg
         ),
@@ -81,6 +109,10 @@ print_help :-
     write('Print version information and exit'), nl,
     write('   -g, --goal GOAL        '),
     write('Run the query GOAL'), nl,
+    write('   -c, --compile FILE     '),
+    write('Load FILE and cache its compiled code in a .qlf file'), nl,
+    write('   -x, --state FILE       '),
+    write('Start from the saved state FILE'), nl,
     write('   -f                     '),
     write('Fast startup. Do not load initialization file (~/.scryerrc)'), nl,
     write('   --no-add-history       '),
@@ -111,6 +143,7 @@ arg_type(g).
 arg_type(t).
 arg_type(c(_)).
 arg_type(g(_)).
+arg_type(q(_)).
 arg_type(t(_)).
 
 ends_with_dot(Ls0) :-
@@ -154,13 +187,22 @@ run_goals([c(Mod)|Goals]) :- !,
         write_term(consult(Mod), [double_quotes(DQ)]), nl
     ),
     run_goals(Goals).
+run_goals([q(Chars)|Goals]) :- !,
+    atom_chars(File, Chars),
+    (   catch(qlf_compile(File), E, print_exception(E)) ->
+        true
+    ;   write('% Warning: initialization failed for: '),
+        double_quotes_option(DQ),
+        write_term(qlf_compile(File), [double_quotes(DQ)]), nl
+    ),
+    run_goals(Goals).
 run_goals([Goal|_]) :-
     loader:write_error(error(domain_error(arg_type, Goal), run_goals/1)),
     nl,
     halt.
 
 repl :-
-    catch(read_and_match, E, print_exception(E)),
+    catch_with_backtrace(read_and_match, E, print_exception(E)),
     false. %% this is for GC, until we get actual GC.
 repl :-
     repl.
@@ -456,6 +498,15 @@ gather_equations([Var = Value | Pairs], OrigVarList, Goals) :-
     gather_equations(Pairs, OrigVarList, Goals0)
     ).
 
+print_exception('$aborted') :-
+    !,
+    write('% Execution Aborted'),
+    nl.
+print_exception(error(E, backtrace(Context, Frames0))) :-
+    !,
+    print_exception(error(E, Context)),
+    query_frames(Frames0, Frames),
+    print_backtrace(Frames).
 print_exception(E) :-
     (  E == error('$interrupt_thrown', repl) -> nl % print the
                                                    % exception on a
@@ -466,6 +517,48 @@ print_exception(E) :-
     loader:write_error(E),
     nl.
 
+print_backtrace([]) :-
+    !.
+print_backtrace(Frames) :-
+    write('% Backtrace:'),
+    nl,
+    maplist(print_backtrace_frame, Frames).
+
+% the frames of the query, without those of the toplevel and the
+% call_residue_vars/2 it calls the query in.
+query_frames([], []).
+query_frames([Frame|Frames0], Frames) :-
+    (  toplevel_frame(Frame) ->
+       Frames = []
+    ;  Frame == iso_ext:call_residue_vars/2,
+       Frames0 = [Next|_],
+       toplevel_frame(Next) ->
+       Frames = []
+    ;  Frames = [Frame|Frames1],
+       query_frames(Frames0, Frames1)
+    ).
+
+toplevel_frame(clause(PI, _, _, _)) :-
+    !,
+    toplevel_frame(PI).
+toplevel_frame('$toplevel':_).
+toplevel_frame(builtins:catch_with_backtrace/4).
+
+print_backtrace_frame(Frame) :-
+    write('%   '),
+    (  Frame = clause(PI, N, File, Line) ->
+       writeq(PI),
+       write(' (clause '),
+       write(N),
+       write(' at '),
+       write(File),
+       write(':'),
+       write(Line),
+       write(')')
+    ;  writeq(Frame)
+    ),
+    nl.
+
 print_exception_with_check(E) :-
     (  E = error(_, _:_) -> true % if the error source contains a line
     % number, a GNU-style error message
@@ -341,6 +341,7 @@ fn from((n, arena): (Number, &mut Arena)) -> HeapCellValue {
             Number::Float(OrderedFloat(n)) => HeapCellValue::from(float_alloc!(n, arena)),
             Number::Integer(n) => HeapCellValue::from(n),
             Number::Rational(n) => HeapCellValue::from(n),
+            Number::Decimal(n) => typed_arena_ptr_as_cell!(n),
             Number::Fixnum(n) => fixnum_as_cell!(n),
         }
     }
@@ -626,6 +627,7 @@ pub fn order_category(self, heap: &[HeapCellValue]) -> Option<TermOrderCategory>
                 Some(TermOrderCategory::Integer)
             }
             Some(Number::Float(_)) => Some(TermOrderCategory::FloatingPoint),
+            Some(Number::Decimal(_)) => Some(TermOrderCategory::Decimal),
             None => match self.get_tag() {
                 HeapCellValueTag::Var | HeapCellValueTag::StackVar | HeapCellValueTag::AttrVar => {
                     Some(TermOrderCategory::Variable)
@@ -33,9 +33,17 @@ fn hello_world() {
 #[test]
 #[cfg_attr(miri, ignore = "blocked on streams.rs UB")]
 fn syntax_error() {
+    let file = std::env::current_dir()
+        .unwrap()
+        .join("tests-pl/syntax_error.pl");
+
     load_module_test(
         "tests-pl/syntax_error.pl",
-        "   error(syntax_error(incomplete_reduction),read_term/3:6).\n",
+        format!(
+            "   error(syntax_error(incomplete_reduction),context(read_term/3,file('{}',6))).\n",
+            file.display()
+        )
+        .as_str(),
     );
 }
 